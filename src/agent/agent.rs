@@ -1,6 +1,5 @@
 use crate::models::{V1AgentKey, V1CreateAgentKeyRequest};
 use anyhow::{anyhow, Result};
-use reqwest::Client;
 use tracing::{debug, info};
 
 /// Creates a new agent key by calling the `/v1/agent/keys` endpoint
@@ -20,7 +19,7 @@ pub async fn create_agent_key(
     api_key: &str,
     request: V1CreateAgentKeyRequest,
 ) -> Result<V1AgentKey> {
-    let client = Client::new();
+    let client = crate::utils::http::shared_client();
     // Change return type to anyhow's Result
     let url = format!("{}/v1/agent/keys", base_url);
 
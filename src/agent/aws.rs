@@ -112,8 +112,103 @@ pub struct StsCredentials {
     pub expiration: Option<DateTime>,
 }
 
+/// Key prefix holding a namespace's data, `data/<namespace>`
+pub fn namespace_prefix(namespace: &str) -> String {
+    format!("data/{}", namespace)
+}
+
+/// Whether `prefix` in `bucket` is part of `namespace`'s data: in the
+/// server's bucket, at or below the namespace's prefix.
+pub fn is_namespace_data(server_bucket: &str, namespace: &str, bucket: &str, prefix: &str) -> bool {
+    let prefix = prefix.trim_matches('/');
+    let base = namespace_prefix(namespace);
+    let below_base = prefix == base
+        || prefix
+            .strip_prefix(&base)
+            .is_some_and(|rest| rest.starts_with('/'));
+    bucket == server_bucket
+        && below_base
+        && !prefix
+            .split('/')
+            .any(|segment| segment == "." || segment == "..")
+}
+
+/// Splits `s3://bucket/some/prefix` into the bucket and the key prefix
+/// (without surrounding slashes, possibly empty).
+pub fn split_s3_uri(uri: &str) -> Option<(String, String)> {
+    let rest = uri.strip_prefix("s3://")?;
+    let (bucket, prefix) = rest.split_once('/').unwrap_or((rest, ""));
+    if bucket.is_empty() {
+        return None;
+    }
+    Some((bucket.to_string(), prefix.trim_matches('/').to_string()))
+}
+
+/// Narrows `base` to the `sub` path below it. Rejects `..` and empty segments
+/// so the result can never climb out of `base`.
+pub fn scoped_prefix(base: &str, sub: Option<&str>) -> Result<String> {
+    let base = base.trim_matches('/');
+    let Some(sub) = sub.map(|s| s.trim_matches('/')).filter(|s| !s.is_empty()) else {
+        return Ok(base.to_string());
+    };
+    if sub
+        .split('/')
+        .any(|segment| segment.is_empty() || segment == "." || segment == "..")
+    {
+        return Err(anyhow::anyhow!("Invalid prefix '{}'", sub));
+    }
+    if base.is_empty() {
+        Ok(sub.to_string())
+    } else {
+        Ok(format!("{}/{}", base, sub))
+    }
+}
+
+/// Policy allowing `object_actions` on objects under `prefix` in `bucket`, and
+/// listing only that part of the bucket.
+pub fn s3_prefix_policy(
+    bucket_name: &str,
+    prefix: &str,
+    object_actions: &[&str],
+) -> serde_json::Value {
+    let prefix = prefix.trim_matches('/');
+    json!({
+      "Version": "2012-10-17",
+      "Statement": [
+        // -- 1) Allow listing objects only under the prefix
+        {
+          "Effect": "Allow",
+          "Action": "s3:ListBucket",
+          "Resource": [
+            format!("arn:aws:s3:::{}", bucket_name)
+          ],
+          "Condition": {
+            "StringLike": {
+              "s3:prefix": [
+                format!("{}/", prefix),
+                format!("{}/*", prefix)
+              ]
+            }
+          }
+        },
+        // -- 2) Allow working with objects under the prefix
+        {
+          "Effect": "Allow",
+          "Action": object_actions,
+          "Resource": [
+            format!("arn:aws:s3:::{}/{}", bucket_name, prefix),
+            format!("arn:aws:s3:::{}/{}/*", bucket_name, prefix)
+          ]
+        }
+      ]
+    })
+}
+
+/// Creates an IAM user `nebu-<namespace>-<name>` whose access is limited to
+/// `prefix` in `bucket_name`, see [`namespace_prefix`].
 pub async fn create_s3_scoped_user(
     bucket_name: &str,
+    prefix: &str,
     namespace: &str,
     name: &str,
 ) -> Result<IamCredentials> {
@@ -143,38 +238,7 @@ pub async fn create_s3_scoped_user(
         }
     }
 
-    let policy_document = json!({
-      "Version": "2012-10-17",
-      "Statement": [
-        // -- 1) Allow listing objects only under data/<namespace> prefix
-        {
-          "Effect": "Allow",
-          "Action": "s3:ListBucket",
-          "Resource": [
-            format!("arn:aws:s3:::{}", bucket_name)
-          ],
-          "Condition": {
-            "StringLike": {
-              "s3:prefix": [
-                format!("data/{}/", namespace),
-                format!("data/{}/*", namespace)
-              ]
-            }
-          }
-        },
-        // -- 2) Allow working with objects under data/<namespace> prefix
-        {
-          "Effect": "Allow",
-          "Action": [
-            "s3:*"
-          ],
-          "Resource": [
-            format!("arn:aws:s3:::{}/data/{}", bucket_name, namespace),
-            format!("arn:aws:s3:::{}/data/{}/*", bucket_name, namespace)
-          ]
-        }
-      ]
-    });
+    let policy_document = s3_prefix_policy(bucket_name, prefix, &["s3:*"]);
 
     debug!(">>> Policy document: {}", policy_document);
 
@@ -307,6 +371,7 @@ pub async fn delete_s3_scoped_user(namespace: &str, name: &str) -> Result<()> {
 /// This approach uses STS GetFederationToken with an inline policy for proper restrictions.
 pub async fn generate_temporary_s3_credentials(
     bucket_name: &str,
+    prefix: &str,
     namespace: &str,
     duration_seconds: i32,
 ) -> Result<StsCredentials> {
@@ -325,40 +390,12 @@ pub async fn generate_temporary_s3_credentials(
         .take(32)
         .collect();
 
-    // Define the inline policy document restricting access to namespace path
-    let policy_document = json!({
-        "Version": "2012-10-17",
-        "Statement": [
-            {
-                "Effect": "Allow",
-                "Action": [
-                    "s3:ListBucket"
-                ],
-                "Resource": [
-                    format!("arn:aws:s3:::{}", bucket_name)
-                ],
-                "Condition": {
-                    "StringLike": {
-                        "s3:prefix": [
-                            format!("data/{}/", namespace),
-                            format!("data/{}/*", namespace)
-                        ]
-                    }
-                }
-            },
-            {
-                "Effect": "Allow",
-                "Action": [
-                    "s3:GetObject",
-                    "s3:PutObject",
-                    "s3:DeleteObject"
-                ],
-                "Resource": [
-                    format!("arn:aws:s3:::{}/data/{}/*", bucket_name, namespace)
-                ]
-            }
-        ]
-    });
+    // Define the inline policy document restricting access to the prefix
+    let policy_document = s3_prefix_policy(
+        bucket_name,
+        prefix,
+        &["s3:GetObject", "s3:PutObject", "s3:DeleteObject"],
+    );
 
     let policy_string = policy_document.to_string();
     debug!("Federation Token Policy: {}", policy_string);
@@ -397,3 +434,95 @@ pub async fn generate_temporary_s3_credentials(
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_prefix_policy_resources() {
+        // Arrange
+        let prefix = scoped_prefix(&namespace_prefix("team-a"), Some("/runs/42/")).unwrap();
+
+        // Act
+        let policy = s3_prefix_policy("nebu-data", &prefix, &["s3:GetObject"]);
+
+        // Assert
+        assert_eq!(prefix, "data/team-a/runs/42");
+        let statements = policy["Statement"].as_array().unwrap();
+        assert_eq!(statements[0]["Resource"], json!(["arn:aws:s3:::nebu-data"]));
+        assert_eq!(
+            statements[0]["Condition"]["StringLike"]["s3:prefix"],
+            json!(["data/team-a/runs/42/", "data/team-a/runs/42/*"])
+        );
+        assert_eq!(
+            statements[1]["Resource"],
+            json!([
+                "arn:aws:s3:::nebu-data/data/team-a/runs/42",
+                "arn:aws:s3:::nebu-data/data/team-a/runs/42/*"
+            ])
+        );
+        assert_eq!(statements[1]["Action"], json!(["s3:GetObject"]));
+    }
+
+    #[test]
+    fn test_namespace_data_is_the_server_bucket_below_the_namespace() {
+        assert!(is_namespace_data("nebu", "team-a", "nebu", "data/team-a"));
+        assert!(is_namespace_data(
+            "nebu",
+            "team-a",
+            "nebu",
+            "data/team-a/ckpt/"
+        ));
+        assert!(!is_namespace_data(
+            "nebu",
+            "team-a",
+            "nebu",
+            "data/team-ab/ckpt"
+        ));
+        assert!(!is_namespace_data(
+            "nebu",
+            "team-a",
+            "nebu",
+            "data/team-b/ckpt"
+        ));
+        assert!(!is_namespace_data(
+            "nebu",
+            "team-a",
+            "nebu",
+            "data/team-a/../team-b"
+        ));
+        assert!(!is_namespace_data(
+            "nebu",
+            "team-a",
+            "other",
+            "data/team-a/ckpt"
+        ));
+    }
+
+    #[test]
+    fn test_scoped_prefix_stays_below_base() {
+        assert_eq!(scoped_prefix("data/team-a", None).unwrap(), "data/team-a");
+        assert_eq!(
+            scoped_prefix("data/team-a", Some("")).unwrap(),
+            "data/team-a"
+        );
+        for sub in ["../team-b", "runs/../../team-b", "runs//x", "./runs"] {
+            assert!(scoped_prefix("data/team-a", Some(sub)).is_err(), "{}", sub);
+        }
+    }
+
+    #[test]
+    fn test_split_s3_uri() {
+        assert_eq!(
+            split_s3_uri("s3://nebu-data/data/team-a/"),
+            Some(("nebu-data".to_string(), "data/team-a".to_string()))
+        );
+        assert_eq!(
+            split_s3_uri("s3://nebu-data"),
+            Some(("nebu-data".to_string(), String::new()))
+        );
+        assert_eq!(split_s3_uri("gs://nebu-data/x"), None);
+        assert_eq!(split_s3_uri("s3:///x"), None);
+    }
+}
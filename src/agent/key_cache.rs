@@ -0,0 +1,439 @@
+// src/agent/key_cache.rs
+//
+// Reuses agent keys minted on a user's behalf instead of asking the auth
+// server for a new one on every message. Keys are cached per (user token,
+// agent) and minted valid a little longer than asked for, so one can be
+// reused while it still has the full requested lifetime left. They're dropped
+// as soon as the token they were minted with stops authenticating, and the
+// cache holds at most `MAX_CACHED_KEYS`, so tokens that are never seen again
+// don't pile up.
+
+use crate::agent::agent::create_agent_key;
+use crate::models::V1CreateAgentKeyRequest;
+use dashmap::DashMap;
+use once_cell::sync::Lazy;
+use short_uuid::ShortUuid;
+use std::future::Future;
+use std::time::{Duration, Instant};
+use tracing::debug;
+
+/// Shortest validity a send may ask for its agent key, so the key doesn't
+/// lapse while the message is still being processed
+pub const MIN_AGENT_KEY_DURATION: Duration = Duration::from_secs(60);
+
+/// How much longer than asked for keys are minted, at most the asked for
+/// duration again. A key is reused for this long.
+const REUSE_WINDOW: Duration = Duration::from_secs(600);
+
+/// Keys kept at most. Once full, expired keys are dropped first, then those
+/// expiring soonest.
+const MAX_CACHED_KEYS: usize = 10_000;
+
+/// Agent keys for processor sends, shared across requests
+pub static AGENT_KEY_CACHE: Lazy<AgentKeyCache> = Lazy::new(AgentKeyCache::default);
+
+struct CachedKey {
+    key: String,
+    expires_at: Instant,
+}
+
+/// Keys are cached per user token digest, agent and validity in seconds
+pub struct AgentKeyCache {
+    keys: DashMap<(String, String, u64), CachedKey>,
+    max_keys: usize,
+}
+
+impl Default for AgentKeyCache {
+    fn default() -> Self {
+        Self::with_max_keys(MAX_CACHED_KEYS)
+    }
+}
+
+/// Cache entries hold a digest of the user token rather than the token itself
+fn token_digest(user_token: &str) -> String {
+    ring::digest::digest(&ring::digest::SHA256, user_token.as_bytes())
+        .as_ref()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// How long a key is minted for when `duration` is asked for
+fn minted_lifetime(duration: Duration) -> Duration {
+    duration + REUSE_WINDOW.min(duration)
+}
+
+impl AgentKeyCache {
+    /// An empty cache holding at most `max_keys` keys
+    pub fn with_max_keys(max_keys: usize) -> Self {
+        Self {
+            keys: DashMap::new(),
+            max_keys,
+        }
+    }
+
+    /// A cached key for `user_token` and `agent_id` that stays valid for at
+    /// least `duration`, or a new one made by `create` with the lifetime it's
+    /// passed. Failures aren't cached.
+    pub async fn get_or_create<F, Fut, E>(
+        &self,
+        user_token: &str,
+        agent_id: &str,
+        duration: Duration,
+        create: F,
+    ) -> Result<String, E>
+    where
+        F: FnOnce(Duration) -> Fut,
+        Fut: Future<Output = Result<String, E>>,
+    {
+        let entry = (
+            token_digest(user_token),
+            agent_id.to_string(),
+            duration.as_secs(),
+        );
+        if let Some(cached) = self.keys.get(&entry) {
+            if cached.expires_at.saturating_duration_since(Instant::now()) >= duration {
+                debug!("[Agent Keys] Reusing cached key for agent {}", agent_id);
+                return Ok(cached.key.clone());
+            }
+        }
+
+        let lifetime = minted_lifetime(duration);
+        // Counted from before the request, so it never outlives the key
+        let minted_at = Instant::now();
+        let key = create(lifetime).await?;
+        if !self.keys.contains_key(&entry) {
+            self.make_room();
+        }
+        self.keys.insert(
+            entry,
+            CachedKey {
+                key: key.clone(),
+                expires_at: minted_at + lifetime,
+            },
+        );
+        Ok(key)
+    }
+
+    /// Frees a slot for a new key when the cache is full: drops the expired
+    /// keys, or the one expiring soonest if none have.
+    fn make_room(&self) {
+        if self.keys.len() < self.max_keys {
+            return;
+        }
+        let now = Instant::now();
+        self.keys.retain(|_, cached| cached.expires_at > now);
+        if self.keys.len() < self.max_keys {
+            return;
+        }
+        let soonest = self
+            .keys
+            .iter()
+            .min_by_key(|cached| cached.expires_at)
+            .map(|cached| cached.key().clone());
+        if let Some(soonest) = soonest {
+            debug!("[Agent Keys] Cache full, dropping the key expiring soonest");
+            self.keys.remove(&soonest);
+        }
+    }
+
+    /// Drops every key minted with `user_token`, e.g. once it fails to
+    /// authenticate.
+    pub fn invalidate_token(&self, user_token: &str) {
+        let digest = token_digest(user_token);
+        self.keys.retain(|(token, _, _), _| token != &digest);
+    }
+}
+
+/// How long the agent key for a send stays valid: `requested_secs` if given,
+/// otherwise `default`. Requests outside `MIN_AGENT_KEY_DURATION..=max` are
+/// refused rather than clamped, so a sender never gets a key other than the
+/// one asked for.
+pub fn agent_key_duration(
+    requested_secs: Option<u64>,
+    default: Duration,
+    max: Duration,
+) -> Result<Duration, String> {
+    let Some(secs) = requested_secs else {
+        return Ok(default);
+    };
+    let duration = Duration::from_secs(secs);
+    if duration < MIN_AGENT_KEY_DURATION || duration > max {
+        return Err(format!(
+            "agent_key_duration must be between {} and {} seconds, got {}",
+            MIN_AGENT_KEY_DURATION.as_secs(),
+            max.as_secs(),
+            secs
+        ));
+    }
+    Ok(duration)
+}
+
+/// An agent key valid for at least `duration` for sending to the processor
+/// with `processor_id` on behalf of `user_token`, reused across sends while
+/// it has that long left.
+pub async fn processor_agent_key(
+    cache: &AgentKeyCache,
+    auth_server: &str,
+    user_token: &str,
+    processor_id: &str,
+    duration: Duration,
+) -> Result<String, String> {
+    let agent_id = format!("processor-{}", processor_id);
+    cache
+        .get_or_create(user_token, &agent_id, duration, |lifetime| async move {
+            let request = V1CreateAgentKeyRequest {
+                agent_id: format!("processor-{}", processor_id),
+                name: format!("send-processor-{}-{}", processor_id, ShortUuid::generate()),
+                duration: i32::try_from(lifetime.as_secs()).unwrap_or(i32::MAX),
+            };
+            create_agent_key(auth_server, user_token, request)
+                .await
+                .map_err(|e| format!("Failed to create agent key: {}", e))?
+                .key
+                .ok_or_else(|| "Agent key response missing key".to_string())
+        })
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::routing::{get, post};
+    use axum::{Json, Router};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    const DAY: Duration = Duration::from_secs(86400);
+
+    async fn counted(calls: &AtomicUsize) -> Result<String, String> {
+        let n = calls.fetch_add(1, Ordering::SeqCst);
+        Ok(format!("a.key-{}", n))
+    }
+
+    #[tokio::test]
+    async fn test_repeated_sends_reuse_cached_key() {
+        // Arrange
+        let calls = Arc::new(AtomicUsize::new(0));
+        let issued = calls.clone();
+        let app = Router::new()
+            .route(
+                "/v1/agent/keys",
+                post(move || {
+                    let n = issued.fetch_add(1, Ordering::SeqCst);
+                    let key = serde_json::json!({
+                        "name": format!("key-{}", n),
+                        "key": format!("a.key-{}", n),
+                    });
+                    async move { Json(key) }
+                }),
+            )
+            .route("/v1/users/me", get(|| async { "{}" }));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let auth_server = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        let cache = AgentKeyCache::default();
+
+        // Act
+        let mut keys = Vec::new();
+        for _ in 0..3 {
+            keys.push(
+                processor_agent_key(&cache, &auth_server, "user-token", "p1", DAY)
+                    .await
+                    .unwrap(),
+            );
+        }
+        let other_processor = processor_agent_key(&cache, &auth_server, "user-token", "p2", DAY)
+            .await
+            .unwrap();
+
+        // Assert
+        assert_eq!(keys, vec!["a.key-0", "a.key-0", "a.key-0"]);
+        assert_eq!(other_processor, "a.key-1");
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_invalidated_or_expired_keys_are_recreated() {
+        let cache = AgentKeyCache::default();
+        let calls = AtomicUsize::new(0);
+        let day = Duration::from_secs(86400);
+
+        let first = cache
+            .get_or_create("tok", "processor-p1", day, |_| counted(&calls))
+            .await
+            .unwrap();
+        cache.invalidate_token("other-tok");
+        let reused = cache
+            .get_or_create("tok", "processor-p1", day, |_| counted(&calls))
+            .await
+            .unwrap();
+        cache.invalidate_token("tok");
+        let recreated = cache
+            .get_or_create("tok", "processor-p1", day, |_| counted(&calls))
+            .await
+            .unwrap();
+        assert_eq!((first.as_str(), reused.as_str()), ("a.key-0", "a.key-0"));
+        assert_eq!(recreated, "a.key-1");
+
+        // Less than the asked for lifetime left, so not reused
+        let cache = AgentKeyCache::default();
+        cache
+            .get_or_create("tok", "processor-p2", day, |_| counted(&calls))
+            .await
+            .unwrap();
+        cache.keys.alter_all(|_, cached| CachedKey {
+            expires_at: Instant::now() + day - Duration::from_secs(1),
+            ..cached
+        });
+        let again = cache
+            .get_or_create("tok", "processor-p2", day, |_| counted(&calls))
+            .await
+            .unwrap();
+        assert_eq!(again, "a.key-3");
+    }
+
+    #[tokio::test]
+    async fn test_cache_stays_within_its_size() {
+        let cache = AgentKeyCache::with_max_keys(2);
+        let calls = AtomicUsize::new(0);
+
+        for agent in ["processor-p1", "processor-p2"] {
+            cache
+                .get_or_create("tok", agent, DAY, |_| counted(&calls))
+                .await
+                .unwrap();
+        }
+        // p1 expires soonest, so it makes room for p3
+        cache.keys.alter(
+            &(
+                token_digest("tok"),
+                "processor-p1".to_string(),
+                DAY.as_secs(),
+            ),
+            |_, cached| CachedKey {
+                expires_at: Instant::now() + DAY + Duration::from_secs(1),
+                ..cached
+            },
+        );
+        cache
+            .get_or_create("tok", "processor-p3", DAY, |_| counted(&calls))
+            .await
+            .unwrap();
+        let p2 = cache
+            .get_or_create("tok", "processor-p2", DAY, |_| counted(&calls))
+            .await
+            .unwrap();
+        let p1 = cache
+            .get_or_create("tok", "processor-p1", DAY, |_| counted(&calls))
+            .await
+            .unwrap();
+
+        assert_eq!(p2, "a.key-1");
+        assert_eq!(p1, "a.key-3");
+        assert_eq!(cache.keys.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_failures_are_not_cached() {
+        let cache = AgentKeyCache::default();
+        let day = Duration::from_secs(86400);
+
+        let failed: Result<String, String> = cache
+            .get_or_create("tok", "processor-p1", day, |_| async {
+                Err("auth server down".to_string())
+            })
+            .await;
+        let key: Result<String, String> = cache
+            .get_or_create("tok", "processor-p1", day, |_| async {
+                Ok("a.fresh".to_string())
+            })
+            .await;
+
+        assert!(failed.is_err());
+        assert_eq!(key.unwrap(), "a.fresh");
+    }
+
+    #[tokio::test]
+    async fn test_configured_duration_is_requested() {
+        let requested = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let seen = requested.clone();
+        let app = Router::new().route(
+            "/v1/agent/keys",
+            post(move |Json(body): Json<serde_json::Value>| {
+                let mut seen = seen.lock().unwrap();
+                seen.push(body["duration"].as_i64().unwrap());
+                let key = serde_json::json!({
+                    "name": "key",
+                    "key": format!("a.key-{}", seen.len()),
+                });
+                async move { Json(key) }
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let auth_server = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        let cache = AgentKeyCache::default();
+
+        let duration = agent_key_duration(Some(900), Duration::from_secs(3600), DAY).unwrap();
+        let short = processor_agent_key(&cache, &auth_server, "user-token", "p1", duration)
+            .await
+            .unwrap();
+        let default = agent_key_duration(None, Duration::from_secs(3600), DAY).unwrap();
+        let long = processor_agent_key(&cache, &auth_server, "user-token", "p1", default)
+            .await
+            .unwrap();
+
+        // A key cached for one duration isn't handed out for another, and each
+        // is minted with room to be reused
+        assert_ne!(short, long);
+        assert_eq!(*requested.lock().unwrap(), vec![1500, 4200]);
+    }
+
+    #[test]
+    fn test_agent_key_duration_bounds() {
+        let default = Duration::from_secs(3600);
+        assert_eq!(agent_key_duration(None, default, DAY), Ok(default));
+        assert_eq!(
+            agent_key_duration(Some(86400), default, DAY),
+            Ok(Duration::from_secs(86400))
+        );
+        assert!(agent_key_duration(Some(86401), default, DAY).is_err());
+        assert!(agent_key_duration(Some(59), default, DAY).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_reused_keys_have_the_requested_lifetime_left() {
+        // Arrange
+        let cache = AgentKeyCache::default();
+        let calls = AtomicUsize::new(0);
+        let duration = Duration::from_secs(900);
+        let lifetimes = std::sync::Mutex::new(Vec::new());
+
+        // Act
+        let first = cache
+            .get_or_create("tok", "processor-p1", duration, |lifetime| {
+                lifetimes.lock().unwrap().push(lifetime);
+                counted(&calls)
+            })
+            .await
+            .unwrap();
+        // Minted 810s ago: 690s left, less than the 900s asked for
+        cache.keys.alter_all(|_, cached| CachedKey {
+            expires_at: Instant::now() + Duration::from_secs(690),
+            ..cached
+        });
+        let second = cache
+            .get_or_create("tok", "processor-p1", duration, |_| counted(&calls))
+            .await
+            .unwrap();
+
+        // Assert
+        assert_eq!(*lifetimes.lock().unwrap(), vec![Duration::from_secs(1500)]);
+        assert_eq!((first.as_str(), second.as_str()), ("a.key-0", "a.key-1"));
+        assert_eq!(
+            minted_lifetime(Duration::from_secs(60)),
+            Duration::from_secs(120)
+        );
+    }
+}
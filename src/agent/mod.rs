@@ -1,3 +1,4 @@
 pub mod agent;
 pub mod aws;
+pub mod key_cache;
 pub mod ns;
\ No newline at end of file
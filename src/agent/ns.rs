@@ -40,3 +40,8 @@ pub async fn auth_ns(
     debug!("User is authorized to access namespace");
     Ok(namespace_entity.owner)
 }
+
+/// Whether any of the given owner IDs is the server's root owner
+pub fn is_root_owner(owner_ids: &[String]) -> bool {
+    owner_ids.contains(&SERVER_CONFIG.root_owner)
+}
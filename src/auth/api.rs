@@ -1,6 +1,7 @@
 use crate::auth::db;
 use crate::auth::models;
 use crate::auth::models::SanitizedApiKey;
+use crate::auth::scopes::ApiKeyScopes;
 use argon2::{
     password_hash::{PasswordHash, PasswordVerifier, SaltString},
     Argon2, PasswordHasher,
@@ -9,7 +10,9 @@ use base64::{engine::general_purpose, Engine as _};
 use rand::rngs::OsRng;
 use rand::RngCore;
 use sea_orm::entity::*;
-use sea_orm::DatabaseConnection;
+use sea_orm::sea_query::Expr;
+use sea_orm::{DatabaseConnection, QueryFilter};
+use tracing::{debug, error, info};
 use uuid::Uuid;
 
 pub async fn get_api_key(
@@ -42,9 +45,17 @@ pub async fn list_api_keys(
         .collect())
 }
 
+/// Generates a new API key, valid for `ttl` or until revoked if `None`, and
+/// limited to `scopes` if given.
 pub async fn generate_api_key(
     db_conn: &DatabaseConnection,
+    ttl: Option<std::time::Duration>,
+    scopes: Option<ApiKeyScopes>,
 ) -> Result<String, Box<dyn std::error::Error>> {
+    if let Some(scopes) = &scopes {
+        scopes.validate()?;
+    }
+
     let mut raw_key = [0u8; 32];
     OsRng.fill_bytes(&mut raw_key);
     let key = general_purpose::STANDARD.encode(&raw_key);
@@ -56,8 +67,13 @@ pub async fn generate_api_key(
         Err(_) => return Err("Failed to hash API key.".to_string().into()),
     };
 
+    let expires_at = match ttl {
+        Some(ttl) => Some(chrono::Utc::now() + chrono::Duration::from_std(ttl)?),
+        None => None,
+    };
+
     let id = Uuid::new_v4().to_string();
-    let api_key = models::ApiKey::new(id.clone(), hash);
+    let api_key = models::ApiKey::new(id.clone(), hash, expires_at, scopes);
     let new_api_key: db::ActiveModel = db::Model::from(api_key).into();
     new_api_key.insert(db_conn).await?;
 
@@ -68,17 +84,33 @@ pub async fn validate_api_key(
     db_conn: &DatabaseConnection,
     provided_key: &str,
 ) -> Result<bool, Box<dyn std::error::Error + Sync + Send>> {
+    Ok(authenticate_api_key(db_conn, provided_key).await?.is_some())
+}
+
+/// Checks a key and returns its scopes, or `None` if the key is unknown,
+/// revoked, expired or its scopes can't be read.
+pub async fn authenticate_api_key(
+    db_conn: &DatabaseConnection,
+    provided_key: &str,
+) -> Result<Option<ApiKeyScopes>, Box<dyn std::error::Error + Sync + Send>> {
     if let Some(full_key) = provided_key.strip_prefix("nebu-") {
         let parts: Vec<&str> = full_key.split('.').collect();
         if parts.len() == 2 {
             let (id, key) = (parts[0], parts[1]);
             if let Some(mut api_key) = db::Entity::find_by_id(id).one(db_conn).await? {
-                if api_key.revoked_at.is_some() {
-                    return Ok(false);
+                if api_key.revoked_at.is_some() || api_key.is_expired(chrono::Utc::now()) {
+                    return Ok(None);
                 }
+                let scopes = match api_key.parse_scopes() {
+                    Ok(scopes) => scopes,
+                    Err(e) => {
+                        error!("Unreadable scopes on API key {}: {}", api_key.id, e);
+                        return Ok(None);
+                    }
+                };
                 let parsed_hash = match PasswordHash::new(&api_key.hash) {
                     Ok(hash) => hash,
-                    Err(_) => return Ok(false),
+                    Err(_) => return Ok(None),
                 };
                 let argon2 = Argon2::default();
                 return match argon2.verify_password(key.as_bytes(), &parsed_hash) {
@@ -86,14 +118,14 @@ pub async fn validate_api_key(
                         let mut active_api_key: db::ActiveModel = api_key.into();
                         active_api_key.last_used_at = Set(Some(chrono::Utc::now()));
                         active_api_key.update(db_conn).await?;
-                        Ok(true)
+                        Ok(Some(scopes))
                     }
-                    Err(_) => Ok(false),
+                    Err(_) => Ok(None),
                 };
             }
         }
     }
-    Ok(false)
+    Ok(None)
 }
 
 pub async fn revoke_api_key(
@@ -110,3 +142,110 @@ pub async fn revoke_api_key(
         Err("API key not found".into())
     }
 }
+
+/// Revokes keys whose expiry has passed, returning how many were revoked.
+pub async fn revoke_expired_api_keys(
+    db_conn: &DatabaseConnection,
+    now: chrono::DateTime<chrono::Utc>,
+) -> Result<u64, sea_orm::DbErr> {
+    let result = db::Entity::update_many()
+        .col_expr(db::Column::RevokedAt, Expr::value(now))
+        .col_expr(db::Column::Hash, Expr::value(String::new()))
+        .filter(db::Column::RevokedAt.is_null())
+        .filter(db::Column::ExpiresAt.lte(now))
+        .exec(db_conn)
+        .await?;
+    Ok(result.rows_affected)
+}
+
+/// Spawns a background Tokio task that revokes expired API keys
+pub fn spawn_expired_key_sweeper(db_conn: DatabaseConnection) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        loop {
+            match revoke_expired_api_keys(&db_conn, chrono::Utc::now()).await {
+                Ok(0) => debug!("No expired API keys to revoke"),
+                Ok(count) => info!("Revoked {} expired API keys", count),
+                Err(e) => error!("Failed to revoke expired API keys: {:?}", e),
+            }
+
+            tokio::time::sleep(tokio::time::Duration::from_secs(5 * 60)).await;
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sea_orm::{ConnectionTrait, Database, Schema};
+
+    async fn setup() -> DatabaseConnection {
+        let db = Database::connect("sqlite::memory:").await.unwrap();
+        let schema = Schema::new(db.get_database_backend());
+        db.execute(
+            db.get_database_backend()
+                .build(&schema.create_table_from_entity(db::Entity)),
+        )
+        .await
+        .unwrap();
+        db
+    }
+
+    fn key_id(key: &str) -> &str {
+        key.strip_prefix("nebu-")
+            .unwrap()
+            .split('.')
+            .next()
+            .unwrap()
+    }
+
+    /// Moves a key's expiry into the past, as if its TTL had run out.
+    async fn expire(db: &DatabaseConnection, id: &str) {
+        let model = db::Entity::find_by_id(id).one(db).await.unwrap().unwrap();
+        let mut active: db::ActiveModel = model.into();
+        active.expires_at = Set(Some(chrono::Utc::now() - chrono::Duration::seconds(1)));
+        active.update(db).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_key_is_rejected_once_expired() {
+        // Arrange
+        let db = setup().await;
+        let key = generate_api_key(&db, Some(std::time::Duration::from_secs(3600)), None)
+            .await
+            .unwrap();
+        assert!(validate_api_key(&db, &key).await.unwrap());
+
+        // Act
+        expire(&db, key_id(&key)).await;
+
+        // Assert
+        assert!(!validate_api_key(&db, &key).await.unwrap());
+        let sanitized = get_api_key(&db, key_id(&key)).await.unwrap();
+        assert!(!sanitized.is_active);
+    }
+
+    #[tokio::test]
+    async fn test_sweep_revokes_only_expired_keys() {
+        let db = setup().await;
+        let expiring = generate_api_key(&db, Some(std::time::Duration::from_secs(3600)), None)
+            .await
+            .unwrap();
+        let permanent = generate_api_key(&db, None, None).await.unwrap();
+        expire(&db, key_id(&expiring)).await;
+
+        let revoked = revoke_expired_api_keys(&db, chrono::Utc::now())
+            .await
+            .unwrap();
+
+        assert_eq!(revoked, 1);
+        let expired = get_api_key(&db, key_id(&expiring)).await.unwrap();
+        assert!(expired.revoked_at.is_some());
+        assert!(validate_api_key(&db, &permanent).await.unwrap());
+        assert_eq!(
+            revoke_expired_api_keys(&db, chrono::Utc::now())
+                .await
+                .unwrap(),
+            0
+        );
+    }
+}
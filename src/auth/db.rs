@@ -1,3 +1,4 @@
+use crate::auth::scopes::ApiKeyScopes;
 use sea_orm::prelude::DateTimeWithTimeZone;
 use sea_orm::DerivePrimaryKey;
 use sea_orm::{ActiveModelBehavior, DeriveEntityModel, DeriveRelation, EnumIter, PrimaryKeyTrait};
@@ -11,6 +12,25 @@ pub struct Model {
     pub created_at: chrono::DateTime<chrono::Utc>,
     pub last_used_at: Option<chrono::DateTime<chrono::Utc>>,
     pub revoked_at: Option<chrono::DateTime<chrono::Utc>>,
+    /// Keys without an expiry stay valid until revoked
+    pub expires_at: Option<chrono::DateTime<chrono::Utc>>,
+    /// `ApiKeyScopes` limiting the key, unrestricted when null
+    pub scopes: Option<serde_json::Value>,
+}
+
+impl Model {
+    /// The key's scopes. Scopes that fail to parse are an error rather than
+    /// no limits.
+    pub fn parse_scopes(&self) -> Result<ApiKeyScopes, serde_json::Error> {
+        match &self.scopes {
+            Some(value) => serde_json::from_value(value.clone()),
+            None => Ok(ApiKeyScopes::default()),
+        }
+    }
+
+    pub fn is_expired(&self, now: chrono::DateTime<chrono::Utc>) -> bool {
+        self.expires_at.is_some_and(|expires_at| expires_at <= now)
+    }
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -1,4 +1,5 @@
 pub mod api;
 pub mod db;
 pub mod models;
+pub mod scopes;
 pub mod server;
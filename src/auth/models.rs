@@ -1,4 +1,5 @@
 use crate::auth::db;
+use crate::auth::scopes::ApiKeyScopes;
 use serde::{Deserialize, Serialize};
 
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
@@ -8,17 +9,26 @@ pub struct ApiKey {
     pub created_at: chrono::DateTime<chrono::Utc>,
     pub last_used_at: Option<chrono::DateTime<chrono::Utc>>,
     pub revoked_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub expires_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub scopes: Option<ApiKeyScopes>,
     pub is_active: bool,
 }
 
 impl ApiKey {
-    pub fn new(id: String, hash: String) -> Self {
+    pub fn new(
+        id: String,
+        hash: String,
+        expires_at: Option<chrono::DateTime<chrono::Utc>>,
+        scopes: Option<ApiKeyScopes>,
+    ) -> Self {
         Self {
             id,
             hash,
             created_at: chrono::Utc::now(),
             last_used_at: None,
             revoked_at: None,
+            expires_at,
+            scopes: scopes.filter(|scopes| !scopes.is_unrestricted()),
             is_active: true,
         }
     }
@@ -26,13 +36,17 @@ impl ApiKey {
 
 impl From<db::Model> for ApiKey {
     fn from(model: db::Model) -> Self {
+        let is_active = model.revoked_at.is_none() && !model.is_expired(chrono::Utc::now());
+        let scopes = model.parse_scopes().ok().filter(|s| !s.is_unrestricted());
         Self {
             id: model.id,
             hash: model.hash,
             created_at: model.created_at,
             last_used_at: model.last_used_at,
             revoked_at: model.revoked_at,
-            is_active: model.revoked_at.is_none(),
+            expires_at: model.expires_at,
+            scopes,
+            is_active,
         }
     }
 }
@@ -45,6 +59,10 @@ impl From<ApiKey> for db::Model {
             created_at: api_key.created_at,
             last_used_at: api_key.last_used_at,
             revoked_at: api_key.revoked_at,
+            expires_at: api_key.expires_at,
+            scopes: api_key
+                .scopes
+                .and_then(|scopes| serde_json::to_value(scopes).ok()),
         }
     }
 }
@@ -55,6 +73,10 @@ pub struct SanitizedApiKey {
     pub created_at: chrono::DateTime<chrono::Utc>,
     pub last_used_at: Option<chrono::DateTime<chrono::Utc>>,
     pub revoked_at: Option<chrono::DateTime<chrono::Utc>>,
+    #[serde(default)]
+    pub expires_at: Option<chrono::DateTime<chrono::Utc>>,
+    #[serde(default)]
+    pub scopes: Option<ApiKeyScopes>,
     pub is_active: bool,
 }
 
@@ -65,6 +87,8 @@ impl From<ApiKey> for SanitizedApiKey {
             created_at: api_key.created_at,
             last_used_at: api_key.last_used_at,
             revoked_at: api_key.revoked_at,
+            expires_at: api_key.expires_at,
+            scopes: api_key.scopes,
             is_active: api_key.is_active,
         }
     }
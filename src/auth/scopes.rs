@@ -0,0 +1,286 @@
+use serde::{Deserialize, Serialize};
+
+/// Resources an action can name, the second segment of `/v1/<resource>/...`
+pub const SCOPED_RESOURCES: &[&str] = &[
+    "auth",
+    "cache",
+    "containers",
+    "namespaces",
+    "processors",
+    "secrets",
+    "users",
+    "volumes",
+];
+
+/// Lets a key read secret values, which listings and lookups otherwise leave
+/// out. Implies `secrets:read`.
+pub const REVEAL_SECRETS: &str = "secrets:reveal";
+
+/// Limits on what an API key may do. A missing list means no limit, so a key
+/// without scopes keeps full access to everything its owner can reach.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ApiKeyScopes {
+    /// Namespaces the key may touch
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub namespaces: Option<Vec<String>>,
+    /// Actions like `containers:read`, `processors:write` or `secrets:*`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub actions: Option<Vec<String>>,
+}
+
+/// What a request needs from a key's scopes.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RequiredScope {
+    pub action: String,
+    /// `None` for routes that aren't tied to one namespace, like listings
+    pub namespace: Option<String>,
+}
+
+impl ApiKeyScopes {
+    pub fn is_unrestricted(&self) -> bool {
+        self.namespaces.is_none() && self.actions.is_none()
+    }
+
+    /// Checks every action is `<resource>:<read|write|*>`, `secrets:reveal`
+    /// or `*`.
+    pub fn validate(&self) -> Result<(), String> {
+        for action in self.actions.iter().flatten() {
+            if action == "*" || action == REVEAL_SECRETS {
+                continue;
+            }
+            let valid = match action.split_once(':') {
+                Some((resource, access)) => {
+                    SCOPED_RESOURCES.contains(&resource) && matches!(access, "read" | "write" | "*")
+                }
+                None => false,
+            };
+            if !valid {
+                return Err(format!(
+                    "Invalid action '{}': expected '<resource>:<read|write|*>' with resource one of {}",
+                    action,
+                    SCOPED_RESOURCES.join(", ")
+                ));
+            }
+        }
+        if let Some(namespaces) = &self.namespaces {
+            if namespaces.iter().any(|ns| ns.trim().is_empty()) {
+                return Err("Namespaces must not be empty".to_string());
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether the scopes cover a request. Namespace limited keys can only
+    /// use routes that name their namespace.
+    pub fn allows(&self, required: &RequiredScope) -> bool {
+        let action_allowed = match &self.actions {
+            None => true,
+            Some(actions) => {
+                let (resource, _) = required
+                    .action
+                    .split_once(':')
+                    .unwrap_or((required.action.as_str(), ""));
+                actions.iter().any(|action| {
+                    action == "*"
+                        || *action == required.action
+                        || *action == format!("{}:*", resource)
+                        || (action == REVEAL_SECRETS && required.action == "secrets:read")
+                })
+            }
+        };
+        let namespace_allowed = match (&self.namespaces, &required.namespace) {
+            (None, _) => true,
+            (Some(allowed), Some(namespace)) => allowed.contains(namespace),
+            (Some(_), None) => false,
+        };
+        action_allowed && namespace_allowed
+    }
+}
+
+/// Works out the scope a request needs from its method, the route template it
+/// matched (e.g. `/v1/containers/:namespace/:name`) and its actual path.
+pub fn required_scope(method: &http::Method, route: &str, path: &str) -> RequiredScope {
+    let route_segments: Vec<&str> = route.split('/').filter(|s| !s.is_empty()).collect();
+    let path_segments: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+
+    // `/v1/<resource>/...`, and the key management routes under `/auth/...`
+    let resource = match route_segments.as_slice() {
+        ["v1", resource, ..] => *resource,
+        [resource, ..] => *resource,
+        [] => "",
+    };
+
+    let param = |name: &str| {
+        route_segments
+            .iter()
+            .position(|segment| *segment == name)
+            .and_then(|i| path_segments.get(i))
+            .map(|value| value.to_string())
+    };
+    let namespace = match resource {
+        "namespaces" => param(":name"),
+        _ => param(":namespace"),
+    };
+
+    let read = matches!(
+        *method,
+        http::Method::GET | http::Method::HEAD | http::Method::OPTIONS
+    ) || route == "/v1/containers/search";
+
+    RequiredScope {
+        action: format!("{}:{}", resource, if read { "read" } else { "write" }),
+        namespace,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use http::Method;
+
+    fn scopes(namespaces: Option<&[&str]>, actions: Option<&[&str]>) -> ApiKeyScopes {
+        let owned = |list: &[&str]| list.iter().map(|s| s.to_string()).collect();
+        ApiKeyScopes {
+            namespaces: namespaces.map(owned),
+            actions: actions.map(owned),
+        }
+    }
+
+    #[test]
+    fn test_required_scope_from_route() {
+        // Act
+        let required = required_scope(
+            &Method::DELETE,
+            "/v1/containers/:namespace/:name",
+            "/v1/containers/team-a/web",
+        );
+
+        // Assert
+        assert_eq!(
+            required,
+            RequiredScope {
+                action: "containers:write".to_string(),
+                namespace: Some("team-a".to_string()),
+            }
+        );
+    }
+
+    #[test]
+    fn test_required_scope_special_routes() {
+        assert_eq!(
+            required_scope(
+                &Method::GET,
+                "/v1/namespaces/:name",
+                "/v1/namespaces/team-a"
+            ),
+            RequiredScope {
+                action: "namespaces:read".to_string(),
+                namespace: Some("team-a".to_string()),
+            }
+        );
+        assert_eq!(
+            required_scope(
+                &Method::POST,
+                "/v1/containers/search",
+                "/v1/containers/search"
+            ),
+            RequiredScope {
+                action: "containers:read".to_string(),
+                namespace: None,
+            }
+        );
+        assert_eq!(
+            required_scope(
+                &Method::POST,
+                "/v1/containers/:namespace/:name/ssh/keys",
+                "/v1/containers/team-a/web/ssh/keys"
+            )
+            .action,
+            "containers:write"
+        );
+        assert_eq!(
+            required_scope(
+                &Method::GET,
+                "/v1/containers/:id/logs",
+                "/v1/containers/abc/logs"
+            )
+            .namespace,
+            None
+        );
+    }
+
+    #[test]
+    fn test_allows_actions() {
+        let required = RequiredScope {
+            action: "processors:write".to_string(),
+            namespace: Some("team-a".to_string()),
+        };
+        assert!(ApiKeyScopes::default().allows(&required));
+        assert!(scopes(None, Some(&["processors:write"])).allows(&required));
+        assert!(scopes(None, Some(&["processors:*"])).allows(&required));
+        assert!(scopes(None, Some(&["*"])).allows(&required));
+        assert!(!scopes(None, Some(&["processors:read"])).allows(&required));
+        assert!(!scopes(None, Some(&["containers:*"])).allows(&required));
+    }
+
+    #[test]
+    fn test_allows_namespaces() {
+        let key = scopes(Some(&["team-a"]), None);
+        let in_namespace = RequiredScope {
+            action: "containers:read".to_string(),
+            namespace: Some("team-a".to_string()),
+        };
+        let other_namespace = RequiredScope {
+            namespace: Some("team-b".to_string()),
+            ..in_namespace.clone()
+        };
+        let any_namespace = RequiredScope {
+            namespace: None,
+            ..in_namespace.clone()
+        };
+
+        assert!(key.allows(&in_namespace));
+        assert!(!key.allows(&other_namespace));
+        assert!(!key.allows(&any_namespace));
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(scopes(Some(&["team-a"]), Some(&["containers:read", "*"]))
+            .validate()
+            .is_ok());
+        assert!(scopes(None, Some(&["containers"])).validate().is_err());
+        assert!(scopes(None, Some(&["boats:read"])).validate().is_err());
+        assert!(scopes(None, Some(&["containers:delete"]))
+            .validate()
+            .is_err());
+        assert!(scopes(Some(&[""]), None).validate().is_err());
+        assert!(scopes(None, Some(&[REVEAL_SECRETS])).validate().is_ok());
+        assert!(scopes(None, Some(&["containers:reveal"]))
+            .validate()
+            .is_err());
+    }
+
+    #[test]
+    fn test_reveal_secrets() {
+        let reveal = RequiredScope {
+            action: REVEAL_SECRETS.to_string(),
+            namespace: Some("team-a".to_string()),
+        };
+        let read = RequiredScope {
+            action: "secrets:read".to_string(),
+            ..reveal.clone()
+        };
+
+        assert!(scopes(None, Some(&[REVEAL_SECRETS])).allows(&reveal));
+        assert!(scopes(None, Some(&[REVEAL_SECRETS])).allows(&read));
+        assert!(scopes(None, Some(&["secrets:*"])).allows(&reveal));
+        assert!(!scopes(None, Some(&["secrets:read"])).allows(&reveal));
+        assert!(
+            !scopes(None, Some(&[REVEAL_SECRETS])).allows(&RequiredScope {
+                action: "secrets:write".to_string(),
+                ..reveal.clone()
+            })
+        );
+    }
+}
@@ -1,7 +1,8 @@
 use crate::auth;
 use crate::auth::models::SanitizedApiKey;
+use crate::auth::scopes::ApiKeyScopes;
 use crate::state::AppState;
-use axum::extract::{Json, Path, State};
+use axum::extract::{Json, Path, Query, State};
 use axum::http::StatusCode;
 use serde::{Deserialize, Serialize};
 
@@ -10,6 +11,31 @@ pub struct ApiKeyRequest {
     pub id: String,
 }
 
+#[derive(Serialize, Deserialize, Default)]
+pub struct GenerateApiKeyParams {
+    /// How long the key stays valid, e.g. "30d". Falls back to `NEBU_API_KEY_TTL`.
+    pub ttl: Option<String>,
+    /// Comma separated namespaces the key is limited to
+    pub namespaces: Option<String>,
+    /// Comma separated actions the key is limited to, e.g. "containers:read,processors:write"
+    pub actions: Option<String>,
+}
+
+impl GenerateApiKeyParams {
+    pub fn scopes(&self) -> ApiKeyScopes {
+        let split = |list: &String| -> Vec<String> {
+            list.split(',')
+                .map(|item| item.trim().to_string())
+                .filter(|item| !item.is_empty())
+                .collect()
+        };
+        ApiKeyScopes {
+            namespaces: self.namespaces.as_ref().map(split),
+            actions: self.actions.as_ref().map(split),
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct RawApiKeyResponse {
     pub api_key: String,
@@ -53,8 +79,25 @@ pub async fn list_api_keys(
 
 pub async fn generate_api_key(
     State(state): State<AppState>,
+    Query(params): Query<GenerateApiKeyParams>,
 ) -> Result<Json<RawApiKeyResponse>, (StatusCode, Json<serde_json::Value>)> {
-    match auth::api::generate_api_key(&state.db_pool).await {
+    let ttl = match &params.ttl {
+        Some(ttl) => Some(humantime::parse_duration(ttl).map_err(|e| {
+            (
+                StatusCode::BAD_REQUEST,
+                Json(serde_json::json!({"error": format!("Invalid ttl '{}': {}", ttl, e)})),
+            )
+        })?),
+        None => crate::config::SERVER_CONFIG.api_key_ttl,
+    };
+    let scopes = params.scopes();
+    scopes.validate().map_err(|e| {
+        (
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({ "error": e })),
+        )
+    })?;
+    match auth::api::generate_api_key(&state.db_pool, ttl, Some(scopes)).await {
         Ok(api_key) => Ok(Json(RawApiKeyResponse::new(api_key))),
         Err(_) => Err((
             StatusCode::INTERNAL_SERVER_ERROR,
@@ -2,12 +2,34 @@ use crate::auth::server::handlers::{generate_api_key, get_api_key, list_api_keys
 use crate::state::AppState;
 use axum::routing::{get, post};
 use axum::Router;
+use std::net::{IpAddr, SocketAddr};
 use tower_http::trace::TraceLayer;
+
+/// Where the auth server listens unless told otherwise. It hands out API
+/// keys, so it stays off the network by default.
+pub const DEFAULT_AUTH_BIND_HOST: &str = "127.0.0.1";
+
 pub async fn health_check() -> &'static str {
     "OK"
 }
 
-pub async fn start_auth_server(app_state: AppState, port: u16) -> std::io::Result<()> {
+/// The address to listen on from a configured host, an IP address like
+/// `0.0.0.0` or `::`, falling back to `DEFAULT_AUTH_BIND_HOST`
+pub fn auth_bind_address(host: Option<&str>, port: u16) -> Result<SocketAddr, String> {
+    let host = host.unwrap_or(DEFAULT_AUTH_BIND_HOST);
+    let ip: IpAddr = host
+        .trim_start_matches('[')
+        .trim_end_matches(']')
+        .parse()
+        .map_err(|_| format!("'{}' is not an IP address", host))?;
+    Ok(SocketAddr::new(ip, port))
+}
+
+pub async fn start_auth_server(
+    app_state: AppState,
+    host: Option<&str>,
+    port: u16,
+) -> std::io::Result<()> {
     let app = Router::new()
         .route("/health", get(health_check))
         .route("/api-keys", get(list_api_keys))
@@ -17,10 +39,31 @@ pub async fn start_auth_server(app_state: AppState, port: u16) -> std::io::Resul
         .layer(TraceLayer::new_for_http())
         .with_state(app_state);
 
-    let addr = format!("{}:{}", "127.0.0.1", port);
-    let listener = tokio::net::TcpListener::bind(&addr).await?;
+    let addr = auth_bind_address(host, port)
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
+    let listener = tokio::net::TcpListener::bind(addr).await?;
     println!("Auth server running at http://{}", addr);
 
     axum::serve(listener, app).await?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bind_address_from_config() {
+        // Act
+        let default = auth_bind_address(None, 8080).unwrap();
+        let all = auth_bind_address(Some("0.0.0.0"), 8080).unwrap();
+        let ipv6 = auth_bind_address(Some("[::]"), 8080).unwrap();
+
+        // Assert
+        assert_eq!(default.to_string(), "127.0.0.1:8080");
+        assert!(default.ip().is_loopback());
+        assert_eq!(all.to_string(), "0.0.0.0:8080");
+        assert_eq!(ipv6.to_string(), "[::]:8080");
+        assert!(auth_bind_address(Some("not an address"), 8080).is_err());
+    }
+}
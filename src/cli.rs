@@ -43,9 +43,9 @@ pub enum Commands {
 
     /// Serve the API.
     Serve {
-        /// The address to bind to.
-        #[arg(long, default_value = "127.0.0.1")]
-        host: String,
+        /// The address to bind to, or NEBU_BIND_HOST, default 127.0.0.1.
+        #[arg(long)]
+        host: Option<String>,
 
         /// The port to bind to.
         #[arg(long, default_value_t = 3000)]
@@ -58,6 +58,11 @@ pub enum Commands {
         /// The port to bind the internal auth server to.
         #[arg(long, default_value_t = 8080)]
         auth_port: u16,
+
+        /// The IP address to bind the internal auth server to, or
+        /// NEBU_AUTH_BIND_HOST, default 127.0.0.1.
+        #[arg(long)]
+        auth_host: Option<String>,
     },
 
     /// Proxy services.
@@ -79,6 +84,10 @@ pub enum Commands {
         /// Run in the background (detached) if true.
         #[arg(short, long, default_value_t = false)]
         background: bool,
+
+        /// Run a single container reconcile pass, print a summary and exit.
+        #[arg(long, default_value_t = false)]
+        once: bool,
     },
 
     /// Fetch logs for a container.
@@ -101,6 +110,12 @@ pub enum Commands {
         command: SendCommands,
     },
 
+    /// Read messages from a processor's stream.
+    Read {
+        #[command(subcommand)]
+        command: ReadCommands,
+    },
+
     /// Login to a Nebulous API server.
     Login {
         /// Address of the API server
@@ -114,6 +129,18 @@ pub enum Commands {
         /// Address of the Hub
         #[arg(long, default_value = None)]
         hub: Option<String>,
+
+        /// Profile to store the login under, replacing any login it had.
+        /// Defaults to `cloud` with a hub and `nebu` without one.
+        #[arg(long)]
+        profile: Option<String>,
+    },
+
+    /// Log out of a profile, removing its API key.
+    Logout {
+        /// Profile to log out of; defaults to the current one
+        #[arg(long)]
+        profile: Option<String>,
     },
 
     /// Auth commands.
@@ -147,7 +174,8 @@ pub enum SelectCommands {
         #[arg(long, default_value = ".")]
         base_dir: String,
 
-        /// The selection criterion: "latest" or "best".
+        /// The selection criterion: "latest", "best:<metric>:<min|max>", "step:<n>" or
+        /// "glob:<pattern>". "best" alone selects the lowest eval_loss.
         #[arg(long, default_value = "best")]
         criteria: String,
     },
@@ -195,8 +223,13 @@ pub struct SecretCommands {
     pub expires_at: Option<i32>,
 
     /// Read the secret value from a file.
-    #[arg(short = 'f', long)]
+    #[arg(short = 'f', long, visible_alias = "from-file")]
     pub file: Option<String>,
+
+    /// Create a secret for every variable in a `.env` file, named
+    /// `<name>-<variable>`, e.g. `app-db-password` for `DB_PASSWORD`.
+    #[arg(long, conflicts_with_all = ["value", "file"])]
+    pub from_env_file: Option<String>,
 }
 
 #[derive(Subcommand)]
@@ -246,6 +279,10 @@ pub enum SyncCommands {
         /// Sync from the NEBU_SYNC_CONFIG environment variable.
         #[arg(long, default_value_t = false)]
         config_from_env: bool,
+
+        /// Check the paths against their destinations without transferring.
+        #[arg(long, default_value_t = false)]
+        verify: bool,
     },
 
     /// Ensure all syncs are complete.
@@ -257,6 +294,14 @@ pub enum SyncCommands {
         /// Interval in seconds to sync.
         #[arg(short, long, default_value_t = 2)]
         interval_seconds: u64,
+
+        /// Give up after this many seconds and exit with an error.
+        #[arg(long)]
+        timeout_seconds: Option<u64>,
+
+        /// Exit successfully instead of failing when the timeout is reached.
+        #[arg(long, default_value_t = false)]
+        proceed_on_timeout: bool,
     },
 }
 
@@ -293,6 +338,10 @@ pub struct ContainerCommands {
     #[arg(long)]
     pub platform: Option<String>,
 
+    /// Platforms to fall back through, in order, e.g. "runpod,kube"
+    #[arg(long, value_delimiter = ',')]
+    pub platforms: Option<Vec<String>>,
+
     /// Container image
     #[arg(long)]
     pub image: Option<String>,
@@ -361,7 +410,11 @@ pub struct ContainerCommands {
     #[arg(long)]
     pub meter_unit: Option<String>,
 
-    /// Restart policy of the container
+    /// Extra meter dimensions in KEY=VALUE format
+    #[arg(long, value_parser = parse_key_val, action = ArgAction::Append)]
+    pub meter_dimension: Option<Vec<(String, String)>>,
+
+    /// Restart policy of the container (Always, OnFailure, Never)
     #[arg(long)]
     pub restart: Option<String>,
 
@@ -369,6 +422,18 @@ pub struct ContainerCommands {
     #[arg(long)]
     pub queue: Option<String>,
 
+    /// Priority within the queue, higher starts first
+    #[arg(long)]
+    pub priority: Option<i32>,
+
+    /// Allow higher priority containers in the queue to preempt this one
+    #[arg(long)]
+    pub preemptible: Option<bool>,
+
+    /// URL to notify when the container becomes running, completed or failed
+    #[arg(long)]
+    pub webhook_url: Option<String>,
+
     /// Timeout for the container
     #[arg(long)]
     pub timeout: Option<String>,
@@ -512,6 +577,58 @@ pub struct SendMessageCommands {
     pub wait: bool,
 }
 
+/// Read resources.
+#[derive(Subcommand)]
+pub enum ReadCommands {
+    /// Read the messages on a processor's stream.
+    #[command(aliases = ["processor", "proc"])]
+    Processors {
+        #[command(flatten)]
+        command: ReadProcessorCommands,
+    },
+}
+
+/// Parameters for reading a processor's stream
+#[derive(Args)]
+pub struct ReadProcessorCommands {
+    /// Processor as `<namespace>/<name>`, or its name with --namespace
+    pub name: String,
+
+    /// Processor namespace
+    #[arg(long, short)]
+    pub namespace: Option<String>,
+
+    /// Consumer group to read as. Messages go to one reader per group and
+    /// the group stays on the stream, so without one the messages are only
+    /// peeked at and left for the processor.
+    #[arg(long, short)]
+    pub group: Option<String>,
+
+    /// Keep reading as messages arrive
+    #[arg(long, short, default_value_t = false)]
+    pub follow: bool,
+
+    /// Only print messages this JSONPath selects something in, e.g. `$.content.error`
+    #[arg(long)]
+    pub filter: Option<String>,
+
+    /// Output format
+    #[arg(long, short, default_value = "text", value_parser = ["text", "json"])]
+    pub output: String,
+
+    /// Messages fetched per read
+    #[arg(long, default_value_t = 10)]
+    pub max_records: u64,
+
+    /// How long a read waits for messages, in milliseconds
+    #[arg(long, default_value_t = 5000)]
+    pub wait_ms: u64,
+
+    /// Acknowledge messages as they are read
+    #[arg(long, default_value_t = false, requires = "group")]
+    pub ack: bool,
+}
+
 /// Subcommands for the "work" command
 #[derive(Subcommand)]
 pub enum WorkCommands {}
@@ -540,7 +657,20 @@ pub enum ApiKeyActions {
     },
 
     /// Generate a new API key.
-    Generate,
+    Generate {
+        /// How long the key stays valid, e.g. "30d". Defaults to the server's NEBU_API_KEY_TTL.
+        #[arg(long)]
+        ttl: Option<String>,
+
+        /// Limit the key to these namespaces.
+        #[arg(long, value_delimiter = ',')]
+        namespaces: Option<Vec<String>>,
+
+        /// Limit the key to these actions, e.g. "containers:read,processors:write".
+        /// "secrets:reveal" lets it read secret values.
+        #[arg(long, value_delimiter = ',')]
+        actions: Option<Vec<String>>,
+    },
 
     /// Revoke an API key.
     Revoke {
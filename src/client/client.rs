@@ -1,10 +1,11 @@
 use crate::config::ClientConfig;
-use crate::models::V1StreamData;
+use crate::models::{V1StreamData, V1StreamMessage};
 use crate::resources::v1::containers::models::{
     V1Container, V1ContainerRequest, V1ContainerSearch, V1Containers, V1UpdateContainer,
 };
 use crate::resources::v1::processors::models::{
-    V1Processor, V1ProcessorRequest, V1ProcessorScaleRequest, V1Processors, V1UpdateProcessor,
+    V1PeekStreamParams, V1Processor, V1ProcessorRequest, V1ProcessorScaleRequest, V1Processors,
+    V1ReadStreamRequest, V1UpdateProcessor,
 };
 use crate::resources::v1::secrets::models::{V1Secret, V1SecretRequest, V1Secrets};
 use reqwest::Client as HttpClient;
@@ -183,14 +184,17 @@ impl NebulousClient {
         }
     }
 
-    /// Gets a specific secret by namespace and name, returning a typed `V1Secret`.
-    /// `name` cannot be empty.
+    /// Gets a specific secret by namespace and name, returning a typed `V1Secret`
+    /// with its value. `name` cannot be empty.
     pub async fn get_secret(
         &self,
         name: &str,
         namespace: &str,
     ) -> Result<V1Secret, Box<dyn std::error::Error>> {
-        let url = format!("{}/v1/secrets/{}/{}", self.base_url, namespace, name);
+        let url = format!(
+            "{}/v1/secrets/{}/{}?reveal=true",
+            self.base_url, namespace, name
+        );
         let response = self
             .http_client
             .get(&url)
@@ -211,7 +215,7 @@ impl NebulousClient {
         }
     }
 
-    /// Lists all secrets, returning a typed `V1Secrets`.
+    /// Lists all secrets, returning a typed `V1Secrets`. Values are left out.
     pub async fn get_secrets(&self) -> Result<V1Secrets, Box<dyn std::error::Error>> {
         let url = format!("{}/v1/secrets", self.base_url);
         let response = self
@@ -546,4 +550,70 @@ impl NebulousClient {
             .into())
         }
     }
+
+    /// Reads the next messages on a processor's stream for a consumer group,
+    /// waiting up to `wait_time_ms` for some to arrive.
+    pub async fn read_processor_stream(
+        &self,
+        name: &str,
+        namespace: &str,
+        read_request: &V1ReadStreamRequest,
+    ) -> Result<Vec<V1StreamMessage>, Box<dyn Error>> {
+        let url = format!(
+            "{}/v1/processors/{}/{}/stream",
+            self.base_url, namespace, name
+        );
+
+        let response = self
+            .http_client
+            .post(&url)
+            .header("Authorization", format!("Bearer {}", self.api_key))
+            .json(read_request)
+            .send()
+            .await?;
+
+        if response.status().is_success() {
+            Ok(response.json::<Vec<V1StreamMessage>>().await?)
+        } else {
+            let error_text = response.text().await?;
+            Err(format!(
+                "Failed to read stream of processor '{}/{}': {}",
+                namespace, name, error_text
+            )
+            .into())
+        }
+    }
+
+    /// Reads the messages on a processor's stream after `params.after`
+    /// without a consumer group, leaving them for the processor.
+    pub async fn peek_processor_stream(
+        &self,
+        name: &str,
+        namespace: &str,
+        params: &V1PeekStreamParams,
+    ) -> Result<Vec<V1StreamMessage>, Box<dyn Error>> {
+        let url = format!(
+            "{}/v1/processors/{}/{}/stream/entries",
+            self.base_url, namespace, name
+        );
+
+        let response = self
+            .http_client
+            .get(&url)
+            .header("Authorization", format!("Bearer {}", self.api_key))
+            .query(params)
+            .send()
+            .await?;
+
+        if response.status().is_success() {
+            Ok(response.json::<Vec<V1StreamMessage>>().await?)
+        } else {
+            let error_text = response.text().await?;
+            Err(format!(
+                "Failed to read stream of processor '{}/{}': {}",
+                namespace, name, error_text
+            )
+            .into())
+        }
+    }
 }
@@ -22,6 +22,12 @@ fn pretty_print_api_key(api_key: SanitizedApiKey) {
             .revoked_at
             .map_or("N/A".to_string(), |dt| dt.to_string())
     );
+    println!(
+        "Expires at: {}",
+        api_key
+            .expires_at
+            .map_or("Never".to_string(), |dt| dt.to_string())
+    );
     println!();
 }
 
@@ -41,9 +47,29 @@ pub async fn get_api_key(id: &str) -> Result<(), Box<dyn Error>> {
     Ok(())
 }
 
-pub async fn generate_api_key() -> Result<(), Box<dyn Error>> {
+pub async fn generate_api_key(
+    ttl: Option<&str>,
+    namespaces: Option<Vec<String>>,
+    actions: Option<Vec<String>>,
+) -> Result<(), Box<dyn Error>> {
     let url = format!("{}/api-key/generate", SERVER);
-    match reqwest::Client::new().get(&url).send().await {
+    let mut query: Vec<(&str, String)> = Vec::new();
+    if let Some(ttl) = ttl {
+        query.push(("ttl", ttl.to_string()));
+    }
+    if let Some(namespaces) = namespaces {
+        query.push(("namespaces", namespaces.join(",")));
+    }
+    if let Some(actions) = actions {
+        query.push(("actions", actions.join(",")));
+    }
+    match reqwest::Client::new().get(&url).query(&query).send().await {
+        Ok(response) if !response.status().is_success() => {
+            eprintln!(
+                "Failed to generate API key: {}",
+                response.text().await.unwrap_or_default()
+            );
+        }
         Ok(response) => {
             let api_key = response.json::<RawApiKeyResponse>().await?;
             println!("Generated a new API key:\n");
@@ -3,8 +3,9 @@ use nebulous::models::{V1Meter, V1ResourceMetaRequest};
 use nebulous::resources::v1::containers::models::{
     RestartPolicy, V1ContainerRequest, V1ContainerResources, V1EnvVar,
 };
-use nebulous::resources::v1::secrets::models::V1SecretRequest;
+use nebulous::resources::v1::secrets::models::{V1SecretRequest, V1SecretsRequest};
 use nebulous::resources::v1::volumes::models::{V1VolumeConfig, V1VolumeDriver, V1VolumePath};
+use nebulous::validate::{validate_name, validate_secret_value, MAX_SECRET_VALUE_BYTES};
 use serde_json::Value;
 use std::collections::HashMap;
 use std::error::Error;
@@ -66,6 +67,11 @@ pub async fn create_container(
                 metric: command.meter_metric.clone().unwrap_or_default(),
                 currency: command.meter_currency.clone().unwrap_or_default(),
                 json_path: None,
+                dimensions: command
+                    .meter_dimension
+                    .clone()
+                    .map(|dims| dims.into_iter().collect()),
+                subject: None,
             }])
         } else {
             None
@@ -82,6 +88,7 @@ pub async fn create_container(
             args: None, // TODO
             accelerators: command.accelerators,
             platform: command.platform,
+            platforms: command.platforms,
             env: env,
             volumes: Some(volumes.unwrap().paths),
             metadata: Some(V1ResourceMetaRequest {
@@ -106,6 +113,12 @@ pub async fn create_container(
             proxy_port: command.proxy_port,
             authz: None,
             health_check: None,
+            bootstrap: None,
+            tailscale: None,
+            priority: command.priority,
+            preemptible: command.preemptible,
+            user: None,
+            webhook_url: command.webhook_url.clone(),
         }
     };
 
@@ -132,6 +145,10 @@ pub async fn create_container(
 pub async fn create_secret(
     command: crate::cli::SecretCommands, // define your CLI struct accordingly
 ) -> Result<(), Box<dyn std::error::Error>> {
+    if let Some(env_file) = &command.from_env_file {
+        return create_secrets_from_env_file(&command, env_file).await;
+    }
+
     println!("Creating secret...");
 
     // Build the metadata (reused whether file is provided or not)
@@ -145,7 +162,7 @@ pub async fn create_secret(
     let secret_request = if let Some(file) = command.file {
         // If the user provided a file, read *raw* contents as the secret value
         println!("Reading secret file: {}", file);
-        let file_content = std::fs::read_to_string(&file)?;
+        let file_content = read_secret_file(&file)?;
         println!("File content read");
 
         V1SecretRequest {
@@ -182,3 +199,260 @@ pub async fn create_secret(
         Err(format!("Failed to create secret: {}", error_text).into())
     }
 }
+
+/// Largest `.env` file read for `--from-env-file`
+const MAX_ENV_FILE_BYTES: u64 = 1024 * 1024;
+
+/// Reads a file that has to be UTF-8 text no larger than `max_bytes`.
+fn read_text_file(path: &str, max_bytes: u64) -> Result<String, Box<dyn Error>> {
+    let size = std::fs::metadata(path)?.len();
+    if size > max_bytes {
+        return Err(format!(
+            "{} is {} bytes, at most {} are allowed",
+            path, size, max_bytes
+        )
+        .into());
+    }
+    String::from_utf8(std::fs::read(path)?)
+        .map_err(|_| format!("{} is not valid UTF-8 text", path).into())
+}
+
+/// Reads a secret value from `path` as is.
+fn read_secret_file(path: &str) -> Result<String, Box<dyn Error>> {
+    read_text_file(path, MAX_SECRET_VALUE_BYTES as u64)
+}
+
+/// The `KEY=VALUE` lines of a `.env` file, taking values literally: nothing
+/// is expanded, so a `$` stays a `$`. Values may be wrapped in single or
+/// double quotes, and double quoted ones understand `\"`, `\\` and `\n`.
+/// Blank lines, `#` comments and an `export ` prefix are skipped.
+fn parse_env_file(content: &str) -> Result<Vec<(String, String)>, String> {
+    let mut variables: Vec<(String, String)> = Vec::new();
+    for (index, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let line = line.strip_prefix("export ").unwrap_or(line);
+        let Some((key, value)) = line.split_once('=') else {
+            return Err(format!("Line {}: expected KEY=VALUE", index + 1));
+        };
+        let key = key.trim();
+        let valid_key = key.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+            && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+        if !valid_key {
+            return Err(format!(
+                "Line {}: invalid variable name '{}'",
+                index + 1,
+                key
+            ));
+        }
+        let value =
+            parse_env_value(value.trim()).map_err(|e| format!("Line {}: {}", index + 1, e))?;
+        if variables.iter().any(|(k, _)| k == key) {
+            return Err(format!("Line {}: {} is set more than once", index + 1, key));
+        }
+        variables.push((key.to_string(), value));
+    }
+    Ok(variables)
+}
+
+fn parse_env_value(value: &str) -> Result<String, String> {
+    if let Some(rest) = value.strip_prefix('\'') {
+        return rest
+            .strip_suffix('\'')
+            .map(str::to_string)
+            .ok_or_else(|| "unterminated single quote".to_string());
+    }
+    if let Some(rest) = value.strip_prefix('"') {
+        let mut unquoted = String::new();
+        let mut chars = rest.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '"' if chars.as_str().is_empty() => return Ok(unquoted),
+                '"' => return Err("text after the closing double quote".to_string()),
+                '\\' => match chars.next() {
+                    Some('n') => unquoted.push('\n'),
+                    Some(escaped @ ('"' | '\\')) => unquoted.push(escaped),
+                    Some(other) => {
+                        unquoted.push('\\');
+                        unquoted.push(other);
+                    }
+                    None => break,
+                },
+                c => unquoted.push(c),
+            }
+        }
+        return Err("unterminated double quote".to_string());
+    }
+    // Unquoted values end at a comment
+    let value = match value.find(" #") {
+        Some(comment) => &value[..comment],
+        None => value,
+    };
+    Ok(value.trim_end().to_string())
+}
+
+/// A secret request for every variable in the `.env` file at `path`, named
+/// after `prefix` and the variable.
+fn read_env_file_secrets(
+    path: &str,
+    prefix: &str,
+    namespace: Option<&str>,
+    expires_at: Option<i32>,
+) -> Result<Vec<V1SecretRequest>, Box<dyn Error>> {
+    let content = read_text_file(path, MAX_ENV_FILE_BYTES)?;
+
+    let mut secrets = Vec::new();
+    for (key, value) in parse_env_file(&content).map_err(|e| format!("{}: {}", path, e))? {
+        let name = format!("{}-{}", prefix, key.to_lowercase().replace('_', "-"));
+        validate_name(&name).map_err(|e| format!("Variable {}: {}", key, e))?;
+        validate_secret_value(&value).map_err(|e| format!("Variable {}: {}", key, e))?;
+        secrets.push(V1SecretRequest {
+            metadata: V1ResourceMetaRequest {
+                name: Some(name),
+                namespace: namespace.map(str::to_string),
+                ..Default::default()
+            },
+            value,
+            expires_at,
+        });
+    }
+    if secrets.is_empty() {
+        return Err(format!("No variables found in {}", path).into());
+    }
+    Ok(secrets)
+}
+
+async fn create_secrets_from_env_file(
+    command: &crate::cli::SecretCommands,
+    env_file: &str,
+) -> Result<(), Box<dyn Error>> {
+    println!("Reading env file: {}", env_file);
+    let secrets = read_env_file_secrets(
+        env_file,
+        &command.name,
+        command.namespace.as_deref(),
+        command.expires_at,
+    )?;
+    println!("Creating {} secrets...", secrets.len());
+
+    let response = server_request_with_payload(
+        "/v1/secrets/bulk",
+        reqwest::Method::POST,
+        Some(V1SecretsRequest { secrets }),
+    )
+    .await?;
+
+    if response.status().is_success() {
+        let created: serde_json::Value = response.json().await?;
+        println!("Secrets created successfully!");
+        for secret in created["secrets"].as_array().into_iter().flatten() {
+            println!("  {}", secret["metadata"]["name"]);
+        }
+        Ok(())
+    } else {
+        let error_text = response.text().await?;
+        Err(format!("Failed to create secrets: {}", error_text).into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    fn write_file(dir: &tempfile::TempDir, name: &str, content: &[u8]) -> String {
+        let path = dir.path().join(name);
+        std::fs::File::create(&path)
+            .unwrap()
+            .write_all(content)
+            .unwrap();
+        path.to_string_lossy().to_string()
+    }
+
+    #[test]
+    fn test_secret_file_is_read_verbatim() {
+        // Arrange
+        let dir = tempfile::tempdir().unwrap();
+        let key = "-----BEGIN KEY-----\nabc$def \"quoted\"\n-----END KEY-----\n";
+        let path = write_file(&dir, "key.pem", key.as_bytes());
+
+        // Act
+        let value = read_secret_file(&path).unwrap();
+
+        // Assert
+        assert_eq!(value, key);
+
+        let binary = write_file(&dir, "key.der", &[0x30, 0x82, 0xff, 0xfe]);
+        assert!(read_secret_file(&binary)
+            .unwrap_err()
+            .to_string()
+            .contains("UTF-8"));
+        let large = write_file(&dir, "big", &vec![b'a'; MAX_SECRET_VALUE_BYTES + 1]);
+        assert!(read_secret_file(&large).is_err());
+    }
+
+    #[test]
+    fn test_env_file_becomes_one_secret_per_variable() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = write_file(
+            &dir,
+            ".env",
+            b"# database\nDB_PASSWORD=s3cr3t\nAPI_TOKEN=\"tok en\"\n\n",
+        );
+
+        let secrets = read_env_file_secrets(&path, "app", Some("team"), Some(100)).unwrap();
+
+        let names: Vec<_> = secrets
+            .iter()
+            .map(|s| s.metadata.name.clone().unwrap())
+            .collect();
+        assert_eq!(names, vec!["app-db-password", "app-api-token"]);
+        assert_eq!(secrets[0].value, "s3cr3t");
+        assert_eq!(secrets[1].value, "tok en");
+        assert!(secrets
+            .iter()
+            .all(|s| s.metadata.namespace.as_deref() == Some("team") && s.expires_at == Some(100)));
+
+        let empty = write_file(&dir, "empty.env", b"# nothing\n");
+        assert!(read_env_file_secrets(&empty, "app", None, None).is_err());
+        let bad_name = write_file(&dir, "bad.env", b"WEIRD.KEY=1\n");
+        assert!(read_env_file_secrets(&bad_name, "app", None, None).is_err());
+    }
+
+    #[test]
+    fn test_env_file_values_are_literal() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = write_file(
+            &dir,
+            ".env",
+            concat!(
+                "HOME_DIR=/home/app\n",
+                "PASSWORD=pa$$word$HOME_DIR\n",
+                "export SINGLE='${HOME_DIR} stays'\n",
+                "DOUBLE=\"say \\\"$HOME_DIR\\\"\\n\"\n",
+                "COMMENTED=value # note\n",
+            )
+            .as_bytes(),
+        );
+
+        let secrets = read_env_file_secrets(&path, "app", None, None).unwrap();
+
+        let values: Vec<_> = secrets.iter().map(|s| s.value.as_str()).collect();
+        assert_eq!(
+            values,
+            vec![
+                "/home/app",
+                "pa$$word$HOME_DIR",
+                "${HOME_DIR} stays",
+                "say \"$HOME_DIR\"\n",
+                "value",
+            ]
+        );
+        assert!(parse_env_file("A='open").is_err());
+        assert!(parse_env_file("A=\"open").is_err());
+        assert!(parse_env_file("A=1\nA=2").is_err());
+        assert!(parse_env_file("just text").is_err());
+    }
+}
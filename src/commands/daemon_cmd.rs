@@ -1,6 +1,24 @@
+use nebulous::create_app_state;
+use nebulous::resources::v1::containers::controller::ContainerController;
 use std::error::Error;
 use std::fs::OpenOptions;
 use std::process::{Command, Stdio};
+use std::sync::Arc;
+
+/// Reconciles every active container once and exits, for running the
+/// controller from cron or a Kubernetes job. Fails when any reconcile did.
+pub async fn execute_once() -> Result<(), Box<dyn Error>> {
+    let app_state = create_app_state().await?;
+    let controller = ContainerController::new(Arc::new(app_state));
+
+    let summary = controller.reconcile_once().await;
+    println!("Reconcile finished: {}", summary);
+
+    if !summary.failing.is_empty() {
+        return Err(format!("{} container(s) failed to reconcile", summary.failing.len()).into());
+    }
+    Ok(())
+}
 
 pub async fn execute_daemon(host: &str, port: u16, background: bool) -> Result<(), Box<dyn Error>> {
     if background {
@@ -1,7 +1,7 @@
 use chrono::{DateTime, Utc};
 use serde::Serialize;
 
-use crate::commands::request::server_request;
+use crate::commands::request::{prepare_request, server_request};
 use nebulous::config::ClientConfig;
 use nebulous::resources::v1::containers::models::{V1Container, V1Containers};
 use serde_json::Value;
@@ -220,55 +220,94 @@ pub async fn get_secrets(id: Option<String>) -> Result<(), Box<dyn Error>> {
 }
 
 pub async fn get_accelerators(platform: Option<String>) -> Result<(), Box<dyn Error>> {
-    use nebulous::accelerator::aws::AwsProvider;
-    use nebulous::accelerator::base::{AcceleratorProvider, Config};
-    use nebulous::accelerator::runpod::RunPodProvider;
+    use nebulous::accelerator::base::Config;
+    use nebulous::resources::v1::containers::models::V1AcceleratorOfferings;
+    use nebulous::resources::v1::containers::pricing::{
+        accelerator_catalog, catalog_provider, CATALOG_PLATFORMS,
+    };
     use prettytable::{format, Cell, Row, Table};
 
-    // Load the default accelerator configuration
-    let config = Config::default();
-
-    // Create a table to display the accelerators
-    let mut table = Table::new();
-
-    // Add table headers
-    let mut headers = vec![Cell::new("NAME"), Cell::new("MEMORY (GB)")];
-
-    // If a platform is specified, add a column for platform-specific names
-    if platform.is_some() {
-        headers.push(Cell::new("PLATFORM NAME"));
+    if let Some(unknown) = platform
+        .as_deref()
+        .filter(|p| catalog_provider(p).is_none())
+    {
+        eprintln!(
+            "Unknown platform: {}. Supported platforms are {}.",
+            unknown,
+            CATALOG_PLATFORMS
+                .iter()
+                .map(|p| format!("'{}'", p))
+                .collect::<Vec<_>>()
+                .join(" and ")
+        );
+        return Ok(());
     }
 
-    table.add_row(Row::new(headers));
-
-    // Get the appropriate provider based on the platform parameter
-    let provider: Option<Box<dyn AcceleratorProvider>> = match platform.as_deref() {
-        Some("aws") => Some(Box::new(AwsProvider::new())),
-        Some("runpod") => Some(Box::new(RunPodProvider::new())),
-        Some(unknown) => {
+    // Prices come from the server; when it can't be reached the catalog is
+    // listed unpriced
+    let url = match platform.as_deref() {
+        Some(platform) => format!("/v1/accelerators?platform={}", platform),
+        None => "/v1/accelerators".to_string(),
+    };
+    let response: Result<reqwest::Response, Box<dyn Error>> =
+        match prepare_request(&url, reqwest::Method::GET) {
+            Ok(request) => request.send().await.map_err(Into::into),
+            Err(e) => Err(e),
+        };
+    let accelerators = match response {
+        Ok(response) if response.status().is_success() => {
+            response
+                .json::<V1AcceleratorOfferings>()
+                .await?
+                .accelerators
+        }
+        // It answered but refused, e.g. a revoked API key, which listing
+        // without prices would hide
+        Ok(response) => {
+            return Err(format!("Failed to list accelerators: {}", response.status()).into());
+        }
+        Err(e) => {
             eprintln!(
-                "Unknown platform: {}. Supported platforms are 'aws' and 'runpod'.",
-                unknown
+                "Could not fetch pricing from the server ({}), listing without it",
+                e
             );
-            return Ok(());
+            let config = Config::default();
+            CATALOG_PLATFORMS
+                .iter()
+                .filter(|p| platform.is_none() || platform.as_deref() == Some(**p))
+                .filter_map(|p| catalog_provider(p))
+                .flat_map(|provider| accelerator_catalog(&config, provider.as_ref(), &[]))
+                .collect()
         }
-        None => None,
     };
 
-    // Add rows for each accelerator
-    for acc in &config.accelerators.supported {
-        let mut row = vec![Cell::new(&acc.name), Cell::new(&acc.memory.to_string())];
-
-        // If a platform is specified, add the platform-specific name
-        if let Some(ref provider) = provider {
-            let platform_name = provider
-                .get_platform_name(&acc.name)
-                .map(|s| s.as_str())
-                .unwrap_or("N/A");
-            row.push(Cell::new(platform_name));
-        }
+    let mut table = Table::new();
+    table.add_row(Row::new(vec![
+        Cell::new("NAME"),
+        Cell::new("MEMORY (GB)"),
+        Cell::new("PLATFORM"),
+        Cell::new("PLATFORM NAME"),
+        Cell::new("PRICE/HR"),
+        Cell::new("SPOT/HR"),
+        Cell::new("STOCK"),
+    ]));
 
-        table.add_row(Row::new(row));
+    let price = |p: Option<f64>| p.map_or("N/A".to_string(), |p| format!("${:.2}", p));
+    for acc in &accelerators {
+        let stock = match (&acc.stock_status, acc.available) {
+            (Some(status), _) => status.clone(),
+            (None, Some(false)) => "Unavailable".to_string(),
+            (None, _) => "N/A".to_string(),
+        };
+        table.add_row(Row::new(vec![
+            Cell::new(&acc.name),
+            Cell::new(&acc.memory.to_string()),
+            Cell::new(&acc.platform),
+            Cell::new(&acc.platform_name),
+            Cell::new(&price(acc.price_per_hour)),
+            Cell::new(&price(acc.spot_price_per_hour)),
+            Cell::new(&stock),
+        ]));
     }
 
     table.set_format(*format::consts::FORMAT_CLEAN);
@@ -352,6 +391,7 @@ pub async fn get_processors(
         prettytable::Cell::new("NAMESPACE"),
         prettytable::Cell::new("STREAM"),
         prettytable::Cell::new("REPLICAS (MIN/MAX)"),
+        prettytable::Cell::new("READY"),
         prettytable::Cell::new("STATUS"),
         prettytable::Cell::new("PRESSURE"),
         prettytable::Cell::new("CREATED"),
@@ -399,6 +439,19 @@ pub async fn get_processors(
                 (None, None) => "N/A".to_string(),
             };
 
+            // Ready out of warm replicas
+            let ready_str = match (
+                status_obj
+                    .and_then(|s| s.get("ready_replicas"))
+                    .and_then(Value::as_i64),
+                status_obj
+                    .and_then(|s| s.get("warm_replicas"))
+                    .and_then(Value::as_i64),
+            ) {
+                (Some(ready), Some(warm)) => format!("{}/{}", ready, warm),
+                _ => "N/A".to_string(),
+            };
+
             let status = status_obj
                 .and_then(|s| s.get("status"))
                 .and_then(Value::as_str)
@@ -439,6 +492,7 @@ pub async fn get_processors(
                 prettytable::Cell::new(namespace),
                 prettytable::Cell::new(stream),
                 prettytable::Cell::new(&replicas_str),
+                prettytable::Cell::new(&ready_str),
                 prettytable::Cell::new(status),
                 prettytable::Cell::new(&pressure),
                 prettytable::Cell::new(&created),
@@ -1,4 +1,6 @@
 use nebulous::config::ClientConfig;
+use nebulous::resources::v1::containers::bootstrap;
+use nebulous::resources::v1::containers::models::V1ContainerBootstrap;
 use std::error::Error as StdError;
 use std::io::Write;
 
@@ -113,18 +115,18 @@ pub async fn fetch_container_logs(
         Ok("Log streaming finished.".to_string())
     } else {
         // Call the REST API endpoint
-        // Step 1: Fetch container ID by calling your server's HTTP GET /v1/containers/:namespace/:name
-        let container_id = fetch_container_id_from_api(&namespace, &name).await?;
+        // Step 1: Fetch the container by calling your server's HTTP GET /v1/containers/:namespace/:name
+        let container = fetch_container_from_api(&namespace, &name).await?;
 
         // Step 2: Run the local SSH command to stream log content.
         //         This uses the streaming `stream_ssh_command_ts`.
         let cmd = vec![
             "cat".to_string(),
-            "$HOME/.logs/nebu_container.log".to_string(),
+            bootstrap::log_file(container.bootstrap.as_ref()),
         ];
 
         nebulous::ssh::exec::stream_ssh_command_ts(
-            &format!("container-{}", container_id),
+            &format!("container-{}", container.metadata.id),
             cmd,
             false,        // Not interactive for cat
             false,        // No TTY needed for cat
@@ -139,10 +141,10 @@ pub async fn fetch_container_logs(
 
 /// Helper function: calls GET /v1/containers/<namespace>/<name>
 /// and returns the container's `.metadata.id`.
-async fn fetch_container_id_from_api(
+async fn fetch_container_from_api(
     namespace: &str,
     name: &str,
-) -> Result<String, Box<dyn StdError>> {
+) -> Result<V1Container, Box<dyn StdError>> {
     let config = ClientConfig::read()?;
     let current_server = config.get_current_server_config().unwrap();
     let server = current_server.server.as_ref().unwrap();
@@ -163,14 +165,16 @@ async fn fetch_container_id_from_api(
         .json::<V1Container>()
         .await?;
 
-    Ok(container.metadata.id)
+    Ok(container)
 }
 
 /// Minimal struct matching the server's "Container" JSON shape.
-/// We only need the `metadata.id` field for this flow.
+/// We only need the `metadata.id` field and where the logs are written.
 #[derive(Deserialize)]
 struct V1Container {
     metadata: V1ResourceMeta,
+    #[serde(default)]
+    bootstrap: Option<V1ContainerBootstrap>,
 }
 
 /// Minimal struct for container's metadata (includes ID).
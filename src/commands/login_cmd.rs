@@ -9,6 +9,7 @@ pub async fn execute(
     nebu_url: String,
     auth: Option<String>,
     hub: Option<String>,
+    profile: Option<String>,
 ) -> Result<(), Box<dyn Error>> {
     if auth.is_none() ^ hub.is_none() {
         eprintln!("Either auth or hub URL provided. Please provide both or neither.");
@@ -35,8 +36,8 @@ pub async fn execute(
         io::stdout().flush()?;
         let api_key = rpassword::read_password()?;
 
-        client_config.add_server(ClientServerConfig {
-            name: "cloud".to_string(),
+        client_config.update_server(ClientServerConfig {
+            name: profile.unwrap_or_else(|| "cloud".to_string()),
             server: Some(nebu_url),
             api_key: Some(api_key),
             auth_server: Some(auth_url),
@@ -62,8 +63,8 @@ When you're running nebulous on Kubernetes, use:
         io::stdout().flush()?;
         let api_key = rpassword::read_password()?;
 
-        client_config.add_server(ClientServerConfig {
-            name: "nebu".to_string(),
+        client_config.update_server(ClientServerConfig {
+            name: profile.unwrap_or_else(|| "nebu".to_string()),
             server: Some(nebu_url),
             api_key: Some(api_key),
             auth_server: None,
@@ -76,3 +77,20 @@ When you're running nebulous on Kubernetes, use:
     println!("\nLogin successful!");
     Ok(())
 }
+
+/// Removes `profile`, or the current profile, from the config.
+pub fn logout(profile: Option<String>) -> Result<(), Box<dyn Error>> {
+    let mut client_config = ClientConfig::read()?;
+
+    let Some(name) = profile.or_else(|| client_config.current_server.clone()) else {
+        return Err("Not logged in to any profile".into());
+    };
+    if !client_config.contains_server(&name) {
+        return Err(format!("Profile '{}' not found in configuration", name).into());
+    }
+    client_config.drop_server(&name);
+    client_config.write()?;
+
+    println!("Logged out of '{}'", name);
+    Ok(())
+}
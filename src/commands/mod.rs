@@ -8,6 +8,7 @@ pub mod get_cmd;
 pub mod log_cmd;
 pub mod login_cmd;
 pub mod proxy_cmd;
+pub mod read_cmd;
 mod request;
 pub mod send_cmd;
 pub mod serve_cmd;
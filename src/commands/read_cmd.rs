@@ -0,0 +1,307 @@
+// src/commands/read_cmd.rs
+//
+// `nebu read processor`: prints the messages on a processor's stream,
+// peeking at them or reading as a consumer group, optionally following it
+// and filtering with a JSONPath, for debugging what producers send.
+
+use crate::cli::ReadProcessorCommands;
+use nebulous::client::client::NebulousClient;
+use nebulous::models::V1StreamMessage;
+use nebulous::resources::v1::processors::models::{V1PeekStreamParams, V1ReadStreamRequest};
+use serde_json::Value;
+use std::error::Error;
+use std::io::Write;
+
+pub async fn read_processor(args: &ReadProcessorCommands) -> Result<(), Box<dyn Error>> {
+    let client = NebulousClient::new_from_config()?;
+    let stdout = std::io::stdout();
+    read_messages(&client, args, &mut stdout.lock()).await?;
+    Ok(())
+}
+
+/// The namespace and name of the processor `args` reads, `-` standing for
+/// the user's own namespace when there's none
+pub fn processor_ref(args: &ReadProcessorCommands) -> (String, String) {
+    match args.name.split_once('/') {
+        Some((namespace, name)) => (namespace.to_string(), name.to_string()),
+        None => (
+            args.namespace.clone().unwrap_or_else(|| "-".to_string()),
+            args.name.clone(),
+        ),
+    }
+}
+
+/// Whether `filter` selects anything in `message` other than null or false.
+/// Every message matches without a filter.
+pub fn matches_filter(filter: Option<&str>, message: &Value) -> Result<bool, Box<dyn Error>> {
+    let Some(filter) = filter else {
+        return Ok(true);
+    };
+    let selected = jsonpath_lib::select(message, filter)
+        .map_err(|e| format!("Invalid filter '{}': {:?}", filter, e))?;
+    Ok(selected
+        .iter()
+        .any(|value| !matches!(value, Value::Null | Value::Bool(false))))
+}
+
+/// A message as printed: the whole message as one line of JSON, or its
+/// entry id followed by its content
+pub fn format_message(message: &V1StreamMessage, output: &str) -> Result<String, Box<dyn Error>> {
+    if output == "json" {
+        return Ok(serde_json::to_string(message)?);
+    }
+    Ok(format!(
+        "{}\t{}",
+        message.entry_id.as_deref().unwrap_or("-"),
+        serde_json::to_string(&message.content)?
+    ))
+}
+
+/// Reads batches of messages and writes those matching the filter to `out`,
+/// once or, with `follow`, until interrupted. Returns how many were written.
+/// Without a group the stream is peeked at from its start, each read picking
+/// up after the last message seen. The API keys messages carry are dropped
+/// before they're filtered or shown.
+pub async fn read_messages<W: Write>(
+    client: &NebulousClient,
+    args: &ReadProcessorCommands,
+    out: &mut W,
+) -> Result<usize, Box<dyn Error>> {
+    let (namespace, name) = processor_ref(args);
+    let filter = args.filter.as_deref();
+    // Catch a bad filter before anything is read
+    matches_filter(filter, &Value::Null)?;
+
+    let mut after = None;
+    let mut written = 0;
+    loop {
+        let messages = match &args.group {
+            Some(group) => {
+                let read_request = V1ReadStreamRequest {
+                    consumer_group: group.clone(),
+                    max_records: args.max_records,
+                    wait_time_ms: args.wait_ms,
+                    auto_ack: args.ack,
+                };
+                client
+                    .read_processor_stream(&name, &namespace, &read_request)
+                    .await?
+            }
+            None => {
+                let params = V1PeekStreamParams {
+                    after: after.clone(),
+                    max_records: args.max_records,
+                    wait_time_ms: args.wait_ms,
+                };
+                client
+                    .peek_processor_stream(&name, &namespace, &params)
+                    .await?
+            }
+        };
+        if let Some(last) = messages.last() {
+            after = last.entry_id.clone().or(after);
+        }
+        for message in messages {
+            let message = V1StreamMessage {
+                api_key: None,
+                ..message
+            };
+            if matches_filter(filter, &serde_json::to_value(&message)?)? {
+                writeln!(out, "{}", format_message(&message, &args.output)?)?;
+                written += 1;
+            }
+        }
+        out.flush()?;
+
+        if !args.follow {
+            return Ok(written);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::{Cli, Commands, ReadCommands};
+    use axum::extract::{Path, Query, State};
+    use axum::http::StatusCode;
+    use axum::routing::{get, post};
+    use axum::{Json, Router};
+    use clap::Parser;
+    use serde_json::json;
+    use std::sync::{Arc, Mutex};
+
+    type Reads = Arc<Mutex<Vec<(String, String, V1ReadStreamRequest)>>>;
+
+    fn message(entry_id: &str, content: Value) -> V1StreamMessage {
+        V1StreamMessage {
+            kind: "StreamMessage".to_string(),
+            id: format!("msg-{}", entry_id),
+            content,
+            created_at: 1_700_000_000,
+            return_stream: None,
+            user_id: None,
+            orgs: None,
+            handle: None,
+            adapter: None,
+            api_key: None,
+            entry_id: Some(entry_id.to_string()),
+        }
+    }
+
+    fn parse(args: &[&str]) -> ReadProcessorCommands {
+        let cli = Cli::try_parse_from(args).unwrap();
+        match cli.command {
+            Commands::Read {
+                command: ReadCommands::Processors { command },
+            } => command,
+            _ => panic!("expected the read processor command"),
+        }
+    }
+
+    /// Serves the stream read endpoint, recording each read
+    async fn stream_server(messages: Vec<V1StreamMessage>) -> (String, Reads) {
+        let reads: Reads = Arc::default();
+        let app = Router::new()
+            .route(
+                "/v1/processors/:namespace/:name/stream",
+                post(
+                    |State((reads, messages)): State<(Reads, Vec<V1StreamMessage>)>,
+                     Path((namespace, name)): Path<(String, String)>,
+                     Json(request): Json<V1ReadStreamRequest>| async move {
+                        reads.lock().unwrap().push((namespace, name, request));
+                        Json(messages)
+                    },
+                ),
+            )
+            .with_state((reads.clone(), messages));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        (format!("http://{}", addr), reads)
+    }
+
+    #[tokio::test]
+    async fn test_read_processor_parses_and_reads_the_stream() {
+        // Arrange
+        let args = parse(&[
+            "nebu",
+            "read",
+            "processor",
+            "team/echo",
+            "--group",
+            "debug",
+            "--filter",
+            "$.content.error",
+            "--output",
+            "json",
+        ]);
+        let (server, reads) = stream_server(vec![
+            message("1-0", json!({"prompt": "hi"})),
+            V1StreamMessage {
+                api_key: Some("agent-key".to_string()),
+                ..message("2-0", json!({"error": "CUDA out of memory"}))
+            },
+        ])
+        .await;
+        let client = NebulousClient::new(server, "key".to_string());
+        let mut out = Vec::new();
+
+        // Act
+        let written = read_messages(&client, &args, &mut out).await.unwrap();
+
+        // Assert
+        assert_eq!(written, 1);
+        let printed: V1StreamMessage =
+            serde_json::from_str(String::from_utf8(out).unwrap().trim()).unwrap();
+        assert_eq!(printed.entry_id.as_deref(), Some("2-0"));
+        assert_eq!(printed.api_key, None);
+        let reads = reads.lock().unwrap();
+        assert_eq!(reads.len(), 1);
+        let (namespace, name, request) = &reads[0];
+        assert_eq!((namespace.as_str(), name.as_str()), ("team", "echo"));
+        assert_eq!(request.consumer_group, "debug");
+        assert!(!request.auto_ack);
+    }
+
+    #[tokio::test]
+    async fn test_follow_peeks_after_the_last_message() {
+        let args = parse(&["nebu", "read", "proc", "team/echo", "--follow"]);
+        let peeks: Arc<Mutex<Vec<Option<String>>>> = Arc::default();
+        let app = Router::new()
+            .route(
+                "/v1/processors/:namespace/:name/stream/entries",
+                get(
+                    |State(peeks): State<Arc<Mutex<Vec<Option<String>>>>>,
+                     Query(params): Query<V1PeekStreamParams>| async move {
+                        let mut peeks = peeks.lock().unwrap();
+                        peeks.push(params.after);
+                        match peeks.len() {
+                            1 => Ok(Json(vec![
+                                message("1-0", json!({"n": 1})),
+                                message("2-0", json!({"n": 2})),
+                            ])),
+                            2 => Ok(Json(Vec::new())),
+                            3 => Ok(Json(vec![message("3-0", json!({"n": 3}))])),
+                            _ => Err(StatusCode::SERVICE_UNAVAILABLE),
+                        }
+                    },
+                ),
+            )
+            .with_state(peeks.clone());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        let client = NebulousClient::new(format!("http://{}", addr), "key".to_string());
+        let mut out = Vec::new();
+
+        let result = read_messages(&client, &args, &mut out).await;
+
+        assert!(result.is_err());
+        assert_eq!(String::from_utf8(out).unwrap().lines().count(), 3);
+        assert_eq!(
+            *peeks.lock().unwrap(),
+            vec![
+                None,
+                Some("2-0".to_string()),
+                Some("2-0".to_string()),
+                Some("3-0".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn test_read_processor_defaults() {
+        let args = parse(&["nebu", "read", "proc", "echo", "-n", "team"]);
+
+        assert_eq!(
+            processor_ref(&args),
+            ("team".to_string(), "echo".to_string())
+        );
+        assert_eq!(args.group, None);
+        assert_eq!(args.output, "text");
+        assert!(!args.follow);
+        assert_eq!(
+            processor_ref(&parse(&["nebu", "read", "proc", "echo"])).0,
+            "-"
+        );
+        assert!(Cli::try_parse_from(["nebu", "read", "proc", "echo", "-o", "yaml"]).is_err());
+        assert!(Cli::try_parse_from(["nebu", "read", "proc", "echo", "--ack"]).is_err());
+    }
+
+    #[test]
+    fn test_filter_and_text_output() {
+        let failed = serde_json::to_value(message("2-0", json!({"error": "oom"}))).unwrap();
+        let ok = serde_json::to_value(message("3-0", json!({"error": null}))).unwrap();
+
+        assert!(matches_filter(None, &ok).unwrap());
+        assert!(matches_filter(Some("$.content.error"), &failed).unwrap());
+        assert!(!matches_filter(Some("$.content.error"), &ok).unwrap());
+        assert!(matches_filter(Some("$.content[?("), &ok).is_err());
+
+        assert_eq!(
+            format_message(&message("2-0", json!({"error": "oom"})), "text").unwrap(),
+            "2-0\t{\"error\":\"oom\"}"
+        );
+    }
+}
@@ -53,6 +53,8 @@ pub async fn send_messages(args: &crate::cli::SendMessageCommands) -> Result<(),
         wait: if args.wait { Some(true) } else { None },
         stream: None,
         user_key: None,
+        agent_key_duration: None,
+        timeout_ms: None,
     };
     debug!("Payload: {:?}", payload);
 
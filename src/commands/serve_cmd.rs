@@ -1,5 +1,6 @@
 // src/commands/serve.rs
 
+use nebulous::config::SERVER_CONFIG;
 use nebulous::create_app;
 use nebulous::create_app_state;
 use nebulous::proxy::server::start_proxy;
@@ -8,24 +9,34 @@ use nebulous::resources::v1::processors::controller::ProcessorController;
 use std::error::Error;
 
 pub async fn execute(
-    host: String,
+    host: Option<String>,
     port: u16,
     internal_auth: bool,
     auth_port: u16,
+    auth_host: Option<String>,
 ) -> Result<(), Box<dyn Error>> {
+    let auth_host = auth_host.or_else(|| SERVER_CONFIG.auth_bind_host.clone());
+    nebulous::auth::server::main::auth_bind_address(auth_host.as_deref(), auth_port)?;
+
     let app_state = create_app_state().await?;
     let app = create_app(app_state.clone()).await;
 
     println!("Starting container controller");
     let controller = ContainerController::new(std::sync::Arc::new(app_state.clone()));
     controller.spawn_reconciler();
+    controller.spawn_purger();
+    controller.spawn_volume_gc();
     println!("Container controller started");
 
     println!("Starting processor controller");
     let processor_controller = ProcessorController::new(std::sync::Arc::new(app_state.clone()));
     processor_controller.spawn_reconciler();
+    processor_controller.spawn_purger();
+    processor_controller.spawn_reclaimer();
     println!("Processor controller started");
 
+    nebulous::auth::api::spawn_expired_key_sweeper(app_state.db_pool.clone());
+
     println!("Starting proxy server");
     tokio::spawn({
         let proxy_state = app_state.clone();
@@ -42,8 +53,12 @@ pub async fn execute(
         tokio::spawn({
             let auth_state = app_state.clone();
             async move {
-                if let Err(e) =
-                    nebulous::auth::server::main::start_auth_server(auth_state, auth_port).await
+                if let Err(e) = nebulous::auth::server::main::start_auth_server(
+                    auth_state,
+                    auth_host.as_deref(),
+                    auth_port,
+                )
+                .await
                 {
                     eprintln!("Error in auth server: {}", e);
                 }
@@ -56,10 +71,28 @@ pub async fn execute(
 
     // Run it
     println!("Starting main server");
+    let host = host
+        .or_else(|| SERVER_CONFIG.bind_host.clone())
+        .unwrap_or_else(|| "127.0.0.1".to_string());
     let addr = format!("{}:{}", host, port);
-    let listener = tokio::net::TcpListener::bind(&addr).await?;
-    println!("Server running at http://{}", addr);
-    axum::serve(listener, app).await?;
+    let listener = std::net::TcpListener::bind(&addr)?;
+    let tls = nebulous::tls::server_rustls_config().await?;
+
+    if let (Some(_), Some(redirect_port)) = (&tls, SERVER_CONFIG.tls_redirect_port) {
+        let redirect_addr = format!("{}:{}", host, redirect_port);
+        let redirect_listener = std::net::TcpListener::bind(&redirect_addr)?;
+        println!("Redirecting http://{} to HTTPS", redirect_addr);
+        tokio::spawn(async move {
+            let app = nebulous::tls::redirect_app(port);
+            if let Err(e) = nebulous::tls::serve(redirect_listener, app, None).await {
+                eprintln!("Error in HTTPS redirect server: {}", e);
+            }
+        });
+    }
+
+    let scheme = if tls.is_some() { "https" } else { "http" };
+    println!("Server running at {}://{}", scheme, addr);
+    nebulous::tls::serve(listener, app, tls).await?;
 
     Ok(())
 }
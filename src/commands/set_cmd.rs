@@ -6,15 +6,8 @@ pub async fn set_context(server_name: &str) -> Result<(), Box<dyn Error>> {
     // Read the current config
     let mut config = ClientConfig::read()?;
 
-    // Check if the server exists
-    let server_exists = config.contains_server(server_name);
-
-    if !server_exists {
-        return Err(format!("Server '{}' not found in configuration", server_name).into());
-    }
-
-    // Update the current_server field
-    config.current_server = Some(server_name.to_string());
+    // Make it the current server, if it exists
+    config.switch_server(server_name)?;
 
     // Write the updated config back to disk
     config.write()?;
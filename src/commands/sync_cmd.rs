@@ -11,6 +11,7 @@ use std::error::Error;
 //   - source: "/path/to/local/file.txt"
 //     dest: "s3://your-bucket/file.txt"
 
+#[allow(clippy::too_many_arguments)]
 pub async fn execute_sync(
     config_path: String,
     interval_seconds: u64,
@@ -19,6 +20,7 @@ pub async fn execute_sync(
     background: bool,
     block_once: bool,
     config_from_env: bool,
+    verify: bool,
 ) -> Result<(), Box<dyn Error>> {
     // If config_from_env is true, attempt to read from NEBU_SYNC_CONFIG
     // and write the contents to the config_path file.
@@ -57,6 +59,10 @@ pub async fn execute_sync(
     // Keep the temp file alive for the duration of the function
     let _rclone_config = rclone::setup_rclone_config_from_env()?;
 
+    if verify {
+        return rclone::execute_verify(&config_path).await;
+    }
+
     // Create symlinks before starting any sync operations
     if let Err(e) = rclone::create_symlinks_from_config(&config_path) {
         println!("Warning: Failed to create symlinks: {}", e);
@@ -189,72 +195,188 @@ pub async fn execute_sync(
     }
 }
 
-/// Continuously checks for differences between two paths using `rsync` in "check" mode (\
-/// --dry-run + --itemize-changes). It will keep looping until no differences are found.
+/// How a wait for syncs to settle ended
+#[derive(Debug, PartialEq)]
+pub enum WaitOutcome {
+    Synced,
+    TimedOut,
+}
+
+/// Continuously checks for differences between the source and destination of
+/// every path in the sync config, until no differences are found.
 ///
-/// * `source` - The source path (e.g., "/path/to/source").
-/// * `dest` - The destination path (e.g., "/path/to/destination").
+/// * `config_path` - Path to the YAML sync configuration.
 /// * `poll_interval` - How long to wait (in seconds) between checks.
-///
-/// This function returns once differences are finally zero, or if it hits
-/// an optional maximum iteration limit (if you implement one).
-#[allow(dead_code)]
+/// * `timeout_seconds` - Give up after this many seconds. Without it, waits forever.
+/// * `proceed_on_timeout` - Return successfully instead of an error on timeout.
 pub async fn execute_wait(
     config_path: &str,
     poll_interval: u64,
+    timeout_seconds: Option<u64>,
+    proceed_on_timeout: bool,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    use nebulous::volumes::rclone::{check_paths, VolumeConfig};
-
     let _rclone_config = rclone::setup_rclone_config_from_env()?;
 
-    loop {
-        // Load the config (re-reads each loop in case it changes)
-        let config = match VolumeConfig::read_from_file(config_path) {
-            Ok(cfg) => cfg,
-            Err(e) => {
-                eprintln!("Failed to read config file {}: {}", config_path, e);
-                // If you need to bail out when config is missing, return Err here.
-                // Otherwise, just wait and retry.
-                tokio::time::sleep(std::time::Duration::from_secs(poll_interval)).await;
-                continue;
-            }
-        };
+    let outcome = wait_until(
+        || paths_in_sync(config_path, poll_interval),
+        std::time::Duration::from_secs(poll_interval),
+        timeout_seconds.map(std::time::Duration::from_secs),
+    )
+    .await?;
 
-        if config.paths.is_empty() {
-            println!("No paths found in {}. Nothing to check.", config_path);
-            return Ok(());
+    if outcome == WaitOutcome::TimedOut {
+        let message = format!(
+            "Timed out after {}s waiting for {} to sync",
+            timeout_seconds.unwrap_or_default(),
+            config_path
+        );
+        if proceed_on_timeout {
+            eprintln!("[WARN] {}; proceeding anyway", message);
+        } else {
+            eprintln!("[ERROR] {}", message);
+            return Err(message.into());
         }
+    }
+
+    Ok(())
+}
 
-        let mut all_clean = true;
+/// Checks every path in the sync config once, returning true when all are in sync.
+async fn paths_in_sync(
+    config_path: &str,
+    poll_interval: u64,
+) -> Result<bool, Box<dyn std::error::Error>> {
+    use nebulous::volumes::rclone::{check_paths, VolumeConfig};
 
-        // Compare each source/dest pair
-        for path in &config.paths {
-            // Now check_paths returns `bool`:
-            let in_sync = check_paths(&path.source, &path.dest).await?;
-            if !in_sync {
-                // If any path is out of sync, we set all_clean to false.
-                println!(
-                    "Differences found in {} → {}. They are not currently matched.",
-                    path.source, path.dest
-                );
-                all_clean = false;
-            }
+    // Load the config (re-reads each check in case it changes)
+    let config = match VolumeConfig::read_from_file(config_path) {
+        Ok(cfg) => cfg,
+        Err(e) => {
+            eprintln!("Failed to read config file {}: {}", config_path, e);
+            return Ok(false);
         }
+    };
 
-        if all_clean {
-            println!(
-                "All entries in {} are now in sync! No differences remain.",
-                config_path
-            );
-            break;
-        } else {
+    if config.paths.is_empty() {
+        println!("No paths found in {}. Nothing to check.", config_path);
+        return Ok(true);
+    }
+
+    let mut all_clean = true;
+
+    // Compare each source/dest pair
+    for path in &config.paths {
+        let in_sync = check_paths(&path.source, &path.dest).await?;
+        if !in_sync {
             println!(
-                "Some differences remain. Checking again in {} seconds...",
-                poll_interval
+                "Differences found in {} → {}. They are not currently matched.",
+                path.source, path.dest
             );
-            tokio::time::sleep(std::time::Duration::from_secs(poll_interval)).await;
+            all_clean = false;
         }
     }
 
-    Ok(())
+    if all_clean {
+        println!(
+            "All entries in {} are now in sync! No differences remain.",
+            config_path
+        );
+    } else {
+        println!(
+            "Some differences remain. Checking again in {} seconds...",
+            poll_interval
+        );
+    }
+    Ok(all_clean)
+}
+
+/// Runs `check` every `poll_interval` until it returns true, or until `timeout` elapses.
+async fn wait_until<F, Fut>(
+    mut check: F,
+    poll_interval: std::time::Duration,
+    timeout: Option<std::time::Duration>,
+) -> Result<WaitOutcome, Box<dyn std::error::Error>>
+where
+    F: FnMut() -> Fut,
+    Fut: std::future::Future<Output = Result<bool, Box<dyn std::error::Error>>>,
+{
+    let started = std::time::Instant::now();
+
+    loop {
+        if check().await? {
+            return Ok(WaitOutcome::Synced);
+        }
+
+        let sleep_for = match timeout {
+            Some(timeout) => {
+                let elapsed = started.elapsed();
+                if elapsed >= timeout {
+                    return Ok(WaitOutcome::TimedOut);
+                }
+                poll_interval.min(timeout - elapsed)
+            }
+            None => poll_interval,
+        };
+        tokio::time::sleep(sleep_for).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_wait_returns_after_timeout() {
+        // Arrange
+        let started = std::time::Instant::now();
+
+        // Act
+        let outcome = wait_until(
+            || async { Ok::<_, Box<dyn std::error::Error>>(false) },
+            Duration::from_millis(10),
+            Some(Duration::from_millis(50)),
+        )
+        .await
+        .unwrap();
+
+        // Assert
+        assert_eq!(outcome, WaitOutcome::TimedOut);
+        assert!(started.elapsed() >= Duration::from_millis(50));
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+
+    #[tokio::test]
+    async fn test_wait_returns_once_synced() {
+        let mut checks = 0;
+        let outcome = wait_until(
+            || {
+                checks += 1;
+                let done = checks >= 3;
+                async move { Ok::<_, Box<dyn std::error::Error>>(done) }
+            },
+            Duration::from_millis(1),
+            Some(Duration::from_secs(5)),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(outcome, WaitOutcome::Synced);
+        assert_eq!(checks, 3);
+    }
+
+    #[tokio::test]
+    async fn test_wait_timeout_does_not_oversleep_poll_interval() {
+        let started = std::time::Instant::now();
+        let outcome = wait_until(
+            || async { Ok::<_, Box<dyn std::error::Error>>(false) },
+            Duration::from_secs(60),
+            Some(Duration::from_millis(20)),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(outcome, WaitOutcome::TimedOut);
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
 }
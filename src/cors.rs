@@ -0,0 +1,166 @@
+// src/cors.rs
+//
+// Builds the server's CORS policy from `CorsConfig`.
+
+use crate::config::CorsConfig;
+use crate::logging::REQUEST_ID_HEADER;
+use axum::http::{HeaderName, HeaderValue, Method};
+use tower_http::cors::{AllowHeaders, AllowOrigin, CorsLayer};
+
+/// The CORS layer for `config`, or an error describing the invalid setting.
+pub fn cors_layer(config: &CorsConfig) -> Result<CorsLayer, String> {
+    let wildcard_origin = config.allowed_origins.iter().any(|o| o == "*");
+    let wildcard_headers = config.allowed_headers.iter().any(|h| h == "*");
+
+    if wildcard_origin && config.allowed_origins.len() > 1 {
+        return Err("'*' can't be combined with other allowed origins".to_string());
+    }
+    if config.allow_credentials && (wildcard_origin || wildcard_headers) {
+        return Err(
+            "Credentials can't be allowed together with '*' origins or headers".to_string(),
+        );
+    }
+
+    let origins = if wildcard_origin {
+        AllowOrigin::any()
+    } else {
+        let origins = config
+            .allowed_origins
+            .iter()
+            .map(|origin| {
+                HeaderValue::from_str(origin)
+                    .map_err(|e| format!("Invalid CORS origin '{}': {}", origin, e))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        AllowOrigin::list(origins)
+    };
+
+    let methods = config
+        .allowed_methods
+        .iter()
+        .map(|method| {
+            Method::from_bytes(method.to_uppercase().as_bytes())
+                .map_err(|e| format!("Invalid CORS method '{}': {}", method, e))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let headers = if wildcard_headers {
+        AllowHeaders::any()
+    } else {
+        let headers = config
+            .allowed_headers
+            .iter()
+            .map(|header| {
+                HeaderName::from_bytes(header.to_lowercase().as_bytes())
+                    .map_err(|e| format!("Invalid CORS header '{}': {}", header, e))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        AllowHeaders::list(headers)
+    };
+
+    Ok(CorsLayer::new()
+        .allow_origin(origins)
+        .allow_methods(methods)
+        .allow_headers(headers)
+        .allow_credentials(config.allow_credentials)
+        .expose_headers([HeaderName::from_static(REQUEST_ID_HEADER)]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::routing::get;
+    use axum::Router;
+
+    fn config(origins: &[&str], allow_credentials: bool) -> CorsConfig {
+        CorsConfig {
+            allowed_origins: origins.iter().map(|o| o.to_string()).collect(),
+            allowed_methods: vec!["GET".to_string(), "post".to_string()],
+            allowed_headers: vec!["Authorization".to_string()],
+            allow_credentials,
+        }
+    }
+
+    /// Serves `/` behind the CORS layer and returns its URL
+    async fn serve(config: &CorsConfig) -> String {
+        let app = Router::new()
+            .route("/", get(|| async { "ok" }))
+            .layer(cors_layer(config).unwrap());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        format!("http://{}/", addr)
+    }
+
+    async fn allowed_origin(url: &str, method: reqwest::Method, origin: &str) -> Option<String> {
+        let mut request = reqwest::Client::new()
+            .request(method.clone(), url)
+            .header("Origin", origin);
+        if method == reqwest::Method::OPTIONS {
+            request = request.header("Access-Control-Request-Method", "GET");
+        }
+        request
+            .send()
+            .await
+            .unwrap()
+            .headers()
+            .get("access-control-allow-origin")
+            .map(|value| value.to_str().unwrap().to_string())
+    }
+
+    #[tokio::test]
+    async fn test_disallowed_origin_is_rejected() {
+        // Arrange
+        let url = serve(&config(&["https://app.example.com"], true)).await;
+
+        // Act
+        let simple = allowed_origin(&url, reqwest::Method::GET, "https://evil.example.com").await;
+        let preflight =
+            allowed_origin(&url, reqwest::Method::OPTIONS, "https://evil.example.com").await;
+
+        // Assert
+        assert_eq!(simple, None);
+        assert_eq!(preflight, None);
+    }
+
+    #[tokio::test]
+    async fn test_allowed_origin_is_echoed() {
+        let url = serve(&config(&["https://app.example.com"], true)).await;
+        assert_eq!(
+            allowed_origin(&url, reqwest::Method::OPTIONS, "https://app.example.com")
+                .await
+                .as_deref(),
+            Some("https://app.example.com")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_default_refuses_every_origin() {
+        let url = serve(&config(&[], false)).await;
+        assert_eq!(
+            allowed_origin(&url, reqwest::Method::GET, "https://app.example.com").await,
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn test_wildcard_opt_in() {
+        let url = serve(&config(&["*"], false)).await;
+        assert_eq!(
+            allowed_origin(&url, reqwest::Method::GET, "http://localhost:3000")
+                .await
+                .as_deref(),
+            Some("*")
+        );
+    }
+
+    #[test]
+    fn test_invalid_configs() {
+        assert!(cors_layer(&config(&["*"], true)).is_err());
+        assert!(cors_layer(&config(&["*", "https://app.example.com"], false)).is_err());
+
+        let mut bad_method = config(&[], false);
+        bad_method.allowed_methods = vec!["GET POST".to_string()];
+        assert!(cors_layer(&bad_method).is_err());
+    }
+}
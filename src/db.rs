@@ -1,17 +1,26 @@
-use crate::config::SERVER_CONFIG;
+use crate::config::{DbPoolConfig, SERVER_CONFIG};
+use sea_orm::sea_query::{Alias, ColumnDef, Table};
 use sea_orm::{ConnectOptions, ConnectionTrait, Database, DatabaseConnection, DbErr, Schema};
 use std::time::Duration;
 
 pub type DbPool = DatabaseConnection;
 
 // Helper function to create connection options
-fn create_connect_options(url: String) -> ConnectOptions {
+fn create_connect_options(url: String, pool: &DbPoolConfig) -> ConnectOptions {
+    let url = match pool.statement_timeout {
+        Some(timeout) if is_postgres_url(&url) => with_statement_timeout(&url, timeout),
+        Some(_) => {
+            println!("Warning: statement timeout is only applied to Postgres databases");
+            url
+        }
+        None => url,
+    };
     let mut opt = ConnectOptions::new(url);
 
-    // --- CONFIGURE POOL SIZE AND TIMEOUTS HERE ---
-    opt.max_connections(80) // Example: Set pool size to 50
-        .connect_timeout(Duration::from_secs(8))
-        .acquire_timeout(Duration::from_secs(15)) // Increased acquire timeout slightly
+    opt.max_connections(pool.max_connections)
+        .min_connections(pool.min_connections)
+        .connect_timeout(pool.connect_timeout)
+        .acquire_timeout(pool.acquire_timeout)
         .idle_timeout(Duration::from_secs(60 * 5)) // Increased idle timeout (5 minutes)
         .max_lifetime(Duration::from_secs(60 * 10)); // Increased max lifetime (10 minutes)
                                                      // .sqlx_logging(true); // Uncomment for more verbose SQL logs if needed
@@ -19,6 +28,41 @@ fn create_connect_options(url: String) -> ConnectOptions {
     opt
 }
 
+fn is_postgres_url(url: &str) -> bool {
+    url.starts_with("postgres://") || url.starts_with("postgresql://")
+}
+
+/// Sets Postgres' `statement_timeout` for every connection through the
+/// `options` startup parameter in the URL.
+fn with_statement_timeout(url: &str, timeout: Duration) -> String {
+    let separator = if url.contains('?') { '&' } else { '?' };
+    format!(
+        "{}{}options=-c%20statement_timeout%3D{}",
+        url,
+        separator,
+        timeout.as_millis()
+    )
+}
+
+/// Connects with `opts`, giving up once the connect timeout has passed so an
+/// unreachable database fails startup with a clear error instead of hanging.
+async fn connect(opts: ConnectOptions) -> Result<DbPool, DbErr> {
+    let timeout = opts
+        .get_connect_timeout()
+        .unwrap_or(DbPoolConfig::default().connect_timeout);
+    match tokio::time::timeout(timeout, Database::connect(opts)).await {
+        Ok(Ok(db)) => Ok(db),
+        Ok(Err(e)) => Err(DbErr::Custom(format!(
+            "Could not connect to the database: {}",
+            e
+        ))),
+        Err(_) => Err(DbErr::Custom(format!(
+            "Could not connect to the database within {:?}; check that it is reachable or raise NEBU_DB_CONNECT_TIMEOUT",
+            timeout
+        ))),
+    }
+}
+
 pub async fn init_db() -> Result<DbPool, DbErr> {
     let database_url = &SERVER_CONFIG.database_url;
     println!("Connecting to database at: {}", database_url);
@@ -56,8 +100,8 @@ pub async fn init_db() -> Result<DbPool, DbErr> {
                     println!("Using fallback database URL: {}", fallback_url);
 
                     // --- Use ConnectOptions for fallback ---
-                    let opts = create_connect_options(fallback_url);
-                    let db = Database::connect(opts).await?;
+                    let opts = create_connect_options(fallback_url, &SERVER_CONFIG.db_pool);
+                    let db = connect(opts).await?;
                     // --------------------------------------
 
                     create_tables(&db).await?;
@@ -73,8 +117,8 @@ pub async fn init_db() -> Result<DbPool, DbErr> {
     }
 
     // --- Use ConnectOptions for primary URL ---
-    let opts = create_connect_options(database_url.clone()); // Clone if needed or ensure ownership
-    let db = Database::connect(opts).await?;
+    let opts = create_connect_options(database_url.clone(), &SERVER_CONFIG.db_pool); // Clone if needed or ensure ownership
+    let db = connect(opts).await?;
     // -----------------------------------------
 
     create_tables(&db).await?;
@@ -142,5 +186,393 @@ async fn create_tables(db: &DbPool) -> Result<(), DbErr> {
     )
     .await?;
 
+    db.execute(
+        db.get_database_backend().build(
+            schema
+                .create_table_from_entity(crate::entities::container_events::Entity)
+                .if_not_exists(),
+        ),
+    )
+    .await?;
+
+    db.execute(
+        db.get_database_backend().build(
+            schema
+                .create_table_from_entity(crate::entities::idempotency_keys::Entity)
+                .if_not_exists(),
+        ),
+    )
+    .await?;
+
+    migrate_columns(db).await?;
+
+    crate::resources::v1::containers::notify::install_trigger(db).await?;
+
+    Ok(())
+}
+
+// Columns added after a table was first created. `create_table_from_entity` only
+// runs for new tables, so existing databases need these added explicitly.
+async fn migrate_columns(db: &DbPool) -> Result<(), DbErr> {
+    add_column_if_missing(
+        db,
+        "containers",
+        ColumnDef::new(Alias::new("deleted_at"))
+            .timestamp_with_time_zone()
+            .null()
+            .to_owned(),
+    )
+    .await?;
+
+    add_column_if_missing(
+        db,
+        "processors",
+        ColumnDef::new(Alias::new("deleted_at"))
+            .timestamp_with_time_zone()
+            .null()
+            .to_owned(),
+    )
+    .await?;
+
+    add_column_if_missing(
+        db,
+        "containers",
+        ColumnDef::new(Alias::new("bootstrap"))
+            .json()
+            .null()
+            .to_owned(),
+    )
+    .await?;
+
+    add_column_if_missing(
+        db,
+        "containers",
+        ColumnDef::new(Alias::new("tailscale"))
+            .json()
+            .null()
+            .to_owned(),
+    )
+    .await?;
+
+    add_column_if_missing(
+        db,
+        "containers",
+        ColumnDef::new(Alias::new("total_cost"))
+            .double()
+            .null()
+            .to_owned(),
+    )
+    .await?;
+
+    add_column_if_missing(
+        db,
+        "containers",
+        ColumnDef::new(Alias::new("cost_accrued_at"))
+            .big_integer()
+            .null()
+            .to_owned(),
+    )
+    .await?;
+
+    add_column_if_missing(
+        db,
+        "containers",
+        ColumnDef::new(Alias::new("priority"))
+            .integer()
+            .null()
+            .to_owned(),
+    )
+    .await?;
+
+    add_column_if_missing(
+        db,
+        "containers",
+        ColumnDef::new(Alias::new("preemptible"))
+            .boolean()
+            .null()
+            .to_owned(),
+    )
+    .await?;
+
+    add_column_if_missing(
+        db,
+        "containers",
+        ColumnDef::new(Alias::new("webhook_url"))
+            .string()
+            .null()
+            .to_owned(),
+    )
+    .await?;
+
+    add_column_if_missing(
+        db,
+        "containers",
+        ColumnDef::new(Alias::new("sync_progress"))
+            .json()
+            .null()
+            .to_owned(),
+    )
+    .await?;
+
+    add_column_if_missing(
+        db,
+        "containers",
+        ColumnDef::new(Alias::new("ssh_reachable"))
+            .boolean()
+            .null()
+            .to_owned(),
+    )
+    .await?;
+
+    add_column_if_missing(
+        db,
+        "containers",
+        ColumnDef::new(Alias::new("last_ssh_check"))
+            .big_integer()
+            .null()
+            .to_owned(),
+    )
+    .await?;
+
+    add_column_if_missing(
+        db,
+        "containers",
+        ColumnDef::new(Alias::new("usage")).json().null().to_owned(),
+    )
+    .await?;
+
+    add_column_if_missing(
+        db,
+        "containers",
+        ColumnDef::new(Alias::new("failure_reason"))
+            .json()
+            .null()
+            .to_owned(),
+    )
+    .await?;
+
+    add_column_if_missing(
+        db,
+        "processors",
+        ColumnDef::new(Alias::new("stream_max_len"))
+            .big_integer()
+            .null()
+            .to_owned(),
+    )
+    .await?;
+
+    add_column_if_missing(
+        db,
+        "processors",
+        ColumnDef::new(Alias::new("queue_backend"))
+            .string()
+            .null()
+            .to_owned(),
+    )
+    .await?;
+
+    add_column_if_missing(
+        db,
+        "processors",
+        ColumnDef::new(Alias::new("warmup_message"))
+            .json()
+            .null()
+            .to_owned(),
+    )
+    .await?;
+
+    add_column_if_missing(
+        db,
+        "namespaces",
+        ColumnDef::new(Alias::new("default_env"))
+            .json()
+            .null()
+            .to_owned(),
+    )
+    .await?;
+
+    add_column_if_missing(
+        db,
+        "namespaces",
+        ColumnDef::new(Alias::new("default_user"))
+            .string()
+            .null()
+            .to_owned(),
+    )
+    .await?;
+
+    add_column_if_missing(
+        db,
+        "namespaces",
+        ColumnDef::new(Alias::new("registry_secret"))
+            .string()
+            .null()
+            .to_owned(),
+    )
+    .await?;
+
+    add_column_if_missing(
+        db,
+        "namespaces",
+        ColumnDef::new(Alias::new("default_platform"))
+            .string()
+            .null()
+            .to_owned(),
+    )
+    .await?;
+
+    add_column_if_missing(
+        db,
+        "namespaces",
+        ColumnDef::new(Alias::new("default_accelerators"))
+            .json()
+            .null()
+            .to_owned(),
+    )
+    .await?;
+
+    add_column_if_missing(
+        db,
+        "api-keys",
+        ColumnDef::new(Alias::new("expires_at"))
+            .timestamp_with_time_zone()
+            .null()
+            .to_owned(),
+    )
+    .await?;
+
+    add_column_if_missing(
+        db,
+        "api-keys",
+        ColumnDef::new(Alias::new("scopes"))
+            .json()
+            .null()
+            .to_owned(),
+    )
+    .await?;
+
+    add_column_if_missing(
+        db,
+        "secrets",
+        ColumnDef::new(Alias::new("version"))
+            .integer()
+            .not_null()
+            .default(1)
+            .to_owned(),
+    )
+    .await?;
+
     Ok(())
 }
+
+async fn add_column_if_missing(
+    db: &DbPool,
+    table: &str,
+    mut column: ColumnDef,
+) -> Result<(), DbErr> {
+    let stmt = Table::alter()
+        .table(Alias::new(table))
+        .add_column(&mut column)
+        .to_owned();
+
+    match db.execute(db.get_database_backend().build(&stmt)).await {
+        Ok(_) => Ok(()),
+        Err(e) => {
+            let msg = e.to_string().to_lowercase();
+            // Postgres: "already exists", SQLite: "duplicate column name"
+            if msg.contains("already exists") || msg.contains("duplicate column") {
+                Ok(())
+            } else {
+                Err(e)
+            }
+        }
+    }
+}
+
+/// Connects to the Postgres at `NEBU_TEST_DATABASE_URL` with every table
+/// created in a schema of its own, so tests running at once don't see each
+/// other's rows. SQLite can't hold the containers table's array columns.
+#[cfg(test)]
+pub(crate) async fn test_db() -> DbPool {
+    let url = std::env::var("NEBU_TEST_DATABASE_URL").expect("NEBU_TEST_DATABASE_URL is not set");
+    let schema = format!("test_{}", short_uuid::ShortUuid::generate());
+    Database::connect(&url)
+        .await
+        .unwrap()
+        .execute_unprepared(&format!("CREATE SCHEMA \"{}\"", schema))
+        .await
+        .unwrap();
+
+    let mut opts = ConnectOptions::new(url);
+    opts.max_connections(5).set_schema_search_path(schema);
+    let db = Database::connect(opts).await.unwrap();
+    create_tables(&db).await.unwrap();
+    db
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pool_config_is_applied_to_connect_options() {
+        // Arrange
+        let pool = DbPoolConfig {
+            max_connections: 12,
+            min_connections: 3,
+            connect_timeout: Duration::from_secs(2),
+            acquire_timeout: Duration::from_secs(4),
+            statement_timeout: Some(Duration::from_secs(30)),
+        };
+
+        // Act
+        let opts = create_connect_options("postgres://nebu@db:5432/nebu".to_string(), &pool);
+
+        // Assert
+        assert_eq!(opts.get_max_connections(), Some(12));
+        assert_eq!(opts.get_min_connections(), Some(3));
+        assert_eq!(opts.get_connect_timeout(), Some(Duration::from_secs(2)));
+        assert_eq!(opts.get_acquire_timeout(), Some(Duration::from_secs(4)));
+        assert_eq!(
+            opts.get_url(),
+            "postgres://nebu@db:5432/nebu?options=-c%20statement_timeout%3D30000"
+        );
+    }
+
+    #[test]
+    fn test_statement_timeout_only_for_postgres() {
+        let pool = DbPoolConfig {
+            statement_timeout: Some(Duration::from_millis(1500)),
+            ..Default::default()
+        };
+
+        let with_params =
+            create_connect_options("postgresql://db/nebu?sslmode=require".to_string(), &pool);
+        let sqlite = create_connect_options("sqlite:/tmp/nebu.db".to_string(), &pool);
+
+        assert_eq!(
+            with_params.get_url(),
+            "postgresql://db/nebu?sslmode=require&options=-c%20statement_timeout%3D1500"
+        );
+        assert_eq!(sqlite.get_url(), "sqlite:/tmp/nebu.db");
+    }
+
+    #[tokio::test]
+    async fn test_unreachable_database_fails_fast() {
+        let pool = DbPoolConfig {
+            connect_timeout: Duration::from_millis(500),
+            acquire_timeout: Duration::from_millis(500),
+            ..Default::default()
+        };
+        // Nothing listens on port 1
+        let opts = create_connect_options("postgres://nebu@127.0.0.1:1/nebu".to_string(), &pool);
+
+        let started = std::time::Instant::now();
+        let err = connect(opts).await.unwrap_err();
+
+        assert!(started.elapsed() < Duration::from_secs(5));
+        assert!(err
+            .to_string()
+            .contains("Could not connect to the database"));
+    }
+}
@@ -1,2 +1,3 @@
+pub mod records;
 pub mod server;
 // pub mod server_dyn;
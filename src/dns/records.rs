@@ -0,0 +1,370 @@
+// src/dns/records.rs
+//
+// Public DNS records for containers. When a container becomes ready with a
+// public IP, `watch` points `<name>.<namespace>.<zone>` at it, with a CNAME
+// when the address is a hostname; `delete` removes the record again. What a
+// record points at is kept in the container's `controller_data`, so a watch
+// started after a controller restart still updates or removes it.
+
+use crate::config::{DnsConfig, DnsProviderConfig, SERVER_CONFIG};
+use crate::resources::v1::containers::base::ContainerStatus;
+use serde::Deserialize;
+use serde_json::json;
+use std::net::IpAddr;
+use tracing::{debug, info};
+
+#[derive(Debug, thiserror::Error)]
+pub enum DnsError {
+    #[error("DNS provider request failed: {0}")]
+    Request(#[from] reqwest::Error),
+
+    #[error("DNS provider rejected the request: {0}")]
+    Provider(String),
+}
+
+/// What to do with a container's record after a watch iteration.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DnsAction {
+    /// Create the record, or repoint it at a new address
+    Upsert(String),
+    Delete,
+    Keep,
+}
+
+/// Decides the record change for a container currently pointed at
+/// `registered` (None if there is no record yet).
+pub fn dns_action(
+    registered: Option<&str>,
+    status: &ContainerStatus,
+    ready: bool,
+    public_ip: Option<&str>,
+) -> DnsAction {
+    if status.is_inactive() {
+        return if registered.is_some() {
+            DnsAction::Delete
+        } else {
+            DnsAction::Keep
+        };
+    }
+    match public_ip {
+        Some(ip) if *status == ContainerStatus::Running && ready && registered != Some(ip) => {
+            DnsAction::Upsert(ip.to_string())
+        }
+        _ => DnsAction::Keep,
+    }
+}
+
+/// Lowercases and replaces anything that isn't allowed in a DNS label.
+fn dns_label(value: &str) -> String {
+    value
+        .to_lowercase()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+        .collect::<String>()
+        .trim_matches('-')
+        .chars()
+        .take(63)
+        .collect()
+}
+
+/// The stable name of a container's record, e.g. `web.team-a.containers.example.com`
+pub fn container_record_name(zone: &str, namespace: &str, name: &str) -> String {
+    format!("{}.{}.{}", dns_label(name), dns_label(namespace), zone)
+}
+
+/// `A` or `AAAA` for IP addresses, `CNAME` for anything else.
+fn record_type(content: &str) -> &'static str {
+    match content.parse::<IpAddr>() {
+        Ok(IpAddr::V4(_)) => "A",
+        Ok(IpAddr::V6(_)) => "AAAA",
+        Err(_) => "CNAME",
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct CloudflareResponse<T> {
+    success: bool,
+    #[serde(default)]
+    errors: Vec<serde_json::Value>,
+    result: Option<T>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CloudflareRecord {
+    id: String,
+    #[serde(rename = "type")]
+    record_type: String,
+}
+
+pub struct DnsClient {
+    config: DnsConfig,
+    http: reqwest::Client,
+}
+
+impl DnsClient {
+    pub fn new(config: DnsConfig) -> Self {
+        Self {
+            config,
+            http: crate::utils::http::shared_client(),
+        }
+    }
+
+    /// The client for the server's configured zone, if DNS is enabled.
+    pub fn from_server_config() -> Option<Self> {
+        SERVER_CONFIG.dns.clone().map(Self::new)
+    }
+
+    pub fn record_name(&self, namespace: &str, name: &str) -> String {
+        container_record_name(&self.config.zone, namespace, name)
+    }
+
+    /// Points `fqdn` at `content`, replacing whatever record it had.
+    pub async fn upsert(&self, fqdn: &str, content: &str) -> Result<(), DnsError> {
+        let record_type = record_type(content);
+        let body = json!({
+            "type": record_type,
+            "name": fqdn,
+            "content": content,
+            "ttl": self.config.ttl,
+            "proxied": false,
+        });
+
+        let existing = self.find_records(fqdn).await?;
+        // A name can't hold a CNAME next to other records, so drop mismatched types
+        let mut reusable = None;
+        for record in existing {
+            if reusable.is_none() && record.record_type == record_type {
+                reusable = Some(record);
+            } else {
+                self.delete_record(&record.id).await?;
+            }
+        }
+
+        let request = match &reusable {
+            Some(record) => self.http.put(self.records_url(Some(&record.id))),
+            None => self.http.post(self.records_url(None)),
+        };
+        let response: CloudflareResponse<serde_json::Value> = request
+            .bearer_auth(self.api_token())
+            .json(&body)
+            .send()
+            .await?
+            .json()
+            .await?;
+        Self::check(&response)?;
+        info!("[DNS] Pointed {} at {} ({})", fqdn, content, record_type);
+        Ok(())
+    }
+
+    /// Removes every record for `fqdn`. Deleting a name without records is a no-op.
+    pub async fn delete(&self, fqdn: &str) -> Result<(), DnsError> {
+        for record in self.find_records(fqdn).await? {
+            self.delete_record(&record.id).await?;
+        }
+        debug!("[DNS] Removed records for {}", fqdn);
+        Ok(())
+    }
+
+    fn api_token(&self) -> &str {
+        let DnsProviderConfig::Cloudflare { api_token, .. } = &self.config.provider;
+        api_token
+    }
+
+    fn records_url(&self, record_id: Option<&str>) -> String {
+        let DnsProviderConfig::Cloudflare {
+            zone_id, api_url, ..
+        } = &self.config.provider;
+        match record_id {
+            Some(id) => format!("{}/zones/{}/dns_records/{}", api_url, zone_id, id),
+            None => format!("{}/zones/{}/dns_records", api_url, zone_id),
+        }
+    }
+
+    async fn find_records(&self, fqdn: &str) -> Result<Vec<CloudflareRecord>, DnsError> {
+        let response: CloudflareResponse<Vec<CloudflareRecord>> = self
+            .http
+            .get(self.records_url(None))
+            .query(&[("name", fqdn)])
+            .bearer_auth(self.api_token())
+            .send()
+            .await?
+            .json()
+            .await?;
+        Self::check(&response)?;
+        Ok(response.result.unwrap_or_default())
+    }
+
+    async fn delete_record(&self, record_id: &str) -> Result<(), DnsError> {
+        let response: CloudflareResponse<serde_json::Value> = self
+            .http
+            .delete(self.records_url(Some(record_id)))
+            .bearer_auth(self.api_token())
+            .send()
+            .await?
+            .json()
+            .await?;
+        Self::check(&response)
+    }
+
+    fn check<T>(response: &CloudflareResponse<T>) -> Result<(), DnsError> {
+        if response.success {
+            Ok(())
+        } else {
+            Err(DnsError::Provider(
+                serde_json::to_string(&response.errors).unwrap_or_default(),
+            ))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::extract::{Path, Query, State};
+    use axum::routing::{get, put};
+    use axum::{Json, Router};
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn test_dns_action_on_status_transitions() {
+        // Arrange
+        let ip = Some("203.0.113.7");
+
+        // Act
+        let creating = dns_action(None, &ContainerStatus::Creating, false, ip);
+        let ready = dns_action(None, &ContainerStatus::Running, true, ip);
+        let unchanged = dns_action(ip, &ContainerStatus::Running, true, ip);
+        let moved = dns_action(ip, &ContainerStatus::Running, true, Some("203.0.113.9"));
+        let stopped = dns_action(ip, &ContainerStatus::Stopped, false, None);
+        let never_registered = dns_action(None, &ContainerStatus::Failed, false, None);
+
+        // Assert
+        assert_eq!(creating, DnsAction::Keep);
+        assert_eq!(ready, DnsAction::Upsert("203.0.113.7".to_string()));
+        assert_eq!(unchanged, DnsAction::Keep);
+        assert_eq!(moved, DnsAction::Upsert("203.0.113.9".to_string()));
+        assert_eq!(stopped, DnsAction::Delete);
+        assert_eq!(never_registered, DnsAction::Keep);
+    }
+
+    #[test]
+    fn test_container_record_name() {
+        assert_eq!(
+            container_record_name("containers.example.com", "Team_A", "web.1"),
+            "web-1.team-a.containers.example.com"
+        );
+        assert_eq!(record_type("203.0.113.7"), "A");
+        assert_eq!(record_type("2001:db8::1"), "AAAA");
+        assert_eq!(record_type("pod.example.net"), "CNAME");
+    }
+
+    /// Records held by the fake Cloudflare API, by id
+    type Records = Arc<Mutex<HashMap<String, serde_json::Value>>>;
+
+    /// Serves the subset of the Cloudflare DNS records API the client uses
+    async fn serve_fake_cloudflare(records: Records) -> String {
+        async fn list(
+            State(records): State<Records>,
+            Query(params): Query<HashMap<String, String>>,
+        ) -> Json<serde_json::Value> {
+            let records = records.lock().unwrap();
+            let matching: Vec<_> = records
+                .values()
+                .filter(|r| {
+                    Some(r["name"].as_str().unwrap()) == params.get("name").map(|s| s.as_str())
+                })
+                .cloned()
+                .collect();
+            Json(json!({"success": true, "errors": [], "result": matching}))
+        }
+        async fn create(
+            State(records): State<Records>,
+            Json(mut record): Json<serde_json::Value>,
+        ) -> Json<serde_json::Value> {
+            let mut records = records.lock().unwrap();
+            let id = format!("rec{}", records.len() + 1);
+            record["id"] = json!(id);
+            records.insert(id, record.clone());
+            Json(json!({"success": true, "errors": [], "result": record}))
+        }
+        async fn update(
+            State(records): State<Records>,
+            Path((_, id)): Path<(String, String)>,
+            Json(mut record): Json<serde_json::Value>,
+        ) -> Json<serde_json::Value> {
+            record["id"] = json!(id);
+            records.lock().unwrap().insert(id, record.clone());
+            Json(json!({"success": true, "errors": [], "result": record}))
+        }
+        async fn remove(
+            State(records): State<Records>,
+            Path((_, id)): Path<(String, String)>,
+        ) -> Json<serde_json::Value> {
+            records.lock().unwrap().remove(&id);
+            Json(json!({"success": true, "errors": [], "result": {"id": id}}))
+        }
+
+        let app = Router::new()
+            .route("/zones/:zone/dns_records", get(list).post(create))
+            .route("/zones/:zone/dns_records/:id", put(update).delete(remove))
+            .with_state(records);
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        format!("http://{}", addr)
+    }
+
+    fn client(api_url: String) -> DnsClient {
+        DnsClient::new(DnsConfig {
+            zone: "containers.example.com".to_string(),
+            ttl: 60,
+            provider: DnsProviderConfig::Cloudflare {
+                api_token: "token".to_string(),
+                zone_id: "zone1".to_string(),
+                api_url,
+            },
+        })
+    }
+
+    #[tokio::test]
+    async fn test_record_created_updated_and_deleted() {
+        let records = Records::default();
+        let dns = client(serve_fake_cloudflare(records.clone()).await);
+        let fqdn = dns.record_name("team-a", "web");
+
+        dns.upsert(&fqdn, "203.0.113.7").await.unwrap();
+        dns.upsert(&fqdn, "203.0.113.9").await.unwrap();
+        {
+            let records = records.lock().unwrap();
+            assert_eq!(records.len(), 1);
+            let record = records.values().next().unwrap();
+            assert_eq!(record["name"], "web.team-a.containers.example.com");
+            assert_eq!(record["type"], "A");
+            assert_eq!(record["content"], "203.0.113.9");
+        }
+
+        dns.delete(&fqdn).await.unwrap();
+        assert!(records.lock().unwrap().is_empty());
+        // Deleting again is a no-op
+        dns.delete(&fqdn).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_hostname_gets_a_cname() {
+        let records = Records::default();
+        let dns = client(serve_fake_cloudflare(records.clone()).await);
+        let fqdn = dns.record_name("team-a", "web");
+
+        dns.upsert(&fqdn, "203.0.113.7").await.unwrap();
+        dns.upsert(&fqdn, "abc123-8080.proxy.runpod.net")
+            .await
+            .unwrap();
+
+        let records = records.lock().unwrap();
+        assert_eq!(records.len(), 1);
+        let record = records.values().next().unwrap();
+        assert_eq!(record["type"], "CNAME");
+        assert_eq!(record["content"], "abc123-8080.proxy.runpod.net");
+    }
+}
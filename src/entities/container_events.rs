@@ -0,0 +1,79 @@
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, PartialEq, Eq, DeriveEntityModel, Serialize, Deserialize)]
+#[sea_orm(table_name = "container_events")]
+pub struct Model {
+    #[sea_orm(primary_key, column_type = "Text", auto_increment = false)]
+    pub id: String,
+    pub container_id: String,
+    pub old_status: Option<String>,
+    pub new_status: Option<String>,
+    pub message: Option<String>,
+    pub actor: String,
+    pub created_at: DateTimeWithTimeZone,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
+
+impl Model {
+    pub fn to_v1(&self) -> crate::resources::v1::containers::models::V1ContainerEvent {
+        crate::resources::v1::containers::models::V1ContainerEvent {
+            id: self.id.clone(),
+            container_id: self.container_id.clone(),
+            old_status: self.old_status.clone(),
+            new_status: self.new_status.clone(),
+            message: self.message.clone(),
+            actor: self.actor.clone(),
+            created_at: self.created_at.timestamp(),
+        }
+    }
+}
+
+/// Returns true when moving from `old` to `new` is a real status transition
+/// that should be recorded. Status updates that only touch other fields
+/// (ports, cost, readiness, ...) leave the status unchanged and are skipped.
+pub fn is_transition(old: Option<&str>, new: Option<&str>) -> bool {
+    match new {
+        Some(new) => old.map(|o| !o.eq_ignore_ascii_case(new)).unwrap_or(true),
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_transition_on_status_change() {
+        // Arrange
+        let old = Some("creating");
+        let new = Some("running");
+
+        // Act
+        let result = is_transition(old, new);
+
+        // Assert
+        assert!(result);
+    }
+
+    #[test]
+    fn test_is_transition_from_no_status() {
+        assert!(is_transition(None, Some("defined")));
+    }
+
+    #[test]
+    fn test_is_not_transition_when_status_unchanged() {
+        assert!(!is_transition(Some("running"), Some("running")));
+        assert!(!is_transition(Some("Running"), Some("running")));
+    }
+
+    #[test]
+    fn test_is_not_transition_without_new_status() {
+        assert!(!is_transition(Some("running"), None));
+        assert!(!is_transition(None, None));
+    }
+}
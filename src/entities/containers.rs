@@ -7,8 +7,9 @@ use std::collections::HashMap;
 
 use crate::models::{V1AuthzConfig, V1Meter};
 use crate::resources::v1::containers::models::{
-    V1Container, V1ContainerHealthCheck, V1ContainerResources, V1ContainerStatus, V1EnvVar,
-    V1PortRequest, V1SSHKey,
+    ControllerData, V1Container, V1ContainerBootstrap, V1ContainerHealthCheck,
+    V1ContainerResources, V1ContainerStatus, V1ContainerTailscale, V1EnvVar, V1FailureReason,
+    V1PortRequest, V1ResourceUsage, V1RestartState, V1SSHKey, V1SyncProgress,
 };
 use crate::resources::v1::volumes::models::V1VolumePath;
 
@@ -36,6 +37,11 @@ pub struct Model {
     pub resource_name: Option<String>,
     pub resource_namespace: Option<String>,
     pub resource_cost_per_hr: Option<f64>,
+    /// Dollars spent on the container so far, accumulated while its pod runs
+    pub total_cost: Option<f64>,
+    /// When spend was last added to `total_cost`, in unix seconds, while the
+    /// pod runs. Kept in the database so a restart doesn't lose billed time.
+    pub cost_accrued_at: Option<i64>,
     pub command: Option<String>,
     pub args: Option<String>,
     pub labels: Option<Json>,
@@ -55,6 +61,24 @@ pub struct Model {
     pub controller_data: Option<Json>,
     pub container_user: Option<String>,
     pub ssh_keys: Option<Json>,
+    pub bootstrap: Option<Json>,
+    pub tailscale: Option<Json>,
+    pub priority: Option<i32>,
+    pub preemptible: Option<bool>,
+    pub webhook_url: Option<String>,
+    /// Latest volume sync progress reported from inside the container. Kept
+    /// out of `status` so reports and status updates don't overwrite each other.
+    pub sync_progress: Option<Json>,
+    /// Whether the latest SSH check reached the container, and when it ran
+    pub ssh_reachable: Option<bool>,
+    pub last_ssh_check: Option<i64>,
+    /// Recent resource usage samples, oldest first. Only the container's
+    /// watch loop writes it.
+    pub usage: Option<Json>,
+    /// Why the current pod failed, as recognised from its logs. Cleared when
+    /// a new pod replaces it.
+    pub failure_reason: Option<Json>,
+    pub deleted_at: Option<DateTimeWithTimeZone>,
     pub updated_at: DateTimeWithTimeZone,
     pub created_at: DateTimeWithTimeZone,
 }
@@ -124,6 +148,18 @@ impl Model {
         }
     }
 
+    /// `controller_data` as a `ControllerData`, empty when unset.
+    pub fn typed_controller_data(&self) -> Result<ControllerData, serde_json::Error> {
+        self.parse_controller_data::<ControllerData>()
+            .map(Option::unwrap_or_default)
+    }
+
+    /// Attempt to parse the `restart` entry of `controller_data` into a `V1RestartState`.
+    pub fn parse_restart_state(&self) -> Result<Option<V1RestartState>, serde_json::Error> {
+        self.parse_controller_data::<ControllerData>()
+            .map(|data| data.and_then(|data| data.restart))
+    }
+
     /// Attempt to parse `resources` into a `V1ContainerResources`.
     pub fn parse_resources(&self) -> Result<Option<V1ContainerResources>, serde_json::Error> {
         if let Some(json_value) = &self.resources {
@@ -169,12 +205,68 @@ impl Model {
         }
     }
 
+    /// Attempt to parse `bootstrap` into a `V1ContainerBootstrap`.
+    pub fn parse_bootstrap(&self) -> Result<Option<V1ContainerBootstrap>, serde_json::Error> {
+        if let Some(json_value) = &self.bootstrap {
+            serde_json::from_value(json_value.clone()).map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Attempt to parse `tailscale` into a `V1ContainerTailscale`.
+    pub fn parse_tailscale(&self) -> Result<Option<V1ContainerTailscale>, serde_json::Error> {
+        if let Some(json_value) = &self.tailscale {
+            serde_json::from_value(json_value.clone()).map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+
+    pub fn parse_sync_progress(&self) -> Result<Option<V1SyncProgress>, serde_json::Error> {
+        if let Some(json_value) = &self.sync_progress {
+            serde_json::from_value(json_value.clone()).map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+
+    pub fn parse_usage(&self) -> Result<Vec<V1ResourceUsage>, serde_json::Error> {
+        if let Some(json_value) = &self.usage {
+            serde_json::from_value(json_value.clone())
+        } else {
+            Ok(Vec::new())
+        }
+    }
+
+    pub fn parse_failure_reason(&self) -> Result<Option<V1FailureReason>, serde_json::Error> {
+        if let Some(json_value) = &self.failure_reason {
+            serde_json::from_value(json_value.clone()).map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+
     /// Construct a full V1Container from the current model row.
     /// Returns a serde_json Error if any JSON parsing in subfields fails.
     pub fn to_v1_container(&self) -> Result<V1Container, serde_json::Error> {
         let env = self.parse_env()?;
         let volumes = self.parse_volumes()?;
-        let status = self.parse_status()?;
+        let mut status = self.parse_status()?;
+        if let Some(progress) = self.parse_sync_progress()? {
+            status.get_or_insert_with(Default::default).sync_progress = Some(progress);
+        }
+        if self.last_ssh_check.is_some() {
+            let status = status.get_or_insert_with(Default::default);
+            status.ssh_reachable = self.ssh_reachable;
+            status.last_ssh_check = self.last_ssh_check;
+        }
+        if let Some(latest) = self.parse_usage()?.pop() {
+            status.get_or_insert_with(Default::default).usage = Some(latest);
+        }
+        if let Some(reason) = self.parse_failure_reason()? {
+            status.get_or_insert_with(Default::default).failure_reason = Some(reason);
+        }
         let labels = self.parse_labels()?;
         let meters = self.parse_meters()?;
         let resources = self.parse_resources()?;
@@ -182,6 +274,8 @@ impl Model {
         let ports = self.parse_ports()?;
         let authz = self.parse_authz()?;
         let health_check = self.parse_health_check()?;
+        let bootstrap = self.parse_bootstrap()?;
+        let tailscale = self.parse_tailscale()?;
 
         // Build metadata; fill with defaults or unwrap as needed
         let metadata = crate::models::V1ResourceMeta {
@@ -200,6 +294,7 @@ impl Model {
         let container = V1Container {
             kind: "Container".to_owned(), // or use default_container_kind() if needed
             platform: self.platform.clone().unwrap_or_default(),
+            platforms: self.platforms.clone(),
             metadata,
             image: self.image.clone(),
             env,
@@ -218,8 +313,75 @@ impl Model {
             ports: ports.clone(),
             proxy_port: self.proxy_port.clone(),
             authz,
+            bootstrap,
+            tailscale,
+            priority: self.priority,
+            preemptible: self.preemptible,
+            webhook_url: self.webhook_url.clone(),
         };
 
         Ok(container)
     }
 }
+
+#[cfg(test)]
+impl Model {
+    /// Container `ns/train` on RunPod with nothing else set, for tests to adjust
+    pub(crate) fn test_fixture() -> Self {
+        Self {
+            id: "c1".to_string(),
+            namespace: "ns".to_string(),
+            name: "train".to_string(),
+            full_name: "ns/train".to_string(),
+            owner: "me".to_string(),
+            owner_ref: None,
+            image: "busybox".to_string(),
+            env: None,
+            volumes: None,
+            local_volumes: None,
+            accelerators: None,
+            cpu_request: None,
+            memory_request: None,
+            status: None,
+            platform: Some("runpod".to_string()),
+            platforms: None,
+            resource_name: None,
+            resource_namespace: None,
+            resource_cost_per_hr: None,
+            total_cost: None,
+            cost_accrued_at: None,
+            command: None,
+            args: None,
+            labels: None,
+            meters: None,
+            queue: None,
+            ports: None,
+            proxy_port: None,
+            timeout: None,
+            resources: None,
+            health_check: None,
+            restart: "Never".to_string(),
+            authz: None,
+            public_addr: None,
+            tailnet_ip: None,
+            created_by: None,
+            desired_status: None,
+            controller_data: None,
+            container_user: None,
+            ssh_keys: None,
+            bootstrap: None,
+            tailscale: None,
+            priority: None,
+            preemptible: None,
+            webhook_url: None,
+            sync_progress: None,
+            ssh_reachable: None,
+            last_ssh_check: None,
+            usage: None,
+            failure_reason: None,
+            deleted_at: None,
+            updated_at: chrono::Utc::now().into(),
+            created_at: chrono::Utc::now().into(),
+        }
+    }
+}
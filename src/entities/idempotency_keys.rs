@@ -0,0 +1,156 @@
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// A client supplied `Idempotency-Key` for a create request. The row is
+/// claimed before the container is declared and points at the created
+/// container once it exists.
+#[derive(Clone, Debug, PartialEq, Eq, DeriveEntityModel, Serialize, Deserialize)]
+#[sea_orm(table_name = "idempotency_keys")]
+pub struct Model {
+    /// `<owner>/<key>`, see [`record_id`]
+    #[sea_orm(primary_key, column_type = "Text", auto_increment = false)]
+    pub id: String,
+    pub key: String,
+    pub owner: String,
+    /// Unset while the original request is still in flight
+    pub container_id: Option<String>,
+    pub created_at: DateTimeWithTimeZone,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
+
+impl Model {
+    /// Whether the key is older than `ttl` and may be reused
+    pub fn is_expired(&self, now: chrono::DateTime<chrono::Utc>, ttl: chrono::Duration) -> bool {
+        self.created_at.with_timezone(&chrono::Utc) + ttl <= now
+    }
+}
+
+/// Outcome of claiming an idempotency key for a create request
+#[derive(Debug, Clone, PartialEq)]
+pub enum IdempotencyClaim {
+    /// The key is now held by this request; carries the record id
+    Claimed(String),
+    /// Another request with the same key hasn't finished yet
+    InFlight,
+    /// The key already created this container
+    Completed(String),
+}
+
+/// Keys are scoped to the requesting user, so two users can't collide.
+pub fn record_id(owner: &str, key: &str) -> String {
+    format!("{}/{}", owner, key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mutation::Mutation;
+    use sea_orm::{ConnectionTrait, Database, DatabaseConnection, Schema};
+
+    async fn setup() -> DatabaseConnection {
+        let db = Database::connect("sqlite::memory:").await.unwrap();
+        let schema = Schema::new(db.get_database_backend());
+        db.execute(
+            db.get_database_backend()
+                .build(&schema.create_table_from_entity(Entity)),
+        )
+        .await
+        .unwrap();
+        db
+    }
+
+    #[tokio::test]
+    async fn test_same_key_yields_one_container() {
+        // Arrange
+        let db = setup().await;
+        let ttl = chrono::Duration::hours(24);
+        let mut created = Vec::new();
+
+        // Act
+        for _ in 0..2 {
+            match Mutation::claim_idempotency_key(&db, "a@b.com", "retry-1", ttl)
+                .await
+                .unwrap()
+            {
+                IdempotencyClaim::Claimed(record_id) => {
+                    let container_id = format!("container-{}", created.len());
+                    Mutation::complete_idempotency_key(&db, record_id, container_id.clone())
+                        .await
+                        .unwrap();
+                    created.push(container_id);
+                }
+                IdempotencyClaim::Completed(container_id) => {
+                    assert_eq!(container_id, created[0]);
+                }
+                IdempotencyClaim::InFlight => panic!("key should not be in flight"),
+            }
+        }
+
+        // Assert
+        assert_eq!(created, vec!["container-0".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn test_unfinished_key_is_in_flight() {
+        let db = setup().await;
+        let ttl = chrono::Duration::hours(24);
+
+        let first = Mutation::claim_idempotency_key(&db, "a@b.com", "retry-1", ttl)
+            .await
+            .unwrap();
+        let second = Mutation::claim_idempotency_key(&db, "a@b.com", "retry-1", ttl)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            first,
+            IdempotencyClaim::Claimed(record_id("a@b.com", "retry-1"))
+        );
+        assert_eq!(second, IdempotencyClaim::InFlight);
+    }
+
+    #[tokio::test]
+    async fn test_keys_are_scoped_to_owner() {
+        let db = setup().await;
+        let ttl = chrono::Duration::hours(24);
+
+        Mutation::claim_idempotency_key(&db, "a@b.com", "retry-1", ttl)
+            .await
+            .unwrap();
+        let other = Mutation::claim_idempotency_key(&db, "c@d.com", "retry-1", ttl)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            other,
+            IdempotencyClaim::Claimed(record_id("c@d.com", "retry-1"))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_expired_key_can_be_claimed_again() {
+        let db = setup().await;
+
+        let IdempotencyClaim::Claimed(record_id) =
+            Mutation::claim_idempotency_key(&db, "a@b.com", "retry-1", chrono::Duration::hours(24))
+                .await
+                .unwrap()
+        else {
+            panic!("expected a fresh claim");
+        };
+        Mutation::complete_idempotency_key(&db, record_id, "container-0".to_string())
+            .await
+            .unwrap();
+
+        let claim =
+            Mutation::claim_idempotency_key(&db, "a@b.com", "retry-1", chrono::Duration::zero())
+                .await
+                .unwrap();
+
+        assert!(matches!(claim, IdempotencyClaim::Claimed(_)));
+    }
+}
@@ -1,5 +1,7 @@
 // src/entities/mod.rs
+pub mod container_events;
 pub mod containers;
+pub mod idempotency_keys;
 pub mod namespaces;
 pub mod processors;
 pub mod secrets;
@@ -12,6 +12,11 @@ pub struct Model {
     pub owner: String,
     pub owner_ref: Option<String>,
     pub labels: Option<Json>,
+    pub default_env: Option<Json>,
+    pub default_user: Option<String>,
+    pub registry_secret: Option<String>,
+    pub default_platform: Option<String>,
+    pub default_accelerators: Option<Json>,
     pub created_by: String,
     pub updated_at: DateTimeWithTimeZone,
     pub created_at: DateTimeWithTimeZone,
@@ -39,12 +44,38 @@ impl Model {
             owner,
             owner_ref: None,
             labels,
+            default_env: None,
+            default_user: None,
+            registry_secret: None,
+            default_platform: None,
+            default_accelerators: None,
             created_by,
             updated_at: now,
             created_at: now,
         })
     }
 
+    /// Attempt to parse `default_env` into a vector of `V1EnvVar`.
+    pub fn parse_default_env(
+        &self,
+    ) -> Result<Option<Vec<crate::resources::v1::containers::models::V1EnvVar>>, serde_json::Error>
+    {
+        if let Some(json_value) = &self.default_env {
+            serde_json::from_value(json_value.clone()).map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Attempt to parse `default_accelerators` into a vector of accelerators.
+    pub fn parse_default_accelerators(&self) -> Result<Option<Vec<String>>, serde_json::Error> {
+        if let Some(json_value) = &self.default_accelerators {
+            serde_json::from_value(json_value.clone()).map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+
     pub fn to_v1(&self) -> crate::resources::v1::namespaces::models::V1Namespace {
         crate::resources::v1::namespaces::models::V1Namespace {
             kind: "Namespace".to_string(),
@@ -62,6 +93,11 @@ impl Model {
                 created_at: self.created_at.timestamp(),
                 updated_at: self.updated_at.timestamp(),
             },
+            default_env: self.parse_default_env().unwrap_or_default(),
+            default_user: self.default_user.clone(),
+            registry_secret: self.registry_secret.clone(),
+            default_platform: self.default_platform.clone(),
+            default_accelerators: self.parse_default_accelerators().unwrap_or_default(),
         }
     }
 }
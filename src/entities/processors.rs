@@ -26,6 +26,9 @@ pub struct Model {
     pub max_replicas: Option<i32>,
     pub desired_replicas: Option<i32>,
     pub stream: String,
+    pub stream_max_len: Option<i64>,
+    pub queue_backend: Option<String>,
+    pub warmup_message: Option<Json>,
     pub schema: Option<Json>,
     pub common_schema: Option<String>,
     pub status: Option<Json>,
@@ -34,6 +37,7 @@ pub struct Model {
     pub created_by: Option<String>,
     pub desired_status: Option<String>,
     pub controller_data: Option<Json>,
+    pub deleted_at: Option<DateTimeWithTimeZone>,
     pub updated_at: DateTimeWithTimeZone,
     pub created_at: DateTimeWithTimeZone,
 }
@@ -56,6 +60,7 @@ impl Model {
                     status: Some(status_str),
                     message: None,
                     pressure: None,
+                    ..Default::default()
                 }));
             }
 
@@ -129,15 +134,55 @@ impl Model {
             kind: "Processor".to_owned(), // or use default_container_kind() if needed
             metadata,
             stream: self.stream.clone(),
+            stream_max_len: self.stream_max_len.and_then(|n| u64::try_from(n).ok()),
+            queue_backend: self.queue_backend.clone(),
             schema: self.schema.clone(),
             common_schema: self.common_schema.clone(),
             min_replicas: self.min_replicas,
             max_replicas: self.max_replicas,
             scale,
+            warmup_message: self.warmup_message.clone(),
             container,
             status,
+            stream_info: None,
         };
 
         Ok(processor)
     }
 }
+
+#[cfg(test)]
+impl Model {
+    /// Processor `ns/name` with nothing else set, for tests to adjust
+    pub(crate) fn test_fixture() -> Self {
+        Self {
+            id: "p1".to_string(),
+            namespace: "ns".to_string(),
+            name: "name".to_string(),
+            full_name: "ns/name".to_string(),
+            labels: None,
+            owner: "me".to_string(),
+            container: None,
+            cluster: None,
+            scale: serde_json::Value::Null,
+            min_replicas: None,
+            max_replicas: None,
+            desired_replicas: None,
+            stream: "processor:ns:name".to_string(),
+            stream_max_len: None,
+            queue_backend: None,
+            warmup_message: None,
+            schema: None,
+            common_schema: None,
+            status: None,
+            resource_name: None,
+            resource_namespace: None,
+            created_by: None,
+            desired_status: None,
+            controller_data: None,
+            deleted_at: None,
+            updated_at: chrono::Utc::now().into(),
+            created_at: chrono::Utc::now().into(),
+        }
+    }
+}
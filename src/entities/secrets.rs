@@ -26,6 +26,8 @@ pub struct Model {
     pub updated_at: DateTimeWithTimeZone,
     pub created_at: DateTimeWithTimeZone,
     pub expires_at: Option<i32>,
+    #[sea_orm(default_value = 1)]
+    pub version: i32,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -33,11 +35,20 @@ pub enum Relation {}
 
 impl ActiveModelBehavior for ActiveModel {}
 
+/// What tests encrypt with, so none of them has to set `NEBU_ENCRYPTION_KEY`
+#[cfg(test)]
+pub const TEST_ENCRYPTION_KEY: &str = "0123456789abcdef0123456789abcdef";
+
 impl Model {
     // Get encryption key from environment
     fn get_encryption_key() -> Result<[u8; 32], String> {
-        let key = env::var("NEBU_ENCRYPTION_KEY")
-            .map_err(|_| "NEBU_ENCRYPTION_KEY environment variable not set".to_string())?;
+        let key = match env::var("NEBU_ENCRYPTION_KEY") {
+            Ok(key) => key,
+            #[cfg(test)]
+            Err(_) => TEST_ENCRYPTION_KEY.to_string(),
+            #[cfg(not(test))]
+            Err(_) => return Err("NEBU_ENCRYPTION_KEY environment variable not set".to_string()),
+        };
 
         // Ensure the key is exactly 32 bytes (256 bits)
         if key.len() != 32 {
@@ -129,6 +140,7 @@ impl Model {
             updated_at: now,
             created_at: now,
             expires_at,
+            version: 1,
         })
     }
 }
@@ -13,10 +13,111 @@
 //! [`thiserror`]: https://crates.io/crates/thiserror
 //! [#1116]: https://github.com/tokio-rs/axum/issues/1116#issuecomment-1186197684
 
-use axum::{extract::rejection::JsonRejection, response::IntoResponse, Json};
+use crate::logging::current_request_id;
+use axum::{extract::rejection::JsonRejection, http::StatusCode, response::IntoResponse, Json};
+use sea_orm::{DbErr, RuntimeErr, SqlErr};
 use serde_json::json;
+use std::future::Future;
+use std::time::Duration;
 use thiserror::Error;
 
+/// Attempts a read query gets before a transient failure is returned
+pub const DB_READ_ATTEMPTS: u32 = 3;
+
+/// Wait before the first retry of a read query, doubled for each one after
+const DB_RETRY_BACKOFF: Duration = Duration::from_millis(50);
+
+/// What kind of failure a database error is, which decides whether it's
+/// worth retrying and what status the caller sees.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DbErrorKind {
+    /// The connection failed or none could be had; retrying may succeed
+    Connection,
+    /// A unique or foreign key constraint rejected the write
+    Constraint,
+    NotFound,
+    Other,
+}
+
+impl DbErrorKind {
+    pub fn is_transient(self) -> bool {
+        self == DbErrorKind::Connection
+    }
+}
+
+/// Whether a Postgres SQLSTATE means the connection itself failed: class 08
+/// (connection exception) or the server shutting down (57P01-57P03).
+fn is_connection_sqlstate(code: &str) -> bool {
+    code.starts_with("08") || matches!(code, "57P01" | "57P02" | "57P03")
+}
+
+fn is_connection_runtime_err(err: &RuntimeErr) -> bool {
+    match err {
+        RuntimeErr::SqlxError(sqlx_err) => match sqlx_err {
+            sqlx::Error::Io(_)
+            | sqlx::Error::Tls(_)
+            | sqlx::Error::PoolTimedOut
+            | sqlx::Error::PoolClosed
+            | sqlx::Error::WorkerCrashed => true,
+            sqlx::Error::Database(db_err) => db_err
+                .code()
+                .is_some_and(|code| is_connection_sqlstate(&code)),
+            _ => false,
+        },
+        RuntimeErr::Internal(_) => false,
+    }
+}
+
+pub fn classify_db_error(err: &DbErr) -> DbErrorKind {
+    if let Some(SqlErr::UniqueConstraintViolation(_) | SqlErr::ForeignKeyConstraintViolation(_)) =
+        err.sql_err()
+    {
+        return DbErrorKind::Constraint;
+    }
+    match err {
+        DbErr::RecordNotFound(_) => DbErrorKind::NotFound,
+        DbErr::ConnectionAcquire(_) | DbErr::Conn(_) => DbErrorKind::Connection,
+        DbErr::Exec(runtime_err) | DbErr::Query(runtime_err)
+            if is_connection_runtime_err(runtime_err) =>
+        {
+            DbErrorKind::Connection
+        }
+        _ => DbErrorKind::Other,
+    }
+}
+
+/// Runs a read query, retrying it with backoff while it fails on a transient
+/// connection error. Writes shouldn't go through this: a write whose
+/// connection dropped may still have been applied.
+pub async fn retry_read<T, F, Fut>(mut query: F) -> Result<T, DbErr>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, DbErr>>,
+{
+    let mut backoff = DB_RETRY_BACKOFF;
+    let mut attempt = 1;
+    loop {
+        match query().await {
+            Err(e) if attempt < DB_READ_ATTEMPTS && classify_db_error(&e).is_transient() => {
+                tracing::warn!(
+                    "Transient database error (attempt {}/{}), retrying in {:?}: {}",
+                    attempt,
+                    DB_READ_ATTEMPTS,
+                    backoff,
+                    e
+                );
+                tokio::time::sleep(backoff).await;
+                backoff *= 2;
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Errors returned by API handlers. Every variant renders as
+/// `{"error": <message>, "code": <code>, "request_id": <id>}` with the
+/// status that fits it, so clients can rely on one shape.
 // We derive `thiserror::Error`
 #[derive(Debug, Error)]
 pub enum ApiError {
@@ -24,23 +125,332 @@ pub enum ApiError {
     // implementation. See `thiserror` docs for more information
     #[error(transparent)]
     JsonExtractorRejection(#[from] JsonRejection),
+
+    /// The request is invalid, e.g. it failed validation
+    #[error("{0}")]
+    BadRequest(String),
+
+    /// The caller isn't authenticated
+    #[error("{0}")]
+    Unauthorized(String),
+
+    /// The caller is authenticated but may not do this
+    #[error("{0}")]
+    Forbidden(String),
+
+    #[error("{0}")]
+    NotFound(String),
+
+    #[error("{0}")]
+    Conflict(String),
+
+    /// Waiting on something else (e.g. a processor's reply) took too long
+    #[error("{0}")]
+    Timeout(String),
+
+    #[error("Database error: {0}")]
+    Database(#[from] DbErr),
+
+    #[error("{0}")]
+    Internal(String),
+
+    /// An error from code that still builds `(StatusCode, Json)` tuples
+    #[error("{1}")]
+    Status(StatusCode, String),
+}
+
+/// The `code` for an error with `status` when no variant says otherwise
+fn code_for_status(status: StatusCode) -> &'static str {
+    match status {
+        StatusCode::BAD_REQUEST => "bad_request",
+        StatusCode::UNAUTHORIZED => "unauthorized",
+        StatusCode::FORBIDDEN => "forbidden",
+        StatusCode::NOT_FOUND => "not_found",
+        StatusCode::CONFLICT => "conflict",
+        StatusCode::REQUEST_TIMEOUT => "timeout",
+        StatusCode::UNPROCESSABLE_ENTITY => "invalid_body",
+        StatusCode::TOO_MANY_REQUESTS => "rate_limited",
+        StatusCode::SERVICE_UNAVAILABLE => "unavailable",
+        _ => "internal",
+    }
+}
+
+impl ApiError {
+    pub fn status(&self) -> StatusCode {
+        match self {
+            ApiError::JsonExtractorRejection(rejection) => rejection.status(),
+            ApiError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            ApiError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            ApiError::Forbidden(_) => StatusCode::FORBIDDEN,
+            ApiError::NotFound(_) => StatusCode::NOT_FOUND,
+            ApiError::Conflict(_) => StatusCode::CONFLICT,
+            ApiError::Timeout(_) => StatusCode::REQUEST_TIMEOUT,
+            ApiError::Database(e) => match classify_db_error(e) {
+                DbErrorKind::NotFound => StatusCode::NOT_FOUND,
+                DbErrorKind::Constraint => StatusCode::CONFLICT,
+                DbErrorKind::Connection => StatusCode::SERVICE_UNAVAILABLE,
+                DbErrorKind::Other => StatusCode::INTERNAL_SERVER_ERROR,
+            },
+            ApiError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            ApiError::Status(status, _) => *status,
+        }
+    }
+
+    /// Machine readable kind of the error, stable across message changes
+    pub fn code(&self) -> &'static str {
+        match self {
+            ApiError::JsonExtractorRejection(_) => "invalid_body",
+            ApiError::Database(e) if classify_db_error(e) == DbErrorKind::Other => "database_error",
+            other => code_for_status(other.status()),
+        }
+    }
+
+    fn message(&self) -> String {
+        match self {
+            ApiError::JsonExtractorRejection(rejection) => rejection.body_text(),
+            other => other.to_string(),
+        }
+    }
+}
+
+impl From<(StatusCode, Json<serde_json::Value>)> for ApiError {
+    fn from((status, Json(body)): (StatusCode, Json<serde_json::Value>)) -> Self {
+        let message = match body.get("error").and_then(|e| e.as_str()) {
+            Some(error) => error.to_string(),
+            None => body.to_string(),
+        };
+        ApiError::Status(status, message)
+    }
 }
 
 // We implement `IntoResponse` so ApiError can be used as a response
 impl IntoResponse for ApiError {
     fn into_response(self) -> axum::response::Response {
-        let (status, message) = match self {
-            ApiError::JsonExtractorRejection(json_rejection) => {
-                (json_rejection.status(), json_rejection.body_text())
+        let status = self.status();
+        let message = self.message();
+        match &self {
+            ApiError::JsonExtractorRejection(_) => {
+                tracing::error!("{} JSON Extraction Error: {}", status.as_u16(), message)
             }
-        };
-        tracing::error!("422 JSON Extraction Error: {}", message);
+            _ if status.is_server_error() => tracing::error!("{}: {}", status, message),
+            _ => tracing::debug!("{}: {}", status, message),
+        }
 
         let payload = json!({
-            "message": message,
-            "origin": "with_rejection"
+            "error": message,
+            "code": self.code(),
+            "request_id": current_request_id(),
         });
 
         (status, Json(payload)).into_response()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::logging::REQUEST_ID;
+
+    async fn body_of(error: ApiError) -> (StatusCode, serde_json::Value) {
+        let response = error.into_response();
+        let status = response.status();
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, serde_json::from_slice(&bytes).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_error_body_shape() {
+        // Arrange
+        let error = ApiError::BadRequest("Invalid name: too long".to_string());
+
+        // Act
+        let (status, body) = REQUEST_ID
+            .scope("req-123".to_string(), body_of(error))
+            .await;
+
+        // Assert
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(
+            body,
+            json!({
+                "error": "Invalid name: too long",
+                "code": "bad_request",
+                "request_id": "req-123",
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_statuses_and_codes() {
+        let cases = [
+            (
+                ApiError::Unauthorized("Authentication token missing".to_string()),
+                StatusCode::UNAUTHORIZED,
+                "unauthorized",
+            ),
+            (
+                ApiError::NotFound("Processor not found".to_string()),
+                StatusCode::NOT_FOUND,
+                "not_found",
+            ),
+            (
+                ApiError::Timeout("Timed out".to_string()),
+                StatusCode::REQUEST_TIMEOUT,
+                "timeout",
+            ),
+            (
+                ApiError::Database(DbErr::Custom("connection reset".to_string())),
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "database_error",
+            ),
+            (
+                ApiError::Database(DbErr::RecordNotFound("processor".to_string())),
+                StatusCode::NOT_FOUND,
+                "not_found",
+            ),
+        ];
+
+        for (error, expected_status, expected_code) in cases {
+            let message = error.to_string();
+            let (status, body) = body_of(error).await;
+            assert_eq!(status, expected_status);
+            assert_eq!(body["code"], expected_code);
+            assert_eq!(body["error"], message);
+            // Outside a request there's no id, but the key is always there
+            assert_eq!(body["request_id"], serde_json::Value::Null);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_tuple_errors_convert() {
+        let error: ApiError = (
+            StatusCode::CONFLICT,
+            Json(json!({"error": "Processor already exists"})),
+        )
+            .into();
+
+        let (status, body) = body_of(error).await;
+
+        assert_eq!(status, StatusCode::CONFLICT);
+        assert_eq!(body["error"], "Processor already exists");
+        assert_eq!(body["code"], "conflict");
+    }
+
+    #[test]
+    fn test_classify_db_errors() {
+        let io = std::io::Error::new(std::io::ErrorKind::ConnectionReset, "reset by peer");
+        let cases = [
+            (
+                DbErr::ConnectionAcquire(sea_orm::error::ConnAcquireErr::Timeout),
+                DbErrorKind::Connection,
+            ),
+            (
+                DbErr::Conn(RuntimeErr::Internal("refused".to_string())),
+                DbErrorKind::Connection,
+            ),
+            (
+                DbErr::Query(RuntimeErr::SqlxError(sqlx::Error::Io(io))),
+                DbErrorKind::Connection,
+            ),
+            (
+                DbErr::Exec(RuntimeErr::SqlxError(sqlx::Error::PoolTimedOut)),
+                DbErrorKind::Connection,
+            ),
+            (
+                DbErr::Query(RuntimeErr::SqlxError(sqlx::Error::RowNotFound)),
+                DbErrorKind::Other,
+            ),
+            (
+                DbErr::RecordNotFound("processor".to_string()),
+                DbErrorKind::NotFound,
+            ),
+            (DbErr::Custom("bad input".to_string()), DbErrorKind::Other),
+        ];
+
+        for (err, expected) in cases {
+            assert_eq!(classify_db_error(&err), expected, "{}", err);
+        }
+        assert!(is_connection_sqlstate("08006"));
+        assert!(is_connection_sqlstate("57P01"));
+        assert!(!is_connection_sqlstate("23505"));
+    }
+
+    #[tokio::test]
+    async fn test_constraint_violation_is_a_conflict() {
+        use crate::entities::namespaces;
+        use sea_orm::{ActiveModelTrait, ActiveValue::Set, ConnectionTrait, Database, Schema};
+
+        let db = Database::connect("sqlite::memory:").await.unwrap();
+        let schema = Schema::new(db.get_database_backend());
+        db.execute(
+            db.get_database_backend()
+                .build(&schema.create_table_from_entity(namespaces::Entity)),
+        )
+        .await
+        .unwrap();
+        let namespace = || namespaces::ActiveModel {
+            id: Set("ns-1".to_string()),
+            name: Set("team".to_string()),
+            owner: Set("me".to_string()),
+            owner_ref: Set(None),
+            labels: Set(None),
+            default_env: Set(None),
+            default_user: Set(None),
+            registry_secret: Set(None),
+            default_platform: Set(None),
+            default_accelerators: Set(None),
+            created_by: Set("me".to_string()),
+            updated_at: Set(chrono::Utc::now().into()),
+            created_at: Set(chrono::Utc::now().into()),
+        };
+        namespace().insert(&db).await.unwrap();
+
+        let err = namespace().insert(&db).await.unwrap_err();
+
+        assert_eq!(classify_db_error(&err), DbErrorKind::Constraint);
+        let error = ApiError::Database(err);
+        assert_eq!(error.status(), StatusCode::CONFLICT);
+        assert_eq!(error.code(), "conflict");
+    }
+
+    #[tokio::test]
+    async fn test_retry_read_retries_only_transient_errors() {
+        use std::sync::atomic::{AtomicU32, Ordering};
+
+        let attempts = AtomicU32::new(0);
+        let counter = &attempts;
+        let recovered = retry_read(|| async move {
+            match counter.fetch_add(1, Ordering::SeqCst) {
+                0 | 1 => Err(DbErr::Conn(RuntimeErr::Internal("dropped".to_string()))),
+                n => Ok(n),
+            }
+        })
+        .await;
+        assert_eq!(recovered.unwrap(), 2);
+
+        let attempts = AtomicU32::new(0);
+        let counter = &attempts;
+        let not_found: Result<(), DbErr> = retry_read(|| async move {
+            counter.fetch_add(1, Ordering::SeqCst);
+            Err(DbErr::RecordNotFound("processor".to_string()))
+        })
+        .await;
+        assert!(not_found.is_err());
+        assert_eq!(attempts.into_inner(), 1);
+
+        let attempts = AtomicU32::new(0);
+        let counter = &attempts;
+        let down: Result<(), DbErr> = retry_read(|| async move {
+            counter.fetch_add(1, Ordering::SeqCst);
+            Err(DbErr::Conn(RuntimeErr::Internal("down".to_string())))
+        })
+        .await;
+        assert_eq!(
+            ApiError::Database(down.unwrap_err()).status(),
+            StatusCode::SERVICE_UNAVAILABLE
+        );
+        assert_eq!(attempts.into_inner(), DB_READ_ATTEMPTS);
+    }
+}
@@ -1,5 +1,15 @@
-use axum::{response::IntoResponse, Json};
-use serde_json::json;
+use crate::proxy::meter_breaker::METER_INGEST;
+use crate::resources::v1::containers::controller::PLATFORM_OPERATIONS;
+use crate::state::{AppState, MessageQueue};
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use rdkafka::producer::Producer;
+use sea_orm::ConnectionTrait;
+use serde_json::{json, Value};
+use std::time::Duration;
+use tracing::warn;
+
+/// How long a single dependency check may take before it counts as down
+const READINESS_CHECK_TIMEOUT: Duration = Duration::from_secs(3);
 
 pub async fn root_handler() -> impl IntoResponse {
     let response = json!({
@@ -11,6 +21,7 @@ pub async fn root_handler() -> impl IntoResponse {
     Json(response)
 }
 
+/// Liveness: the process is up and serving requests. Touches no dependencies.
 pub async fn health_handler() -> impl IntoResponse {
     let response = json!({
         "status": "ok",
@@ -18,3 +29,208 @@ pub async fn health_handler() -> impl IntoResponse {
     });
     Json(response)
 }
+
+/// Readiness: the database and the message queue are reachable. Responds 503
+/// with the status of each dependency when any of them isn't. The endpoint is
+/// unauthenticated, so why a check failed is only logged.
+pub async fn ready_handler(State(state): State<AppState>) -> impl IntoResponse {
+    let (database, message_queue) =
+        tokio::join!(check_database(&state), check_message_queue(&state));
+
+    let ready = database.is_ok() && message_queue.is_ok();
+    let response = json!({
+        "status": if ready { "ready" } else { "unavailable" },
+        "timestamp": chrono::Utc::now().to_rfc3339(),
+        "checks": {
+            "database": check_status("database", database),
+            "message_queue": check_status("message queue", message_queue),
+        },
+    });
+    let status = if ready {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (status, Json(response))
+}
+
+fn check_status(dependency: &str, result: Result<(), String>) -> Value {
+    match result {
+        Ok(()) => json!({"status": "ok"}),
+        Err(e) => {
+            warn!("Readiness check of the {} failed: {}", dependency, e);
+            json!({"status": "error"})
+        }
+    }
+}
+
+async fn check_database(state: &AppState) -> Result<(), String> {
+    match tokio::time::timeout(
+        READINESS_CHECK_TIMEOUT,
+        state.db_pool.execute_unprepared("SELECT 1"),
+    )
+    .await
+    {
+        Ok(Ok(_)) => Ok(()),
+        Ok(Err(e)) => Err(e.to_string()),
+        Err(_) => Err("timed out".to_string()),
+    }
+}
+
+async fn check_message_queue(state: &AppState) -> Result<(), String> {
+    let check = async {
+        match &state.message_queue {
+            MessageQueue::Redis { client } => {
+                let mut conn = client
+                    .get_multiplexed_async_connection()
+                    .await
+                    .map_err(|e| e.to_string())?;
+                redis::cmd("PING")
+                    .query_async::<String>(&mut conn)
+                    .await
+                    .map(|_| ())
+                    .map_err(|e| e.to_string())
+            }
+            MessageQueue::Kafka { producer, .. } => {
+                let producer = producer.clone();
+                tokio::task::spawn_blocking(move || {
+                    producer
+                        .client()
+                        .fetch_metadata(None, READINESS_CHECK_TIMEOUT)
+                        .map(|_| ())
+                        .map_err(|e| e.to_string())
+                })
+                .await
+                .map_err(|e| e.to_string())?
+            }
+        }
+    };
+    tokio::time::timeout(READINESS_CHECK_TIMEOUT, check)
+        .await
+        .unwrap_or_else(|_| Err("timed out".to_string()))
+}
+
+/// Prometheus text exposition of the reconcile work pool and the OpenMeter
+/// circuit breaker.
+pub async fn metrics_handler() -> impl IntoResponse {
+    let pool = &*PLATFORM_OPERATIONS;
+    let meters = METER_INGEST.stats();
+    let body = format!(
+        "# HELP nebu_reconcile_queue_depth Container platform operations waiting for a slot\n\
+         # TYPE nebu_reconcile_queue_depth gauge\n\
+         nebu_reconcile_queue_depth {}\n\
+         # HELP nebu_reconcile_in_flight Container platform operations running\n\
+         # TYPE nebu_reconcile_in_flight gauge\n\
+         nebu_reconcile_in_flight {}\n\
+         # HELP nebu_reconcile_concurrency_limit Most container platform operations run at once\n\
+         # TYPE nebu_reconcile_concurrency_limit gauge\n\
+         nebu_reconcile_concurrency_limit {}\n\
+         # HELP nebu_openmeter_breaker_state OpenMeter ingest breaker, 0 closed, 1 open, 2 half-open\n\
+         # TYPE nebu_openmeter_breaker_state gauge\n\
+         nebu_openmeter_breaker_state {}\n\
+         # HELP nebu_openmeter_consecutive_failures OpenMeter ingests failed since the last success\n\
+         # TYPE nebu_openmeter_consecutive_failures gauge\n\
+         nebu_openmeter_consecutive_failures {}\n\
+         # HELP nebu_openmeter_spooled_events Meter events held back while the breaker is open\n\
+         # TYPE nebu_openmeter_spooled_events gauge\n\
+         nebu_openmeter_spooled_events {}\n\
+         # HELP nebu_openmeter_dropped_events_total Meter events dropped because the spool was full\n\
+         # TYPE nebu_openmeter_dropped_events_total counter\n\
+         nebu_openmeter_dropped_events_total {}\n\
+         # HELP nebu_openmeter_rejected_events_total Meter events dropped because OpenMeter refused them\n\
+         # TYPE nebu_openmeter_rejected_events_total counter\n\
+         nebu_openmeter_rejected_events_total {}\n",
+        pool.queue_depth(),
+        pool.in_flight(),
+        pool.limit(),
+        meters.state.gauge(),
+        meters.consecutive_failures,
+        meters.spooled,
+        meters.dropped,
+        meters.rejected
+    );
+    (
+        [(
+            axum::http::header::CONTENT_TYPE,
+            "text/plain; version=0.0.4",
+        )],
+        body,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::response::Response;
+    use sea_orm::{Database, DatabaseConnection};
+    use std::sync::Arc;
+
+    /// A Redis client pointing at a port nothing listens on
+    fn unreachable_redis() -> MessageQueue {
+        MessageQueue::Redis {
+            client: Arc::new(redis::Client::open("redis://127.0.0.1:1").unwrap()),
+        }
+    }
+
+    async fn body_json(response: Response) -> Value {
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        serde_json::from_slice(&bytes).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_ready_reports_unreachable_message_queue() {
+        // Arrange
+        let state = AppState {
+            db_pool: Database::connect("sqlite::memory:").await.unwrap(),
+            message_queue: unreachable_redis(),
+            extra_queues: Default::default(),
+        };
+
+        // Act
+        let response = ready_handler(State(state)).await.into_response();
+
+        // Assert
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        let body = body_json(response).await;
+        assert_eq!(body["status"], "unavailable");
+        assert_eq!(body["checks"]["database"]["status"], "ok");
+        assert_eq!(body["checks"]["message_queue"]["status"], "error");
+        // Connection details stay in the logs
+        assert_eq!(body["checks"]["message_queue"], json!({"status": "error"}));
+    }
+
+    #[tokio::test]
+    async fn test_ready_reports_unreachable_database() {
+        let state = AppState {
+            db_pool: DatabaseConnection::Disconnected,
+            message_queue: unreachable_redis(),
+            extra_queues: Default::default(),
+        };
+
+        let response = ready_handler(State(state)).await.into_response();
+
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        let body = body_json(response).await;
+        assert_eq!(body["checks"]["database"]["status"], "error");
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a Redis at NEBU_TEST_REDIS_URL"]
+    async fn test_ready_when_dependencies_are_up() {
+        let url = std::env::var("NEBU_TEST_REDIS_URL").expect("NEBU_TEST_REDIS_URL is not set");
+        let state = AppState {
+            db_pool: Database::connect("sqlite::memory:").await.unwrap(),
+            message_queue: MessageQueue::Redis {
+                client: Arc::new(redis::Client::open(url).unwrap()),
+            },
+            extra_queues: Default::default(),
+        };
+
+        let response = ready_handler(State(state)).await.into_response();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(body_json(response).await["status"], "ready");
+    }
+}
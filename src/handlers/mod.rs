@@ -1,5 +1,5 @@
 // src/handlers/mod.rs
 
 pub mod basic;
-pub use basic::{health_handler, root_handler};
+pub use basic::{health_handler, metrics_handler, ready_handler, root_handler};
 pub mod v1;
@@ -0,0 +1,66 @@
+// src/handlers/v1/accelerators.rs
+
+use crate::accelerator::base::Config;
+use crate::resources::v1::containers::datacenters::DatacenterClient;
+use crate::resources::v1::containers::models::V1AcceleratorOfferings;
+use crate::resources::v1::containers::pricing::{
+    accelerator_catalog, catalog_provider, CATALOG_PLATFORMS,
+};
+use axum::{extract::Query as QueryParams, http::StatusCode, Json};
+use serde::Deserialize;
+use serde_json::json;
+use tracing::warn;
+
+#[derive(Deserialize, Debug)]
+pub struct AcceleratorParams {
+    platform: Option<String>,
+}
+
+/// Handler: List the supported accelerators per platform with their current
+/// price and stock. Without pricing the accelerators are still listed.
+pub async fn list_accelerators(
+    QueryParams(params): QueryParams<AcceleratorParams>,
+) -> Result<Json<V1AcceleratorOfferings>, (StatusCode, Json<serde_json::Value>)> {
+    let platforms: Vec<&str> = match params.platform.as_deref() {
+        Some(platform) if catalog_provider(platform).is_none() => {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                Json(json!({
+                    "error": format!(
+                        "Unknown platform '{}', expected one of: {}",
+                        platform,
+                        CATALOG_PLATFORMS.join(", ")
+                    )
+                })),
+            ))
+        }
+        Some(platform) => vec![platform],
+        None => CATALOG_PLATFORMS.to_vec(),
+    };
+
+    let runpod_pricing = match DatacenterClient::from_env() {
+        Some(client) if platforms.contains(&"runpod") => {
+            client.cached_gpu_types().await.unwrap_or_else(|e| {
+                warn!("Failed to fetch accelerator pricing: {}", e);
+                Vec::new()
+            })
+        }
+        _ => Vec::new(),
+    };
+
+    let config = Config::default();
+    let mut accelerators = Vec::new();
+    for platform in platforms {
+        let Some(provider) = catalog_provider(platform) else {
+            continue;
+        };
+        let pricing = if platform == "runpod" {
+            runpod_pricing.as_slice()
+        } else {
+            &[]
+        };
+        accelerators.extend(accelerator_catalog(&config, provider.as_ref(), pricing));
+    }
+
+    Ok(Json(V1AcceleratorOfferings { accelerators }))
+}
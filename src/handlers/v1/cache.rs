@@ -1,194 +1,159 @@
 use crate::models::V1UserProfile;
 use crate::query::Query;
 use crate::state::{AppState, MessageQueue};
+use crate::utils::namespace::resolve_namespace;
 use axum::{
     extract::{Extension, Path, Query as QueryParam, State},
-    http::StatusCode,
+    http::header::{ETAG, IF_MATCH, IF_NONE_MATCH},
+    http::{HeaderMap, HeaderName, StatusCode},
     response::IntoResponse,
     Json,
 };
-use futures::stream::{self, StreamExt};
+use redis::aio::MultiplexedConnection;
 use redis::AsyncCommands;
-use serde::Deserialize;
+use sea_orm::DatabaseConnection;
+use serde::{Deserialize, Serialize};
 use serde_json::json;
 use std::collections::HashSet;
 use tracing::{debug, error, info};
 
-#[derive(Deserialize, Debug)]
+/// Keys returned per page unless `limit` says otherwise
+const DEFAULT_CACHE_KEY_LIMIT: usize = 100;
+const MAX_CACHE_KEY_LIMIT: usize = 1000;
+/// `SCAN` calls one page makes at most, so a page of sparse matches can't
+/// walk the whole keyspace in one request
+const MAX_SCAN_ROUNDS: usize = 20;
+
+#[derive(Deserialize, Debug, Default)]
 pub struct CacheKeyParams {
+    /// Defaults to every namespace the caller can access
     namespace: Option<String>,
+    /// Only keys starting with this, after the `cache:<namespace>:` part
+    prefix: Option<String>,
+    /// `next_cursor` of the previous page, absent for the first
+    cursor: Option<String>,
+    limit: Option<usize>,
 }
 
-/// Handler: List cache keys for a given namespace or all accessible namespaces.
-pub async fn list_cache_keys(
-    State(state): State<AppState>,
-    Extension(user_profile): Extension<V1UserProfile>,
-    QueryParam(params): QueryParam<CacheKeyParams>,
-) -> Result<Json<Vec<String>>, (StatusCode, Json<serde_json::Value>)> {
-    let db_pool = &state.db_pool;
-    let requested_namespace = params.namespace;
-
-    info!(
-        "Listing cache keys (namespace: {:?}) requested by user: {}",
-        requested_namespace, user_profile.email
-    );
+/// A page of cache keys. Pass `next_cursor` back as `cursor` for the next
+/// one; it's absent on the last page. A page may come back short, even
+/// empty, before the last one.
+#[derive(Serialize, Debug, Default, PartialEq)]
+pub struct V1CacheKeys {
+    pub keys: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next_cursor: Option<String>,
+}
 
-    // 1. Get Redis client from AppState
-    let redis_client = match &state.message_queue {
-        MessageQueue::Redis { client } => client.clone(),
-        _ => {
-            error!("Redis client not available in AppState. Cache operations require Redis.");
-            return Err((
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(json!({ "error": "Redis client not configured on the server." })),
-            ));
+/// Escapes the characters Redis `MATCH` patterns treat specially
+fn escape_glob(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        if matches!(c, '*' | '?' | '[' | ']' | '\\') {
+            escaped.push('\\');
         }
-    };
+        escaped.push(c);
+    }
+    escaped
+}
 
-    let mut all_keys = HashSet::new(); // Use HashSet to avoid duplicates
+/// The `MATCH` pattern for keys of `namespace` starting with `prefix`
+pub fn cache_key_pattern(namespace: &str, prefix: &str) -> String {
+    format!("cache:{}:{}*", escape_glob(namespace), escape_glob(prefix))
+}
 
-    // Determine which namespaces to scan
-    let namespaces_to_scan: Vec<String> = if let Some(ns) = requested_namespace {
-        // If a specific namespace is requested, just use that one
-        info!("Scanning specific namespace: {}", ns);
-        vec![ns]
-    } else {
-        // If no namespace is requested, find all namespaces the user has access to
-        info!(
-            "No specific namespace provided. Fetching all accessible namespaces for user {}",
-            user_profile.email
-        );
-        let mut owner_ids: Vec<String> = user_profile
-            .organizations
-            .as_ref()
-            .map(|orgs| orgs.keys().cloned().collect())
-            .unwrap_or_default();
-        owner_ids.push(user_profile.email.clone());
-        let owner_id_refs: Vec<&str> = owner_ids.iter().map(|s| s.as_str()).collect();
-
-        match Query::find_namespaces_by_owners(db_pool, &owner_id_refs).await {
-            Ok(namespaces) => {
-                let ns_names: Vec<String> = namespaces.into_iter().map(|n| n.id).collect();
-                info!(
-                    "Found {} accessible namespaces: {:?}",
-                    ns_names.len(),
-                    ns_names
-                );
-                ns_names
-            }
-            Err(e) => {
-                error!(
-                    "Failed to query namespaces for owners {:?}: {}",
-                    owner_id_refs, e
-                );
-                return Err((
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    Json(json!({ "error": "Failed to retrieve accessible namespaces." })),
-                ));
-            }
-        }
-    };
+/// The keys a listing covers: those in `namespaces` starting with `prefix`
+#[derive(Debug, Clone, PartialEq)]
+pub struct CacheKeyFilter {
+    pub namespaces: HashSet<String>,
+    pub prefix: String,
+}
 
-    // 2. Scan Redis for keys in the determined namespaces concurrently
-    let results = stream::iter(namespaces_to_scan)
-        .map(|ns| {
-            let client = redis_client.clone();
-            async move {
-                let pattern = format!("cache:{}:*", ns);
-                debug!("Scanning Redis with pattern: {}", pattern);
-                let mut keys_for_ns = Vec::new();
-                let mut conn = match client.get_multiplexed_async_connection().await {
-                    Ok(conn) => conn,
-                    Err(e) => {
-                        error!("Failed to get Redis connection for namespace {}: {}", ns, e);
-                        return Err(format!("Redis connection failed for namespace {}", ns));
-                    }
-                };
-                let mut iter: redis::AsyncIter<String> = match conn.scan_match(&pattern).await {
-                    Ok(iter) => iter,
-                    Err(e) => {
-                        error!("Redis SCAN failed for pattern {}: {}", pattern, e);
-                        return Err(format!("Redis SCAN failed for namespace {}", ns));
-                    }
-                };
-                while let Some(key) = iter.next_item().await {
-                    keys_for_ns.push(key);
-                }
-                debug!("Found {} keys for namespace {}", keys_for_ns.len(), ns);
-                Ok(keys_for_ns)
-            }
-        })
-        .buffer_unordered(10) // Allow up to 10 scans to run concurrently
-        .collect::<Vec<Result<Vec<String>, String>>>()
-        .await;
-
-    // 3. Aggregate results and handle errors
-    for result in results {
-        match result {
-            Ok(keys) => {
-                for key in keys {
-                    all_keys.insert(key);
-                }
-            }
-            Err(e) => {
-                // Log the error, but continue aggregating results from other namespaces
-                error!("Error during Redis scan: {}", e);
-                // Optionally, you could return an error here if any scan fails
-                // return Err((StatusCode::INTERNAL_SERVER_ERROR, Json(json!({ "error": e }))));
-            }
+impl CacheKeyFilter {
+    /// The `MATCH` pattern to scan with; across namespaces when there's more
+    /// than one, narrowed further by `matches`
+    pub fn pattern(&self) -> String {
+        match self.namespaces.iter().collect::<Vec<_>>().as_slice() {
+            [namespace] => cache_key_pattern(namespace, &self.prefix),
+            _ => format!("cache:*:{}*", escape_glob(&self.prefix)),
         }
     }
 
-    let final_keys: Vec<String> = all_keys.into_iter().collect();
-    info!(
-        "Found {} total unique cache keys across scanned namespaces",
-        final_keys.len()
-    );
-
-    // 4. Return the aggregated keys
-    Ok(Json(final_keys))
+    pub fn matches(&self, key: &str) -> bool {
+        key.strip_prefix("cache:")
+            .and_then(|rest| rest.split_once(':'))
+            .is_some_and(|(namespace, key)| {
+                self.namespaces.contains(namespace) && key.starts_with(&self.prefix)
+            })
+    }
 }
 
-/// Handler: Get a specific cache key's value
-pub async fn get_cache_key(
-    State(state): State<AppState>,
-    Extension(user_profile): Extension<V1UserProfile>,
-    Path((namespace, key_suffix)): Path<(String, String)>,
-) -> Result<Json<String>, (StatusCode, Json<serde_json::Value>)> {
-    let db_pool = &state.db_pool;
-    info!(
-        "Getting cache key suffix '{}' in namespace '{}' requested by user: {}",
-        key_suffix, namespace, user_profile.email
-    );
+/// Runs `SCAN` from `cursor` until at least `limit` keys passing `filter`
+/// are found, the keyspace is exhausted or `max_rounds` scans were made.
+/// Returns the keys and the cursor to continue from, 0 when done. `SCAN`
+/// works in batches, so a page can hold a few more than `limit` keys, and a
+/// key may show up on two pages if it's written while scanning.
+pub async fn scan_cache_keys<C: redis::aio::ConnectionLike>(
+    conn: &mut C,
+    filter: &CacheKeyFilter,
+    cursor: u64,
+    limit: usize,
+    max_rounds: usize,
+) -> redis::RedisResult<(Vec<String>, u64)> {
+    let pattern = filter.pattern();
+    let mut keys = Vec::new();
+    let mut cursor = cursor;
+    for _ in 0..max_rounds {
+        let (next, batch): (u64, Vec<String>) = redis::cmd("SCAN")
+            .arg(cursor)
+            .arg("MATCH")
+            .arg(&pattern)
+            .arg("COUNT")
+            .arg(limit.max(10))
+            .query_async(conn)
+            .await?;
+        keys.extend(batch.into_iter().filter(|key| filter.matches(key)));
+        cursor = next;
+        if cursor == 0 || keys.len() >= limit {
+            break;
+        }
+    }
+    Ok((keys, cursor))
+}
 
-    // 1. Verify user access to the namespace
-    let mut owner_ids: Vec<String> = user_profile
-        .organizations
-        .as_ref()
-        .map(|orgs| orgs.keys().cloned().collect())
-        .unwrap_or_default();
-    owner_ids.push(user_profile.email.clone());
+/// The namespaces the user owns, directly or through an organization
+async fn accessible_cache_namespaces(
+    db_pool: &DatabaseConnection,
+    user_profile: &V1UserProfile,
+) -> Result<HashSet<String>, (StatusCode, Json<serde_json::Value>)> {
+    let owner_ids = user_profile.owner_ids();
     let owner_id_refs: Vec<&str> = owner_ids.iter().map(|s| s.as_str()).collect();
 
-    let accessible_namespaces =
-        match Query::find_namespaces_by_owners(db_pool, &owner_id_refs).await {
-            Ok(namespaces) => namespaces
-                .into_iter()
-                .map(|n| n.id)
-                .collect::<HashSet<String>>(),
-            Err(e) => {
-                error!(
-                    "Failed to query namespaces for owners {:?}: {}",
-                    owner_id_refs, e
-                );
-                return Err((
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    Json(json!({ "error": "Failed to retrieve accessible namespaces." })),
-                ));
-            }
-        };
+    match Query::find_namespaces_by_owners(db_pool, &owner_id_refs).await {
+        Ok(namespaces) => Ok(namespaces.into_iter().map(|n| n.id).collect()),
+        Err(e) => {
+            error!(
+                "Failed to query namespaces for owners {:?}: {}",
+                owner_id_refs, e
+            );
+            Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({ "error": "Failed to retrieve accessible namespaces." })),
+            ))
+        }
+    }
+}
 
-    if !accessible_namespaces.contains(&namespace) {
+/// Checks the user owns `namespace`, directly or through an organization
+async fn authorize_cache_namespace(
+    db_pool: &DatabaseConnection,
+    user_profile: &V1UserProfile,
+    namespace: &str,
+) -> Result<(), (StatusCode, Json<serde_json::Value>)> {
+    if !accessible_cache_namespaces(db_pool, user_profile)
+        .await?
+        .contains(namespace)
+    {
         error!(
             "User {} does not have access to namespace '{}'",
             user_profile.email, namespace
@@ -198,42 +163,294 @@ pub async fn get_cache_key(
             Json(json!({ "error": "Access denied to the specified namespace." })),
         ));
     }
+    Ok(())
+}
+
+/// The keys `params` asks for: those of its `namespace` if the user can
+/// access it, otherwise of all the namespaces they can
+async fn cache_key_filter(
+    db_pool: &DatabaseConnection,
+    user_profile: &V1UserProfile,
+    params: &CacheKeyParams,
+) -> Result<CacheKeyFilter, (StatusCode, Json<serde_json::Value>)> {
+    let namespaces = match params.namespace.as_deref() {
+        Some(namespace) => {
+            let namespace = resolve_namespace(namespace, user_profile);
+            authorize_cache_namespace(db_pool, user_profile, &namespace).await?;
+            HashSet::from([namespace])
+        }
+        None => accessible_cache_namespaces(db_pool, user_profile).await?,
+    };
+    Ok(CacheKeyFilter {
+        namespaces,
+        prefix: params.prefix.clone().unwrap_or_default(),
+    })
+}
+
+fn scan_failed(e: redis::RedisError) -> (StatusCode, Json<serde_json::Value>) {
+    error!("Redis SCAN failed: {}", e);
+    (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        Json(json!({ "error": "Failed to execute Redis SCAN command." })),
+    )
+}
 
-    // 2. Get Redis client
+/// A connection to the Redis the cache lives in
+async fn cache_connection(
+    state: &AppState,
+) -> Result<MultiplexedConnection, (StatusCode, Json<serde_json::Value>)> {
     let redis_client = match &state.message_queue {
         MessageQueue::Redis { client } => client.clone(),
         _ => {
-            error!("Redis client not available in AppState.");
+            error!("Redis client not available in AppState. Cache operations require Redis.");
             return Err((
                 StatusCode::INTERNAL_SERVER_ERROR,
-                Json(json!({ "error": "Redis client not configured." })),
+                Json(json!({ "error": "Redis client not configured on the server." })),
             ));
         }
     };
 
-    // 3. Get Redis connection
-    let mut conn = match redis_client.get_multiplexed_async_connection().await {
-        Ok(conn) => conn,
-        Err(e) => {
+    redis_client
+        .get_multiplexed_async_connection()
+        .await
+        .map_err(|e| {
             error!("Failed to get async Redis connection: {}", e);
-            return Err((
+            (
                 StatusCode::INTERNAL_SERVER_ERROR,
                 Json(json!({ "error": "Failed to connect to Redis." })),
-            ));
+            )
+        })
+}
+
+/// Handler: List all cache keys in a namespace, or in every namespace the
+/// caller can access. `/v1/cache/pages` lists them a page at a time.
+pub async fn list_cache_keys(
+    State(state): State<AppState>,
+    Extension(user_profile): Extension<V1UserProfile>,
+    QueryParam(params): QueryParam<CacheKeyParams>,
+) -> Result<Json<Vec<String>>, (StatusCode, Json<serde_json::Value>)> {
+    info!(
+        "Listing cache keys (namespace: {:?}, prefix: {:?}) requested by user: {}",
+        params.namespace, params.prefix, user_profile.email
+    );
+    let filter = cache_key_filter(&state.db_pool, &user_profile, &params).await?;
+    let mut conn = cache_connection(&state).await?;
+
+    let mut all_keys = HashSet::new();
+    let mut cursor = 0;
+    loop {
+        let (keys, next) = scan_cache_keys(
+            &mut conn,
+            &filter,
+            cursor,
+            MAX_CACHE_KEY_LIMIT,
+            MAX_SCAN_ROUNDS,
+        )
+        .await
+        .map_err(scan_failed)?;
+        all_keys.extend(keys);
+        if next == 0 {
+            break;
+        }
+        cursor = next;
+    }
+
+    info!("Found {} cache keys", all_keys.len());
+    Ok(Json(all_keys.into_iter().collect()))
+}
+
+/// Handler: List a page of cache keys in a namespace, or in every namespace
+/// the caller can access
+pub async fn list_cache_key_pages(
+    State(state): State<AppState>,
+    Extension(user_profile): Extension<V1UserProfile>,
+    QueryParam(params): QueryParam<CacheKeyParams>,
+) -> Result<Json<V1CacheKeys>, (StatusCode, Json<serde_json::Value>)> {
+    info!(
+        "Listing a page of cache keys (namespace: {:?}, prefix: {:?}) requested by user: {}",
+        params.namespace, params.prefix, user_profile.email
+    );
+
+    let cursor = match params.cursor.as_deref() {
+        Some(cursor) => cursor.parse::<u64>().map_err(|_| {
+            (
+                StatusCode::BAD_REQUEST,
+                Json(json!({ "error": format!("Invalid cursor '{}'", cursor) })),
+            )
+        })?,
+        None => 0,
+    };
+    let limit = params
+        .limit
+        .unwrap_or(DEFAULT_CACHE_KEY_LIMIT)
+        .clamp(1, MAX_CACHE_KEY_LIMIT);
+
+    let filter = cache_key_filter(&state.db_pool, &user_profile, &params).await?;
+    let mut conn = cache_connection(&state).await?;
+
+    // Scan one page of keys
+    debug!(
+        "Scanning Redis with pattern {} from cursor {}",
+        filter.pattern(),
+        cursor
+    );
+    let (keys, next) = scan_cache_keys(&mut conn, &filter, cursor, limit, MAX_SCAN_ROUNDS)
+        .await
+        .map_err(scan_failed)?;
+
+    info!("Found {} cache keys on this page", keys.len());
+    Ok(Json(V1CacheKeys {
+        keys,
+        next_cursor: (next != 0).then(|| next.to_string()),
+    }))
+}
+
+/// Keys are stored as `cache:<namespace>:<key>`. The key captured from the
+/// path might start with a '/', which is dropped.
+fn full_cache_key(namespace: &str, key_suffix: &str) -> String {
+    let clean_key_suffix = key_suffix.strip_prefix('/').unwrap_or(key_suffix);
+    format!("cache:{}:{}", namespace, clean_key_suffix)
+}
+
+/// The entity tag of a cached value: its quoted SHA-1, which Redis scripts
+/// can compute too
+pub fn cache_etag(value: &str) -> String {
+    let digest = ring::digest::digest(&ring::digest::SHA1_FOR_LEGACY_USE_ONLY, value.as_bytes());
+    let hex: String = digest
+        .as_ref()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect();
+    format!("\"{}\"", hex)
+}
+
+/// What must hold for a cache write to happen
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CachePrecondition {
+    Always,
+    /// `If-None-Match: *`, set only when the key doesn't exist
+    Absent,
+    /// `If-Match: *`, set only when the key exists
+    Exists,
+    /// `If-Match: "<etag>"`, set only when the current value has this tag
+    Matches(String),
+}
+
+impl CachePrecondition {
+    /// Reads `If-Match` and `If-None-Match`, of which at most one may be given
+    pub fn from_headers(headers: &HeaderMap) -> Result<Self, String> {
+        let header = |name: HeaderName| {
+            headers
+                .get(&name)
+                .map(|v| v.to_str().map(|v| v.trim().to_string()))
+                .transpose()
+                .map_err(|_| format!("{} must be ASCII", name))
+        };
+        match (header(IF_MATCH)?, header(IF_NONE_MATCH)?) {
+            (Some(_), Some(_)) => {
+                Err("Send either If-Match or If-None-Match, not both".to_string())
+            }
+            (None, Some(tag)) if tag == "*" => Ok(Self::Absent),
+            (None, Some(_)) => Err("If-None-Match only supports '*'".to_string()),
+            (Some(tag), None) if tag == "*" => Ok(Self::Exists),
+            (Some(tag), None) => {
+                let tag = tag.trim_start_matches("W/");
+                if tag.len() < 2 || !tag.starts_with('"') || !tag.ends_with('"') {
+                    return Err(format!(
+                        "If-Match must be '*' or a quoted ETag, got {}",
+                        tag
+                    ));
+                }
+                Ok(Self::Matches(tag.to_string()))
+            }
+            (None, None) => Ok(Self::Always),
+        }
+    }
+}
+
+/// Sets the value only if the current one hashes to the given SHA-1, in one
+/// step so no other write can slip in between. ARGV[3] is the TTL in
+/// seconds, or empty to keep the current one.
+const COMPARE_AND_SET_SCRIPT: &str = r#"
+local current = redis.call('GET', KEYS[1])
+if not current or redis.sha1hex(current) ~= ARGV[1] then
+    return 0
+end
+if ARGV[3] == '' then
+    redis.call('SET', KEYS[1], ARGV[2], 'KEEPTTL')
+else
+    redis.call('SET', KEYS[1], ARGV[2], 'EX', ARGV[3])
+end
+return 1
+"#;
+
+/// Writes `value` to `key` if `precondition` holds, expiring it after
+/// `ttl_secs` when given. Returns whether the value was written.
+pub async fn set_cache_value<C: redis::aio::ConnectionLike>(
+    conn: &mut C,
+    key: &str,
+    value: &str,
+    ttl_secs: Option<u64>,
+    precondition: &CachePrecondition,
+) -> redis::RedisResult<bool> {
+    let condition = match precondition {
+        CachePrecondition::Always => None,
+        CachePrecondition::Absent => Some("NX"),
+        CachePrecondition::Exists => Some("XX"),
+        CachePrecondition::Matches(etag) => {
+            let written: i32 = redis::Script::new(COMPARE_AND_SET_SCRIPT)
+                .key(key)
+                .arg(etag.trim_matches('"'))
+                .arg(value)
+                .arg(ttl_secs.map(|t| t.to_string()).unwrap_or_default())
+                .invoke_async(conn)
+                .await?;
+            return Ok(written == 1);
         }
     };
 
-    // 4. Construct the full key
-    // The key_suffix captured by `*` might start with a '/', remove it if present.
-    let clean_key_suffix = key_suffix.strip_prefix('/').unwrap_or(&key_suffix);
-    let full_key = format!("cache:{}:{}", namespace, clean_key_suffix);
+    let mut cmd = redis::cmd("SET");
+    cmd.arg(key).arg(value);
+    if let Some(condition) = condition {
+        cmd.arg(condition);
+    }
+    if let Some(ttl) = ttl_secs {
+        cmd.arg("EX").arg(ttl);
+    }
+    // A condition that doesn't hold makes SET reply nil
+    let reply: Option<String> = cmd.query_async(conn).await?;
+    Ok(reply.is_some())
+}
+
+#[derive(Deserialize, Debug, Default)]
+pub struct PutCacheKeyParams {
+    /// How long the key lives, e.g. `30s`; without it the key doesn't expire
+    /// (or, on compare-and-swap, keeps its expiry)
+    ttl: Option<String>,
+}
+
+/// Handler: Get a specific cache key's value, with its `ETag`
+pub async fn get_cache_key(
+    State(state): State<AppState>,
+    Extension(user_profile): Extension<V1UserProfile>,
+    Path((namespace, key_suffix)): Path<(String, String)>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    let db_pool = &state.db_pool;
+    info!(
+        "Getting cache key suffix '{}' in namespace '{}' requested by user: {}",
+        key_suffix, namespace, user_profile.email
+    );
+
+    authorize_cache_namespace(db_pool, &user_profile, &namespace).await?;
+    let mut conn = cache_connection(&state).await?;
+
+    let full_key = full_cache_key(&namespace, &key_suffix);
     info!("Attempting to GET key: {}", full_key);
 
-    // 5. Execute GET command
     match conn.get::<_, Option<String>>(&full_key).await {
         Ok(Some(value)) => {
             debug!("Found value for key {}: (value hidden)", full_key);
-            Ok(Json(value))
+            Ok(([(ETAG, cache_etag(&value))], Json(value)))
         }
         Ok(None) => {
             info!("Key not found: {}", full_key);
@@ -252,87 +469,90 @@ pub async fn get_cache_key(
     }
 }
 
-/// Handler: Delete a specific cache key
-pub async fn delete_cache_key(
+/// Handler: Set a cache key's value. `If-None-Match: *` only creates it,
+/// `If-Match` only replaces it (a specific value when given its `ETag`), and
+/// a precondition that doesn't hold gives 412. Together these are enough for
+/// simple locks and leader election.
+pub async fn put_cache_key(
     State(state): State<AppState>,
     Extension(user_profile): Extension<V1UserProfile>,
     Path((namespace, key_suffix)): Path<(String, String)>,
-) -> Result<StatusCode, (StatusCode, Json<serde_json::Value>)> {
-    // Return StatusCode on success
+    QueryParam(params): QueryParam<PutCacheKeyParams>,
+    headers: HeaderMap,
+    Json(value): Json<String>,
+) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
     let db_pool = &state.db_pool;
     info!(
-        "Deleting cache key suffix '{}' in namespace '{}' requested by user: {}",
+        "Setting cache key suffix '{}' in namespace '{}' requested by user: {}",
         key_suffix, namespace, user_profile.email
     );
 
-    // 1. Verify user access to the namespace (same logic as get_cache_key)
-    let mut owner_ids: Vec<String> = user_profile
-        .organizations
-        .as_ref()
-        .map(|orgs| orgs.keys().cloned().collect())
-        .unwrap_or_default();
-    owner_ids.push(user_profile.email.clone());
-    let owner_id_refs: Vec<&str> = owner_ids.iter().map(|s| s.as_str()).collect();
-
-    let accessible_namespaces =
-        match Query::find_namespaces_by_owners(db_pool, &owner_id_refs).await {
-            Ok(namespaces) => namespaces
-                .into_iter()
-                .map(|n| n.id)
-                .collect::<HashSet<String>>(),
-            Err(e) => {
-                error!(
-                    "Failed to query namespaces for owners {:?}: {}",
-                    owner_id_refs, e
-                );
+    let precondition = CachePrecondition::from_headers(&headers)
+        .map_err(|e| (StatusCode::BAD_REQUEST, Json(json!({ "error": e }))))?;
+    let ttl_secs = match params.ttl.as_deref() {
+        Some(ttl) => match humantime::parse_duration(ttl) {
+            Ok(ttl) if ttl.as_secs() > 0 => Some(ttl.as_secs()),
+            _ => {
                 return Err((
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    Json(json!({ "error": "Failed to retrieve accessible namespaces." })),
-                ));
+                    StatusCode::BAD_REQUEST,
+                    Json(json!({ "error": format!("Invalid ttl '{}', e.g. '30s'", ttl) })),
+                ))
             }
-        };
+        },
+        None => None,
+    };
 
-    if !accessible_namespaces.contains(&namespace) {
-        error!(
-            "User {} does not have access to namespace '{}'",
-            user_profile.email, namespace
+    authorize_cache_namespace(db_pool, &user_profile, &namespace).await?;
+    let mut conn = cache_connection(&state).await?;
+
+    let full_key = full_cache_key(&namespace, &key_suffix);
+    let written = set_cache_value(&mut conn, &full_key, &value, ttl_secs, &precondition)
+        .await
+        .map_err(|e| {
+            error!("Redis SET failed for key {}: {}", full_key, e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({ "error": "Failed to execute Redis SET command." })),
+            )
+        })?;
+
+    if !written {
+        info!(
+            "Precondition {:?} failed for cache key {}",
+            precondition, full_key
         );
         return Err((
-            StatusCode::FORBIDDEN,
-            Json(json!({ "error": "Access denied to the specified namespace." })),
+            StatusCode::PRECONDITION_FAILED,
+            Json(json!({ "error": "Cache key precondition failed." })),
         ));
     }
 
-    // 2. Get Redis client
-    let redis_client = match &state.message_queue {
-        MessageQueue::Redis { client } => client.clone(),
-        _ => {
-            error!("Redis client not available in AppState.");
-            return Err((
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(json!({ "error": "Redis client not configured." })),
-            ));
-        }
-    };
+    let etag = cache_etag(&value);
+    Ok((
+        [(ETAG, etag.clone())],
+        Json(json!({ "key": full_key, "etag": etag })),
+    ))
+}
 
-    // 3. Get Redis connection
-    let mut conn = match redis_client.get_multiplexed_async_connection().await {
-        Ok(conn) => conn,
-        Err(e) => {
-            error!("Failed to get async Redis connection: {}", e);
-            return Err((
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(json!({ "error": "Failed to connect to Redis." })),
-            ));
-        }
-    };
+/// Handler: Delete a specific cache key
+pub async fn delete_cache_key(
+    State(state): State<AppState>,
+    Extension(user_profile): Extension<V1UserProfile>,
+    Path((namespace, key_suffix)): Path<(String, String)>,
+) -> Result<StatusCode, (StatusCode, Json<serde_json::Value>)> {
+    // Return StatusCode on success
+    let db_pool = &state.db_pool;
+    info!(
+        "Deleting cache key suffix '{}' in namespace '{}' requested by user: {}",
+        key_suffix, namespace, user_profile.email
+    );
+
+    authorize_cache_namespace(db_pool, &user_profile, &namespace).await?;
+    let mut conn = cache_connection(&state).await?;
 
-    // 4. Construct the full key
-    let clean_key_suffix = key_suffix.strip_prefix('/').unwrap_or(&key_suffix);
-    let full_key = format!("cache:{}:{}", namespace, clean_key_suffix);
+    let full_key = full_cache_key(&namespace, &key_suffix);
     info!("Attempting to DEL key: {}", full_key);
 
-    // 5. Execute DEL command
     match conn.del::<_, i32>(&full_key).await {
         // DEL returns the number of keys deleted
         Ok(num_deleted) => {
@@ -351,3 +571,176 @@ pub async fn delete_cache_key(
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cache_key_pattern_escapes_glob_characters() {
+        assert_eq!(cache_key_pattern("team-a", ""), "cache:team-a:*");
+        assert_eq!(
+            cache_key_pattern("team-a", "models/*[v1]?"),
+            "cache:team-a:models/\\*\\[v1\\]\\?*"
+        );
+    }
+
+    #[test]
+    fn test_cache_key_filter_across_namespaces() {
+        let filter = CacheKeyFilter {
+            namespaces: HashSet::from(["alice".to_string(), "team".to_string()]),
+            prefix: "models/".to_string(),
+        };
+
+        assert_eq!(filter.pattern(), "cache:*:models/*");
+        assert!(filter.matches("cache:alice:models/a"));
+        assert!(filter.matches("cache:team:models/b:c"));
+        assert!(!filter.matches("cache:bob:models/a"));
+        assert!(!filter.matches("cache:alice:data/models/a"));
+        assert!(!filter.matches("cache:alice"));
+
+        let one = CacheKeyFilter {
+            namespaces: HashSet::from(["alice".to_string()]),
+            prefix: String::new(),
+        };
+        assert_eq!(one.pattern(), "cache:alice:*");
+    }
+
+    fn headers(name: &'static str, value: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(name, value.parse().unwrap());
+        headers
+    }
+
+    #[test]
+    fn test_precondition_from_headers() {
+        assert_eq!(
+            CachePrecondition::from_headers(&HeaderMap::new()),
+            Ok(CachePrecondition::Always)
+        );
+        assert_eq!(
+            CachePrecondition::from_headers(&headers("if-none-match", "*")),
+            Ok(CachePrecondition::Absent)
+        );
+        assert_eq!(
+            CachePrecondition::from_headers(&headers("if-match", "*")),
+            Ok(CachePrecondition::Exists)
+        );
+        let etag = cache_etag("leader-a");
+        assert_eq!(
+            CachePrecondition::from_headers(&headers("if-match", &etag)),
+            Ok(CachePrecondition::Matches(etag))
+        );
+        assert!(CachePrecondition::from_headers(&headers("if-match", "abc")).is_err());
+        assert!(CachePrecondition::from_headers(&headers("if-none-match", "\"abc\"")).is_err());
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a Redis at NEBU_TEST_REDIS_URL"]
+    async fn test_set_if_absent_and_compare_and_swap() {
+        let url = std::env::var("NEBU_TEST_REDIS_URL").expect("NEBU_TEST_REDIS_URL is not set");
+        // Arrange
+        let client = redis::Client::open(url).unwrap();
+        let mut conn = client.get_multiplexed_async_connection().await.unwrap();
+        let key = format!("cache:test:lock-{}", short_uuid::ShortUuid::generate());
+        let absent = CachePrecondition::Absent;
+
+        // Act & Assert: only the first claim wins
+        assert!(
+            set_cache_value(&mut conn, &key, "leader-a", Some(30), &absent)
+                .await
+                .unwrap()
+        );
+        assert!(
+            !set_cache_value(&mut conn, &key, "leader-b", Some(30), &absent)
+                .await
+                .unwrap()
+        );
+
+        // A swap against a stale tag fails, against the current one succeeds
+        let stale = CachePrecondition::Matches(cache_etag("leader-b"));
+        assert!(!set_cache_value(&mut conn, &key, "leader-c", None, &stale)
+            .await
+            .unwrap());
+        let current = CachePrecondition::Matches(cache_etag("leader-a"));
+        assert!(set_cache_value(&mut conn, &key, "leader-c", None, &current)
+            .await
+            .unwrap());
+
+        let value: String = conn.get(&key).await.unwrap();
+        assert_eq!(value, "leader-c");
+        // The swap kept the claim's expiry
+        let ttl: i64 = conn.ttl(&key).await.unwrap();
+        assert!(ttl > 0 && ttl <= 30, "ttl was {}", ttl);
+
+        let _: () = conn.del(&key).await.unwrap();
+        assert!(
+            !set_cache_value(&mut conn, &key, "x", None, &CachePrecondition::Exists)
+                .await
+                .unwrap()
+        );
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a Redis at NEBU_TEST_REDIS_URL"]
+    async fn test_scan_pages_through_many_keys() {
+        let url = std::env::var("NEBU_TEST_REDIS_URL").expect("NEBU_TEST_REDIS_URL is not set");
+        // Arrange
+        let client = redis::Client::open(url).unwrap();
+        let mut conn = client.get_multiplexed_async_connection().await.unwrap();
+        let namespace = format!("test-{}", short_uuid::ShortUuid::generate());
+        let other = format!("{}-other", namespace);
+        for i in 0..250 {
+            let prefix = if i % 5 == 0 { "models" } else { "data" };
+            let _: () = conn
+                .set(format!("cache:{}:{}/{}", namespace, prefix, i), i)
+                .await
+                .unwrap();
+        }
+        let _: () = conn
+            .set(format!("cache:{}:models/0", other), 0)
+            .await
+            .unwrap();
+
+        let filter = |prefix: &str| CacheKeyFilter {
+            namespaces: HashSet::from([namespace.clone()]),
+            prefix: prefix.to_string(),
+        };
+
+        // Act
+        let mut seen = HashSet::new();
+        let mut pages = 0;
+        let mut cursor = 0;
+        loop {
+            let (keys, next) = scan_cache_keys(&mut conn, &filter(""), cursor, 40, MAX_SCAN_ROUNDS)
+                .await
+                .unwrap();
+            pages += 1;
+            seen.extend(keys);
+            if next == 0 {
+                break;
+            }
+            cursor = next;
+        }
+        let (models, _) = scan_cache_keys(&mut conn, &filter("models/"), 0, 1000, usize::MAX)
+            .await
+            .unwrap();
+        // One round of a small COUNT covers a few of the 250 keys, so it stops short
+        let (_, next) = scan_cache_keys(&mut conn, &filter("models/"), 0, 10, 1)
+            .await
+            .unwrap();
+
+        // Assert
+        assert_eq!(seen.len(), 250);
+        assert!(pages > 1, "expected several pages, got {}", pages);
+        assert!(seen
+            .iter()
+            .all(|k| k.starts_with(&format!("cache:{}:", namespace))));
+        assert_eq!(models.into_iter().collect::<HashSet<_>>().len(), 50);
+        assert_ne!(next, 0);
+
+        let mut keys: Vec<String> = seen.into_iter().collect();
+        keys.push(format!("cache:{}:models/0", other));
+        let _: () = conn.del(keys).await.unwrap();
+    }
+}
@@ -1,49 +1,57 @@
 // src/handlers/containers.rs
 
-use crate::models::{V1AuthzConfig, V1Meter, V1ResourceMeta, V1ResourceMetaRequest, V1UserProfile};
-use crate::resources::v1::containers::factory::platform_factory;
+use crate::models::{V1ListParams, V1Meter, V1ResourceMeta, V1ResourceMetaRequest, V1UserProfile};
+use crate::resources::v1::containers::base::{
+    apply_container_patch, changed_fields, exec_user, get_tailscale_device_name,
+    lookup_env_secrets, resolve_env, ContainerStatus, RECREATE_FIELDS,
+};
+use crate::resources::v1::containers::bootstrap;
+use crate::resources::v1::containers::factory::{platform_factory, PLATFORMS};
 use crate::resources::v1::containers::models::{
-    V1Container, V1ContainerHealthCheck, V1ContainerRequest, V1ContainerResources,
-    V1ContainerSearch, V1Containers, V1EnvVar, V1UpdateContainer,
+    V1Container, V1ContainerBatchItem, V1ContainerBatchRequest, V1ContainerBatchResult,
+    V1ContainerEvents, V1ContainerRequest, V1ContainerSearch, V1ContainerSsh,
+    V1ContainerSshKeyParams, V1Containers, V1CreateContainerParams, V1LogParams, V1SSHKey,
+    V1SshKeyRotation, V1SyncProgress, V1UpdateContainer,
+};
+use crate::resources::v1::containers::ssh_rotation::{
+    authorize_temporary_key, rotate_ssh_keypair, RotationError,
 };
-use crate::resources::v1::volumes::models::V1VolumePath;
 // Adjust the crate paths below to match your own project structure:
-use crate::agent::ns::auth_ns;
+use crate::agent::ns::{auth_ns, is_root_owner};
 use crate::entities::containers;
+use crate::entities::idempotency_keys::IdempotencyClaim;
 use crate::mutation::Mutation;
 use crate::query::Query;
+use crate::ssh::keys;
 use crate::state::AppState;
 use crate::utils::namespace::resolve_namespace;
 use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
 use axum::{
-    extract::Extension, extract::Json, extract::Path, extract::State, http::StatusCode,
-    response::IntoResponse,
+    extract::Extension, extract::Json, extract::Path, extract::Query as QueryParams,
+    extract::State, http::HeaderMap, http::StatusCode, response::IntoResponse,
 };
 use futures::{SinkExt, StreamExt};
 use sea_orm::sea_query::extension::postgres::PgExpr;
 use sea_orm::sea_query::{Alias, Expr};
-use sea_orm::{ColumnTrait, Condition, DatabaseConnection, EntityTrait, QueryFilter};
+use sea_orm::{ColumnTrait, Condition, DatabaseConnection, DbErr, EntityTrait, QueryFilter};
 use serde_json::json;
 use std::process::Stdio;
 use std::sync::Arc;
 use tokio::io::{AsyncBufReadExt, BufReader};
 use tokio::sync::Mutex;
-use tracing::{debug, error};
+use tracing::{debug, error, warn};
 
 pub async fn get_container(
     State(state): State<AppState>,
     Extension(user_profile): Extension<V1UserProfile>,
     Path((namespace, name)): Path<(String, String)>,
+    QueryParams(params): QueryParams<V1ListParams>,
 ) -> Result<Json<V1Container>, (StatusCode, Json<serde_json::Value>)> {
     let db_pool = &state.db_pool;
     let resolved_namespace = resolve_namespace(&namespace, &user_profile);
 
-    let mut owner_ids: Vec<String> = if let Some(orgs) = &user_profile.organizations {
-        orgs.keys().cloned().collect()
-    } else {
-        Vec::new()
-    };
-    owner_ids.push(user_profile.email.clone());
+    let owner_ids = user_profile.owner_ids();
+    check_include_deleted(&params, &owner_ids)?;
 
     let owner = auth_ns(db_pool, &owner_ids, &resolved_namespace)
         .await
@@ -58,14 +66,24 @@ pub async fn get_container(
         "Getting container by namespace and name: {} {}",
         resolved_namespace, name
     );
-    let container = match Query::find_container_by_namespace_name_and_owners(
-        db_pool,
-        &resolved_namespace,
-        &name,
-        &vec![owner.as_str()],
-    )
-    .await
-    {
+    let container = if params.include_deleted {
+        Query::find_container_by_namespace_name_and_owners_including_deleted(
+            db_pool,
+            &resolved_namespace,
+            &name,
+            &vec![owner.as_str()],
+        )
+        .await
+    } else {
+        Query::find_container_by_namespace_name_and_owners(
+            db_pool,
+            &resolved_namespace,
+            &name,
+            &vec![owner.as_str()],
+        )
+        .await
+    };
+    let container = match container {
         Ok(container) => container,
         Err(e) => {
             return Err((
@@ -81,42 +99,42 @@ pub async fn get_container(
         "Getting container by id: {}",
         container.clone().id.to_string()
     );
-    _get_container_by_id(db_pool, &container.clone().id.to_string(), &user_profile).await
+    _get_container_by_id(
+        db_pool,
+        &container.clone().id.to_string(),
+        &user_profile,
+        params.include_deleted,
+    )
+    .await
 }
 
 pub async fn get_container_by_id(
     State(state): State<AppState>,
     Extension(user_profile): Extension<V1UserProfile>,
     Path(id): Path<String>,
+    QueryParams(params): QueryParams<V1ListParams>,
 ) -> Result<Json<V1Container>, (StatusCode, Json<serde_json::Value>)> {
     let db_pool = &state.db_pool;
 
-    _get_container_by_id(db_pool, &id, &user_profile).await
+    _get_container_by_id(db_pool, &id, &user_profile, params.include_deleted).await
 }
 
 pub async fn _get_container_by_id(
     db_pool: &DatabaseConnection,
     id: &str,
     user_profile: &V1UserProfile,
+    include_deleted: bool,
 ) -> Result<Json<V1Container>, (StatusCode, Json<serde_json::Value>)> {
-    let mut owner_ids: Vec<String> = if let Some(orgs) = &user_profile.organizations {
-        orgs.keys().cloned().collect()
-    } else {
-        Vec::new()
-    };
-
-    // Include user's email (assuming owner_id is user's email)
-    owner_ids.push(user_profile.email.clone());
+    let owner_ids = user_profile.owner_ids();
+    check_include_deleted(&V1ListParams { include_deleted }, &owner_ids)?;
     let owner_id_refs: Vec<&str> = owner_ids.iter().map(|s| s.as_str()).collect();
 
-    let container = Query::find_container_by_id_and_owners(db_pool, &id, &owner_id_refs)
-        .await
-        .map_err(|e| {
-            (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(json!({"error": format!("Database error: {}", e)})),
-            )
-        })?;
+    let container = if include_deleted {
+        Query::find_container_by_id_and_owners_including_deleted(db_pool, &id, &owner_id_refs).await
+    } else {
+        Query::find_container_by_id_and_owners(db_pool, &id, &owner_id_refs).await
+    };
+    let container = container.map_err(container_lookup_error)?;
 
     let owner = auth_ns(db_pool, &owner_ids, &container.namespace)
         .await
@@ -147,6 +165,7 @@ pub async fn _get_container_by_id(
         },
         image: container.image.clone(),
         platform: container.platform.unwrap_or_default(),
+        platforms: container.platforms,
         env: container
             .env
             .and_then(|v| serde_json::from_value(v).ok())
@@ -178,6 +197,15 @@ pub async fn _get_container_by_id(
         ports: container.ports.and_then(|v| serde_json::from_value(v).ok()),
         proxy_port: container.proxy_port,
         authz: container.authz.and_then(|v| serde_json::from_value(v).ok()),
+        bootstrap: container
+            .bootstrap
+            .and_then(|v| serde_json::from_value(v).ok()),
+        tailscale: container
+            .tailscale
+            .and_then(|v| serde_json::from_value(v).ok()),
+        priority: container.priority,
+        preemptible: container.preemptible,
+        webhook_url: container.webhook_url,
     };
 
     Ok(Json(out_container))
@@ -187,29 +215,27 @@ pub async fn _get_container_by_id(
 pub async fn list_containers(
     State(state): State<AppState>,
     Extension(user_profile): Extension<V1UserProfile>,
+    QueryParams(params): QueryParams<V1ListParams>,
 ) -> Result<Json<V1Containers>, (StatusCode, Json<serde_json::Value>)> {
     let db_pool = &state.db_pool;
 
-    let mut owner_ids: Vec<String> = if let Some(orgs) = &user_profile.organizations {
-        orgs.keys().cloned().collect()
-    } else {
-        Vec::new()
-    };
-
-    // Include user's email (assuming owner_id is user's email)
-    owner_ids.push(user_profile.email.clone());
+    let owner_ids = user_profile.owner_ids();
+    check_include_deleted(&params, &owner_ids)?;
 
     let owner_id_refs: Vec<&str> = owner_ids.iter().map(|s| s.as_str()).collect();
 
     // Query containers for all owner_ids
-    let container_models = Query::find_containers_by_owners(db_pool, &owner_id_refs)
-        .await
-        .map_err(|e| {
-            (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(json!({"error": format!("Database error: {}", e)})),
-            )
-        })?;
+    let container_models = if params.include_deleted {
+        Query::find_containers_by_owners_including_deleted(db_pool, &owner_id_refs).await
+    } else {
+        Query::find_containers_by_owners(db_pool, &owner_id_refs).await
+    };
+    let container_models = container_models.map_err(|e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"error": format!("Database error: {}", e)})),
+        )
+    })?;
 
     // Convert database models to API response models
     let containers = container_models
@@ -238,6 +264,7 @@ pub async fn list_containers(
             command: c.command,
             args: c.args,
             platform: c.platform.unwrap_or_default(),
+            platforms: c.platforms,
             volumes: c.volumes.and_then(|v| serde_json::from_value(v).ok()),
             accelerators: c.accelerators,
             meters: c.meters.and_then(|v| serde_json::from_value(v).ok()),
@@ -251,19 +278,43 @@ pub async fn list_containers(
             ports: c.ports.and_then(|v| serde_json::from_value(v).ok()),
             proxy_port: c.proxy_port,
             authz: c.authz.and_then(|v| serde_json::from_value(v).ok()),
+            bootstrap: c.bootstrap.and_then(|v| serde_json::from_value(v).ok()),
+            tailscale: c.tailscale.and_then(|v| serde_json::from_value(v).ok()),
+            priority: c.priority,
+            preemptible: c.preemptible,
+            webhook_url: c.webhook_url,
         })
         .collect();
 
     Ok(Json(V1Containers { containers }))
 }
 
+/// Header carrying a client chosen key that makes container creation safe to retry
+pub const IDEMPOTENCY_KEY_HEADER: &str = "idempotency-key";
+
 pub async fn create_container(
     State(state): State<AppState>,
     Extension(user_profile): Extension<V1UserProfile>,
+    headers: HeaderMap,
+    QueryParams(params): QueryParams<V1CreateContainerParams>,
     Json(container_request): Json<V1ContainerRequest>,
-) -> Result<Json<V1Container>, (StatusCode, Json<serde_json::Value>)> {
+) -> Result<axum::response::Response, (StatusCode, Json<serde_json::Value>)> {
     let db_pool = &state.db_pool;
 
+    let idempotency_key = match headers.get(IDEMPOTENCY_KEY_HEADER) {
+        Some(value) => {
+            let key = value.to_str().unwrap_or_default().to_string();
+            crate::validate::validate_idempotency_key(&key).map_err(|e| {
+                (
+                    StatusCode::BAD_REQUEST,
+                    Json(json!({ "error": e.to_string() })),
+                )
+            })?;
+            Some(key)
+        }
+        None => None,
+    };
+
     match crate::validate::validate_name(
         &container_request
             .clone()
@@ -282,41 +333,63 @@ pub async fn create_container(
     }
     debug!("Container request: {:?}", container_request);
 
+    crate::validate::validate_image(&container_request.image).map_err(|e| {
+        (
+            StatusCode::BAD_REQUEST,
+            Json(json!({ "error": e.to_string() })),
+        )
+    })?;
+    if let Some(webhook_url) = &container_request.webhook_url {
+        check_webhook_url(webhook_url)?;
+    }
+    if let Some(ssh_keys) = &container_request.ssh_keys {
+        crate::validate::validate_ssh_keys(ssh_keys).map_err(|e| {
+            (
+                StatusCode::BAD_REQUEST,
+                Json(json!({ "error": e.to_string() })),
+            )
+        })?;
+    }
+    if let Some(user) = container_request.user.as_deref().filter(|u| !u.is_empty()) {
+        crate::validate::validate_container_user(user).map_err(|e| {
+            (
+                StatusCode::BAD_REQUEST,
+                Json(json!({ "error": e.to_string() })),
+            )
+        })?;
+    }
+    if crate::config::SERVER_CONFIG.validate_image_exists {
+        if let Err(e) = crate::oci::client::image_exists(&container_request.image).await {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                Json(json!({
+                    "error": format!("Image '{}' not found: {}", container_request.image, e)
+                })),
+            ));
+        }
+    }
+
     let namespace_opt = container_request
         .clone()
         .metadata
         .unwrap_or_default()
         .namespace;
 
-    let handle = match user_profile.handle.clone() {
-        Some(handle) => handle,
-        None => user_profile
-            .email
-            .clone()
-            .replace("@", "-")
-            .replace(".", "-"),
-    };
-    debug!("Handle: {:?}", handle);
-
+    let implicit_namespace = namespace_opt.is_none();
     let namespace = match namespace_opt {
         Some(namespace) => resolve_namespace(&namespace, &user_profile),
-        None => match crate::handlers::v1::namespaces::ensure_namespace(
-            db_pool,
-            &handle,
-            &user_profile.email,
-            &user_profile.email,
-            None,
-        )
-        .await
-        {
-            Ok(_) => handle,
-            Err(e) => {
-                return Err((
-                    StatusCode::BAD_REQUEST,
-                    Json(json!({ "error": format!("Invalid namespace: {}", e) })),
-                ));
-            }
-        },
+        // A dry run doesn't create the user's namespace, it only has to be creatable
+        None if params.dry_run && crate::config::SERVER_CONFIG.allow_implicit_namespaces => {
+            crate::utils::namespace::user_handle(&user_profile)
+        }
+        None => {
+            crate::handlers::v1::namespaces::implicit_namespace(
+                db_pool,
+                &user_profile,
+                crate::config::SERVER_CONFIG.allow_implicit_namespaces,
+            )
+            .await?
+        }
     };
     debug!(">> Using namespace for container creation: {:?}", namespace);
 
@@ -328,32 +401,150 @@ pub async fn create_container(
     })?;
     debug!("Validated namespace");
 
-    let mut owner_ids: Vec<String> = if let Some(orgs) = &user_profile.organizations {
-        orgs.keys().cloned().collect()
-    } else {
-        Vec::new()
-    };
-    owner_ids.push(user_profile.email.clone());
+    let owner_ids = user_profile.owner_ids();
+    check_meter_subjects(container_request.meters.as_ref(), &owner_ids)?;
 
     debug!("Authorizing namespace");
-    let owner = auth_ns(db_pool, &owner_ids, &namespace)
+    let owner = match auth_ns(db_pool, &owner_ids, &namespace).await {
+        Ok(owner) => owner,
+        // The user's namespace that a real request would create
+        Err(_) if params.dry_run && implicit_namespace => user_profile.email.clone(),
+        Err(e) => {
+            return Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({"error": format!("Authorization error: {}", e)})),
+            ))
+        }
+    };
+    debug!("Authorized namespace");
+
+    // Applied here as well as in `declare` so the namespace's default platform
+    // picks the platform, and its defaults are validated like the request's
+    let container_request = crate::resources::v1::containers::base::with_namespace_defaults(
+        db_pool,
+        &namespace,
+        &container_request,
+    )
+    .await
+    .map_err(|e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({ "error": format!("Failed to apply namespace defaults: {}", e) })),
+        )
+    })?;
+
+    // Required env vars have to resolve up front, the same way they do at create
+    let env = container_request.env.clone().unwrap_or_default();
+    let secrets = lookup_env_secrets(db_pool, &namespace, &env)
         .await
         .map_err(|e| {
             (
                 StatusCode::INTERNAL_SERVER_ERROR,
-                Json(json!({"error": format!("Authorization error: {}", e)})),
+                Json(json!({ "error": format!("Database error: {}", e) })),
             )
         })?;
-    debug!("Authorized namespace");
-    let platform = platform_factory(
-        container_request
-            .clone()
-            .platform
-            .unwrap_or("runpod".to_string()),
-    );
+    resolve_env(&env, &secrets).map_err(|e| {
+        (
+            StatusCode::BAD_REQUEST,
+            Json(json!({ "error": e.to_string() })),
+        )
+    })?;
+
+    if let Some(platforms) = &container_request.platforms {
+        crate::validate::validate_platforms(platforms, PLATFORMS).map_err(|e| {
+            (
+                StatusCode::BAD_REQUEST,
+                Json(json!({ "error": e.to_string() })),
+            )
+        })?;
+    }
+    // Without an explicit platform, start on the first one to fall back from
+    let platform_name = container_request
+        .platform
+        .clone()
+        .or_else(|| {
+            container_request
+                .platforms
+                .as_ref()
+                .and_then(|platforms| platforms.first().cloned())
+        })
+        .unwrap_or("runpod".to_string());
+    if !PLATFORMS.contains(&platform_name.as_str()) {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(json!({
+                "error": format!(
+                    "Invalid platform '{}': expected one of: {}",
+                    platform_name,
+                    PLATFORMS.join(", ")
+                )
+            })),
+        ));
+    }
+    check_ssh_keys_supported(
+        container_request.ssh_keys.as_deref(),
+        std::iter::once(platform_name.as_str()).chain(
+            container_request
+                .platforms
+                .iter()
+                .flatten()
+                .map(|p| p.as_str()),
+        ),
+    )?;
+    let platform = platform_factory(platform_name);
+
+    if let Some(accelerators) = &container_request.accelerators {
+        let supported = platform.accelerator_map();
+        for accelerator in accelerators {
+            crate::validate::validate_accelerator(accelerator, &supported).map_err(|e| {
+                (
+                    StatusCode::BAD_REQUEST,
+                    Json(json!({ "error": e.to_string() })),
+                )
+            })?;
+        }
+    }
+
+    if params.dry_run {
+        debug!("Planning container in namespace {} (dry run)", namespace);
+        let plan = platform
+            .plan(&container_request, db_pool, &owner, &namespace)
+            .await
+            .map_err(|e| {
+                (
+                    StatusCode::BAD_REQUEST,
+                    Json(json!({"error": e.to_string()})),
+                )
+            })?;
+        return Ok(Json(plan).into_response());
+    }
+
+    let idempotency_record = match &idempotency_key {
+        Some(key) => match claim_idempotency_key(db_pool, &user_profile, key).await? {
+            IdempotencyClaim::Claimed(record_id) => Some(record_id),
+            IdempotencyClaim::Completed(container_id) => {
+                debug!(
+                    "Idempotency key {} already created container {}",
+                    key, container_id
+                );
+                return replay_created_container(db_pool, container_id)
+                    .await
+                    .map(IntoResponse::into_response);
+            }
+            IdempotencyClaim::InFlight => {
+                return Err((
+                    StatusCode::CONFLICT,
+                    Json(json!({
+                        "error": "A request with this idempotency key is already in progress"
+                    })),
+                ));
+            }
+        },
+        None => None,
+    };
 
     debug!("Declaring container with namespace: {:?}", namespace);
-    let container = platform
+    let container = match platform
         .declare(
             &container_request,
             db_pool,
@@ -363,12 +554,122 @@ pub async fn create_container(
             None,
         )
         .await
+    {
+        Ok(container) => container,
+        Err(e) => {
+            // Release the key so the client can retry
+            if let Some(record_id) = idempotency_record {
+                if let Err(err) = Mutation::delete_idempotency_key(db_pool, record_id).await {
+                    error!("Failed to release idempotency key: {}", err);
+                }
+            }
+            return Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({"error": e.to_string()})),
+            ));
+        }
+    };
+
+    if let Some(record_id) = idempotency_record {
+        Mutation::complete_idempotency_key(db_pool, record_id, container.metadata.id.clone())
+            .await
+            .map_err(|e| {
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    Json(json!({"error": format!("Failed to store idempotency key: {}", e)})),
+                )
+            })?;
+    }
+
+    Ok(Json(container).into_response())
+}
+
+/// A webhook URL is only accepted when webhooks are on, i.e. there's a
+/// secret to sign them with, and it targets an allowed address.
+fn check_webhook_url(url: &str) -> Result<(), (StatusCode, Json<serde_json::Value>)> {
+    let config = &crate::config::SERVER_CONFIG;
+    if config.webhook_secret.is_none() {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(json!({
+                "error": "Webhooks are disabled on this server: NEBU_WEBHOOK_SECRET is not set"
+            })),
+        ));
+    }
+    crate::validate::validate_webhook_url(url, config.webhook_allow_private).map_err(|e| {
+        (
+            StatusCode::BAD_REQUEST,
+            Json(json!({ "error": e.to_string() })),
+        )
+    })
+}
+
+/// `ssh_keys` are installed by the bootstrap script, which kube pods don't
+/// run, so they're refused for containers that may be placed on kube rather
+/// than silently left out.
+fn check_ssh_keys_supported<'a>(
+    ssh_keys: Option<&[V1SSHKey]>,
+    platforms: impl IntoIterator<Item = &'a str>,
+) -> Result<(), (StatusCode, Json<serde_json::Value>)> {
+    if ssh_keys.map_or(true, |keys| keys.is_empty()) {
+        return Ok(());
+    }
+    if platforms.into_iter().any(|platform| platform == "kube") {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(json!({ "error": "ssh_keys are not supported on the kube platform" })),
+        ));
+    }
+    Ok(())
+}
+
+async fn claim_idempotency_key(
+    db_pool: &DatabaseConnection,
+    user_profile: &V1UserProfile,
+    key: &str,
+) -> Result<IdempotencyClaim, (StatusCode, Json<serde_json::Value>)> {
+    let ttl = chrono::Duration::from_std(crate::config::SERVER_CONFIG.idempotency_key_ttl)
+        .unwrap_or(chrono::Duration::days(1));
+
+    Mutation::claim_idempotency_key(db_pool, &user_profile.email, key, ttl)
+        .await
         .map_err(|e| {
             (
                 StatusCode::INTERNAL_SERVER_ERROR,
-                Json(json!({"error": e.to_string()})),
+                Json(json!({"error": format!("Database error: {}", e)})),
             )
-        })?;
+        })
+}
+
+/// Returns the container created by an earlier request with the same idempotency key
+async fn replay_created_container(
+    db_pool: &DatabaseConnection,
+    container_id: String,
+) -> Result<Json<V1Container>, (StatusCode, Json<serde_json::Value>)> {
+    let container = Query::find_container_by_id(db_pool, container_id.clone())
+        .await
+        .map_err(|e| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({"error": format!("Database error: {}", e)})),
+            )
+        })?
+        .ok_or((
+            StatusCode::CONFLICT,
+            Json(json!({
+                "error": format!(
+                    "Container '{}' created with this idempotency key no longer exists",
+                    container_id
+                )
+            })),
+        ))?;
+
+    let container = container.to_v1_container().map_err(|e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"error": format!("Failed to parse container: {}", e)})),
+        )
+    })?;
 
     Ok(Json(container))
 }
@@ -381,14 +682,7 @@ pub async fn delete_container(
     let db_pool = &state.db_pool;
     let resolved_namespace = resolve_namespace(&namespace, &user_profile);
 
-    let mut owner_ids: Vec<String> = if let Some(orgs) = &user_profile.organizations {
-        orgs.keys().cloned().collect()
-    } else {
-        Vec::new()
-    };
-
-    // Include user's email (assuming owner_id is user's email)
-    owner_ids.push(user_profile.email.clone());
+    let owner_ids = user_profile.owner_ids();
     let owner_id_refs: Vec<&str> = owner_ids.iter().map(|s| s.as_str()).collect();
 
     let container = match Query::find_container_by_namespace_name_and_owners(
@@ -426,24 +720,12 @@ pub async fn _delete_container_by_id(
     id: &str,
     user_profile: &V1UserProfile,
 ) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
-    let mut owner_ids: Vec<String> = if let Some(orgs) = &user_profile.organizations {
-        orgs.keys().cloned().collect()
-    } else {
-        Vec::new()
-    };
-
-    // Include user's email (assuming owner_id is user's email)
-    owner_ids.push(user_profile.email.clone());
+    let owner_ids = user_profile.owner_ids();
     let owner_id_refs: Vec<&str> = owner_ids.iter().map(|s| s.as_str()).collect();
 
     let container = Query::find_container_by_id_and_owners(db_pool, &id, &owner_id_refs)
         .await
-        .map_err(|e| {
-            (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(json!({"error": format!("Database error: {}", e)})),
-            )
-        })?;
+        .map_err(container_lookup_error)?;
 
     // Check if user has permission to delete this container
     let _owner_id = container.owner.clone();
@@ -474,32 +756,439 @@ pub async fn _delete_container_by_id(
     Ok(StatusCode::OK)
 }
 
-pub async fn fetch_container_logs_by_id(
+/// Handler: store the volume sync progress `nebu sync volumes` reports from
+/// inside a container, shown in the container's status.
+pub async fn report_container_sync_progress(
     State(state): State<AppState>,
     Extension(user_profile): Extension<V1UserProfile>,
     Path(id): Path<String>,
-) -> Result<Json<String>, (StatusCode, Json<serde_json::Value>)> {
+    Json(progress): Json<V1SyncProgress>,
+) -> Result<StatusCode, (StatusCode, Json<serde_json::Value>)> {
     let db_pool = &state.db_pool;
 
-    _fetch_container_logs_by_id(db_pool, &id, &user_profile).await
+    let owner_ids = user_profile.owner_ids();
+    let owner_id_refs: Vec<&str> = owner_ids.iter().map(|s| s.as_str()).collect();
+
+    let container = Query::find_container_by_id_and_owners(db_pool, &id, &owner_id_refs)
+        .await
+        .map_err(container_lookup_error)?;
+
+    Mutation::update_container_sync_progress(db_pool, container.id, progress)
+        .await
+        .map_err(|e| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({"error": format!("Failed to store sync progress: {}", e)})),
+            )
+        })?;
+
+    Ok(StatusCode::NO_CONTENT)
 }
 
-pub async fn fetch_container_logs(
+pub async fn fetch_container_logs_by_id(
     State(state): State<AppState>,
     Extension(user_profile): Extension<V1UserProfile>,
-    Path((namespace, name)): Path<(String, String)>,
+    Path(id): Path<String>,
+    QueryParams(params): QueryParams<V1LogParams>,
 ) -> Result<Json<String>, (StatusCode, Json<serde_json::Value>)> {
     let db_pool = &state.db_pool;
+
+    _fetch_container_logs_by_id(db_pool, &id, &user_profile, &params).await
+}
+
+pub async fn stop_container(
+    State(state): State<AppState>,
+    Extension(user_profile): Extension<V1UserProfile>,
+    Path((namespace, name)): Path<(String, String)>,
+) -> Result<Json<V1Container>, (StatusCode, Json<serde_json::Value>)> {
+    _set_container_desired_status(
+        &state.db_pool,
+        &user_profile,
+        &namespace,
+        &name,
+        ContainerStatus::Stopped,
+    )
+    .await
+}
+
+pub async fn start_container(
+    State(state): State<AppState>,
+    Extension(user_profile): Extension<V1UserProfile>,
+    Path((namespace, name)): Path<(String, String)>,
+) -> Result<Json<V1Container>, (StatusCode, Json<serde_json::Value>)> {
+    _set_container_desired_status(
+        &state.db_pool,
+        &user_profile,
+        &namespace,
+        &name,
+        ContainerStatus::Running,
+    )
+    .await
+}
+
+/// Replace the SSH keypair of a running container, authorizing the new key on
+/// the container over its current connection before the old one is removed.
+pub async fn rotate_container_ssh(
+    State(state): State<AppState>,
+    Extension(user_profile): Extension<V1UserProfile>,
+    Path((namespace, name)): Path<(String, String)>,
+) -> Result<Json<V1SshKeyRotation>, (StatusCode, Json<serde_json::Value>)> {
+    let db_pool = &state.db_pool;
     let resolved_namespace = resolve_namespace(&namespace, &user_profile);
 
-    let mut owner_ids: Vec<String> = if let Some(orgs) = &user_profile.organizations {
-        orgs.keys().cloned().collect()
-    } else {
-        Vec::new()
+    let owner_ids = user_profile.owner_ids();
+    let owner_id_refs: Vec<&str> = owner_ids.iter().map(|s| s.as_str()).collect();
+
+    let container = Query::find_container_by_namespace_name_and_owners(
+        db_pool,
+        &resolved_namespace,
+        &name,
+        &owner_id_refs,
+    )
+    .await
+    .map_err(container_lookup_error)?;
+
+    let running = is_running(&container);
+    let platform = match container.platform.clone() {
+        Some(platform) if running && (platform == "runpod" || platform == "kube") => platform,
+        _ => {
+            return Err((
+                StatusCode::CONFLICT,
+                Json(json!({"error": "SSH keys can only be rotated on a running container"})),
+            ))
+        }
+    };
+
+    let new_keypair = tokio::task::spawn_blocking(keys::generate_ssh_keypair)
+        .await
+        .map_err(|e| e.to_string())
+        .and_then(|result| result.map_err(|e| e.to_string()))
+        .map_err(|e| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({"error": format!("Failed to generate SSH keypair: {}", e)})),
+            )
+        })?;
+
+    let platform = platform_factory(platform);
+    let platform = &platform;
+    let container_id = container.id.as_str();
+    let public_key = rotate_ssh_keypair(
+        db_pool,
+        &container.namespace,
+        container_id,
+        new_keypair,
+        move |command| async move {
+            platform
+                .exec(container_id, &command, db_pool)
+                .await
+                .map_err(|e| e.to_string())
+        },
+    )
+    .await
+    .map_err(|e| {
+        let status = match e {
+            RotationError::NoKeypair => StatusCode::CONFLICT,
+            RotationError::Install(_) | RotationError::Revoke(_) => StatusCode::BAD_GATEWAY,
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        (status, Json(json!({"error": e.to_string()})))
+    })?;
+
+    Ok(Json(V1SshKeyRotation {
+        container_id: container.id.clone(),
+        public_key,
+    }))
+}
+
+fn is_running(container: &containers::Model) -> bool {
+    container
+        .parse_status()
+        .ok()
+        .flatten()
+        .and_then(|s| s.status)
+        .and_then(|s| s.parse::<ContainerStatus>().ok())
+        == Some(ContainerStatus::Running)
+}
+
+/// Generated SSH keys last this long unless the request asks otherwise
+const DEFAULT_SSH_KEY_TTL: std::time::Duration = std::time::Duration::from_secs(60 * 60);
+const MAX_SSH_KEY_TTL: std::time::Duration = std::time::Duration::from_secs(24 * 60 * 60);
+
+/// The running container `namespace/name` with how to SSH into it
+async fn running_container_ssh(
+    db_pool: &DatabaseConnection,
+    user_profile: &V1UserProfile,
+    namespace: &str,
+    name: &str,
+) -> Result<(containers::Model, V1ContainerSsh), (StatusCode, Json<serde_json::Value>)> {
+    let resolved_namespace = resolve_namespace(namespace, user_profile);
+
+    let owner_ids = user_profile.owner_ids();
+    let owner_id_refs: Vec<&str> = owner_ids.iter().map(|s| s.as_str()).collect();
+
+    let container = Query::find_container_by_namespace_name_and_owners(
+        db_pool,
+        &resolved_namespace,
+        name,
+        &owner_id_refs,
+    )
+    .await
+    .map_err(container_lookup_error)?;
+
+    if !is_running(&container) {
+        return Err((
+            StatusCode::CONFLICT,
+            Json(json!({"error": "SSH is only available on a running container"})),
+        ));
+    }
+
+    let host = match container.tailnet_ip.clone() {
+        Some(ip) => ip,
+        None => get_tailscale_device_name(&container).await,
+    };
+    let user = exec_user(&container);
+    let details = V1ContainerSsh {
+        container_id: container.id.clone(),
+        command: format!("ssh {}@{}", user, host),
+        host,
+        port: 22,
+        user,
+        ..Default::default()
+    };
+    Ok((container, details))
+}
+
+/// Handler: How to SSH into a running container over the tailnet
+pub async fn get_container_ssh(
+    State(state): State<AppState>,
+    Extension(user_profile): Extension<V1UserProfile>,
+    Path((namespace, name)): Path<(String, String)>,
+) -> Result<Json<V1ContainerSsh>, (StatusCode, Json<serde_json::Value>)> {
+    let (_, details) =
+        running_container_ssh(&state.db_pool, &user_profile, &namespace, &name).await?;
+    Ok(Json(details))
+}
+
+/// Handler: Generate a keypair and authorize it on a running container until
+/// it expires, so plain `ssh` works without `nebu exec`. Returns the SSH
+/// details with the private key.
+pub async fn create_container_ssh_key(
+    State(state): State<AppState>,
+    Extension(user_profile): Extension<V1UserProfile>,
+    Path((namespace, name)): Path<(String, String)>,
+    QueryParams(params): QueryParams<V1ContainerSshKeyParams>,
+) -> Result<Json<V1ContainerSsh>, (StatusCode, Json<serde_json::Value>)> {
+    let db_pool = &state.db_pool;
+    let (container, mut details) =
+        running_container_ssh(db_pool, &user_profile, &namespace, &name).await?;
+
+    let ttl = match params.ttl.as_deref() {
+        Some(ttl) => humantime::parse_duration(ttl).map_err(|e| {
+            (
+                StatusCode::BAD_REQUEST,
+                Json(json!({"error": format!("Invalid ttl '{}': {}", ttl, e)})),
+            )
+        })?,
+        None => DEFAULT_SSH_KEY_TTL,
+    };
+    if ttl.is_zero() || ttl > MAX_SSH_KEY_TTL {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(json!({"error": format!(
+                "ttl must be between 1s and {}",
+                humantime::format_duration(MAX_SSH_KEY_TTL)
+            )})),
+        ));
+    }
+    let platform = match container.platform.clone() {
+        Some(platform) if platform == "runpod" || platform == "kube" => platform,
+        _ => {
+            return Err((
+                StatusCode::CONFLICT,
+                Json(json!({"error": "Container platform doesn't support SSH keys"})),
+            ))
+        }
     };
 
-    // Include user's email (assuming owner_id is user's email)
-    owner_ids.push(user_profile.email.clone());
+    let (private_key, public_key) = tokio::task::spawn_blocking(keys::generate_ssh_keypair)
+        .await
+        .map_err(|e| e.to_string())
+        .and_then(|result| result.map_err(|e| e.to_string()))
+        .map_err(|e| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({"error": format!("Failed to generate SSH keypair: {}", e)})),
+            )
+        })?;
+    let expires_at =
+        chrono::Utc::now() + chrono::Duration::from_std(ttl).unwrap_or(chrono::Duration::hours(1));
+
+    let platform = platform_factory(platform);
+    let platform = &platform;
+    let container_id = container.id.as_str();
+    authorize_temporary_key(
+        move |command| async move {
+            platform
+                .exec(container_id, &command, db_pool)
+                .await
+                .map_err(|e| e.to_string())
+        },
+        &public_key,
+        expires_at,
+    )
+    .await
+    .map_err(|e| {
+        (
+            StatusCode::BAD_GATEWAY,
+            Json(json!({"error": e.to_string()})),
+        )
+    })?;
+
+    details.private_key = Some(private_key);
+    details.public_key = Some(public_key);
+    details.expires_at = Some(expires_at.timestamp());
+    Ok(Json(details))
+}
+
+/// Record the desired status; the controller stops or resumes the container on
+/// its next reconcile.
+async fn _set_container_desired_status(
+    db_pool: &DatabaseConnection,
+    user_profile: &V1UserProfile,
+    namespace: &str,
+    name: &str,
+    desired_status: ContainerStatus,
+) -> Result<Json<V1Container>, (StatusCode, Json<serde_json::Value>)> {
+    let resolved_namespace = resolve_namespace(namespace, user_profile);
+
+    let owner_ids = user_profile.owner_ids();
+    let owner_id_refs: Vec<&str> = owner_ids.iter().map(|s| s.as_str()).collect();
+
+    let container = Query::find_container_by_namespace_name_and_owners(
+        db_pool,
+        &resolved_namespace,
+        name,
+        &owner_id_refs,
+    )
+    .await
+    .map_err(|e| match e {
+        sea_orm::DbErr::RecordNotFound(_) => (
+            StatusCode::NOT_FOUND,
+            Json(json!({"error": "Container not found"})),
+        ),
+        _ => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"error": format!("Database error: {}", e)})),
+        ),
+    })?;
+
+    debug!(
+        "Setting desired status of container {} to {}",
+        container.id, desired_status
+    );
+    let updated = Mutation::update_container_desired_status(
+        db_pool,
+        container.id.clone(),
+        Some(desired_status.to_string()),
+    )
+    .await
+    .map_err(|e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"error": format!("Failed to update container: {}", e)})),
+        )
+    })?;
+
+    // Tag the reconcile that acts on this change with the request's id
+    let updated = match crate::logging::current_request_id() {
+        Some(request_id) => {
+            Mutation::update_container_controller_data(db_pool, updated.id.clone(), |data| {
+                data.request_id = Some(request_id)
+            })
+            .await
+            .unwrap_or_else(|e| {
+                warn!(
+                    "Failed to record request id for container {}: {}",
+                    updated.id, e
+                );
+                updated
+            })
+        }
+        None => updated,
+    };
+
+    let out_container = updated.to_v1_container().map_err(|e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"error": format!("Failed to parse container: {}", e)})),
+        )
+    })?;
+    Ok(Json(out_container))
+}
+
+pub async fn get_container_events(
+    State(state): State<AppState>,
+    Extension(user_profile): Extension<V1UserProfile>,
+    Path((namespace, name)): Path<(String, String)>,
+) -> Result<Json<V1ContainerEvents>, (StatusCode, Json<serde_json::Value>)> {
+    let db_pool = &state.db_pool;
+    let resolved_namespace = resolve_namespace(&namespace, &user_profile);
+
+    let owner_ids = user_profile.owner_ids();
+
+    let owner = auth_ns(db_pool, &owner_ids, &resolved_namespace)
+        .await
+        .map_err(|e| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({"error": format!("Authorization error: {}", e)})),
+            )
+        })?;
+
+    let container = Query::find_container_by_namespace_name_and_owners(
+        db_pool,
+        &resolved_namespace,
+        &name,
+        &vec![owner.as_str()],
+    )
+    .await
+    .map_err(|e| match e {
+        sea_orm::DbErr::RecordNotFound(_) => (
+            StatusCode::NOT_FOUND,
+            Json(json!({"error": "Container not found"})),
+        ),
+        _ => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"error": format!("Database error: {}", e)})),
+        ),
+    })?;
+
+    debug!("Getting events for container: {}", container.id);
+    let events = Query::find_container_events(db_pool, &container.id)
+        .await
+        .map_err(|e| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({"error": format!("Database error: {}", e)})),
+            )
+        })?;
+
+    Ok(Json(V1ContainerEvents {
+        events: events.iter().map(|event| event.to_v1()).collect(),
+    }))
+}
+
+pub async fn fetch_container_logs(
+    State(state): State<AppState>,
+    Extension(user_profile): Extension<V1UserProfile>,
+    Path((namespace, name)): Path<(String, String)>,
+    QueryParams(params): QueryParams<V1LogParams>,
+) -> Result<Json<String>, (StatusCode, Json<serde_json::Value>)> {
+    let db_pool = &state.db_pool;
+    let resolved_namespace = resolve_namespace(&namespace, &user_profile);
+
+    let owner_ids = user_profile.owner_ids();
     let owner_id_refs: Vec<&str> = owner_ids.iter().map(|s| s.as_str()).collect();
 
     let container = Query::find_container_by_namespace_name_and_owners(
@@ -516,23 +1205,23 @@ pub async fn fetch_container_logs(
         )
     })?;
 
-    _fetch_container_logs_by_id(db_pool, &container.clone().id.to_string(), &user_profile).await
+    _fetch_container_logs_by_id(
+        db_pool,
+        &container.clone().id.to_string(),
+        &user_profile,
+        &params,
+    )
+    .await
 }
 
 pub async fn _fetch_container_logs_by_id(
     db_pool: &DatabaseConnection,
     id: &str,
     user_profile: &V1UserProfile,
+    params: &V1LogParams,
 ) -> Result<Json<String>, (StatusCode, Json<serde_json::Value>)> {
     // Collect owner IDs from user_profile to use in your `Query` call
-    let mut owner_ids: Vec<String> = user_profile
-        .organizations
-        .as_ref()
-        .map(|orgs| orgs.keys().cloned().collect())
-        .unwrap_or_default();
-
-    // Add user email if necessary for ownership checks
-    owner_ids.push(user_profile.email.clone());
+    let owner_ids = user_profile.owner_ids();
     let owner_id_refs: Vec<&str> = owner_ids.iter().map(|s| s.as_str()).collect();
 
     // Find the container in the DB, ensuring the user has permission
@@ -549,7 +1238,7 @@ pub async fn _fetch_container_logs_by_id(
 
     // Use the helper function to fetch logs
     let logs = platform
-        .logs(&container.id.to_string(), db_pool)
+        .logs(&container.id.to_string(), params, db_pool)
         .await
         .map_err(|err| {
             (
@@ -562,6 +1251,10 @@ pub async fn _fetch_container_logs_by_id(
 }
 
 #[axum::debug_handler]
+/// Apply a merge patch to a container. Changes to labels, timeout, health_check,
+/// meters, restart, queue, proxy_port and authz are written in place; changes to
+/// any of `RECREATE_FIELDS` recreate the container, or are rejected when
+/// `no_delete` is set.
 pub async fn patch_container(
     State(state): State<AppState>,
     Extension(user_profile): Extension<V1UserProfile>,
@@ -572,406 +1265,169 @@ pub async fn patch_container(
     let resolved_namespace = resolve_namespace(&namespace, &user_profile);
 
     // Collect owner IDs from user_profile to use in your `Query` call
-    let mut owner_ids: Vec<String> = user_profile
-        .organizations
-        .as_ref()
-        .map(|orgs| orgs.keys().cloned().collect())
-        .unwrap_or_default();
-
-    // Add user email if necessary for ownership checks
-    owner_ids.push(user_profile.email.clone());
+    let owner_ids = user_profile.owner_ids();
     let owner_id_refs: Vec<&str> = owner_ids.iter().map(|s| s.as_str()).collect();
 
     // Find the container in the DB, ensuring the user has permission
-    let container = match Query::find_container_by_namespace_name_and_owners(
+    let container = Query::find_container_by_namespace_name_and_owners(
         db_pool,
         &resolved_namespace,
         &name,
         &owner_id_refs,
     )
     .await
-    {
-        Ok(container) => container,
-        Err(e) => {
-            return Err((
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(json!({"error": format!("Database error: {}", e)})),
-            ));
-        }
-    };
-
-    let container_ref = &container;
-    let no_delete = update_request.no_delete.unwrap_or(false);
-
-    let container_env = container
-        .env
-        .clone()
-        .and_then(|json_value| serde_json::from_value::<Vec<V1EnvVar>>(json_value).ok())
-        .unwrap_or_default();
-
-    let container_volumes = container
-        .volumes
-        .clone()
-        .and_then(|json_value| serde_json::from_value::<Vec<V1VolumePath>>(json_value).ok())
-        .unwrap_or_default();
-
-    let container_resources = container
-        .resources
-        .clone()
-        .and_then(|json_value| serde_json::from_value::<V1ContainerResources>(json_value).ok())
-        .unwrap_or_default();
-
-    let container_meters = container
-        .meters
-        .clone()
-        .and_then(|json_value| serde_json::from_value::<Vec<V1Meter>>(json_value).ok())
-        .unwrap_or_default();
-
-    let container_health_check = container
-        .health_check
-        .clone()
-        .and_then(|json_value| serde_json::from_value::<V1ContainerHealthCheck>(json_value).ok())
-        .unwrap_or_default();
-
-    let container_authz = container
-        .authz
-        .clone()
-        .and_then(|json_value| serde_json::from_value::<V1AuthzConfig>(json_value).ok())
-        .unwrap_or_default();
-
-    //
-    //
-    //
-
-    let updated_platform = update_request
-        .platform
-        .clone()
-        .unwrap_or(container.platform.clone().unwrap_or_default());
-    let updated_image = update_request
-        .image
-        .clone()
-        .unwrap_or(container.image.clone());
-    // let updated_ports = update_request.ports.clone().unwrap_or(container.ports);
-    // let updated_authz = update_request.authz.clone().unwrap_or(container.authz);
-
-    let updated_env = update_request.env.clone().unwrap_or(container_env.clone());
-    let updated_command = update_request
-        .command
-        .clone()
-        .unwrap_or_else(|| container.command.clone().unwrap_or_default());
-    let updated_args = update_request
-        .args
-        .clone()
-        .or_else(|| container.args.clone());
-    let updated_volumes = update_request
-        .volumes
-        .clone()
-        .unwrap_or(container_volumes.clone());
-    let updated_accelerators = update_request
-        .accelerators
-        .clone()
-        .unwrap_or_else(|| container.accelerators.clone().unwrap_or_default());
-    let updated_resources = update_request
-        .resources
-        .clone()
-        .unwrap_or(container_resources.clone());
-    let updated_meters = update_request
-        .meters
-        .clone()
-        .unwrap_or(container_meters.clone());
-    let updated_restart = update_request
-        .restart
-        .clone()
-        .unwrap_or_else(|| container.restart.clone());
-    let updated_queue = update_request
-        .queue
-        .clone()
-        .or_else(|| container.queue.clone());
-    let updated_timeout = update_request
-        .timeout
-        .clone()
-        .or_else(|| container.timeout.clone());
-    let updated_proxy_port = update_request
-        .proxy_port
-        .clone()
-        .unwrap_or_else(|| container.proxy_port.clone().unwrap_or_default());
-    let updated_health_check = update_request
-        .health_check
-        .clone()
-        .unwrap_or_else(|| container_health_check.clone());
-    let updated_authz = update_request
-        .authz
-        .clone()
-        .unwrap_or_else(|| container_authz.clone());
-
-    // Log changes in debug
-    {
-        {
-            debug!("Comparing new container fields with old container fields");
-        }
-    }
-    {
-        {
-            if updated_platform != container.platform.clone().unwrap_or_default() {
-                debug!(
-                    "platform changed from '{:?}' to '{:?}'",
-                    container.platform.clone().unwrap_or_default(),
-                    updated_platform
-                );
-            }
-        }
-    }
-    {
-        {
-            if updated_image != container.image {
-                debug!(
-                    "image changed from '{:?}' to '{:?}'",
-                    container.image, updated_image
-                );
-            }
-        }
-    }
-    {
-        {
-            let container_env_clone = container_env.clone();
-            if Some(updated_env.clone()) != Some(container_env_clone.clone()) {
-                debug!(
-                    "env changed from '{:?}' to '{:?}'",
-                    container_env_clone,
-                    updated_env.clone()
-                );
-            }
-        }
-    }
-    {
-        {
-            if Some(updated_command.clone()) != container.command.clone() {
-                debug!(
-                    "command changed from '{:?}' to '{:?}'",
-                    container.command, updated_command
-                );
-            }
-        }
-    }
-    {
-        {
-            if Some(updated_args.clone()) != Some(container.args.clone()) {
-                debug!(
-                    "args changed from '{:?}' to '{:?}'",
-                    container.args, updated_args
-                );
-            }
-        }
-    }
-    {
-        {
-            let container_volumes_clone = container_volumes.clone();
-            if Some(updated_volumes.clone()) != Some(container_volumes_clone.clone()) {
-                debug!(
-                    "volumes changed from '{:?}' to '{:?}'",
-                    container_volumes_clone,
-                    updated_volumes.clone()
-                );
-            }
-        }
-    }
-    {
-        {
-            if Some(updated_accelerators.clone()) != container.accelerators.clone() {
-                debug!(
-                    "accelerators changed from '{:?}' to '{:?}'",
-                    container.accelerators, updated_accelerators
-                );
-            }
-        }
-    }
-    {
-        {
-            let container_resources_clone = container_resources.clone();
-            if Some(updated_resources.clone()) != Some(container_resources_clone.clone()) {
-                debug!(
-                    "resources changed from '{:?}' to '{:?}'",
-                    container_resources_clone,
-                    updated_resources.clone()
-                );
-            }
-        }
-    }
-    {
-        {
-            let container_meters_clone = container_meters.clone();
-            if Some(updated_meters.clone()) != Some(container_meters_clone.clone()) {
-                debug!(
-                    "meters changed from '{:?}' to '{:?}'",
-                    container_meters_clone,
-                    updated_meters.clone()
-                );
-            }
-        }
+    .map_err(|e| match e {
+        sea_orm::DbErr::RecordNotFound(_) => (
+            StatusCode::NOT_FOUND,
+            Json(json!({"error": "Container not found"})),
+        ),
+        _ => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"error": format!("Database error: {}", e)})),
+        ),
+    })?;
+
+    let current = container.to_v1_container().map_err(|e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"error": format!("Failed to parse container: {}", e)})),
+        )
+    })?;
+    let updated = apply_container_patch(&current, &update_request);
+    if updated.meters != current.meters {
+        check_meter_subjects(updated.meters.as_ref(), &owner_ids)?;
     }
-    {
-        {
-            if updated_restart != container.restart.clone() {
-                debug!(
-                    "restart changed from '{:?}' to '{:?}'",
-                    container.restart, updated_restart
-                );
-            }
-        }
+    if let Some(ssh_keys) = &updated.ssh_keys {
+        crate::validate::validate_ssh_keys(ssh_keys).map_err(|e| {
+            (
+                StatusCode::BAD_REQUEST,
+                Json(json!({ "error": e.to_string() })),
+            )
+        })?;
     }
-    {
-        {
-            if Some(updated_queue.clone()) != Some(container.queue.clone()) {
-                debug!(
-                    "queue changed from '{:?}' to '{:?}'",
-                    container.queue, updated_queue
-                );
-            }
-        }
+    if updated.ssh_keys != current.ssh_keys {
+        check_ssh_keys_supported(
+            updated.ssh_keys.as_deref(),
+            std::iter::once(updated.platform.as_str())
+                .chain(updated.platforms.iter().flatten().map(|p| p.as_str())),
+        )?;
     }
-    {
-        {
-            if Some(updated_timeout.clone()) != Some(container.timeout.clone()) {
-                debug!(
-                    "timeout changed from '{:?}' to '{:?}'",
-                    container.timeout, updated_timeout
-                );
-            }
-        }
+    let changed = changed_fields(&current, &updated);
+    debug!("Container {} changed fields: {:?}", container.id, changed);
+
+    if changed.is_empty() {
+        debug!("No changes to container, skipping update");
+        return Ok(Json(current));
     }
-    {
-        {
-            if Some(updated_proxy_port.clone()) != container.proxy_port.clone() {
-                debug!(
-                    "proxy_port changed from '{:?}' to '{:?}'",
-                    container.proxy_port, updated_proxy_port
-                );
-            }
-        }
+
+    let recreate_fields: Vec<&str> = changed
+        .iter()
+        .copied()
+        .filter(|field| RECREATE_FIELDS.contains(field))
+        .collect();
+
+    if recreate_fields.is_empty() {
+        let updated_model =
+            Mutation::update_container_in_place(db_pool, container.id.clone(), &updated)
+                .await
+                .map_err(|e| {
+                    (
+                        StatusCode::INTERNAL_SERVER_ERROR,
+                        Json(json!({"error": format!("Failed to update container: {}", e)})),
+                    )
+                })?;
+        let out_container = updated_model.to_v1_container().map_err(|e| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({"error": format!("Failed to parse container: {}", e)})),
+            )
+        })?;
+        return Ok(Json(out_container));
     }
-    {
-        {
-            if Some(updated_health_check.clone()) != Some(container_health_check.clone()) {
-                debug!(
-                    "health_check changed from '{:?}' to '{:?}'",
-                    container.health_check, updated_health_check
-                );
-            }
-        }
+
+    if update_request.no_delete.unwrap_or(false) {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(json!({
+                "error": format!(
+                    "Changes to {} require recreating the container, but no_delete=true",
+                    recreate_fields.join(", ")
+                ),
+                "fields": recreate_fields,
+            })),
+        ));
     }
-    {
-        {
-            if Some(updated_authz.clone()) != Some(container_authz.clone()) {
-                debug!(
-                    "authz changed from '{:?}' to '{:?}'",
-                    container.authz, updated_authz
-                );
-            }
-        }
+
+    debug!("Deleting old container");
+    if let Err(e) = _delete_container_by_id(db_pool, &container.id, &user_profile).await {
+        return Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"error": format!("Failed to delete container: {:?}", e)})),
+        ));
     }
 
-    let changed_outside_metadata = {
-        let container_platform = container.platform.clone();
-        updated_platform.clone() != container_platform.unwrap_or_default()
-            || updated_image.clone() != container.image
-            // || updated_ssh_keys != container.ssh_keys
-            // || updated_ports != container.ports
-            // || updated_authz != container.authz
-            || Some(updated_env.clone()) != Some(container_env)
-            || Some(updated_command.clone()) != container.command
-            || updated_args != container.args
-            || Some(updated_volumes.clone()) != Some(container_volumes)
-            || Some(updated_accelerators.clone()) != container.accelerators
-            || Some(updated_resources.clone()) != Some(container_resources)
-            || Some(updated_meters.clone()) != Some(container_meters)
-            || updated_restart.clone() != container.restart
-            || updated_queue != container.queue
-            || updated_timeout != container.timeout
-            || Some(updated_proxy_port.clone()) != container.proxy_port
-            || Some(updated_health_check.clone()) != Some(container_health_check)
-            || Some(updated_authz.clone()) != Some(container_authz)
+    // Now we create the new container with the patched values
+    debug!("Creating new container with updated fields");
+    let platform_name = if updated.platform.is_empty() {
+        "runpod".to_string()
+    } else {
+        updated.platform.clone()
     };
-
-    // If anything changed, we may need to delete+recreate the container unless no_delete = true.
-    if changed_outside_metadata {
-        debug!("Container changed outside metadata");
-        if no_delete {
-            return Err((
-                StatusCode::BAD_REQUEST,
-                Json(json!({
-                    "error": "Container changes require deletion, but no_delete=true"
-                })),
-            ));
-        }
-
-        debug!("Deleting old container");
-        if let Err(e) = _delete_container_by_id(db_pool, &container.id, &user_profile).await {
-            return Err((
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(json!({"error": format!("Failed to delete container: {:?}", e)})),
-            ));
-        }
-
-        let request_meta = V1ResourceMetaRequest {
+    let to_create = V1ContainerRequest {
+        kind: "Container".to_string(),
+        platform: Some(platform_name.clone()),
+        platforms: updated.platforms,
+        metadata: Some(V1ResourceMetaRequest {
             name: Some(container.name.clone()),
             namespace: Some(container.namespace.clone()),
-            ..Default::default()
-        };
-
-        // Now we create the new container with merged (old + new) values
-        debug!("Creating new container with updated fields");
-        let to_create = V1ContainerRequest {
-            kind: "Container".to_string(),
-            platform: Some(updated_platform),
-            image: updated_image,
-            ssh_keys: None,
-            ports: None,
-            metadata: Some(request_meta),
-            env: Some(updated_env),
-            command: Some(updated_command),
-            args: updated_args,
-            volumes: Some(updated_volumes),
-            accelerators: Some(updated_accelerators),
-            resources: Some(updated_resources),
-            meters: Some(updated_meters),
-            restart: updated_restart,
-            queue: updated_queue,
-            timeout: updated_timeout,
-            proxy_port: Some(updated_proxy_port),
-            health_check: Some(updated_health_check),
-            authz: Some(updated_authz),
-        };
+            labels: updated.metadata.labels.clone(),
+            owner: Some(container.owner.clone()),
+            owner_ref: container.owner_ref.clone(),
+        }),
+        image: updated.image,
+        env: updated.env,
+        command: updated.command,
+        args: updated.args,
+        volumes: updated.volumes,
+        accelerators: updated.accelerators,
+        resources: updated.resources,
+        meters: updated.meters,
+        restart: updated.restart,
+        queue: updated.queue,
+        timeout: updated.timeout,
+        health_check: updated.health_check,
+        ssh_keys: updated.ssh_keys,
+        ports: updated.ports,
+        proxy_port: updated.proxy_port,
+        authz: updated.authz,
+        bootstrap: updated.bootstrap,
+        tailscale: updated.tailscale,
+        priority: updated.priority,
+        preemptible: updated.preemptible,
+        user: container.container_user.clone(),
+        webhook_url: updated.webhook_url,
+    };
 
-        let platform = platform_factory(
-            update_request
-                .clone()
-                .platform
-                .unwrap_or("runpod".to_string()),
-        );
-        let created = platform
-            .declare(
-                &to_create,
-                db_pool,
-                &user_profile,
-                &user_profile.email,
-                &namespace,
-                None,
+    let platform = platform_factory(platform_name);
+    let created = platform
+        .declare(
+            &to_create,
+            db_pool,
+            &user_profile,
+            &container.owner,
+            &container.namespace,
+            None,
+        )
+        .await
+        .map_err(|e| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({"error": e.to_string()})),
             )
-            .await
-            .map_err(|e| {
-                (
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    Json(json!({"error": e.to_string()})),
-                )
-            })?;
-        debug!("Created new container: {:?}", created);
-
-        return Ok(Json(created));
-    } else {
-        debug!("No changes to LLM server, skipping update");
-    }
+        })?;
+    debug!("Created new container: {:?}", created);
 
-    Ok(Json(container_ref.to_v1_container().unwrap()))
+    Ok(Json(created))
 }
 
 pub async fn _search_containers(
@@ -981,14 +1437,7 @@ pub async fn _search_containers(
 ) -> Result<Vec<V1Container>, (StatusCode, Json<serde_json::Value>)> {
     debug!("Searching for containers: {:?}", search);
     // Collect owner IDs from user_profile
-    let mut owner_ids: Vec<String> = user_profile
-        .organizations
-        .as_ref()
-        .map(|orgs| orgs.keys().cloned().collect())
-        .unwrap_or_default();
-
-    // Add user's email to owner IDs
-    owner_ids.push(user_profile.email.clone());
+    let owner_ids = user_profile.owner_ids();
     let owner_id_refs: Vec<&str> = owner_ids.iter().map(|s| s.as_str()).collect();
 
     let mut conditions = Condition::all();
@@ -1173,6 +1622,174 @@ pub async fn search_containers(
     Ok(Json(V1Containers { containers }))
 }
 
+/// 404 for a container the caller can't see, 500 for anything else.
+fn container_lookup_error(e: DbErr) -> (StatusCode, Json<serde_json::Value>) {
+    match e {
+        DbErr::RecordNotFound(msg) => (StatusCode::NOT_FOUND, Json(json!({"error": msg}))),
+        e => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"error": format!("Database error: {}", e)})),
+        ),
+    }
+}
+
+/// Most containers a single batch request may select
+const MAX_BATCH_SIZE: usize = 500;
+
+/// Containers a batch request works on at once
+const BATCH_CONCURRENCY: usize = 8;
+
+/// Resolves a batch request to container ids. Label selectors only match the
+/// caller's own containers; explicit ids are checked per item.
+async fn batch_container_ids(
+    db_pool: &DatabaseConnection,
+    request: &V1ContainerBatchRequest,
+    user_profile: &V1UserProfile,
+) -> Result<Vec<String>, (StatusCode, Json<serde_json::Value>)> {
+    let ids = match (&request.ids, &request.labels) {
+        (Some(ids), None) => {
+            let mut unique = Vec::with_capacity(ids.len());
+            for id in ids {
+                if !unique.contains(id) {
+                    unique.push(id.clone());
+                }
+            }
+            unique
+        }
+        (None, Some(labels)) if !labels.is_empty() => {
+            let search = V1ContainerSearch {
+                namespace: request
+                    .namespace
+                    .as_ref()
+                    .map(|ns| resolve_namespace(ns, user_profile)),
+                labels: Some(labels.clone()),
+                ..Default::default()
+            };
+            _search_containers(db_pool, &search, user_profile)
+                .await?
+                .into_iter()
+                .map(|c| c.metadata.id)
+                .collect()
+        }
+        _ => {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                Json(json!({"error": "Provide either 'ids' or a non-empty 'labels' selector"})),
+            ))
+        }
+    };
+
+    if ids.len() > MAX_BATCH_SIZE {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(json!({
+                "error": format!(
+                    "Batch selects {} containers, at most {} are allowed",
+                    ids.len(),
+                    MAX_BATCH_SIZE
+                )
+            })),
+        ));
+    }
+    Ok(ids)
+}
+
+/// Runs `operation` for every id, a few at a time, collecting one result per
+/// id in request order. A failing item never stops the rest.
+async fn run_container_batch<F, Fut>(ids: Vec<String>, operation: F) -> V1ContainerBatchResult
+where
+    F: Fn(String) -> Fut,
+    Fut: std::future::Future<
+        Output = Result<Option<V1Container>, (StatusCode, Json<serde_json::Value>)>,
+    >,
+{
+    let results: Vec<V1ContainerBatchItem> = futures::stream::iter(ids)
+        .map(|id| {
+            let outcome = operation(id.clone());
+            async move {
+                match outcome.await {
+                    Ok(container) => V1ContainerBatchItem {
+                        id,
+                        success: true,
+                        status_code: StatusCode::OK.as_u16(),
+                        error: None,
+                        container,
+                    },
+                    Err((status, Json(body))) => V1ContainerBatchItem {
+                        id,
+                        success: false,
+                        status_code: status.as_u16(),
+                        error: Some(
+                            body.get("error")
+                                .and_then(|e| e.as_str())
+                                .map(str::to_string)
+                                .unwrap_or_else(|| body.to_string()),
+                        ),
+                        container: None,
+                    },
+                }
+            }
+        })
+        .buffered(BATCH_CONCURRENCY)
+        .collect()
+        .await;
+
+    let succeeded = results.iter().filter(|r| r.success).count();
+    V1ContainerBatchResult {
+        failed: results.len() - succeeded,
+        succeeded,
+        results,
+    }
+}
+
+/// Deletes many containers at once, reporting success or failure per container.
+pub async fn batch_delete_containers(
+    State(state): State<AppState>,
+    Extension(user_profile): Extension<V1UserProfile>,
+    Json(request): Json<V1ContainerBatchRequest>,
+) -> Result<Json<V1ContainerBatchResult>, (StatusCode, Json<serde_json::Value>)> {
+    let db_pool = &state.db_pool;
+    let ids = batch_container_ids(db_pool, &request, &user_profile).await?;
+
+    let result = run_container_batch(ids, |id| {
+        let user_profile = &user_profile;
+        async move {
+            _delete_container_by_id(db_pool, &id, user_profile)
+                .await
+                .map(|_| None)
+        }
+    })
+    .await;
+    debug!(
+        "Batch delete: {} succeeded, {} failed",
+        result.succeeded, result.failed
+    );
+
+    Ok(Json(result))
+}
+
+/// Fetches many containers at once; missing ones are reported per item.
+pub async fn batch_container_status(
+    State(state): State<AppState>,
+    Extension(user_profile): Extension<V1UserProfile>,
+    Json(request): Json<V1ContainerBatchRequest>,
+) -> Result<Json<V1ContainerBatchResult>, (StatusCode, Json<serde_json::Value>)> {
+    let db_pool = &state.db_pool;
+    let ids = batch_container_ids(db_pool, &request, &user_profile).await?;
+
+    let result = run_container_batch(ids, |id| {
+        let user_profile = &user_profile;
+        async move {
+            _get_container_by_id(db_pool, &id, user_profile, false)
+                .await
+                .map(|Json(container)| Some(container))
+        }
+    })
+    .await;
+
+    Ok(Json(result))
+}
+
 // At the end of the file, add WebSocket support for streaming logs
 pub async fn stream_logs_ws(
     ws: WebSocketUpgrade,
@@ -1210,18 +1827,19 @@ async fn handle_socket_by_id(
     let (sender, _receiver) = socket.split(); // Receiver is not used anymore
 
     // Fetch container info
-    let mut owner_ids: Vec<String> = user_profile
-        .organizations
-        .as_ref()
-        .map(|orgs| orgs.keys().cloned().collect())
-        .unwrap_or_default();
-    owner_ids.push(user_profile.email.clone());
+    let owner_ids = user_profile.owner_ids();
     let owner_id_refs: Vec<&str> = owner_ids.iter().map(|s| s.as_str()).collect();
 
     match Query::find_container_by_id_and_owners(db_pool, &id, &owner_id_refs).await {
         Ok(container) => {
             // Start streaming logs (passing only the sender)
-            stream_container_logs(sender, container.id.to_string()).await;
+            stream_container_logs(
+                sender,
+                container.id.to_string(),
+                exec_user(&container),
+                bootstrap::container_log_file(&container),
+            )
+            .await;
         }
         Err(e) => {
             // If container fetch fails AFTER successful auth/upgrade, send error on socket
@@ -1253,12 +1871,7 @@ async fn handle_socket(
     let (sender, _receiver) = socket.split(); // Receiver is not used anymore
 
     // Fetch container info
-    let mut owner_ids: Vec<String> = user_profile
-        .organizations
-        .as_ref()
-        .map(|orgs| orgs.keys().cloned().collect())
-        .unwrap_or_default();
-    owner_ids.push(user_profile.email.clone());
+    let owner_ids = user_profile.owner_ids();
     let owner_id_refs: Vec<&str> = owner_ids.iter().map(|s| s.as_str()).collect();
 
     match Query::find_container_by_namespace_name_and_owners(
@@ -1271,7 +1884,13 @@ async fn handle_socket(
     {
         Ok(container) => {
             // Start streaming logs
-            stream_container_logs(sender, container.id.to_string()).await;
+            stream_container_logs(
+                sender,
+                container.id.to_string(),
+                exec_user(&container),
+                bootstrap::container_log_file(&container),
+            )
+            .await;
         }
         Err(e) => {
             // If container fetch fails AFTER successful auth/upgrade, send error on socket
@@ -1287,7 +1906,7 @@ async fn handle_socket(
     }
 }
 
-async fn stream_container_logs<S>(sender: S, container_id: String)
+async fn stream_container_logs<S>(sender: S, container_id: String, user: String, log_file: String)
 where
     S: SinkExt<Message> + Unpin + Send + 'static,
     <S as futures::Sink<Message>>::Error: std::fmt::Debug + Send,
@@ -1299,11 +1918,12 @@ where
     cmd.arg("-o")
         .arg("StrictHostKeyChecking=no")
         .arg("-l")
-        .arg("root") // TODO: get user from API
+        .arg(user)
         .arg(ssh_host)
         .arg("tail")
-        .arg("-f")
-        .arg("$HOME/.logs/nebu_container.log")
+        // Follows the name, so streaming carries on when the log is rotated
+        .arg("-F")
+        .arg(bootstrap::quoted_log_file(&log_file))
         .stdout(Stdio::piped())
         .stderr(Stdio::piped()); // Capture stderr too
 
@@ -1373,3 +1993,399 @@ where
         debug!("WebSocket connection closed by server.");
     }
 }
+
+/// Only admins (the root owner) may set a meter's subject, since it decides
+/// who the usage is billed to
+pub(crate) fn check_meter_subjects(
+    meters: Option<&Vec<V1Meter>>,
+    owner_ids: &[String],
+) -> Result<(), (StatusCode, Json<serde_json::Value>)> {
+    let sets_subject = meters.into_iter().flatten().any(|m| m.subject.is_some());
+    if sets_subject && !is_root_owner(owner_ids) {
+        return Err((
+            StatusCode::FORBIDDEN,
+            Json(json!({"error": "Only admins can set a meter's subject"})),
+        ));
+    }
+    Ok(())
+}
+
+/// Only admins (the root owner) may ask for soft-deleted resources
+pub(crate) fn check_include_deleted(
+    params: &V1ListParams,
+    owner_ids: &[String],
+) -> Result<(), (StatusCode, Json<serde_json::Value>)> {
+    if params.include_deleted && !is_root_owner(owner_ids) {
+        return Err((
+            StatusCode::FORBIDDEN,
+            Json(json!({"error": "include_deleted is only available to admins"})),
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn user() -> V1UserProfile {
+        V1UserProfile {
+            email: "me@example.com".to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_batch_reports_missing_ids_alongside_successes() {
+        // Arrange
+        let ids = vec!["c1".to_string(), "missing".to_string(), "c2".to_string()];
+
+        // Act
+        let result = run_container_batch(ids, |id| async move {
+            if id == "missing" {
+                Err(container_lookup_error(DbErr::RecordNotFound(format!(
+                    "Container with id '{}' not found",
+                    id
+                ))))
+            } else {
+                Ok(None)
+            }
+        })
+        .await;
+
+        // Assert
+        assert_eq!(result.succeeded, 2);
+        assert_eq!(result.failed, 1);
+        let ids: Vec<&str> = result.results.iter().map(|r| r.id.as_str()).collect();
+        assert_eq!(ids, vec!["c1", "missing", "c2"]);
+        let missing = &result.results[1];
+        assert!(!missing.success);
+        assert_eq!(missing.status_code, 404);
+        assert_eq!(
+            missing.error.as_deref(),
+            Some("Container with id 'missing' not found")
+        );
+        assert!(result.results[0].success && result.results[2].success);
+    }
+
+    #[tokio::test]
+    async fn test_batch_keeps_going_after_server_errors() {
+        let result = run_container_batch(vec!["a".to_string(), "b".to_string()], |id| async move {
+            if id == "a" {
+                Err((
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    Json(json!({"error": "Failed to delete container: boom"})),
+                ))
+            } else {
+                Ok(None)
+            }
+        })
+        .await;
+
+        assert_eq!(result.results[0].status_code, 500);
+        assert!(result.results[1].success);
+        assert_eq!((result.succeeded, result.failed), (1, 1));
+    }
+
+    #[tokio::test]
+    async fn test_batch_ids_need_exactly_one_selector() {
+        let db = DatabaseConnection::Disconnected;
+        let labels = HashMap::from([("sweep".to_string(), "42".to_string())]);
+
+        let neither = V1ContainerBatchRequest::default();
+        let both = V1ContainerBatchRequest {
+            ids: Some(vec!["c1".to_string()]),
+            labels: Some(labels),
+            namespace: None,
+        };
+        for request in [neither, both] {
+            let err = batch_container_ids(&db, &request, &user())
+                .await
+                .unwrap_err();
+            assert_eq!(err.0, StatusCode::BAD_REQUEST);
+        }
+
+        let duplicated = V1ContainerBatchRequest {
+            ids: Some(vec!["c1".to_string(), "c2".to_string(), "c1".to_string()]),
+            ..Default::default()
+        };
+        let ids = batch_container_ids(&db, &duplicated, &user())
+            .await
+            .unwrap();
+        assert_eq!(ids, vec!["c1".to_string(), "c2".to_string()]);
+
+        let too_many = V1ContainerBatchRequest {
+            ids: Some((0..=MAX_BATCH_SIZE).map(|i| i.to_string()).collect()),
+            ..Default::default()
+        };
+        let err = batch_container_ids(&db, &too_many, &user())
+            .await
+            .unwrap_err();
+        assert_eq!(err.0, StatusCode::BAD_REQUEST);
+    }
+
+    async fn dry_run_state() -> AppState {
+        use crate::state::MessageQueue;
+
+        AppState {
+            db_pool: crate::db::test_db().await,
+            message_queue: MessageQueue::Redis {
+                client: Arc::new(redis::Client::open("redis://127.0.0.1:1").unwrap()),
+            },
+            extra_queues: Default::default(),
+        }
+    }
+
+    fn dry_run_request(image: &str) -> V1ContainerRequest {
+        V1ContainerRequest {
+            platform: Some("kube".to_string()),
+            metadata: Some(V1ResourceMetaRequest {
+                name: Some("trial".to_string()),
+                ..Default::default()
+            }),
+            image: image.to_string(),
+            ..Default::default()
+        }
+    }
+
+    async fn create(
+        state: &AppState,
+        request: V1ContainerRequest,
+    ) -> Result<axum::response::Response, (StatusCode, Json<serde_json::Value>)> {
+        create_container(
+            State(state.clone()),
+            Extension(user()),
+            HeaderMap::new(),
+            QueryParams(V1CreateContainerParams { dry_run: true }),
+            Json(request),
+        )
+        .await
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a Postgres at NEBU_TEST_DATABASE_URL"]
+    async fn test_dry_run_returns_plan_without_creating_anything() {
+        use crate::entities::namespaces;
+        use crate::resources::v1::containers::models::V1ContainerPlan;
+
+        // Arrange
+        let state = dry_run_state().await;
+
+        // Act
+        let response = create(&state, dry_run_request("busybox:latest"))
+            .await
+            .unwrap();
+
+        // Assert
+        assert_eq!(response.status(), StatusCode::OK);
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let plan: V1ContainerPlan = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(plan.platform, "kube");
+        assert!(containers::Entity::find()
+            .all(&state.db_pool)
+            .await
+            .unwrap()
+            .is_empty());
+        // Not even the implicit namespace
+        assert!(namespaces::Entity::find()
+            .all(&state.db_pool)
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a Postgres at NEBU_TEST_DATABASE_URL"]
+    async fn test_dry_run_still_validates() {
+        let state = dry_run_state().await;
+
+        let (status, _) = create(&state, dry_run_request("not a valid image!"))
+            .await
+            .unwrap_err();
+
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(containers::Entity::find()
+            .all(&state.db_pool)
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a Postgres at NEBU_TEST_DATABASE_URL"]
+    async fn test_only_missing_containers_are_not_found() {
+        use sea_orm::ConnectionTrait;
+
+        let state = dry_run_state().await;
+        let (missing, _) = _get_container_by_id(&state.db_pool, "c1", &user(), false)
+            .await
+            .unwrap_err();
+        let (missing_delete, _) = _delete_container_by_id(&state.db_pool, "c1", &user())
+            .await
+            .err()
+            .unwrap();
+
+        state
+            .db_pool
+            .execute_unprepared("DROP TABLE containers")
+            .await
+            .unwrap();
+        let (broken, body) = _get_container_by_id(&state.db_pool, "c1", &user(), false)
+            .await
+            .unwrap_err();
+        let (broken_delete, _) = _delete_container_by_id(&state.db_pool, "c1", &user())
+            .await
+            .err()
+            .unwrap();
+
+        assert_eq!(
+            (missing, missing_delete),
+            (StatusCode::NOT_FOUND, StatusCode::NOT_FOUND)
+        );
+        assert_eq!(
+            (broken, broken_delete),
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                StatusCode::INTERNAL_SERVER_ERROR
+            )
+        );
+        assert!(body.0["error"]
+            .as_str()
+            .unwrap()
+            .starts_with("Database error"));
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a Postgres at NEBU_TEST_DATABASE_URL"]
+    async fn test_ssh_keys_are_refused_on_kube() {
+        let state = dry_run_state().await;
+        let key = V1SSHKey {
+            public_key: Some(
+                "ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAAIBfu6M2GuTXcOmhgUu1PPGUSrht7g4CQLwJ2ddo9wDvO dev@laptop"
+                    .to_string(),
+            ),
+            public_key_secret: None,
+            copy_local: None,
+        };
+        let on_kube = V1ContainerRequest {
+            ssh_keys: Some(vec![key.clone()]),
+            ..dry_run_request("busybox:latest")
+        };
+        let falling_back_to_kube = V1ContainerRequest {
+            platform: None,
+            platforms: Some(vec!["runpod".to_string(), "kube".to_string()]),
+            ..on_kube.clone()
+        };
+
+        for request in [on_kube, falling_back_to_kube] {
+            let (status, body) = create(&state, request).await.unwrap_err();
+            assert_eq!(status, StatusCode::BAD_REQUEST);
+            assert_eq!(
+                body.0["error"],
+                "ssh_keys are not supported on the kube platform"
+            );
+        }
+        let no_keys = V1ContainerRequest {
+            ssh_keys: Some(Vec::new()),
+            ..dry_run_request("busybox:latest")
+        };
+        assert!(create(&state, no_keys).await.is_ok());
+    }
+
+    async fn insert_container(state: &AppState, status: ContainerStatus) {
+        use crate::resources::v1::containers::models::V1ContainerStatus;
+        use sea_orm::{ActiveModelTrait, Set};
+
+        containers::ActiveModel {
+            id: Set("c1".to_string()),
+            namespace: Set("ns".to_string()),
+            name: Set("train".to_string()),
+            full_name: Set("ns/train".to_string()),
+            owner: Set(user().email),
+            image: Set("busybox".to_string()),
+            restart: Set("Never".to_string()),
+            platform: Set(Some("runpod".to_string())),
+            status: Set(Some(json!(V1ContainerStatus {
+                status: Some(status.to_string()),
+                ..Default::default()
+            }))),
+            tailnet_ip: Set(Some("100.64.0.7".to_string())),
+            container_user: Set(Some("ubuntu".to_string())),
+            updated_at: Set(chrono::Utc::now().into()),
+            created_at: Set(chrono::Utc::now().into()),
+            ..Default::default()
+        }
+        .insert(&state.db_pool)
+        .await
+        .unwrap();
+    }
+
+    async fn ssh(
+        state: &AppState,
+    ) -> Result<Json<V1ContainerSsh>, (StatusCode, Json<serde_json::Value>)> {
+        get_container_ssh(
+            State(state.clone()),
+            Extension(user()),
+            Path(("ns".to_string(), "train".to_string())),
+        )
+        .await
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a Postgres at NEBU_TEST_DATABASE_URL"]
+    async fn test_ssh_details_match_the_container() {
+        // Arrange
+        let state = dry_run_state().await;
+        insert_container(&state, ContainerStatus::Running).await;
+
+        // Act
+        let Json(details) = ssh(&state).await.unwrap();
+
+        // Assert
+        assert_eq!(details.container_id, "c1");
+        assert_eq!(details.host, "100.64.0.7");
+        assert_eq!(details.user, "ubuntu");
+        assert_eq!(details.port, 22);
+        assert_eq!(details.command, "ssh ubuntu@100.64.0.7");
+        assert_eq!(details.private_key, None);
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a Postgres at NEBU_TEST_DATABASE_URL"]
+    async fn test_ssh_details_need_a_running_container() {
+        let state = dry_run_state().await;
+        insert_container(&state, ContainerStatus::Pending).await;
+
+        let (status, _) = ssh(&state).await.unwrap_err();
+
+        assert_eq!(status, StatusCode::CONFLICT);
+    }
+
+    #[test]
+    fn test_only_admins_set_meter_subjects() {
+        let billed_elsewhere = vec![V1Meter {
+            metric: "runtime".to_string(),
+            subject: Some("someone-else".to_string()),
+            ..Default::default()
+        }];
+        let owner_ids = user().owner_ids();
+
+        let (status, _) = check_meter_subjects(Some(&billed_elsewhere), &owner_ids).unwrap_err();
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        assert!(check_meter_subjects(
+            Some(&billed_elsewhere),
+            &[crate::config::SERVER_CONFIG.root_owner.clone()]
+        )
+        .is_ok());
+
+        let own = vec![V1Meter {
+            subject: None,
+            ..billed_elsewhere[0].clone()
+        }];
+        assert!(check_meter_subjects(Some(&own), &owner_ids).is_ok());
+        assert!(check_meter_subjects(None, &owner_ids).is_ok());
+    }
+}
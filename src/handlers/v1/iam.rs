@@ -1,20 +1,25 @@
 use crate::agent::aws::{
     create_s3_scoped_user, delete_s3_scoped_user, generate_temporary_s3_credentials,
-    IamCredentials, StsCredentials,
+    is_namespace_data, namespace_prefix, scoped_prefix, split_s3_uri, IamCredentials,
+    StsCredentials,
 };
 use crate::agent::ns::auth_ns;
 use crate::config::SERVER_CONFIG;
+use crate::handlers::v1::namespaces::implicit_namespace;
 use crate::models::{V1ResourceMeta, V1UserProfile};
+use crate::query::Query;
 use crate::state::AppState;
+use crate::utils::namespace::{resolve_namespace, user_handle};
 use aws_config::{self, BehaviorVersion, Region};
 use aws_sdk_iam::Client as IamClient;
 use axum::{
-    extract::{Extension, Path, State},
+    extract::{Extension, Path, Query as QueryParams, State},
     http::StatusCode,
     response::IntoResponse,
     Json,
 };
-use serde::Serialize;
+use sea_orm::DatabaseConnection;
+use serde::{Deserialize, Serialize};
 use serde_json::json;
 use tracing::{debug, error};
 
@@ -26,6 +31,8 @@ pub struct V1IamCredentialsResponse {
     access_key_id: String,
     secret_access_key: String,
     base_key: String,
+    /// Key prefix the credentials are limited to
+    prefix: String,
 }
 
 #[derive(Serialize)]
@@ -37,74 +44,145 @@ pub struct V1StsCredentialsResponse {
     session_token: String,
     expiration: Option<i64>,
     s3_base_uri: String,
+    /// Key prefix the credentials are limited to
+    prefix: String,
 }
 
-/// Handler: Create a new S3-scoped IAM user for a given namespace and name
-pub async fn create_scoped_s3_token(
-    State(state): State<AppState>,
-    Extension(user_profile): Extension<V1UserProfile>,
-    Path((namespace, name)): Path<(String, String)>,
-) -> Result<Json<V1IamCredentialsResponse>, (StatusCode, Json<serde_json::Value>)> {
-    debug!(?namespace, ?name, "Entered create_scoped_s3_token handler");
-    let db_pool = &state.db_pool;
+/// Narrows generated credentials below the namespace's data.
+#[derive(Debug, Deserialize, Default)]
+pub struct V1S3TokenParams {
+    /// Scope to this volume's source path instead of the namespace's data
+    pub volume: Option<String>,
+    /// A path below the namespace's data, or below `volume` when given
+    pub prefix: Option<String>,
+}
 
-    // --- Authorization ---
-    debug!("Starting authorization step");
-    let mut owner_ids: Vec<String> = if let Some(orgs) = &user_profile.organizations {
-        orgs.keys().cloned().collect()
-    } else {
-        Vec::new()
-    };
-    // Also allow authorization if the namespace matches the user's handle
-    if let Some(handle) = &user_profile.handle {
-        owner_ids.push(handle.clone());
-
-        debug!("Ensuring namespace: {}", handle);
-        match crate::handlers::v1::namespaces::ensure_namespace(
-            db_pool,
-            &handle,
-            &user_profile.email,
-            &user_profile.email,
-            None,
-        )
-        .await
-        {
-            Ok(_) => (),
-            Err(e) => {
+/// The bucket and key prefix credentials for `namespace` are limited to. A
+/// volume can only narrow them: one whose source is outside the namespace's
+/// data in `server_bucket` is refused.
+async fn s3_token_scope(
+    db_pool: &DatabaseConnection,
+    server_bucket: &str,
+    owner_ids: &[String],
+    namespace: &str,
+    params: &V1S3TokenParams,
+) -> Result<(String, String), (StatusCode, Json<serde_json::Value>)> {
+    let (bucket, base) = match &params.volume {
+        Some(volume_name) => {
+            let owner_id_refs: Vec<&str> = owner_ids.iter().map(|s| s.as_str()).collect();
+            let volume = Query::find_volume_by_namespace_name_and_owners(
+                db_pool,
+                namespace,
+                volume_name,
+                &owner_id_refs,
+            )
+            .await
+            .map_err(|e| {
+                (
+                    StatusCode::NOT_FOUND,
+                    Json(json!({"error": format!("Volume not found: {}", e)})),
+                )
+            })?;
+            let (bucket, prefix) = split_s3_uri(&volume.source)
+                .filter(|(_, prefix)| !prefix.is_empty())
+                .ok_or_else(|| {
+                    (
+                        StatusCode::BAD_REQUEST,
+                        Json(json!({
+                            "error": format!(
+                                "Volume '{}' source '{}' has no S3 key prefix to scope to",
+                                volume_name, volume.source
+                            )
+                        })),
+                    )
+                })?;
+            if !is_namespace_data(server_bucket, namespace, &bucket, &prefix) {
                 return Err((
-                    StatusCode::BAD_REQUEST,
-                    Json(json!({ "error": format!("Invalid namespace: {}", e) })),
+                    StatusCode::FORBIDDEN,
+                    Json(json!({
+                        "error": format!(
+                            "Volume '{}' source '{}' is outside namespace '{}'",
+                            volume_name, volume.source, namespace
+                        )
+                    })),
                 ));
             }
+            (bucket, prefix)
         }
+        None => (server_bucket.to_string(), namespace_prefix(namespace)),
+    };
+
+    let prefix = scoped_prefix(&base, params.prefix.as_deref()).map_err(|e| {
+        (
+            StatusCode::BAD_REQUEST,
+            Json(json!({"error": e.to_string()})),
+        )
+    })?;
+    Ok((bucket, prefix))
+}
+
+/// Resolves the namespace a token is for and checks the caller owns it, the
+/// same way the namespace handlers do: `-` names the caller's own namespace,
+/// which is only created when `allow_implicit` is on. Returns the namespace
+/// and its owner.
+async fn authorize_namespace(
+    db_pool: &DatabaseConnection,
+    user_profile: &V1UserProfile,
+    namespace: &str,
+    allow_implicit: bool,
+) -> Result<(String, String), (StatusCode, Json<serde_json::Value>)> {
+    let namespace = resolve_namespace(namespace, user_profile);
+    if namespace == user_handle(user_profile) {
+        implicit_namespace(db_pool, user_profile, allow_implicit).await?;
     }
-    owner_ids.push(user_profile.email.clone());
-    debug!(?owner_ids, "Constructed owner_ids for authorization check");
-
-    debug!("Calling auth_ns");
-    let owner = match auth_ns(db_pool, &owner_ids, &namespace).await {
-        Ok(owner) => {
-            debug!(?owner, "auth_ns successful");
-            owner
-        }
+
+    match auth_ns(db_pool, &user_profile.owner_ids(), &namespace).await {
+        Ok(owner) => Ok((namespace, owner)),
         Err(e) => {
             error!("Authorization failed for namespace {}: {}", namespace, e);
-            debug!("Returning 403 Forbidden due to auth_ns failure");
-            return Err((
+            Err((
                 StatusCode::FORBIDDEN,
                 Json(json!({"error": format!("Not authorized for namespace '{}'", namespace)})),
-            ));
+            ))
         }
-    };
+    }
+}
 
-    // --- Get Bucket Name from Config ---
-    // Bucket name is read from global SERVER_CONFIG at startup
-    let bucket_name = SERVER_CONFIG.bucket_name.clone();
-    debug!(?bucket_name, "Retrieved bucket name from config");
+/// Handler: Create a new S3-scoped IAM user for a given namespace and name
+pub async fn create_scoped_s3_token(
+    State(state): State<AppState>,
+    Extension(user_profile): Extension<V1UserProfile>,
+    Path((namespace, name)): Path<(String, String)>,
+    QueryParams(params): QueryParams<V1S3TokenParams>,
+) -> Result<Json<V1IamCredentialsResponse>, (StatusCode, Json<serde_json::Value>)> {
+    debug!(?namespace, ?name, "Entered create_scoped_s3_token handler");
+    let db_pool = &state.db_pool;
+
+    // --- Authorization ---
+    debug!("Starting authorization step");
+    let (namespace, owner) = authorize_namespace(
+        db_pool,
+        &user_profile,
+        &namespace,
+        SERVER_CONFIG.allow_implicit_namespaces,
+    )
+    .await?;
+    let owner_ids = user_profile.owner_ids();
+
+    // --- Resolve the bucket and prefix to scope to ---
+    let (bucket_name, prefix) = s3_token_scope(
+        db_pool,
+        &SERVER_CONFIG.bucket_name,
+        &owner_ids,
+        &namespace,
+        &params,
+    )
+    .await?;
+    debug!(?bucket_name, ?prefix, "Resolved S3 token scope");
 
     // --- Call AWS Agent ---
     debug!("Calling create_s3_scoped_user");
-    let credentials = match create_s3_scoped_user(&bucket_name, &namespace, &name).await {
+    let credentials = match create_s3_scoped_user(&bucket_name, &prefix, &namespace, &name).await {
         Ok(creds) => {
             debug!("create_s3_scoped_user successful");
             creds
@@ -145,7 +223,8 @@ pub async fn create_scoped_s3_token(
         username: credentials.username.clone(),
         access_key_id: credentials.access_key_id,
         secret_access_key: credentials.secret_access_key,
-        base_key: format!("s3://{}/data/{}", bucket_name, namespace),
+        base_key: format!("s3://{}/{}", bucket_name, prefix),
+        prefix,
     };
 
     debug!("Returning Ok response");
@@ -161,30 +240,13 @@ pub async fn delete_scoped_s3_token(
     let db_pool = &state.db_pool;
 
     // --- Authorization ---
-    let mut owner_ids: Vec<String> = if let Some(orgs) = &user_profile.organizations {
-        orgs.keys().cloned().collect()
-    } else {
-        Vec::new()
-    };
-    // Also allow authorization if the namespace matches the user's handle
-    if let Some(handle) = &user_profile.handle {
-        owner_ids.push(handle.clone());
-    }
-    owner_ids.push(user_profile.email.clone());
-
-    match auth_ns(db_pool, &owner_ids, &namespace).await {
-        Ok(_) => (),
-        Err(e) => {
-            error!(
-                "Authorization failed for delete request on namespace {}: {}",
-                namespace, e
-            );
-            return Err((
-                StatusCode::FORBIDDEN,
-                Json(json!({"error": format!("Not authorized for namespace '{}'", namespace)})),
-            ));
-        }
-    };
+    let (namespace, _) = authorize_namespace(
+        db_pool,
+        &user_profile,
+        &namespace,
+        SERVER_CONFIG.allow_implicit_namespaces,
+    )
+    .await?;
 
     // --- Call AWS Agent to Delete ---
     let config = aws_config::defaults(BehaviorVersion::latest())
@@ -219,6 +281,7 @@ pub async fn generate_temp_s3_credentials(
     State(state): State<AppState>,
     Extension(user_profile): Extension<V1UserProfile>,
     Path((namespace, name)): Path<(String, String)>,
+    QueryParams(params): QueryParams<V1S3TokenParams>,
 ) -> Result<Json<V1StsCredentialsResponse>, (StatusCode, Json<serde_json::Value>)> {
     debug!(
         ?namespace,
@@ -228,69 +291,51 @@ pub async fn generate_temp_s3_credentials(
     let db_pool = &state.db_pool;
 
     // --- Authorization ---
-    let mut owner_ids: Vec<String> = if let Some(orgs) = &user_profile.organizations {
-        orgs.keys().cloned().collect()
-    } else {
-        Vec::new()
-    };
-    if let Some(handle) = &user_profile.handle {
-        owner_ids.push(handle.clone());
-
-        debug!("Ensuring namespace: {}", handle);
-        match crate::handlers::v1::namespaces::ensure_namespace(
-            db_pool,
-            &handle,
-            &user_profile.email,
-            &user_profile.email,
-            None,
-        )
-        .await
-        {
-            Ok(_) => (),
-            Err(e) => {
-                return Err((
-                    StatusCode::BAD_REQUEST,
-                    Json(json!({ "error": format!("Invalid namespace: {}", e) })),
-                ));
-            }
-        }
-    }
-    owner_ids.push(user_profile.email.clone());
-
-    let owner = match auth_ns(db_pool, &owner_ids, &namespace).await {
-        Ok(owner) => owner,
-        Err(e) => {
-            error!("Authorization failed for namespace {}: {}", namespace, e);
-            return Err((
-                StatusCode::FORBIDDEN,
-                Json(json!({"error": format!("Not authorized for namespace '{}'", namespace)})),
-            ));
-        }
-    };
+    let (namespace, owner) = authorize_namespace(
+        db_pool,
+        &user_profile,
+        &namespace,
+        SERVER_CONFIG.allow_implicit_namespaces,
+    )
+    .await?;
+    let owner_ids = user_profile.owner_ids();
 
-    // --- Get Bucket Name from Config ---
-    let bucket_name = SERVER_CONFIG.bucket_name.clone();
+    // --- Resolve the bucket and prefix to scope to ---
+    let (bucket_name, prefix) = s3_token_scope(
+        db_pool,
+        &SERVER_CONFIG.bucket_name,
+        &owner_ids,
+        &namespace,
+        &params,
+    )
+    .await?;
 
     // Default duration: 1 hour (3600 seconds)
     let duration_seconds = 3600;
 
     // --- Call AWS Agent with inline policy ---
-    let credentials =
-        match generate_temporary_s3_credentials(&bucket_name, &namespace, duration_seconds).await {
-            Ok(creds) => creds,
-            Err(e) => {
-                error!(
-                    "Failed to generate temporary S3 credentials '{}/{}': {}",
-                    namespace, name, e
-                );
-                return Err((
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    Json(json!({
-                        "error": format!("Failed to generate temporary AWS credentials: {}", e)
-                    })),
-                ));
-            }
-        };
+    let credentials = match generate_temporary_s3_credentials(
+        &bucket_name,
+        &prefix,
+        &namespace,
+        duration_seconds,
+    )
+    .await
+    {
+        Ok(creds) => creds,
+        Err(e) => {
+            error!(
+                "Failed to generate temporary S3 credentials '{}/{}': {}",
+                namespace, name, e
+            );
+            return Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({
+                    "error": format!("Failed to generate temporary AWS credentials: {}", e)
+                })),
+            ));
+        }
+    };
 
     // Convert expiration DateTime to Unix timestamp if present
     let expiration_timestamp = credentials.expiration.map(|dt| dt.as_secs_f64() as i64);
@@ -313,8 +358,136 @@ pub async fn generate_temp_s3_credentials(
         secret_access_key: credentials.secret_access_key,
         session_token: credentials.session_token,
         expiration: expiration_timestamp,
-        s3_base_uri: format!("s3://{}/data/{}", bucket_name, namespace),
+        s3_base_uri: format!("s3://{}/{}", bucket_name, prefix),
+        prefix,
     };
 
     Ok(Json(response))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::entities::{namespaces, volumes};
+    use sea_orm::{
+        ActiveModelTrait, ColumnTrait, ConnectionTrait, Database, EntityTrait, QueryFilter, Schema,
+    };
+
+    async fn db_with_volumes(sources: &[(&str, &str, &str)]) -> DatabaseConnection {
+        let db = Database::connect("sqlite::memory:").await.unwrap();
+        let schema = Schema::new(db.get_database_backend());
+        let stmt = schema.create_table_from_entity(volumes::Entity);
+        db.execute(db.get_database_backend().build(&stmt))
+            .await
+            .unwrap();
+        for (namespace, name, source) in sources {
+            let volume = volumes::Model::new(
+                format!("{}-{}", namespace, name),
+                name.to_string(),
+                namespace.to_string(),
+                "me".to_string(),
+                "me".to_string(),
+                None,
+                source.to_string(),
+            )
+            .unwrap();
+            volumes::ActiveModel::from(volume)
+                .insert(&db)
+                .await
+                .unwrap();
+        }
+        db
+    }
+
+    #[tokio::test]
+    async fn test_volume_scope_must_be_namespace_data() {
+        // Arrange
+        let db = db_with_volumes(&[
+            ("team-a", "ckpt", "s3://nebu/data/team-a/ckpt"),
+            ("team-a", "theirs", "s3://nebu/data/team-b/ckpt"),
+            ("team-a", "elsewhere", "s3://other-bucket/data/team-a"),
+        ])
+        .await;
+        let owners = vec!["me".to_string()];
+        let scope = |volume: &str, prefix: Option<&str>| V1S3TokenParams {
+            volume: Some(volume.to_string()),
+            prefix: prefix.map(String::from),
+        };
+
+        // Act
+        let own = s3_token_scope(
+            &db,
+            "nebu",
+            &owners,
+            "team-a",
+            &scope("ckpt", Some("run-1")),
+        )
+        .await
+        .unwrap();
+        let other_namespace =
+            s3_token_scope(&db, "nebu", &owners, "team-a", &scope("theirs", None)).await;
+        let other_bucket =
+            s3_token_scope(&db, "nebu", &owners, "team-a", &scope("elsewhere", None)).await;
+
+        // Assert
+        assert_eq!(
+            own,
+            ("nebu".to_string(), "data/team-a/ckpt/run-1".to_string())
+        );
+        assert_eq!(other_namespace.unwrap_err().0, StatusCode::FORBIDDEN);
+        assert_eq!(other_bucket.unwrap_err().0, StatusCode::FORBIDDEN);
+    }
+
+    #[tokio::test]
+    async fn test_tokens_only_for_owned_namespaces() {
+        let db = Database::connect("sqlite::memory:").await.unwrap();
+        let schema = Schema::new(db.get_database_backend());
+        db.execute(
+            db.get_database_backend()
+                .build(&schema.create_table_from_entity(namespaces::Entity)),
+        )
+        .await
+        .unwrap();
+        crate::handlers::v1::namespaces::ensure_namespace(
+            &db,
+            "jane",
+            "someone@example.com",
+            "someone@example.com",
+            None,
+        )
+        .await
+        .unwrap();
+        let jane = V1UserProfile {
+            email: "jane@example.com".to_string(),
+            handle: Some("jane".to_string()),
+            ..Default::default()
+        };
+        let joe = V1UserProfile {
+            email: "joe@example.com".to_string(),
+            handle: Some("joe".to_string()),
+            ..Default::default()
+        };
+
+        // A namespace named like the caller's handle isn't theirs
+        let (status, _) = authorize_namespace(&db, &jane, "-", true)
+            .await
+            .unwrap_err();
+        assert_eq!(status, StatusCode::FORBIDDEN);
+
+        // The caller's own namespace is only created when that's allowed
+        let (status, _) = authorize_namespace(&db, &joe, "joe", false)
+            .await
+            .unwrap_err();
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(namespaces::Entity::find()
+            .filter(namespaces::Column::Name.eq("joe"))
+            .one(&db)
+            .await
+            .unwrap()
+            .is_none());
+        assert_eq!(
+            authorize_namespace(&db, &joe, "-", true).await.unwrap(),
+            ("joe".to_string(), "joe@example.com".to_string())
+        );
+    }
+}
@@ -1,3 +1,4 @@
+pub mod accelerators;
 pub mod auth;
 pub mod cache;
 pub mod container;
@@ -6,24 +7,32 @@ pub mod namespaces;
 pub mod processors;
 pub mod secrets;
 pub mod volumes;
+pub use accelerators::list_accelerators;
 pub use auth::get_user_profile;
-pub use cache::{delete_cache_key, get_cache_key, list_cache_keys};
+pub use cache::{
+    delete_cache_key, get_cache_key, list_cache_key_pages, list_cache_keys, put_cache_key,
+};
 pub use container::{
-    create_container, delete_container, delete_container_by_id, fetch_container_logs,
-    fetch_container_logs_by_id, get_container, get_container_by_id, list_containers,
-    patch_container, search_containers, stream_logs_ws, stream_logs_ws_by_id,
+    batch_container_status, batch_delete_containers, create_container, create_container_ssh_key,
+    delete_container, delete_container_by_id, fetch_container_logs, fetch_container_logs_by_id,
+    get_container, get_container_by_id, get_container_events, get_container_ssh, list_containers,
+    patch_container, report_container_sync_progress, rotate_container_ssh, search_containers,
+    start_container, stop_container, stream_logs_ws, stream_logs_ws_by_id,
 };
 pub use iam::{create_scoped_s3_token, delete_scoped_s3_token, generate_temp_s3_credentials};
 pub use namespaces::{
-    create_namespace, delete_namespace, ensure_namespace, get_namespace, list_namespaces,
+    create_namespace, delete_namespace, ensure_namespace, get_namespace, get_namespace_spend,
+    list_namespaces, update_namespace,
 };
 pub use processors::{
-    check_processor_health, create_processor, delete_processor, get_processor, get_processor_logs,
-    list_processors, processor_websocket, read_processor_stream, read_return_message,
-    scale_processor, send_processor, stream_processor_return_ws, update_processor,
+    ack_processor_stream, check_processor_health, create_processor, delete_processor,
+    get_processor, get_processor_log_pages, get_processor_logs, get_processor_pending,
+    list_processors, peek_processor_stream, processor_websocket, read_processor_stream,
+    read_return_message, scale_processor, send_processor, stream_processor_return_ws,
+    update_processor,
 };
 pub use secrets::{
-    create_secret, delete_secret, delete_secret_by_id, get_secret, get_secret_by_id, list_secrets,
-    update_secret, update_secret_by_id,
+    create_secret, create_secrets, delete_secret, delete_secret_by_id, get_secret,
+    get_secret_by_id, list_secrets, update_secret, update_secret_by_id,
 };
 pub use volumes::{create_volume, delete_volume, get_volume, list_volumes};
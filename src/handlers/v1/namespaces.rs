@@ -2,18 +2,37 @@
 
 use crate::config::SERVER_CONFIG;
 use crate::entities::namespaces::{self, ActiveModel as NamespaceActiveModel};
-use crate::handlers::v1::volumes::ensure_volume;
+use crate::entities::volumes;
 use crate::models::V1UserProfile;
-use crate::resources::v1::namespaces::models::{V1Namespace, V1NamespaceRequest, V1Namespaces};
+use crate::mutation::Mutation;
+use crate::query::Query;
+use crate::resources::v1::containers::factory::{
+    platform_accelerator_map, platform_factory, PLATFORMS,
+};
+use crate::resources::v1::namespaces::base::{
+    plan_deletion, summarize_spend, NamespaceContents, NamespaceDeleteError, NamespaceDeletion,
+};
+use crate::resources::v1::namespaces::models::{
+    V1ContainerSpend, V1DeleteNamespaceParams, V1Namespace, V1NamespaceRequest, V1NamespaceSpend,
+    V1Namespaces, V1UpdateNamespace,
+};
+use crate::resources::v1::processors::base::ProcessorPlatform;
+use crate::resources::v1::processors::standard::StandardProcessor;
+use crate::resources::v1::volumes::base::ensure_volume;
 use crate::state::AppState;
-use axum::{extract::Extension, extract::Json, extract::Path, extract::State, http::StatusCode};
+use crate::utils::namespace::resolve_namespace;
+use axum::{
+    extract::Extension, extract::Json, extract::Path, extract::Query as QueryParams,
+    extract::State, http::StatusCode,
+};
 use sea_orm::DbErr;
 use sea_orm::{
     ActiveModelTrait, ActiveValue::Set, ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter,
 };
 use serde_json::json;
 use short_uuid;
-use tracing::debug;
+use std::sync::Arc;
+use tracing::{debug, info};
 
 pub async fn get_namespace(
     State(state): State<AppState>,
@@ -22,14 +41,7 @@ pub async fn get_namespace(
 ) -> Result<Json<V1Namespace>, (StatusCode, Json<serde_json::Value>)> {
     let db_pool = &state.db_pool;
 
-    let mut owner_ids: Vec<String> = if let Some(orgs) = &user_profile.organizations {
-        orgs.keys().cloned().collect()
-    } else {
-        Vec::new()
-    };
-
-    // Include user's email (assuming owner_id is user's email)
-    owner_ids.push(user_profile.email.clone());
+    let owner_ids = user_profile.owner_ids();
     let owner_id_refs: Vec<&str> = owner_ids.iter().map(|s| s.as_str()).collect();
 
     let namespace_entity = namespaces::Entity::find()
@@ -57,6 +69,52 @@ pub async fn get_namespace(
     Ok(Json(namespace_entity.to_v1()))
 }
 
+/// Active and historical spend across a namespace's containers.
+pub async fn get_namespace_spend(
+    State(state): State<AppState>,
+    Extension(user_profile): Extension<V1UserProfile>,
+    Path(name): Path<String>,
+) -> Result<Json<V1NamespaceSpend>, (StatusCode, Json<serde_json::Value>)> {
+    let db_pool = &state.db_pool;
+    let name = resolve_namespace(&name, &user_profile);
+
+    let owner_ids = user_profile.owner_ids();
+    let owner_id_refs: Vec<&str> = owner_ids.iter().map(|s| s.as_str()).collect();
+
+    let containers = Query::find_containers_by_namespace_and_owners_including_deleted(
+        db_pool,
+        &name,
+        &owner_id_refs,
+    )
+    .await
+    .map_err(|err| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"error": format!("Database error: {}", err)})),
+        )
+    })?;
+
+    let spend = containers
+        .into_iter()
+        .map(|c| {
+            let status = c.parse_status().ok().flatten();
+            V1ContainerSpend {
+                id: c.id,
+                name: c.name,
+                cost_per_hr: status
+                    .as_ref()
+                    .and_then(|s| s.cost_per_hr)
+                    .or(c.resource_cost_per_hr),
+                status: status.and_then(|s| s.status),
+                total_cost: c.total_cost.unwrap_or(0.0),
+                deleted: c.deleted_at.is_some(),
+            }
+        })
+        .collect();
+
+    Ok(Json(summarize_spend(&name, spend)))
+}
+
 pub async fn create_namespace(
     State(state): State<AppState>,
     Extension(user_profile): Extension<V1UserProfile>,
@@ -64,13 +122,21 @@ pub async fn create_namespace(
 ) -> Result<Json<V1Namespace>, (StatusCode, Json<serde_json::Value>)> {
     let db_pool = &state.db_pool;
 
+    crate::validate::validate_namespace(&namespace.metadata.name).map_err(|err| {
+        (
+            StatusCode::BAD_REQUEST,
+            Json(json!({ "error": err.to_string() })),
+        )
+    })?;
+    validate_default_platform(namespace.default_platform.as_deref())?;
+    validate_default_accelerators(
+        namespace.default_accelerators.as_deref(),
+        namespace.default_platform.as_deref(),
+    )?;
+    validate_default_user(namespace.default_user.as_deref())?;
+
     // Get owner IDs from organizations and email
-    let mut owner_ids: Vec<String> = if let Some(orgs) = &user_profile.organizations {
-        orgs.keys().cloned().collect()
-    } else {
-        Vec::new()
-    };
-    owner_ids.push(user_profile.email.clone());
+    let owner_ids = user_profile.owner_ids();
     let owner_id_refs: Vec<&str> = owner_ids.iter().map(|s| s.as_str()).collect();
 
     let mut owner_id = user_profile.email.clone();
@@ -142,6 +208,18 @@ pub async fn create_namespace(
         owner: Set(namespace_entity.owner),
         owner_ref: Set(namespace_entity.owner_ref),
         labels: Set(namespace_entity.labels),
+        default_env: Set(namespace
+            .default_env
+            .as_ref()
+            .map(|env| serde_json::to_value(env).unwrap_or_default())),
+        default_user: Set(namespace.default_user.clone().filter(|u| !u.is_empty())),
+        registry_secret: Set(namespace.registry_secret.clone()),
+        default_platform: Set(namespace.default_platform.clone().filter(|p| !p.is_empty())),
+        default_accelerators: Set(namespace
+            .default_accelerators
+            .as_ref()
+            .filter(|accelerators| !accelerators.is_empty())
+            .map(|accelerators| json!(accelerators))),
         created_by: Set(namespace_entity.created_by),
         updated_at: Set(namespace_entity.updated_at),
         created_at: Set(namespace_entity.created_at),
@@ -181,20 +259,150 @@ pub async fn create_namespace(
     Ok(Json(namespace_entity.to_v1()))
 }
 
+pub async fn update_namespace(
+    State(state): State<AppState>,
+    Extension(user_profile): Extension<V1UserProfile>,
+    Path(name): Path<String>,
+    Json(update): Json<V1UpdateNamespace>,
+) -> Result<Json<V1Namespace>, (StatusCode, Json<serde_json::Value>)> {
+    let db_pool = &state.db_pool;
+
+    let owner_ids = user_profile.owner_ids();
+    let owner_id_refs: Vec<&str> = owner_ids.iter().map(|s| s.as_str()).collect();
+
+    let namespace_entity = namespaces::Entity::find()
+        .filter(namespaces::Column::Name.eq(name.clone()))
+        .filter(namespaces::Column::Owner.is_in(owner_id_refs))
+        .one(db_pool)
+        .await
+        .map_err(|err| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({"error": format!("Database error: {}", err)})),
+            )
+        })?
+        .ok_or((
+            StatusCode::NOT_FOUND,
+            Json(json!({"error": format!("Namespace with name '{}' not found", name)})),
+        ))?;
+
+    validate_default_platform(update.default_platform.as_deref())?;
+    validate_default_user(update.default_user.as_deref())?;
+    // A new platform may not support the accelerators already set, and the
+    // other way round
+    if update.default_platform.is_some() || update.default_accelerators.is_some() {
+        let accelerators = match &update.default_accelerators {
+            Some(accelerators) => Some(accelerators.clone()),
+            None => namespace_entity
+                .parse_default_accelerators()
+                .unwrap_or_default(),
+        };
+        let platform = update
+            .default_platform
+            .clone()
+            .or_else(|| namespace_entity.default_platform.clone());
+        validate_default_accelerators(accelerators.as_deref(), platform.as_deref())?;
+    }
+
+    let mut namespace_am: NamespaceActiveModel = namespace_entity.into();
+    if let Some(labels) = update.labels {
+        namespace_am.labels = Set(Some(json!(labels)));
+    }
+    if let Some(default_env) = update.default_env {
+        namespace_am.default_env = Set(Some(json!(default_env)));
+    }
+    if let Some(default_user) = update.default_user {
+        namespace_am.default_user = Set(Some(default_user).filter(|u| !u.is_empty()));
+    }
+    if let Some(registry_secret) = update.registry_secret {
+        namespace_am.registry_secret = Set(Some(registry_secret).filter(|s| !s.is_empty()));
+    }
+    if let Some(default_platform) = update.default_platform {
+        namespace_am.default_platform = Set(Some(default_platform).filter(|p| !p.is_empty()));
+    }
+    if let Some(default_accelerators) = update.default_accelerators {
+        namespace_am.default_accelerators = Set(Some(default_accelerators)
+            .filter(|accelerators| !accelerators.is_empty())
+            .map(|accelerators| json!(accelerators)));
+    }
+    namespace_am.updated_at = Set(chrono::Utc::now().into());
+
+    let namespace_entity = namespace_am.update(db_pool).await.map_err(|err| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({"error": format!("Failed to update namespace: {}", err)})),
+        )
+    })?;
+
+    Ok(Json(namespace_entity.to_v1()))
+}
+
+/// An empty platform clears the default, anything else must be known
+fn validate_default_platform(
+    platform: Option<&str>,
+) -> Result<(), (StatusCode, Json<serde_json::Value>)> {
+    match platform.filter(|p| !p.is_empty()) {
+        Some(platform) => crate::validate::validate_platforms(&[platform.to_string()], PLATFORMS)
+            .map_err(|e| {
+                (
+                    StatusCode::BAD_REQUEST,
+                    Json(json!({ "error": format!("Invalid default_platform: {}", e) })),
+                )
+            }),
+        None => Ok(()),
+    }
+}
+
+/// Default accelerators must be ones `platform`, or RunPod without one,
+/// supports, as containers created with them are checked the same way
+fn validate_default_accelerators(
+    accelerators: Option<&[String]>,
+    platform: Option<&str>,
+) -> Result<(), (StatusCode, Json<serde_json::Value>)> {
+    let Some(accelerators) = accelerators.filter(|a| !a.is_empty()) else {
+        return Ok(());
+    };
+    let invalid = |e: String| {
+        (
+            StatusCode::BAD_REQUEST,
+            Json(json!({ "error": format!("Invalid default_accelerators: {}", e) })),
+        )
+    };
+    let platform = platform.filter(|p| !p.is_empty()).unwrap_or("runpod");
+    if !PLATFORMS.contains(&platform) {
+        return Err(invalid(format!("unknown platform '{}'", platform)));
+    }
+    let supported = platform_accelerator_map(platform);
+    for accelerator in accelerators {
+        crate::validate::validate_accelerator(accelerator, &supported)
+            .map_err(|e| invalid(e.to_string()))?;
+    }
+    Ok(())
+}
+
+/// An empty user clears the default, anything else must be a valid user
+fn validate_default_user(user: Option<&str>) -> Result<(), (StatusCode, Json<serde_json::Value>)> {
+    match user.filter(|u| !u.is_empty()) {
+        Some(user) => crate::validate::validate_container_user(user).map_err(|e| {
+            (
+                StatusCode::BAD_REQUEST,
+                Json(json!({ "error": format!("Invalid default_user: {}", e) })),
+            )
+        }),
+        None => Ok(()),
+    }
+}
+
 pub async fn delete_namespace(
     State(state): State<AppState>,
     Extension(user_profile): Extension<V1UserProfile>,
     Path(name): Path<String>,
+    QueryParams(params): QueryParams<V1DeleteNamespaceParams>,
 ) -> Result<(), (StatusCode, Json<serde_json::Value>)> {
     let db_pool = &state.db_pool;
 
     // Get owner IDs from organizations and email
-    let mut owner_ids: Vec<String> = if let Some(orgs) = &user_profile.organizations {
-        orgs.keys().cloned().collect()
-    } else {
-        Vec::new()
-    };
-    owner_ids.push(user_profile.email.clone());
+    let owner_ids = user_profile.owner_ids();
     let owner_id_refs: Vec<&str> = owner_ids.iter().map(|s| s.as_str()).collect();
 
     // Find the namespace to delete
@@ -220,6 +428,54 @@ pub async fn delete_namespace(
         })),
     ))?;
 
+    let contents = namespace_contents(db_pool, &namespace_entity.name)
+        .await
+        .map_err(|err| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({"error": format!("Database error: {}", err)})),
+            )
+        })?;
+
+    let deletion =
+        plan_deletion(&namespace_entity.name, &contents, params.cascade).map_err(|err| {
+            let status = match err {
+                NamespaceDeleteError::Protected(_) => StatusCode::FORBIDDEN,
+                NamespaceDeleteError::NotEmpty(_, _) => StatusCode::CONFLICT,
+            };
+            (status, Json(json!({"error": err.to_string()})))
+        })?;
+
+    if deletion == NamespaceDeletion::Cascade {
+        info!(
+            "Cascade deleting namespace '{}': {}",
+            namespace_entity.name,
+            contents.summary()
+        );
+        delete_namespace_resources(&state, &namespace_entity.name)
+            .await
+            .map_err(|err| {
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    Json(
+                        json!({"error": format!("Failed to delete namespace resources: {}", err)}),
+                    ),
+                )
+            })?;
+    }
+
+    // The namespace's volumes go with it
+    volumes::Entity::delete_many()
+        .filter(volumes::Column::Namespace.eq(namespace_entity.name.clone()))
+        .exec(db_pool)
+        .await
+        .map_err(|err| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({"error": format!("Failed to delete namespace volumes: {}", err)})),
+            )
+        })?;
+
     // Delete the namespace
     namespaces::Entity::delete_by_id(namespace_entity.id)
         .exec(db_pool)
@@ -234,6 +490,70 @@ pub async fn delete_namespace(
     Ok(())
 }
 
+/// Collects the live resources in a namespace. The namespace's own default
+/// volume is not counted, it is removed together with the namespace.
+async fn namespace_contents(
+    db_pool: &DatabaseConnection,
+    namespace: &str,
+) -> Result<NamespaceContents, DbErr> {
+    let (containers, processors, secrets, volumes) =
+        Query::find_namespace_resources(db_pool, namespace).await?;
+
+    Ok(NamespaceContents {
+        containers: containers.into_iter().map(|c| c.name).collect(),
+        processors: processors.into_iter().map(|p| p.name).collect(),
+        secrets: secrets.into_iter().map(|s| s.name).collect(),
+        volumes: volumes
+            .into_iter()
+            .filter(|v| v.name != namespace)
+            .map(|v| v.name)
+            .collect(),
+    })
+}
+
+/// Deletes the containers, processors and secrets in a namespace through the
+/// owning platforms. Processors go first since they own containers of their
+/// own. Volumes are removed with the namespace itself.
+async fn delete_namespace_resources(
+    state: &AppState,
+    namespace: &str,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let db_pool = &state.db_pool;
+
+    let (_, processors, _, _) = Query::find_namespace_resources(db_pool, namespace).await?;
+    if !processors.is_empty() {
+        let platform = StandardProcessor::new(Arc::new(state.clone()));
+        for processor in processors {
+            debug!(
+                "Deleting processor {} in namespace {}",
+                processor.id, namespace
+            );
+            platform.delete(&processor.id, db_pool).await?;
+        }
+    }
+
+    let (containers, _, secrets, _) = Query::find_namespace_resources(db_pool, namespace).await?;
+    for container in containers {
+        debug!(
+            "Deleting container {} in namespace {}",
+            container.id, namespace
+        );
+        if let Some(platform_name) = container.platform.clone() {
+            platform_factory(platform_name)
+                .delete(&container.id, db_pool)
+                .await?;
+        }
+        Mutation::delete_container(db_pool, container.id).await?;
+    }
+
+    for secret in secrets {
+        debug!("Deleting secret {} in namespace {}", secret.id, namespace);
+        Mutation::delete_secret(db_pool, secret.id).await?;
+    }
+
+    Ok(())
+}
+
 /// Internal helper function to ensure a namespace exists with the given parameters.
 /// Returns the namespace if it exists, or creates it if it doesn't.
 pub async fn ensure_namespace(
@@ -269,6 +589,11 @@ pub async fn ensure_namespace(
         owner: Set(owner.to_string()),
         owner_ref: Set(None),
         labels: Set(labels),
+        default_env: Set(None),
+        default_user: Set(None),
+        registry_secret: Set(None),
+        default_platform: Set(None),
+        default_accelerators: Set(None),
         created_by: Set(created_by.to_string()),
         updated_at: Set(chrono::Utc::now().into()),
         created_at: Set(chrono::Utc::now().into()),
@@ -280,6 +605,60 @@ pub async fn ensure_namespace(
     Ok((namespace_entity, true))
 }
 
+/// The namespace for a request that doesn't name one: the user's personal
+/// namespace, as named by `user_handle`. It is created on first use unless
+/// `allow_implicit` is off, in which case it has to exist already and the
+/// request is rejected with a 400 otherwise.
+pub async fn implicit_namespace(
+    db_pool: &DatabaseConnection,
+    user_profile: &V1UserProfile,
+    allow_implicit: bool,
+) -> Result<String, (StatusCode, Json<serde_json::Value>)> {
+    let handle = crate::utils::namespace::user_handle(user_profile);
+    debug!("Handle: {:?}", handle);
+
+    if allow_implicit {
+        ensure_namespace(
+            db_pool,
+            &handle,
+            &user_profile.email,
+            &user_profile.email,
+            None,
+        )
+        .await
+        .map_err(|e| {
+            (
+                StatusCode::BAD_REQUEST,
+                Json(json!({ "error": format!("Invalid namespace: {}", e) })),
+            )
+        })?;
+        return Ok(handle);
+    }
+
+    let existing = namespaces::Entity::find()
+        .filter(namespaces::Column::Name.eq(&handle))
+        .one(db_pool)
+        .await
+        .map_err(|e| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({ "error": format!("Database error: {}", e) })),
+            )
+        })?;
+    match existing {
+        Some(_) => Ok(handle),
+        None => Err((
+            StatusCode::BAD_REQUEST,
+            Json(json!({
+                "error": format!(
+                    "No namespace given and implicit namespace creation is disabled; set metadata.namespace or create namespace '{}'",
+                    handle
+                )
+            })),
+        )),
+    }
+}
+
 /// Handler: List namespaces for the current user (and their organizations)
 pub async fn list_namespaces(
     State(state): State<AppState>,
@@ -288,12 +667,7 @@ pub async fn list_namespaces(
     let db_pool = &state.db_pool;
 
     // Gather all possible owner IDs from user + organizations
-    let mut owner_ids: Vec<String> = if let Some(orgs) = &user_profile.organizations {
-        orgs.keys().cloned().collect()
-    } else {
-        Vec::new()
-    };
-    owner_ids.push(user_profile.email.clone());
+    let owner_ids = user_profile.owner_ids();
     let owner_id_refs: Vec<&str> = owner_ids.iter().map(|s| s.as_str()).collect();
 
     // Retrieve namespaces
@@ -343,3 +717,218 @@ pub async fn ensure_ns_and_resources(
         Err(e) => return Err(Box::new(e)),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::namespace::user_handle;
+    use sea_orm::{ConnectionTrait, Database, Schema};
+
+    async fn db() -> DatabaseConnection {
+        let db = Database::connect("sqlite::memory:").await.unwrap();
+        let schema = Schema::new(db.get_database_backend());
+        db.execute(
+            db.get_database_backend()
+                .build(&schema.create_table_from_entity(namespaces::Entity)),
+        )
+        .await
+        .unwrap();
+        db
+    }
+
+    fn profile() -> V1UserProfile {
+        V1UserProfile {
+            email: "Jane.Doe@example.com".to_string(),
+            ..Default::default()
+        }
+    }
+
+    /// State with namespace `team` holding its own volume and a secret
+    async fn state_with_team_namespace() -> AppState {
+        use crate::entities::secrets;
+        use crate::state::MessageQueue;
+
+        let db = crate::db::test_db().await;
+        let owner = profile().email;
+        for name in ["team", "root"] {
+            ensure_namespace(&db, name, &owner, &owner, None)
+                .await
+                .unwrap();
+        }
+        ensure_volume(
+            &db,
+            "team",
+            "team",
+            &owner,
+            "s3://nebu/data/team",
+            &owner,
+            None,
+        )
+        .await
+        .unwrap();
+        let secret = secrets::Model::new(
+            "s1".to_string(),
+            "token".to_string(),
+            "team".to_string(),
+            owner,
+            "hunter2",
+            None,
+            None,
+            None,
+        )
+        .unwrap();
+        secrets::ActiveModel::from(secret)
+            .insert(&db)
+            .await
+            .unwrap();
+
+        AppState {
+            db_pool: db,
+            message_queue: MessageQueue::Redis {
+                client: Arc::new(redis::Client::open("redis://127.0.0.1:1").unwrap()),
+            },
+            extra_queues: Default::default(),
+        }
+    }
+
+    async fn delete(
+        state: &AppState,
+        name: &str,
+        cascade: bool,
+    ) -> Result<(), (StatusCode, Json<serde_json::Value>)> {
+        delete_namespace(
+            State(state.clone()),
+            Extension(profile()),
+            Path(name.to_string()),
+            QueryParams(V1DeleteNamespaceParams { cascade }),
+        )
+        .await
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a Postgres at NEBU_TEST_DATABASE_URL"]
+    async fn test_delete_namespace_needs_cascade_when_not_empty() {
+        // Arrange
+        let state = state_with_team_namespace().await;
+        let db = &state.db_pool;
+
+        // Act
+        let refused = delete(&state, "team", false).await;
+        let protected = delete(&state, "root", true).await;
+        let missing = delete(&state, "nope", true).await;
+        let cascaded = delete(&state, "team", true).await;
+
+        // Assert
+        let (status, body) = refused.unwrap_err();
+        assert_eq!(status, StatusCode::CONFLICT);
+        assert!(body.0["error"].as_str().unwrap().contains("cascade=true"));
+        assert_eq!(protected.unwrap_err().0, StatusCode::FORBIDDEN);
+        assert_eq!(missing.unwrap_err().0, StatusCode::NOT_FOUND);
+        cascaded.unwrap();
+        let (containers, processors, secrets, volumes) =
+            Query::find_namespace_resources(db, "team").await.unwrap();
+        assert!(containers.is_empty() && processors.is_empty());
+        assert!(secrets.is_empty() && volumes.is_empty());
+        let names: Vec<String> = namespaces::Entity::find()
+            .all(db)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|n| n.name)
+            .collect();
+        assert_eq!(names, vec!["root".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn test_implicit_namespace_created_when_allowed() {
+        // Arrange
+        let db = db().await;
+
+        // Act
+        let namespace = implicit_namespace(&db, &profile(), true).await.unwrap();
+
+        // Assert
+        assert_eq!(namespace, user_handle(&profile()));
+        assert!(namespace.starts_with("jane-doe-example-com-"));
+        let created = namespaces::Entity::find()
+            .filter(namespaces::Column::Name.eq(namespace))
+            .one(&db)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(created.owner, "Jane.Doe@example.com");
+    }
+
+    #[tokio::test]
+    async fn test_implicit_namespace_rejected_when_forbidden() {
+        let db = db().await;
+
+        let (status, body) = implicit_namespace(&db, &profile(), false)
+            .await
+            .unwrap_err();
+
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(body.0["error"]
+            .as_str()
+            .unwrap()
+            .contains("implicit namespace creation is disabled"));
+        assert!(namespaces::Entity::find()
+            .all(&db)
+            .await
+            .unwrap()
+            .is_empty());
+
+        // An existing personal namespace is still used
+        let handle = user_handle(&profile());
+        ensure_namespace(
+            &db,
+            &handle,
+            "Jane.Doe@example.com",
+            "Jane.Doe@example.com",
+            None,
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            implicit_namespace(&db, &profile(), false).await.unwrap(),
+            handle
+        );
+    }
+
+    #[test]
+    fn test_default_user_must_be_a_valid_user() {
+        assert!(validate_default_user(None).is_ok());
+        assert!(validate_default_user(Some("")).is_ok());
+        assert!(validate_default_user(Some("ubuntu")).is_ok());
+
+        let (status, body) = validate_default_user(Some("-oProxyCommand=sh")).unwrap_err();
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(body.0["error"]
+            .as_str()
+            .unwrap()
+            .starts_with("Invalid default_user"));
+    }
+
+    #[test]
+    fn test_default_accelerators_must_suit_the_platform() {
+        let accelerators = vec!["1:A100_SXM".to_string()];
+        assert!(validate_default_accelerators(None, None).is_ok());
+        assert!(validate_default_accelerators(Some(&[]), Some("kube")).is_ok());
+        assert!(validate_default_accelerators(Some(&accelerators), None).is_ok());
+        assert!(validate_default_accelerators(Some(&accelerators), Some("")).is_ok());
+
+        for (accelerators, platform) in [
+            (vec!["A100_SXM".to_string()], None),
+            (vec!["0:A100_SXM".to_string()], None),
+            (accelerators.clone(), Some("kube")),
+        ] {
+            let (status, body) =
+                validate_default_accelerators(Some(&accelerators), platform).unwrap_err();
+            assert_eq!(status, StatusCode::BAD_REQUEST);
+            assert!(body.0["error"]
+                .as_str()
+                .unwrap()
+                .starts_with("Invalid default_accelerators"));
+        }
+    }
+}
@@ -1,27 +1,50 @@
+use crate::agent::key_cache::{agent_key_duration, processor_agent_key, AGENT_KEY_CACHE};
 use crate::agent::ns::auth_ns;
 use crate::config::SERVER_CONFIG;
 use crate::entities::processors;
+use crate::errors::{retry_read, ApiError};
+use crate::handlers::v1::container::{check_include_deleted, check_meter_subjects};
 use crate::middleware::get_user_profile_from_token;
-use crate::models::{V1ResourceMetaRequest, V1StreamData, V1StreamMessage, V1UserProfile};
+use crate::models::{
+    V1ListParams, V1ResourceMetaRequest, V1StreamData, V1StreamMessage, V1UserProfile,
+};
 use crate::query::Query;
+use crate::resources::v1::containers::models::V1LogParams;
 use crate::resources::v1::processors::base::ProcessorPlatform;
+use crate::resources::v1::processors::health::{
+    health_check_timeout_ms, replica_health, with_replica_health,
+};
+use crate::resources::v1::processors::logs::{
+    collect_all_container_logs, collect_container_logs, log_limits,
+};
 use crate::resources::v1::processors::models::{
-    V1Processor, V1ProcessorHealthResponse, V1ProcessorRequest, V1ProcessorScaleRequest,
-    V1Processors, V1ReadStreamRequest, V1UpdateProcessor,
+    V1AckStreamRequest, V1AckStreamResponse, V1ConsumerPending, V1GetProcessorParams,
+    V1HealthCheckParams, V1PeekStreamParams, V1PendingParams, V1Processor,
+    V1ProcessorHealthResponse, V1ProcessorLogs, V1ProcessorLogsParams, V1ProcessorRequest,
+    V1ProcessorScaleRequest, V1ProcessorStreamInfo, V1Processors, V1ReadStreamRequest,
+    V1ReplicaHealth, V1StreamGroupInfo, V1StreamPending, V1UpdateProcessor,
 };
 use crate::resources::v1::processors::standard::StandardProcessor;
+use crate::resources::v1::processors::streams::{
+    init_return_stream, is_terminal_return_message, send_wait_timeout_ms, stream_max_len,
+    xadd_capped, ReturnStreamGuard, RETURN_STREAM_TTL_SECS,
+};
+use crate::resources::v1::processors::topics::{consume_messages, produce_message};
 use crate::state::AppState;
+use crate::streams::redis::{
+    ack_entries, get_group_backlog, get_stream_info, peek_entries, take_reclaimed_entries,
+    GroupBacklog,
+};
 use crate::utils::namespace::resolve_namespace;
 use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
 use axum::{
-    extract::Extension, extract::Json, extract::Path, extract::State, http::StatusCode,
-    response::IntoResponse,
+    extract::Extension, extract::Json, extract::Path, extract::Query as QueryParams,
+    extract::State, http::StatusCode, response::IntoResponse,
 };
 use futures::{SinkExt, StreamExt};
 use sea_orm::{ActiveModelTrait, ActiveValue, DatabaseConnection};
 use serde_json::json;
 use short_uuid::ShortUuid;
-use std::collections::HashMap;
 use std::sync::Arc;
 use tokio::sync::Mutex;
 use tracing::{debug, error, warn};
@@ -30,7 +53,7 @@ pub async fn create_processor(
     State(state): State<AppState>,
     Extension(user_profile): Extension<V1UserProfile>,
     Json(processor_request): Json<V1ProcessorRequest>,
-) -> Result<Json<V1Processor>, (StatusCode, Json<serde_json::Value>)> {
+) -> Result<Json<V1Processor>, ApiError> {
     let db_pool = &state.db_pool;
 
     match crate::validate::validate_name(
@@ -38,62 +61,48 @@ pub async fn create_processor(
     ) {
         Ok(_) => (),
         Err(e) => {
-            return Err((
-                StatusCode::BAD_REQUEST,
-                Json(json!({ "error": format!("Invalid name: {}", e) })),
-            ));
+            return Err(ApiError::BadRequest(format!("Invalid name: {}", e)));
         }
     }
     debug!("Processor request: {:?}", processor_request);
 
-    let namespace_opt = processor_request.clone().metadata.namespace;
+    if processor_request.stream_max_len == Some(0) {
+        return Err(ApiError::BadRequest(
+            "stream_max_len must be a positive integer".to_string(),
+        ));
+    }
 
-    let handle = match user_profile.handle.clone() {
-        Some(handle) => handle,
-        None => user_profile
-            .email
-            .clone()
-            .replace("@", "-")
-            .replace(".", "-"),
-    };
-    debug!("Handle: {:?}", handle);
+    state
+        .queue(processor_request.queue_backend.as_deref())
+        .map_err(ApiError::BadRequest)?;
+
+    let namespace_opt = processor_request.clone().metadata.namespace;
 
     let namespace = match namespace_opt {
         Some(namespace) => namespace,
-        None => match crate::handlers::v1::namespaces::ensure_namespace(
-            db_pool,
-            &handle,
-            &user_profile.email,
-            &user_profile.email,
-            None,
-        )
-        .await
-        {
-            Ok(_) => handle,
-            Err(e) => {
-                return Err((
-                    StatusCode::BAD_REQUEST,
-                    Json(json!({ "error": format!("Invalid namespace: {}", e) })),
-                ));
-            }
-        },
+        None => {
+            crate::handlers::v1::namespaces::implicit_namespace(
+                db_pool,
+                &user_profile,
+                SERVER_CONFIG.allow_implicit_namespaces,
+            )
+            .await?
+        }
     };
     debug!(">> Using namespace for processor creation: {:?}", namespace);
 
-    crate::validate::validate_namespace(&namespace).map_err(|err| {
-        (
-            StatusCode::BAD_REQUEST,
-            Json(json!({ "error": format!("Invalid namespace: {}", err) })),
-        )
-    })?;
+    crate::validate::validate_namespace(&namespace)
+        .map_err(|err| ApiError::BadRequest(format!("Invalid namespace: {}", err)))?;
     debug!("Validated namespace");
 
-    let mut owner_ids: Vec<String> = if let Some(orgs) = &user_profile.organizations {
-        orgs.keys().cloned().collect()
-    } else {
-        Vec::new()
-    };
-    owner_ids.push(user_profile.email.clone());
+    let owner_ids = user_profile.owner_ids();
+    check_meter_subjects(
+        processor_request
+            .container
+            .as_ref()
+            .and_then(|c| c.meters.as_ref()),
+        &owner_ids,
+    )?;
 
     debug!(
         "Authorizing namespace {:?} with owner_ids {:?}",
@@ -101,19 +110,11 @@ pub async fn create_processor(
     );
     let owner = auth_ns(db_pool, &owner_ids, &namespace)
         .await
-        .map_err(|e| {
-            (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(json!({"error": format!("Authorization error: {}", e)})),
-            )
-        })?;
+        .map_err(|e| ApiError::Internal(format!("Authorization error: {}", e)))?;
     debug!("Authorized namespace");
 
     // Create the standard processor platform
-    let app_state = Arc::new(AppState {
-        db_pool: db_pool.clone(),
-        message_queue: state.message_queue.clone(),
-    });
+    let app_state = Arc::new(state.clone());
     let platform = StandardProcessor::new(app_state);
 
     debug!("Declaring processor with namespace: {:?}", namespace);
@@ -130,10 +131,7 @@ pub async fn create_processor(
         Ok(processor) => processor,
         Err(e) => {
             error!("Error declaring processor: {:?}", e);
-            return Err((
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(json!({"error": e.to_string()})),
-            ));
+            return Err(ApiError::Internal(e.to_string()));
         }
     };
 
@@ -145,7 +143,7 @@ pub async fn scale_processor(
     Extension(user_profile): Extension<V1UserProfile>,
     Path((namespace, name)): Path<(String, String)>,
     Json(scale_request): Json<V1ProcessorScaleRequest>,
-) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+) -> Result<impl IntoResponse, ApiError> {
     let result = _scale_processor(
         &state.db_pool,
         &namespace,
@@ -165,22 +163,16 @@ async fn _scale_processor(
     name: &str,
     user_profile: &V1UserProfile,
     scale_request: V1ProcessorScaleRequest,
-) -> Result<V1Processor, (StatusCode, Json<serde_json::Value>)> {
+) -> Result<V1Processor, ApiError> {
     // Validate we have at least one parameter
     if scale_request.replicas.is_none() && scale_request.min_replicas.is_none() {
-        return Err((
-            StatusCode::BAD_REQUEST,
-            Json(json!({"error": "At least one of 'replicas' or 'min_replicas' must be provided"})),
+        return Err(ApiError::BadRequest(
+            "At least one of 'replicas' or 'min_replicas' must be provided".to_string(),
         ));
     }
 
     // Collect owner IDs
-    let mut owner_ids: Vec<String> = if let Some(orgs) = &user_profile.organizations {
-        orgs.keys().cloned().collect()
-    } else {
-        Vec::new()
-    };
-    owner_ids.push(user_profile.email.clone());
+    let owner_ids = user_profile.owner_ids();
     let owner_id_refs: Vec<&str> = owner_ids.iter().map(|s| s.as_str()).collect();
 
     // Find the processor
@@ -194,10 +186,7 @@ async fn _scale_processor(
     {
         Ok(processor) => processor,
         Err(e) => {
-            return Err((
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(json!({"error": format!("Database error: {}", e)})),
-            ));
+            return Err(ApiError::Database(e));
         }
     };
 
@@ -206,9 +195,8 @@ async fn _scale_processor(
     // Handle min_replicas update if provided
     if let Some(min_replicas) = scale_request.min_replicas {
         if min_replicas <= 0 {
-            return Err((
-                StatusCode::BAD_REQUEST,
-                Json(json!({"error": "min_replicas must be a positive integer"})),
+            return Err(ApiError::BadRequest(
+                "min_replicas must be a positive integer".to_string(),
             ));
         }
         debug!("Setting min_replicas to {}", min_replicas);
@@ -220,9 +208,8 @@ async fn _scale_processor(
         // If replicas is explicitly set
         Some(replicas) => {
             if replicas <= 0 {
-                return Err((
-                    StatusCode::BAD_REQUEST,
-                    Json(json!({"error": "replicas must be a positive integer"})),
+                return Err(ApiError::BadRequest(
+                    "replicas must be a positive integer".to_string(),
                 ));
             }
             debug!("Setting desired_replicas to {}", replicas);
@@ -250,30 +237,31 @@ async fn _scale_processor(
     }
 
     // Update the processor in the database
-    let updated_processor = active_model.update(db_pool).await.map_err(|e| {
-        (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(json!({"error": format!("Failed to update processor: {}", e)})),
-        )
-    })?;
+    let updated_processor = active_model
+        .update(db_pool)
+        .await
+        .map_err(|e| ApiError::Internal(format!("Failed to update processor: {}", e)))?;
 
     // Convert the updated processor model to V1Processor for the response
-    let processor_v1 = updated_processor.to_v1_processor().map_err(|e| {
-        (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(json!({"error": format!("Failed to convert processor: {}", e)})),
-        )
-    })?;
+    let processor_v1 = updated_processor
+        .to_v1_processor()
+        .map_err(|e| ApiError::Internal(format!("Failed to convert processor: {}", e)))?;
 
     Ok(processor_v1)
 }
 
+/// Sends a health check to the processor and waits `timeout_ms` (default
+/// 30s, at most 120s) for a replica to answer. Responds 408 when none does
+/// in time.
 #[axum::debug_handler]
 pub async fn check_processor_health(
     State(state): State<AppState>,
     Extension(user_profile): Extension<V1UserProfile>,
     Path((namespace, name)): Path<(String, String)>,
-) -> Result<Json<V1ProcessorHealthResponse>, (StatusCode, Json<serde_json::Value>)> {
+    QueryParams(params): QueryParams<V1HealthCheckParams>,
+) -> Result<Json<V1ProcessorHealthResponse>, ApiError> {
+    let timeout_ms = health_check_timeout_ms(params.timeout_ms).map_err(ApiError::BadRequest)?;
+
     // Changed return type
     debug!(
         "Entering check_processor_health for processor: {} in namespace: {}, user_profile: {:?}",
@@ -287,14 +275,7 @@ pub async fn check_processor_health(
     );
 
     // --- Authorization and Processor Fetching ---
-    let mut owner_ids: Vec<String> = if let Some(orgs) = &user_profile.organizations {
-        debug!("User organizations found: {:?}", orgs.keys());
-        orgs.keys().cloned().collect()
-    } else {
-        debug!("No organizations found for user.");
-        Vec::new()
-    };
-    owner_ids.push(user_profile.email.clone());
+    let owner_ids = user_profile.owner_ids();
     debug!("Collected owner_ids: {:?}", owner_ids);
     let owner_id_refs: Vec<&str> = owner_ids.iter().map(|s| s.as_str()).collect();
 
@@ -314,14 +295,28 @@ pub async fn check_processor_health(
             "Database error finding processor {}:{}: {}",
             resolved_namespace, name, e
         );
-        (
-            StatusCode::NOT_FOUND, // Changed to NOT_FOUND for clarity
-            Json(json!({"error": format!("Processor not found or access denied: {}", e)})),
-        )
+        ApiError::NotFound(format!("Processor not found or access denied: {}", e))
     })?;
     debug!("Successfully found processor: {:?}", processor);
     // --- End Authorization ---
 
+    // Each replica's health comes from its container
+    let owner_ref_string = format!("{}.{}.Processor", processor.name, processor.namespace);
+    let replicas: Vec<V1ReplicaHealth> =
+        Query::find_containers_by_owner_ref(db_pool, &owner_ref_string)
+            .await
+            .map_err(|e| {
+                error!(
+                    "Database error finding containers for processor {}:{}: {}",
+                    resolved_namespace, name, e
+                );
+                ApiError::Internal(format!("Failed to retrieve associated containers: {}", e))
+            })?
+            .iter()
+            .map(replica_health)
+            .collect();
+    debug!("Replica health: {:?}", replicas);
+
     // Construct health stream name
     let health_stream_name = format!("{}.health", processor.stream);
     let message_id = ShortUuid::generate().to_string();
@@ -358,14 +353,15 @@ pub async fn check_processor_health(
         }
         Err(e) => {
             error!("Failed to get user profile for health check: {}", e);
-            return Err((
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(
-                    json!({"error": format!("Failed to get user profile for health check: {}", e)}),
-                ),
-            ));
+            return Err(ApiError::Internal(format!(
+                "Failed to get user profile for health check: {}",
+                e
+            )));
         }
     };
+    let queue = state
+        .queue(processor.queue_backend.as_deref())
+        .map_err(ApiError::BadRequest)?;
 
     let message = V1StreamMessage {
         kind: "HealthCheckRequest".to_string(),
@@ -378,32 +374,25 @@ pub async fn check_processor_health(
         handle: user_prof.handle.clone(),
         adapter: Some(format!("processor-health:{}", processor.id)),
         api_key: None, // Removed agent key
+        entry_id: None,
     };
     debug!(
         "Constructed V1StreamMessage for health check: {:?}",
         message
     );
 
-    match &state.message_queue {
+    match queue {
         crate::state::MessageQueue::Redis { client } => {
             debug!("Using Redis message queue for health check.");
             let mut conn = client.get_connection().map_err(|e| {
                 error!("Redis connection error for health check: {}", e);
-                (
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    Json(
-                        json!({"error": format!("Redis connection error for health check: {}", e)}),
-                    ),
-                )
+                ApiError::Internal(format!("Redis connection error for health check: {}", e))
             })?;
             debug!("Successfully obtained Redis connection for health check.");
 
             let message_json = serde_json::to_string(&message).map_err(|e| {
                 error!("Failed to serialize health check message: {}", e);
-                (
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    Json(json!({"error": format!("Failed to serialize health check message: {}", e)})),
-                )
+                ApiError::Internal(format!("Failed to serialize health check message: {}", e))
             })?;
             debug!("Serialized health check message to JSON: {}", message_json);
 
@@ -411,39 +400,33 @@ pub async fn check_processor_health(
                 "Attempting to XADD health check message to stream: {}",
                 health_stream_name
             );
-            let _stream_id: String = redis::cmd("XADD")
-                .arg(&health_stream_name)
-                .arg("*")
-                .arg("data")
-                .arg(&message_json)
-                .query(&mut conn)
-                .map_err(|e| {
-                    error!(
-                        "Failed to send health check message to stream '{}': {}",
-                        health_stream_name, e
-                    );
-                    (
-                        StatusCode::INTERNAL_SERVER_ERROR,
-                        Json(json!({"error": format!("Failed to send health check to stream: {}", e)})),
-                    )
-                })?;
+            let _stream_id: String = xadd_capped(
+                &health_stream_name,
+                stream_max_len(&processor),
+                &[("data", message_json.as_str())],
+            )
+            .query(&mut conn)
+            .map_err(|e| {
+                error!(
+                    "Failed to send health check message to stream '{}': {}",
+                    health_stream_name, e
+                );
+                ApiError::Internal(format!("Failed to send health check to stream: {}", e))
+            })?;
             debug!(
                 "Successfully sent health check message to stream: {}, Stream ID: {}",
                 health_stream_name, _stream_id
             );
 
+            // Deletes the return stream however this handler exits
+            let _return_stream_guard = ReturnStreamGuard::new(client.clone(), &return_stream_name);
+
             // Initialize return stream
             debug!(
                 "Attempting to XADD init message to return stream: {}",
                 return_stream_name
             );
-            let init_message_id: String = match redis::cmd("XADD")
-                .arg(&return_stream_name)
-                .arg("*")
-                .arg("init")
-                .arg("true")
-                .query(&mut conn)
-            {
+            let init_message_id: String = match init_return_stream(&mut conn, &return_stream_name) {
                 Ok(id) => {
                     debug!(
                         "Successfully added init message to return stream: {}, Init Message ID: {}",
@@ -456,17 +439,14 @@ pub async fn check_processor_health(
                         "Failed to add init message to return stream '{}': {}",
                         return_stream_name, e
                     );
-                    return Err((
-                        StatusCode::INTERNAL_SERVER_ERROR,
-                        Json(
-                            json!({"error": format!("Failed to initialize health return stream: {}", e)}),
-                        ),
-                    ));
+                    return Err(ApiError::Internal(format!(
+                        "Failed to initialize health return stream: {}",
+                        e
+                    )));
                 }
             };
 
-            const HEALTH_CHECK_TIMEOUT_MS: u64 = 30000; // 30 seconds timeout for health check
-            debug!("Health check timeout set to: {}ms", HEALTH_CHECK_TIMEOUT_MS);
+            debug!("Health check timeout set to: {}ms", timeout_ms);
 
             let client_clone = client.clone();
             let return_stream_name_clone = return_stream_name.clone();
@@ -492,15 +472,15 @@ pub async fn check_processor_health(
                 })?;
                 debug!(
                     "[spawn_blocking] Successfully obtained Redis connection. Reading stream: {} with timeout: {}",
-                    return_stream_name_clone, HEALTH_CHECK_TIMEOUT_MS
+                    return_stream_name_clone, timeout_ms
                 );
-                redis::cmd("XREAD")
-                    .arg("BLOCK")
-                    .arg(HEALTH_CHECK_TIMEOUT_MS)
-                    .arg("STREAMS")
-                    .arg(&return_stream_name_clone)
-                    .arg(&init_message_id) // Read after the init message
-                    .query::<redis::streams::StreamReadReply>(&mut conn_blocking)
+                // Read after the init message
+                wait_for_return_entries(
+                    &mut conn_blocking,
+                    &return_stream_name_clone,
+                    &init_message_id,
+                    timeout_ms,
+                )
             })
             .await;
             debug!("Spawn_blocking task for health check read completed.");
@@ -514,12 +494,10 @@ pub async fn check_processor_health(
                             "Timed out waiting for processor health response from stream: {}",
                             return_stream_name
                         );
-                        Err((
-                            StatusCode::REQUEST_TIMEOUT,
-                            Json(
-                                json!({"error": "Timed out waiting for processor health response"}),
-                            ),
-                        ))
+                        Err(ApiError::Timeout(format!(
+                            "Timed out after {}ms waiting for processor health response",
+                            timeout_ms
+                        )))
                     } else {
                         debug!(
                             "Processing {} keys from health response stream: {}",
@@ -577,6 +555,7 @@ pub async fn check_processor_health(
                                                     e
                                                 )),
                                                 details: Some(json!({ "raw_response": data_str })),
+                                                replicas: None,
                                             });
                                             break;
                                         }
@@ -599,11 +578,9 @@ pub async fn check_processor_health(
                                     "Received health response without data or only init message from stream: {}",
                                     return_stream_name
                                 );
-                                Err((
-                                    StatusCode::INTERNAL_SERVER_ERROR,
-                                    Json(
-                                        json!({"error": "Received health response without data or only init message"}),
-                                    ),
+                                Err(ApiError::Internal(
+                                    "Received health response without data or only init message"
+                                        .to_string(),
                                 ))
                             }
                         }
@@ -614,102 +591,86 @@ pub async fn check_processor_health(
                         "Redis error during health check XREAD on stream '{}': {}",
                         return_stream_name, e
                     );
-                    Err((
-                        StatusCode::INTERNAL_SERVER_ERROR,
-                        Json(
-                            json!({"error": format!("Error reading health response stream: {}", e)}),
-                        ),
-                    ))
+                    Err(ApiError::Internal(format!(
+                        "Error reading health response stream: {}",
+                        e
+                    )))
                 }
                 Err(e) => {
                     error!(
                         "Spawn_blocking task failed for health check on stream '{}': {}",
                         return_stream_name, e
                     );
-                    Err((
-                        StatusCode::INTERNAL_SERVER_ERROR,
-                        Json(json!({"error": format!("Health check task execution error: {}", e)})),
-                    ))
+                    Err(ApiError::Internal(format!(
+                        "Health check task execution error: {}",
+                        e
+                    )))
                 }
             };
 
-            // Cleanup the return stream
-            debug!(
-                "Attempting to get Redis connection for cleanup of stream: {}",
-                return_stream_name
-            );
-            let mut conn_cleanup = client.get_connection().unwrap(); // Assume connection is fine after read
-            debug!("Successfully obtained Redis connection for cleanup.");
-            debug!("Attempting to DEL return stream: {}", return_stream_name);
-            let del_result: Result<(), redis::RedisError> = redis::cmd("DEL")
-                .arg(&return_stream_name)
-                .query(&mut conn_cleanup);
-            if let Err(e) = del_result {
-                warn!(
-                    "Failed to delete health check return stream '{}': {}",
-                    return_stream_name, e
-                );
-            } else {
-                debug!(
-                    "Successfully deleted health check return stream: {}",
-                    return_stream_name
-                );
-            }
-
             // Return the processed response or error
             match response_data {
                 Ok(data) => {
+                    let data = with_replica_health(data, replicas);
                     debug!("Returning successful health check response: {:?}", data);
                     Ok(Json(data))
                 }
-                Err(err_tuple) => {
-                    error!(
-                        "Returning error for health check: Status {:?}, Error: {:?}",
-                        err_tuple.0, err_tuple.1
-                    );
-                    Err(err_tuple)
+                Err(err) => {
+                    error!("Returning error for health check: {:?}", err);
+                    Err(err)
                 }
             }
         }
         crate::state::MessageQueue::Kafka { .. } => {
             error!("Kafka not supported for processor health checks.");
-            Err((
-                StatusCode::BAD_REQUEST,
-                Json(
-                    json!({"error": "Kafka streams are not currently supported for health checks"}),
-                ),
+            Err(ApiError::BadRequest(
+                "Kafka streams are not currently supported for health checks".to_string(),
             ))
         }
     }
 }
 
+/// Blocks up to `timeout_ms` for entries after `after_id` on a return
+/// stream. An empty reply means the wait timed out.
+fn wait_for_return_entries(
+    conn: &mut redis::Connection,
+    return_stream: &str,
+    after_id: &str,
+    timeout_ms: u64,
+) -> redis::RedisResult<redis::streams::StreamReadReply> {
+    redis::cmd("XREAD")
+        .arg("BLOCK")
+        .arg(timeout_ms)
+        .arg("STREAMS")
+        .arg(return_stream)
+        .arg(after_id)
+        .query(conn)
+}
+
 #[axum::debug_handler]
 pub async fn list_processors(
     State(state): State<AppState>,
     Extension(user_profile): Extension<V1UserProfile>,
-) -> Result<Json<V1Processors>, (StatusCode, Json<serde_json::Value>)> {
+    QueryParams(params): QueryParams<V1ListParams>,
+) -> Result<Json<V1Processors>, ApiError> {
     let db_pool = &state.db_pool;
 
-    let mut owner_ids: Vec<String> = if let Some(orgs) = &user_profile.organizations {
-        orgs.keys().cloned().collect()
-    } else {
-        Vec::new()
-    };
-
-    // Include user's email (assuming owner_id is user's email)
-    owner_ids.push(user_profile.email.clone());
+    let owner_ids = user_profile.owner_ids();
+    check_include_deleted(&params, &owner_ids)?;
 
     let owner_id_refs: Vec<&str> = owner_ids.iter().map(|s| s.as_str()).collect();
 
     // Query processors for all owner_ids
-    let processor_models = Query::find_processors_by_owners(db_pool, &owner_id_refs)
-        .await
-        .map_err(|e| {
-            (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(json!({"error": format!("Database error: {}", e)})),
-            )
-        })?;
+    let include_deleted = params.include_deleted;
+    let owners = owner_id_refs.as_slice();
+    let processor_models = retry_read(|| async move {
+        if include_deleted {
+            Query::find_processors_by_owners_including_deleted(db_pool, owners).await
+        } else {
+            Query::find_processors_by_owners(db_pool, owners).await
+        }
+    })
+    .await?;
 
     // Convert database models to API response models
     let processors_result: Result<Vec<V1Processor>, _> = processor_models
@@ -717,12 +678,8 @@ pub async fn list_processors(
         .map(|p| p.to_v1_processor())
         .collect();
 
-    let processors = processors_result.map_err(|e| {
-        (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(json!({"error": format!("Failed to convert processors: {}", e)})),
-        )
-    })?;
+    let processors = processors_result
+        .map_err(|e| ApiError::Internal(format!("Failed to convert processors: {}", e)))?;
 
     Ok(Json(V1Processors { processors }))
 }
@@ -731,45 +688,81 @@ pub async fn get_processor(
     State(state): State<AppState>,
     Extension(user_profile): Extension<V1UserProfile>,
     Path((namespace, name)): Path<(String, String)>,
-) -> Result<Json<V1Processor>, (StatusCode, Json<serde_json::Value>)> {
+    QueryParams(params): QueryParams<V1GetProcessorParams>,
+) -> Result<Json<V1Processor>, ApiError> {
     let db_pool = &state.db_pool;
     let resolved_namespace = resolve_namespace(&namespace, &user_profile);
 
-    let mut owner_ids: Vec<String> = if let Some(orgs) = &user_profile.organizations {
-        orgs.keys().cloned().collect()
-    } else {
-        Vec::new()
-    };
-    owner_ids.push(user_profile.email.clone());
+    let owner_ids = user_profile.owner_ids();
+    check_include_deleted(
+        &V1ListParams {
+            include_deleted: params.include_deleted,
+        },
+        &owner_ids,
+    )?;
     let owner_id_refs: Vec<&str> = owner_ids.iter().map(|s| s.as_str()).collect();
 
-    let processor = match Query::find_processor_by_namespace_name_and_owners(
+    // A missing processor comes back as RecordNotFound, which answers 404
+    let processor = find_processor(
         db_pool,
         &resolved_namespace,
         &name,
         &owner_id_refs,
+        params.include_deleted,
     )
-    .await
-    {
-        Ok(processor) => processor,
-        Err(e) => {
-            return Err((
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(json!({"error": format!("Database error: {}", e)})),
-            ));
-        }
-    };
+    .await?;
 
-    let processor_v1 = processor.to_v1_processor().map_err(|e| {
-        (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(json!({"error": format!("Failed to convert processor: {}", e)})),
-        )
-    })?;
+    let mut processor_v1 = processor
+        .to_v1_processor()
+        .map_err(|e| ApiError::Internal(format!("Failed to convert processor: {}", e)))?;
+    if params.include_stream_info {
+        processor_v1.stream_info = Some(processor_stream_info(&state, &processor)?);
+    }
 
     Ok(Json(processor_v1))
 }
 
+/// Reads the processor's stream from the queue it was declared on. Only
+/// Redis streams can be inspected.
+fn processor_stream_info(
+    state: &AppState,
+    processor: &processors::Model,
+) -> Result<V1ProcessorStreamInfo, ApiError> {
+    let queue = state
+        .queue(processor.queue_backend.as_deref())
+        .map_err(ApiError::BadRequest)?;
+    let crate::state::MessageQueue::Redis { client } = queue else {
+        return Err(ApiError::BadRequest(
+            "Stream info is only available for processors on Redis".to_string(),
+        ));
+    };
+    let mut conn = client.get_connection().map_err(|e| {
+        error!("Redis connection error: {}", e);
+        ApiError::Internal(format!("Redis connection error: {}", e))
+    })?;
+
+    let info = get_stream_info(&mut conn, &processor.stream).map_err(|e| {
+        error!("XINFO error for stream '{}': {}", processor.stream, e);
+        ApiError::Internal(format!("Failed to read stream info: {}", e))
+    })?;
+    Ok(V1ProcessorStreamInfo {
+        name: processor.stream.clone(),
+        length: info.length,
+        last_generated_id: info.last_generated_id,
+        groups: info
+            .groups
+            .into_iter()
+            .map(|group| V1StreamGroupInfo {
+                name: group.name,
+                consumers: group.consumers,
+                pending: group.pending,
+                last_delivered_id: group.last_delivered_id,
+                lag: group.lag,
+            })
+            .collect(),
+    })
+}
+
 /// Send a message to a processor
 ///
 /// # Request Parameters
@@ -798,7 +791,7 @@ pub async fn send_processor(
     Extension(user_profile): Extension<V1UserProfile>,
     Path((namespace, name)): Path<(String, String)>,
     Json(stream_data): Json<V1StreamData>,
-) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+) -> Result<impl IntoResponse, ApiError> {
     debug!(
         "Sending processor with namespace: {} and name: {}",
         namespace, name
@@ -809,12 +802,7 @@ pub async fn send_processor(
     debug!("Resolved namespace: {}", resolved_namespace);
 
     // Collect owner IDs from user_profile
-    let mut owner_ids: Vec<String> = if let Some(orgs) = &user_profile.organizations {
-        orgs.keys().cloned().collect()
-    } else {
-        Vec::new()
-    };
-    owner_ids.push(user_profile.email.clone());
+    let owner_ids = user_profile.owner_ids();
     let owner_id_refs: Vec<&str> = owner_ids.iter().map(|s| s.as_str()).collect();
     debug!("Owner IDs: {:?}", owner_ids);
 
@@ -829,10 +817,7 @@ pub async fn send_processor(
     {
         Ok(processor) => processor,
         Err(e) => {
-            return Err((
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(json!({"error": format!("Database error: {}", e)})),
-            ));
+            return Err(ApiError::Database(e));
         }
     };
 
@@ -846,9 +831,8 @@ pub async fn send_processor(
 
     if user_token.is_empty() {
         error!("User token is missing, cannot generate agent key.");
-        return Err((
-            StatusCode::UNAUTHORIZED,
-            Json(json!({"error": "Authentication token missing"})),
+        return Err(ApiError::Unauthorized(
+            "Authentication token missing".to_string(),
         ));
     }
     debug!("User token: {}", user_token);
@@ -856,12 +840,24 @@ pub async fn send_processor(
     let auth_server = SERVER_CONFIG.auth.url.clone();
     if auth_server.is_empty() {
         error!("Auth server URL is empty.");
-        return Err((
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(json!({"error": "Auth server configuration is empty"})),
+        return Err(ApiError::Internal(
+            "Auth server configuration is empty".to_string(),
         ));
     }
 
+    let key_duration = agent_key_duration(
+        stream_data.agent_key_duration,
+        SERVER_CONFIG.agent_key_duration,
+        SERVER_CONFIG.agent_key_max_duration,
+    )
+    .map_err(ApiError::BadRequest)?;
+
+    let wait_timeout_ms = send_wait_timeout_ms(
+        stream_data.timeout_ms,
+        SERVER_CONFIG.send_wait_max_timeout.as_millis() as u64,
+    )
+    .map_err(ApiError::BadRequest)?;
+
     // --- Conditionally Generate Agent Key ---
     let agent_key: String;
     if user_token.starts_with("a.") || user_token.starts_with("k.") {
@@ -869,46 +865,27 @@ pub async fn send_processor(
         agent_key = user_token.clone();
     } else {
         debug!(
-            "Creating agent key request for processor: {} and auth server: {}",
+            "Getting agent key for processor: {} from auth server: {}",
             processor.id, auth_server
         );
-        let agent_key_request = crate::models::V1CreateAgentKeyRequest {
-            agent_id: format!("processor-{}", processor.id),
-            name: format!(
-                "send-processor-{}-{}",
-                processor.id,
-                ShortUuid::generate().to_string()
-            ),
-            duration: 86400, // e.g., 24 hour validity
-        };
-        debug!("Creating agent key request: {:?}", agent_key_request);
-
-        let agent_key_response = crate::agent::agent::create_agent_key(
+        // Reused across sends, so the auth server isn't asked for every message
+        agent_key = processor_agent_key(
+            &AGENT_KEY_CACHE,
             &auth_server,
             &user_token,
-            agent_key_request,
+            &processor.id,
+            key_duration,
         )
         .await
         .map_err(|e| {
             error!("Failed to create agent key: {}", e);
-            (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(json!({"error": format!("Failed to generate temporary agent key: {}", e)})),
-            )
-        })?;
-
-        debug!("Agent key response: {:?}", agent_key_response);
-        agent_key = agent_key_response.key.ok_or_else(|| {
-            error!("Generated agent key response did not contain a key.");
-            (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(json!({"error": "Failed to obtain temporary agent key value"})),
-            )
+            ApiError::Internal(format!("Failed to generate temporary agent key: {}", e))
         })?;
     }
     // --- End Agent Key Generation ---
 
     // Get the stream name
+    let stream_max_len = stream_max_len(&processor);
     let stream_name = processor.stream;
     let id = ShortUuid::generate().to_string();
 
@@ -931,10 +908,12 @@ pub async fn send_processor(
         Ok(user_prof) => user_prof,
         Err(e) => {
             error!("Failed to get user profile: {}", e);
-            return Err((
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(json!({"error": format!("Failed to get user profile: {}", e)})),
-            ));
+            // Keys minted with a token that no longer authenticates go too
+            AGENT_KEY_CACHE.invalidate_token(&user_token);
+            return Err(ApiError::Internal(format!(
+                "Failed to get user profile: {}",
+                e
+            )));
         }
     }; // TODO: make more efficient
     debug!("Sending message with user profile: {:?}", user_prof);
@@ -951,10 +930,14 @@ pub async fn send_processor(
         handle: user_prof.handle.clone(),
         adapter: Some(format!("processor:{}", processor.id)),
         api_key: Some(agent_key),
+        entry_id: None,
     };
 
-    // Access the Redis client from the message queue
-    match &state.message_queue {
+    // The message goes to the queue the processor was declared on
+    let queue = state
+        .queue(processor.queue_backend.as_deref())
+        .map_err(ApiError::BadRequest)?;
+    match queue {
         crate::state::MessageQueue::Redis { client } => {
             // Get a Redis connection
             let mut conn = match client.get_connection() {
@@ -964,30 +947,24 @@ pub async fn send_processor(
                 }
                 Err(e) => {
                     error!("Redis connection error: {}", e);
-                    return Err((
-                        StatusCode::INTERNAL_SERVER_ERROR,
-                        Json(json!({"error": format!("Redis connection error: {}", e)})),
-                    ));
+                    return Err(ApiError::Internal(format!("Redis connection error: {}", e)));
                 }
             };
 
             // Serialize the message to JSON
             let message_json = serde_json::to_string(&message).map_err(|e| {
                 error!("Failed to serialize message: {}", e);
-                (
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    Json(json!({"error": format!("Failed to serialize message: {}", e)})),
-                )
+                ApiError::Internal(format!("Failed to serialize message: {}", e))
             })?;
             debug!("Message serialized successfully: {}", message_json);
 
             // Add the message to the stream using higher-level xadd
-            let stream_id_result: Result<String, redis::RedisError> = redis::cmd("XADD")
-                .arg(stream_name.clone())
-                .arg("*") // Auto-generate ID
-                .arg("data")
-                .arg(&message_json)
-                .query(&mut conn);
+            let stream_id_result: Result<String, redis::RedisError> = xadd_capped(
+                &stream_name,
+                stream_max_len,
+                &[("data", message_json.as_str())],
+            )
+            .query(&mut conn);
 
             let stream_id = match stream_id_result {
                 Ok(id) => {
@@ -996,10 +973,10 @@ pub async fn send_processor(
                 }
                 Err(e) => {
                     error!("Failed to send message to stream '{}': {}", stream_name, e);
-                    return Err((
-                        StatusCode::INTERNAL_SERVER_ERROR,
-                        Json(json!({"error": format!("Failed to send message to stream: {}", e)})),
-                    ));
+                    return Err(ApiError::Internal(format!(
+                        "Failed to send message to stream: {}",
+                        e
+                    )));
                 }
             };
 
@@ -1019,210 +996,14 @@ pub async fn send_processor(
 
             // If the client requested to wait for a response on the actual_return_stream_name
             if should_wait_for_response {
-                tracing::debug!(
-                    "Waiting for response on return stream: {}",
-                    actual_return_stream_name
-                );
-
-                // Create the return stream with a dummy message to ensure it exists, and capture its ID
-                let init_message_id: String = match redis::cmd("XADD")
-                    .arg(&actual_return_stream_name) // Use actual_return_stream_name
-                    .arg("*")
-                    .arg("init")
-                    .arg("true")
-                    .query(&mut conn)
-                {
-                    Ok(id) => {
-                        debug!(
-                            "Added init message to return stream '{}' with ID: {}",
-                            actual_return_stream_name, id
-                        );
-                        id // This value will be assigned to init_message_id
-                    }
-                    Err(e) => {
-                        error!(
-                            "Failed to add init message to return stream '{}': {}. Cannot proceed.",
-                            actual_return_stream_name, e
-                        );
-                        // If we can't even add the init message, waiting is unlikely to work
-                        return Err((
-                            StatusCode::INTERNAL_SERVER_ERROR,
-                            Json(
-                                json!({"error": format!("Failed to initialize return stream: {}", e)}),
-                            ),
-                        ));
-                    }
-                };
-
-                // Wait for response with a timeout (1 hour)
-                const TIMEOUT_MS: u64 = 3600000;
-
-                // --- Prepare for spawn_blocking ---
-                let actual_return_stream_name_clone = actual_return_stream_name.clone();
-                let client_clone = client.clone(); // Clone the client Arc for the blocking task
-                                                   // --- Move blocking call to spawn_blocking ---
-                let read_result = tokio::task::spawn_blocking(move || {
-                    // Get a new connection from the pool inside the blocking task
-                    let mut conn = client_clone.get_connection().map_err(|e| {
-                        redis::RedisError::from((
-                            redis::ErrorKind::IoError,
-                            "Failed to get connection in spawn_blocking",
-                            e.to_string(),
-                        ))
-                    })?;
-
-                    debug!(
-                        "Attempting blocking XREAD on stream '{}' with timeout {}ms",
-                        actual_return_stream_name_clone, TIMEOUT_MS
-                    );
-
-                    redis::cmd("XREAD")
-                        .arg("BLOCK")
-                        .arg(TIMEOUT_MS)
-                        .arg("STREAMS")
-                        .arg(&actual_return_stream_name_clone) // Use the clone
-                        .arg(&init_message_id)
-                        .query::<redis::streams::StreamReadReply>(&mut conn)
-                })
-                .await;
-                // --- End spawn_blocking ---
-
-                // Handle the result from spawn_blocking (which itself returns a Result)
-                let result = match read_result {
-                    Ok(Ok(reply)) => {
-                        // Outer Ok is from spawn_blocking, inner Ok is from redis::cmd
-                        debug!("XREAD successful. Raw reply: {:?}", reply);
-                        reply
-                    }
-                    Ok(Err(e)) => {
-                        // Outer Ok, inner Err (Redis error)
-                        error!(
-                            "Error reading from response stream '{}' inside spawn_blocking: {}",
-                            actual_return_stream_name, // Use original name for logging
-                            e
-                        );
-                        return Err((
-                            StatusCode::INTERNAL_SERVER_ERROR,
-                            Json(
-                                json!({"error": format!("Error reading from response stream: {}", e)}),
-                            ),
-                        ));
-                    }
-                    Err(e) => {
-                        // Outer Err (spawn_blocking join error)
-                        error!(
-                            "Spawn_blocking task failed for stream '{}': {}",
-                            actual_return_stream_name, // Use original name for logging
-                            e
-                        );
-                        return Err((
-                            StatusCode::INTERNAL_SERVER_ERROR,
-                            Json(json!({"error": format!("Task execution error: {}", e)})),
-                        ));
-                    }
-                };
-
-                // Clean up the return stream - Requires getting a connection again
-                let mut conn = match client.get_connection() {
-                    Ok(c) => c,
-                    Err(e) => {
-                        error!("Failed to get connection for DEL command: {}", e);
-                        // Log and continue with processing if response was received.
-                        // If DEL must succeed, return an error here.
-                        return Err((
-                            StatusCode::INTERNAL_SERVER_ERROR,
-                            Json(
-                                json!({"error": format!("Failed get Redis conn for cleanup: {}", e)}),
-                            ),
-                        ));
-                    }
-                };
-                debug!(
-                    "Attempting to delete return stream '{}'",
-                    actual_return_stream_name // Use actual_return_stream_name
-                );
-                let del_result: Result<(), redis::RedisError> = redis::cmd("DEL")
-                    .arg(&actual_return_stream_name) // Use actual_return_stream_name
-                    .query(&mut conn);
-                if let Err(e) = del_result {
-                    // Log error but continue processing the response if we got one
-                    error!(
-                        "Failed to delete return stream '{}': {}. Processing response anyway.",
-                        actual_return_stream_name, // Use actual_return_stream_name
-                        e
-                    );
-                } else {
-                    debug!(
-                        "Successfully deleted return stream '{}'",
-                        actual_return_stream_name // Use actual_return_stream_name
-                    );
-                }
-
-                // Check if we got a response
-                if result.keys.is_empty() {
-                    error!(
-                        "Timed out or received empty response from return stream '{}'",
-                        actual_return_stream_name // Use actual_return_stream_name
-                    );
-                    return Err((
-                        StatusCode::REQUEST_TIMEOUT,
-                        Json(json!({"error": "Timed out waiting for processor response"})),
-                    ));
-                }
-                debug!(
-                    "Received {} keys in response from stream '{}'",
-                    result.keys.len(),
-                    actual_return_stream_name // Use actual_return_stream_name
-                );
-
-                // Process the response
-                for key in result.keys {
-                    debug!("Processing key (stream): {:?}", key.key);
-                    for id in key.ids {
-                        debug!("Processing message ID: {:?}, Map: {:?}", id.id, id.map);
-                        if let Some(data_value) = id.map.get("data") {
-                            debug!("Found 'data' field: {:?}", data_value);
-                            // Convert the Redis value to a string
-                            let data_str = match data_value {
-                                redis::Value::BulkString(bytes) => {
-                                    let s = String::from_utf8_lossy(bytes).to_string();
-                                    debug!("Converted BulkString to string: '{}'", s);
-                                    String::from_utf8_lossy(bytes).to_string()
-                                }
-                                redis::Value::SimpleString(s) => s.clone(),
-                                _ => format!("{:?}", data_value),
-                            };
-                            debug!("Final data_str: '{}'", data_str);
-
-                            // Try to parse the data as JSON
-                            match serde_json::from_str::<serde_json::Value>(&data_str) {
-                                Ok(json_data) => {
-                                    debug!("Successfully parsed data as JSON: {:?}", json_data);
-                                    return Ok(Json(json_data).into_response());
-                                }
-                                Err(e) => {
-                                    warn!(
-                                        "Failed to parse response data as JSON: {}. Returning raw string.",
-                                        e
-                                    );
-                                    return Ok(Json(json!({"raw": data_str})).into_response());
-                                }
-                            }
-                        } else {
-                            debug!("'data' field not found in message map for ID: {:?}", id.id);
-                        }
-                    }
-                }
-
-                // If we couldn't find data in the response
-                error!(
-                    "Processed all messages in response stream '{}', but none contained a 'data' field.",
-                    actual_return_stream_name // Use actual_return_stream_name
-                );
-                return Err((
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    Json(json!({"error": "Received response without data field"})),
-                ));
+                let response = wait_for_send_response(
+                    client,
+                    &mut conn,
+                    &actual_return_stream_name,
+                    wait_timeout_ms,
+                )
+                .await?;
+                return Ok(Json(response).into_response());
             } else {
                 // If not waiting, just return success
                 debug!(
@@ -1238,74 +1019,194 @@ pub async fn send_processor(
                 .into_response())
             }
         }
-        crate::state::MessageQueue::Kafka { .. } => Err((
-            StatusCode::BAD_REQUEST,
-            Json(json!({"error": "Kafka streams are not currently supported"})),
-        )),
+        crate::state::MessageQueue::Kafka { producer, .. } => {
+            if should_wait_for_response {
+                return Err(ApiError::BadRequest(
+                    "Waiting for a response is not supported on Kafka processors".to_string(),
+                ));
+            }
+            let message_json = serde_json::to_string(&message).map_err(|e| {
+                error!("Failed to serialize message: {}", e);
+                ApiError::Internal(format!("Failed to serialize message: {}", e))
+            })?;
+            let stream_id = produce_message(
+                producer,
+                &stream_name,
+                &id,
+                &message_json,
+                &SERVER_CONFIG.kafka,
+            )
+            .await
+            .map_err(|e| {
+                error!(
+                    "Failed to send message to topic for '{}': {}",
+                    stream_name, e
+                );
+                ApiError::Internal(format!("Failed to send message to stream: {}", e))
+            })?;
+            Ok(Json(json!({
+                "success": true,
+                "stream_id": stream_id,
+                "message_id": message.id,
+                "return_stream": actual_return_stream_name,
+            }))
+            .into_response())
+        }
     }
 }
 
-pub async fn delete_processor(
-    State(state): State<AppState>,
-    Extension(user_profile): Extension<V1UserProfile>,
-    Path((namespace, name)): Path<(String, String)>,
-) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
-    debug!("Deleting processor: {} in namespace: {}", name, namespace);
-    let db_pool = &state.db_pool;
-    let resolved_namespace = resolve_namespace(&namespace, &user_profile);
+/// Waits up to `timeout_ms` for the reply to a sent message on
+/// `return_stream`, which is created here and deleted again however the wait
+/// ends. Timing out is an `ApiError::Timeout`.
+async fn wait_for_send_response(
+    client: &Arc<redis::Client>,
+    conn: &mut redis::Connection,
+    return_stream: &str,
+    timeout_ms: u64,
+) -> Result<serde_json::Value, ApiError> {
+    debug!(
+        "Waiting up to {}ms for response on return stream: {}",
+        timeout_ms, return_stream
+    );
 
-    let mut owner_ids: Vec<String> = if let Some(orgs) = &user_profile.organizations {
-        orgs.keys().cloned().collect()
-    } else {
-        Vec::new()
-    };
-    owner_ids.push(user_profile.email.clone());
-    let owner_id_refs: Vec<&str> = owner_ids.iter().map(|s| s.as_str()).collect();
+    // Deletes the return stream however this exits
+    let _return_stream_guard = ReturnStreamGuard::new(client.clone(), return_stream);
 
+    // Create the return stream with a dummy message to ensure it exists, and capture its ID
+    let init_message_id = init_return_stream(conn, return_stream).map_err(|e| {
+        error!(
+            "Failed to add init message to return stream '{}': {}. Cannot proceed.",
+            return_stream, e
+        );
+        // If we can't even add the init message, waiting is unlikely to work
+        ApiError::Internal(format!("Failed to initialize return stream: {}", e))
+    })?;
     debug!(
-        "Finding processor: {} in namespace: {}",
-        name, resolved_namespace
+        "Added init message to return stream '{}' with ID: {}",
+        return_stream, init_message_id
     );
-    let processor = Query::find_processor_by_namespace_name_and_owners(
-        db_pool,
-        &resolved_namespace,
-        &name,
-        &owner_id_refs,
-    )
-    .await
-    .map_err(|e| {
-        (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(json!({"error": format!("Database error: {}", e)})),
-        )
-    })?;
-
-    debug!("Deleting processor: {}", processor.id);
-    let app_state = Arc::new(AppState {
-        db_pool: db_pool.clone(),
-        message_queue: state.message_queue.clone(),
-    });
-    let platform = StandardProcessor::new(app_state);
 
-    let redis = match &state.message_queue {
-        crate::state::MessageQueue::Redis { client } => client,
-        _ => {
-            return Err((
-                StatusCode::BAD_REQUEST,
-                Json(json!({"error": "Kafka streams are not currently supported"})),
+    // Blocking read, so off the async runtime and on its own connection
+    let client_clone = client.clone();
+    let return_stream_clone = return_stream.to_string();
+    let read_result = tokio::task::spawn_blocking(move || {
+        let mut conn = client_clone.get_connection().map_err(|e| {
+            redis::RedisError::from((
+                redis::ErrorKind::IoError,
+                "Failed to get connection in spawn_blocking",
+                e.to_string(),
             ))
+        })?;
+        wait_for_return_entries(
+            &mut conn,
+            &return_stream_clone,
+            &init_message_id,
+            timeout_ms,
+        )
+    })
+    .await;
+
+    let result = match read_result {
+        Ok(Ok(reply)) => {
+            debug!("XREAD successful. Raw reply: {:?}", reply);
+            reply
+        }
+        Ok(Err(e)) => {
+            error!(
+                "Error reading from response stream '{}': {}",
+                return_stream, e
+            );
+            return Err(ApiError::Internal(format!(
+                "Error reading from response stream: {}",
+                e
+            )));
+        }
+        Err(e) => {
+            error!(
+                "Spawn_blocking task failed for stream '{}': {}",
+                return_stream, e
+            );
+            return Err(ApiError::Internal(format!("Task execution error: {}", e)));
         }
     };
 
+    // Check if we got a response
+    if result.keys.is_empty() {
+        warn!(
+            "Timed out after {}ms waiting on return stream '{}'",
+            timeout_ms, return_stream
+        );
+        return Err(ApiError::Timeout(format!(
+            "Timed out after {}ms waiting for processor response",
+            timeout_ms
+        )));
+    }
+
+    for key in result.keys {
+        for id in key.ids {
+            let Some(data_value) = id.map.get("data") else {
+                debug!("'data' field not found in message map for ID: {:?}", id.id);
+                continue;
+            };
+            let data_str = match data_value {
+                redis::Value::BulkString(bytes) => String::from_utf8_lossy(bytes).to_string(),
+                redis::Value::SimpleString(s) => s.clone(),
+                _ => format!("{:?}", data_value),
+            };
+            return Ok(match serde_json::from_str::<serde_json::Value>(&data_str) {
+                Ok(json_data) => json_data,
+                Err(e) => {
+                    warn!(
+                        "Failed to parse response data as JSON: {}. Returning raw string.",
+                        e
+                    );
+                    json!({ "raw": data_str })
+                }
+            });
+        }
+    }
+
+    error!(
+        "Processed all messages in response stream '{}', but none contained a 'data' field.",
+        return_stream
+    );
+    Err(ApiError::Internal(
+        "Received response without data field".to_string(),
+    ))
+}
+
+pub async fn delete_processor(
+    State(state): State<AppState>,
+    Extension(user_profile): Extension<V1UserProfile>,
+    Path((namespace, name)): Path<(String, String)>,
+) -> Result<impl IntoResponse, ApiError> {
+    debug!("Deleting processor: {} in namespace: {}", name, namespace);
+    let db_pool = &state.db_pool;
+    let resolved_namespace = resolve_namespace(&namespace, &user_profile);
+
+    let owner_ids = user_profile.owner_ids();
+    let owner_id_refs: Vec<&str> = owner_ids.iter().map(|s| s.as_str()).collect();
+
+    debug!(
+        "Finding processor: {} in namespace: {}",
+        name, resolved_namespace
+    );
+    let processor = Query::find_processor_by_namespace_name_and_owners(
+        db_pool,
+        &resolved_namespace,
+        &name,
+        &owner_id_refs,
+    )
+    .await?;
+
+    debug!("Deleting processor: {}", processor.id);
+    let app_state = Arc::new(state.clone());
+    let platform = StandardProcessor::new(app_state);
+
     platform
-        .delete(&processor.id, db_pool, redis)
+        .delete(&processor.id, db_pool)
         .await
-        .map_err(|e| {
-            (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(json!({"error": format!("Failed to delete processor: {}", e)})),
-            )
-        })?;
+        .map_err(|e| ApiError::Internal(format!("Failed to delete processor: {}", e)))?;
 
     debug!("Deleted processor: {}", processor.id);
 
@@ -1317,17 +1218,12 @@ pub async fn update_processor(
     Extension(user_profile): Extension<V1UserProfile>,
     Path((namespace, name)): Path<(String, String)>,
     Json(update_request): Json<V1UpdateProcessor>,
-) -> Result<Json<V1Processor>, (StatusCode, Json<serde_json::Value>)> {
+) -> Result<Json<V1Processor>, ApiError> {
     let db_pool = &state.db_pool;
     let resolved_namespace = resolve_namespace(&namespace, &user_profile);
 
     // Collect owner IDs from user_profile
-    let mut owner_ids: Vec<String> = if let Some(orgs) = &user_profile.organizations {
-        orgs.keys().cloned().collect()
-    } else {
-        Vec::new()
-    };
-    owner_ids.push(user_profile.email.clone());
+    let owner_ids = user_profile.owner_ids();
     let owner_id_refs: Vec<&str> = owner_ids.iter().map(|s| s.as_str()).collect();
 
     // Find the processor
@@ -1341,22 +1237,24 @@ pub async fn update_processor(
     {
         Ok(processor) => processor,
         Err(e) => {
-            return Err((
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(json!({"error": format!("Database error: {}", e)})),
-            ));
+            return Err(ApiError::Database(e));
         }
     };
 
+    check_meter_subjects(
+        update_request
+            .container
+            .as_ref()
+            .and_then(|c| c.meters.as_ref()),
+        &owner_ids,
+    )?;
+
     let no_delete = update_request.no_delete.unwrap_or(false);
 
     // Convert processor model to V1Processor for comparison and potential return value
-    let processor_v1 = processor.to_v1_processor().map_err(|e| {
-        (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(json!({"error": format!("Failed to convert processor: {}", e)})),
-        )
-    })?;
+    let processor_v1 = processor
+        .to_v1_processor()
+        .map_err(|e| ApiError::Internal(format!("Failed to convert processor: {}", e)))?;
 
     // --- Start: Determine if recreation is required ---
     let mut requires_recreation = false;
@@ -1573,40 +1471,19 @@ pub async fn update_processor(
     if requires_recreation {
         debug!("Processor configuration changed, recreation required.");
         if no_delete {
-            return Err((
-                StatusCode::BAD_REQUEST,
-                Json(json!({
-                    "error": "Processor changes require deletion, but no_delete=true"
-                })),
+            return Err(ApiError::BadRequest(
+                "Processor changes require deletion, but no_delete=true".to_string(),
             ));
         }
 
         debug!("Deleting old processor");
-        let app_state = Arc::new(AppState {
-            db_pool: db_pool.clone(),
-            message_queue: state.message_queue.clone(),
-        });
+        let app_state = Arc::new(state.clone());
         let platform = StandardProcessor::new(app_state);
 
-        let redis = match &state.message_queue {
-            crate::state::MessageQueue::Redis { client } => client,
-            _ => {
-                return Err((
-                    StatusCode::BAD_REQUEST,
-                    Json(json!({"error": "Kafka streams are not currently supported"})),
-                ))
-            }
-        };
-
         platform
-            .delete(&processor.id, db_pool, redis)
+            .delete(&processor.id, db_pool)
             .await
-            .map_err(|e| {
-                (
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    Json(json!({"error": format!("Failed to delete processor: {}", e)})),
-                )
-            })?;
+            .map_err(|e| ApiError::Internal(format!("Failed to delete processor: {}", e)))?;
 
         // --- Start: Create the potential final processor state by merging updates ---
         // This is needed for the declare call if recreation happens.
@@ -1641,15 +1518,21 @@ pub async fn update_processor(
             min_replicas: update_request.min_replicas.or(processor_v1.min_replicas), // Merge min_replicas
             max_replicas: update_request.max_replicas.or(processor_v1.max_replicas), // Merge max_replicas
             scale: update_request.scale.clone().or(processor_v1.scale.clone()),      // Merge scale
+            stream_max_len: update_request
+                .stream_max_len
+                .or(processor_v1.stream_max_len),
+            // The streams already live on this backend
+            queue_backend: processor_v1.queue_backend.clone(),
+            warmup_message: update_request
+                .warmup_message
+                .clone()
+                .unwrap_or(processor_v1.warmup_message.clone()),
         };
         // --- End: Create the potential final processor state ---
 
         // Create the new processor with merged values
         debug!("Creating new processor with updated fields");
-        let app_state = Arc::new(AppState {
-            db_pool: db_pool.clone(),
-            message_queue: state.message_queue.clone(),
-        });
+        let app_state = Arc::new(state.clone());
         let platform = StandardProcessor::new(app_state);
 
         let created = platform
@@ -1661,12 +1544,7 @@ pub async fn update_processor(
                 &resolved_namespace,
             )
             .await
-            .map_err(|e| {
-                (
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    Json(json!({"error": e.to_string()})),
-                )
-            })?;
+            .map_err(|e| ApiError::Internal(e.to_string()))?;
         debug!("Created new processor: {:?}", created);
 
         return Ok(Json(created));
@@ -1685,10 +1563,7 @@ pub async fn update_processor(
                     .clone()
                     .unwrap_or(serde_json::Value::Null);
                 let new_labels_json = serde_json::to_value(labels).map_err(|e| {
-                    (
-                        StatusCode::INTERNAL_SERVER_ERROR,
-                        Json(json!({"error": format!("Failed to serialize labels: {}", e)})),
-                    )
+                    ApiError::Internal(format!("Failed to serialize labels: {}", e))
                 })?;
 
                 if current_labels_json != new_labels_json {
@@ -1703,9 +1578,8 @@ pub async fn update_processor(
         // Check min_replicas
         if let Some(new_min_replicas) = update_request.min_replicas {
             if new_min_replicas <= 0 {
-                return Err((
-                    StatusCode::BAD_REQUEST,
-                    Json(json!({"error": "min_replicas must be a positive integer"})),
+                return Err(ApiError::BadRequest(
+                    "min_replicas must be a positive integer".to_string(),
                 ));
             }
             let current_min_replicas = processor.min_replicas;
@@ -1731,9 +1605,8 @@ pub async fn update_processor(
         // Check max_replicas
         if let Some(new_max_replicas) = update_request.max_replicas {
             if new_max_replicas <= 0 {
-                return Err((
-                    StatusCode::BAD_REQUEST,
-                    Json(json!({"error": "max_replicas must be a positive integer"})),
+                return Err(ApiError::BadRequest(
+                    "max_replicas must be a positive integer".to_string(),
                 ));
             }
             let current_max_replicas = processor.max_replicas;
@@ -1763,35 +1636,50 @@ pub async fn update_processor(
             }
         }
 
+        // Check stream_max_len
+        if let Some(new_stream_max_len) = update_request.stream_max_len {
+            if new_stream_max_len == 0 {
+                return Err(ApiError::BadRequest(
+                    "stream_max_len must be a positive integer".to_string(),
+                ));
+            }
+            if processor_v1.stream_max_len != Some(new_stream_max_len) {
+                processor_active_model.stream_max_len =
+                    ActiveValue::Set(Some(new_stream_max_len as i64));
+                model_updated = true;
+                debug!("Processor stream_max_len updated.");
+            }
+        }
+
         // Check scale
         if let Some(new_scale) = &update_request.scale {
             if processor_v1.scale.as_ref() != Some(new_scale) {
-                let new_scale_json = serde_json::to_value(new_scale).map_err(|e| {
-                    (
-                        StatusCode::INTERNAL_SERVER_ERROR,
-                        Json(json!({"error": format!("Failed to serialize scale: {}", e)})),
-                    )
-                })?;
+                let new_scale_json = serde_json::to_value(new_scale)
+                    .map_err(|e| ApiError::Internal(format!("Failed to serialize scale: {}", e)))?;
                 processor_active_model.scale = ActiveValue::Set(new_scale_json);
                 model_updated = true;
                 debug!("Processor scale updated.");
             }
         }
 
+        // Check warmup_message, sent as replicas come up so no recreation
+        if let Some(new_warmup_message) = &update_request.warmup_message {
+            if &processor_v1.warmup_message != new_warmup_message {
+                processor_active_model.warmup_message =
+                    ActiveValue::Set(new_warmup_message.clone());
+                model_updated = true;
+                debug!("Processor warmup_message updated.");
+            }
+        }
+
         if model_updated {
             debug!("Applying updates to processor.");
-            let updated_processor_model =
-                processor_active_model.update(db_pool).await.map_err(|e| {
-                    (
-                        StatusCode::INTERNAL_SERVER_ERROR,
-                        Json(json!({"error": format!("Failed to update processor: {}", e)})),
-                    )
-                })?;
+            let updated_processor_model = processor_active_model
+                .update(db_pool)
+                .await
+                .map_err(|e| ApiError::Internal(format!("Failed to update processor: {}", e)))?;
             let updated_processor_v1 = updated_processor_model.to_v1_processor().map_err(|e| {
-                (
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    Json(json!({"error": format!("Failed to convert updated processor: {}", e)})),
-                )
+                ApiError::Internal(format!("Failed to convert updated processor: {}", e))
             })?;
             return Ok(Json(updated_processor_v1));
         } else {
@@ -1803,31 +1691,70 @@ pub async fn update_processor(
     }
 }
 
+/// Logs of every container of a processor, keyed by container name; failed
+/// reads are keyed by container id. Use `/logs/pages` to page through them.
 pub async fn get_processor_logs(
     State(state): State<AppState>,
     Extension(user_profile): Extension<V1UserProfile>,
     Path((namespace, name)): Path<(String, String)>,
-) -> Result<Json<serde_json::Value>, (StatusCode, Json<serde_json::Value>)> {
+    QueryParams(params): QueryParams<V1ProcessorLogsParams>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let limits = log_limits(&params).map_err(ApiError::BadRequest)?;
+    let associated_containers =
+        find_processor_containers(&state.db_pool, &user_profile, &namespace, &name).await?;
+
+    let logs = collect_all_container_logs(
+        associated_containers,
+        &params,
+        limits,
+        |container, log_params| fetch_logs(&state.db_pool, &user_profile, container, log_params),
+    )
+    .await;
+    Ok(Json(serde_json::Value::Object(logs)))
+}
+
+/// One page of a processor's container logs, see `V1ProcessorLogsParams`
+pub async fn get_processor_log_pages(
+    State(state): State<AppState>,
+    Extension(user_profile): Extension<V1UserProfile>,
+    Path((namespace, name)): Path<(String, String)>,
+    QueryParams(params): QueryParams<V1ProcessorLogsParams>,
+) -> Result<Json<V1ProcessorLogs>, ApiError> {
+    let limits = log_limits(&params).map_err(ApiError::BadRequest)?;
+    let associated_containers =
+        find_processor_containers(&state.db_pool, &user_profile, &namespace, &name).await?;
+
+    let logs = collect_container_logs(
+        associated_containers,
+        &params,
+        limits,
+        |container, log_params| fetch_logs(&state.db_pool, &user_profile, container, log_params),
+    )
+    .await;
+    Ok(Json(logs))
+}
+
+/// The containers of the processor `namespace`/`name`, if the user can see it
+async fn find_processor_containers(
+    db_pool: &DatabaseConnection,
+    user_profile: &V1UserProfile,
+    namespace: &str,
+    name: &str,
+) -> Result<Vec<crate::entities::containers::Model>, ApiError> {
     debug!(
         "Fetching logs for processor: {} in namespace: {}",
         name, namespace
     );
-    let db_pool = &state.db_pool;
-    let resolved_namespace = resolve_namespace(&namespace, &user_profile);
+    let resolved_namespace = resolve_namespace(namespace, user_profile);
 
     // --- Authorization and Processor Fetching (similar to get_processor) ---
-    let mut owner_ids: Vec<String> = if let Some(orgs) = &user_profile.organizations {
-        orgs.keys().cloned().collect()
-    } else {
-        Vec::new()
-    };
-    owner_ids.push(user_profile.email.clone());
+    let owner_ids = user_profile.owner_ids();
     let owner_id_refs: Vec<&str> = owner_ids.iter().map(|s| s.as_str()).collect();
 
     let processor = Query::find_processor_by_namespace_name_and_owners(
         db_pool,
         &resolved_namespace,
-        &name,
+        name,
         &owner_id_refs,
     )
     .await
@@ -1837,10 +1764,7 @@ pub async fn get_processor_logs(
             "Database error finding processor {}:{}: {}",
             resolved_namespace, name, e
         );
-        (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(json!({"error": format!("Failed to retrieve processor: {}", e)})),
-        )
+        ApiError::Internal(format!("Failed to retrieve processor: {}", e))
     })?;
     // --- End Authorization ---
 
@@ -1851,86 +1775,43 @@ pub async fn get_processor_logs(
         owner_ref_string
     );
 
-    let associated_containers = match Query::find_containers_by_owner_ref(
-        db_pool,
-        &owner_ref_string,
-    )
-    .await
-    {
-        Ok(containers) => containers,
-        Err(e) => {
+    Query::find_containers_by_owner_ref(db_pool, &owner_ref_string)
+        .await
+        .map_err(|e| {
             error!(
                 "Database error finding containers for processor {}:{} with owner_ref '{}': {}",
                 resolved_namespace, name, owner_ref_string, e
             );
-            return Err((
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(json!({"error": format!("Failed to retrieve associated containers: {}", e)})),
-            ));
-        }
-    };
+            ApiError::Internal(format!("Failed to retrieve associated containers: {}", e))
+        })
+}
 
-    if associated_containers.is_empty() {
-        debug!(
-            "No containers found associated with processor {}:{} (owner_ref: {})",
-            resolved_namespace, name, owner_ref_string
+/// Reads one container's logs for a processor logs response
+async fn fetch_logs(
+    db_pool: &DatabaseConnection,
+    user_profile: &V1UserProfile,
+    container: crate::entities::containers::Model,
+    log_params: V1LogParams,
+) -> Result<String, String> {
+    crate::handlers::v1::container::_fetch_container_logs_by_id(
+        db_pool,
+        &container.id,
+        user_profile,
+        &log_params,
+    )
+    .await
+    .map(|Json(logs)| logs)
+    .map_err(|(status, error_json)| {
+        let error_message = error_json
+            .get("error")
+            .and_then(|v| v.as_str())
+            .unwrap_or("Unknown error");
+        error!(
+            "Failed to fetch logs for container {}: Status {:?}, Error: {}",
+            container.id, status, error_message
         );
-        return Ok(Json(json!({}))); // Return empty JSON if no containers found
-    }
-    // --- End Find Containers ---
-
-    // --- Fetch Logs for Each Container ---
-    let mut all_logs: HashMap<String, serde_json::Value> = HashMap::new();
-    let mut container_errors: HashMap<String, String> = HashMap::new();
-
-    for container in associated_containers {
-        let container_id = container.id;
-        let log_key = if container.name.is_empty() {
-            container_id.clone()
-        } else {
-            container.name.clone()
-        }; // Use container name or ID as key
-
-        match crate::handlers::v1::container::_fetch_container_logs_by_id(
-            db_pool,
-            &container_id,
-            &user_profile,
-        )
-        .await
-        {
-            Ok(Json(logs)) => {
-                all_logs.insert(log_key, json!(logs));
-            }
-            Err((status, error_json)) => {
-                let error_message = error_json
-                    .get("error")
-                    .and_then(|v| v.as_str())
-                    .unwrap_or("Unknown error")
-                    .to_string();
-                error!(
-                    "Failed to fetch logs for container {}: Status {:?}, Error: {}",
-                    container_id, status, error_message
-                );
-                // Store the error to potentially include in the response
-                container_errors.insert(log_key, format!("Status {}: {}", status, error_message));
-                all_logs.insert(container_id.clone(), json!({ "error": error_message }));
-            }
-        }
-    }
-    // --- End Fetch Logs ---
-
-    // --- Prepare Response ---
-    // Optionally, include errors in the response if needed
-    // let response_json = if container_errors.is_empty() {
-    //     json!(all_logs)
-    // } else {
-    //     json!({
-    //         "logs": all_logs,
-    //         "errors": container_errors
-    //     })
-    // };
-
-    Ok(Json(json!(all_logs)))
+        format!("Status {}: {}", status, error_message)
+    })
 }
 
 #[axum::debug_handler]
@@ -1939,7 +1820,7 @@ pub async fn read_processor_stream(
     Extension(user_profile): Extension<V1UserProfile>,
     Path((namespace, name)): Path<(String, String)>,
     Json(read_request): Json<V1ReadStreamRequest>,
-) -> Result<Json<Vec<V1StreamMessage>>, (StatusCode, Json<serde_json::Value>)> {
+) -> Result<Json<Vec<V1StreamMessage>>, ApiError> {
     debug!(
         "Reading processor stream for {}/{} with group {}, max_records: {}, wait_ms: {}",
         namespace,
@@ -1951,12 +1832,7 @@ pub async fn read_processor_stream(
     let db_pool = &state.db_pool;
     let resolved_namespace = resolve_namespace(&namespace, &user_profile);
 
-    let mut owner_ids: Vec<String> = if let Some(orgs) = &user_profile.organizations {
-        orgs.keys().cloned().collect()
-    } else {
-        Vec::new()
-    };
-    owner_ids.push(user_profile.email.clone());
+    let owner_ids = user_profile.owner_ids();
     let owner_id_refs: Vec<&str> = owner_ids.iter().map(|s| s.as_str()).collect();
 
     let processor = Query::find_processor_by_namespace_name_and_owners(
@@ -1971,22 +1847,19 @@ pub async fn read_processor_stream(
             "Database error finding processor {}:{}: {}",
             resolved_namespace, name, e
         );
-        (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(json!({ "error": format!("Failed to retrieve processor: {}", e) })),
-        )
+        ApiError::Internal(format!("Failed to retrieve processor: {}", e))
     })?;
 
+    let queue = state
+        .queue(processor.queue_backend.as_deref())
+        .map_err(ApiError::BadRequest)?;
     let stream_name = processor.stream;
 
-    match &state.message_queue {
+    match queue {
         crate::state::MessageQueue::Redis { client } => {
             let mut conn = client.get_connection().map_err(|e| {
                 error!("Redis connection error: {}", e);
-                (
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    Json(json!({ "error": format!("Redis connection error: {}", e) })),
-                )
+                ApiError::Internal(format!("Redis connection error: {}", e))
             })?;
 
             // Ensure consumer group exists, create if not (MKSTREAM handles stream non-existence)
@@ -2015,89 +1888,367 @@ pub async fn read_processor_stream(
                             "Failed to create/ensure consumer group '{}' for stream '{}': {}",
                             read_request.consumer_group, stream_name, e
                         );
-                        return Err((
-                            StatusCode::INTERNAL_SERVER_ERROR,
-                            Json(
-                                json!({ "error": format!("Failed to setup consumer group: {}", e) }),
-                            ),
-                        ));
+                        return Err(ApiError::Internal(format!(
+                            "Failed to setup consumer group: {}",
+                            e
+                        )));
                     }
                 }
             }
 
-            debug!(
-                "Reading from stream '{}' with group '{}', count {}, block {}ms",
-                stream_name,
-                read_request.consumer_group,
-                read_request.max_records,
-                read_request.wait_time_ms
-            );
+            let consumer = user_profile.email.clone(); // Consumer name, using user's email for now
+            let max_records = read_request.max_records as usize;
+
+            // Entries reclaimed from crashed consumers go out before new ones
+            let mut entries = take_reclaimed_entries(
+                &mut conn,
+                &stream_name,
+                &read_request.consumer_group,
+                &consumer,
+                max_records,
+                SERVER_CONFIG.stream_max_deliveries,
+            )
+            .map_err(|e| {
+                error!(
+                    "Failed to take reclaimed entries for '{}': {}",
+                    stream_name, e
+                );
+                ApiError::Internal(format!("Failed to read from stream: {}", e))
+            })?;
 
-            let reply: redis::streams::StreamReadReply = redis::cmd("XREADGROUP")
-                .arg("GROUP")
-                .arg(read_request.consumer_group.clone())
-                .arg(user_profile.email.clone()) // Consumer name, using user's email for now
-                .arg("COUNT")
-                .arg(read_request.max_records)
-                .arg("BLOCK")
-                .arg(read_request.wait_time_ms)
-                .arg("STREAMS")
-                .arg(stream_name.clone())
-                .arg(">") // Read new messages not yet delivered to other consumers in this group
-                .query(&mut conn)
-                .map_err(|e| {
-                    error!("XREADGROUP error for stream '{}': {}", stream_name, e);
-                    (
-                        StatusCode::INTERNAL_SERVER_ERROR,
-                        Json(json!({ "error": format!("Failed to read from stream: {}", e) })),
-                    )
-                })?;
+            if entries.len() < max_records {
+                debug!(
+                    "Reading from stream '{}' with group '{}', count {}, block {}ms",
+                    stream_name,
+                    read_request.consumer_group,
+                    max_records - entries.len(),
+                    read_request.wait_time_ms
+                );
+
+                let mut cmd = redis::cmd("XREADGROUP");
+                cmd.arg("GROUP")
+                    .arg(read_request.consumer_group.clone())
+                    .arg(&consumer)
+                    .arg("COUNT")
+                    .arg(max_records - entries.len());
+                // Don't hold back reclaimed entries waiting for new ones
+                if entries.is_empty() {
+                    cmd.arg("BLOCK").arg(read_request.wait_time_ms);
+                }
+                let reply: Option<redis::streams::StreamReadReply> = cmd
+                    .arg("STREAMS")
+                    .arg(stream_name.clone())
+                    .arg(">") // Read new messages not yet delivered to other consumers in this group
+                    .query(&mut conn)
+                    .map_err(|e| {
+                        error!("XREADGROUP error for stream '{}': {}", stream_name, e);
+                        ApiError::Internal(format!("Failed to read from stream: {}", e))
+                    })?;
+                if let Some(reply) = reply {
+                    entries.extend(reply.keys.into_iter().flat_map(|key| key.ids));
+                }
+            }
+
+            let mut messages: Vec<V1StreamMessage> = Vec::new();
+            if entries.is_empty() {
+                debug!(
+                    "No new messages in stream '{}' for group '{}' within timeout",
+                    stream_name, read_request.consumer_group
+                );
+                return Ok(Json(messages)); // Return empty list if no messages
+            }
+
+            let mut to_ack: Vec<String> = Vec::new();
+            for id_entry in entries {
+                if let Some(data_val) = id_entry.map.get("data") {
+                    let data_str = match data_val {
+                        redis::Value::BulkString(bytes) => {
+                            String::from_utf8_lossy(&bytes).to_string()
+                        }
+                        redis::Value::SimpleString(s) => s.clone(),
+                        _ => {
+                            warn!("Unexpected data format in stream: {:?}", data_val);
+                            to_ack.push(id_entry.id.clone());
+                            continue;
+                        }
+                    };
+                    match serde_json::from_str::<V1StreamMessage>(&data_str) {
+                        Ok(mut msg) => {
+                            msg.entry_id = Some(id_entry.id.clone());
+                            if read_request.auto_ack {
+                                to_ack.push(id_entry.id.clone());
+                            }
+                            messages.push(msg)
+                        }
+                        Err(e) => {
+                            error!(
+                                "Failed to deserialize V1StreamMessage from stream data '{}': {}",
+                                data_str, e
+                            );
+                            // Nobody can process it, don't let it be reclaimed forever
+                            to_ack.push(id_entry.id.clone());
+                        }
+                    }
+                } else {
+                    warn!(
+                        "'data' field not found in message map for ID: {:?}",
+                        id_entry.id
+                    );
+                    to_ack.push(id_entry.id.clone());
+                }
+            }
+
+            if let Err(e) = ack_entries(
+                &mut conn,
+                &stream_name,
+                &read_request.consumer_group,
+                &to_ack,
+            ) {
+                // The entries stay pending and get reclaimed later
+                warn!("Failed to acknowledge entries on '{}': {}", stream_name, e);
+            }
+
+            Ok(Json(messages))
+        }
+        crate::state::MessageQueue::Kafka { .. } => {
+            // Offsets are committed as messages are read, there is nothing to ack later
+            if !read_request.auto_ack {
+                return Err(ApiError::BadRequest(
+                    "Reads from Kafka processors require auto_ack".to_string(),
+                ));
+            }
+            let consumed = consume_messages(
+                &SERVER_CONFIG.kafka,
+                &stream_name,
+                &read_request.consumer_group,
+                read_request.max_records as usize,
+                std::time::Duration::from_millis(read_request.wait_time_ms),
+            )
+            .await
+            .map_err(|e| {
+                error!("Failed to read from topic for '{}': {}", stream_name, e);
+                ApiError::Internal(format!("Failed to read from stream: {}", e))
+            })?;
+
+            let mut messages = Vec::new();
+            for entry in consumed {
+                match serde_json::from_str::<V1StreamMessage>(&entry.payload) {
+                    Ok(mut msg) => {
+                        msg.entry_id = Some(entry.entry_id);
+                        messages.push(msg);
+                    }
+                    Err(e) => error!(
+                        "Failed to deserialize V1StreamMessage from topic data '{}': {}",
+                        entry.payload, e
+                    ),
+                }
+            }
+            Ok(Json(messages))
+        }
+    }
+}
+
+/// Returns the messages on a processor's stream after `params.after` without
+/// reading as a consumer group, so debugging a stream doesn't leave a group
+/// or pending messages behind. Only Redis streams can be peeked at.
+pub async fn peek_processor_stream(
+    State(state): State<AppState>,
+    Extension(user_profile): Extension<V1UserProfile>,
+    Path((namespace, name)): Path<(String, String)>,
+    QueryParams(params): QueryParams<V1PeekStreamParams>,
+) -> Result<Json<Vec<V1StreamMessage>>, ApiError> {
+    let processor = find_owned_processor(&state, &user_profile, &namespace, &name).await?;
+    let client = redis_client(&state, &processor)?;
+
+    let mut conn = client.get_connection().map_err(|e| {
+        error!("Redis connection error: {}", e);
+        ApiError::Internal(format!("Redis connection error: {}", e))
+    })?;
+
+    let entries = peek_entries(
+        &mut conn,
+        &processor.stream,
+        params.after.as_deref().unwrap_or("0"),
+        params.max_records as usize,
+        Some(params.wait_time_ms),
+    )
+    .map_err(|e| {
+        error!("XREAD error for stream '{}': {}", processor.stream, e);
+        ApiError::Internal(format!("Failed to read from stream: {}", e))
+    })?;
+
+    let messages = entries
+        .into_iter()
+        .filter_map(|entry| {
+            let data: String = entry.get("data")?;
+            match serde_json::from_str::<V1StreamMessage>(&data) {
+                Ok(msg) => Some(V1StreamMessage {
+                    entry_id: Some(entry.id),
+                    ..msg
+                }),
+                Err(e) => {
+                    warn!("Skipping undecodable entry {}: {}", entry.id, e);
+                    None
+                }
+            }
+        })
+        .collect();
+    Ok(Json(messages))
+}
+
+pub async fn ack_processor_stream(
+    State(state): State<AppState>,
+    Extension(user_profile): Extension<V1UserProfile>,
+    Path((namespace, name)): Path<(String, String)>,
+    Json(ack_request): Json<V1AckStreamRequest>,
+) -> Result<Json<V1AckStreamResponse>, ApiError> {
+    let processor = find_owned_processor(&state, &user_profile, &namespace, &name).await?;
+    let client = redis_client(&state, &processor)?;
+
+    let mut conn = client.get_connection().map_err(|e| {
+        error!("Redis connection error: {}", e);
+        ApiError::Internal(format!("Redis connection error: {}", e))
+    })?;
+
+    let acknowledged = ack_entries(
+        &mut conn,
+        &processor.stream,
+        &ack_request.consumer_group,
+        &ack_request.entry_ids,
+    )
+    .map_err(|e| {
+        error!("XACK error for stream '{}': {}", processor.stream, e);
+        ApiError::Internal(format!("Failed to acknowledge messages: {}", e))
+    })?;
+    debug!(
+        "Acknowledged {} of {} entries on '{}' for group '{}'",
+        acknowledged,
+        ack_request.entry_ids.len(),
+        processor.stream,
+        ack_request.consumer_group
+    );
+
+    Ok(Json(V1AckStreamResponse { acknowledged }))
+}
+
+pub async fn get_processor_pending(
+    State(state): State<AppState>,
+    Extension(user_profile): Extension<V1UserProfile>,
+    Path((namespace, name)): Path<(String, String)>,
+    QueryParams(params): QueryParams<V1PendingParams>,
+) -> Result<Json<V1StreamPending>, ApiError> {
+    let processor = find_owned_processor(&state, &user_profile, &namespace, &name).await?;
+    let client = redis_client(&state, &processor)?;
+    let consumer_group = params
+        .consumer_group
+        .unwrap_or_else(|| processor.id.clone());
+
+    let mut conn = client.get_connection().map_err(|e| {
+        error!("Redis connection error: {}", e);
+        ApiError::Internal(format!("Redis connection error: {}", e))
+    })?;
+
+    let backlog = match get_group_backlog(&mut conn, &processor.stream, &consumer_group) {
+        Ok(backlog) => backlog,
+        // The stream or group hasn't been created yet, so nothing is pending
+        Err(e) if e.code() == Some("NOGROUP") || e.to_string().contains("no such key") => {
+            GroupBacklog {
+                pending: 0,
+                lag: 0,
+                consumers: Vec::new(),
+            }
+        }
+        Err(e) => {
+            error!("Failed to read backlog of '{}': {}", processor.stream, e);
+            return Err(ApiError::Internal(format!(
+                "Failed to read pending messages: {}",
+                e
+            )));
+        }
+    };
+
+    Ok(Json(V1StreamPending {
+        stream: processor.stream,
+        consumer_group,
+        pending: backlog.pending,
+        lag: backlog.lag,
+        consumers: backlog
+            .consumers
+            .into_iter()
+            .map(|(name, pending)| V1ConsumerPending { name, pending })
+            .collect(),
+    }))
+}
+
+/// Looks up a processor by namespace and name among `owners`, matching
+/// soft-deleted ones too when `include_deleted` is set. Transient database
+/// failures are retried.
+async fn find_processor(
+    db_pool: &DatabaseConnection,
+    namespace: &str,
+    name: &str,
+    owners: &[&str],
+    include_deleted: bool,
+) -> Result<processors::Model, sea_orm::DbErr> {
+    retry_read(|| async move {
+        if include_deleted {
+            Query::find_processor_by_namespace_name_and_owners_including_deleted(
+                db_pool, namespace, name, owners,
+            )
+            .await
+        } else {
+            Query::find_processor_by_namespace_name_and_owners(db_pool, namespace, name, owners)
+                .await
+        }
+    })
+    .await
+}
 
-            let mut messages: Vec<V1StreamMessage> = Vec::new();
-            if reply.keys.is_empty() {
-                debug!(
-                    "No new messages in stream '{}' for group '{}' within timeout",
-                    stream_name, read_request.consumer_group
-                );
-                return Ok(Json(messages)); // Return empty list if no messages
-            }
+/// Finds a processor owned by the user or one of their organizations.
+async fn find_owned_processor(
+    state: &AppState,
+    user_profile: &V1UserProfile,
+    namespace: &str,
+    name: &str,
+) -> Result<processors::Model, ApiError> {
+    let resolved_namespace = resolve_namespace(namespace, user_profile);
 
-            for key in reply.keys {
-                for id_entry in key.ids {
-                    if let Some(data_val) = id_entry.map.get("data") {
-                        let data_str = match data_val {
-                            redis::Value::BulkString(bytes) => {
-                                String::from_utf8_lossy(&bytes).to_string()
-                            }
-                            redis::Value::SimpleString(s) => s.clone(),
-                            _ => {
-                                warn!("Unexpected data format in stream: {:?}", data_val);
-                                continue;
-                            }
-                        };
-                        match serde_json::from_str::<V1StreamMessage>(&data_str) {
-                            Ok(msg) => messages.push(msg),
-                            Err(e) => {
-                                error!("Failed to deserialize V1StreamMessage from stream data '{}': {}", data_str, e);
-                                // Optionally, decide how to handle deserialization errors (e.g., skip, error out)
-                            }
-                        }
-                    } else {
-                        warn!(
-                            "'data' field not found in message map for ID: {:?}",
-                            id_entry.id
-                        );
-                    }
-                }
-            }
+    let owner_ids = user_profile.owner_ids();
+    let owner_id_refs: Vec<&str> = owner_ids.iter().map(|s| s.as_str()).collect();
 
-            Ok(Json(messages))
+    find_processor(
+        &state.db_pool,
+        &resolved_namespace,
+        name,
+        &owner_id_refs,
+        false,
+    )
+    .await
+    .map_err(|e| {
+        error!(
+            "Database error finding processor {}:{}: {}",
+            resolved_namespace, name, e
+        );
+        match e {
+            sea_orm::DbErr::RecordNotFound(_) => {
+                ApiError::NotFound(format!("Failed to retrieve processor: {}", e))
+            }
+            _ => ApiError::Database(e),
         }
-        crate::state::MessageQueue::Kafka { .. } => Err((
-            StatusCode::BAD_REQUEST,
-            Json(
-                json!({ "error": "Kafka streams are not currently supported for consumer group reads" }),
-            ),
+    })
+}
+
+fn redis_client(
+    state: &AppState,
+    processor: &processors::Model,
+) -> Result<Arc<redis::Client>, ApiError> {
+    let queue = state
+        .queue(processor.queue_backend.as_deref())
+        .map_err(ApiError::BadRequest)?;
+    match queue {
+        crate::state::MessageQueue::Redis { client } => Ok(client.clone()),
+        crate::state::MessageQueue::Kafka { .. } => Err(ApiError::BadRequest(
+            "Kafka streams are not currently supported for consumer groups".to_string(),
         )),
     }
 }
@@ -2108,7 +2259,7 @@ pub async fn read_return_message(
     Extension(user_profile): Extension<V1UserProfile>,
     Path((namespace, name, message_id)): Path<(String, String, String)>,
     Json(read_request): Json<V1ReadStreamRequest>,
-) -> Result<Json<serde_json::Value>, (StatusCode, Json<serde_json::Value>)> {
+) -> Result<Json<serde_json::Value>, ApiError> {
     debug!(
         "Reading return message for processor {}/{} with message_id: {}, wait_time: {}ms",
         namespace, name, message_id, read_request.wait_time_ms
@@ -2118,12 +2269,7 @@ pub async fn read_return_message(
     let resolved_namespace = resolve_namespace(&namespace, &user_profile);
 
     // Collect owner IDs from user_profile
-    let mut owner_ids: Vec<String> = if let Some(orgs) = &user_profile.organizations {
-        orgs.keys().cloned().collect()
-    } else {
-        Vec::new()
-    };
-    owner_ids.push(user_profile.email.clone());
+    let owner_ids = user_profile.owner_ids();
     let owner_id_refs: Vec<&str> = owner_ids.iter().map(|s| s.as_str()).collect();
 
     // Find the processor to get its stream name
@@ -2137,10 +2283,7 @@ pub async fn read_return_message(
     {
         Ok(processor) => processor,
         Err(e) => {
-            return Err((
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(json!({"error": format!("Database error: {}", e)})),
-            ));
+            return Err(ApiError::Database(e));
         }
     };
 
@@ -2148,7 +2291,10 @@ pub async fn read_return_message(
     let return_stream_name = format!("{}.return.{}", processor.stream, message_id);
     debug!("Constructed return stream name: {}", return_stream_name);
 
-    match &state.message_queue {
+    match state
+        .queue(processor.queue_backend.as_deref())
+        .map_err(ApiError::BadRequest)?
+    {
         crate::state::MessageQueue::Redis { client } => {
             // Get a Redis connection
             let mut conn = match client.get_connection() {
@@ -2158,10 +2304,7 @@ pub async fn read_return_message(
                 }
                 Err(e) => {
                     error!("Redis connection error: {}", e);
-                    return Err((
-                        StatusCode::INTERNAL_SERVER_ERROR,
-                        Json(json!({"error": format!("Redis connection error: {}", e)})),
-                    ));
+                    return Err(ApiError::Internal(format!("Redis connection error: {}", e)));
                 }
             };
 
@@ -2173,21 +2316,16 @@ pub async fn read_return_message(
                 Ok(exists) => exists,
                 Err(e) => {
                     error!("Failed to check if return stream exists: {}", e);
-                    return Err((
-                        StatusCode::INTERNAL_SERVER_ERROR,
-                        Json(json!({"error": format!("Failed to check stream existence: {}", e)})),
-                    ));
+                    return Err(ApiError::Internal(format!(
+                        "Failed to check stream existence: {}",
+                        e
+                    )));
                 }
             };
 
             if !stream_exists {
                 debug!("Return stream '{}' does not exist", return_stream_name);
-                return Err((
-                    StatusCode::NOT_FOUND,
-                    Json(
-                        json!({"error": "Return stream not found - message may not exist or may have already been consumed"}),
-                    ),
-                ));
+                return Err(ApiError::NotFound("Return stream not found - message may not exist or may have already been consumed".to_string()));
             }
 
             // Read from the return stream using XREAD with BLOCK and COUNT 1
@@ -2239,29 +2377,25 @@ pub async fn read_return_message(
                         "Error reading from return stream '{}': {}",
                         return_stream_name, e
                     );
-                    return Err((
-                        StatusCode::INTERNAL_SERVER_ERROR,
-                        Json(json!({"error": format!("Error reading from return stream: {}", e)})),
-                    ));
+                    return Err(ApiError::Internal(format!(
+                        "Error reading from return stream: {}",
+                        e
+                    )));
                 }
                 Err(e) => {
                     error!(
                         "Spawn_blocking task failed for return stream '{}': {}",
                         return_stream_name, e
                     );
-                    return Err((
-                        StatusCode::INTERNAL_SERVER_ERROR,
-                        Json(json!({"error": format!("Task execution error: {}", e)})),
-                    ));
+                    return Err(ApiError::Internal(format!("Task execution error: {}", e)));
                 }
             };
 
             // Process the result without cleanup - keep stream intact for subsequent polls
             process_return_stream_result(result, &return_stream_name)
         }
-        crate::state::MessageQueue::Kafka { .. } => Err((
-            StatusCode::BAD_REQUEST,
-            Json(json!({"error": "Kafka streams are not currently supported"})),
+        crate::state::MessageQueue::Kafka { .. } => Err(ApiError::BadRequest(
+            "Kafka streams are not currently supported".to_string(),
         )),
     }
 }
@@ -2270,16 +2404,15 @@ pub async fn read_return_message(
 fn process_return_stream_result(
     result: redis::streams::StreamReadReply,
     return_stream_name: &str,
-) -> Result<Json<serde_json::Value>, (StatusCode, Json<serde_json::Value>)> {
+) -> Result<Json<serde_json::Value>, ApiError> {
     // Check if we got a response
     if result.keys.is_empty() {
         error!(
             "Timed out or received empty response from return stream '{}'",
             return_stream_name
         );
-        return Err((
-            StatusCode::REQUEST_TIMEOUT,
-            Json(json!({"error": "Timed out waiting for return message"})),
+        return Err(ApiError::Timeout(
+            "Timed out waiting for return message".to_string(),
         ));
     }
 
@@ -2340,9 +2473,8 @@ fn process_return_stream_result(
         "Processed all messages in return stream '{}', but none contained a 'data' field.",
         return_stream_name
     );
-    Err((
-        StatusCode::INTERNAL_SERVER_ERROR,
-        Json(json!({"error": "Received return message without data field"})),
+    Err(ApiError::Internal(
+        "Received return message without data field".to_string(),
     ))
 }
 
@@ -2389,6 +2521,8 @@ pub async fn processor_websocket(
 /// # Usage
 /// - For single responses: Connect to the WebSocket and receive one message
 /// - For streaming responses: Connect and receive multiple messages until completion
+/// - Connecting before the processor has replied is fine; messages are pushed as they arrive
+/// - The server ends with a `{"stream_complete": true, ...}` message and closes the socket
 /// - The WebSocket will automatically clean up the return stream when done
 ///
 /// # URL Format
@@ -2427,15 +2561,10 @@ async fn handle_processor_return_socket(
     );
 
     let db_pool = &state.db_pool;
-    let (sender, _receiver) = socket.split();
+    let (sender, receiver) = socket.split();
 
     // Collect owner IDs from user_profile
-    let mut owner_ids: Vec<String> = if let Some(orgs) = &user_profile.organizations {
-        orgs.keys().cloned().collect()
-    } else {
-        Vec::new()
-    };
-    owner_ids.push(user_profile.email.clone());
+    let owner_ids = user_profile.owner_ids();
     let owner_id_refs: Vec<&str> = owner_ids.iter().map(|s| s.as_str()).collect();
 
     // Find the processor to get its stream name
@@ -2462,308 +2591,197 @@ async fn handle_processor_return_socket(
     let return_stream_name = format!("{}.return.{}", processor.stream, message_id);
     debug!("Constructed return stream name: {}", return_stream_name);
 
+    let queue = match state.queue(processor.queue_backend.as_deref()) {
+        Ok(queue) => queue.clone(),
+        Err(e) => {
+            let mut sender_locked = sender;
+            let _ = sender_locked.send(Message::Text(e)).await;
+            let _ = sender_locked.close().await;
+            return;
+        }
+    };
+
     // Start streaming processor return messages
-    stream_processor_return_messages(sender, state, return_stream_name).await;
+    stream_processor_return_messages(sender, receiver, queue, return_stream_name).await;
 }
 
 /// Stream processor return messages via WebSocket
-async fn stream_processor_return_messages<S>(sender: S, state: AppState, return_stream_name: String)
-where
+async fn stream_processor_return_messages<S, R, E>(
+    sender: S,
+    receiver: R,
+    queue: crate::state::MessageQueue,
+    return_stream_name: String,
+) where
     S: SinkExt<Message> + Unpin + Send + 'static,
     <S as futures::Sink<Message>>::Error: std::fmt::Debug + Send,
+    R: futures::Stream<Item = Result<Message, E>> + Unpin,
 {
-    let sender = Arc::new(Mutex::new(sender));
-
-    match &state.message_queue {
+    match &queue {
         crate::state::MessageQueue::Redis { client } => {
-            // Get a Redis connection
-            let mut conn = match client.get_connection() {
-                Ok(conn) => {
-                    debug!("Successfully obtained Redis connection for return stream read.");
-                    conn
-                }
-                Err(e) => {
-                    error!("Redis connection error: {}", e);
-                    let mut sender_lock = sender.lock().await;
-                    let _ = sender_lock
-                        .send(Message::Text(format!("Redis connection error: {}", e)))
-                        .await;
-                    let _ = sender_lock.close().await;
-                    return;
-                }
-            };
-
-            // Check if the return stream exists
-            let stream_exists: bool = match redis::cmd("EXISTS")
-                .arg(&return_stream_name)
-                .query(&mut conn)
-            {
-                Ok(exists) => exists,
-                Err(e) => {
-                    error!("Failed to check if return stream exists: {}", e);
-                    let mut sender_lock = sender.lock().await;
-                    let _ = sender_lock
-                        .send(Message::Text(format!(
-                            "Failed to check stream existence: {}",
-                            e
-                        )))
-                        .await;
-                    let _ = sender_lock.close().await;
-                    return;
-                }
-            };
-
-            if !stream_exists {
-                debug!("Return stream '{}' does not exist", return_stream_name);
-                let mut sender_lock = sender.lock().await;
-                let _ = sender_lock
-                    .send(Message::Text("Return stream not found - message may not exist or may have already been consumed".to_string()))
-                    .await;
-                let _ = sender_lock.close().await;
-                return;
-            }
-
-            // Stream messages continuously until the stream is complete
-            let mut last_id = "0".to_string(); // Start from beginning
-            let mut stream_complete = false;
-            let mut message_count = 0;
-            const MAX_WAIT_MS: u64 = 30000; // 30 seconds timeout per read
-            const MAX_MESSAGES: usize = 1000; // Prevent infinite loops
-
-            while !stream_complete && message_count < MAX_MESSAGES {
-                debug!(
-                    "Reading from return stream '{}' starting from ID '{}'",
-                    return_stream_name, last_id
-                );
-
-                // Move the blocking call to spawn_blocking
-                let return_stream_name_clone = return_stream_name.clone();
-                let last_id_clone = last_id.clone();
-                let client_clone = client.clone();
-
-                let read_result = tokio::task::spawn_blocking(move || {
-                    let mut conn = client_clone.get_connection().map_err(|e| {
-                        redis::RedisError::from((
-                            redis::ErrorKind::IoError,
-                            "Failed to get connection in spawn_blocking",
-                            e.to_string(),
-                        ))
-                    })?;
-
-                    debug!(
-                        "Attempting blocking XREAD on return stream '{}' with timeout {}ms from ID '{}'",
-                        return_stream_name_clone, MAX_WAIT_MS, last_id_clone
-                    );
-
-                    redis::cmd("XREAD")
-                        .arg("BLOCK")
-                        .arg(MAX_WAIT_MS)
-                        .arg("STREAMS")
-                        .arg(&return_stream_name_clone)
-                        .arg(&last_id_clone)
-                        .query::<redis::streams::StreamReadReply>(&mut conn)
-                })
+            stream_redis_return_messages(sender, receiver, client.clone(), return_stream_name)
+                .await;
+        }
+        crate::state::MessageQueue::Kafka { .. } => {
+            let mut sender = sender;
+            let _ = sender
+                .send(Message::Text(
+                    "Kafka streams are not currently supported for WebSocket streaming".to_string(),
+                ))
                 .await;
+            let _ = sender.close().await;
+        }
+    }
+}
 
-                // Handle the result from spawn_blocking
-                let result = match read_result {
-                    Ok(Ok(reply)) => {
-                        debug!("XREAD successful for return stream. Raw reply: {:?}", reply);
-                        reply
-                    }
-                    Ok(Err(e)) => {
-                        if e.to_string().contains("timeout") {
-                            debug!(
-                                "Timeout waiting for messages on return stream '{}'",
-                                return_stream_name
-                            );
-                            stream_complete = true;
-                            break;
-                        } else {
-                            error!(
-                                "Error reading from return stream '{}': {}",
-                                return_stream_name, e
-                            );
-                            let mut sender_lock = sender.lock().await;
-                            let _ = sender_lock
-                                .send(Message::Text(format!(
-                                    "Error reading from return stream: {}",
-                                    e
-                                )))
-                                .await;
-                            break;
-                        }
-                    }
-                    Err(e) => {
-                        error!(
-                            "Spawn_blocking task failed for return stream '{}': {}",
-                            return_stream_name, e
-                        );
-                        let mut sender_lock = sender.lock().await;
-                        let _ = sender_lock
-                            .send(Message::Text(format!("Task execution error: {}", e)))
-                            .await;
-                        break;
-                    }
-                };
+/// Pushes each entry of a Redis return stream to the client as it arrives,
+/// until the processor sends a terminal message, the client disconnects, or
+/// the return stream's TTL has passed. The stream may not exist yet when the
+/// client connects; XREAD simply blocks until the processor first writes it.
+async fn stream_redis_return_messages<S, R, E>(
+    sender: S,
+    mut receiver: R,
+    client: Arc<redis::Client>,
+    return_stream_name: String,
+) where
+    S: SinkExt<Message> + Unpin + Send + 'static,
+    <S as futures::Sink<Message>>::Error: std::fmt::Debug + Send,
+    R: futures::Stream<Item = Result<Message, E>> + Unpin,
+{
+    // Short blocking reads so a disconnect is noticed between them
+    const POLL_MS: u64 = 5000;
+    let deadline =
+        tokio::time::Instant::now() + std::time::Duration::from_secs(RETURN_STREAM_TTL_SECS);
 
-                // Process messages from this read
-                if result.keys.is_empty() {
-                    debug!("No more messages in return stream '{}'", return_stream_name);
-                    stream_complete = true;
-                    break;
-                }
+    let mut sender = sender;
+    let return_stream_guard = ReturnStreamGuard::new(client.clone(), &return_stream_name);
+    let mut last_id = "0".to_string();
+    let mut message_count = 0;
+    // The read in flight, kept across messages from the client so each entry
+    // is read once
+    let mut pending_read = None;
 
-                for key in result.keys {
-                    debug!("Processing key (stream): {:?}", key.key);
-                    for id_entry in key.ids {
-                        debug!(
-                            "Processing message ID: {:?}, Map: {:?}",
-                            id_entry.id, id_entry.map
-                        );
-                        last_id = id_entry.id.clone(); // Update last processed ID
-                        message_count += 1;
+    let reason = loop {
+        if tokio::time::Instant::now() >= deadline {
+            break "timeout";
+        }
 
-                        // Skip the init message if present
-                        if id_entry.map.contains_key("init") {
-                            debug!("Skipping init message");
-                            continue;
-                        }
+        let read = pending_read.get_or_insert_with(|| {
+            let client = client.clone();
+            let return_stream_name = return_stream_name.clone();
+            let last_id = last_id.clone();
+            tokio::task::spawn_blocking(move || {
+                let mut conn = client.get_connection()?;
+                redis::cmd("XREAD")
+                    .arg("BLOCK")
+                    .arg(POLL_MS)
+                    .arg("STREAMS")
+                    .arg(&return_stream_name)
+                    .arg(&last_id)
+                    .query::<Option<redis::streams::StreamReadReply>>(&mut conn)
+            })
+        });
 
-                        if let Some(data_value) = id_entry.map.get("data") {
-                            debug!("Found 'data' field: {:?}", data_value);
+        let reply = tokio::select! {
+            read_result = read => read_result,
+            incoming = receiver.next() => match incoming {
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => {
+                    debug!("Client disconnected from return stream '{}'", return_stream_name);
+                    break "disconnected";
+                }
+                // Anything else from the client, like a ping, is ignored
+                // while the read carries on
+                Some(Ok(_)) => continue,
+            },
+        };
+        pending_read = None;
 
-                            // Convert the Redis value to a string
-                            let data_str = match data_value {
-                                redis::Value::BulkString(bytes) => {
-                                    String::from_utf8_lossy(bytes).to_string()
-                                }
-                                redis::Value::SimpleString(s) => s.clone(),
-                                _ => format!("{:?}", data_value),
-                            };
-                            debug!("Data string: '{}'", data_str);
-
-                            // Try to parse the data as JSON
-                            match serde_json::from_str::<serde_json::Value>(&data_str) {
-                                Ok(json_data) => {
-                                    debug!("Successfully parsed data as JSON: {:?}", json_data);
-
-                                    // Check if this is a stream completion message
-                                    if let Some(stream_complete_flag) =
-                                        json_data.get("stream_complete")
-                                    {
-                                        if stream_complete_flag.as_bool().unwrap_or(false) {
-                                            debug!("Received stream completion signal");
-                                            stream_complete = true;
-                                        }
-                                    }
+        let reply = match reply {
+            Ok(Ok(Some(reply))) => reply,
+            // Nothing new within the poll interval
+            Ok(Ok(None)) => continue,
+            Ok(Err(e)) => {
+                error!(
+                    "Error reading from return stream '{}': {}",
+                    return_stream_name, e
+                );
+                let _ = sender
+                    .send(Message::Text(format!(
+                        "Error reading from return stream: {}",
+                        e
+                    )))
+                    .await;
+                break "error";
+            }
+            Err(e) => {
+                error!(
+                    "Spawn_blocking task failed for return stream '{}': {}",
+                    return_stream_name, e
+                );
+                let _ = sender
+                    .send(Message::Text(format!("Task execution error: {}", e)))
+                    .await;
+                break "error";
+            }
+        };
 
-                                    // Also check for StreamResponseMessage with success status (actual completion pattern)
-                                    if let Some(kind) = json_data.get("kind") {
-                                        if kind.as_str() == Some("StreamResponseMessage") {
-                                            if let Some(status) = json_data.get("status") {
-                                                if status.as_str() == Some("success") {
-                                                    debug!("Received StreamResponseMessage with success status - stream complete");
-                                                    stream_complete = true;
-                                                }
-                                            }
-                                        }
-                                    }
+        let mut terminal = false;
+        for id_entry in reply.keys.into_iter().flat_map(|key| key.ids) {
+            last_id = id_entry.id.clone();
 
-                                    // Send the message to the client
-                                    let mut sender_lock = sender.lock().await;
-                                    match sender_lock.send(Message::Text(data_str)).await {
-                                        Ok(_) => debug!("Sent message to WebSocket client"),
-                                        Err(e) => {
-                                            debug!("Client disconnected: {:?}", e);
-                                            stream_complete = true;
-                                            break;
-                                        }
-                                    }
-                                }
-                                Err(e) => {
-                                    warn!("Failed to parse return data as JSON: {}. Sending raw string.", e);
-                                    let mut sender_lock = sender.lock().await;
-                                    let raw_message = json!({"raw_response": data_str, "parse_error": e.to_string()});
-                                    if let Err(send_err) = sender_lock
-                                        .send(Message::Text(raw_message.to_string()))
-                                        .await
-                                    {
-                                        debug!("Client disconnected: {:?}", send_err);
-                                        stream_complete = true;
-                                        break;
-                                    }
-                                }
-                            }
-                        } else {
-                            debug!(
-                                "'data' field not found in message map for ID: {:?}",
-                                id_entry.id
-                            );
-                        }
-                    }
-                }
+            if id_entry.map.contains_key("init") {
+                continue;
             }
+            let Some(data_value) = id_entry.map.get("data") else {
+                debug!("'data' field not found in message {:?}", id_entry.id);
+                continue;
+            };
+            let data_str = match data_value {
+                redis::Value::BulkString(bytes) => String::from_utf8_lossy(bytes).to_string(),
+                redis::Value::SimpleString(s) => s.clone(),
+                _ => format!("{:?}", data_value),
+            };
 
-            // Clean up the return stream after reading
-            debug!("Cleaning up return stream '{}'", return_stream_name);
-            let mut conn = match client.get_connection() {
-                Ok(c) => c,
+            let text = match serde_json::from_str::<serde_json::Value>(&data_str) {
+                Ok(json_data) => {
+                    terminal |= is_terminal_return_message(&json_data);
+                    data_str
+                }
                 Err(e) => {
-                    error!("Failed to get connection for DEL command: {}", e);
-                    let mut sender_lock = sender.lock().await;
-                    let _ = sender_lock.close().await;
-                    return;
+                    warn!(
+                        "Failed to parse return data as JSON: {}. Sending raw string.",
+                        e
+                    );
+                    json!({"raw_response": data_str, "parse_error": e.to_string()}).to_string()
                 }
             };
 
-            let del_result: Result<(), redis::RedisError> =
-                redis::cmd("DEL").arg(&return_stream_name).query(&mut conn);
-
-            if let Err(e) = del_result {
-                warn!(
-                    "Failed to delete return stream '{}': {}",
-                    return_stream_name, e
-                );
-            } else {
-                debug!(
-                    "Successfully deleted return stream '{}'",
-                    return_stream_name
-                );
+            if let Err(e) = sender.send(Message::Text(text)).await {
+                debug!("Client disconnected: {:?}", e);
+                return;
             }
-
-            // Send completion message and close connection
-            {
-                let mut sender_lock = sender.lock().await;
-                if message_count == 0 {
-                    let _ = sender_lock
-                        .send(Message::Text(
-                            "No messages found in return stream".to_string(),
-                        ))
-                        .await;
-                } else {
-                    let completion_msg = json!({
-                        "stream_complete": true,
-                        "message_count": message_count,
-                        "reason": if message_count >= MAX_MESSAGES { "max_messages_reached" } else { "stream_ended" }
-                    });
-                    let _ = sender_lock
-                        .send(Message::Text(completion_msg.to_string()))
-                        .await;
-                }
-                let _ = sender_lock.close().await;
-                debug!("WebSocket connection closed by server.");
+            message_count += 1;
+            if terminal {
+                break;
             }
         }
-        crate::state::MessageQueue::Kafka { .. } => {
-            let mut sender_lock = sender.lock().await;
-            let _ = sender_lock
-                .send(Message::Text(
-                    "Kafka streams are not currently supported for WebSocket streaming".to_string(),
-                ))
-                .await;
-            let _ = sender_lock.close().await;
+
+        if terminal {
+            break "stream_ended";
         }
+    };
+
+    // Remove the stream before the client hears we're done
+    drop(return_stream_guard);
+
+    if reason != "disconnected" {
+        let completion_msg = json!({
+            "stream_complete": true,
+            "message_count": message_count,
+            "reason": reason,
+        });
+        let _ = sender.send(Message::Text(completion_msg.to_string())).await;
+        let _ = sender.close().await;
+        debug!("WebSocket connection closed by server.");
     }
 }
 
@@ -2785,12 +2803,7 @@ async fn handle_bidirectional_processor_socket(
     let ws_sender = Arc::new(Mutex::new(ws_sender));
 
     // Collect owner IDs from user_profile
-    let mut owner_ids: Vec<String> = if let Some(orgs) = &user_profile.organizations {
-        orgs.keys().cloned().collect()
-    } else {
-        Vec::new()
-    };
-    owner_ids.push(user_profile.email.clone());
+    let owner_ids = user_profile.owner_ids();
     let owner_id_refs: Vec<&str> = owner_ids.iter().map(|s| s.as_str()).collect();
 
     // Find the processor
@@ -2949,24 +2962,14 @@ async fn generate_agent_key_for_processor(
         return Err("Auth server configuration missing".to_string());
     }
 
-    let agent_key_request = crate::models::V1CreateAgentKeyRequest {
-        agent_id: format!("processor-{}", processor.id),
-        name: format!(
-            "websocket-processor-{}-{}",
-            processor.id,
-            ShortUuid::generate().to_string()
-        ),
-        duration: 86400, // 24 hour validity
-    };
-
-    let agent_key_response =
-        crate::agent::agent::create_agent_key(&auth_server, user_token, agent_key_request)
-            .await
-            .map_err(|e| format!("Failed to create agent key: {}", e))?;
-
-    agent_key_response
-        .key
-        .ok_or_else(|| "Agent key response missing key".to_string())
+    processor_agent_key(
+        &AGENT_KEY_CACHE,
+        &auth_server,
+        user_token,
+        &processor.id,
+        SERVER_CONFIG.agent_key_duration,
+    )
+    .await
 }
 
 /// Handle a single WebSocket message by sending it to the processor and streaming back responses
@@ -3005,10 +3008,11 @@ async fn handle_websocket_message(
         handle: user_prof.handle.clone(),
         adapter: Some(format!("processor:{}", processor.id)),
         api_key: Some(agent_key),
+        entry_id: None,
     };
 
     // Send message to processor stream
-    match &state.message_queue {
+    match state.queue(processor.queue_backend.as_deref())? {
         crate::state::MessageQueue::Redis { client } => {
             let mut conn = client
                 .get_connection()
@@ -3017,13 +3021,13 @@ async fn handle_websocket_message(
             let message_json = serde_json::to_string(&stream_message)
                 .map_err(|e| format!("Failed to serialize message: {}", e))?;
 
-            let _stream_id: String = redis::cmd("XADD")
-                .arg(&processor.stream)
-                .arg("*")
-                .arg("data")
-                .arg(&message_json)
-                .query(&mut conn)
-                .map_err(|e| format!("Failed to send message to stream: {}", e))?;
+            let _stream_id: String = xadd_capped(
+                &processor.stream,
+                stream_max_len(&processor),
+                &[("data", message_json.as_str())],
+            )
+            .query(&mut conn)
+            .map_err(|e| format!("Failed to send message to stream: {}", e))?;
 
             debug!(
                 "Sent WebSocket message {} to processor stream {}",
@@ -3158,3 +3162,208 @@ async fn stream_return_messages_to_websocket(
     debug!("Completed streaming return messages for {}", message_id);
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::resources::v1::processors::streams::RETURN_STREAM_MAX_LEN;
+    use axum::routing::get;
+    use axum::Router;
+    use std::collections::HashMap;
+    use tokio_tungstenite::tungstenite::Message as ClientMessage;
+
+    #[tokio::test]
+    #[ignore = "needs a Redis at NEBU_TEST_REDIS_URL"]
+    async fn test_send_wait_times_out_and_cleans_up() {
+        // Arrange
+        let url = std::env::var("NEBU_TEST_REDIS_URL").expect("NEBU_TEST_REDIS_URL is not set");
+        let client = Arc::new(redis::Client::open(url).unwrap());
+        let mut conn = client.get_connection().unwrap();
+        let stream = format!("test:send-return:{}", ShortUuid::generate());
+
+        // Act
+        let started = std::time::Instant::now();
+        let result = wait_for_send_response(&client, &mut conn, &stream, 200).await;
+
+        // Assert
+        let err = result.unwrap_err();
+        assert!(matches!(err, ApiError::Timeout(_)));
+        assert_eq!(err.into_response().status(), StatusCode::REQUEST_TIMEOUT);
+        assert!(started.elapsed() < std::time::Duration::from_secs(5));
+        let exists: u64 = redis::cmd("EXISTS").arg(&stream).query(&mut conn).unwrap();
+        assert_eq!(exists, 0);
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a Redis at NEBU_TEST_REDIS_URL"]
+    async fn test_send_wait_returns_reply_and_cleans_up() {
+        let url = std::env::var("NEBU_TEST_REDIS_URL").expect("NEBU_TEST_REDIS_URL is not set");
+        let client = Arc::new(redis::Client::open(url).unwrap());
+        let mut conn = client.get_connection().unwrap();
+        let stream = format!("test:send-return:{}", ShortUuid::generate());
+
+        let replier = {
+            let client = client.clone();
+            let stream = stream.clone();
+            tokio::task::spawn_blocking(move || {
+                std::thread::sleep(std::time::Duration::from_millis(200));
+                let mut conn = client.get_connection().unwrap();
+                let _: String = xadd_capped(&stream, Some(10), &[("data", r#"{"answer":42}"#)])
+                    .query(&mut conn)
+                    .unwrap();
+            })
+        };
+        let response = wait_for_send_response(&client, &mut conn, &stream, 5_000)
+            .await
+            .unwrap();
+        replier.await.unwrap();
+
+        assert_eq!(response, json!({"answer": 42}));
+        let exists: u64 = redis::cmd("EXISTS").arg(&stream).query(&mut conn).unwrap();
+        assert_eq!(exists, 0);
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a Redis at NEBU_TEST_REDIS_URL"]
+    async fn test_return_stream_ws_pushes_messages_until_terminal() {
+        // Arrange
+        let url = std::env::var("NEBU_TEST_REDIS_URL").expect("NEBU_TEST_REDIS_URL is not set");
+        let client = Arc::new(redis::Client::open(url).unwrap());
+        let stream = format!("test:ws-return:{}", ShortUuid::generate());
+
+        let app = {
+            let client = client.clone();
+            let stream = stream.clone();
+            Router::new().route(
+                "/stream",
+                get(move |ws: WebSocketUpgrade| {
+                    let client = client.clone();
+                    let stream = stream.clone();
+                    async move {
+                        ws.on_upgrade(move |socket| async move {
+                            let (sender, receiver) = socket.split();
+                            stream_redis_return_messages(sender, receiver, client, stream).await;
+                        })
+                    }
+                }),
+            )
+        };
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        // Act
+        let (mut socket, _) = tokio_tungstenite::connect_async(format!("ws://{}/stream", addr))
+            .await
+            .unwrap();
+        let mut conn = client.get_connection().unwrap();
+        for data in [
+            r#"{"content": "a"}"#,
+            r#"{"content": "b"}"#,
+            r#"{"kind": "StreamResponseMessage", "status": "success"}"#,
+        ] {
+            // Pings while a read is blocked must neither lose nor repeat entries
+            socket
+                .send(ClientMessage::Ping(vec![1].into()))
+                .await
+                .unwrap();
+            tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+            let _: String = xadd_capped(&stream, Some(RETURN_STREAM_MAX_LEN), &[("data", data)])
+                .query(&mut conn)
+                .unwrap();
+        }
+
+        let mut received = Vec::new();
+        while let Some(Ok(message)) = socket.next().await {
+            match message {
+                ClientMessage::Text(text) => {
+                    received.push(serde_json::from_str::<serde_json::Value>(&text).unwrap())
+                }
+                ClientMessage::Close(_) => break,
+                _ => {}
+            }
+        }
+
+        // Assert
+        assert_eq!(received.len(), 4);
+        assert_eq!(received[0]["content"], "a");
+        assert_eq!(received[1]["content"], "b");
+        assert_eq!(received[2]["kind"], "StreamResponseMessage");
+        assert_eq!(received[3]["stream_complete"], true);
+        assert_eq!(received[3]["message_count"], 3);
+        assert_eq!(received[3]["reason"], "stream_ended");
+        let exists: u64 = redis::cmd("EXISTS").arg(&stream).query(&mut conn).unwrap();
+        assert_eq!(exists, 0);
+    }
+
+    #[test]
+    #[ignore = "needs a Redis at NEBU_TEST_REDIS_URL"]
+    fn test_health_reply_wait_honors_timeout() {
+        let url = std::env::var("NEBU_TEST_REDIS_URL").expect("NEBU_TEST_REDIS_URL is not set");
+        let client = redis::Client::open(url).unwrap();
+        let mut conn = client.get_connection().unwrap();
+        let stream = format!("test:health-return:{}", ShortUuid::generate());
+        let init_id = init_return_stream(&mut conn, &stream).unwrap();
+
+        let started = std::time::Instant::now();
+        let reply = wait_for_return_entries(&mut conn, &stream, &init_id, 300).unwrap();
+        let waited = started.elapsed();
+
+        assert!(reply.keys.is_empty());
+        assert!(
+            waited >= std::time::Duration::from_millis(250),
+            "{:?}",
+            waited
+        );
+        assert!(waited < std::time::Duration::from_secs(5), "{:?}", waited);
+        let _: () = redis::cmd("DEL").arg(&stream).query(&mut conn).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_return_stream_errors_have_stable_shape() {
+        let timed_out = process_return_stream_result(Default::default(), "ret:1").unwrap_err();
+        let response = timed_out.into_response();
+        assert_eq!(response.status(), StatusCode::REQUEST_TIMEOUT);
+        let body: serde_json::Value = serde_json::from_slice(
+            &axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(
+            body,
+            json!({
+                "error": "Timed out waiting for return message",
+                "code": "timeout",
+                "request_id": null,
+            })
+        );
+
+        let reply = redis::streams::StreamReadReply {
+            keys: vec![redis::streams::StreamKey {
+                key: "ret:1".to_string(),
+                ids: vec![redis::streams::StreamId {
+                    id: "1-0".to_string(),
+                    map: HashMap::from([("init".to_string(), redis::Value::Int(1))]),
+                }],
+            }],
+        };
+        let missing_data = process_return_stream_result(reply, "ret:1").unwrap_err();
+        assert_eq!(missing_data.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(missing_data.code(), "internal");
+    }
+
+    #[tokio::test]
+    async fn test_stream_reads_use_the_processor_queue() {
+        let state = AppState::with_kafka_for_tests().await;
+        let on_redis = processors::Model::test_fixture();
+        let on_kafka = processors::Model {
+            queue_backend: Some("kafka".to_string()),
+            ..processors::Model::test_fixture()
+        };
+
+        assert!(redis_client(&state, &on_redis).is_ok());
+        let err = redis_client(&state, &on_kafka).unwrap_err();
+        assert_eq!(err.status(), StatusCode::BAD_REQUEST);
+    }
+}
@@ -1,34 +1,73 @@
 use crate::agent::ns::auth_ns;
+use crate::auth::scopes::{ApiKeyScopes, RequiredScope, REVEAL_SECRETS};
 use crate::models::V1ResourceMeta;
-use crate::resources::v1::secrets::models::{V1Secret, V1SecretRequest};
+use crate::resources::v1::secrets::models::{
+    V1Secret, V1SecretRequest, V1Secrets, V1SecretsRequest,
+};
 use crate::utils::namespace::resolve_namespace;
 use crate::{
     entities::secrets, models::V1UserProfile, mutation::Mutation, query::Query, state::AppState,
 };
 use axum::{
-    extract::{Extension, Json, Path, State},
+    extract::{Extension, Json, Path, Query as QueryParams, State},
     http::StatusCode,
     response::IntoResponse,
 };
 use sea_orm::*;
+use serde::Deserialize;
 use serde_json::json;
 use short_uuid::ShortUuid;
 use tracing::{debug, info};
 
-/// Handler: List secrets for the current user (and their organizations)
+/// Query for reading secrets. Values are left out unless `reveal` is set,
+/// which restricted API keys need the `secrets:reveal` scope for.
+#[derive(Debug, Default, Deserialize)]
+pub struct SecretReadParams {
+    #[serde(default)]
+    pub reveal: bool,
+}
+
+/// Whether the caller may see the values of secrets in `namespace`. Listings
+/// span namespaces and pass None, so keys limited to namespaces can't reveal
+/// them there.
+fn check_reveal(
+    scopes: Option<&ApiKeyScopes>,
+    namespace: Option<&str>,
+) -> Result<(), (StatusCode, Json<serde_json::Value>)> {
+    let Some(scopes) = scopes.filter(|s| !s.is_unrestricted()) else {
+        return Ok(());
+    };
+    let required = RequiredScope {
+        action: REVEAL_SECRETS.to_string(),
+        namespace: namespace.map(|ns| ns.to_string()),
+    };
+    if scopes.allows(&required) {
+        Ok(())
+    } else {
+        Err((
+            StatusCode::FORBIDDEN,
+            Json(json!({
+                "error": format!("Revealing secret values requires the '{}' scope", REVEAL_SECRETS)
+            })),
+        ))
+    }
+}
+
+/// Handler: List secrets for the current user (and their organizations).
+/// Only metadata is returned unless `?reveal=true`.
 pub async fn list_secrets(
     State(state): State<AppState>,
     Extension(user_profile): Extension<V1UserProfile>,
+    scopes: Option<Extension<ApiKeyScopes>>,
+    QueryParams(params): QueryParams<SecretReadParams>,
 ) -> Result<Json<Vec<V1Secret>>, (StatusCode, Json<serde_json::Value>)> {
     let db_pool = &state.db_pool;
+    if params.reveal {
+        check_reveal(scopes.as_deref(), None)?;
+    }
 
     // Gather all possible owner IDs from user + organizations
-    let mut owner_ids: Vec<String> = user_profile
-        .organizations
-        .as_ref()
-        .map(|orgs| orgs.keys().cloned().collect())
-        .unwrap_or_default();
-    owner_ids.push(user_profile.email.clone());
+    let owner_ids = user_profile.owner_ids();
     let owner_id_refs: Vec<&str> = owner_ids.iter().map(|s| s.as_str()).collect();
 
     info!("Listing secrets for user: {}", owner_ids.join(", "));
@@ -45,46 +84,33 @@ pub async fn list_secrets(
 
     info!("Found {} secrets", secrets_list.len());
 
-    // Transform them into our V1Secret response (decrypted if needed)
-    let response = secrets_list
+    let response: Vec<V1Secret> = secrets_list
         .into_iter()
-        .map(|secret| {
-            let decrypted_value = secret.decrypt_value().ok(); // returns Result; ignore errors
-            V1Secret {
-                kind: "Secret".to_string(),
-                metadata: V1ResourceMeta {
-                    id: secret.id,
-                    name: secret.name,
-                    namespace: secret.namespace,
-                    owner: secret.owner,
-                    owner_ref: secret.owner_ref,
-                    created_by: secret.created_by.unwrap_or_default(),
-                    labels: secret
-                        .labels
-                        .as_ref()
-                        .and_then(|v| serde_json::from_value(v.clone()).ok())
-                        .unwrap_or_default(),
-                    created_at: secret.created_at.timestamp(),
-                    updated_at: secret.updated_at.timestamp(),
-                },
-                value: decrypted_value,
-                expires_at: secret.expires_at,
-            }
-        })
+        .map(|secret| secret_response(secret, params.reveal))
         .collect();
 
-    debug!("Found secrets response: {:?}", response);
+    debug!(
+        "Found {} secrets, revealed: {}",
+        response.len(),
+        params.reveal
+    );
     Ok(Json(response))
 }
 
-/// Handler: Get a single secret by namespace and name
+/// Handler: Get a single secret by namespace and name. The value is only
+/// included with `?reveal=true`.
 pub async fn get_secret(
     State(state): State<AppState>,
     Extension(user_profile): Extension<V1UserProfile>,
+    scopes: Option<Extension<ApiKeyScopes>>,
     Path((namespace, name)): Path<(String, String)>,
+    QueryParams(params): QueryParams<SecretReadParams>,
 ) -> Result<Json<V1Secret>, (StatusCode, Json<serde_json::Value>)> {
     let db_pool = &state.db_pool;
     let resolved_namespace = resolve_namespace(&namespace, &user_profile);
+    if params.reveal {
+        check_reveal(scopes.as_deref(), Some(&resolved_namespace))?;
+    }
 
     // Attempt to retrieve the secret from the DB
     let secret = Query::find_secret_by_namespace_and_name(db_pool, &resolved_namespace, &name)
@@ -107,56 +133,32 @@ pub async fn get_secret(
         }
     };
 
-    // Decrypt the value if needed
-    let decrypted_value = secret_model.decrypt_value().ok();
-
-    // Build and return the V1Secret response
-    let secret_response = V1Secret {
-        kind: "Secret".to_string(),
-        metadata: V1ResourceMeta {
-            id: secret_model.id,
-            name: secret_model.name,
-            namespace: secret_model.namespace,
-            owner: secret_model.owner,
-            owner_ref: secret_model.owner_ref,
-            created_by: secret_model.created_by.unwrap_or_default(),
-            labels: secret_model
-                .labels
-                .as_ref()
-                .and_then(|v| serde_json::from_value(v.clone()).ok())
-                .unwrap_or_default(),
-            created_at: secret_model.created_at.timestamp(),
-            updated_at: secret_model.updated_at.timestamp(),
-        },
-        value: decrypted_value,
-        expires_at: secret_model.expires_at,
-    };
-
-    Ok(Json(secret_response))
+    Ok(Json(secret_response(secret_model, params.reveal)))
 }
 
 pub async fn get_secret_by_id(
     State(state): State<AppState>,
     Extension(user_profile): Extension<V1UserProfile>,
+    scopes: Option<Extension<ApiKeyScopes>>,
     Path(id): Path<String>,
+    QueryParams(params): QueryParams<SecretReadParams>,
 ) -> Result<Json<V1Secret>, (StatusCode, Json<serde_json::Value>)> {
     let db_pool = &state.db_pool;
 
-    _get_secret_by_id(db_pool, &id, &user_profile).await
+    let secret = _get_secret_by_id(db_pool, &id, &user_profile).await?;
+    if params.reveal {
+        check_reveal(scopes.as_deref(), Some(&secret.namespace))?;
+    }
+    Ok(Json(secret_response(secret, params.reveal)))
 }
 
 pub async fn _get_secret_by_id(
     db_pool: &DatabaseConnection,
     id: &str,
     user_profile: &V1UserProfile,
-) -> Result<Json<V1Secret>, (StatusCode, Json<serde_json::Value>)> {
+) -> Result<secrets::Model, (StatusCode, Json<serde_json::Value>)> {
     // Gather owners
-    let mut owner_ids: Vec<String> = user_profile
-        .organizations
-        .as_ref()
-        .map(|orgs| orgs.keys().cloned().collect())
-        .unwrap_or_default();
-    owner_ids.push(user_profile.email.clone());
+    let owner_ids = user_profile.owner_ids();
     let owner_id_refs: Vec<&str> = owner_ids.iter().map(|s| s.as_str()).collect();
 
     info!("Getting secret for user: {}", owner_ids.join(", "));
@@ -172,32 +174,7 @@ pub async fn _get_secret_by_id(
         })?;
 
     info!("Found secret: {}", secret_model.id);
-
-    // Decrypt
-    let decrypted_value = secret_model.decrypt_value().ok();
-
-    let secret_response = V1Secret {
-        kind: "Secret".to_string(),
-        metadata: V1ResourceMeta {
-            id: secret_model.id,
-            name: secret_model.name,
-            namespace: secret_model.namespace,
-            owner: secret_model.owner,
-            owner_ref: secret_model.owner_ref,
-            created_by: secret_model.created_by.unwrap_or_default(),
-            labels: secret_model
-                .labels
-                .as_ref()
-                .and_then(|v| serde_json::from_value(v.clone()).ok())
-                .unwrap_or_default(),
-            created_at: secret_model.created_at.timestamp(),
-            updated_at: secret_model.updated_at.timestamp(),
-        },
-        value: decrypted_value,
-        expires_at: secret_model.expires_at,
-    };
-
-    Ok(Json(secret_response))
+    Ok(secret_model)
 }
 
 /// Handler: Create a new secret
@@ -208,6 +185,96 @@ pub async fn create_secret(
 ) -> Result<Json<V1Secret>, (StatusCode, Json<serde_json::Value>)> {
     let db_pool = &state.db_pool;
 
+    let secret_model = prepare_secret(db_pool, &user_profile, &payload).await?;
+
+    // Insert into DB
+    let inserted = secrets::ActiveModel::from(secret_model)
+        .insert(db_pool)
+        .await
+        .map_err(|err| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({ "error": format!("Failed to store secret: {}", err) })),
+            )
+        })?;
+
+    Ok(Json(secret_response(inserted, true)))
+}
+
+/// Most secrets a bulk create may hold
+const MAX_BULK_SECRETS: usize = 500;
+
+/// Handler: Create several secrets in one transaction, e.g. from a `.env`
+/// file. Every secret is validated before any is stored.
+pub async fn create_secrets(
+    State(state): State<AppState>,
+    Extension(user_profile): Extension<V1UserProfile>,
+    Json(payload): Json<V1SecretsRequest>,
+) -> Result<Json<V1Secrets>, (StatusCode, Json<serde_json::Value>)> {
+    let db_pool = &state.db_pool;
+
+    if payload.secrets.is_empty() || payload.secrets.len() > MAX_BULK_SECRETS {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(json!({
+                "error": format!("Provide between 1 and {} secrets", MAX_BULK_SECRETS)
+            })),
+        ));
+    }
+
+    let mut models = Vec::with_capacity(payload.secrets.len());
+    let mut seen = std::collections::HashSet::new();
+    for request in &payload.secrets {
+        let model = prepare_secret(db_pool, &user_profile, request).await?;
+        if !seen.insert(model.full_name.clone()) {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                Json(json!({ "error": format!("Secret '{}' is listed twice", model.full_name) })),
+            ));
+        }
+        models.push(model);
+    }
+
+    let txn = db_pool.begin().await.map_err(|err| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({ "error": format!("Failed to store secrets: {}", err) })),
+        )
+    })?;
+    let mut created = Vec::with_capacity(models.len());
+    for model in models {
+        let full_name = model.full_name.clone();
+        let inserted = secrets::ActiveModel::from(model)
+            .insert(&txn)
+            .await
+            .map_err(|err| {
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    Json(json!({
+                        "error": format!("Failed to store secret '{}': {}", full_name, err)
+                    })),
+                )
+            })?;
+        created.push(secret_response(inserted, true));
+    }
+    txn.commit().await.map_err(|err| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({ "error": format!("Failed to store secrets: {}", err) })),
+        )
+    })?;
+
+    info!("Created {} secrets", created.len());
+    Ok(Json(V1Secrets { secrets: created }))
+}
+
+/// Validates a secret request, authorizes its namespace and encrypts its
+/// value, giving the model to insert
+async fn prepare_secret(
+    db_pool: &DatabaseConnection,
+    user_profile: &V1UserProfile,
+    payload: &V1SecretRequest,
+) -> Result<secrets::Model, (StatusCode, Json<serde_json::Value>)> {
     // Generate a unique ID for the secret. You might use `short_uuid`, etc.
     let secret_id = ShortUuid::generate().to_string();
 
@@ -225,36 +292,25 @@ pub async fn create_secret(
         )
     })?;
 
-    let namespace_opt = payload.metadata.namespace;
+    crate::validate::validate_secret_value(&payload.value).map_err(|err| {
+        (
+            StatusCode::BAD_REQUEST,
+            Json(json!({ "error": err.to_string() })),
+        )
+    })?;
 
-    let handle = match user_profile.handle.clone() {
-        Some(handle) => handle,
-        None => user_profile
-            .email
-            .clone()
-            .replace("@", "-")
-            .replace(".", "-"),
-    };
+    let namespace_opt = payload.metadata.namespace.clone();
 
     let namespace = match namespace_opt {
         Some(namespace) => namespace,
-        None => match crate::handlers::v1::namespaces::ensure_namespace(
-            db_pool,
-            &handle,
-            &user_profile.email,
-            &user_profile.email,
-            None,
-        )
-        .await
-        {
-            Ok(_) => handle,
-            Err(e) => {
-                return Err((
-                    StatusCode::BAD_REQUEST,
-                    Json(json!({ "error": format!("Invalid namespace: {}", e) })),
-                ));
-            }
-        },
+        None => {
+            crate::handlers::v1::namespaces::implicit_namespace(
+                db_pool,
+                user_profile,
+                crate::config::SERVER_CONFIG.allow_implicit_namespaces,
+            )
+            .await?
+        }
     };
 
     crate::validate::validate_namespace(&namespace).map_err(|err| {
@@ -264,12 +320,7 @@ pub async fn create_secret(
         )
     })?;
 
-    let mut owner_ids: Vec<String> = if let Some(orgs) = &user_profile.organizations {
-        orgs.keys().cloned().collect()
-    } else {
-        Vec::new()
-    };
-    owner_ids.push(user_profile.email.clone());
+    let owner_ids = user_profile.owner_ids();
 
     let owner = auth_ns(db_pool, &owner_ids, &namespace)
         .await
@@ -281,8 +332,8 @@ pub async fn create_secret(
         })?;
 
     // Create the new Model, which will auto-encrypt the secret value
-    let secret_model = secrets::Model::new(
-        secret_id.clone(),
+    secrets::Model::new(
+        secret_id,
         name,
         namespace,
         owner,
@@ -296,19 +347,12 @@ pub async fn create_secret(
             StatusCode::INTERNAL_SERVER_ERROR,
             Json(json!({ "error": format!("Failed to encrypt secret: {}", err) })),
         )
-    })?;
-
-    // Insert into DB
-    let inserted = secrets::ActiveModel::from(secret_model)
-        .insert(db_pool)
-        .await
-        .map_err(|err| {
-            (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(json!({ "error": format!("Failed to store secret: {}", err) })),
-            )
-        })?;
+    })
+}
 
+/// The API view of a stored secret, with its value decrypted when `reveal`
+/// is set and left out otherwise
+fn secret_response(inserted: secrets::Model, reveal: bool) -> V1Secret {
     let created_by = inserted.created_by.clone().unwrap_or_default();
     let labels = inserted
         .labels
@@ -317,10 +361,14 @@ pub async fn create_secret(
         .unwrap_or_default();
 
     // Now decrypt using `inserted`, leaving the prior fields intact
-    let decrypted_value = inserted.decrypt_value().ok();
+    let decrypted_value = if reveal {
+        inserted.decrypt_value().ok()
+    } else {
+        None
+    };
 
     // Finally build the response
-    let response = V1Secret {
+    V1Secret {
         kind: "Secret".to_string(),
         metadata: V1ResourceMeta {
             id: inserted.id,
@@ -334,10 +382,9 @@ pub async fn create_secret(
             updated_at: inserted.updated_at.timestamp(),
         },
         value: decrypted_value,
+        version: Some(inserted.version),
         expires_at: inserted.expires_at,
-    };
-
-    Ok(Json(response))
+    }
 }
 
 /// Handler: Update a secret by namespace/name
@@ -393,12 +440,7 @@ pub async fn _update_secret_by_id(
     payload: &V1SecretRequest,
 ) -> Result<Json<V1Secret>, (StatusCode, Json<serde_json::Value>)> {
     // Gather owners
-    let mut owner_ids: Vec<String> = user_profile
-        .organizations
-        .as_ref()
-        .map(|orgs| orgs.keys().cloned().collect())
-        .unwrap_or_default();
-    owner_ids.push(user_profile.email.clone());
+    let owner_ids = user_profile.owner_ids();
     let owner_id_refs: Vec<&str> = owner_ids.iter().map(|s| s.as_str()).collect();
 
     // Fetch the model to ensure it exists and user can access
@@ -428,32 +470,8 @@ pub async fn _update_secret_by_id(
         )
     })?;
 
-    // Decrypt the newly updated secret
-    let decrypted_value = updated_secret.decrypt_value().ok();
-
-    // Build response
-    let response = V1Secret {
-        kind: "Secret".to_string(),
-        metadata: V1ResourceMeta {
-            id: updated_secret.id,
-            name: updated_secret.name,
-            namespace: updated_secret.namespace,
-            owner: updated_secret.owner,
-            owner_ref: updated_secret.owner_ref,
-            created_by: updated_secret.created_by.unwrap_or_default(),
-            labels: updated_secret
-                .labels
-                .as_ref()
-                .and_then(|v| serde_json::from_value(v.clone()).ok())
-                .unwrap_or_default(),
-            created_at: updated_secret.created_at.timestamp(),
-            updated_at: updated_secret.updated_at.timestamp(),
-        },
-        value: decrypted_value,
-        expires_at: updated_secret.expires_at,
-    };
-
-    Ok(Json(response))
+    // The value is only shown through a read with `reveal`
+    Ok(Json(secret_response(updated_secret, false)))
 }
 
 /// Handler: Delete a secret by namespace/name
@@ -508,12 +526,7 @@ pub async fn _delete_secret_by_id(
     user_profile: &V1UserProfile,
 ) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
     // Gather owners
-    let mut owner_ids: Vec<String> = user_profile
-        .organizations
-        .as_ref()
-        .map(|orgs| orgs.keys().cloned().collect())
-        .unwrap_or_default();
-    owner_ids.push(user_profile.email.clone());
+    let owner_ids = user_profile.owner_ids();
     let owner_id_refs: Vec<&str> = owner_ids.iter().map(|s| s.as_str()).collect();
 
     // Make sure the secret is accessible
@@ -547,3 +560,193 @@ pub async fn _delete_secret_by_id(
     // Return a 200 OK
     Ok(StatusCode::OK)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::V1ResourceMetaRequest;
+    use crate::state::MessageQueue;
+    use std::sync::Arc;
+
+    async fn state_with_secret() -> AppState {
+        let db = Database::connect("sqlite::memory:").await.unwrap();
+        let stmt = Schema::new(db.get_database_backend()).create_table_from_entity(secrets::Entity);
+        db.execute(db.get_database_backend().build(&stmt))
+            .await
+            .unwrap();
+        let secret = secrets::Model::new(
+            "s1".to_string(),
+            "db-password".to_string(),
+            "team-a".to_string(),
+            "me@example.com".to_string(),
+            "hunter2",
+            Some("me@example.com".to_string()),
+            None,
+            None,
+        )
+        .unwrap();
+        secrets::ActiveModel::from(secret)
+            .insert(&db)
+            .await
+            .unwrap();
+        AppState {
+            db_pool: db,
+            message_queue: MessageQueue::Redis {
+                client: Arc::new(redis::Client::open("redis://127.0.0.1:1").unwrap()),
+            },
+            extra_queues: Default::default(),
+        }
+    }
+
+    fn user() -> V1UserProfile {
+        V1UserProfile {
+            email: "me@example.com".to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn key_scopes(actions: &[&str]) -> Option<Extension<ApiKeyScopes>> {
+        Some(Extension(ApiKeyScopes {
+            namespaces: None,
+            actions: Some(actions.iter().map(|a| String::from(*a)).collect()),
+        }))
+    }
+
+    fn reveal(reveal: bool) -> QueryParams<SecretReadParams> {
+        QueryParams(SecretReadParams { reveal })
+    }
+
+    #[tokio::test]
+    async fn test_list_secrets_leaves_out_values() {
+        // Arrange
+        let state = state_with_secret().await;
+
+        // Act
+        let Json(listed) = list_secrets(
+            State(state),
+            Extension(user()),
+            key_scopes(&["secrets:read"]),
+            reveal(false),
+        )
+        .await
+        .unwrap();
+
+        // Assert
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed[0].metadata.name, "db-password");
+        assert_eq!(listed[0].metadata.namespace, "team-a");
+        assert_eq!(listed[0].version, Some(1));
+        assert_eq!(listed[0].value, None);
+        let body = serde_json::to_string(&listed).unwrap();
+        assert!(!body.contains("hunter2"));
+    }
+
+    #[tokio::test]
+    async fn test_reveal_requires_the_reveal_scope() {
+        let state = state_with_secret().await;
+        let path = || Path(("team-a".to_string(), "db-password".to_string()));
+
+        let Json(hidden) = get_secret(
+            State(state.clone()),
+            Extension(user()),
+            key_scopes(&["secrets:read"]),
+            path(),
+            reveal(false),
+        )
+        .await
+        .unwrap();
+        assert_eq!(hidden.value, None);
+
+        let (status, _) = get_secret(
+            State(state.clone()),
+            Extension(user()),
+            key_scopes(&["secrets:read"]),
+            path(),
+            reveal(true),
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(status, StatusCode::FORBIDDEN);
+
+        // Listings span namespaces, so a key limited to some can't reveal them
+        let limited = ApiKeyScopes {
+            namespaces: Some(vec!["team-a".to_string()]),
+            ..key_scopes(&[REVEAL_SECRETS]).unwrap().0
+        };
+        let (status, _) = list_secrets(
+            State(state.clone()),
+            Extension(user()),
+            Some(Extension(limited)),
+            reveal(true),
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(status, StatusCode::FORBIDDEN);
+
+        let Json(revealed) = get_secret(
+            State(state.clone()),
+            Extension(user()),
+            key_scopes(&[REVEAL_SECRETS]),
+            path(),
+            reveal(true),
+        )
+        .await
+        .unwrap();
+        assert_eq!(revealed.value.as_deref(), Some("hunter2"));
+
+        let Json(by_id) = get_secret_by_id(
+            State(state.clone()),
+            Extension(user()),
+            None,
+            Path("s1".to_string()),
+            reveal(true),
+        )
+        .await
+        .unwrap();
+        assert_eq!(by_id.value.as_deref(), Some("hunter2"));
+
+        let Json(listed) = list_secrets(
+            State(state),
+            Extension(user()),
+            key_scopes(&[REVEAL_SECRETS]),
+            reveal(true),
+        )
+        .await
+        .unwrap();
+        assert_eq!(listed[0].value.as_deref(), Some("hunter2"));
+    }
+
+    #[tokio::test]
+    async fn test_update_does_not_reveal_the_value() {
+        let state = state_with_secret().await;
+        let payload = V1SecretRequest {
+            metadata: V1ResourceMetaRequest {
+                name: Some("db-password".to_string()),
+                ..Default::default()
+            },
+            value: "hunter3".to_string(),
+            expires_at: None,
+        };
+
+        let Json(updated) = update_secret_by_id(
+            State(state.clone()),
+            Extension(user()),
+            Path("s1".to_string()),
+            Json(payload),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(updated.value, None);
+        let Json(revealed) = get_secret_by_id(
+            State(state),
+            Extension(user()),
+            None,
+            Path("s1".to_string()),
+            reveal(true),
+        )
+        .await
+        .unwrap();
+        assert_eq!(revealed.value.as_deref(), Some("hunter3"));
+    }
+}
@@ -17,10 +17,7 @@ use axum::{
     response::IntoResponse,
 };
 use chrono;
-use sea_orm::DbErr;
-use sea_orm::{
-    ActiveModelTrait, ActiveValue::Set, ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter,
-};
+use sea_orm::{ActiveModelTrait, ActiveValue::Set, ColumnTrait, EntityTrait, QueryFilter};
 use serde_json::json;
 use short_uuid;
 
@@ -32,14 +29,7 @@ pub async fn get_volume(
     let db_pool = &state.db_pool;
     let resolved_namespace = resolve_namespace(&namespace, &user_profile);
 
-    let mut owner_ids: Vec<String> = if let Some(orgs) = &user_profile.organizations {
-        orgs.keys().cloned().collect()
-    } else {
-        Vec::new()
-    };
-
-    // Include user's email (assuming owner_id is user's email)
-    owner_ids.push(user_profile.email.clone());
+    let owner_ids = user_profile.owner_ids();
     let owner_id_refs: Vec<&str> = owner_ids.iter().map(|s| s.as_str()).collect();
 
     let volume = Query::find_volume_by_namespace_name_and_owners(
@@ -67,44 +57,21 @@ pub async fn create_volume(
     let db_pool = &state.db_pool;
 
     // Get owner IDs from organizations and email
-    let mut owner_ids: Vec<String> = if let Some(orgs) = &user_profile.organizations {
-        orgs.keys().cloned().collect()
-    } else {
-        Vec::new()
-    };
-    owner_ids.push(user_profile.email.clone());
+    let owner_ids = user_profile.owner_ids();
     let owner_id_refs: Vec<&str> = owner_ids.iter().map(|s| s.as_str()).collect();
 
     let namespace_opt = volume.clone().metadata.namespace;
 
-    let handle = match user_profile.handle.clone() {
-        Some(handle) => handle,
-        None => user_profile
-            .email
-            .clone()
-            .replace("@", "-")
-            .replace(".", "-"),
-    };
-
     let namespace = match namespace_opt {
         Some(namespace) => namespace,
-        None => match crate::handlers::v1::namespaces::ensure_namespace(
-            db_pool,
-            &handle,
-            &user_profile.email,
-            &user_profile.email,
-            None,
-        )
-        .await
-        {
-            Ok(_) => handle,
-            Err(e) => {
-                return Err((
-                    StatusCode::BAD_REQUEST,
-                    Json(json!({ "error": format!("Invalid namespace: {}", e) })),
-                ));
-            }
-        },
+        None => {
+            crate::handlers::v1::namespaces::implicit_namespace(
+                db_pool,
+                &user_profile,
+                crate::config::SERVER_CONFIG.allow_implicit_namespaces,
+            )
+            .await?
+        }
     };
 
     let name = volume
@@ -183,12 +150,7 @@ pub async fn delete_volume(
     let resolved_namespace = resolve_namespace(&namespace, &user_profile);
 
     // Collect owner IDs
-    let mut owner_ids: Vec<String> = if let Some(orgs) = &user_profile.organizations {
-        orgs.keys().cloned().collect()
-    } else {
-        Vec::new()
-    };
-    owner_ids.push(user_profile.email.clone());
+    let owner_ids = user_profile.owner_ids();
     let owner_id_refs: Vec<&str> = owner_ids.iter().map(|s| s.as_str()).collect();
 
     // 1) Look up volume by namespace + name
@@ -220,72 +182,6 @@ pub async fn delete_volume(
     Ok(())
 }
 
-/// Internal helper function to ensure a volume exists with the given parameters.
-/// Returns the volume if it exists, or creates it if it doesn't.
-pub async fn ensure_volume(
-    db_pool: &DatabaseConnection,
-    namespace: &str,
-    name: &str,
-    owner: &str,
-    source: &str,
-    created_by: &str,
-    labels: Option<serde_json::Value>,
-) -> Result<volumes::Model, DbErr> {
-    // First, try to find the volume by namespace and name
-    let existing_volume = volumes::Entity::find()
-        .filter(volumes::Column::Namespace.eq(namespace))
-        .filter(volumes::Column::Name.eq(name))
-        .one(db_pool)
-        .await?;
-
-    // If the volume exists and has the same source, return it
-    if let Some(volume) = existing_volume {
-        if volume.source == source {
-            return Ok(volume);
-        } else {
-            return Err(DbErr::Custom(format!(
-                "Volume with name '{}' already exists in namespace '{}', but has a different source ('{}' vs '{}'). This is currently not supported.",
-                name, namespace, volume.source, source
-            )));
-        }
-    }
-
-    // If we get here, either the volume doesn't exist or has a different source
-    // Generate a unique ID for the new volume
-    let id = short_uuid::ShortUuid::generate().to_string();
-
-    // Create the volume entity
-    let volume_entity = volumes::Model::new(
-        id,
-        name.to_string(),
-        namespace.to_string(),
-        owner.to_string(),
-        created_by.to_string(),
-        labels,
-        source.to_string(),
-    )
-    .map_err(|e| DbErr::Custom(format!("Failed to create volume: {}", e)))?;
-
-    // Insert the volume into the database
-    let volume_entity = VolumeActiveModel {
-        id: Set(volume_entity.id),
-        name: Set(volume_entity.name),
-        namespace: Set(volume_entity.namespace),
-        full_name: Set(volume_entity.full_name),
-        owner: Set(volume_entity.owner),
-        owner_ref: Set(volume_entity.owner_ref),
-        source: Set(volume_entity.source),
-        labels: Set(volume_entity.labels),
-        created_by: Set(volume_entity.created_by),
-        updated_at: Set(volume_entity.updated_at),
-        created_at: Set(volume_entity.created_at),
-    };
-
-    let volume_entity = volume_entity.insert(db_pool).await?;
-
-    Ok(volume_entity)
-}
-
 /// Handler: List volumes for the current user (and their organizations)
 pub async fn list_volumes(
     State(state): State<AppState>,
@@ -294,12 +190,7 @@ pub async fn list_volumes(
     let db_pool = &state.db_pool;
 
     // Gather all possible owner IDs from user + organizations
-    let mut owner_ids: Vec<String> = if let Some(orgs) = &user_profile.organizations {
-        orgs.keys().cloned().collect()
-    } else {
-        Vec::new()
-    };
-    owner_ids.push(user_profile.email.clone());
+    let owner_ids = user_profile.owner_ids();
     let owner_id_refs: Vec<&str> = owner_ids.iter().map(|s| s.as_str()).collect();
 
     // Retrieve volumes
@@ -332,12 +223,7 @@ pub async fn update_volume(
     let db_pool = &state.db_pool;
     let resolved_namespace = resolve_namespace(&namespace, &user_profile);
 
-    let mut owner_ids: Vec<String> = if let Some(orgs) = &user_profile.organizations {
-        orgs.keys().cloned().collect()
-    } else {
-        Vec::new()
-    };
-    owner_ids.push(user_profile.email.clone());
+    let owner_ids = user_profile.owner_ids();
     let owner_id_refs: Vec<&str> = owner_ids.iter().map(|s| s.as_str()).collect();
 
     let volume = Query::find_volume_by_namespace_name_and_owners(
@@ -6,11 +6,13 @@ pub mod auth;
 pub mod cli;
 pub mod client;
 pub mod config;
+pub mod cors;
 pub mod db;
 pub mod dns;
 pub mod entities;
 pub mod errors;
 pub mod handlers;
+pub mod logging;
 pub mod middleware;
 pub mod models;
 pub mod mutation;
@@ -20,19 +22,21 @@ pub mod org;
 pub mod orign;
 pub mod proxy;
 pub mod query;
+pub mod rate_limit;
 pub mod resources;
 pub mod routes;
 pub mod select;
 pub mod ssh;
 pub mod state;
 pub mod streams;
+pub mod tls;
 pub mod utils;
 pub mod validate;
 pub mod volumes;
 
-use crate::config::SERVER_CONFIG;
+use crate::config::{KafkaConfig, RedisConfig, SERVER_CONFIG};
 use crate::handlers::v1::namespaces::ensure_namespace;
-use crate::handlers::v1::volumes::ensure_volume;
+use crate::resources::v1::volumes::base::{check_base_volumes, ensure_base_volumes};
 use axum::Router;
 use db::init_db;
 use rdkafka::admin::AdminClient;
@@ -42,79 +46,76 @@ use routes::create_routes;
 use sea_orm::DatabaseConnection;
 use state::AppState;
 use state::MessageQueue;
-use std::env;
 use std::sync::Arc;
-use tower_http::cors::{Any, CorsLayer};
 use tower_http::trace::TraceLayer;
-use url::Url;
 
-/// Create and return the application state.
+/// Create and return the application state. Every entry point running the
+/// server or its controllers starts here, so the settings are checked before
+/// connecting to anything.
 pub async fn create_app_state() -> Result<AppState, Box<dyn std::error::Error>> {
+    SERVER_CONFIG.validate()?;
     println!("Creating app state");
     let db_pool = init_db().await?;
     println!("Database pool created");
 
-    // Initialize the appropriate message queue based on configuration
-    let message_queue = match SERVER_CONFIG.message_queue_type.to_lowercase().as_str() {
-        "redis" => {
-            let redis_url = SERVER_CONFIG.redis.get_url();
-
-            if let Ok(parsed_url) = Url::parse(redis_url.as_str()) {
-
-                if let Some(host) = parsed_url.host_str() {
-                    env::set_var("REDIS_HOST", host);
-                }
+    let message_queue = create_message_queue(
+        &SERVER_CONFIG.message_queue_type,
+        &SERVER_CONFIG.redis,
+        &SERVER_CONFIG.kafka,
+    )?;
+    let mut extra_queues = std::collections::HashMap::new();
+    for backend in &SERVER_CONFIG.message_queue_backends {
+        let queue = create_message_queue(backend, &SERVER_CONFIG.redis, &SERVER_CONFIG.kafka)?;
+        extra_queues.insert(backend.clone(), queue);
+    }
 
-                if let Some(port) = parsed_url.port() {
-                    env::set_var("REDIS_PORT", port.to_string());
-                }
+    ensure_base_resources(&db_pool).await?;
 
-                if let Some(password) = parsed_url.password() {
-                    env::set_var("REDIS_PASSWORD", password);
-                }
+    let app_state = AppState {
+        db_pool,
+        message_queue,
+        extra_queues,
+    };
 
-            };
-            let redis_client = Arc::new(redis::Client::open(redis_url.as_str())?);
+    Ok(app_state)
+}
 
-            MessageQueue::Redis {
+/// Create the message queue for `queue_type`. Connection details stay in the
+/// clients; nothing is written back to the process environment, which child
+/// processes would otherwise inherit.
+pub fn create_message_queue(
+    queue_type: &str,
+    redis: &RedisConfig,
+    kafka: &KafkaConfig,
+) -> Result<MessageQueue, Box<dyn std::error::Error>> {
+    match queue_type.to_lowercase().as_str() {
+        "redis" => {
+            let redis_client = Arc::new(redis::Client::open(redis.get_url().as_str())?);
+            Ok(MessageQueue::Redis {
                 client: redis_client,
-            }
+            })
         }
         "kafka" => {
             let mut kafka_client_config = KafkaClientConfig::new();
             let kafka_config = kafka_client_config
-                .set("bootstrap.servers", &SERVER_CONFIG.kafka.bootstrap_servers)
-                .set("message.timeout.ms", &SERVER_CONFIG.kafka.timeout_ms.to_string());
+                .set("bootstrap.servers", &kafka.bootstrap_servers)
+                .set("message.timeout.ms", &kafka.timeout_ms.to_string());
 
             let producer = Arc::new(kafka_config.clone().create::<FutureProducer>()?);
             let admin = Arc::new(kafka_config.create::<AdminClient<_>>()?);
 
-            MessageQueue::Kafka { producer, admin }
+            Ok(MessageQueue::Kafka { producer, admin })
         }
-        unsupported => {
-            return Err(format!("Unsupported message queue type: {}", unsupported).into())
-        }
-    };
-
-    ensure_base_resources(&db_pool).await?;
-
-    let app_state = AppState {
-        db_pool,
-        message_queue,
-    };
-
-    Ok(app_state)
+        unsupported => Err(format!("Unsupported message queue type: {}", unsupported).into()),
+    }
 }
 
 /// Given the `AppState`, create and return the Axum `Router`.
 pub async fn create_app(app_state: AppState) -> Router {
     let routes = create_routes(app_state.clone());
 
-    // Define a CORS layer (this example allows any origin, headers, and methods)
-    let cors = CorsLayer::new()
-        .allow_origin(Any)
-        .allow_methods(Any)
-        .allow_headers(Any);
+    let cors = cors::cors_layer(&SERVER_CONFIG.cors)
+        .unwrap_or_else(|e| panic!("Invalid CORS configuration: {}", e));
 
     let app = routes
         .layer(TraceLayer::new_for_http())
@@ -127,33 +128,77 @@ pub async fn create_app(app_state: AppState) -> Router {
 pub async fn ensure_base_resources(
     db_pool: &DatabaseConnection,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    match ensure_namespace(
+    ensure_namespace(
         db_pool,
         "root",
         &SERVER_CONFIG.root_owner,
         &SERVER_CONFIG.root_owner,
         None,
     )
-    .await
-    {
-        Ok(_) => (),
-        Err(e) => return Err(Box::new(e)),
-    }
+    .await?;
 
-    match ensure_volume(
+    ensure_base_volumes(
         db_pool,
-        "root",
-        "root",
+        &SERVER_CONFIG.base_volumes,
         &SERVER_CONFIG.root_owner,
-        format!("s3://{}", &SERVER_CONFIG.bucket_name).as_str(),
-        "root",
-        None,
     )
-    .await
-    {
-        Ok(_) => (),
-        Err(e) => return Err(Box::new(e)),
-    }
+    .await?;
+
+    check_base_volumes(&SERVER_CONFIG.base_volumes).await;
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_create_message_queue_leaves_env_untouched() {
+        // Arrange
+        let redis = RedisConfig {
+            host: "redis.internal".to_string(),
+            port: 6380,
+            user: Some("nebu".to_string()),
+            password: Some("hunter2".to_string()),
+            database: 0,
+            publish_url: None,
+        };
+        let kafka = KafkaConfig {
+            bootstrap_servers: "localhost:9092".to_string(),
+            timeout_ms: 5000,
+            topic_partitions: 1,
+            topic_replication: 1,
+        };
+        // Only the variables it used to set; other tests may change the rest
+        let redis_vars =
+            || ["REDIS_HOST", "REDIS_PORT", "REDIS_PASSWORD"].map(|key| std::env::var(key).ok());
+        let before = redis_vars();
+
+        // Act
+        let queue = create_message_queue("redis", &redis, &kafka).unwrap();
+
+        // Assert
+        assert!(matches!(queue, MessageQueue::Redis { .. }));
+        assert_eq!(redis_vars(), before);
+    }
+
+    #[test]
+    fn test_create_message_queue_rejects_unknown_type() {
+        let kafka = KafkaConfig {
+            bootstrap_servers: "localhost:9092".to_string(),
+            timeout_ms: 5000,
+            topic_partitions: 1,
+            topic_replication: 1,
+        };
+        let redis = RedisConfig {
+            host: "localhost".to_string(),
+            port: 6379,
+            user: None,
+            password: None,
+            database: 0,
+            publish_url: None,
+        };
+        assert!(create_message_queue("nats", &redis, &kafka).is_err());
+    }
+}
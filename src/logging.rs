@@ -0,0 +1,299 @@
+// src/logging.rs
+//
+// Tracing subscriber setup. `LOG_FORMAT=json` switches to one JSON object per
+// line, with the fields of the enclosing request and container spans
+// flattened in, so logs can be shipped to Loki or ELK as they are.
+//
+// Every request gets an `X-Request-Id`, taken from the caller or generated.
+// Handlers stamp it into the `controller_data` of containers they change, and
+// the reconcile task for a container runs in a span carrying it, so the logs
+// from handler to mutation to RunPod watch share one id.
+
+use crate::auth::scopes::required_scope;
+use crate::entities::containers;
+use axum::body::Body;
+use axum::extract::MatchedPath;
+use axum::middleware::Next;
+use axum::response::Response;
+use http::{HeaderValue, Request};
+use std::io;
+use std::str::FromStr;
+use tracing::{field, Span, Subscriber};
+use tracing_subscriber::fmt::MakeWriter;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::EnvFilter;
+
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// Longest caller supplied request id we pass through; longer ones are replaced
+const MAX_REQUEST_ID_LEN: usize = 128;
+
+tokio::task_local! {
+    pub(crate) static REQUEST_ID: String;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LogFormat {
+    Text,
+    Json,
+}
+
+impl FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "" | "text" => Ok(LogFormat::Text),
+            "json" => Ok(LogFormat::Json),
+            other => Err(format!(
+                "Unknown log format '{}', expected 'text' or 'json'",
+                other
+            )),
+        }
+    }
+}
+
+impl LogFormat {
+    /// Reads `LOG_FORMAT`, defaulting to human readable text.
+    pub fn from_env() -> Self {
+        std::env::var("LOG_FORMAT")
+            .map(|v| {
+                v.parse()
+                    .expect("Invalid value for LOG_FORMAT, e.g. 'json'")
+            })
+            .unwrap_or(LogFormat::Text)
+    }
+}
+
+/// Builds the subscriber for `format`, filtered by `RUST_LOG` (default `info`).
+pub fn build_subscriber<W>(format: LogFormat, writer: W) -> Box<dyn Subscriber + Send + Sync>
+where
+    W: for<'a> MakeWriter<'a> + Send + Sync + 'static,
+{
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
+    let builder = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(writer);
+    match format {
+        LogFormat::Text => Box::new(builder.finish()),
+        LogFormat::Json => Box::new(
+            builder
+                .json()
+                .flatten_event(true)
+                .with_current_span(true)
+                .with_span_list(false)
+                .finish(),
+        ),
+    }
+}
+
+/// Installs the global subscriber for the format chosen by `LOG_FORMAT`.
+pub fn init() {
+    build_subscriber(LogFormat::from_env(), io::stdout).init();
+}
+
+/// The id of the request the current task is serving, if any.
+pub fn current_request_id() -> Option<String> {
+    REQUEST_ID.try_with(|id| id.clone()).ok()
+}
+
+/// Keeps the caller's `X-Request-Id` if it is a sane header value, otherwise
+/// generates one. The id is visible to the rest of the request through
+/// `current_request_id` and echoed back on the response.
+pub async fn request_id_middleware(mut request: Request<Body>, next: Next) -> Response {
+    let request_id = request
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .filter(|id| !id.is_empty() && id.len() <= MAX_REQUEST_ID_LEN)
+        .map(|id| id.to_string())
+        .unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+    // Checked above to be a valid header value, or a generated uuid
+    let header = HeaderValue::from_str(&request_id).expect("request id is a valid header value");
+    request
+        .headers_mut()
+        .insert(REQUEST_ID_HEADER, header.clone());
+
+    let mut response = REQUEST_ID.scope(request_id, next.run(request)).await;
+    response.headers_mut().insert(REQUEST_ID_HEADER, header);
+    response
+}
+
+/// The span every HTTP request runs in. `owner` is filled in by the auth
+/// middleware once the caller is known.
+pub fn request_span(request: &Request<Body>) -> Span {
+    let request_id = request
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("");
+    let namespace = request.extensions().get::<MatchedPath>().and_then(|route| {
+        required_scope(request.method(), route.as_str(), request.uri().path()).namespace
+    });
+
+    let span = tracing::info_span!(
+        "request",
+        method = %request.method(),
+        uri = %request.uri(),
+        request_id = %request_id,
+        namespace = field::Empty,
+        owner = field::Empty,
+    );
+    if let Some(namespace) = namespace {
+        span.record("namespace", namespace.as_str());
+    }
+    span
+}
+
+/// The span a container's reconcile task runs in, so every controller log
+/// line for it carries the same identifying fields, including the id of the
+/// last request that changed it.
+pub fn container_span(container: &containers::Model) -> Span {
+    let request_id = container
+        .typed_controller_data()
+        .ok()
+        .and_then(|data| data.request_id)
+        .unwrap_or_default();
+    tracing::info_span!(
+        "container",
+        container_id = %container.id,
+        namespace = %container.namespace,
+        owner = %container.owner,
+        request_id = %request_id,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    /// Collects everything the subscriber writes
+    #[derive(Clone, Default)]
+    struct Captured(Arc<Mutex<Vec<u8>>>);
+
+    impl io::Write for Captured {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Captured {
+        fn output(&self) -> String {
+            String::from_utf8(self.0.lock().unwrap().clone()).unwrap()
+        }
+    }
+
+    fn log_with(format: LogFormat) -> String {
+        let captured = Captured::default();
+        let writer = captured.clone();
+        let subscriber = build_subscriber(format, move || writer.clone());
+        tracing::subscriber::with_default(subscriber, || {
+            let span = tracing::info_span!("container", container_id = "c1", namespace = "ns");
+            let _entered = span.enter();
+            tracing::info!(owner = "me", "Reconciling");
+        });
+        captured.output()
+    }
+
+    #[test]
+    fn test_json_subscriber_emits_json_lines() {
+        // Arrange
+        let format: LogFormat = "JSON".parse().unwrap();
+
+        // Act
+        let output = log_with(format);
+
+        // Assert
+        let line: serde_json::Value = serde_json::from_str(output.trim()).unwrap();
+        assert_eq!(line["message"], "Reconciling");
+        assert_eq!(line["owner"], "me");
+        assert_eq!(line["span"]["container_id"], "c1");
+        assert_eq!(line["span"]["namespace"], "ns");
+    }
+
+    #[test]
+    fn test_text_subscriber_emits_plain_lines() {
+        let output = log_with(LogFormat::Text);
+        assert!(output.contains("Reconciling"));
+        assert!(output.contains("container_id"));
+        assert!(serde_json::from_str::<serde_json::Value>(output.trim()).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_request_id_round_trips_and_tags_spans() {
+        // Arrange
+        let captured = Captured::default();
+        let writer = captured.clone();
+        let _default =
+            tracing::subscriber::set_default(build_subscriber(LogFormat::Json, move || {
+                writer.clone()
+            }));
+        let app = axum::Router::new()
+            .route(
+                "/",
+                axum::routing::get(|| async {
+                    tracing::info!("Handling request");
+                    current_request_id().unwrap_or_default()
+                }),
+            )
+            .layer(tower_http::trace::TraceLayer::new_for_http().make_span_with(request_span))
+            .layer(axum::middleware::from_fn(request_id_middleware));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        // Act
+        let response = reqwest::Client::new()
+            .get(&url)
+            .header("X-Request-Id", "req-abc-123")
+            .send()
+            .await
+            .unwrap();
+
+        // Assert
+        assert_eq!(response.headers()[REQUEST_ID_HEADER], "req-abc-123");
+        assert_eq!(response.text().await.unwrap(), "req-abc-123");
+        let logged = captured
+            .output()
+            .lines()
+            .filter_map(|line| serde_json::from_str::<serde_json::Value>(line).ok())
+            .find(|line| line["message"] == "Handling request")
+            .expect("handler log line");
+        assert_eq!(logged["span"]["request_id"], "req-abc-123");
+    }
+
+    #[tokio::test]
+    async fn test_request_id_is_generated_when_missing() {
+        let app = axum::Router::new()
+            .route(
+                "/",
+                axum::routing::get(|| async { current_request_id().unwrap_or_default() }),
+            )
+            .layer(axum::middleware::from_fn(request_id_middleware));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        let response = reqwest::get(&url).await.unwrap();
+        let header = response.headers()[REQUEST_ID_HEADER]
+            .to_str()
+            .unwrap()
+            .to_string();
+        assert!(uuid::Uuid::parse_str(&header).is_ok());
+        assert_eq!(response.text().await.unwrap(), header);
+        assert_eq!(current_request_id(), None);
+    }
+
+    #[test]
+    fn test_parse_log_format() {
+        assert_eq!("".parse::<LogFormat>(), Ok(LogFormat::Text));
+        assert_eq!("text".parse::<LogFormat>(), Ok(LogFormat::Text));
+        assert!("yaml".parse::<LogFormat>().is_err());
+    }
+}
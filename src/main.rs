@@ -5,12 +5,12 @@ use std::path::Path;
 
 use crate::cli::{
     ApiKeyActions, AuthCommands, Cli, Commands, CreateCommands, DeleteCommands, GetCommands,
-    ProxyCommands, SelectCommands, SendCommands, SetCommands, ShowCommands, SyncCommands,
+    ProxyCommands, ReadCommands, SelectCommands, SendCommands, SetCommands, ShowCommands,
+    SyncCommands,
 };
 use clap::Parser;
 use nebulous::select::checkpoint::select_checkpoint;
 use std::error::Error;
-use tracing_subscriber;
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn Error>> {
@@ -18,8 +18,8 @@ async fn main() -> Result<(), Box<dyn Error>> {
     if std::env::var("RUST_LOG").is_err() {
         std::env::set_var("RUST_LOG", "info");
     }
-    // Initialize tracing
-    tracing_subscriber::fmt::init();
+    // Initialize tracing, as JSON when LOG_FORMAT=json
+    nebulous::logging::init();
 
     // Parse command-line arguments
     let cli = Cli::parse();
@@ -30,8 +30,9 @@ async fn main() -> Result<(), Box<dyn Error>> {
             port,
             internal_auth,
             auth_port,
+            auth_host,
         } => {
-            commands::serve_cmd::execute(host, port, internal_auth, auth_port).await?;
+            commands::serve_cmd::execute(host, port, internal_auth, auth_port, auth_host).await?;
         }
         Commands::Sync { command } => match command {
             SyncCommands::Volumes {
@@ -42,6 +43,7 @@ async fn main() -> Result<(), Box<dyn Error>> {
                 background,
                 block_once,
                 config_from_env,
+                verify,
             } => {
                 commands::sync_cmd::execute_sync(
                     config,
@@ -51,14 +53,23 @@ async fn main() -> Result<(), Box<dyn Error>> {
                     background,
                     block_once,
                     config_from_env,
+                    verify,
                 )
                 .await?;
             }
             SyncCommands::Wait {
                 config,
                 interval_seconds,
+                timeout_seconds,
+                proceed_on_timeout,
             } => {
-                commands::sync_cmd::execute_wait(&config, interval_seconds).await?;
+                commands::sync_cmd::execute_wait(
+                    &config,
+                    interval_seconds,
+                    timeout_seconds,
+                    proceed_on_timeout,
+                )
+                .await?;
             }
         },
         Commands::Create { command } => match command {
@@ -119,7 +130,7 @@ async fn main() -> Result<(), Box<dyn Error>> {
                     Ok(Some(checkpoint)) => println!("{}", checkpoint.to_str().unwrap_or("")),
                     Ok(None) => println!("No checkpoint found"),
                     Err(e) => {
-                        eprintln!("Error selecting checkpoint: {:?}", e);
+                        eprintln!("Error selecting checkpoint: {}", e);
                     }
                 }
             }
@@ -129,12 +140,22 @@ async fn main() -> Result<(), Box<dyn Error>> {
                 commands::send_cmd::send_messages(&command).await?;
             }
         },
+        Commands::Read { command } => match command {
+            ReadCommands::Processors { command } => {
+                commands::read_cmd::read_processor(&command).await?;
+            }
+        },
         Commands::Daemon {
             host,
             port,
             background,
+            once,
         } => {
-            commands::daemon_cmd::execute_daemon(&host, port, background).await?;
+            if once {
+                commands::daemon_cmd::execute_once().await?;
+            } else {
+                commands::daemon_cmd::execute_daemon(&host, port, background).await?;
+            }
         }
         Commands::Logs {
             name,
@@ -143,8 +164,16 @@ async fn main() -> Result<(), Box<dyn Error>> {
         } => {
             commands::log_cmd::fetch_container_logs(name, namespace, follow).await?;
         }
-        Commands::Login { url, auth, hub } => {
-            commands::login_cmd::execute(url, auth, hub).await?;
+        Commands::Login {
+            url,
+            auth,
+            hub,
+            profile,
+        } => {
+            commands::login_cmd::execute(url, auth, hub, profile).await?;
+        }
+        Commands::Logout { profile } => {
+            commands::login_cmd::logout(profile)?;
         }
         Commands::Exec(args) => {
             commands::exec_cmd::exec_cmd(args).await?;
@@ -157,8 +186,13 @@ async fn main() -> Result<(), Box<dyn Error>> {
                 ApiKeyActions::Get { id } => {
                     commands::auth_cmd::get_api_key(&id).await?;
                 }
-                ApiKeyActions::Generate => {
-                    commands::auth_cmd::generate_api_key().await?;
+                ApiKeyActions::Generate {
+                    ttl,
+                    namespaces,
+                    actions,
+                } => {
+                    commands::auth_cmd::generate_api_key(ttl.as_deref(), namespaces, actions)
+                        .await?;
                 }
                 ApiKeyActions::Revoke { id } => {
                     commands::auth_cmd::revoke_api_key(&id).await?;
@@ -1,9 +1,10 @@
 use crate::auth;
+use crate::auth::scopes::{required_scope, ApiKeyScopes, RequiredScope};
 use crate::config::{ClientConfig, ServerConfig, SERVER_CONFIG};
 use crate::models::V1UserProfile;
 use crate::AppState;
 use axum::{
-    extract::{Request, State},
+    extract::{MatchedPath, Request, State},
     http::StatusCode,
     middleware::Next,
     response::{IntoResponse, Response},
@@ -35,10 +36,25 @@ pub async fn auth_middleware(
             println!("Bearer token is empty");
             unauthorized_response()
         } else {
-            match get_user_profile_from_token(db_pool, token).await {
-                Ok(user_profile) => {
+            match authenticate_token(db_pool, token).await {
+                Ok((user_profile, scopes)) => {
+                    if !scopes.is_unrestricted() {
+                        let route = request
+                            .extensions()
+                            .get::<MatchedPath>()
+                            .map(|path| path.as_str().to_string())
+                            .unwrap_or_else(|| request.uri().path().to_string());
+                        let required =
+                            required_scope(request.method(), &route, request.uri().path());
+                        if !scopes.allows(&required) {
+                            debug!("API key scopes {:?} don't cover {:?}", scopes, required);
+                            return forbidden_response(&required);
+                        }
+                    }
+                    tracing::Span::current().record("owner", user_profile.email.as_str());
                     let mut req = request;
                     req.extensions_mut().insert(user_profile);
+                    req.extensions_mut().insert(scopes);
                     next.run(req).await
                 }
                 Err(_) => unauthorized_response(),
@@ -81,10 +97,23 @@ pub async fn get_user_profile_from_token(
     db_conn: &DatabaseConnection,
     token: &str,
 ) -> Result<V1UserProfile, StatusCode> {
+    authenticate_token(db_conn, token)
+        .await
+        .map(|(user_profile, _)| user_profile)
+}
+
+/// Get a user profile and the scopes limiting the token. Only internal API
+/// keys carry scopes, external tokens are unrestricted.
+pub async fn authenticate_token(
+    db_conn: &DatabaseConnection,
+    token: &str,
+) -> Result<(V1UserProfile, ApiKeyScopes), StatusCode> {
     if token.starts_with("nebu-") {
-        get_user_profile_from_internal_token(db_conn, token).await
+        authenticate_internal_token(db_conn, token).await
     } else {
-        get_user_profile_from_external_token(token).await
+        get_user_profile_from_external_token(token)
+            .await
+            .map(|user_profile| (user_profile, ApiKeyScopes::default()))
     }
 }
 
@@ -93,11 +122,20 @@ pub async fn get_user_profile_from_internal_token(
     db_conn: &DatabaseConnection,
     token: &str,
 ) -> Result<V1UserProfile, StatusCode> {
+    authenticate_internal_token(db_conn, token)
+        .await
+        .map(|(user_profile, _)| user_profile)
+}
+
+async fn authenticate_internal_token(
+    db_conn: &DatabaseConnection,
+    token: &str,
+) -> Result<(V1UserProfile, ApiKeyScopes), StatusCode> {
     debug!("Validating internal token: {}", token);
-    let is_valid = auth::api::validate_api_key(db_conn, token).await;
-    match is_valid {
-        Ok(is_valid) => {
-            if is_valid {
+    let scopes = auth::api::authenticate_api_key(db_conn, token).await;
+    match scopes {
+        Ok(scopes) => {
+            if let Some(scopes) = scopes {
                 println!("✅ Internal token is valid");
 
                 let user_profile = V1UserProfile {
@@ -114,7 +152,7 @@ pub async fn get_user_profile_from_internal_token(
                     updated: None,
                     token: None,
                 };
-                Ok(user_profile)
+                Ok((user_profile, scopes))
             } else {
                 println!("❌ Internal token is invalid");
                 Err(StatusCode::UNAUTHORIZED)
@@ -147,7 +185,7 @@ pub async fn get_user_profile_from_external_token(
     println!("🔐 Making auth request to: {}", auth_url);
 
     // Validate the token with auth server
-    let client = reqwest::Client::new();
+    let client = crate::utils::http::shared_client();
     let auth_header = format!("Bearer {}", token);
     let user_profile_result = client
         .get(auth_url)
@@ -180,6 +218,28 @@ pub async fn get_user_profile_from_external_token(
     }
 }
 
+fn forbidden_response(required: &RequiredScope) -> Response {
+    let message = match &required.namespace {
+        Some(namespace) => format!(
+            "API key scopes don't allow '{}' in namespace '{}'",
+            required.action, namespace
+        ),
+        None => format!(
+            "API key scopes don't allow '{}' on this route",
+            required.action
+        ),
+    };
+    let error_response = json!({
+        "error": {
+            "message": message,
+            "type": "permission_error",
+            "param": null,
+            "code": null
+        }
+    });
+    (StatusCode::FORBIDDEN, Json(error_response)).into_response()
+}
+
 fn unauthorized_response() -> Response {
     let error_response = json!({
         "error": {
@@ -191,3 +251,124 @@ fn unauthorized_response() -> Response {
     });
     (StatusCode::UNAUTHORIZED, Json(error_response)).into_response()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::MessageQueue;
+    use axum::routing::get;
+    use axum::Router;
+    use sea_orm::{ConnectionTrait, Database, Schema};
+    use std::sync::Arc;
+
+    /// Serves a couple of container routes behind `auth_middleware` and
+    /// returns the base URL with a key limited to `scopes`.
+    async fn serve_with_key(scopes: ApiKeyScopes) -> (String, String) {
+        let db = Database::connect("sqlite::memory:").await.unwrap();
+        let schema = Schema::new(db.get_database_backend());
+        db.execute(
+            db.get_database_backend()
+                .build(&schema.create_table_from_entity(auth::db::Entity)),
+        )
+        .await
+        .unwrap();
+        let key = auth::api::generate_api_key(&db, None, Some(scopes))
+            .await
+            .unwrap();
+
+        let state = AppState {
+            db_pool: db,
+            message_queue: MessageQueue::Redis {
+                client: Arc::new(redis::Client::open("redis://127.0.0.1").unwrap()),
+            },
+            extra_queues: Default::default(),
+        };
+        let app = Router::new()
+            .route(
+                "/v1/containers/:namespace/:name",
+                get(|| async { "ok" }).delete(|| async { "ok" }),
+            )
+            .route("/v1/containers", get(|| async { "ok" }))
+            .layer(axum::middleware::from_fn_with_state(
+                state.clone(),
+                auth_middleware,
+            ))
+            .with_state(state);
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        (format!("http://{}", addr), key)
+    }
+
+    async fn status(method: reqwest::Method, url: String, key: &str) -> u16 {
+        reqwest::Client::new()
+            .request(method, url)
+            .bearer_auth(key)
+            .send()
+            .await
+            .unwrap()
+            .status()
+            .as_u16()
+    }
+
+    #[tokio::test]
+    async fn test_scoped_key_allowed_and_denied() {
+        // Arrange
+        let (base, key) = serve_with_key(ApiKeyScopes {
+            namespaces: Some(vec!["team-a".to_string()]),
+            actions: Some(vec!["containers:read".to_string()]),
+        })
+        .await;
+        let get = reqwest::Method::GET;
+
+        // Act
+        let allowed = status(
+            get.clone(),
+            format!("{}/v1/containers/team-a/web", base),
+            &key,
+        )
+        .await;
+        let wrong_action = status(
+            reqwest::Method::DELETE,
+            format!("{}/v1/containers/team-a/web", base),
+            &key,
+        )
+        .await;
+        let wrong_namespace = status(
+            get.clone(),
+            format!("{}/v1/containers/team-b/web", base),
+            &key,
+        )
+        .await;
+        let no_namespace = status(get, format!("{}/v1/containers", base), &key).await;
+
+        // Assert
+        assert_eq!(allowed, 200);
+        assert_eq!(wrong_action, 403);
+        assert_eq!(wrong_namespace, 403);
+        assert_eq!(no_namespace, 403);
+    }
+
+    #[tokio::test]
+    async fn test_unscoped_key_keeps_full_access() {
+        let (base, key) = serve_with_key(ApiKeyScopes::default()).await;
+
+        for (method, path) in [
+            (reqwest::Method::GET, "/v1/containers/team-b/web"),
+            (reqwest::Method::DELETE, "/v1/containers/team-b/web"),
+            (reqwest::Method::GET, "/v1/containers"),
+        ] {
+            assert_eq!(status(method, format!("{}{}", base, path), &key).await, 200);
+        }
+        assert_eq!(
+            status(
+                reqwest::Method::GET,
+                format!("{}/v1/containers", base),
+                "nebu-bad.key"
+            )
+            .await,
+            401
+        );
+    }
+}
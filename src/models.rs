@@ -25,6 +25,14 @@ pub struct V1ResourceMetaRequest {
     pub owner_ref: Option<String>,
 }
 
+/// Query parameters accepted by the list/get resource endpoints
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct V1ListParams {
+    /// Include soft-deleted resources (admins only)
+    #[serde(default)]
+    pub include_deleted: bool,
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
 pub struct V1UserProfile {
     pub email: String,
@@ -42,6 +50,20 @@ pub struct V1UserProfile {
     pub token: Option<String>,
 }
 
+impl V1UserProfile {
+    /// Everyone whose resources this user may act on: each organization
+    /// they belong to, and the user themselves by email.
+    pub fn owner_ids(&self) -> Vec<String> {
+        let mut owner_ids: Vec<String> = self
+            .organizations
+            .as_ref()
+            .map(|orgs| orgs.keys().cloned().collect())
+            .unwrap_or_default();
+        owner_ids.push(self.email.clone());
+        owner_ids
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
 pub struct V1CreateAgentKeyRequest {
     pub agent_id: String,
@@ -151,6 +173,13 @@ pub struct V1Meter {
     pub unit: String,
     pub metric: String,
     pub json_path: Option<String>,
+    /// Extra fields merged into each event's data, e.g. project or model
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dimensions: Option<HashMap<String, String>>,
+    /// Event subject, defaults to the owner (or container for request meters).
+    /// Only admins can set it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub subject: Option<String>,
 }
 
 //
@@ -164,6 +193,14 @@ pub struct V1StreamData {
     pub wait: Option<bool>,
     pub stream: Option<bool>,
     pub user_key: Option<String>,
+    /// Seconds the agent key minted for the message stays valid, within the
+    /// server's bounds. Defaults to `NEBU_AGENT_KEY_DURATION`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub agent_key_duration: Option<u64>,
+    /// Milliseconds to wait for the response when `wait` is set, at most the
+    /// server's `NEBU_SEND_WAIT_MAX_TIMEOUT`, which is also the default
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout_ms: Option<u64>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -180,6 +217,9 @@ pub struct V1StreamMessage {
     pub handle: Option<String>,
     pub adapter: Option<String>,
     pub api_key: Option<String>,
+    /// Id of the Redis stream entry, set on stream reads for acknowledging
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub entry_id: Option<String>,
 }
 
 fn kind_v1_stream_message() -> String {
@@ -234,3 +274,43 @@ pub struct V1OpenAIStreamResponse {
 fn kind_v1_openai_stream_response() -> String {
     "OpenAIStreamResponse".to_string()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_owner_ids_include_organizations_and_email() {
+        // Arrange
+        let profile = V1UserProfile {
+            email: "ada@example.com".to_string(),
+            organizations: Some(HashMap::from([
+                ("org-a".to_string(), HashMap::new()),
+                ("org-b".to_string(), HashMap::new()),
+            ])),
+            ..Default::default()
+        };
+
+        // Act
+        let mut owner_ids = profile.owner_ids();
+
+        // Assert
+        owner_ids.sort();
+        assert_eq!(owner_ids, vec!["ada@example.com", "org-a", "org-b"]);
+    }
+
+    #[test]
+    fn test_owner_ids_without_organizations() {
+        let profile = V1UserProfile {
+            email: "ada@example.com".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(profile.owner_ids(), vec!["ada@example.com"]);
+
+        let empty_orgs = V1UserProfile {
+            organizations: Some(HashMap::new()),
+            ..profile
+        };
+        assert_eq!(empty_orgs.owner_ids(), vec!["ada@example.com"]);
+    }
+}
@@ -1,11 +1,21 @@
+use crate::entities::container_events;
 use crate::entities::containers;
+use crate::entities::idempotency_keys::{self, IdempotencyClaim};
 use crate::entities::processors;
 use crate::entities::secrets;
-use crate::resources::v1::containers::models::{V1Port, V1UpdateContainer};
+use crate::resources::v1::containers::base::cost_for;
+use crate::resources::v1::containers::models::{
+    ControllerData, V1Container, V1FailureReason, V1Port, V1ResourceUsage, V1SyncProgress,
+    V1UpdateContainer,
+};
+use crate::resources::v1::containers::usage;
+use crate::resources::v1::containers::webhooks;
 use crate::resources::v1::processors::models::V1ProcessorStatus;
+use sea_orm::sea_query::Expr;
 use sea_orm::*;
 use serde_json::json;
 use short_uuid::ShortUuid;
+use std::collections::HashMap;
 use tracing::{debug, error, info};
 
 pub struct Mutation;
@@ -18,7 +28,8 @@ impl Mutation {
         form_data.insert(db).await
     }
 
-    /// Mutation to update the resource_name field in a container
+    /// Mutation to update the resource_name field in a container. The new
+    /// resource starts without the `failure_reason` of the one before.
     pub async fn update_container_resource_name(
         db: &DatabaseConnection,
         id: String,
@@ -32,6 +43,26 @@ impl Mutation {
         let mut container: containers::ActiveModel = container.into();
 
         container.resource_name = Set(Some(resource_name));
+        container.failure_reason = Set(None);
+        container.updated_at = Set(chrono::Utc::now().into());
+
+        container.update(db).await
+    }
+
+    /// Mutation to update the platform a container runs on
+    pub async fn update_container_platform(
+        db: &DatabaseConnection,
+        id: String,
+        platform: String,
+    ) -> Result<containers::Model, DbErr> {
+        let container = containers::Entity::find_by_id(id)
+            .one(db)
+            .await?
+            .ok_or(DbErr::Custom("Container not found".to_string()))?;
+
+        let mut container: containers::ActiveModel = container.into();
+
+        container.platform = Set(Some(platform));
         container.updated_at = Set(chrono::Utc::now().into());
 
         container.update(db).await
@@ -56,6 +87,62 @@ impl Mutation {
         container.update(db).await
     }
 
+    /// Adds what a container's running pod cost at `cost_per_hr` since spend
+    /// was last accrued to its running spend, mirroring the new total into its
+    /// status, and accrues from `now` (unix seconds) next time. The first call
+    /// after the pod starts running only marks `now`. Returns the new total.
+    pub async fn accrue_container_cost(
+        db: &DatabaseConnection,
+        id: String,
+        cost_per_hr: f64,
+        now: i64,
+    ) -> Result<f64, DbErr> {
+        let container = containers::Entity::find_by_id(id)
+            .one(db)
+            .await?
+            .ok_or(DbErr::Custom("Container not found".to_string()))?;
+
+        let elapsed = container
+            .cost_accrued_at
+            .map(|previous| {
+                std::time::Duration::from_secs(now.saturating_sub(previous).max(0) as u64)
+            })
+            .unwrap_or_default();
+        let total = container.total_cost.unwrap_or(0.0) + cost_for(cost_per_hr, elapsed);
+        let mut status = container
+            .parse_status()
+            .map_err(|e| DbErr::Custom(e.to_string()))?
+            .unwrap_or_default();
+        status.total_cost = Some(total);
+
+        let mut container: containers::ActiveModel = container.into();
+        container.total_cost = Set(Some(total));
+        container.cost_accrued_at = Set(Some(now));
+        container.status = Set(Some(json!(status)));
+        container.updated_at = Set(chrono::Utc::now().into());
+        container.update(db).await?;
+
+        Ok(total)
+    }
+
+    /// Stops accruing spend for a container whose pod no longer runs, so the
+    /// time until it runs again isn't charged.
+    pub async fn stop_container_cost_accrual(
+        db: &DatabaseConnection,
+        id: String,
+    ) -> Result<(), DbErr> {
+        containers::Entity::update_many()
+            .col_expr(
+                containers::Column::CostAccruedAt,
+                Expr::value(Option::<i64>::None),
+            )
+            .filter(containers::Column::Id.eq(id))
+            .filter(containers::Column::CostAccruedAt.is_not_null())
+            .exec(db)
+            .await?;
+        Ok(())
+    }
+
     /// Mutation to update the container "pod IP"
     pub async fn update_container_tailnet_ip(
         db: &DatabaseConnection,
@@ -131,6 +218,8 @@ impl Mutation {
             existing_status
         );
 
+        let old_status = existing_status.status.clone();
+        let container_id = container.id.clone();
         let mut container: containers::ActiveModel = container.into();
 
         // 1. Parse any existing status from the database
@@ -190,7 +279,135 @@ impl Mutation {
         );
 
         // 4. Update in the database
-        container.update(db).await
+        let updated = container.update(db).await?;
+
+        // 5. Record the transition, if the status actually changed
+        if container_events::is_transition(old_status.as_deref(), existing_status.status.as_deref())
+        {
+            if let Err(e) = Mutation::create_container_event(
+                db,
+                container_id,
+                old_status.clone(),
+                existing_status.status.clone(),
+                existing_status.message.clone(),
+                "controller".to_string(),
+            )
+            .await
+            {
+                error!("[Mutation] Failed to record container event: {:?}", e);
+            }
+            webhooks::notify_status_transition(
+                &updated,
+                old_status.as_deref(),
+                existing_status.status.as_deref(),
+                existing_status.message.as_deref(),
+            );
+        }
+
+        Ok(updated)
+    }
+
+    /// Append a status transition event for a container
+    pub async fn create_container_event(
+        db: &DatabaseConnection,
+        container_id: String,
+        old_status: Option<String>,
+        new_status: Option<String>,
+        message: Option<String>,
+        actor: String,
+    ) -> Result<container_events::Model, DbErr> {
+        let event = container_events::ActiveModel {
+            id: Set(ShortUuid::generate().to_string()),
+            container_id: Set(container_id),
+            old_status: Set(old_status),
+            new_status: Set(new_status),
+            message: Set(message),
+            actor: Set(actor),
+            created_at: Set(chrono::Utc::now().into()),
+        };
+
+        event.insert(db).await
+    }
+
+    /// Claim an idempotency key for a create request. Keys older than `ttl`
+    /// are released and claimed again.
+    pub async fn claim_idempotency_key(
+        db: &DatabaseConnection,
+        owner: &str,
+        key: &str,
+        ttl: chrono::Duration,
+    ) -> Result<IdempotencyClaim, DbErr> {
+        let id = idempotency_keys::record_id(owner, key);
+
+        if let Some(existing) = idempotency_keys::Entity::find_by_id(id.clone())
+            .one(db)
+            .await?
+        {
+            if !existing.is_expired(chrono::Utc::now(), ttl) {
+                return Ok(match existing.container_id {
+                    Some(container_id) => IdempotencyClaim::Completed(container_id),
+                    None => IdempotencyClaim::InFlight,
+                });
+            }
+            debug!("[Mutation] Idempotency key {} expired, releasing", id);
+            idempotency_keys::Entity::delete_by_id(id.clone())
+                .exec(db)
+                .await?;
+        }
+
+        let record = idempotency_keys::ActiveModel {
+            id: Set(id.clone()),
+            key: Set(key.to_string()),
+            owner: Set(owner.to_string()),
+            container_id: Set(None),
+            created_at: Set(chrono::Utc::now().into()),
+        };
+
+        match record.insert(db).await {
+            Ok(_) => Ok(IdempotencyClaim::Claimed(id)),
+            // A concurrent request claimed it between the lookup and the insert
+            Err(e) if matches!(e.sql_err(), Some(SqlErr::UniqueConstraintViolation(_))) => {
+                Ok(IdempotencyClaim::InFlight)
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Point a claimed idempotency key at the container it created
+    pub async fn complete_idempotency_key(
+        db: &DatabaseConnection,
+        id: String,
+        container_id: String,
+    ) -> Result<idempotency_keys::Model, DbErr> {
+        let record = idempotency_keys::Entity::find_by_id(id)
+            .one(db)
+            .await?
+            .ok_or(DbErr::Custom("Idempotency key not found".to_string()))?;
+
+        let mut record: idempotency_keys::ActiveModel = record.into();
+        record.container_id = Set(Some(container_id));
+        record.update(db).await
+    }
+
+    /// Release an idempotency key, e.g. when the request it guarded failed
+    pub async fn delete_idempotency_key(
+        db: &DatabaseConnection,
+        id: String,
+    ) -> Result<DeleteResult, DbErr> {
+        idempotency_keys::Entity::delete_by_id(id).exec(db).await
+    }
+
+    /// Delete idempotency keys created before `older_than`
+    pub async fn purge_expired_idempotency_keys(
+        db: &DatabaseConnection,
+        older_than: chrono::DateTime<chrono::Utc>,
+    ) -> Result<u64, DbErr> {
+        let result = idempotency_keys::Entity::delete_many()
+            .filter(idempotency_keys::Column::CreatedAt.lt(older_than))
+            .exec(db)
+            .await?;
+
+        Ok(result.rows_affected)
     }
 
     // Mutation to update multiple container fields
@@ -227,6 +444,10 @@ impl Mutation {
         }
 
         if let Some(labels) = update_data.labels {
+            let labels: HashMap<String, String> = labels
+                .into_iter()
+                .filter_map(|(key, value)| value.map(|value| (key, value)))
+                .collect();
             container.labels = Set(Some(json!(labels).into()));
         }
 
@@ -248,19 +469,188 @@ impl Mutation {
         container.update(db).await
     }
 
-    // Mutation to delete a container by ID
+    /// Write the fields of a patched container that don't require recreating it
+    /// (see `base::RECREATE_FIELDS`).
+    pub async fn update_container_in_place(
+        db: &DatabaseConnection,
+        id: String,
+        updated: &V1Container,
+    ) -> Result<containers::Model, DbErr> {
+        let container = containers::Entity::find_by_id(id)
+            .one(db)
+            .await?
+            .ok_or(DbErr::Custom("Container not found".to_string()))?;
+
+        let mut container: containers::ActiveModel = container.into();
+
+        container.labels = Set(updated.metadata.labels.as_ref().map(|l| json!(l)));
+        container.timeout = Set(updated.timeout.clone());
+        container.health_check = Set(updated.health_check.as_ref().map(|h| json!(h)));
+        container.meters = Set(updated.meters.as_ref().map(|m| json!(m)));
+        container.restart = Set(updated.restart.clone());
+        container.queue = Set(updated.queue.clone());
+        container.proxy_port = Set(updated.proxy_port);
+        container.authz = Set(updated.authz.as_ref().map(|a| json!(a)));
+        container.updated_at = Set(chrono::Utc::now().into());
+
+        container.update(db).await
+    }
+
+    /// Soft delete a container by ID. The row is hidden from queries and
+    /// hard deleted later by the purge job.
     pub async fn delete_container(
         db: &DatabaseConnection,
         id: String,
-    ) -> Result<DeleteResult, DbErr> {
-        let result = containers::Entity::delete_by_id(id).exec(db).await?;
+    ) -> Result<containers::Model, DbErr> {
+        let container = containers::Entity::find_by_id(id)
+            .one(db)
+            .await?
+            .ok_or(DbErr::Custom("Container not found".to_string()))?;
+
+        if container.deleted_at.is_some() {
+            debug!("[Mutation] Container {} already deleted", container.id);
+            return Ok(container);
+        }
+
+        // Free up the unique full name so the container can be recreated
+        let full_name = deleted_full_name(&container.full_name, &container.id);
+        let mut container: containers::ActiveModel = container.into();
+
+        container.full_name = Set(full_name);
+        container.deleted_at = Set(Some(chrono::Utc::now().into()));
+        container.updated_at = Set(chrono::Utc::now().into());
+
+        container.update(db).await
+    }
+
+    /// Hard delete containers that were soft deleted before `older_than`,
+    /// along with their events
+    pub async fn purge_deleted_containers(
+        db: &DatabaseConnection,
+        older_than: chrono::DateTime<chrono::Utc>,
+    ) -> Result<u64, DbErr> {
+        let expired: Vec<String> = containers::Entity::find()
+            .filter(containers::Column::DeletedAt.is_not_null())
+            .filter(containers::Column::DeletedAt.lt(older_than))
+            .all(db)
+            .await?
+            .into_iter()
+            .map(|c| c.id)
+            .collect();
+
+        if expired.is_empty() {
+            return Ok(0);
+        }
+
+        container_events::Entity::delete_many()
+            .filter(container_events::Column::ContainerId.is_in(expired.clone()))
+            .exec(db)
+            .await?;
+
+        let result = containers::Entity::delete_many()
+            .filter(containers::Column::Id.is_in(expired))
+            .exec(db)
+            .await?;
+
+        Ok(result.rows_affected)
+    }
+
+    /// Apply `update` to a container's `controller_data`, leaving the fields
+    /// it doesn't touch in place. Data that doesn't parse is left as it is
+    /// and an error returned.
+    pub async fn update_container_controller_data(
+        db: &DatabaseConnection,
+        id: String,
+        update: impl FnOnce(&mut ControllerData),
+    ) -> Result<containers::Model, DbErr> {
+        let container = containers::Entity::find_by_id(id)
+            .one(db)
+            .await?
+            .ok_or(DbErr::Custom("Container not found".to_string()))?;
+
+        let mut data = container.typed_controller_data().map_err(|e| {
+            DbErr::Custom(format!(
+                "Unreadable controller_data of container {}: {}",
+                container.id, e
+            ))
+        })?;
+        update(&mut data);
+
+        let mut container: containers::ActiveModel = container.into();
+        container.controller_data = Set(Some(data.to_json()));
+        container.updated_at = Set(chrono::Utc::now().into());
+
+        container.update(db).await
+    }
+
+    /// Store a resource usage sample after the few before it. The container's
+    /// status shows the latest. Only the container's watch loop samples, so
+    /// nothing else writes the history in between; like an SSH check, a
+    /// sample leaves `updated_at` as it is.
+    pub async fn record_container_usage(
+        db: &DatabaseConnection,
+        id: String,
+        sample: V1ResourceUsage,
+    ) -> Result<(), DbErr> {
+        let container = containers::Entity::find_by_id(id.clone())
+            .one(db)
+            .await?
+            .ok_or(DbErr::Custom("Container not found".to_string()))?;
 
-        // Check if any row was actually deleted
+        // An unreadable history is started over rather than kept forever
+        let mut history = container.parse_usage().unwrap_or_default();
+        usage::push_sample(&mut history, sample);
+
+        containers::Entity::update_many()
+            .col_expr(containers::Column::Usage, Expr::value(json!(history)))
+            .filter(containers::Column::Id.eq(id))
+            .exec(db)
+            .await?;
+        Ok(())
+    }
+
+    /// Store the result of an SSH check, shown in the container's status. A
+    /// check is no change to the container, so `updated_at` stays as it is.
+    pub async fn record_container_ssh_check(
+        db: &DatabaseConnection,
+        id: String,
+        reachable: bool,
+        checked_at: i64,
+    ) -> Result<(), DbErr> {
+        let result = containers::Entity::update_many()
+            .col_expr(containers::Column::SshReachable, Expr::value(reachable))
+            .col_expr(containers::Column::LastSshCheck, Expr::value(checked_at))
+            .filter(containers::Column::Id.eq(id))
+            .exec(db)
+            .await?;
         if result.rows_affected == 0 {
             return Err(DbErr::Custom("Container not found".to_string()));
         }
+        Ok(())
+    }
 
-        Ok(result)
+    /// Store why a container failed, as recognised from its logs
+    pub async fn record_container_failure_reason(
+        db: &DatabaseConnection,
+        id: String,
+        reason: V1FailureReason,
+    ) -> Result<(), DbErr> {
+        let result = containers::Entity::update_many()
+            .col_expr(
+                containers::Column::FailureReason,
+                Expr::value(json!(reason)),
+            )
+            .col_expr(
+                containers::Column::UpdatedAt,
+                Expr::value(chrono::Utc::now()),
+            )
+            .filter(containers::Column::Id.eq(id))
+            .exec(db)
+            .await?;
+        if result.rows_affected == 0 {
+            return Err(DbErr::Custom("Container not found".to_string()));
+        }
+        Ok(())
     }
 
     /// Mutation to update the container user
@@ -287,6 +677,31 @@ impl Mutation {
         container_am.update(db).await
     }
 
+    /// Store the latest volume sync progress of a container, in one statement
+    /// so a concurrent status update can't undo it
+    pub async fn update_container_sync_progress(
+        db: &DatabaseConnection,
+        id: String,
+        progress: V1SyncProgress,
+    ) -> Result<(), DbErr> {
+        let result = containers::Entity::update_many()
+            .col_expr(
+                containers::Column::SyncProgress,
+                Expr::value(json!(progress)),
+            )
+            .col_expr(
+                containers::Column::UpdatedAt,
+                Expr::value(chrono::Utc::now()),
+            )
+            .filter(containers::Column::Id.eq(id))
+            .exec(db)
+            .await?;
+        if result.rows_affected == 0 {
+            return Err(DbErr::Custom("Container not found".to_string()));
+        }
+        Ok(())
+    }
+
     /// Store a container's SSH keypair (private & public) in the `secrets` table.
     /// Returns tuples (private_key_secret, public_key_secret).
     pub async fn store_ssh_keypair(
@@ -354,7 +769,8 @@ impl Mutation {
         Ok((private_inserted, public_inserted))
     }
 
-    /// Update an existing secret by re-encrypting if `new_value` is provided.
+    /// Update an existing secret by re-encrypting if `new_value` is provided,
+    /// which also bumps its version.
     pub async fn update_secret(
         db: &DatabaseConnection,
         secret: secrets::Model,
@@ -362,6 +778,7 @@ impl Mutation {
         new_value: Option<String>,
         new_labels: Option<serde_json::Value>,
     ) -> Result<secrets::Model, DbErr> {
+        let secret_version = secret.version;
         let mut active_model = secrets::ActiveModel::from(secret);
 
         // If a new name is provided
@@ -375,6 +792,7 @@ impl Mutation {
                 secrets::Model::encrypt_value(&value).map_err(|e| DbErr::Custom(e))?;
             active_model.encrypted_value = Set(encrypted_value);
             active_model.nonce = Set(nonce);
+            active_model.version = Set(secret_version + 1);
         }
 
         // If new labels are provided
@@ -461,6 +879,62 @@ impl Mutation {
         processor_am.update(db).await
     }
 
+    /// Records how many of a processor's replicas are warm and ready in its
+    /// status, leaving the rest of the status as it is. Nothing is written
+    /// when the counts are unchanged; returns whether they were.
+    pub async fn update_processor_replica_counts(
+        db: &DatabaseConnection,
+        id: String,
+        warm_replicas: i32,
+        ready_replicas: i32,
+    ) -> Result<bool, DbErr> {
+        let processor = processors::Entity::find_by_id(id.clone())
+            .one(db)
+            .await?
+            .ok_or_else(|| DbErr::Custom(format!("Processor '{}' not found", id)))?;
+
+        let mut status = processor
+            .parse_status()
+            .map_err(|e| DbErr::Custom(e.to_string()))?
+            .unwrap_or_default();
+        if status.warm_replicas == Some(warm_replicas)
+            && status.ready_replicas == Some(ready_replicas)
+        {
+            return Ok(false);
+        }
+        status.warm_replicas = Some(warm_replicas);
+        status.ready_replicas = Some(ready_replicas);
+
+        let mut processor_am: processors::ActiveModel = processor.into();
+        processor_am.status = sea_orm::ActiveValue::Set(Some(json!(status)));
+        processor_am.updated_at = sea_orm::ActiveValue::Set(chrono::Utc::now().into());
+        processor_am.update(db).await?;
+        Ok(true)
+    }
+
+    /// Mutation to update just the `desired_status` of a container.
+    pub async fn update_container_desired_status(
+        db: &DatabaseConnection,
+        id: String,
+        new_desired_status: Option<String>,
+    ) -> Result<containers::Model, DbErr> {
+        let container = containers::Entity::find_by_id(id.clone())
+            .one(db)
+            .await?
+            .ok_or_else(|| DbErr::Custom(format!("Container '{}' not found", id)))?;
+
+        let mut container_am: containers::ActiveModel = container.into();
+        container_am.desired_status = sea_orm::ActiveValue::Set(new_desired_status);
+        container_am.updated_at = sea_orm::ActiveValue::Set(chrono::Utc::now().into());
+
+        info!(
+            "[Mutation] Updating container '{}' desired_status to: {:?}",
+            id, container_am.desired_status
+        );
+
+        container_am.update(db).await
+    }
+
     /// Mutation to update just the `desired_status` of a processor.
     pub async fn update_processor_desired_status(
         db: &DatabaseConnection,
@@ -489,4 +963,418 @@ impl Mutation {
         // 4) Write it back to the database
         processor_am.update(db).await
     }
+
+    /// Soft delete a processor by ID. The row is hidden from queries and
+    /// hard deleted later by the purge job.
+    pub async fn delete_processor(
+        db: &DatabaseConnection,
+        id: String,
+    ) -> Result<processors::Model, DbErr> {
+        let processor = processors::Entity::find_by_id(id)
+            .one(db)
+            .await?
+            .ok_or(DbErr::Custom("Processor not found".to_string()))?;
+
+        if processor.deleted_at.is_some() {
+            debug!("[Mutation] Processor {} already deleted", processor.id);
+            return Ok(processor);
+        }
+
+        let full_name = deleted_full_name(&processor.full_name, &processor.id);
+        let mut processor: processors::ActiveModel = processor.into();
+
+        processor.full_name = Set(full_name);
+        processor.deleted_at = Set(Some(chrono::Utc::now().into()));
+        processor.updated_at = Set(chrono::Utc::now().into());
+
+        processor.update(db).await
+    }
+
+    /// Hard delete processors that were soft deleted before `older_than`
+    pub async fn purge_deleted_processors(
+        db: &DatabaseConnection,
+        older_than: chrono::DateTime<chrono::Utc>,
+    ) -> Result<u64, DbErr> {
+        let result = processors::Entity::delete_many()
+            .filter(processors::Column::DeletedAt.is_not_null())
+            .filter(processors::Column::DeletedAt.lt(older_than))
+            .exec(db)
+            .await?;
+
+        Ok(result.rows_affected)
+    }
+}
+
+/// The full name a soft-deleted resource is renamed to, so the original
+/// `namespace/name` can be reused while the row waits to be purged.
+pub fn deleted_full_name(full_name: &str, id: &str) -> String {
+    format!("{}#deleted-{}", full_name, id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::resources::v1::containers::models::V1ContainerStatus;
+
+    #[test]
+    fn test_deleted_full_name_frees_original_name() {
+        // Arrange
+        let full_name = "my-ns/my-container";
+
+        // Act
+        let result = deleted_full_name(full_name, "abc123");
+
+        // Assert
+        assert_ne!(result, full_name);
+        assert_eq!(result, "my-ns/my-container#deleted-abc123");
+    }
+
+    #[test]
+    fn test_deleted_full_name_is_unique_per_id() {
+        assert_ne!(
+            deleted_full_name("ns/name", "id1"),
+            deleted_full_name("ns/name", "id2")
+        );
+    }
+
+    /// A database holding running container `c1`
+    async fn db_with_running_container() -> DatabaseConnection {
+        let db = crate::db::test_db().await;
+        containers::ActiveModel {
+            id: Set("c1".to_string()),
+            namespace: Set("ns".to_string()),
+            name: Set("train".to_string()),
+            full_name: Set("ns/train".to_string()),
+            owner: Set("me".to_string()),
+            image: Set("busybox".to_string()),
+            restart: Set("Never".to_string()),
+            status: Set(Some(json!(V1ContainerStatus {
+                status: Some("running".to_string()),
+                ..Default::default()
+            }))),
+            updated_at: Set(chrono::Utc::now().into()),
+            created_at: Set(chrono::Utc::now().into()),
+            ..Default::default()
+        }
+        .insert(&db)
+        .await
+        .unwrap();
+        db
+    }
+
+    async fn v1_status(db: &DatabaseConnection) -> V1ContainerStatus {
+        containers::Entity::find_by_id("c1")
+            .one(db)
+            .await
+            .unwrap()
+            .unwrap()
+            .to_v1_container()
+            .unwrap()
+            .status
+            .unwrap()
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a Postgres at NEBU_TEST_DATABASE_URL"]
+    async fn test_ssh_check_is_reflected_in_status() {
+        let db = db_with_running_container().await;
+        let before = containers::Entity::find_by_id("c1")
+            .one(&db)
+            .await
+            .unwrap()
+            .unwrap()
+            .updated_at;
+
+        Mutation::record_container_ssh_check(&db, "c1".to_string(), false, 100)
+            .await
+            .unwrap();
+        let unreachable = v1_status(&db).await;
+        assert_eq!(unreachable.ssh_reachable, Some(false));
+        assert_eq!(unreachable.last_ssh_check, Some(100));
+        assert_eq!(unreachable.status.as_deref(), Some("running"));
+
+        Mutation::record_container_ssh_check(&db, "c1".to_string(), true, 160)
+            .await
+            .unwrap();
+        let reachable = v1_status(&db).await;
+        assert_eq!(reachable.ssh_reachable, Some(true));
+        assert_eq!(reachable.last_ssh_check, Some(160));
+        let after = containers::Entity::find_by_id("c1")
+            .one(&db)
+            .await
+            .unwrap()
+            .unwrap()
+            .updated_at;
+        assert_eq!(before, after);
+
+        // Later status updates leave the check alone
+        Mutation::update_container_status(
+            &db,
+            "c1".to_string(),
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            Some(true),
+        )
+        .await
+        .unwrap();
+        let updated = v1_status(&db).await;
+        assert_eq!(updated.ready, Some(true));
+        assert_eq!(updated.ssh_reachable, Some(true));
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a Postgres at NEBU_TEST_DATABASE_URL"]
+    async fn test_spend_accrues_across_restarts() {
+        let db = db_with_running_container().await;
+        let accrue = |now: i64| Mutation::accrue_container_cost(&db, "c1".to_string(), 0.36, now);
+
+        // The first poll only marks when the pod was seen running
+        assert_eq!(accrue(1_000).await.unwrap(), 0.0);
+        let total = accrue(1_020).await.unwrap();
+        assert!((total - 0.002).abs() < 1e-9, "total was {}", total);
+        // A restarted controller picks up where the last poll left off
+        let total = accrue(1_045).await.unwrap();
+        assert!((total - 0.0045).abs() < 1e-9, "total was {}", total);
+        assert_eq!(v1_status(&db).await.total_cost, Some(total));
+
+        // Time the pod isn't running isn't charged
+        Mutation::stop_container_cost_accrual(&db, "c1".to_string())
+            .await
+            .unwrap();
+        assert_eq!(accrue(5_000).await.unwrap(), total);
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a Postgres at NEBU_TEST_DATABASE_URL"]
+    async fn test_unreadable_controller_data_is_left_alone() {
+        let db = db_with_running_container().await;
+        let unreadable = json!({"restart": "soon", "thread_id": "t1"});
+        containers::Entity::update_many()
+            .col_expr(
+                containers::Column::ControllerData,
+                Expr::value(unreadable.clone()),
+            )
+            .filter(containers::Column::Id.eq("c1"))
+            .exec(&db)
+            .await
+            .unwrap();
+
+        let result = Mutation::update_container_controller_data(&db, "c1".to_string(), |data| {
+            data.request_id = Some("r1".to_string())
+        })
+        .await;
+
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("Unreadable controller_data"));
+        let stored = containers::Entity::find_by_id("c1")
+            .one(&db)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(stored.controller_data, Some(unreadable));
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a Postgres at NEBU_TEST_DATABASE_URL"]
+    async fn test_sync_progress_survives_status_updates() {
+        let db = db_with_running_container().await;
+        let progress = V1SyncProgress {
+            source: "s3://bucket/data".to_string(),
+            dest: "/data".to_string(),
+            bytes: 10,
+            total_bytes: 100,
+            transfers: 1,
+            total_transfers: 10,
+            errors: 0,
+            eta_secs: Some(9),
+            speed: None,
+            updated_at: 100,
+        };
+
+        Mutation::update_container_sync_progress(&db, "c1".to_string(), progress.clone())
+            .await
+            .unwrap();
+        let updated = Mutation::update_container_status(
+            &db,
+            "c1".to_string(),
+            Some("failed".to_string()),
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+        )
+        .await
+        .unwrap();
+
+        let status = updated.to_v1_container().unwrap().status.unwrap();
+        assert_eq!(status.status.as_deref(), Some("failed"));
+        assert_eq!(status.sync_progress, Some(progress.clone()));
+        assert!(
+            Mutation::update_container_sync_progress(&db, "gone".to_string(), progress)
+                .await
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a Postgres at NEBU_TEST_DATABASE_URL"]
+    async fn test_failure_reason_is_cleared_for_a_new_pod() {
+        let db = db_with_running_container().await;
+        let reason = V1FailureReason {
+            kind: "cuda_out_of_memory".to_string(),
+            line: "torch.cuda.OutOfMemoryError: CUDA out of memory.".to_string(),
+        };
+
+        Mutation::record_container_failure_reason(&db, "c1".to_string(), reason.clone())
+            .await
+            .unwrap();
+        Mutation::update_container_status(
+            &db,
+            "c1".to_string(),
+            Some("failed".to_string()),
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+        )
+        .await
+        .unwrap();
+        assert_eq!(v1_status(&db).await.failure_reason, Some(reason));
+
+        Mutation::update_container_resource_name(&db, "c1".to_string(), "pod2".to_string())
+            .await
+            .unwrap();
+        assert_eq!(v1_status(&db).await.failure_reason, None);
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a Postgres at NEBU_TEST_DATABASE_URL"]
+    async fn test_usage_history_survives_status_updates() {
+        let db = db_with_running_container().await;
+        let sample = |timestamp| V1ResourceUsage {
+            timestamp,
+            cpu_count: Some(4),
+            ..Default::default()
+        };
+        let before = containers::Entity::find_by_id("c1")
+            .one(&db)
+            .await
+            .unwrap()
+            .unwrap()
+            .updated_at;
+
+        for timestamp in 0..(usage::USAGE_SAMPLES_KEPT as i64 + 2) {
+            Mutation::record_container_usage(&db, "c1".to_string(), sample(timestamp))
+                .await
+                .unwrap();
+        }
+        let sampled = containers::Entity::find_by_id("c1")
+            .one(&db)
+            .await
+            .unwrap()
+            .unwrap();
+        Mutation::update_container_status(
+            &db,
+            "c1".to_string(),
+            Some("failed".to_string()),
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+        )
+        .await
+        .unwrap();
+
+        let stored = containers::Entity::find_by_id("c1")
+            .one(&db)
+            .await
+            .unwrap()
+            .unwrap();
+        let history = stored.parse_usage().unwrap();
+        assert_eq!(history.len(), usage::USAGE_SAMPLES_KEPT);
+        assert_eq!(history[0].timestamp, 2);
+        let status = v1_status(&db).await;
+        assert_eq!(status.status.as_deref(), Some("failed"));
+        assert_eq!(
+            status.usage,
+            Some(sample(usage::USAGE_SAMPLES_KEPT as i64 + 1))
+        );
+        assert_eq!(sampled.updated_at, before);
+    }
+
+    #[tokio::test]
+    async fn test_replica_counts_are_written_only_on_change() {
+        let db = Database::connect("sqlite::memory:").await.unwrap();
+        let backend = db.get_database_backend();
+        let stmt = Schema::new(backend).create_table_from_entity(processors::Entity);
+        db.execute(backend.build(&stmt)).await.unwrap();
+        let processor = processors::ActiveModel::from(processors::Model {
+            status: Some(json!(V1ProcessorStatus {
+                status: Some("Running".to_string()),
+                ..Default::default()
+            })),
+            ..processors::Model::test_fixture()
+        })
+        .insert(&db)
+        .await
+        .unwrap();
+        let id = processor.id.clone();
+
+        assert!(
+            Mutation::update_processor_replica_counts(&db, id.clone(), 2, 1)
+                .await
+                .unwrap()
+        );
+        let counted = processors::Entity::find_by_id(id.clone())
+            .one(&db)
+            .await
+            .unwrap()
+            .unwrap();
+        let status = counted.parse_status().unwrap().unwrap();
+        assert_eq!(status.status.as_deref(), Some("Running"));
+        assert_eq!(
+            (status.warm_replicas, status.ready_replicas),
+            (Some(2), Some(1))
+        );
+
+        assert!(
+            !Mutation::update_processor_replica_counts(&db, id.clone(), 2, 1)
+                .await
+                .unwrap()
+        );
+        let unchanged = processors::Entity::find_by_id(id.clone())
+            .one(&db)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(unchanged.updated_at, counted.updated_at);
+
+        // A status that doesn't parse is left for someone to look at
+        let mut broken: processors::ActiveModel = unchanged.into();
+        broken.status = Set(Some(json!(42)));
+        broken.update(&db).await.unwrap();
+        assert!(
+            Mutation::update_processor_replica_counts(&db, id.clone(), 3, 3)
+                .await
+                .is_err()
+        );
+        let kept = processors::Entity::find_by_id(id)
+            .one(&db)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(kept.status, Some(json!(42)));
+    }
 }
@@ -2,32 +2,55 @@ use oci_distribution::client::Client;
 use oci_distribution::manifest::{OciImageIndex, OciImageManifest, OciManifest};
 use oci_distribution::secrets::RegistryAuth;
 use oci_distribution::Reference;
+use once_cell::sync::Lazy;
 use serde_json::Value;
 use tracing::debug;
 
+/// Shared so registry connections and auth tokens are reused across lookups
+static OCI_CLIENT: Lazy<Client> = Lazy::new(Client::default);
+
+/// Checks that a manifest exists for `image_ref` without pulling it.
+/// Uses a HEAD request against the registry, falling back to GET when unsupported.
+pub async fn image_exists(image_ref: &str) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let client = &*OCI_CLIENT;
+    let reference: Reference = image_ref.parse()?;
+
+    let digest = client
+        .fetch_manifest_digest(&reference, &RegistryAuth::Anonymous)
+        .await?;
+    debug!("Found manifest for {} with digest={}", image_ref, digest);
+    Ok(())
+}
+
 // Example of manually pulling an image from a multi-arch index
 // *without* specifying architecture or OS
 pub async fn pull_and_parse_config(
     image_ref: &str,
+    auth: &RegistryAuth,
 ) -> Result<(OciImageManifest, String), Box<dyn std::error::Error + Send + Sync>> {
-    // 1. Create the OCI client (no “pull options” or “ClientConfig” needed)
-    let client = Client::default();
+    // 1. Use the shared OCI client for anonymous pulls. The client keeps the
+    //    tokens it gets, so credentialed pulls get their own rather than
+    //    leaving them to anyone else pulling from that registry.
+    let own_client;
+    let client = match auth {
+        RegistryAuth::Anonymous => &*OCI_CLIENT,
+        _ => {
+            own_client = Client::default();
+            &own_client
+        }
+    };
     let reference: Reference = image_ref.parse()?;
 
-    // 2. Authenticate if needed (Anonymous here)
+    // 2. Authenticate if needed
     client
-        .auth(
-            &reference,
-            &RegistryAuth::Anonymous,
-            oci_distribution::RegistryOperation::Pull,
-        )
+        .auth(&reference, auth, oci_distribution::RegistryOperation::Pull)
         .await?;
 
     // 3. Pull the manifest (could be `Image` or `Index`)
     let (manifest_enum, top_digest) = client
         // `_pull_manifest` is the internal function. But for public usage,
         // you can use `pull_manifest` and then match on the returned `OciManifest`.
-        .pull_manifest(&reference, &RegistryAuth::Anonymous)
+        .pull_manifest(&reference, auth)
         .await?;
 
     // 4. If it’s an image index -> pick a sub-manifest yourself
@@ -62,9 +85,7 @@ pub async fn pull_and_parse_config(
     };
 
     // 5. Now pull again (this time we expect a single `Image`).
-    let (manifest_enum2, pinned_digest) = client
-        .pull_manifest(&pinned_reference, &RegistryAuth::Anonymous)
-        .await?;
+    let (manifest_enum2, pinned_digest) = client.pull_manifest(&pinned_reference, auth).await?;
 
     let image_manifest = match manifest_enum2 {
         OciManifest::Image(img) => {
@@ -93,7 +114,7 @@ pub async fn pull_and_parse_config(
         .get("config")
         .and_then(|c| c.get("User"))
         .and_then(|u| u.as_str())
-        .unwrap_or("root");
+        .unwrap_or_default();
 
     debug!("Found user={user}");
     Ok((image_manifest, user.to_owned()))
@@ -5,7 +5,10 @@ use crate::proxy::meters::{send_request_metrics, send_response_metrics};
 use crate::query::Query;
 use crate::resources::v1::containers::base::get_tailscale_device_name;
 use crate::AppState;
-use axum::body::Body;
+use axum::body::{Body, HttpBody};
+use axum::extract::ws::{CloseFrame, Message, WebSocket, WebSocketUpgrade};
+use axum::extract::{OriginalUri, Path};
+use axum::http::HeaderName;
 use axum::http::Uri;
 use axum::{
     body::Bytes,
@@ -13,9 +16,29 @@ use axum::{
     http::{HeaderMap, Method, StatusCode},
     response::{IntoResponse, Response},
 };
-use serde_json::Value;
+use axum::{Extension, Json};
+use futures::{SinkExt, StreamExt, TryStreamExt};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::protocol::{
+    frame::coding::CloseCode, CloseFrame as UpstreamCloseFrame,
+};
+use tokio_tungstenite::tungstenite::Message as UpstreamMessage;
 use tracing::{debug, error};
 
+/// Headers that describe a single connection and must not be forwarded
+const HOP_BY_HOP_HEADERS: &[&str] = &[
+    "connection",
+    "keep-alive",
+    "proxy-authenticate",
+    "proxy-authorization",
+    "te",
+    "trailer",
+    "transfer-encoding",
+    "upgrade",
+];
+
 #[allow(dead_code)]
 pub async fn forward_container(
     State(_app_state): State<AppState>, // replace with actual state usage if needed
@@ -151,7 +174,7 @@ pub async fn forward_container(
     let target_url = format!("http://{}{}{}", hostname, port_str, full_uri);
     debug!("[PROXY] Target URL with path: {target_url}");
 
-    let client = reqwest::Client::new();
+    let client = crate::utils::http::shared_client();
 
     // Build outbound request with the same method, body, and forwarded headers
     let mut req_builder = client.request(method.clone(), &target_url).body(body);
@@ -207,3 +230,580 @@ pub async fn forward_container(
         }
     }
 }
+
+/// Copies `headers` minus hop-by-hop headers and `host`. When
+/// `strip_authorization` is set the caller's credentials are dropped too, so a
+/// Nebulous API key never reaches the container.
+pub fn forwardable_headers(headers: &HeaderMap, strip_authorization: bool) -> HeaderMap {
+    let mut connection_listed: Vec<String> = headers
+        .get_all("connection")
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .map(|v| v.trim().to_ascii_lowercase())
+        .collect();
+    connection_listed.extend(HOP_BY_HOP_HEADERS.iter().map(|h| h.to_string()));
+    connection_listed.push("host".to_string());
+    if strip_authorization {
+        connection_listed.push("authorization".to_string());
+    }
+
+    let mut forwarded = HeaderMap::new();
+    for (key, value) in headers.iter() {
+        if !connection_listed.iter().any(|h| h == key.as_str()) {
+            forwarded.append(key.clone(), value.clone());
+        }
+    }
+    forwarded
+}
+
+/// The base URL of the service a container exposes on its `proxy_port`,
+/// reached over its tailnet address.
+pub async fn container_proxy_target(
+    container: &crate::entities::containers::Model,
+) -> Result<String, (StatusCode, Json<Value>)> {
+    let port = container.proxy_port.ok_or_else(|| {
+        (
+            StatusCode::CONFLICT,
+            Json(json!({"error": "Container has no proxy_port set"})),
+        )
+    })?;
+    let hostname = match &container.tailnet_ip {
+        Some(ip) => ip.clone(),
+        None => get_tailscale_device_name(container).await,
+    };
+    Ok(format!("http://{}:{}", hostname, port))
+}
+
+/// Streams an incoming body into a request body. reqwest needs a `Sync`
+/// stream, which axum's body isn't, so chunks are relayed through a channel.
+fn outbound_body(body: Body) -> reqwest::Body {
+    if body.size_hint().exact() == Some(0) {
+        return reqwest::Body::from(Bytes::new());
+    }
+    let (mut tx, rx) = futures::channel::mpsc::channel::<Result<Bytes, axum::Error>>(8);
+    tokio::spawn(async move {
+        let mut chunks = body.into_data_stream();
+        while let Some(chunk) = chunks.next().await {
+            if tx.send(chunk).await.is_err() {
+                break;
+            }
+        }
+    });
+    reqwest::Body::wrap_stream(rx)
+}
+
+/// Sends the request on to `target_base` + `path_and_query`, streaming both
+/// bodies. `prefix` is passed as `X-Forwarded-Prefix` so the service can build
+/// links that route back through the proxy.
+pub async fn forward_streaming(
+    client: &reqwest::Client,
+    target_base: &str,
+    path_and_query: &str,
+    prefix: &str,
+    method: Method,
+    headers: &HeaderMap,
+    body: Body,
+) -> Response {
+    let target_url = format!("{}{}", target_base, path_and_query);
+    debug!("[PROXY] Forwarding {} to {}", method, target_url);
+
+    let mut outbound = forwardable_headers(headers, true);
+    if let Ok(value) = prefix.parse() {
+        outbound.insert(HeaderName::from_static("x-forwarded-prefix"), value);
+    }
+
+    let result = client
+        .request(method, &target_url)
+        .headers(outbound)
+        .body(outbound_body(body))
+        .send()
+        .await;
+
+    match result {
+        Ok(resp) => {
+            let mut response = Response::builder().status(resp.status());
+            if let Some(response_headers) = response.headers_mut() {
+                *response_headers = forwardable_headers(resp.headers(), false);
+            }
+            response
+                .body(Body::from_stream(resp.bytes_stream().map_err(|e| {
+                    error!("[PROXY] Error streaming response body: {}", e);
+                    e
+                })))
+                .unwrap_or_else(|_| StatusCode::BAD_GATEWAY.into_response())
+        }
+        Err(e) => {
+            error!("[PROXY] Forwarding to {} failed: {}", target_url, e);
+            (
+                StatusCode::BAD_GATEWAY,
+                Json(json!({"error": format!("Failed to reach container: {}", e)})),
+            )
+                .into_response()
+        }
+    }
+}
+
+/// How long the other side of a bridged websocket gets to finish closing
+const WS_CLOSE_GRACE: std::time::Duration = std::time::Duration::from_secs(5);
+
+fn to_upstream(message: Message) -> UpstreamMessage {
+    match message {
+        Message::Text(text) => UpstreamMessage::Text(text),
+        Message::Binary(data) => UpstreamMessage::Binary(data),
+        Message::Ping(data) => UpstreamMessage::Ping(data),
+        Message::Pong(data) => UpstreamMessage::Pong(data),
+        Message::Close(frame) => UpstreamMessage::Close(frame.map(|f| UpstreamCloseFrame {
+            code: CloseCode::from(f.code),
+            reason: f.reason,
+        })),
+    }
+}
+
+fn from_upstream(message: UpstreamMessage) -> Option<Message> {
+    match message {
+        UpstreamMessage::Text(text) => Some(Message::Text(text)),
+        UpstreamMessage::Binary(data) => Some(Message::Binary(data)),
+        UpstreamMessage::Ping(data) => Some(Message::Ping(data)),
+        UpstreamMessage::Pong(data) => Some(Message::Pong(data)),
+        UpstreamMessage::Close(frame) => Some(Message::Close(frame.map(|f| CloseFrame {
+            code: f.code.into(),
+            reason: f.reason,
+        }))),
+        // Raw frames only show up when writing, never when reading
+        UpstreamMessage::Frame(_) => None,
+    }
+}
+
+/// Connects to the websocket at `target_url`, then upgrades the client and
+/// relays frames both ways until either side closes. Each direction awaits
+/// its send before reading the next frame, so a slow reader on one end
+/// slows the writer on the other instead of buffering without bound.
+pub async fn proxy_websocket(
+    ws: WebSocketUpgrade,
+    target_url: &str,
+    prefix: &str,
+    headers: &HeaderMap,
+) -> Response {
+    let mut request = match target_url.into_client_request() {
+        Ok(request) => request,
+        Err(e) => {
+            return (
+                StatusCode::BAD_GATEWAY,
+                Json(json!({"error": format!("Invalid websocket target: {}", e)})),
+            )
+                .into_response()
+        }
+    };
+    // The handshake headers belong to the client's connection, except for
+    // the subprotocols it asked for
+    for (key, value) in forwardable_headers(headers, true).iter() {
+        let name = key.as_str();
+        if name.starts_with("sec-websocket-") && name != "sec-websocket-protocol" {
+            continue;
+        }
+        request.headers_mut().append(key.clone(), value.clone());
+    }
+    if let Ok(value) = prefix.parse() {
+        request
+            .headers_mut()
+            .insert(HeaderName::from_static("x-forwarded-prefix"), value);
+    }
+
+    debug!("[PROXY] Opening websocket to {}", target_url);
+    let (upstream, upstream_response) = match tokio_tungstenite::connect_async(request).await {
+        Ok(connected) => connected,
+        Err(e) => {
+            error!(
+                "[PROXY] Websocket connection to {} failed: {}",
+                target_url, e
+            );
+            return (
+                StatusCode::BAD_GATEWAY,
+                Json(json!({"error": format!("Failed to reach container: {}", e)})),
+            )
+                .into_response();
+        }
+    };
+
+    let ws = match upstream_response
+        .headers()
+        .get("sec-websocket-protocol")
+        .and_then(|v| v.to_str().ok())
+    {
+        Some(protocol) => ws.protocols([protocol.to_string()]),
+        None => ws,
+    };
+
+    ws.on_upgrade(move |client| bridge_websockets(client, upstream))
+}
+
+async fn bridge_websockets<S>(client: WebSocket, upstream: tokio_tungstenite::WebSocketStream<S>)
+where
+    S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin,
+{
+    let (mut client_tx, mut client_rx) = client.split();
+    let (mut upstream_tx, mut upstream_rx) = upstream.split();
+
+    let client_to_upstream = async {
+        while let Some(Ok(message)) = client_rx.next().await {
+            let closing = matches!(message, Message::Close(_));
+            if upstream_tx.send(to_upstream(message)).await.is_err() || closing {
+                break;
+            }
+        }
+        let _ = upstream_tx.close().await;
+    };
+
+    let upstream_to_client = async {
+        while let Some(Ok(message)) = upstream_rx.next().await {
+            let Some(message) = from_upstream(message) else {
+                continue;
+            };
+            let closing = matches!(message, Message::Close(_));
+            if client_tx.send(message).await.is_err() || closing {
+                break;
+            }
+        }
+        let _ = client_tx.close().await;
+    };
+
+    // Once one side is done, give the other a moment to finish the closing
+    // handshake, then drop both
+    tokio::pin!(client_to_upstream, upstream_to_client);
+    tokio::select! {
+        _ = &mut client_to_upstream => {
+            debug!("[PROXY] Client closed the websocket");
+            let _ = tokio::time::timeout(WS_CLOSE_GRACE, &mut upstream_to_client).await;
+        }
+        _ = &mut upstream_to_client => {
+            debug!("[PROXY] Container closed the websocket");
+            let _ = tokio::time::timeout(WS_CLOSE_GRACE, &mut client_to_upstream).await;
+        }
+    }
+}
+
+/// The part of a `/v1/containers/:namespace/:name/proxy/*path` request URI
+/// that goes to the container, still percent-encoded as the client sent it
+fn upstream_path_and_query(uri: &Uri) -> String {
+    // "", "v1", "containers", namespace, name, "proxy", then the rest
+    let path = uri.path().splitn(7, '/').nth(6).unwrap_or("");
+    match uri.query() {
+        Some(query) => format!("/{}?{}", path, query),
+        None => format!("/{}", path),
+    }
+}
+
+/// `ANY /v1/containers/:namespace/:name/proxy/*path`: forwards to the
+/// container's `proxy_port`, upgrading to a bridged websocket when the client
+/// asks for one. Only owners of the container can reach it.
+pub async fn proxy_container_http(
+    State(state): State<AppState>,
+    Extension(user_profile): Extension<V1UserProfile>,
+    Path(params): Path<HashMap<String, String>>,
+    OriginalUri(uri): OriginalUri,
+    ws: Option<WebSocketUpgrade>,
+    method: Method,
+    headers: HeaderMap,
+    body: Body,
+) -> Response {
+    let (Some(namespace), Some(name)) = (params.get("namespace"), params.get("name")) else {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(json!({"error": "Missing namespace or name"})),
+        )
+            .into_response();
+    };
+    let resolved_namespace = crate::utils::namespace::resolve_namespace(namespace, &user_profile);
+
+    let owner_ids = user_profile.owner_ids();
+    let owner_id_refs: Vec<&str> = owner_ids.iter().map(|s| s.as_str()).collect();
+
+    let container = match Query::find_container_by_namespace_name_and_owners(
+        &state.db_pool,
+        &resolved_namespace,
+        name,
+        &owner_id_refs,
+    )
+    .await
+    {
+        Ok(container) => container,
+        Err(sea_orm::DbErr::RecordNotFound(_)) => {
+            return (
+                StatusCode::NOT_FOUND,
+                Json(json!({"error": "Container not found"})),
+            )
+                .into_response()
+        }
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({"error": format!("Database error: {}", e)})),
+            )
+                .into_response()
+        }
+    };
+
+    let target_base = match container_proxy_target(&container).await {
+        Ok(target) => target,
+        Err(e) => return e.into_response(),
+    };
+
+    let path_and_query = upstream_path_and_query(&uri);
+    let prefix = format!("/v1/containers/{}/{}/proxy", resolved_namespace, name);
+
+    if let Some(ws) = ws {
+        let target_url = format!(
+            "{}{}",
+            target_base.replacen("http://", "ws://", 1),
+            path_and_query
+        );
+        return proxy_websocket(ws, &target_url, &prefix, &headers).await;
+    }
+
+    forward_streaming(
+        &crate::utils::http::proxy_client(),
+        &target_base,
+        &path_and_query,
+        &prefix,
+        method,
+        &headers,
+        body,
+    )
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::routing::any;
+    use axum::Router;
+
+    /// Echoes the method, path, query and body back, with the headers it saw
+    async fn serve_echo() -> String {
+        let app = Router::new().fallback(any(
+            |method: Method, uri: Uri, headers: HeaderMap, body: Bytes| async move {
+                let seen: HashMap<String, String> = headers
+                    .iter()
+                    .map(|(k, v)| (k.to_string(), v.to_str().unwrap_or("").to_string()))
+                    .collect();
+                (
+                    [("x-echo", "yes")],
+                    Json(json!({
+                        "method": method.as_str(),
+                        "uri": uri.to_string(),
+                        "headers": seen,
+                        "body": String::from_utf8_lossy(&body),
+                    })),
+                )
+            },
+        ));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        format!("http://{}", addr)
+    }
+
+    #[tokio::test]
+    async fn test_forward_streaming_to_echo_container() {
+        // Arrange
+        let target = serve_echo().await;
+        let mut headers = HeaderMap::new();
+        headers.insert("authorization", "Bearer nebu-key".parse().unwrap());
+        headers.insert("x-custom", "kept".parse().unwrap());
+        headers.insert("connection", "x-dropped".parse().unwrap());
+        headers.insert("x-dropped", "per-hop".parse().unwrap());
+
+        // Act
+        let response = forward_streaming(
+            &reqwest::Client::new(),
+            &target,
+            "/api/items?limit=2",
+            "/v1/containers/ns/web/proxy",
+            Method::POST,
+            &headers,
+            Body::from("hello container"),
+        )
+        .await;
+
+        // Assert
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["x-echo"], "yes");
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let echoed: Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(echoed["method"], "POST");
+        assert_eq!(echoed["uri"], "/api/items?limit=2");
+        assert_eq!(echoed["body"], "hello container");
+        assert_eq!(echoed["headers"]["x-custom"], "kept");
+        assert_eq!(
+            echoed["headers"]["x-forwarded-prefix"],
+            "/v1/containers/ns/web/proxy"
+        );
+        assert!(echoed["headers"].get("authorization").is_none());
+        assert!(echoed["headers"].get("x-dropped").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_forward_streaming_passes_redirects_back() {
+        let app = Router::new().fallback(any(|| async {
+            axum::response::Redirect::temporary("http://169.254.169.254/latest/meta-data")
+        }));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        let response = forward_streaming(
+            &crate::utils::http::proxy_client(),
+            &format!("http://{}", addr),
+            "/",
+            "/v1/containers/ns/web/proxy",
+            Method::GET,
+            &HeaderMap::new(),
+            Body::empty(),
+        )
+        .await;
+
+        assert_eq!(response.status(), StatusCode::TEMPORARY_REDIRECT);
+        assert_eq!(
+            response.headers()["location"],
+            "http://169.254.169.254/latest/meta-data"
+        );
+    }
+
+    #[test]
+    fn test_upstream_path_keeps_encoding() {
+        let uri = |s: &str| s.parse::<Uri>().unwrap();
+        assert_eq!(
+            upstream_path_and_query(&uri(
+                "/v1/containers/ns/web/proxy/files/a%2Fb%20c?x=1%2B2&y"
+            )),
+            "/files/a%2Fb%20c?x=1%2B2&y"
+        );
+        assert_eq!(
+            upstream_path_and_query(&uri("/v1/containers/ns/web/proxy")),
+            "/"
+        );
+        assert_eq!(
+            upstream_path_and_query(&uri("/v1/containers/ns/web/proxy/?q=1")),
+            "/?q=1"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_forward_streaming_unreachable_container() {
+        let response = forward_streaming(
+            &reqwest::Client::new(),
+            "http://127.0.0.1:1",
+            "/",
+            "/v1/containers/ns/web/proxy",
+            Method::GET,
+            &HeaderMap::new(),
+            Body::empty(),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::BAD_GATEWAY);
+    }
+
+    /// A websocket server that echoes every data frame
+    async fn serve_ws_echo() -> String {
+        let app = Router::new().route(
+            "/echo",
+            axum::routing::get(|ws: WebSocketUpgrade| async move {
+                ws.on_upgrade(|mut socket| async move {
+                    while let Some(Ok(message)) = socket.next().await {
+                        match message {
+                            Message::Text(_) | Message::Binary(_) => {
+                                if socket.send(message).await.is_err() {
+                                    break;
+                                }
+                            }
+                            Message::Close(_) => break,
+                            _ => {}
+                        }
+                    }
+                })
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        format!("ws://{}/echo", addr)
+    }
+
+    #[tokio::test]
+    async fn test_proxy_websocket_echo() {
+        // Arrange
+        let target = serve_ws_echo().await;
+        let proxy = Router::new().route(
+            "/proxy",
+            axum::routing::get(move |ws: WebSocketUpgrade, headers: HeaderMap| {
+                let target = target.clone();
+                async move { proxy_websocket(ws, &target, "/proxy", &headers).await }
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, proxy).await.unwrap() });
+        let (mut socket, _) = tokio_tungstenite::connect_async(format!("ws://{}/proxy", addr))
+            .await
+            .unwrap();
+
+        // Act
+        socket
+            .send(UpstreamMessage::Text("hello".to_string()))
+            .await
+            .unwrap();
+        socket
+            .send(UpstreamMessage::Binary(vec![1, 2, 3]))
+            .await
+            .unwrap();
+        let first = socket.next().await.unwrap().unwrap();
+        let second = socket.next().await.unwrap().unwrap();
+        socket.close(None).await.unwrap();
+        let after_close = socket.next().await;
+
+        // Assert
+        assert_eq!(first, UpstreamMessage::Text("hello".to_string()));
+        assert_eq!(second, UpstreamMessage::Binary(vec![1, 2, 3]));
+        assert!(matches!(
+            after_close,
+            None | Some(Ok(UpstreamMessage::Close(_))) | Some(Err(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_proxy_websocket_unreachable_container() {
+        let proxy = Router::new().route(
+            "/proxy",
+            axum::routing::get(|ws: WebSocketUpgrade, headers: HeaderMap| async move {
+                proxy_websocket(ws, "ws://127.0.0.1:1/", "/proxy", &headers).await
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, proxy).await.unwrap() });
+
+        let result = tokio_tungstenite::connect_async(format!("ws://{}/proxy", addr)).await;
+        match result {
+            Err(tokio_tungstenite::tungstenite::Error::Http(response)) => {
+                assert_eq!(response.status(), StatusCode::BAD_GATEWAY)
+            }
+            other => panic!(
+                "expected a 502 handshake failure, got {:?}",
+                other.map(|_| ())
+            ),
+        }
+    }
+
+    #[test]
+    fn test_forwardable_headers_keeps_authorization_on_responses() {
+        let mut headers = HeaderMap::new();
+        headers.insert("authorization", "Bearer x".parse().unwrap());
+        headers.insert("transfer-encoding", "chunked".parse().unwrap());
+        let kept = forwardable_headers(&headers, false);
+        assert!(kept.contains_key("authorization"));
+        assert!(!kept.contains_key("transfer-encoding"));
+    }
+}
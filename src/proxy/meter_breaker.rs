@@ -0,0 +1,546 @@
+// src/proxy/meter_breaker.rs
+//
+// A circuit breaker around OpenMeter ingestion. After
+// `NEBU_OPENMETER_BREAKER_THRESHOLD` failed ingests in a row the breaker
+// opens and events are spooled in memory instead of sent, so the container
+// watch loops and the proxy don't spend their time on requests bound to fail.
+// Once `NEBU_OPENMETER_BREAKER_COOLDOWN` has passed a single ingest goes out as
+// a probe: if it succeeds the breaker closes and the spool drains, one batch
+// along with each later ingest, otherwise it opens for another cooldown. A
+// probe that doesn't come back within a cooldown, e.g. because its caller
+// went away, counts as failed.
+//
+// Events OpenMeter refuses with a 4xx would be refused again, so they're
+// logged and dropped rather than spooled; OpenMeter answered, so they don't
+// count against the breaker either.
+
+use async_trait::async_trait;
+use once_cell::sync::Lazy;
+use openmeter::{CloudEvent, MeterClient};
+use std::collections::VecDeque;
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+/// Spooled events sent along with each successful ingest
+const SPOOL_FLUSH_BATCH: usize = 100;
+
+/// The breaker every OpenMeter ingest in the process goes through
+pub static METER_INGEST: Lazy<MeterIngest> = Lazy::new(|| {
+    let config = &crate::config::SERVER_CONFIG;
+    MeterIngest::new(
+        config.openmeter_breaker_threshold,
+        config.openmeter_breaker_cooldown,
+        config.openmeter_spool_size,
+    )
+});
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BreakerState {
+    /// Ingests go out
+    Closed,
+    /// Ingests are spooled until the cooldown ends
+    Open { until: Instant },
+    /// A probe is out, other ingests are spooled until it comes back or
+    /// `deadline` passes
+    HalfOpen { deadline: Instant },
+}
+
+impl BreakerState {
+    /// The value of the `nebu_openmeter_breaker_state` gauge
+    pub fn gauge(&self) -> u8 {
+        match self {
+            BreakerState::Closed => 0,
+            BreakerState::Open { .. } => 1,
+            BreakerState::HalfOpen { .. } => 2,
+        }
+    }
+}
+
+/// Decides whether a call may go out, from how the previous ones went
+#[derive(Debug)]
+pub struct CircuitBreaker {
+    threshold: u32,
+    cooldown: Duration,
+    state: BreakerState,
+    consecutive_failures: u32,
+}
+
+impl CircuitBreaker {
+    pub fn new(threshold: u32, cooldown: Duration) -> Self {
+        Self {
+            threshold: threshold.max(1),
+            cooldown,
+            state: BreakerState::Closed,
+            consecutive_failures: 0,
+        }
+    }
+
+    pub fn state(&self) -> BreakerState {
+        self.state
+    }
+
+    pub fn consecutive_failures(&self) -> u32 {
+        self.consecutive_failures
+    }
+
+    /// Whether a call may go out at `now`. The first call after the cooldown
+    /// is let through as the probe, turning the breaker half-open, and so is
+    /// the first after a probe that never came back.
+    pub fn allow(&mut self, now: Instant) -> bool {
+        match self.state {
+            BreakerState::Closed => true,
+            BreakerState::Open { until: deadline } | BreakerState::HalfOpen { deadline }
+                if now >= deadline =>
+            {
+                self.state = BreakerState::HalfOpen {
+                    deadline: now + self.cooldown,
+                };
+                true
+            }
+            BreakerState::Open { .. } | BreakerState::HalfOpen { .. } => false,
+        }
+    }
+
+    pub fn record_success(&mut self) {
+        self.state = BreakerState::Closed;
+        self.consecutive_failures = 0;
+    }
+
+    /// Counts a failed call at `now`, opening the breaker when it was the
+    /// probe or reached the threshold
+    pub fn record_failure(&mut self, now: Instant) {
+        self.consecutive_failures = self.consecutive_failures.saturating_add(1);
+        if matches!(self.state, BreakerState::HalfOpen { .. })
+            || self.consecutive_failures >= self.threshold
+        {
+            self.state = BreakerState::Open {
+                until: now + self.cooldown,
+            };
+        }
+    }
+}
+
+/// Why an ingest failed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IngestError {
+    /// OpenMeter couldn't be reached or failed itself; worth retrying
+    Unavailable(String),
+    /// OpenMeter refused the events; sending them again won't help
+    Rejected(String),
+}
+
+impl fmt::Display for IngestError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IngestError::Unavailable(e) => write!(f, "{}", e),
+            IngestError::Rejected(e) => write!(f, "rejected: {}", e),
+        }
+    }
+}
+
+/// Where ingested events are sent, the OpenMeter client outside of tests
+#[async_trait]
+pub trait MeterSink: Sync {
+    async fn ingest(&self, events: &[CloudEvent]) -> Result<(), IngestError>;
+}
+
+#[async_trait]
+impl MeterSink for MeterClient {
+    async fn ingest(&self, events: &[CloudEvent]) -> Result<(), IngestError> {
+        self.ingest_events(events).await.map_err(|e| {
+            // Timeouts and rate limits pass; any other 4xx is about the events
+            let rejected = e.status().is_some_and(|status| {
+                status.is_client_error()
+                    && status != reqwest::StatusCode::REQUEST_TIMEOUT
+                    && status != reqwest::StatusCode::TOO_MANY_REQUESTS
+            });
+            if rejected {
+                IngestError::Rejected(e.to_string())
+            } else {
+                IngestError::Unavailable(e.to_string())
+            }
+        })
+    }
+}
+
+/// What became of ingested events
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Delivery {
+    Sent,
+    /// Held back while the breaker is open, sent once it closes
+    Spooled,
+}
+
+/// A snapshot of `MeterIngest` for `/metrics`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MeterIngestStats {
+    pub state: BreakerState,
+    pub consecutive_failures: u32,
+    pub spooled: usize,
+    /// Events dropped because the spool was full
+    pub dropped: u64,
+    /// Events dropped because OpenMeter refused them
+    pub rejected: u64,
+}
+
+/// OpenMeter ingestion behind a circuit breaker, with a bounded spool for
+/// the events held back while it's open
+pub struct MeterIngest {
+    breaker: Mutex<CircuitBreaker>,
+    spool: Mutex<VecDeque<CloudEvent>>,
+    spool_size: usize,
+    dropped: AtomicU64,
+    rejected: AtomicU64,
+}
+
+impl MeterIngest {
+    pub fn new(threshold: u32, cooldown: Duration, spool_size: usize) -> Self {
+        Self {
+            breaker: Mutex::new(CircuitBreaker::new(threshold, cooldown)),
+            spool: Mutex::new(VecDeque::new()),
+            spool_size,
+            dropped: AtomicU64::new(0),
+            rejected: AtomicU64::new(0),
+        }
+    }
+
+    /// Sends `events` to `sink`, or spools them while the breaker is open. A
+    /// failed send spools them as well and returns the error; refused ones
+    /// are dropped.
+    pub async fn ingest(
+        &self,
+        sink: &dyn MeterSink,
+        events: Vec<CloudEvent>,
+    ) -> Result<Delivery, String> {
+        if !self.breaker.lock().unwrap().allow(Instant::now()) {
+            self.spool_back(events);
+            return Ok(Delivery::Spooled);
+        }
+
+        match sink.ingest(&events).await {
+            Ok(()) => self.record_success(),
+            Err(IngestError::Unavailable(e)) => {
+                self.record_failure(&e);
+                self.spool_back(events);
+                return Err(e);
+            }
+            Err(e @ IngestError::Rejected(_)) => {
+                self.record_success();
+                self.reject(&events, &e);
+                return Err(e.to_string());
+            }
+        }
+
+        let batch = self.take_batch();
+        if !batch.is_empty() {
+            match sink.ingest(&batch).await {
+                Ok(()) => {}
+                Err(IngestError::Unavailable(e)) => {
+                    self.record_failure(&e);
+                    self.spool_front(batch);
+                }
+                // Some of the batch was refused; find out which one by one
+                Err(IngestError::Rejected(_)) => self.flush_one_by_one(sink, batch).await,
+            }
+        }
+        Ok(Delivery::Sent)
+    }
+
+    /// Sends spooled `events` one at a time, dropping those refused and
+    /// putting the rest back once OpenMeter stops answering
+    async fn flush_one_by_one(&self, sink: &dyn MeterSink, events: Vec<CloudEvent>) {
+        let mut events = events.into_iter();
+        while let Some(event) = events.next() {
+            match sink.ingest(std::slice::from_ref(&event)).await {
+                Ok(()) => {}
+                Err(e @ IngestError::Rejected(_)) => self.reject(&[event], &e),
+                Err(IngestError::Unavailable(e)) => {
+                    self.record_failure(&e);
+                    self.spool_front(std::iter::once(event).chain(events).collect());
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Drops events OpenMeter refused, logging enough to replay them by hand
+    fn reject(&self, events: &[CloudEvent], error: &IngestError) {
+        self.rejected
+            .fetch_add(events.len() as u64, Ordering::Relaxed);
+        for event in events {
+            warn!(
+                "[Metrics] Dropping meter event {} for {} ({}): {}",
+                event.id, event.subject, event.r#type, error
+            );
+        }
+    }
+
+    pub fn stats(&self) -> MeterIngestStats {
+        let breaker = self.breaker.lock().unwrap();
+        MeterIngestStats {
+            state: breaker.state(),
+            consecutive_failures: breaker.consecutive_failures(),
+            spooled: self.spool.lock().unwrap().len(),
+            dropped: self.dropped.load(Ordering::Relaxed),
+            rejected: self.rejected.load(Ordering::Relaxed),
+        }
+    }
+
+    fn record_success(&self) {
+        let mut breaker = self.breaker.lock().unwrap();
+        if breaker.state() != BreakerState::Closed {
+            info!("[Metrics] OpenMeter ingest succeeded, closing the breaker");
+        }
+        breaker.record_success();
+    }
+
+    fn record_failure(&self, error: &str) {
+        let mut breaker = self.breaker.lock().unwrap();
+        let was_open = matches!(breaker.state(), BreakerState::Open { .. });
+        breaker.record_failure(Instant::now());
+        if !was_open && matches!(breaker.state(), BreakerState::Open { .. }) {
+            warn!(
+                "[Metrics] OpenMeter ingest failed {} time(s) in a row, spooling events: {}",
+                breaker.consecutive_failures(),
+                error
+            );
+        }
+    }
+
+    fn take_batch(&self) -> Vec<CloudEvent> {
+        let mut spool = self.spool.lock().unwrap();
+        let n = spool.len().min(SPOOL_FLUSH_BATCH);
+        spool.drain(..n).collect()
+    }
+
+    /// Queues newer events, dropping the oldest beyond `spool_size`
+    fn spool_back(&self, events: Vec<CloudEvent>) {
+        let mut spool = self.spool.lock().unwrap();
+        spool.extend(events);
+        self.trim(&mut spool);
+    }
+
+    /// Puts back events taken from the front of the spool
+    fn spool_front(&self, events: Vec<CloudEvent>) {
+        let mut spool = self.spool.lock().unwrap();
+        for event in events.into_iter().rev() {
+            spool.push_front(event);
+        }
+        self.trim(&mut spool);
+    }
+
+    fn trim(&self, spool: &mut VecDeque<CloudEvent>) {
+        let excess = spool.len().saturating_sub(self.spool_size);
+        if excess > 0 {
+            spool.drain(..excess);
+            self.dropped.fetch_add(excess as u64, Ordering::Relaxed);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicBool;
+
+    /// Records what it was sent, failing while `failing` is set and refusing
+    /// any batch with an event whose id starts with `poison`
+    #[derive(Default)]
+    struct FakeSink {
+        failing: AtomicBool,
+        sent: Mutex<Vec<String>>,
+        calls: AtomicU64,
+    }
+
+    #[async_trait]
+    impl MeterSink for FakeSink {
+        async fn ingest(&self, events: &[CloudEvent]) -> Result<(), IngestError> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            if self.failing.load(Ordering::SeqCst) {
+                return Err(IngestError::Unavailable(
+                    "503 Service Unavailable".to_string(),
+                ));
+            }
+            if events.iter().any(|e| e.id.starts_with("poison")) {
+                return Err(IngestError::Rejected("400 Bad Request".to_string()));
+            }
+            let mut sent = self.sent.lock().unwrap();
+            sent.extend(events.iter().map(|e| e.id.clone()));
+            Ok(())
+        }
+    }
+
+    fn event(id: &str) -> CloudEvent {
+        CloudEvent {
+            id: id.to_string(),
+            source: "nebulous-test".to_string(),
+            specversion: "1.0".to_string(),
+            r#type: "runtime".to_string(),
+            subject: "container-1".to_string(),
+            time: None,
+            dataschema: None,
+            datacontenttype: None,
+            data: None,
+        }
+    }
+
+    #[test]
+    fn test_breaker_opens_half_opens_and_closes() {
+        // Arrange
+        let start = Instant::now();
+        let cooldown = Duration::from_secs(30);
+        let mut breaker = CircuitBreaker::new(3, cooldown);
+
+        // Act / Assert: failures below the threshold keep it closed
+        assert!(breaker.allow(start));
+        breaker.record_failure(start);
+        breaker.record_failure(start);
+        assert_eq!(breaker.state(), BreakerState::Closed);
+
+        // Reaching the threshold opens it for the cooldown
+        breaker.record_failure(start);
+        assert_eq!(
+            breaker.state(),
+            BreakerState::Open {
+                until: start + cooldown
+            }
+        );
+        assert!(!breaker.allow(start + Duration::from_secs(29)));
+
+        // After the cooldown one probe goes out, the rest wait for it
+        let probe_at = start + cooldown;
+        assert!(breaker.allow(probe_at));
+        assert_eq!(
+            breaker.state(),
+            BreakerState::HalfOpen {
+                deadline: probe_at + cooldown
+            }
+        );
+        assert!(!breaker.allow(probe_at));
+
+        // A successful probe closes it
+        breaker.record_success();
+        assert_eq!(breaker.state(), BreakerState::Closed);
+        assert_eq!(breaker.consecutive_failures(), 0);
+        assert!(breaker.allow(probe_at));
+    }
+
+    #[test]
+    fn test_failed_probe_reopens_breaker() {
+        let start = Instant::now();
+        let cooldown = Duration::from_secs(10);
+        let mut breaker = CircuitBreaker::new(2, cooldown);
+        breaker.record_failure(start);
+        breaker.record_failure(start);
+
+        let probe_at = start + cooldown;
+        assert!(breaker.allow(probe_at));
+        breaker.record_failure(probe_at);
+
+        assert_eq!(
+            breaker.state(),
+            BreakerState::Open {
+                until: probe_at + cooldown
+            }
+        );
+        assert!(!breaker.allow(probe_at + Duration::from_secs(1)));
+        assert_eq!(breaker.state().gauge(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_open_breaker_spools_until_probe_succeeds() {
+        let ingest = MeterIngest::new(2, Duration::ZERO, 3);
+        let sink = FakeSink::default();
+        sink.failing.store(true, Ordering::SeqCst);
+
+        // Two failures open the breaker, spooling what failed
+        assert!(ingest.ingest(&sink, vec![event("a")]).await.is_err());
+        assert!(ingest.ingest(&sink, vec![event("b")]).await.is_err());
+        let stats = ingest.stats();
+        assert!(matches!(stats.state, BreakerState::Open { .. }));
+        assert_eq!(stats.consecutive_failures, 2);
+        assert_eq!(stats.spooled, 2);
+
+        // Without a cooldown the next ingest is the probe; it fails and the
+        // spool overflows, dropping the oldest event
+        assert!(ingest
+            .ingest(&sink, vec![event("c"), event("d")])
+            .await
+            .is_err());
+        assert_eq!(ingest.stats().spooled, 3);
+        assert_eq!(ingest.stats().dropped, 1);
+
+        // A successful probe closes the breaker and flushes the spool
+        sink.failing.store(false, Ordering::SeqCst);
+        let delivery = ingest.ingest(&sink, vec![event("e")]).await.unwrap();
+        assert_eq!(delivery, Delivery::Sent);
+        assert_eq!(*sink.sent.lock().unwrap(), vec!["e", "b", "c", "d"]);
+        let stats = ingest.stats();
+        assert_eq!(stats.state, BreakerState::Closed);
+        assert_eq!(stats.spooled, 0);
+    }
+
+    #[tokio::test]
+    async fn test_open_breaker_makes_no_calls() {
+        let ingest = MeterIngest::new(1, Duration::from_secs(60), 10);
+        let sink = FakeSink::default();
+        sink.failing.store(true, Ordering::SeqCst);
+        assert!(ingest.ingest(&sink, vec![event("a")]).await.is_err());
+
+        let delivery = ingest.ingest(&sink, vec![event("b")]).await.unwrap();
+
+        assert_eq!(delivery, Delivery::Spooled);
+        assert_eq!(sink.calls.load(Ordering::SeqCst), 1);
+        assert_eq!(ingest.stats().spooled, 2);
+    }
+
+    #[test]
+    fn test_lost_probe_lets_another_through() {
+        let start = Instant::now();
+        let cooldown = Duration::from_secs(10);
+        let mut breaker = CircuitBreaker::new(1, cooldown);
+        breaker.record_failure(start);
+
+        // The probe's caller goes away without recording anything
+        let probe_at = start + cooldown;
+        assert!(breaker.allow(probe_at));
+        assert!(!breaker.allow(probe_at + Duration::from_secs(9)));
+
+        let retry_at = probe_at + cooldown;
+        assert!(breaker.allow(retry_at));
+        assert_eq!(
+            breaker.state(),
+            BreakerState::HalfOpen {
+                deadline: retry_at + cooldown
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_refused_events_are_dropped_not_spooled() {
+        let ingest = MeterIngest::new(1, Duration::ZERO, 10);
+        let sink = FakeSink::default();
+
+        let result = ingest.ingest(&sink, vec![event("poison-1")]).await;
+
+        assert!(result.unwrap_err().contains("400 Bad Request"));
+        let stats = ingest.stats();
+        assert_eq!(stats.state, BreakerState::Closed);
+        assert_eq!((stats.spooled, stats.rejected), (0, 1));
+
+        // A refused spooled batch is sent one by one, so only the bad event goes
+        sink.failing.store(true, Ordering::SeqCst);
+        for id in ["a", "poison-2", "b"] {
+            assert!(ingest.ingest(&sink, vec![event(id)]).await.is_err());
+        }
+        sink.failing.store(false, Ordering::SeqCst);
+        ingest.ingest(&sink, vec![event("c")]).await.unwrap();
+
+        assert_eq!(*sink.sent.lock().unwrap(), vec!["c", "a", "b"]);
+        let stats = ingest.stats();
+        assert_eq!((stats.spooled, stats.rejected), (0, 2));
+    }
+}
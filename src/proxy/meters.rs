@@ -1,22 +1,76 @@
+use crate::models::V1Meter;
 use crate::proxy::authz::extract_json_path;
+use crate::proxy::meter_breaker::{Delivery, METER_INGEST};
+use once_cell::sync::OnceCell;
 use openmeter::{CloudEvent, MeterClient};
 use serde_json::Value;
 use short_uuid::ShortUuid;
 use tracing::{debug, error, warn};
 
+/// One OpenMeter client for the whole process, built from `OPENMETER_URL`
+/// and `OPENMETER_TOKEN` on first successful use
+static METER_CLIENT: OnceCell<MeterClient> = OnceCell::new();
+
+/// The shared OpenMeter client, or why it couldn't be configured. Failures
+/// aren't kept, so the next call tries again.
+pub fn meter_client() -> Result<&'static MeterClient, String> {
+    METER_CLIENT.get_or_try_init(|| {
+        let openmeter_url = std::env::var("OPENMETER_URL")
+            .map_err(|_| "OPENMETER_URL environment variable not set".to_string())?;
+        let openmeter_token = std::env::var("OPENMETER_TOKEN")
+            .map_err(|_| "OPENMETER_TOKEN environment variable not set".to_string())?;
+        Ok(MeterClient::new(openmeter_url, openmeter_token))
+    })
+}
+
+/// The CloudEvent source for meters emitted by `component`, unless the
+/// deployment sets `NEBU_OPENMETER_SOURCE`.
+pub fn meter_source(component: &str) -> String {
+    crate::config::SERVER_CONFIG
+        .openmeter_source
+        .clone()
+        .unwrap_or_else(|| component.to_string())
+}
+
+/// Builds the CloudEvent for one meter reading. The meter's `dimensions` are
+/// merged into `data` without overwriting the built-in fields billing relies
+/// on, and its `subject` replaces `default_subject` when set.
+pub fn meter_event(
+    id: String,
+    source: String,
+    default_subject: &str,
+    meter: &V1Meter,
+    mut data: Value,
+) -> CloudEvent {
+    if let (Some(fields), Some(dimensions)) = (data.as_object_mut(), &meter.dimensions) {
+        for (key, value) in dimensions {
+            fields
+                .entry(key.clone())
+                .or_insert_with(|| Value::String(value.clone()));
+        }
+    }
+    CloudEvent {
+        id,
+        source,
+        specversion: "1.0".to_string(),
+        r#type: meter.metric.clone(),
+        subject: meter
+            .subject
+            .clone()
+            .unwrap_or_else(|| default_subject.to_string()),
+        time: Some(chrono::Utc::now().to_rfc3339()),
+        dataschema: None,
+        datacontenttype: Some("application/json".to_string()),
+        data: Some(data),
+    }
+}
+
 pub async fn send_request_metrics(
     container_id: &str,
-    meters: &[crate::models::V1Meter],
+    meters: &[V1Meter],
     json_body_opt: &Option<Value>,
 ) -> Result<(), String> {
-    // Get OpenMeter configuration from environment
-    let openmeter_url = std::env::var("OPENMETER_URL")
-        .map_err(|_| "OPENMETER_URL environment variable not set".to_string())?;
-
-    let openmeter_token = std::env::var("OPENMETER_TOKEN")
-        .map_err(|_| "OPENMETER_TOKEN environment variable not set".to_string())?;
-
-    let meter_client = MeterClient::new(openmeter_url, openmeter_token);
+    let meter_client = meter_client()?;
 
     for meter in meters {
         // Skip meters that are not for request processing
@@ -64,31 +118,32 @@ pub async fn send_request_metrics(
         debug!("[PROXY] request metrics data: {data:?}");
 
         // Create CloudEvent
-        let cloud_event = CloudEvent {
-            id: ShortUuid::generate().to_string(),
-            source: "nebulous-proxy".to_string(),
-            specversion: "1.0".to_string(),
-            r#type: meter.metric.clone(),
-            subject: container_id.to_string(),
-            time: Some(chrono::Utc::now().to_rfc3339()),
-            dataschema: None,
-            datacontenttype: Some("application/json".to_string()),
-            data: Some(data),
-        };
+        let cloud_event = meter_event(
+            ShortUuid::generate().to_string(),
+            meter_source("nebulous-proxy"),
+            container_id,
+            meter,
+            data,
+        );
 
         // Send the event to OpenMeter
-        if let Err(e) = meter_client.ingest_events(&[cloud_event]).await {
-            error!(
-                "[Metrics] Failed to report meter {:?} for container {}: {}",
-                meter, container_id, e
-            );
-            return Err(format!("Failed to send metrics: {}", e));
+        match METER_INGEST.ingest(meter_client, vec![cloud_event]).await {
+            Ok(Delivery::Sent) => debug!(
+                "[Metrics] Successfully reported meter {:?} for container {}",
+                meter, container_id
+            ),
+            Ok(Delivery::Spooled) => debug!(
+                "[Metrics] OpenMeter breaker is open, spooled meter {:?} for container {}",
+                meter, container_id
+            ),
+            Err(e) => {
+                error!(
+                    "[Metrics] Failed to report meter {:?} for container {}: {}",
+                    meter, container_id, e
+                );
+                return Err(format!("Failed to send metrics: {}", e));
+            }
         }
-
-        debug!(
-            "[Metrics] Successfully reported meter {:?} for container {}",
-            meter, container_id
-        );
     }
 
     Ok(())
@@ -96,17 +151,10 @@ pub async fn send_request_metrics(
 
 pub async fn send_response_metrics(
     container_id: &str,
-    meters: &[crate::models::V1Meter],
+    meters: &[V1Meter],
     json_response: &Value,
 ) -> Result<(), String> {
-    // Get OpenMeter configuration from environment
-    let openmeter_url = std::env::var("OPENMETER_URL")
-        .map_err(|_| "OPENMETER_URL environment variable not set".to_string())?;
-
-    let openmeter_token = std::env::var("OPENMETER_TOKEN")
-        .map_err(|_| "OPENMETER_TOKEN environment variable not set".to_string())?;
-
-    let meter_client = MeterClient::new(openmeter_url, openmeter_token);
+    let meter_client = meter_client()?;
 
     for meter in meters {
         // Only process response_value meters
@@ -151,32 +199,102 @@ pub async fn send_response_metrics(
         debug!("[PROXY] response metrics data: {data:?}");
 
         // Create CloudEvent
-        let cloud_event = CloudEvent {
-            id: ShortUuid::generate().to_string(),
-            source: "nebulous-proxy".to_string(),
-            specversion: "1.0".to_string(),
-            r#type: meter.metric.clone(),
-            subject: container_id.to_string(),
-            time: Some(chrono::Utc::now().to_rfc3339()),
-            dataschema: None,
-            datacontenttype: Some("application/json".to_string()),
-            data: Some(data),
-        };
+        let cloud_event = meter_event(
+            ShortUuid::generate().to_string(),
+            meter_source("nebulous-proxy"),
+            container_id,
+            meter,
+            data,
+        );
 
         // Send the event to OpenMeter
-        if let Err(e) = meter_client.ingest_events(&[cloud_event]).await {
-            error!(
-                "[Metrics] Failed to report response meter {:?} for container {}: {}",
-                meter, container_id, e
-            );
-            return Err(format!("Failed to send response metrics: {}", e));
+        match METER_INGEST.ingest(meter_client, vec![cloud_event]).await {
+            Ok(Delivery::Sent) => debug!(
+                "[Metrics] Successfully reported response meter {:?} for container {}",
+                meter, container_id
+            ),
+            Ok(Delivery::Spooled) => debug!(
+                "[Metrics] OpenMeter breaker is open, spooled response meter {:?} for container {}",
+                meter, container_id
+            ),
+            Err(e) => {
+                error!(
+                    "[Metrics] Failed to report response meter {:?} for container {}: {}",
+                    meter, container_id, e
+                );
+                return Err(format!("Failed to send response metrics: {}", e));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn runtime_meter() -> V1Meter {
+        V1Meter {
+            cost: Some(0.1),
+            costp: None,
+            currency: "USD".to_string(),
+            unit: "second".to_string(),
+            metric: "runtime".to_string(),
+            json_path: None,
+            dimensions: None,
+            subject: None,
         }
+    }
 
-        debug!(
-            "[Metrics] Successfully reported response meter {:?} for container {}",
-            meter, container_id
+    #[test]
+    fn test_meter_event_includes_custom_dimensions() {
+        // Arrange
+        let meter = V1Meter {
+            dimensions: Some(HashMap::from([
+                ("project".to_string(), "search".to_string()),
+                ("model".to_string(), "llama-3".to_string()),
+            ])),
+            ..runtime_meter()
+        };
+        let data = serde_json::json!({"value": 60.0, "metric": "runtime"});
+
+        // Act
+        let event = meter_event(
+            "evt-1".to_string(),
+            "acme-billing".to_string(),
+            "owner@example.com",
+            &meter,
+            data,
         );
+
+        // Assert
+        let data = event.data.unwrap();
+        assert_eq!(data["project"], "search");
+        assert_eq!(data["model"], "llama-3");
+        assert_eq!(data["value"], 60.0);
+        assert_eq!(event.source, "acme-billing");
+        assert_eq!(event.subject, "owner@example.com");
     }
 
-    Ok(())
+    #[test]
+    fn test_meter_event_dimensions_do_not_override_builtin_fields() {
+        let meter = V1Meter {
+            dimensions: Some(HashMap::from([("metric".to_string(), "free".to_string())])),
+            subject: Some("team-a".to_string()),
+            ..runtime_meter()
+        };
+
+        let event = meter_event(
+            "evt-2".to_string(),
+            "nebulous-proxy".to_string(),
+            "container-1",
+            &meter,
+            serde_json::json!({"metric": "runtime"}),
+        );
+
+        assert_eq!(event.data.unwrap()["metric"], "runtime");
+        assert_eq!(event.subject, "team-a");
+    }
 }
@@ -1,4 +1,5 @@
 pub mod authz;
 pub mod containers;
+pub mod meter_breaker;
 pub mod meters;
 pub mod server;
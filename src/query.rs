@@ -1,4 +1,5 @@
 // src/query.rs
+use crate::entities::container_events;
 use crate::entities::containers;
 use crate::entities::namespaces;
 use crate::entities::processors;
@@ -18,10 +19,36 @@ impl Query {
         owners: &[&str],
     ) -> Result<Vec<containers::Model>, DbErr> {
         containers::Entity::find()
+            .filter(containers::Column::DeletedAt.is_null())
             .filter(containers::Column::Owner.is_in(owners.iter().copied()))
             .all(db)
             .await
     }
+
+    /// Like `find_containers_by_owners`, but also returns soft-deleted containers
+    pub async fn find_containers_by_owners_including_deleted(
+        db: &DatabaseConnection,
+        owners: &[&str],
+    ) -> Result<Vec<containers::Model>, DbErr> {
+        containers::Entity::find()
+            .filter(containers::Column::Owner.is_in(owners.iter().copied()))
+            .all(db)
+            .await
+    }
+
+    /// Every container in a namespace for the given owners, soft-deleted ones included
+    pub async fn find_containers_by_namespace_and_owners_including_deleted(
+        db: &DatabaseConnection,
+        namespace: &str,
+        owners: &[&str],
+    ) -> Result<Vec<containers::Model>, DbErr> {
+        containers::Entity::find()
+            .filter(containers::Column::Namespace.eq(namespace))
+            .filter(containers::Column::Owner.is_in(owners.iter().copied()))
+            .all(db)
+            .await
+    }
+
     pub async fn find_container_by_id(
         db: &DatabaseConnection,
         id: String,
@@ -35,6 +62,7 @@ impl Query {
         name: &str,
     ) -> Result<Option<containers::Model>, DbErr> {
         containers::Entity::find()
+            .filter(containers::Column::DeletedAt.is_null())
             .filter(containers::Column::Namespace.eq(namespace))
             .filter(containers::Column::Name.eq(name))
             .one(db)
@@ -48,6 +76,7 @@ impl Query {
         owners: &[&str],
     ) -> Result<containers::Model, DbErr> {
         let result = containers::Entity::find()
+            .filter(containers::Column::DeletedAt.is_null())
             .filter(containers::Column::Namespace.eq(namespace))
             .filter(containers::Column::Name.eq(name))
             .filter(containers::Column::Owner.is_in(owners.iter().copied()))
@@ -59,10 +88,50 @@ impl Query {
         )))
     }
 
+    /// Like `find_container_by_namespace_name_and_owners`, but also matches
+    /// soft-deleted containers. The most recently created match is returned.
+    pub async fn find_container_by_namespace_name_and_owners_including_deleted(
+        db: &DatabaseConnection,
+        namespace: &str,
+        name: &str,
+        owners: &[&str],
+    ) -> Result<containers::Model, DbErr> {
+        let result = containers::Entity::find()
+            .filter(containers::Column::Namespace.eq(namespace))
+            .filter(containers::Column::Name.eq(name))
+            .filter(containers::Column::Owner.is_in(owners.iter().copied()))
+            .order_by_desc(containers::Column::CreatedAt)
+            .one(db)
+            .await?;
+
+        result.ok_or(DbErr::RecordNotFound(format!(
+            "Container with namespace '{namespace}' and name '{name}' not found for the specified owners"
+        )))
+    }
+
     pub async fn find_container_by_id_and_owners(
         db: &DatabaseConnection,
         id: &str,
         owners: &[&str],
+    ) -> Result<containers::Model, DbErr> {
+        let result = containers::Entity::find()
+            .filter(containers::Column::DeletedAt.is_null())
+            .filter(containers::Column::Id.eq(id))
+            .filter(containers::Column::Owner.is_in(owners.iter().copied()))
+            .one(db)
+            .await?;
+
+        result.ok_or(DbErr::RecordNotFound(format!(
+            "Container with id '{}' not found for the specified owners",
+            id
+        )))
+    }
+
+    /// Like `find_container_by_id_and_owners`, but also matches soft-deleted containers
+    pub async fn find_container_by_id_and_owners_including_deleted(
+        db: &DatabaseConnection,
+        id: &str,
+        owners: &[&str],
     ) -> Result<containers::Model, DbErr> {
         let result = containers::Entity::find()
             .filter(containers::Column::Id.eq(id))
@@ -102,7 +171,21 @@ impl Query {
     pub async fn find_all_containers(
         db: &DatabaseConnection,
     ) -> Result<Vec<containers::Model>, DbErr> {
-        containers::Entity::find().all(db).await
+        containers::Entity::find()
+            .filter(containers::Column::DeletedAt.is_null())
+            .all(db)
+            .await
+    }
+
+    /// Fetches every container on a platform, soft-deleted ones included
+    pub async fn find_containers_by_platform_including_deleted(
+        db: &DatabaseConnection,
+        platform: &str,
+    ) -> Result<Vec<containers::Model>, DbErr> {
+        containers::Entity::find()
+            .filter(containers::Column::Platform.eq(platform))
+            .all(db)
+            .await
     }
 
     /// Fetches the status of a container by its ID
@@ -154,8 +237,25 @@ impl Query {
                 [Value::from(status)],
             ));
         }
+        // Stopped ones only when they're to be resumed
+        status_condition = status_condition.add(
+            Condition::all()
+                .add(Expr::cust_with_values(
+                    "lower(status->>'status') = $1",
+                    [Value::from(
+                        ContainerStatus::Stopped.to_string().to_lowercase(),
+                    )],
+                ))
+                .add(Expr::cust_with_values(
+                    "lower(desired_status) = $1",
+                    [Value::from(
+                        ContainerStatus::Running.to_string().to_lowercase(),
+                    )],
+                )),
+        );
 
         containers::Entity::find()
+            .filter(containers::Column::DeletedAt.is_null())
             .filter(status_condition)
             .all(db)
             .await
@@ -168,6 +268,7 @@ impl Query {
     ) -> Result<Vec<containers::Model>, DbErr> {
         let lowercase_status = status.to_string().to_lowercase();
         containers::Entity::find()
+            .filter(containers::Column::DeletedAt.is_null())
             .filter(Expr::cust_with_values(
                 "lower(status->>'status') = $1",
                 [Value::from(lowercase_status)],
@@ -176,74 +277,18 @@ impl Query {
             .await
     }
 
-    /// Return true if no other containers in the same queue
-    /// are in an active or running state. Otherwise false.
-    pub async fn is_queue_free(
+    /// Fetches the other containers in a queue, deleted ones excluded
+    pub async fn find_other_containers_in_queue(
         db: &DatabaseConnection,
         queue_name: &str,
         this_container_id: &str,
-    ) -> Result<bool, DbErr> {
-        // Define which statuses qualify as "active/running"
-        // i.e., statuses that imply the container is still going or starting
-        let active_like_statuses = vec![
-            ContainerStatus::Defined.to_string().to_lowercase(),
-            ContainerStatus::Creating.to_string().to_lowercase(),
-            ContainerStatus::Created.to_string().to_lowercase(),
-            ContainerStatus::Queued.to_string().to_lowercase(),
-            ContainerStatus::Pending.to_string().to_lowercase(),
-            ContainerStatus::Running.to_string().to_lowercase(),
-            ContainerStatus::Restarting.to_string().to_lowercase(),
-        ];
-
-        // Build a condition that checks if lower(status->>'status') is in one of these
-        let mut active_condition = Condition::any();
-        for status_str in active_like_statuses {
-            active_condition = active_condition.add(Expr::cust_with_values(
-                "lower(status->>'status') = $1",
-                [Value::from(status_str)],
-            ));
-        }
-
-        // First check if there's any active container in the queue
-        let another_active_container = containers::Entity::find()
-            .filter(containers::Column::Queue.eq(queue_name))
-            .filter(containers::Column::Id.ne(this_container_id))
-            .filter(active_condition)
-            .one(db)
-            .await?;
-
-        if another_active_container.is_some() {
-            return Ok(false);
-        }
-
-        // If no active containers, check if this is the next container in line
-        // Compare against lowercase 'queued'
-        let queued_status_lower = ContainerStatus::Queued.to_string().to_lowercase();
-        let next_container = containers::Entity::find()
+    ) -> Result<Vec<containers::Model>, DbErr> {
+        containers::Entity::find()
+            .filter(containers::Column::DeletedAt.is_null())
             .filter(containers::Column::Queue.eq(queue_name))
             .filter(containers::Column::Id.ne(this_container_id))
-            .filter(Expr::cust_with_values(
-                "lower(status->>'status') = $1",
-                [Value::from(queued_status_lower)],
-            ))
-            .order_by_asc(containers::Column::CreatedAt)
-            .one(db)
-            .await?;
-
-        // If there's a queued container with an earlier creation time, this container should wait
-        if let Some(earlier_container) = next_container {
-            let this_container = containers::Entity::find_by_id(this_container_id)
-                .one(db)
-                .await?;
-
-            if let Some(this_container) = this_container {
-                if earlier_container.created_at < this_container.created_at {
-                    return Ok(false);
-                }
-            }
-        }
-
-        Ok(true)
+            .all(db)
+            .await
     }
 
     /// Fetch and decrypt `(private_key, public_key)` for a container by ID.
@@ -343,6 +388,18 @@ impl Query {
     pub async fn find_processors_by_owners(
         db: &DatabaseConnection,
         owners: &[&str],
+    ) -> Result<Vec<processors::Model>, DbErr> {
+        processors::Entity::find()
+            .filter(processors::Column::DeletedAt.is_null())
+            .filter(processors::Column::Owner.is_in(owners.iter().copied()))
+            .all(db)
+            .await
+    }
+
+    /// Like `find_processors_by_owners`, but also returns soft-deleted processors
+    pub async fn find_processors_by_owners_including_deleted(
+        db: &DatabaseConnection,
+        owners: &[&str],
     ) -> Result<Vec<processors::Model>, DbErr> {
         processors::Entity::find()
             .filter(processors::Column::Owner.is_in(owners.iter().copied()))
@@ -358,6 +415,7 @@ impl Query {
         owners: &[&str],
     ) -> Result<processors::Model, DbErr> {
         let result = processors::Entity::find()
+            .filter(processors::Column::DeletedAt.is_null())
             .filter(processors::Column::Namespace.eq(namespace))
             .filter(processors::Column::Name.eq(name))
             .filter(processors::Column::Owner.is_in(owners.iter().copied()))
@@ -369,6 +427,27 @@ impl Query {
         )))
     }
 
+    /// Like `find_processor_by_namespace_name_and_owners`, but also matches
+    /// soft-deleted processors. The most recently created match is returned.
+    pub async fn find_processor_by_namespace_name_and_owners_including_deleted(
+        db: &DatabaseConnection,
+        namespace: &str,
+        name: &str,
+        owners: &[&str],
+    ) -> Result<processors::Model, DbErr> {
+        let result = processors::Entity::find()
+            .filter(processors::Column::Namespace.eq(namespace))
+            .filter(processors::Column::Name.eq(name))
+            .filter(processors::Column::Owner.is_in(owners.iter().copied()))
+            .order_by_desc(processors::Column::CreatedAt)
+            .one(db)
+            .await?;
+
+        result.ok_or(DbErr::RecordNotFound(format!(
+            "Processor with namespace '{namespace}' and name '{name}' not found for the specified owners"
+        )))
+    }
+
     /// Finds a processor by ID and owners
     pub async fn find_processor_by_id_and_owners(
         db: &DatabaseConnection,
@@ -376,6 +455,7 @@ impl Query {
         owners: &[&str],
     ) -> Result<processors::Model, DbErr> {
         let result = processors::Entity::find()
+            .filter(processors::Column::DeletedAt.is_null())
             .filter(processors::Column::Id.eq(id))
             .filter(processors::Column::Owner.is_in(owners.iter().copied()))
             .one(db)
@@ -415,6 +495,7 @@ impl Query {
         }
 
         processors::Entity::find()
+            .filter(processors::Column::DeletedAt.is_null())
             .filter(status_condition)
             .all(db)
             .await
@@ -426,11 +507,24 @@ impl Query {
         owner_ref_value: &str,
     ) -> Result<Vec<containers::Model>, DbErr> {
         containers::Entity::find()
+            .filter(containers::Column::DeletedAt.is_null())
             .filter(containers::Column::OwnerRef.eq(owner_ref_value))
             .all(db)
             .await
     }
 
+    /// Find the status transition events for a container, oldest first
+    pub async fn find_container_events(
+        db: &DatabaseConnection,
+        container_id: &str,
+    ) -> Result<Vec<container_events::Model>, DbErr> {
+        container_events::Entity::find()
+            .filter(container_events::Column::ContainerId.eq(container_id))
+            .order_by_asc(container_events::Column::CreatedAt)
+            .all(db)
+            .await
+    }
+
     /// Find a volume by namespace, name, and owners
     pub async fn find_volume_by_namespace_name_and_owners(
         db: &DatabaseConnection,
@@ -495,6 +589,7 @@ impl Query {
 
         // Find containers with this processor as owner_ref and with active status
         let count = containers::Entity::find()
+            .filter(containers::Column::DeletedAt.is_null())
             .filter(containers::Column::OwnerRef.eq(owner_ref))
             .filter(status_condition)
             .count(db)
@@ -503,6 +598,43 @@ impl Query {
         Ok(count)
     }
 
+    /// Fetch the live containers, processors, secrets and volumes in a namespace
+    pub async fn find_namespace_resources(
+        db: &DatabaseConnection,
+        namespace: &str,
+    ) -> Result<
+        (
+            Vec<containers::Model>,
+            Vec<processors::Model>,
+            Vec<secrets::Model>,
+            Vec<crate::entities::volumes::Model>,
+        ),
+        DbErr,
+    > {
+        use crate::entities::volumes;
+
+        let containers = containers::Entity::find()
+            .filter(containers::Column::DeletedAt.is_null())
+            .filter(containers::Column::Namespace.eq(namespace))
+            .all(db)
+            .await?;
+        let processors = processors::Entity::find()
+            .filter(processors::Column::DeletedAt.is_null())
+            .filter(processors::Column::Namespace.eq(namespace))
+            .all(db)
+            .await?;
+        let secrets = secrets::Entity::find()
+            .filter(secrets::Column::Namespace.eq(namespace))
+            .all(db)
+            .await?;
+        let volumes = volumes::Entity::find()
+            .filter(volumes::Column::Namespace.eq(namespace))
+            .all(db)
+            .await?;
+
+        Ok((containers, processors, secrets, volumes))
+    }
+
     /// Fetch all namespaces for a given list of owners
     pub async fn find_namespaces_by_owners(
         db: &DatabaseConnection,
@@ -514,3 +646,136 @@ impl Query {
             .await
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn insert_container(
+        db: &DatabaseConnection,
+        id: &str,
+        status: ContainerStatus,
+        desired_status: Option<ContainerStatus>,
+    ) {
+        containers::ActiveModel::from(containers::Model {
+            id: id.to_string(),
+            name: id.to_string(),
+            full_name: format!("ns/{}", id),
+            status: Some(serde_json::json!(V1ContainerStatus {
+                status: Some(status.to_string()),
+                ..Default::default()
+            })),
+            desired_status: desired_status.map(|s| s.to_string()),
+            ..containers::Model::test_fixture()
+        })
+        .insert(db)
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a Postgres at NEBU_TEST_DATABASE_URL"]
+    async fn test_active_containers_include_stopped_ones_to_resume() {
+        // Arrange
+        let db = crate::db::test_db().await;
+        insert_container(&db, "running", ContainerStatus::Running, None).await;
+        insert_container(
+            &db,
+            "resume",
+            ContainerStatus::Stopped,
+            Some(ContainerStatus::Running),
+        )
+        .await;
+        insert_container(
+            &db,
+            "stopped",
+            ContainerStatus::Stopped,
+            Some(ContainerStatus::Stopped),
+        )
+        .await;
+        insert_container(&db, "done", ContainerStatus::Completed, None).await;
+
+        // Act
+        let containers = Query::find_all_active_containers(&db).await.unwrap();
+
+        // Assert
+        let mut ids: Vec<&str> = containers.iter().map(|c| c.id.as_str()).collect();
+        ids.sort();
+        assert_eq!(ids, vec!["resume", "running"]);
+    }
+
+    fn sorted_ids<'a>(ids: impl Iterator<Item = &'a str>) -> Vec<&'a str> {
+        let mut ids: Vec<&str> = ids.collect();
+        ids.sort();
+        ids
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a Postgres at NEBU_TEST_DATABASE_URL"]
+    async fn test_lists_leave_out_soft_deleted_resources() {
+        let db = crate::db::test_db().await;
+        let deleted_at = Some(chrono::Utc::now().into());
+        for (id, deleted_at) in [("live", None), ("gone", deleted_at)] {
+            containers::ActiveModel::from(containers::Model {
+                id: id.to_string(),
+                name: id.to_string(),
+                full_name: format!("ns/{}", id),
+                deleted_at,
+                ..containers::Model::test_fixture()
+            })
+            .insert(&db)
+            .await
+            .unwrap();
+            processors::ActiveModel::from(processors::Model {
+                id: id.to_string(),
+                name: id.to_string(),
+                full_name: format!("ns/{}", id),
+                deleted_at,
+                ..processors::Model::test_fixture()
+            })
+            .insert(&db)
+            .await
+            .unwrap();
+        }
+
+        let containers = Query::find_containers_by_owners(&db, &["me"])
+            .await
+            .unwrap();
+        let all_containers = Query::find_all_containers(&db).await.unwrap();
+        let with_deleted = Query::find_containers_by_owners_including_deleted(&db, &["me"])
+            .await
+            .unwrap();
+        let processors = Query::find_processors_by_owners(&db, &["me"])
+            .await
+            .unwrap();
+        let processors_with_deleted =
+            Query::find_processors_by_owners_including_deleted(&db, &["me"])
+                .await
+                .unwrap();
+
+        assert_eq!(
+            sorted_ids(containers.iter().map(|c| c.id.as_str())),
+            vec!["live"]
+        );
+        assert_eq!(
+            sorted_ids(all_containers.iter().map(|c| c.id.as_str())),
+            vec!["live"]
+        );
+        assert_eq!(
+            sorted_ids(with_deleted.iter().map(|c| c.id.as_str())),
+            vec!["gone", "live"]
+        );
+        assert_eq!(
+            sorted_ids(processors.iter().map(|p| p.id.as_str())),
+            vec!["live"]
+        );
+        assert_eq!(
+            sorted_ids(processors_with_deleted.iter().map(|p| p.id.as_str())),
+            vec!["gone", "live"]
+        );
+        assert!(Query::find_containers_by_owners(&db, &["someone-else"])
+            .await
+            .unwrap()
+            .is_empty());
+    }
+}
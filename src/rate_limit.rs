@@ -0,0 +1,228 @@
+// src/rate_limit.rs
+//
+// Per API key rate limiting. Every key gets a token bucket in Redis, so the
+// limit holds across server replicas.
+
+use crate::config::SERVER_CONFIG;
+use crate::state::{AppState, MessageQueue};
+use axum::{
+    extract::{Request, State},
+    http::{header, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use once_cell::sync::Lazy;
+use redis::aio::MultiplexedConnection;
+use serde_json::json;
+use tokio::sync::OnceCell;
+use tracing::{debug, warn};
+
+/// Paths that are never rate limited
+pub const EXEMPT_PATHS: &[&str] = &["/health", "/health/ready", "/metrics"];
+
+/// Refills the bucket for the time since its last request, then takes a token
+/// if there is one. Returns `{allowed, wait_ms}` where `wait_ms` is how long
+/// until the next token when the request is rejected.
+static TOKEN_BUCKET: Lazy<redis::Script> = Lazy::new(|| {
+    redis::Script::new(
+        r#"
+local rate = tonumber(ARGV[1])
+local burst = tonumber(ARGV[2])
+local now = tonumber(ARGV[3])
+local bucket = redis.call('HMGET', KEYS[1], 'tokens', 'ts')
+local tokens = tonumber(bucket[1]) or burst
+local ts = tonumber(bucket[2]) or now
+tokens = math.min(burst, tokens + math.max(0, now - ts) * rate / 1000)
+local allowed = 0
+local wait_ms = 0
+if tokens >= 1 then
+  tokens = tokens - 1
+  allowed = 1
+else
+  wait_ms = math.ceil((1 - tokens) * 1000 / rate)
+end
+redis.call('HSET', KEYS[1], 'tokens', tostring(tokens), 'ts', now)
+redis.call('PEXPIRE', KEYS[1], math.ceil(burst * 1000 / rate) + 1000)
+return {allowed, wait_ms}
+"#,
+    )
+});
+
+static CONNECTION: OnceCell<MultiplexedConnection> = OnceCell::const_new();
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RateDecision {
+    Allowed,
+    Limited { retry_after_secs: u64 },
+}
+
+/// Takes a token from the bucket at `key`, which holds up to `burst` tokens
+/// and refills at `rps` tokens per second.
+pub async fn take_token(
+    conn: &mut MultiplexedConnection,
+    key: &str,
+    rps: f64,
+    burst: u32,
+    now_ms: u64,
+) -> redis::RedisResult<RateDecision> {
+    let (allowed, wait_ms): (i64, i64) = TOKEN_BUCKET
+        .key(key)
+        .arg(rps)
+        .arg(burst)
+        .arg(now_ms)
+        .invoke_async(conn)
+        .await?;
+
+    if allowed == 1 {
+        Ok(RateDecision::Allowed)
+    } else {
+        Ok(RateDecision::Limited {
+            // Retry-After is in whole seconds, so round up
+            retry_after_secs: (wait_ms.max(1) as u64).div_ceil(1000),
+        })
+    }
+}
+
+/// The Redis key of an API key's bucket. Keys are hashed so they never end
+/// up in Redis in plain text.
+pub fn bucket_key(api_key: &str) -> String {
+    let digest = ring::digest::digest(&ring::digest::SHA256, api_key.as_bytes());
+    let hex: String = digest
+        .as_ref()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect();
+    format!("ratelimit:{}", hex)
+}
+
+/// Rejects requests with 429 once their API key exceeds
+/// `NEBU_RATE_LIMIT_RPS`. Requests without a bearer token are left to the
+/// auth middleware, and the limiter lets requests through if Redis is down.
+pub async fn rate_limit_middleware(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let rps = SERVER_CONFIG.rate_limit_rps;
+    if rps <= 0.0 || EXEMPT_PATHS.contains(&request.uri().path()) {
+        return next.run(request).await;
+    }
+
+    let Some(key) = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .filter(|token| !token.is_empty())
+        .map(bucket_key)
+    else {
+        return next.run(request).await;
+    };
+
+    let MessageQueue::Redis { client } = &state.message_queue else {
+        return next.run(request).await;
+    };
+    let conn = CONNECTION
+        .get_or_try_init(|| client.get_multiplexed_async_connection())
+        .await;
+    let mut conn = match conn {
+        Ok(conn) => conn.clone(),
+        Err(e) => {
+            warn!("Rate limiter could not connect to Redis: {}", e);
+            return next.run(request).await;
+        }
+    };
+
+    let now_ms = chrono::Utc::now().timestamp_millis() as u64;
+    match take_token(&mut conn, &key, rps, SERVER_CONFIG.rate_limit_burst, now_ms).await {
+        Ok(RateDecision::Allowed) => next.run(request).await,
+        Ok(RateDecision::Limited { retry_after_secs }) => {
+            debug!("Rate limited {} for {}s", key, retry_after_secs);
+            rate_limited_response(retry_after_secs)
+        }
+        Err(e) => {
+            warn!("Rate limiter failed, letting request through: {}", e);
+            next.run(request).await
+        }
+    }
+}
+
+fn rate_limited_response(retry_after_secs: u64) -> Response {
+    let error_response = json!({
+        "error": {
+            "message": "Rate limit exceeded",
+            "type": "rate_limit_error",
+            "param": null,
+            "code": null
+        }
+    });
+    (
+        StatusCode::TOO_MANY_REQUESTS,
+        [(header::RETRY_AFTER, retry_after_secs.to_string())],
+        Json(error_response),
+    )
+        .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    #[ignore = "needs a Redis at NEBU_TEST_REDIS_URL"]
+    async fn test_request_past_burst_is_rejected() {
+        // Arrange
+        let url = std::env::var("NEBU_TEST_REDIS_URL").expect("NEBU_TEST_REDIS_URL is not set");
+        let client = redis::Client::open(url).unwrap();
+        let mut conn = client.get_multiplexed_async_connection().await.unwrap();
+        let key = bucket_key(&short_uuid::ShortUuid::generate().to_string());
+        let now_ms = 1_000_000;
+
+        // Act
+        let mut decisions = Vec::new();
+        for _ in 0..4 {
+            decisions.push(take_token(&mut conn, &key, 1.0, 3, now_ms).await.unwrap());
+        }
+
+        // Assert
+        assert_eq!(decisions[..3], vec![RateDecision::Allowed; 3]);
+        assert_eq!(
+            decisions[3],
+            RateDecision::Limited {
+                retry_after_secs: 1
+            }
+        );
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a Redis at NEBU_TEST_REDIS_URL"]
+    async fn test_bucket_refills_over_time() {
+        let url = std::env::var("NEBU_TEST_REDIS_URL").expect("NEBU_TEST_REDIS_URL is not set");
+        let client = redis::Client::open(url).unwrap();
+        let mut conn = client.get_multiplexed_async_connection().await.unwrap();
+        let key = bucket_key(&short_uuid::ShortUuid::generate().to_string());
+
+        for _ in 0..2 {
+            take_token(&mut conn, &key, 2.0, 2, 0).await.unwrap();
+        }
+        assert_ne!(
+            take_token(&mut conn, &key, 2.0, 2, 100).await.unwrap(),
+            RateDecision::Allowed
+        );
+        // Half a second at 2 rps is one token
+        assert_eq!(
+            take_token(&mut conn, &key, 2.0, 2, 600).await.unwrap(),
+            RateDecision::Allowed
+        );
+    }
+
+    #[test]
+    fn test_bucket_key_hides_api_key() {
+        let key = bucket_key("secret-api-key");
+        assert!(key.starts_with("ratelimit:"));
+        assert!(!key.contains("secret-api-key"));
+        assert_eq!(key, bucket_key("secret-api-key"));
+        assert_ne!(key, bucket_key("other-api-key"));
+    }
+}
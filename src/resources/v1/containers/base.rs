@@ -1,13 +1,20 @@
 use crate::agent::agent::create_agent_key;
-use crate::agent::aws::create_s3_scoped_user;
-use crate::config::{ClientConfig, SERVER_CONFIG};
+use crate::agent::aws::{create_s3_scoped_user, namespace_prefix};
+use crate::config::{
+    ClientConfig, DEFAULT_TAILSCALE_HOSTNAME_TEMPLATE, DEFAULT_TAILSCALE_TAG, SERVER_CONFIG,
+};
 use crate::entities::containers;
-use crate::handlers::v1::volumes::ensure_volume;
 use crate::models::{V1CreateAgentKeyRequest, V1UserProfile};
 use crate::orign::get_orign_server;
 use crate::query::Query;
-use crate::resources::v1::containers::models::{V1Container, V1ContainerRequest};
-use sea_orm::DatabaseConnection;
+use crate::resources::v1::containers::models::{
+    V1Container, V1ContainerRequest, V1ContainerTailscale, V1EnvVar, V1LogParams, V1UpdateContainer,
+};
+use crate::resources::v1::containers::ssh_rotation::shell_quote;
+use crate::resources::v1::volumes::base::ensure_volume;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use sea_orm::{ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter};
 use std::collections::HashMap;
 use std::fmt;
 use std::net::IpAddr;
@@ -97,6 +104,602 @@ impl ContainerStatus {
     }
 }
 
+/// Maximum number of times an `OnFailure` container is restarted
+pub const MAX_RESTART_ATTEMPTS: u32 = 5;
+const RESTART_BACKOFF_BASE_SECS: u64 = 10;
+const RESTART_BACKOFF_MAX_SECS: u64 = 300;
+
+/// What the controller should do once a container reaches a terminal status
+#[derive(Debug, Clone, PartialEq)]
+pub enum RestartDecision {
+    /// Recreate the container after waiting `backoff`; `attempt` is 1-based
+    Restart {
+        attempt: u32,
+        backoff: std::time::Duration,
+    },
+    /// The policy wants a restart but the attempt limit was reached
+    Exhausted,
+    /// Leave the container in its terminal status
+    NoRestart,
+}
+
+/// Decide whether a container in `status` should be restarted under `policy`,
+/// given the number of restarts already performed.
+pub fn restart_decision(policy: &str, status: &ContainerStatus, attempts: u32) -> RestartDecision {
+    use crate::resources::v1::containers::models::RestartPolicy;
+
+    let policy = RestartPolicy::from_str(policy).unwrap_or(RestartPolicy::Never);
+    let should_restart = match policy {
+        RestartPolicy::Always => matches!(
+            status,
+            ContainerStatus::Completed
+                | ContainerStatus::Failed
+                | ContainerStatus::Stopped
+                | ContainerStatus::Exited
+        ),
+        RestartPolicy::OnFailure => *status == ContainerStatus::Failed,
+        RestartPolicy::Never => false,
+    };
+
+    if !should_restart {
+        return RestartDecision::NoRestart;
+    }
+    if policy == RestartPolicy::OnFailure && attempts >= MAX_RESTART_ATTEMPTS {
+        return RestartDecision::Exhausted;
+    }
+
+    let backoff_secs = RESTART_BACKOFF_BASE_SECS
+        .saturating_mul(2u64.saturating_pow(attempts))
+        .min(RESTART_BACKOFF_MAX_SECS);
+
+    RestartDecision::Restart {
+        attempt: attempts + 1,
+        backoff: std::time::Duration::from_secs(backoff_secs),
+    }
+}
+
+/// Dollars a pod costing `cost_per_hr` accrues over `elapsed`. Bogus (negative
+/// or non-finite) rates accrue nothing.
+pub fn cost_for(cost_per_hr: f64, elapsed: std::time::Duration) -> f64 {
+    if !cost_per_hr.is_finite() || cost_per_hr <= 0.0 {
+        return 0.0;
+    }
+    cost_per_hr * elapsed.as_secs_f64() / 3600.0
+}
+
+/// Whether a container that has never become ready has outlived its `timeout`.
+/// The deadline counts from the creation of its current pod when known, of the
+/// container otherwise, so a pod stuck creating is still terminated and a
+/// restarted one gets a fresh deadline. Times are unix seconds.
+pub fn never_ready_timeout_exceeded(
+    created_at: i64,
+    pod_created_at: Option<i64>,
+    now: i64,
+    timeout: std::time::Duration,
+) -> bool {
+    let since = pod_created_at.map_or(created_at, |p| p.max(created_at));
+    now.saturating_sub(since) >= i64::try_from(timeout.as_secs()).unwrap_or(i64::MAX)
+}
+
+/// What queue scheduling needs to know about a container
+#[derive(Debug, Clone, PartialEq)]
+pub struct QueueEntry {
+    pub id: String,
+    pub status: Option<ContainerStatus>,
+    pub priority: i32,
+    pub preemptible: bool,
+    /// Unix milliseconds
+    pub created_at: i64,
+}
+
+impl From<&containers::Model> for QueueEntry {
+    fn from(container: &containers::Model) -> Self {
+        QueueEntry {
+            id: container.id.clone(),
+            status: container
+                .parse_status()
+                .ok()
+                .flatten()
+                .and_then(|s| s.status)
+                .and_then(|s| ContainerStatus::from_str(&s).ok()),
+            priority: container.priority.unwrap_or(0),
+            preemptible: container.preemptible.unwrap_or(false),
+            created_at: container.created_at.timestamp_millis(),
+        }
+    }
+}
+
+impl QueueEntry {
+    /// Started and not finished yet, so it occupies the queue
+    fn holds_queue(&self) -> bool {
+        matches!(
+            self.status,
+            Some(
+                ContainerStatus::Creating
+                    | ContainerStatus::Created
+                    | ContainerStatus::Running
+                    | ContainerStatus::Restarting
+            )
+        )
+    }
+
+    fn waits_in_queue(&self) -> bool {
+        self.status.as_ref().is_some_and(|s| s.needs_start())
+    }
+
+    /// Whether this entry starts before `other`: higher priority first, then
+    /// first come, first served.
+    pub fn precedes(&self, other: &QueueEntry) -> bool {
+        self.priority > other.priority
+            || (self.priority == other.priority && self.created_at < other.created_at)
+    }
+}
+
+/// Whether `entry` may start, given the other containers in its queue: none of
+/// them holds the queue and none is waiting ahead of it.
+pub fn queue_is_free_for(entry: &QueueEntry, others: &[QueueEntry]) -> bool {
+    others
+        .iter()
+        .all(|other| !other.holds_queue() && !(other.waits_in_queue() && other.precedes(entry)))
+}
+
+/// The containers to stop so `entry` can take over its queue. Only returns any
+/// when `entry` is next in line and every container holding the queue is
+/// preemptible and of lower priority.
+pub fn preemption_victims<'a>(entry: &QueueEntry, others: &'a [QueueEntry]) -> Vec<&'a QueueEntry> {
+    let mut victims = Vec::new();
+    for other in others {
+        if other.holds_queue() {
+            if !other.preemptible || other.priority >= entry.priority {
+                return Vec::new();
+            }
+            victims.push(other);
+        } else if other.waits_in_queue() && other.precedes(entry) {
+            return Vec::new();
+        }
+    }
+    victims
+}
+
+/// What `reconcile` should do to move a container towards its desired status
+#[derive(Debug, Clone, PartialEq)]
+pub enum DesiredStatusAction {
+    /// Stop the container's pod, keeping its volume
+    Stop,
+    /// Bring a stopped container back up
+    Resume,
+    /// Desired and current status already agree
+    None,
+}
+
+/// Compare a container's `desired_status` against its current status.
+pub fn desired_status_action(
+    desired: Option<&str>,
+    status: &ContainerStatus,
+) -> DesiredStatusAction {
+    match desired.and_then(|d| ContainerStatus::from_str(d).ok()) {
+        Some(ContainerStatus::Stopped) if status.is_active() => DesiredStatusAction::Stop,
+        Some(ContainerStatus::Running) if *status == ContainerStatus::Stopped => {
+            DesiredStatusAction::Resume
+        }
+        _ => DesiredStatusAction::None,
+    }
+}
+
+/// Container fields that can only be changed by recreating the container
+pub const RECREATE_FIELDS: &[&str] = &[
+    "platform",
+    "image",
+    "env",
+    "command",
+    "args",
+    "volumes",
+    "accelerators",
+    "resources",
+];
+
+/// Apply an env merge patch by key. Patch entries with neither a value nor a
+/// secret remove the variable; others replace it in place or are appended.
+pub fn merge_env_patch(current: &[V1EnvVar], patch: &[V1EnvVar]) -> Vec<V1EnvVar> {
+    let mut merged = current.to_vec();
+    for var in patch {
+        let remove = var.value.is_none() && var.secret_name.is_none();
+        match merged.iter().position(|v| v.key == var.key) {
+            Some(i) if remove => {
+                merged.remove(i);
+            }
+            Some(i) => merged[i] = var.clone(),
+            None if remove => {}
+            None => merged.push(var.clone()),
+        }
+    }
+    merged
+}
+
+/// Apply a label merge patch; `None` values remove the label.
+pub fn merge_labels_patch(
+    current: &HashMap<String, String>,
+    patch: &HashMap<String, Option<String>>,
+) -> HashMap<String, String> {
+    let mut merged = current.clone();
+    for (key, value) in patch {
+        match value {
+            Some(value) => {
+                merged.insert(key.clone(), value.clone());
+            }
+            None => {
+                merged.remove(key);
+            }
+        }
+    }
+    merged
+}
+
+/// Apply a `PATCH` request to a container. Unset fields are left unchanged;
+/// env and labels are merged rather than replaced.
+pub fn apply_container_patch(current: &V1Container, patch: &V1UpdateContainer) -> V1Container {
+    let mut updated = current.clone();
+
+    if let Some(platform) = &patch.platform {
+        updated.platform = platform.clone();
+    }
+    if let Some(image) = &patch.image {
+        updated.image = image.clone();
+    }
+    if let Some(env) = &patch.env {
+        updated.env = Some(merge_env_patch(
+            current.env.as_deref().unwrap_or_default(),
+            env,
+        ));
+    }
+    if let Some(command) = &patch.command {
+        updated.command = Some(command.clone());
+    }
+    if let Some(args) = &patch.args {
+        updated.args = Some(args.clone());
+    }
+    if let Some(volumes) = &patch.volumes {
+        updated.volumes = Some(volumes.clone());
+    }
+    if let Some(accelerators) = &patch.accelerators {
+        updated.accelerators = Some(accelerators.clone());
+    }
+    if let Some(labels) = &patch.labels {
+        updated.metadata.labels = Some(merge_labels_patch(
+            &current.metadata.labels.clone().unwrap_or_default(),
+            labels,
+        ));
+    }
+    if let Some(health_check) = &patch.health_check {
+        updated.health_check = Some(health_check.clone());
+    }
+    if let Some(meters) = &patch.meters {
+        updated.meters = Some(meters.clone());
+    }
+    if let Some(restart) = &patch.restart {
+        updated.restart = restart.clone();
+    }
+    if let Some(queue) = &patch.queue {
+        updated.queue = Some(queue.clone());
+    }
+    if let Some(timeout) = &patch.timeout {
+        updated.timeout = Some(timeout.clone());
+    }
+    if let Some(resources) = &patch.resources {
+        updated.resources = Some(resources.clone());
+    }
+    if let Some(proxy_port) = patch.proxy_port {
+        updated.proxy_port = Some(proxy_port);
+    }
+    if let Some(authz) = &patch.authz {
+        updated.authz = Some(authz.clone());
+    }
+    updated
+}
+
+/// Names of the fields that differ between two versions of a container.
+/// Unset and empty lists or maps are treated as equal.
+pub fn changed_fields(current: &V1Container, updated: &V1Container) -> Vec<&'static str> {
+    let mut changed = Vec::new();
+    let mut check = |name: &'static str, differs: bool| {
+        if differs {
+            changed.push(name);
+        }
+    };
+
+    check("platform", current.platform != updated.platform);
+    check("image", current.image != updated.image);
+    check(
+        "env",
+        current.env.clone().unwrap_or_default() != updated.env.clone().unwrap_or_default(),
+    );
+    check("command", current.command != updated.command);
+    check("args", current.args != updated.args);
+    check(
+        "volumes",
+        current.volumes.clone().unwrap_or_default() != updated.volumes.clone().unwrap_or_default(),
+    );
+    check(
+        "accelerators",
+        current.accelerators.clone().unwrap_or_default()
+            != updated.accelerators.clone().unwrap_or_default(),
+    );
+    check(
+        "resources",
+        current.resources.clone().unwrap_or_default()
+            != updated.resources.clone().unwrap_or_default(),
+    );
+    check(
+        "labels",
+        current.metadata.labels.clone().unwrap_or_default()
+            != updated.metadata.labels.clone().unwrap_or_default(),
+    );
+    check(
+        "health_check",
+        current.health_check.clone().unwrap_or_default()
+            != updated.health_check.clone().unwrap_or_default(),
+    );
+    check(
+        "meters",
+        current.meters.clone().unwrap_or_default() != updated.meters.clone().unwrap_or_default(),
+    );
+    check("restart", current.restart != updated.restart);
+    check("queue", current.queue != updated.queue);
+    check("timeout", current.timeout != updated.timeout);
+    check("proxy_port", current.proxy_port != updated.proxy_port);
+    check("authz", current.authz != updated.authz);
+
+    changed
+}
+
+/// Merge namespace default env under a container's own env. Variables set on
+/// the container win; defaults keep their relative order and come first.
+pub fn merge_env(defaults: &[V1EnvVar], env: &[V1EnvVar]) -> Vec<V1EnvVar> {
+    let mut merged: Vec<V1EnvVar> = defaults
+        .iter()
+        .filter(|default| !env.iter().any(|var| var.key == default.key))
+        .cloned()
+        .collect();
+    merged.extend(env.iter().cloned());
+    merged
+}
+
+/// User commands run as in a container when neither its image, its request
+/// nor its namespace set one
+pub const DEFAULT_CONTAINER_USER: &str = "root";
+
+/// The first non-empty user among `candidates`, or [`DEFAULT_CONTAINER_USER`].
+pub fn resolve_container_user<'a>(candidates: impl IntoIterator<Item = Option<&'a str>>) -> String {
+    candidates
+        .into_iter()
+        .flatten()
+        .map(str::trim)
+        .find(|user| !user.is_empty())
+        .unwrap_or(DEFAULT_CONTAINER_USER)
+        .to_string()
+}
+
+/// The user to exec, read logs and check files as in a container.
+pub fn exec_user(container: &containers::Model) -> String {
+    resolve_container_user([container.container_user.as_deref()])
+}
+
+/// What looking up the secret behind an env var turned up.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SecretLookup {
+    Found(String),
+    NotFound,
+    Undecryptable(String),
+}
+
+#[derive(Debug, thiserror::Error, PartialEq)]
+pub enum EnvResolveError {
+    #[error("Required secret '{secret}' for env var '{key}' was not found")]
+    SecretNotFound { key: String, secret: String },
+
+    #[error("Required secret '{secret}' for env var '{key}' could not be decrypted: {reason}")]
+    SecretUndecryptable {
+        key: String,
+        secret: String,
+        reason: String,
+    },
+
+    #[error("Required env var '{0}' has no value")]
+    MissingValue(String),
+}
+
+/// Resolve env vars to key/value pairs using the already looked up `secrets`,
+/// keyed by secret name. Optional vars without a value are skipped; a
+/// required one fails the whole resolution.
+pub fn resolve_env(
+    env: &[V1EnvVar],
+    secrets: &HashMap<String, SecretLookup>,
+) -> Result<Vec<(String, String)>, EnvResolveError> {
+    let mut resolved = Vec::new();
+    for var in env {
+        let value = match &var.secret_name {
+            Some(secret) => match secrets.get(secret).unwrap_or(&SecretLookup::NotFound) {
+                SecretLookup::Found(value) => Some(value.clone()),
+                SecretLookup::NotFound if var.required => {
+                    return Err(EnvResolveError::SecretNotFound {
+                        key: var.key.clone(),
+                        secret: secret.clone(),
+                    })
+                }
+                SecretLookup::Undecryptable(reason) if var.required => {
+                    return Err(EnvResolveError::SecretUndecryptable {
+                        key: var.key.clone(),
+                        secret: secret.clone(),
+                        reason: reason.clone(),
+                    })
+                }
+                lookup => {
+                    error!(
+                        "Skipping env var '{}': secret '{}' unavailable ({:?})",
+                        var.key, secret, lookup
+                    );
+                    None
+                }
+            },
+            None => var.value.clone(),
+        };
+        match value {
+            Some(value) => resolved.push((var.key.clone(), value)),
+            None if var.required => return Err(EnvResolveError::MissingValue(var.key.clone())),
+            None => error!("Skipping env var '{}': no value", var.key),
+        }
+    }
+    Ok(resolved)
+}
+
+/// `$$`, `$VAR` or `${VAR}`
+static VARIABLE_REF: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"\$\$|\$([A-Za-z0-9_]+)|\$\{([A-Za-z0-9_]+)\}").unwrap());
+
+/// `$$`, an escaped `\$`, `$VAR` or `${VAR}`, as the shell reads them
+static SHELL_VARIABLE_REF: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"\$\$|\\\$|\$([A-Za-z_][A-Za-z0-9_]*)|\$\{([A-Za-z_][A-Za-z0-9_]*)\}").unwrap()
+});
+
+/// Substitutes `$VAR` and `${VAR}` in `input` with their values in `env`.
+/// Unknown variables expand to nothing, and `$$` is a literal `$`.
+///
+/// Volume paths are expanded with the container's resolved env: the
+/// namespace's `default_env` under the container's own env, secrets
+/// decrypted, plus the `NEBU_*` variables the platform sets. Their
+/// `{id}`-style placeholders are replaced before this.
+pub fn expand_variables(input: &str, env: &HashMap<String, String>) -> String {
+    VARIABLE_REF
+        .replace_all(input, |caps: &regex::Captures| {
+            match caps.get(1).or_else(|| caps.get(2)) {
+                Some(key) => env.get(key.as_str()).cloned().unwrap_or_default(),
+                None => "$".to_string(),
+            }
+        })
+        .to_string()
+}
+
+/// Substitutes the variables of a shell command that `env` has, each value
+/// single-quoted so it stays one word and isn't evaluated. Everything else,
+/// `$HOME`, `$1`, `$$` or an escaped `\$VAR`, is left for the shell.
+pub fn expand_command_variables(input: &str, env: &HashMap<String, String>) -> String {
+    SHELL_VARIABLE_REF
+        .replace_all(input, |caps: &regex::Captures| {
+            caps.get(1)
+                .or_else(|| caps.get(2))
+                .and_then(|key| env.get(key.as_str()))
+                .map(|value| shell_quote(value))
+                .unwrap_or_else(|| caps[0].to_string())
+        })
+        .to_string()
+}
+
+/// The env a container's command and args are expanded with. Variables
+/// backed by a secret are left out, so the container's shell resolves them at
+/// runtime from the same env, keeping their values out of the pod's start
+/// command.
+pub fn command_env(env: &HashMap<String, String>, vars: &[V1EnvVar]) -> HashMap<String, String> {
+    let mut command_env = env.clone();
+    for var in vars.iter().filter(|var| var.secret_name.is_some()) {
+        command_env.remove(&var.key);
+    }
+    command_env
+}
+
+/// The command a container runs: its `command` followed by its `args`, both
+/// expanded with `env`, see [`expand_command_variables`]. None without a
+/// command.
+pub fn expanded_command(
+    command: Option<&str>,
+    args: Option<&str>,
+    env: &HashMap<String, String>,
+) -> Option<String> {
+    let mut expanded = expand_command_variables(command?, env);
+    if let Some(args) = args.filter(|args| !args.trim().is_empty()) {
+        expanded.push(' ');
+        expanded.push_str(&expand_command_variables(args, env));
+    }
+    Some(expanded)
+}
+
+/// Look up and decrypt every secret referenced by `env` in `namespace`.
+pub async fn lookup_env_secrets(
+    db: &DatabaseConnection,
+    namespace: &str,
+    env: &[V1EnvVar],
+) -> Result<HashMap<String, SecretLookup>, sea_orm::DbErr> {
+    let mut secrets = HashMap::new();
+    for secret_name in env.iter().filter_map(|var| var.secret_name.as_ref()) {
+        if secrets.contains_key(secret_name) {
+            continue;
+        }
+        let lookup =
+            match Query::find_secret_by_namespace_and_name(db, namespace, secret_name).await? {
+                Some(secret) => match secret.decrypt_value() {
+                    Ok(value) => SecretLookup::Found(value),
+                    Err(e) => SecretLookup::Undecryptable(e),
+                },
+                None => SecretLookup::NotFound,
+            };
+        secrets.insert(secret_name.clone(), lookup);
+    }
+    Ok(secrets)
+}
+
+/// Return `config` with its namespace's defaults applied: `default_env` merged
+/// under the container env, `default_user` when the request sets no user,
+/// `default_platform` when it names no platform and `default_accelerators`
+/// when it leaves `accelerators` out. An empty `accelerators` asks for none
+/// and is kept. Applying the defaults again changes nothing.
+pub async fn with_namespace_defaults(
+    db: &DatabaseConnection,
+    namespace: &str,
+    config: &V1ContainerRequest,
+) -> Result<V1ContainerRequest, Box<dyn std::error::Error + Send + Sync>> {
+    let namespace_model = crate::entities::namespaces::Entity::find()
+        .filter(crate::entities::namespaces::Column::Name.eq(namespace))
+        .one(db)
+        .await?;
+
+    let Some(ns) = namespace_model else {
+        return Ok(config.clone());
+    };
+    let defaults = ns.parse_default_env()?.unwrap_or_default();
+    let mut config = config.clone();
+    if config.user.as_deref().map_or(true, |u| u.trim().is_empty()) {
+        // Namespaces saved before users were validated may hold anything
+        config.user = ns
+            .default_user
+            .clone()
+            .filter(|u| crate::validate::validate_container_user(u).is_ok());
+    }
+    let names_platform = config.platform.is_some()
+        || config
+            .platforms
+            .as_ref()
+            .is_some_and(|platforms| !platforms.is_empty());
+    if !names_platform {
+        config.platform = ns.default_platform.clone();
+    }
+    if config.accelerators.is_none() {
+        config.accelerators = ns.parse_default_accelerators()?;
+    }
+    if defaults.is_empty() {
+        return Ok(config);
+    }
+
+    debug!(
+        "Merging {} default env vars from namespace {}",
+        defaults.len(),
+        namespace
+    );
+    config.env = Some(merge_env(
+        &defaults,
+        &config.env.clone().unwrap_or_default(),
+    ));
+    Ok(config)
+}
+
 impl fmt::Display for ContainerStatus {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
@@ -166,6 +769,7 @@ pub trait ContainerPlatform {
     async fn logs(
         &self,
         container_id: &str,
+        params: &V1LogParams,
         db: &DatabaseConnection,
     ) -> Result<String, Box<dyn std::error::Error + Send + Sync>>;
 
@@ -218,8 +822,9 @@ pub trait ContainerPlatform {
         };
 
         debug!("Creating s3 token");
+        let s3_prefix = namespace_prefix(&model.namespace);
         let s3_token =
-            match create_s3_scoped_user(&SERVER_CONFIG.bucket_name, &model.namespace, &model.id).await {
+            match create_s3_scoped_user(&SERVER_CONFIG.bucket_name, &s3_prefix, &model.namespace, &model.id).await {
                 Ok(token) => token,
                 Err(e) => {
                     error!("Error creating s3 token: {:?}", e);
@@ -384,7 +989,7 @@ pub trait ContainerPlatform {
                         reusable: Some(false),
                         ephemeral: Some(true),
                         preauthorized: Some(true),
-                        tags: Some(vec!["tag:container".to_string()]),
+                        tags: Some(get_tailscale_tags(model)),
                     }),
                 },
             },
@@ -560,6 +1165,7 @@ pub trait ContainerPlatform {
             updated_at: Set(secret.updated_at),
             created_at: Set(secret.created_at),
             expires_at: Set(None),
+            version: Set(secret.version),
         };
 
         debug!("[DEBUG] store_agent_key_secret: Inserting secret into database");
@@ -594,7 +1200,84 @@ pub trait ContainerController {
 }
 
 pub async fn get_tailscale_device_name(model: &containers::Model) -> String {
-    format!("container-{}", model.id)
+    let template = model
+        .parse_tailscale()
+        .ok()
+        .flatten()
+        .and_then(|ts| ts.hostname)
+        .or_else(|| {
+            SERVER_CONFIG
+                .tailscale
+                .as_ref()
+                .map(|ts| ts.hostname_template.clone())
+        })
+        .unwrap_or(DEFAULT_TAILSCALE_HOSTNAME_TEMPLATE.to_string());
+    render_hostname(&template, &model.id, &model.name, &model.namespace)
+}
+
+/// Renders a Tailscale hostname template. Characters Tailscale doesn't accept are
+/// replaced with hyphens, and hostnames longer than a DNS label fall back to the
+/// default `container-{id}` form so they stay unique.
+pub fn render_hostname(template: &str, id: &str, name: &str, namespace: &str) -> String {
+    let rendered: String = template
+        .replace("{id}", id)
+        .replace("{name}", name)
+        .replace("{namespace}", namespace)
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' {
+                c
+            } else {
+                '-'
+            }
+        })
+        .collect();
+    let rendered = rendered.trim_matches('-').to_string();
+
+    if rendered.is_empty() || rendered.len() > crate::validate::MAX_NAME_LENGTH {
+        return DEFAULT_TAILSCALE_HOSTNAME_TEMPLATE.replace("{id}", id);
+    }
+    rendered
+}
+
+/// Tags advertised by the container's Tailscale device.
+pub fn get_tailscale_tags(model: &containers::Model) -> Vec<String> {
+    model
+        .parse_tailscale()
+        .ok()
+        .flatten()
+        .and_then(|ts| ts.tags)
+        .filter(|tags| !tags.is_empty())
+        .or_else(|| SERVER_CONFIG.tailscale.as_ref().map(|ts| ts.tags.clone()))
+        .unwrap_or_else(|| vec![DEFAULT_TAILSCALE_TAG.to_string()])
+}
+
+/// Checks the Tailscale settings of a container request against the server config.
+pub fn validate_container_tailscale(
+    tailscale: &V1ContainerTailscale,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    if let Some(tags) = &tailscale.tags {
+        let allowed_tags = SERVER_CONFIG
+            .tailscale
+            .as_ref()
+            .map(|ts| ts.allowed_tags.clone())
+            .unwrap_or_else(|| vec![DEFAULT_TAILSCALE_TAG.to_string()]);
+        for tag in tags {
+            crate::validate::validate_tailscale_tag(tag)?;
+            if !allowed_tags.contains(tag) {
+                return Err(format!(
+                    "Tailscale tag '{}' is not allowed. Allowed tags: {}",
+                    tag,
+                    allowed_tags.join(", ")
+                )
+                .into());
+            }
+        }
+    }
+    if let Some(hostname) = &tailscale.hostname {
+        crate::validate::validate_hostname_template(hostname)?;
+    }
+    Ok(())
 }
 
 /// Fetches the IPv4 address of a device from Tailscale using its hostname.
@@ -663,3 +1346,777 @@ pub async fn get_ip_for_tailscale_device_hostname(
     );
     Ok(ipv4.to_string())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::resources::v1::containers::models::V1ContainerHealthCheck;
+    use std::time::Duration;
+
+    fn env_var(key: &str, value: Option<&str>) -> V1EnvVar {
+        V1EnvVar {
+            key: key.to_string(),
+            value: value.map(|v| v.to_string()),
+            secret_name: None,
+            required: false,
+        }
+    }
+
+    #[test]
+    fn test_patch_in_place_fields_do_not_require_recreation() {
+        // Arrange
+        let current = V1Container {
+            timeout: Some("1h".to_string()),
+            ..Default::default()
+        };
+        let patch = V1UpdateContainer {
+            labels: Some(HashMap::from([("team".to_string(), Some("a".to_string()))])),
+            timeout: Some("2h".to_string()),
+            health_check: Some(V1ContainerHealthCheck {
+                path: Some("/health".to_string()),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+
+        // Act
+        let updated = apply_container_patch(&current, &patch);
+        let changed = changed_fields(&current, &updated);
+
+        // Assert
+        assert_eq!(changed, vec!["labels", "health_check", "timeout"]);
+        assert!(!changed.iter().any(|f| RECREATE_FIELDS.contains(f)));
+        assert_eq!(updated.timeout, Some("2h".to_string()));
+    }
+
+    #[test]
+    fn test_patch_env_requires_recreation() {
+        let current = V1Container {
+            env: Some(vec![env_var("A", Some("1"))]),
+            ..Default::default()
+        };
+        let patch = V1UpdateContainer {
+            env: Some(vec![env_var("A", Some("2"))]),
+            image: Some("nginx:latest".to_string()),
+            ..Default::default()
+        };
+
+        let changed = changed_fields(&current, &apply_container_patch(&current, &patch));
+
+        assert_eq!(changed, vec!["image", "env"]);
+        assert!(changed.iter().all(|f| RECREATE_FIELDS.contains(f)));
+    }
+
+    #[test]
+    fn test_patch_without_changes() {
+        let current = V1Container {
+            env: Some(vec![env_var("A", Some("1"))]),
+            timeout: Some("1h".to_string()),
+            ..Default::default()
+        };
+        let patch = V1UpdateContainer {
+            env: Some(vec![env_var("A", Some("1"))]),
+            timeout: Some("1h".to_string()),
+            ..Default::default()
+        };
+
+        let changed = changed_fields(&current, &apply_container_patch(&current, &patch));
+
+        assert!(changed.is_empty());
+    }
+
+    #[test]
+    fn test_merge_env_patch() {
+        let current = vec![env_var("A", Some("1")), env_var("B", Some("2"))];
+        let patch = vec![
+            env_var("A", None),
+            env_var("B", Some("3")),
+            env_var("C", Some("4")),
+        ];
+
+        let merged = merge_env_patch(&current, &patch);
+
+        assert_eq!(
+            merged,
+            vec![env_var("B", Some("3")), env_var("C", Some("4"))]
+        );
+    }
+
+    #[test]
+    fn test_merge_labels_patch() {
+        let current = HashMap::from([
+            ("a".to_string(), "1".to_string()),
+            ("b".to_string(), "2".to_string()),
+        ]);
+        let patch = HashMap::from([
+            ("a".to_string(), None),
+            ("c".to_string(), Some("3".to_string())),
+        ]);
+
+        let merged = merge_labels_patch(&current, &patch);
+
+        assert_eq!(
+            merged,
+            HashMap::from([
+                ("b".to_string(), "2".to_string()),
+                ("c".to_string(), "3".to_string()),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_cost_accumulates_over_ticks() {
+        // Arrange
+        let ticks = [
+            (0.36, std::time::Duration::from_secs(20)),
+            (0.36, std::time::Duration::from_secs(25)),
+            // The rate changed between polls
+            (0.72, std::time::Duration::from_secs(10)),
+            (0.72, std::time::Duration::from_secs(3600)),
+        ];
+
+        // Act
+        let total = ticks.iter().fold(0.0, |total, (rate, elapsed)| {
+            total + cost_for(*rate, *elapsed)
+        });
+
+        // Assert
+        let expected = 0.002 + 0.0025 + 0.002 + 0.72;
+        assert!((total - expected).abs() < 1e-9, "total was {}", total);
+    }
+
+    #[test]
+    fn test_cost_ignores_bogus_rates() {
+        let minute = std::time::Duration::from_secs(60);
+        assert_eq!(cost_for(-1.0, minute), 0.0);
+        assert_eq!(cost_for(f64::NAN, minute), 0.0);
+        assert_eq!(cost_for(0.5, std::time::Duration::ZERO), 0.0);
+    }
+
+    fn queue_entry(
+        id: &str,
+        status: ContainerStatus,
+        priority: i32,
+        preemptible: bool,
+        created_at: i64,
+    ) -> QueueEntry {
+        QueueEntry {
+            id: id.to_string(),
+            status: Some(status),
+            priority,
+            preemptible,
+            created_at,
+        }
+    }
+
+    #[test]
+    fn test_preempts_lower_priority_preemptible_container() {
+        // Arrange
+        let urgent = queue_entry("urgent", ContainerStatus::Queued, 10, false, 2_000);
+        let others = vec![
+            queue_entry("batch", ContainerStatus::Running, 0, true, 1_000),
+            queue_entry("done", ContainerStatus::Completed, 50, false, 500),
+        ];
+
+        // Act
+        let victims = preemption_victims(&urgent, &others);
+
+        // Assert
+        let ids: Vec<&str> = victims.iter().map(|v| v.id.as_str()).collect();
+        assert_eq!(ids, vec!["batch"]);
+        assert!(!queue_is_free_for(&urgent, &others));
+    }
+
+    #[test]
+    fn test_no_preemption_of_protected_or_equal_priority_containers() {
+        let urgent = queue_entry("urgent", ContainerStatus::Queued, 10, false, 2_000);
+
+        let not_preemptible = vec![queue_entry("a", ContainerStatus::Running, 0, false, 1_000)];
+        assert!(preemption_victims(&urgent, &not_preemptible).is_empty());
+
+        let same_priority = vec![queue_entry("a", ContainerStatus::Running, 10, true, 1_000)];
+        assert!(preemption_victims(&urgent, &same_priority).is_empty());
+
+        let free_queue = vec![queue_entry("a", ContainerStatus::Stopped, 0, true, 1_000)];
+        assert!(preemption_victims(&urgent, &free_queue).is_empty());
+        assert!(queue_is_free_for(&urgent, &free_queue));
+    }
+
+    #[test]
+    fn test_no_preemption_when_another_container_is_ahead() {
+        let urgent = queue_entry("urgent", ContainerStatus::Queued, 10, false, 2_000);
+        let others = vec![
+            queue_entry("batch", ContainerStatus::Running, 0, true, 1_000),
+            queue_entry("earlier", ContainerStatus::Queued, 10, false, 1_500),
+        ];
+        assert!(preemption_victims(&urgent, &others).is_empty());
+
+        // Once the preempted container is back in the queue, priority decides
+        let requeued = vec![queue_entry(
+            "batch",
+            ContainerStatus::Queued,
+            0,
+            true,
+            1_000,
+        )];
+        assert!(queue_is_free_for(&urgent, &requeued));
+        assert!(!queue_is_free_for(&requeued[0], &[urgent]));
+    }
+
+    #[test]
+    fn test_resolve_container_user_prefers_image_then_fallback() {
+        assert_eq!(resolve_container_user([Some("app"), Some("ubuntu")]), "app");
+        assert_eq!(resolve_container_user([Some(""), Some("ubuntu")]), "ubuntu");
+        assert_eq!(
+            resolve_container_user([Some(" "), None]),
+            DEFAULT_CONTAINER_USER
+        );
+        assert_eq!(resolve_container_user([]), "root");
+    }
+
+    #[tokio::test]
+    async fn test_namespace_default_user_is_the_fallback() {
+        use crate::entities::namespaces;
+        use sea_orm::{ActiveModelTrait, ConnectionTrait, Database, Schema, Set};
+
+        let db = Database::connect("sqlite::memory:").await.unwrap();
+        let schema = Schema::new(db.get_database_backend());
+        db.execute(
+            db.get_database_backend()
+                .build(&schema.create_table_from_entity(namespaces::Entity)),
+        )
+        .await
+        .unwrap();
+        let mut namespace: namespaces::ActiveModel = namespaces::Model::new(
+            "ns1".into(),
+            "team-a".into(),
+            "me".into(),
+            "me".into(),
+            None,
+        )
+        .unwrap()
+        .into();
+        namespace.default_user = Set(Some("ubuntu".to_string()));
+        namespace.insert(&db).await.unwrap();
+
+        let request = V1ContainerRequest::default();
+        let defaulted = with_namespace_defaults(&db, "team-a", &request)
+            .await
+            .unwrap();
+        assert_eq!(defaulted.user.as_deref(), Some("ubuntu"));
+
+        let request = V1ContainerRequest {
+            user: Some("app".to_string()),
+            ..Default::default()
+        };
+        let own = with_namespace_defaults(&db, "team-a", &request)
+            .await
+            .unwrap();
+        assert_eq!(own.user.as_deref(), Some("app"));
+
+        let other = with_namespace_defaults(&db, "team-b", &V1ContainerRequest::default())
+            .await
+            .unwrap();
+        assert_eq!(other.user, None);
+    }
+
+    #[tokio::test]
+    async fn test_invalid_namespace_default_user_is_not_applied() {
+        use crate::entities::namespaces;
+        use sea_orm::{ActiveModelTrait, ConnectionTrait, Database, Schema, Set};
+
+        let db = Database::connect("sqlite::memory:").await.unwrap();
+        let schema = Schema::new(db.get_database_backend());
+        db.execute(
+            db.get_database_backend()
+                .build(&schema.create_table_from_entity(namespaces::Entity)),
+        )
+        .await
+        .unwrap();
+        let mut namespace: namespaces::ActiveModel = namespaces::Model::new(
+            "ns1".into(),
+            "team-a".into(),
+            "me".into(),
+            "me".into(),
+            None,
+        )
+        .unwrap()
+        .into();
+        namespace.default_user = Set(Some("-oProxyCommand=sh".to_string()));
+        namespace.insert(&db).await.unwrap();
+
+        let defaulted = with_namespace_defaults(&db, "team-a", &V1ContainerRequest::default())
+            .await
+            .unwrap();
+
+        assert_eq!(defaulted.user, None);
+        assert_eq!(
+            resolve_container_user([defaulted.user.as_deref()]),
+            DEFAULT_CONTAINER_USER
+        );
+    }
+
+    #[tokio::test]
+    async fn test_namespace_default_platform_and_accelerators() {
+        use crate::entities::namespaces;
+        use sea_orm::{ActiveModelTrait, ConnectionTrait, Database, Schema, Set};
+
+        let db = Database::connect("sqlite::memory:").await.unwrap();
+        let schema = Schema::new(db.get_database_backend());
+        db.execute(
+            db.get_database_backend()
+                .build(&schema.create_table_from_entity(namespaces::Entity)),
+        )
+        .await
+        .unwrap();
+        let mut namespace: namespaces::ActiveModel = namespaces::Model::new(
+            "ns1".into(),
+            "team-a".into(),
+            "me".into(),
+            "me".into(),
+            None,
+        )
+        .unwrap()
+        .into();
+        namespace.default_platform = Set(Some("kube".to_string()));
+        namespace.default_accelerators = Set(Some(serde_json::json!(["1:A100_SXM"])));
+        namespace.insert(&db).await.unwrap();
+
+        let defaulted = with_namespace_defaults(&db, "team-a", &V1ContainerRequest::default())
+            .await
+            .unwrap();
+        assert_eq!(defaulted.platform.as_deref(), Some("kube"));
+        assert_eq!(defaulted.accelerators, Some(vec!["1:A100_SXM".to_string()]));
+        let again = with_namespace_defaults(&db, "team-a", &defaulted)
+            .await
+            .unwrap();
+        assert_eq!(again.platform, defaulted.platform);
+        assert_eq!(again.accelerators, defaulted.accelerators);
+
+        let request = V1ContainerRequest {
+            platform: Some("runpod".to_string()),
+            accelerators: Some(vec!["2:H100_SXM".to_string()]),
+            ..Default::default()
+        };
+        let own = with_namespace_defaults(&db, "team-a", &request)
+            .await
+            .unwrap();
+        assert_eq!(own.platform.as_deref(), Some("runpod"));
+        assert_eq!(own.accelerators, Some(vec!["2:H100_SXM".to_string()]));
+
+        // Fallback platforms and an explicit empty list override the defaults too
+        let request = V1ContainerRequest {
+            platforms: Some(vec!["runpod".to_string(), "kube".to_string()]),
+            accelerators: Some(Vec::new()),
+            ..Default::default()
+        };
+        let own = with_namespace_defaults(&db, "team-a", &request)
+            .await
+            .unwrap();
+        assert_eq!(own.platform, None);
+        assert_eq!(own.accelerators, Some(Vec::new()));
+
+        let other = with_namespace_defaults(&db, "team-b", &V1ContainerRequest::default())
+            .await
+            .unwrap();
+        assert_eq!(other.platform, None);
+        assert_eq!(other.accelerators, None);
+    }
+
+    #[test]
+    fn test_never_ready_container_times_out_after_creation() {
+        // Arrange
+        let created_at = 1_700_000_000;
+        let timeout = std::time::Duration::from_secs(600);
+
+        // Act
+        let before = never_ready_timeout_exceeded(created_at, None, created_at + 599, timeout);
+        let after = never_ready_timeout_exceeded(created_at, None, created_at + 600, timeout);
+
+        // Assert
+        assert!(!before);
+        assert!(after);
+    }
+
+    #[test]
+    fn test_never_ready_timeout_restarts_with_the_container() {
+        let created_at = 1_700_000_000;
+        let restarted_at = created_at + 3_000; // The new pod's creation
+        let timeout = std::time::Duration::from_secs(600);
+
+        assert!(!never_ready_timeout_exceeded(
+            created_at,
+            Some(restarted_at),
+            restarted_at + 300,
+            timeout
+        ));
+        assert!(never_ready_timeout_exceeded(
+            created_at,
+            Some(restarted_at),
+            restarted_at + 600,
+            timeout
+        ));
+        // A stale pod time before creation doesn't extend the deadline
+        assert!(never_ready_timeout_exceeded(
+            created_at,
+            Some(created_at - 1_000),
+            created_at + 600,
+            timeout
+        ));
+    }
+
+    #[test]
+    fn test_desired_stopped_stops_running_container() {
+        // Arrange
+        let desired = Some("stopped");
+
+        // Act
+        let action = desired_status_action(desired, &ContainerStatus::Running);
+
+        // Assert
+        assert_eq!(action, DesiredStatusAction::Stop);
+    }
+
+    #[test]
+    fn test_desired_stopped_stops_container_before_it_starts() {
+        assert_eq!(
+            desired_status_action(Some("stopped"), &ContainerStatus::Pending),
+            DesiredStatusAction::Stop
+        );
+        assert_eq!(
+            desired_status_action(Some("Stopped"), &ContainerStatus::Queued),
+            DesiredStatusAction::Stop
+        );
+    }
+
+    #[test]
+    fn test_desired_running_resumes_stopped_container() {
+        assert_eq!(
+            desired_status_action(Some("running"), &ContainerStatus::Stopped),
+            DesiredStatusAction::Resume
+        );
+    }
+
+    #[test]
+    fn test_desired_status_already_reached() {
+        assert_eq!(
+            desired_status_action(Some("stopped"), &ContainerStatus::Stopped),
+            DesiredStatusAction::None
+        );
+        assert_eq!(
+            desired_status_action(Some("running"), &ContainerStatus::Running),
+            DesiredStatusAction::None
+        );
+        assert_eq!(
+            desired_status_action(Some("running"), &ContainerStatus::Failed),
+            DesiredStatusAction::None
+        );
+        assert_eq!(
+            desired_status_action(None, &ContainerStatus::Running),
+            DesiredStatusAction::None
+        );
+    }
+
+    #[test]
+    fn test_render_hostname_default_template() {
+        // Arrange
+        let template = DEFAULT_TAILSCALE_HOSTNAME_TEMPLATE;
+
+        // Act
+        let hostname = render_hostname(template, "AbC123", "my-model", "team-a");
+
+        // Assert
+        assert_eq!(hostname, "container-AbC123");
+    }
+
+    #[test]
+    fn test_render_hostname_custom_template() {
+        assert_eq!(
+            render_hostname("{namespace}-{name}-{id}", "abc", "my_model", "team-a"),
+            "team-a-my-model-abc"
+        );
+    }
+
+    #[test]
+    fn test_render_hostname_too_long_falls_back() {
+        let long_name = "x".repeat(80);
+        assert_eq!(
+            render_hostname("{name}-{id}", "abc", &long_name, "ns"),
+            "container-abc"
+        );
+    }
+
+    #[test]
+    fn test_on_failure_restarts_failed_container() {
+        // Arrange
+        let status = ContainerStatus::Failed;
+
+        // Act
+        let decision = restart_decision("OnFailure", &status, 0);
+
+        // Assert
+        assert_eq!(
+            decision,
+            RestartDecision::Restart {
+                attempt: 1,
+                backoff: Duration::from_secs(10)
+            }
+        );
+    }
+
+    #[test]
+    fn test_on_failure_ignores_successful_exit() {
+        let decision = restart_decision("OnFailure", &ContainerStatus::Completed, 0);
+        assert_eq!(decision, RestartDecision::NoRestart);
+    }
+
+    #[test]
+    fn test_on_failure_backoff_is_exponential_and_capped() {
+        let backoffs: Vec<u64> = (0..MAX_RESTART_ATTEMPTS)
+            .map(|attempts| {
+                match restart_decision("OnFailure", &ContainerStatus::Failed, attempts) {
+                    RestartDecision::Restart { backoff, .. } => backoff.as_secs(),
+                    other => panic!("unexpected decision {:?}", other),
+                }
+            })
+            .collect();
+        assert_eq!(backoffs, vec![10, 20, 40, 80, 160]);
+
+        match restart_decision("Always", &ContainerStatus::Failed, 20) {
+            RestartDecision::Restart { backoff, .. } => assert_eq!(backoff.as_secs(), 300),
+            other => panic!("unexpected decision {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_on_failure_stops_at_max_attempts() {
+        let decision =
+            restart_decision("OnFailure", &ContainerStatus::Failed, MAX_RESTART_ATTEMPTS);
+        assert_eq!(decision, RestartDecision::Exhausted);
+    }
+
+    #[test]
+    fn test_always_restarts_regardless_of_exit() {
+        for status in [
+            ContainerStatus::Completed,
+            ContainerStatus::Failed,
+            ContainerStatus::Stopped,
+            ContainerStatus::Exited,
+        ] {
+            assert!(matches!(
+                restart_decision("Always", &status, 0),
+                RestartDecision::Restart { attempt: 1, .. }
+            ));
+        }
+    }
+
+    #[test]
+    fn test_merge_env_adds_namespace_defaults() {
+        // Arrange
+        let defaults = vec![env_var("HF_HOME", Some("/nebu/cache/hf"))];
+        let env = vec![env_var("MODEL", Some("llama"))];
+
+        // Act
+        let merged = merge_env(&defaults, &env);
+
+        // Assert
+        assert_eq!(
+            merged,
+            vec![
+                env_var("HF_HOME", Some("/nebu/cache/hf")),
+                env_var("MODEL", Some("llama"))
+            ]
+        );
+    }
+
+    #[test]
+    fn test_merge_env_container_value_wins() {
+        let defaults = vec![
+            env_var("HF_HOME", Some("/nebu/cache/hf")),
+            env_var("HTTP_PROXY", Some("http://proxy:3128")),
+        ];
+        let env = vec![env_var("HF_HOME", Some("/data/hf"))];
+
+        let merged = merge_env(&defaults, &env);
+
+        assert_eq!(
+            merged,
+            vec![
+                env_var("HTTP_PROXY", Some("http://proxy:3128")),
+                env_var("HF_HOME", Some("/data/hf"))
+            ]
+        );
+    }
+
+    #[test]
+    fn test_merge_env_keeps_secret_references() {
+        let defaults = vec![V1EnvVar {
+            key: "HF_TOKEN".to_string(),
+            value: None,
+            secret_name: Some("hf-token".to_string()),
+            required: false,
+        }];
+
+        let merged = merge_env(&defaults, &[]);
+
+        assert_eq!(merged, defaults);
+    }
+
+    #[test]
+    fn test_never_and_invalid_are_not_restarted() {
+        assert_eq!(
+            restart_decision("Never", &ContainerStatus::Failed, 0),
+            RestartDecision::NoRestart
+        );
+        assert_eq!(
+            restart_decision("Always", &ContainerStatus::Invalid, 0),
+            RestartDecision::NoRestart
+        );
+        assert_eq!(
+            restart_decision("bogus", &ContainerStatus::Failed, 0),
+            RestartDecision::NoRestart
+        );
+    }
+
+    fn secret_var(key: &str, secret: &str, required: bool) -> V1EnvVar {
+        V1EnvVar {
+            key: key.to_string(),
+            value: None,
+            secret_name: Some(secret.to_string()),
+            required,
+        }
+    }
+
+    #[test]
+    fn test_resolve_env_fails_on_missing_required_secret() {
+        let env = vec![
+            secret_var("HF_TOKEN", "hf-token", true),
+            secret_var("WANDB_KEY", "wandb", true),
+        ];
+        let secrets = HashMap::from([(
+            "wandb".to_string(),
+            SecretLookup::Undecryptable("bad key".to_string()),
+        )]);
+
+        assert_eq!(
+            resolve_env(&env, &secrets),
+            Err(EnvResolveError::SecretNotFound {
+                key: "HF_TOKEN".to_string(),
+                secret: "hf-token".to_string(),
+            })
+        );
+        assert_eq!(
+            resolve_env(&env[1..], &secrets),
+            Err(EnvResolveError::SecretUndecryptable {
+                key: "WANDB_KEY".to_string(),
+                secret: "wandb".to_string(),
+                reason: "bad key".to_string(),
+            })
+        );
+    }
+
+    #[test]
+    fn test_resolve_env_skips_missing_optional_secret() {
+        let env = vec![
+            env_var("MODE", Some("train")),
+            secret_var("HF_TOKEN", "hf-token", false),
+            secret_var("WANDB_KEY", "wandb", false),
+            env_var("EMPTY", None),
+        ];
+        let secrets = HashMap::from([
+            (
+                "wandb".to_string(),
+                SecretLookup::Found("s3cret".to_string()),
+            ),
+            (
+                "hf-token".to_string(),
+                SecretLookup::Undecryptable("bad key".to_string()),
+            ),
+        ]);
+
+        assert_eq!(
+            resolve_env(&env, &secrets),
+            Ok(vec![
+                ("MODE".to_string(), "train".to_string()),
+                ("WANDB_KEY".to_string(), "s3cret".to_string()),
+            ])
+        );
+    }
+
+    fn resolved_env() -> HashMap<String, String> {
+        HashMap::from([
+            ("BUCKET".to_string(), "datasets".to_string()),
+            ("EPOCHS".to_string(), "3".to_string()),
+            ("WANDB_KEY".to_string(), "s3cret".to_string()),
+        ])
+    }
+
+    #[test]
+    fn test_expand_variables_in_volume_paths() {
+        // Arrange
+        let env = resolved_env();
+
+        // Act
+        let source = expand_variables("s3://$BUCKET/${EPOCHS}x/$UNSET/", &env);
+        let dest = expand_variables("/data/cost$$/$${BUCKET}", &env);
+
+        // Assert
+        assert_eq!(source, "s3://datasets/3x//");
+        assert_eq!(dest, "/data/cost$/${BUCKET}");
+    }
+
+    #[test]
+    fn test_expanded_command_includes_args() {
+        let env = resolved_env();
+
+        let command = expanded_command(
+            Some("python train.py --bucket $BUCKET"),
+            Some("--epochs ${EPOCHS}"),
+            &env,
+        );
+
+        assert_eq!(
+            command.as_deref(),
+            Some("python train.py --bucket 'datasets' --epochs '3'")
+        );
+        assert_eq!(expanded_command(None, Some("--epochs 1"), &env), None);
+    }
+
+    #[test]
+    fn test_expanded_command_leaves_shell_variables_alone() {
+        let mut env = resolved_env();
+        env.insert("PROMPT".to_string(), "it's; rm -rf /".to_string());
+
+        let command = expanded_command(
+            Some("for i in 1 2; do echo $i $HOME $1 $$ \\$BUCKET; done"),
+            Some("--prompt $PROMPT --pid $$BUCKET"),
+            &env,
+        );
+
+        assert_eq!(
+            command.as_deref(),
+            Some(
+                "for i in 1 2; do echo $i $HOME $1 $$ \\$BUCKET; done \
+                 --prompt 'it'\\''s; rm -rf /' --pid $$BUCKET"
+            )
+        );
+    }
+
+    #[test]
+    fn test_secrets_in_command_are_left_to_the_shell() {
+        let vars = vec![
+            env_var("EPOCHS", Some("3")),
+            secret_var("WANDB_KEY", "wandb", true),
+        ];
+        let env = command_env(&resolved_env(), &vars);
+
+        let command = expanded_command(Some("train --key $WANDB_KEY"), Some("-n $EPOCHS"), &env);
+
+        assert_eq!(command.as_deref(), Some("train --key $WANDB_KEY -n '3'"));
+        // Volumes still get the value
+        assert_eq!(
+            expand_variables("s3://$WANDB_KEY", &resolved_env()),
+            "s3://s3cret"
+        );
+    }
+}
@@ -0,0 +1,1044 @@
+// src/resources/v1/containers/bootstrap.rs
+//
+// The bash script that runs before the user command on platforms without a
+// native agent (RunPod). The script is made of named sections which can be
+// overridden individually, or replaced entirely, through a container's
+// `bootstrap` field.
+//
+// Templates may reference `{{hostname}}`, `{{tags}}`, `{{command}}`, `{{log_file}}`,
+// `{{log_max_bytes}}`, `{{log_keep}}`, `{{log_check_interval}}`, `{{sync_timeout}}`
+// and `{{authorized_keys}}`.
+// A full script override may also include `{{section:<name>}}` to pull in a
+// (possibly overridden) section.
+//
+// The user command is never pasted into the script: `{{command}}` becomes a
+// bash invocation that decodes it from base64 at runtime (see
+// `command_invocation`), so it can't break out of the script and isn't
+// echoed by `set -x`. Base64 only encodes it; anything written into the
+// command can still be read from the pod's arguments.
+
+use crate::entities::containers;
+use crate::resources::v1::containers::models::{V1ContainerBootstrap, V1LogParams};
+use crate::resources::v1::containers::ssh_rotation::shell_quote;
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
+use std::collections::HashMap;
+use std::time::Duration;
+
+pub const DEFAULT_LOG_FILE: &str = "$HOME/.logs/nebu_container.log";
+/// Size the log file is rotated at unless the container sets `log_max_size_mb`
+pub const DEFAULT_LOG_MAX_SIZE_MB: u64 = 100;
+/// Rotated log files kept unless the container sets `log_keep`
+pub const DEFAULT_LOG_KEEP: u32 = 3;
+/// How often the log file's size is checked against `log_max_size_mb`
+pub const LOG_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+
+// `nebu_log` tees the script's output into the log file, so output is passed
+// on as it comes, newline or not. Every `log_check_interval` the file's size
+// is checked, and once past `log_max_bytes` it's copied to `<log_file>.1`,
+// shifting older ones up to `<log_file>.<log_keep>`, and truncated. `tee`
+// appends, so it carries on at the start of the emptied file; what it writes
+// between the copy and the truncation is lost. Each check that finds the file
+// grown also appends `<unix time> <size>` to `<log_file>.idx`, which is how
+// reads with `since` find where to start; it's emptied along with the log.
+const SETUP: &str = r#"
+mkdir -p "$(dirname "{{log_file}}")"
+nebu_log() {
+    { set +x; } 2>/dev/null
+    local log_file="{{log_file}}" max_bytes={{log_max_bytes}} keep={{log_keep}}
+    local tee_pid i size last_size=0
+    tee -a "$log_file" <&0 &
+    tee_pid=$!
+    while kill -0 "$tee_pid" 2>/dev/null; do
+        size=$(stat -c %s "$log_file" 2>/dev/null || echo 0)
+        if [ "$size" -gt "$last_size" ]; then
+            echo "$(date +%s) $size" >> "$log_file.idx"
+        fi
+        last_size=$size
+        if [ "$max_bytes" -gt 0 ] && [ "$size" -ge "$max_bytes" ]; then
+            for ((i = keep - 1; i >= 1; i--)); do
+                if [ -f "$log_file.$i" ]; then mv -f "$log_file.$i" "$log_file.$((i + 1))"; fi
+            done
+            cp -f "$log_file" "$log_file.1" && : > "$log_file"
+            : > "$log_file.idx"
+            last_size=0
+        fi
+        sleep {{log_check_interval}}
+    done
+}
+set -x
+exec > >(nebu_log) 2>&1
+
+nvidia-smi
+echo "[DEBUG] Starting setup..."
+"#;
+
+// Lets users SSH in with the keys from the container's `ssh_keys`.
+// `{{authorized_keys}}` adds each one unless it's already there, so a
+// restarted script doesn't duplicate them.
+const SSH_KEYS: &str = r#"
+echo "[DEBUG] Adding authorized SSH keys..."
+mkdir -p ~/.ssh && chmod 700 ~/.ssh
+touch ~/.ssh/authorized_keys && chmod 600 ~/.ssh/authorized_keys
+{{authorized_keys}}
+"#;
+
+const CURL_INSTALL: &str = r#"
+echo "[DEBUG] Installing curl (if not present)..."
+if ! command -v curl &> /dev/null; then
+    apt-get update && apt-get install -y curl \
+    || (apk update && apk add --no-cache curl) \
+    || echo 'Failed to install curl'
+fi
+echo "[DEBUG] Done installing curl..."
+"#;
+
+const NEBU_INSTALL: &str = r#"
+echo "[DEBUG] Installing nebu (if not present)..."
+if ! command -v nebu &> /dev/null; then
+    curl -s https://raw.githubusercontent.com/agentsea/nebulous/main/remote_install.sh | bash \
+    || echo 'Failed to install nebu'
+fi
+echo "[DEBUG] Done installing nebu; checking version..."
+nebu --version
+"#;
+
+const CACHE: &str = r#"
+echo "[DEBUG] Setting HF_HOME to /nebu/cache/huggingface"
+mkdir -p /nebu/cache/huggingface
+"#;
+
+const TAILSCALE_INSTALL: &str = r#"
+echo "[DEBUG] Installing tailscale (if not present)..."
+if ! command -v tailscale &> /dev/null; then
+    echo "[DEBUG] Tailscale not installed. Installing..."
+    curl -fsSL https://tailscale.com/install.sh | sh
+else
+    echo "[DEBUG] Tailscale already installed."
+fi
+"#;
+
+const TAILSCALE_UP: &str = r#"
+echo "[DEBUG] Starting tailscale daemon ..."
+tailscaled --tun=userspace-networking --socks5-server=localhost:1055 --outbound-http-proxy-listen=localhost:1055  > "$HOME/.logs/tailscaled.log" 2>&1 &
+
+echo "[DEBUG] Waiting for tailscale daemon to start..."
+daemon_running=false
+for i in $(seq 1 10); do
+    echo "[DEBUG] Checking tailscale status (attempt $i)..."
+    status_output=$(tailscale status 2>&1)
+    echo "$status_output"
+
+    # Check if we have either a valid Tailscale IP address or 'Logged out' status
+    if grep -Eq -- '(^|[^0-9])([0-9]{1,3}\.){3}[0-9]{1,3}([^0-9]|$)' <<< "$status_output" || echo "$status_output" | grep -q "Logged out."; then
+        echo "[DEBUG] Tailscale daemon is running (found IP address or 'Logged out' status)."
+        daemon_running=true
+        break
+    else
+        echo "[DEBUG] Tailscale not yet ready, retrying..."
+        sleep 1
+    fi
+done
+
+# Check if daemon was confirmed running after the loop
+if [ "$daemon_running" = false ]; then
+    echo "[ERROR] Tailscale daemon did not get an IP address or 'Logged out' status after 10 attempts."
+    echo "[DEBUG] Last tailscale status output:"
+    echo "$status_output"
+    echo "[DEBUG] Checking tailscaled logs..."
+    cat "$HOME/.logs/tailscaled.log"
+    exit 1
+fi
+
+echo "[DEBUG] Checking if TS_AUTHKEY is set..."
+if [ -z "$TS_AUTHKEY" ]; then
+    echo "[ERROR] TS_AUTHKEY is not set. Please set it and try again."
+    exit 1
+fi
+
+echo "[DEBUG] Starting tailscale up..."
+{ set +x; } 2>/dev/null
+tailscale up --auth-key=$TS_AUTHKEY --hostname="{{hostname}}" --ssh --advertise-tags={{tags}}
+set -x
+"#;
+
+const SYNC: &str = r#"
+echo "[DEBUG] Invoking nebu sync..."
+nebu sync volumes --config /nebu/sync.yaml --interval-seconds 5 \
+    --create-if-missing --config-from-env
+
+echo "[DEBUG] Invoking nebu sync background..."
+nebu sync volumes --config /nebu/sync.yaml --interval-seconds 5 \
+    --create-if-missing --watch --background --block-once --config-from-env
+"#;
+
+const COMMAND: &str = r#"
+nvidia-smi
+echo "[DEBUG] All done with base_command; now running the user command"
+{{command}}
+"#;
+
+const WAIT: &str = r#"
+echo "[DEBUG] Waiting for final sync (timeout {{sync_timeout}}s)..."
+if ! nebu sync wait --config /nebu/sync.yaml --interval-seconds 5 --timeout-seconds {{sync_timeout}}; then
+    echo "[ERROR] Final sync did not complete within {{sync_timeout}}s"
+    NEBU_SYNC_FAILED=1
+fi
+"#;
+
+const DONE: &str = r#"
+echo "[DEBUG] Writing /done.txt..."
+if [ -n "$NEBU_SYNC_FAILED" ]; then
+    echo "sync_failed" > /done.txt
+else
+    echo "done" > /done.txt
+fi
+"#;
+
+// Keeps the pod up so its logs and volumes can still be read until it's
+// deleted. Sleeping in the background and waiting on it lets the trap stop
+// the sleep and end the script as soon as the pod is stopped, and nothing is
+// logged while idle. The trap is set before anything is printed, so the pod
+// can be stopped cleanly once it says it's idling.
+const IDLE: &str = r#"
+{ set +x; } 2>/dev/null
+trap 'kill $! 2>/dev/null; exit 0' TERM INT
+echo "[DEBUG] Command finished, idling until the container is deleted"
+while :; do
+    sleep 86400 &
+    wait $!
+done
+"#;
+
+/// Section names in the order they run, with their default templates.
+pub const SECTIONS: &[(&str, &str)] = &[
+    ("setup", SETUP),
+    ("ssh_keys", SSH_KEYS),
+    ("curl_install", CURL_INSTALL),
+    ("nebu_install", NEBU_INSTALL),
+    ("cache", CACHE),
+    ("tailscale_install", TAILSCALE_INSTALL),
+    ("tailscale_up", TAILSCALE_UP),
+    ("sync", SYNC),
+    ("command", COMMAND),
+    ("wait", WAIT),
+    ("done", DONE),
+    ("idle", IDLE),
+];
+
+/// Sections only run for containers that aren't restarted, see `render`.
+pub const NEVER_RESTART_SECTIONS: &[&str] = &["done", "idle"];
+
+/// Sections omitted when `skip_install` is set.
+pub const INSTALL_SECTIONS: &[&str] = &["curl_install", "nebu_install", "tailscale_install"];
+
+/// Values substituted into the bootstrap templates.
+#[derive(Debug, Clone)]
+pub struct BootstrapVars {
+    pub hostname: String,
+    /// Comma-separated Tailscale tags to advertise
+    pub tags: String,
+    pub command: String,
+    pub log_file: String,
+    /// Bytes the log file may grow to before it's rotated, 0 never rotates
+    pub log_max_bytes: u64,
+    /// Rotated log files kept
+    pub log_keep: u32,
+    /// How often the log file's size is checked for rotation
+    pub log_check_interval: Duration,
+    /// Seconds to wait for the final volume sync before giving up
+    pub sync_timeout: u64,
+    /// Public keys added to the user's authorized_keys
+    pub authorized_keys: Vec<String>,
+}
+
+impl BootstrapVars {
+    /// Vars with the log settings of `bootstrap`, or the defaults
+    pub fn new(
+        hostname: String,
+        tags: String,
+        command: String,
+        bootstrap: Option<&V1ContainerBootstrap>,
+        sync_timeout: u64,
+    ) -> Self {
+        Self {
+            hostname,
+            tags,
+            command,
+            log_file: log_file(bootstrap),
+            log_max_bytes: bootstrap
+                .and_then(|b| b.log_max_size_mb)
+                .unwrap_or(DEFAULT_LOG_MAX_SIZE_MB)
+                * 1024
+                * 1024,
+            log_keep: bootstrap
+                .and_then(|b| b.log_keep)
+                .unwrap_or(DEFAULT_LOG_KEEP),
+            log_check_interval: LOG_CHECK_INTERVAL,
+            sync_timeout,
+            authorized_keys: Vec::new(),
+        }
+    }
+}
+
+/// The log file the bootstrap of `bootstrap` writes to
+pub fn log_file(bootstrap: Option<&V1ContainerBootstrap>) -> String {
+    bootstrap
+        .and_then(|b| b.log_file.clone())
+        .unwrap_or_else(|| DEFAULT_LOG_FILE.to_string())
+}
+
+/// The log file a container's bootstrap writes to, where `logs` reads from.
+/// An unreadable `bootstrap` falls back to the default, as it does when the
+/// script is rendered.
+pub fn container_log_file(container: &containers::Model) -> String {
+    log_file(container.parse_bootstrap().ok().flatten().as_ref())
+}
+
+/// A validated `log_file` quoted for a shell, leaving a leading `$HOME` to
+/// be expanded
+pub fn quoted_log_file(log_file: &str) -> String {
+    match log_file.strip_prefix("$HOME/") {
+        Some(rest) => format!("\"$HOME\"/{}", shell_quote(rest)),
+        None => shell_quote(log_file),
+    }
+}
+
+/// A shell command printing `log_file` as narrowed by `params`. With `since`
+/// it prints nothing unless the file was written to at or after then, and
+/// otherwise starts where the file ended at the last size check before then,
+/// going by `nebu_log`'s index; so it may start up to a check interval early.
+pub fn log_read_command(log_file: &str, params: &V1LogParams) -> String {
+    let log_file = quoted_log_file(log_file);
+    let Some(since) = params.since else {
+        return match params.tail_lines {
+            Some(lines) => format!("tail -n {} {}", lines, log_file),
+            None => format!("cat {}", log_file),
+        };
+    };
+    let read = format!("tail -c +$((${{offset:-0}} + 1)) {}", log_file);
+    let read = match params.tail_lines {
+        Some(lines) => format!("{} | tail -n {}", read, lines),
+        None => read,
+    };
+    format!(
+        "if [ \"$(stat -c %Y {log})\" -ge {since} ]; then \
+         offset=$(awk -v since={since} '$1 < since {{ offset = $2 }} END {{ print offset + 0 }}' {log}.idx 2>/dev/null); \
+         {read}; fi",
+        log = log_file,
+        since = since,
+        read = read
+    )
+}
+
+/// State of a container's `/done.txt`, written by the `done` section.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DoneFile {
+    Missing,
+    Done,
+    /// The command finished but the final sync timed out or failed
+    SyncFailed,
+}
+
+/// Prefixes the result of [`DONE_FILE_CHECK`], so it can be told apart from
+/// login banners and other noise in the SSH output
+pub const DONE_MARKER: &str = "NEBU_DONE=";
+
+/// Command that prints the first line of `/done.txt`, or `0` if it doesn't
+/// exist, after [`DONE_MARKER`].
+pub const DONE_FILE_CHECK: &str = "echo NEBU_DONE=$(head -n 1 /done.txt 2>/dev/null || echo 0)";
+
+/// Parses the output of [`DONE_FILE_CHECK`] from the last marked line. Output
+/// without a marker means the check didn't run, so the file counts as missing.
+pub fn parse_done_file(output: &str) -> DoneFile {
+    let value = output
+        .lines()
+        .filter_map(|line| line.rsplit_once(DONE_MARKER).map(|(_, value)| value))
+        .last()
+        .unwrap_or("");
+    match value.trim() {
+        "" | "0" => DoneFile::Missing,
+        "sync_failed" => DoneFile::SyncFailed,
+        _ => DoneFile::Done,
+    }
+}
+
+/// Checks that all overridden sections exist and the log settings are usable.
+pub fn validate(bootstrap: &V1ContainerBootstrap) -> Result<(), String> {
+    if let Some(log_file) = &bootstrap.log_file {
+        // It's pasted into the script and into SSH commands, so it's kept to
+        // plain path characters, after an optional leading $HOME
+        let path = log_file.strip_prefix("$HOME/").unwrap_or(log_file);
+        if path.is_empty()
+            || !path
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '/' | '.' | '_' | '-'))
+        {
+            return Err(format!(
+                "Invalid bootstrap log_file '{}': must be a path of letters, digits and '/._-', optionally starting with '$HOME/'",
+                log_file
+            ));
+        }
+    }
+    if bootstrap.log_keep == Some(0) {
+        return Err("Bootstrap log_keep must be at least 1".to_string());
+    }
+    if let Some(sections) = &bootstrap.sections {
+        for name in sections.keys() {
+            if !SECTIONS.iter().any(|(section, _)| section == name) {
+                return Err(format!(
+                    "Unknown bootstrap section '{}'. Valid sections: {}",
+                    name,
+                    SECTIONS
+                        .iter()
+                        .map(|(section, _)| *section)
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Renders the bootstrap script. The `done` and `idle` sections are only
+/// included when `include_done` is set (containers with a `Never` restart
+/// policy). A whole-script override that places `done` but not `idle` gets
+/// `idle` right after it, as `done` kept the pod up before `idle` was split
+/// out of it.
+pub fn render(
+    vars: &BootstrapVars,
+    bootstrap: Option<&V1ContainerBootstrap>,
+    include_done: bool,
+) -> String {
+    let empty = HashMap::new();
+    let overrides = bootstrap
+        .and_then(|b| b.sections.as_ref())
+        .unwrap_or(&empty);
+    let skip_install = bootstrap.map(|b| b.skip_install).unwrap_or(false);
+    let skipped = |name: &str| skip_install && INSTALL_SECTIONS.contains(&name);
+
+    let section = |name: &str, default: &str| -> String {
+        if skipped(name) {
+            return String::new();
+        }
+        overrides
+            .get(name)
+            .cloned()
+            .unwrap_or_else(|| default.to_string())
+    };
+
+    let script = match bootstrap.and_then(|b| b.script.as_ref()) {
+        Some(script) => {
+            SECTIONS
+                .iter()
+                .fold(with_idle_after_done(script), |acc, (name, default)| {
+                    acc.replace(
+                        &format!("{{{{section:{}}}}}", name),
+                        &section(*name, *default),
+                    )
+                })
+        }
+        None => SECTIONS
+            .iter()
+            .filter(|(name, _)| include_done || !NEVER_RESTART_SECTIONS.contains(name))
+            .filter(|(name, _)| !skipped(*name))
+            .filter(|(name, _)| *name != "ssh_keys" || !vars.authorized_keys.is_empty())
+            .map(|(name, default)| section(*name, *default))
+            .collect::<Vec<_>>()
+            .join("\n"),
+    };
+
+    substitute(&script, vars)
+}
+
+/// `script` with `{{section:idle}}` placed after `{{section:done}}` unless
+/// it already places it
+fn with_idle_after_done(script: &str) -> String {
+    if script.contains("{{section:idle}}") {
+        return script.to_string();
+    }
+    script.replace("{{section:done}}", "{{section:done}}\n{{section:idle}}")
+}
+
+/// A bash command running `command` in a child shell. The command travels
+/// base64 encoded, in a here-string since `set -x` doesn't trace those, and
+/// the child shell doesn't inherit `set -x`. Being a separate process, it
+/// only sees the script's exported variables, and none of its functions.
+pub fn command_invocation(command: &str) -> String {
+    format!("bash <(base64 -d <<< '{}')", BASE64.encode(command))
+}
+
+fn substitute(template: &str, vars: &BootstrapVars) -> String {
+    template
+        .replace("{{hostname}}", &vars.hostname)
+        .replace("{{tags}}", &vars.tags)
+        .replace("{{log_file}}", &vars.log_file)
+        .replace("{{log_max_bytes}}", &vars.log_max_bytes.to_string())
+        .replace("{{log_keep}}", &vars.log_keep.to_string())
+        .replace(
+            "{{log_check_interval}}",
+            &vars.log_check_interval.as_secs_f64().to_string(),
+        )
+        .replace("{{sync_timeout}}", &vars.sync_timeout.to_string())
+        .replace("{{command}}", &command_invocation(&vars.command))
+        .replace(
+            "{{authorized_keys}}",
+            &authorized_keys_commands(&vars.authorized_keys),
+        )
+}
+
+/// Commands appending each of `keys` to authorized_keys unless it's there
+fn authorized_keys_commands(keys: &[String]) -> String {
+    keys.iter()
+        .map(|key| {
+            let key = shell_quote(key.trim());
+            format!(
+                "grep -qxF {key} ~/.ssh/authorized_keys || printf '%s\\n' {key} >> ~/.ssh/authorized_keys",
+                key = key
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vars() -> BootstrapVars {
+        BootstrapVars {
+            hostname: "container-abc".to_string(),
+            tags: "tag:container".to_string(),
+            command: "python train.py".to_string(),
+            log_file: DEFAULT_LOG_FILE.to_string(),
+            log_max_bytes: DEFAULT_LOG_MAX_SIZE_MB * 1024 * 1024,
+            log_keep: DEFAULT_LOG_KEEP,
+            log_check_interval: LOG_CHECK_INTERVAL,
+            sync_timeout: 600,
+            authorized_keys: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_render_default_script() {
+        // Arrange
+        let vars = vars();
+
+        // Act
+        let script = render(&vars, None, false);
+
+        // Assert
+        assert!(script.contains("log_file=\"$HOME/.logs/nebu_container.log\""));
+        assert!(script.contains("exec > >(nebu_log) 2>&1"));
+        assert!(script.contains("--hostname=\"container-abc\""));
+        assert!(script.contains(&command_invocation("python train.py")));
+        assert!(script.contains("([0-9]{1,3}\\.){3}"));
+        assert!(script.contains("nebu sync wait"));
+        assert!(script.contains("--timeout-seconds 600"));
+        assert!(!script.contains("/done.txt"));
+        assert!(!script.contains("{{"));
+    }
+
+    #[test]
+    fn test_render_tailscale_up_with_custom_tags() {
+        let vars = BootstrapVars {
+            hostname: "team-a-model-abc".to_string(),
+            tags: "tag:team-a,tag:gpu".to_string(),
+            ..vars()
+        };
+
+        let script = render(&vars, None, false);
+
+        assert!(script.contains(
+            "tailscale up --auth-key=$TS_AUTHKEY --hostname=\"team-a-model-abc\" --ssh --advertise-tags=tag:team-a,tag:gpu"
+        ));
+    }
+
+    #[test]
+    fn test_render_includes_done_section() {
+        let script = render(&vars(), None, true);
+        assert!(script.contains("echo \"done\" > /done.txt"));
+        assert!(script.find("nebu sync wait").unwrap() < script.find("/done.txt").unwrap());
+    }
+
+    #[test]
+    fn test_never_script_idles_quietly() {
+        let script = render(&vars(), None, true);
+
+        let idle = &script[script.find("/done.txt").unwrap()..];
+        assert!(idle.contains("trap 'kill $! 2>/dev/null; exit 0' TERM INT"));
+        assert!(idle.contains("wait $!"));
+        // Tracing is off before the loop, and the loop itself prints nothing
+        let idle_loop = &idle[idle.find("while :; do").unwrap()..];
+        assert!(idle.find("{ set +x; }").unwrap() < idle.find("while :; do").unwrap());
+        assert!(!idle_loop.contains("echo"));
+        assert!(!script.contains(">>>all done"));
+
+        assert!(!render(&vars(), None, false).contains("sleep 86400"));
+    }
+
+    #[test]
+    fn test_script_override_with_done_still_idles() {
+        let bootstrap = V1ContainerBootstrap {
+            script: Some("{{section:command}}\n{{section:done}}\n".to_string()),
+            ..Default::default()
+        };
+        let script = render(&vars(), Some(&bootstrap), true);
+        assert!(script.find("/done.txt").unwrap() < script.find("sleep 86400").unwrap());
+
+        // Placing idle elsewhere, or not at all without done, is left alone
+        let bootstrap = V1ContainerBootstrap {
+            script: Some("{{section:idle}}\n{{section:done}}".to_string()),
+            ..Default::default()
+        };
+        let script = render(&vars(), Some(&bootstrap), true);
+        assert_eq!(script.matches("sleep 86400").count(), 1);
+        assert!(script.find("sleep 86400").unwrap() < script.find("/done.txt").unwrap());
+        let bootstrap = V1ContainerBootstrap {
+            script: Some("{{section:command}}".to_string()),
+            ..Default::default()
+        };
+        assert!(!render(&vars(), Some(&bootstrap), true).contains("sleep 86400"));
+    }
+
+    /// Runs the idle section in bash and stops it like a pod deletion would,
+    /// once it says it's idling.
+    #[test]
+    fn test_idle_section_exits_on_sigterm() {
+        use std::io::BufRead;
+
+        let mut child = std::process::Command::new("bash")
+            .arg("-c")
+            .arg(IDLE)
+            .stdout(std::process::Stdio::piped())
+            .spawn()
+            .unwrap();
+        let mut stdout = std::io::BufReader::new(child.stdout.take().unwrap());
+        let mut ready = String::new();
+        stdout.read_line(&mut ready).unwrap();
+
+        std::process::Command::new("kill")
+            .arg("-TERM")
+            .arg(child.id().to_string())
+            .status()
+            .unwrap();
+        let started = std::time::Instant::now();
+        let status = child.wait().unwrap();
+        let mut rest = String::new();
+        std::io::Read::read_to_string(&mut stdout, &mut rest).unwrap();
+
+        assert!(status.success());
+        assert!(started.elapsed() < std::time::Duration::from_secs(5));
+        assert_eq!(
+            ready.trim(),
+            "[DEBUG] Command finished, idling until the container is deleted"
+        );
+        assert_eq!(rest, "");
+    }
+
+    #[test]
+    fn test_render_adds_provided_ssh_keys() {
+        let laptop =
+            "ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAAIBfu6M2GuTXcOmhgUu1PPGUSrht7g4CQLwJ2ddo9wDvO dev@laptop";
+        let with_keys = BootstrapVars {
+            authorized_keys: vec![laptop.to_string(), "ssh-rsa AAAAB3 o'brien".to_string()],
+            ..vars()
+        };
+
+        let script = render(&with_keys, None, false);
+
+        assert!(script.contains(&format!(
+            "grep -qxF '{key}' ~/.ssh/authorized_keys || printf '%s\\n' '{key}' >> ~/.ssh/authorized_keys",
+            key = laptop
+        )));
+        assert!(script.contains("'ssh-rsa AAAAB3 o'\\''brien'"));
+        assert!(script.find("exec > >(nebu_log)").unwrap() < script.find(laptop).unwrap());
+        assert!(script.find(laptop).unwrap() < script.find("tailscale up").unwrap());
+
+        assert!(!render(&vars(), None, false).contains("authorized_keys"));
+    }
+
+    /// Runs the ssh_keys section in bash, twice like a restarted pod would.
+    #[test]
+    fn test_ssh_keys_section_writes_authorized_keys() {
+        let home = tempfile::tempdir().unwrap();
+        let key =
+            "ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAAIBfu6M2GuTXcOmhgUu1PPGUSrht7g4CQLwJ2ddo9wDvO dev@laptop";
+        let vars = BootstrapVars {
+            authorized_keys: vec![key.to_string()],
+            ..vars()
+        };
+        let section = substitute(SSH_KEYS, &vars);
+
+        for _ in 0..2 {
+            let status = std::process::Command::new("bash")
+                .arg("-c")
+                .arg(&section)
+                .env("HOME", home.path())
+                .stdout(std::process::Stdio::null())
+                .status()
+                .unwrap();
+            assert!(status.success());
+        }
+
+        let authorized_keys =
+            std::fs::read_to_string(home.path().join(".ssh").join("authorized_keys")).unwrap();
+        assert_eq!(authorized_keys, format!("{}\n", key));
+    }
+
+    #[test]
+    fn test_render_with_section_override() {
+        let bootstrap = V1ContainerBootstrap {
+            sections: Some(HashMap::from([(
+                "tailscale_install".to_string(),
+                "echo skipping tailscale on {{hostname}}".to_string(),
+            )])),
+            ..Default::default()
+        };
+
+        let script = render(&vars(), Some(&bootstrap), false);
+
+        assert!(script.contains("echo skipping tailscale on container-abc"));
+        assert!(!script.contains("tailscale.com/install.sh"));
+        assert!(script.contains("nebu sync volumes"));
+    }
+
+    #[test]
+    fn test_render_with_script_override() {
+        let bootstrap = V1ContainerBootstrap {
+            script: Some("{{section:setup}}\nexec {{command}}".to_string()),
+            sections: Some(HashMap::from([(
+                "setup".to_string(),
+                "echo custom setup".to_string(),
+            )])),
+            ..Default::default()
+        };
+
+        let script = render(&vars(), Some(&bootstrap), true);
+
+        assert_eq!(
+            script,
+            format!(
+                "echo custom setup\nexec {}",
+                command_invocation("python train.py")
+            )
+        );
+    }
+
+    #[test]
+    fn test_render_skip_install_omits_install_blocks() {
+        let bootstrap = V1ContainerBootstrap {
+            skip_install: true,
+            ..Default::default()
+        };
+
+        let script = render(&vars(), Some(&bootstrap), false);
+
+        assert!(!script.contains("Installing curl"));
+        assert!(!script.contains("remote_install.sh"));
+        assert!(!script.contains("tailscale.com/install.sh"));
+        assert!(script.contains("tailscale up --auth-key=$TS_AUTHKEY"));
+        assert!(script.contains("nebu sync volumes"));
+        assert!(script.contains(&command_invocation("python train.py")));
+    }
+
+    #[test]
+    fn test_render_skip_install_with_script_override() {
+        let bootstrap = V1ContainerBootstrap {
+            script: Some("{{section:nebu_install}}{{section:sync}}".to_string()),
+            skip_install: true,
+            ..Default::default()
+        };
+
+        let script = render(&vars(), Some(&bootstrap), false);
+
+        assert_eq!(script, SYNC);
+    }
+
+    #[test]
+    fn test_render_done_reports_sync_failure() {
+        let script = render(&vars(), None, true);
+        assert!(script.contains("NEBU_SYNC_FAILED=1"));
+        assert!(script.contains("echo \"sync_failed\" > /done.txt"));
+    }
+
+    #[test]
+    fn test_command_stays_out_of_script_and_trace() {
+        let vars = BootstrapVars {
+            command: "echo \"token=$NEBU_TEST_SECRET\"; echo 'key=sk-literal-123'".to_string(),
+            ..vars()
+        };
+        let bootstrap = V1ContainerBootstrap {
+            script: Some("set -x\n{{command}}".to_string()),
+            ..Default::default()
+        };
+
+        let script = render(&vars, Some(&bootstrap), false);
+        // Encoded, not hidden
+        assert!(script.contains(&BASE64.encode(&vars.command)));
+        assert!(!script.contains("sk-literal-123"));
+        assert!(!script.contains("NEBU_TEST_SECRET"));
+        let default_script = render(&vars, None, false);
+        assert!(!default_script.contains("sk-literal-123"));
+        assert!(default_script.contains("{ set +x; } 2>/dev/null\ntailscale up"));
+
+        let output = std::process::Command::new("bash")
+            .arg("-c")
+            .arg(&script)
+            .env("NEBU_TEST_SECRET", "s3cr3t-from-env")
+            .output()
+            .unwrap();
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let trace = String::from_utf8_lossy(&output.stderr);
+        assert!(output.status.success(), "{}", trace);
+        assert_eq!(stdout, "token=s3cr3t-from-env\nkey=sk-literal-123\n");
+        assert!(!trace.contains("s3cr3t-from-env"), "{}", trace);
+        assert!(!trace.contains("sk-literal-123"), "{}", trace);
+    }
+
+    #[test]
+    fn test_parse_done_file() {
+        assert_eq!(parse_done_file("NEBU_DONE=0\n"), DoneFile::Missing);
+        assert_eq!(parse_done_file(""), DoneFile::Missing);
+        assert_eq!(parse_done_file("NEBU_DONE=done\n"), DoneFile::Done);
+        assert_eq!(
+            parse_done_file("NEBU_DONE= sync_failed \n"),
+            DoneFile::SyncFailed
+        );
+    }
+
+    #[test]
+    fn test_parse_done_file_ignores_banner_noise() {
+        let output = "Welcome to Ubuntu 22.04.4 LTS\r\n\
+                      \r\n\
+                      * Documentation:  https://help.ubuntu.com\r\n\
+                      Last login: Tue Oct  1 10:00:00 2024 from 100.64.0.1\r\n\
+                      NEBU_DONE=done\r\n";
+        assert_eq!(parse_done_file(output), DoneFile::Done);
+
+        let output = "1\ndone\nNEBU_DONE=0\nlogout\n";
+        assert_eq!(parse_done_file(output), DoneFile::Missing);
+
+        // A shell echoing the command before running it
+        let output = format!("+ {}\nNEBU_DONE=sync_failed\n", DONE_FILE_CHECK);
+        assert_eq!(parse_done_file(&output), DoneFile::SyncFailed);
+
+        // The check never ran
+        assert_eq!(
+            parse_done_file("done\nPermission denied\n"),
+            DoneFile::Missing
+        );
+    }
+
+    #[test]
+    fn test_validate_rejects_unknown_section() {
+        let bootstrap = V1ContainerBootstrap {
+            sections: Some(HashMap::from([("nope".to_string(), String::new())])),
+            ..Default::default()
+        };
+        assert!(validate(&bootstrap).is_err());
+        assert!(validate(&V1ContainerBootstrap::default()).is_ok());
+    }
+
+    #[test]
+    fn test_log_settings_from_bootstrap() {
+        let bootstrap = V1ContainerBootstrap {
+            log_file: Some("/workspace/logs/job.log".to_string()),
+            log_max_size_mb: Some(5),
+            log_keep: Some(2),
+            ..Default::default()
+        };
+
+        let vars = BootstrapVars::new(
+            "h".to_string(),
+            "tag:container".to_string(),
+            "true".to_string(),
+            Some(&bootstrap),
+            600,
+        );
+        let script = render(&vars, Some(&bootstrap), false);
+
+        assert!(script.contains("mkdir -p \"$(dirname \"/workspace/logs/job.log\")\""));
+        assert!(script.contains("log_file=\"/workspace/logs/job.log\" max_bytes=5242880 keep=2"));
+        assert!(script.contains("cp -f \"$log_file\" \"$log_file.1\" && : > \"$log_file\""));
+        assert!(script.contains(": > \"$log_file.idx\""));
+        assert_eq!(log_file(None), DEFAULT_LOG_FILE);
+        assert!(validate(&bootstrap).is_ok());
+        for bad in [
+            "",
+            "/logs/$(rm -rf ~)",
+            "/logs/a\"b",
+            "/logs/a b",
+            "/logs/a;reboot",
+            "/logs/a|b",
+            "/logs/$USER/job.log",
+            "$HOME/",
+        ] {
+            let bootstrap = V1ContainerBootstrap {
+                log_file: Some(bad.to_string()),
+                ..Default::default()
+            };
+            assert!(validate(&bootstrap).is_err(), "{}", bad);
+        }
+        let bootstrap = V1ContainerBootstrap {
+            log_keep: Some(0),
+            ..Default::default()
+        };
+        assert!(validate(&bootstrap).is_err());
+    }
+
+    #[test]
+    fn test_log_read_command() {
+        let file = "$HOME/.logs/nebu_container.log";
+        assert_eq!(
+            log_read_command(file, &V1LogParams::default()),
+            "cat \"$HOME\"/'.logs/nebu_container.log'"
+        );
+        assert_eq!(
+            log_read_command(
+                file,
+                &V1LogParams {
+                    tail_lines: Some(50),
+                    since: Some(1700000000),
+                }
+            ),
+            "if [ \"$(stat -c %Y \"$HOME\"/'.logs/nebu_container.log')\" -ge 1700000000 ]; \
+             then offset=$(awk -v since=1700000000 '$1 < since { offset = $2 } END { print offset + 0 }' \
+             \"$HOME\"/'.logs/nebu_container.log'.idx 2>/dev/null); \
+             tail -c +$((${offset:-0} + 1)) \"$HOME\"/'.logs/nebu_container.log' | tail -n 50; fi"
+        );
+        assert_eq!(
+            quoted_log_file("/workspace/job.log"),
+            "'/workspace/job.log'"
+        );
+    }
+
+    #[test]
+    fn test_rendered_setup_rotates_the_log() {
+        let dir = tempfile::tempdir().unwrap();
+        let log = dir.path().join("logs").join("job.log");
+        let vars = BootstrapVars {
+            log_file: log.to_string_lossy().to_string(),
+            log_max_bytes: 200,
+            log_keep: 2,
+            log_check_interval: Duration::from_millis(10),
+            ..vars()
+        };
+        // In bursts, each bigger than the limit, for the checks to catch up
+        let bootstrap = V1ContainerBootstrap {
+            script: Some(
+                "{{section:setup}}\nfor i in $(seq 1 100); do echo \"line $i\"; \
+                 if [ $((i % 25)) = 0 ]; then sleep 0.2; fi; done"
+                    .to_string(),
+            ),
+            ..Default::default()
+        };
+        let script = render(&vars, Some(&bootstrap), false);
+
+        let output = std::process::Command::new("bash")
+            .arg("-c")
+            .arg(&script)
+            .output()
+            .unwrap();
+
+        assert!(output.status.success());
+        // Still printed in full, while the files only keep the tail
+        assert!(String::from_utf8_lossy(&output.stdout).contains("line 1\n"));
+        let rotated = |n: u32| log.with_file_name(format!("job.log.{}", n));
+        assert!(rotated(1).exists());
+        assert!(rotated(2).exists());
+        assert!(!rotated(3).exists());
+        assert!(std::fs::metadata(&rotated(1)).unwrap().len() >= 200);
+        // Emptied when the last burst filled it up
+        let current = std::fs::read_to_string(&log).unwrap_or_default();
+        assert!(current.len() < 200);
+        let newest = std::fs::read_to_string(rotated(1)).unwrap() + &current;
+        assert!(newest.contains("line 100\n"));
+    }
+
+    #[test]
+    fn test_since_leaves_out_earlier_lines() {
+        let dir = tempfile::tempdir().unwrap();
+        let log = dir.path().join("job.log");
+        let marker = dir.path().join("since");
+        let vars = BootstrapVars {
+            log_file: log.to_string_lossy().to_string(),
+            log_check_interval: Duration::from_millis(10),
+            ..vars()
+        };
+        // A whole second apart on both sides of the marked time
+        let bootstrap = V1ContainerBootstrap {
+            script: Some(format!(
+                "{{{{section:setup}}}}\nset +x\necho early\nsleep 1.1\ndate +%s > {}\n\
+                 sleep 1.1\necho late\nsleep 0.2",
+                marker.display()
+            )),
+            ..Default::default()
+        };
+        let script = render(&vars, Some(&bootstrap), false);
+        let status = std::process::Command::new("bash")
+            .arg("-c")
+            .arg(&script)
+            .stdout(std::process::Stdio::null())
+            .status()
+            .unwrap();
+        assert!(status.success());
+        let since = std::fs::read_to_string(&marker)
+            .unwrap()
+            .trim()
+            .parse()
+            .unwrap();
+
+        let read = |params: &V1LogParams| {
+            let output = std::process::Command::new("bash")
+                .arg("-c")
+                .arg(log_read_command(&vars.log_file, params))
+                .output()
+                .unwrap();
+            String::from_utf8(output.stdout).unwrap()
+        };
+        let logs = read(&V1LogParams {
+            tail_lines: None,
+            since: Some(since),
+        });
+
+        assert!(logs.contains("late\n"), "{:?}", logs);
+        assert!(!logs.contains("early\n"), "{:?}", logs);
+        assert!(read(&V1LogParams::default()).contains("early\n"));
+    }
+
+    #[test]
+    fn test_setup_logs_output_without_newline_right_away() {
+        let dir = tempfile::tempdir().unwrap();
+        let log = dir.path().join("job.log");
+        let vars = BootstrapVars {
+            log_file: log.to_string_lossy().to_string(),
+            ..vars()
+        };
+        let bootstrap = V1ContainerBootstrap {
+            script: Some("{{section:setup}}\nprintf 'progress 50%%\\r'\nsleep 30".to_string()),
+            ..Default::default()
+        };
+        let script = render(&vars, Some(&bootstrap), false);
+
+        let mut child = std::process::Command::new("bash")
+            .arg("-c")
+            .arg(&script)
+            .stdout(std::process::Stdio::null())
+            .stderr(std::process::Stdio::null())
+            .spawn()
+            .unwrap();
+        let deadline = std::time::Instant::now() + Duration::from_secs(10);
+        let mut logged = String::new();
+        while !logged.contains("progress 50%\r") && std::time::Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(20));
+            logged = std::fs::read_to_string(&log).unwrap_or_default();
+        }
+        let still_running = child.try_wait().unwrap().is_none();
+        child.kill().unwrap();
+        child.wait().unwrap();
+
+        assert!(logged.contains("progress 50%\r"), "{:?}", logged);
+        assert!(still_running);
+    }
+}
@@ -1,41 +1,124 @@
+use crate::config::SERVER_CONFIG;
 use crate::entities::containers;
 use crate::query::Query;
-use crate::state::AppState;
+use crate::resources::v1::containers::factory::{platform_factory, PlatformType};
+use crate::resources::v1::containers::fallback;
+use crate::resources::v1::containers::models::ControllerData;
+use crate::resources::v1::containers::notify;
+use crate::resources::v1::containers::reconcile_stats::{ReconcileStats, ReconcileSummary};
+use crate::resources::v1::containers::volume_gc::{
+    collect_orphaned_volumes, volume_usage, NetworkVolumeClient, VOLUME_GC_INTERVAL,
+};
+use crate::state::{AppState, MessageQueue};
+use crate::utils::lease::{LeaseManager, PROCESS_HOLDER_ID};
+use crate::utils::work_pool::WorkPool;
 use std::sync::Arc;
 use tokio::task::JoinHandle;
-use tracing::{debug, error, info};
+use tracing::{debug, error, info, warn, Instrument};
 
 use anyhow::Result;
 use dashmap::DashMap;
 use once_cell::sync::Lazy;
-use sea_orm::ActiveModelTrait;
-use serde::{Deserialize, Serialize};
+use sea_orm::{ActiveModelTrait, ConnectionTrait};
 use short_uuid::ShortUuid;
 
-/// A struct defining any reconciler metadata you want to store in `controller_data`.
-/// This might hold more fields (timestamps, logs, etc.) if desired.
-#[derive(Debug, Clone, Serialize, Deserialize)]
-struct ReconcilerData {
-    thread_id: Option<String>,
-}
+/// How often the reconciler runs when nothing wakes it sooner
+const RECONCILE_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_secs(2);
+
+/// Passes between the summaries the loop logs, about one a minute when idle
+const RECONCILE_SUMMARY_EVERY: u64 = 30;
 
 /// A global map from some container "thread_id" -> the running JoinHandle.
 /// We’ll store the `thread_id` in DB and look it up here to see if it’s finished.
 static CONTAINER_RECON_TASKS: Lazy<DashMap<String, JoinHandle<()>>> = Lazy::new(DashMap::new);
 
+/// Caps the platform operations all container reconcilers run at once, so a
+/// burst of containers doesn't hammer the provider APIs or the database.
+pub static PLATFORM_OPERATIONS: Lazy<WorkPool> =
+    Lazy::new(|| WorkPool::new(SERVER_CONFIG.reconcile_concurrency));
+
+/// The Redis key of the lease on reconciling a container
+pub fn reconcile_lease_key(container_id: &str) -> String {
+    format!("lease:reconcile:container:{}", container_id)
+}
+
+/// The platform a container runs on, built by `factory` and taking one
+/// reconcile step at a time with `single_pass`
+fn platform(factory: fn(String) -> PlatformType, name: String, single_pass: bool) -> PlatformType {
+    let platform = factory(name);
+    if single_pass {
+        platform.single_pass()
+    } else {
+        platform
+    }
+}
+
 pub struct ContainerController {
     app_state: Arc<AppState>,
+    stats: Arc<ReconcileStats>,
+    /// Keeps replicas from reconciling the same container at once. Only
+    /// available with Redis; without it a single replica is assumed.
+    leases: Option<LeaseManager>,
+    /// Builds the platforms containers are reconciled on
+    platform_factory: fn(String) -> PlatformType,
 }
 
 impl ContainerController {
     pub fn new(app_state: Arc<AppState>) -> Self {
-        Self { app_state }
+        let leases = match &app_state.message_queue {
+            MessageQueue::Redis { client } => Some(LeaseManager::new(
+                client.clone(),
+                PROCESS_HOLDER_ID.clone(),
+                SERVER_CONFIG.reconcile_lease_ttl,
+            )),
+            _ => None,
+        };
+        Self {
+            app_state,
+            stats: Arc::new(ReconcileStats::default()),
+            leases,
+            platform_factory,
+        }
+    }
+
+    /// Reconcile on the platforms `factory` builds instead of `platform_factory`'s
+    pub fn with_platform_factory(mut self, factory: fn(String) -> PlatformType) -> Self {
+        self.platform_factory = factory;
+        self
+    }
+
+    /// How the reconcile loop has been doing
+    pub fn stats(&self) -> &ReconcileStats {
+        &self.stats
     }
 
     /// The main loop that spawns or skips reconciliation tasks (threads).
     /// Each container’s `controller_data` field will hold the JSON specifying its `thread_id`.
     pub async fn reconcile(&self) {
+        self.reconcile_pass(false).await;
+    }
+
+    /// Runs a single pass and waits for the reconcile tasks it started,
+    /// for running the controller as a one-off job. Each container takes one
+    /// reconcile step rather than being watched until it ends.
+    pub async fn reconcile_once(&self) -> ReconcileSummary {
+        for thread_id in self.reconcile_pass(true).await {
+            if let Some((_, handle)) = CONTAINER_RECON_TASKS.remove(&thread_id) {
+                if let Err(e) = handle.await {
+                    error!("[Container Controller] Reconcile task failed: {:?}", e);
+                }
+            }
+        }
+        self.stats.summary()
+    }
+
+    /// One pass over the active containers, returning the thread ids of the
+    /// reconcile tasks it spawned. With `single_pass` those tasks return
+    /// after one step.
+    async fn reconcile_pass(&self, single_pass: bool) -> Vec<String> {
         info!("[Container Controller] Starting container reconciliation process");
+        self.stats.record_pass();
+        let mut spawned = Vec::new();
 
         match Query::find_all_active_containers(&self.app_state.db_pool).await {
             Ok(containers) => {
@@ -43,17 +126,26 @@ impl ContainerController {
                     "[DEBUG:controller.rs:reconcile] Found {} containers to reconcile",
                     containers.len()
                 );
+                // Deleted and finished containers aren't reconciled anymore
+                self.stats.retain_containers(
+                    &containers.iter().map(|c| c.id.as_str()).collect::<Vec<_>>(),
+                );
                 for container in containers {
                     debug!(
                         "[DEBUG:controller.rs:reconcile] Inspecting container {}",
                         container.id
                     );
-                    // Attempt to parse `controller_data` as `ReconcilerData`.
-                    let mut existing_data =
-                        match container.parse_controller_data::<ReconcilerData>() {
-                            Ok(Some(data)) => data,
-                            _ => ReconcilerData { thread_id: None },
-                        };
+                    // Rewriting the thread_id would lose whatever is there
+                    let mut existing_data = match container.typed_controller_data() {
+                        Ok(data) => data,
+                        Err(e) => {
+                            error!(
+                                "[Container Controller] Unreadable controller_data of container {}, not reconciling it: {}",
+                                container.id, e
+                            );
+                            continue;
+                        }
+                    };
 
                     debug!(
                         "[DEBUG:controller.rs:reconcile] Existing thread_id = {:?}",
@@ -119,10 +211,37 @@ impl ContainerController {
                     }
 
                     // Actually spawn a background task
+                    let span = crate::logging::container_span(&container);
                     let handle = tokio::spawn({
                         let db_pool = self.app_state.db_pool.clone();
+                        let stats = self.stats.clone();
+                        let leases = self.leases.clone();
+                        let factory = self.platform_factory;
                         let container_clone = container.clone();
                         async move {
+                            // Another replica may be on this container already
+                            let lease_key = reconcile_lease_key(&container_clone.id);
+                            let lease = match &leases {
+                                Some(leases) => match leases.try_acquire(&lease_key).await {
+                                    Ok(Some(lease)) => Some(lease),
+                                    Ok(None) => {
+                                        info!(
+                                            "[Container Controller] Container {} is being reconciled by another replica; skipping.",
+                                            container_clone.id
+                                        );
+                                        return;
+                                    }
+                                    // Without it another replica may be acting on the container
+                                    Err(e) => {
+                                        warn!(
+                                            "[Container Controller] Failed to take reconcile lease for container {}, skipping this pass: {}",
+                                            container_clone.id, e
+                                        );
+                                        return;
+                                    }
+                                },
+                                None => None,
+                            };
                             info!(
                                 "[Container Controller] Reconciling container {} in background task",
                                 container_clone.id
@@ -131,29 +250,72 @@ impl ContainerController {
                                 "[DEBUG:controller.rs:spawn] Calling platform.reconcile for container {}",
                                 container_clone.id
                             );
-                            // If your platform_factory is async, call it here.
-                            let platform_name = container_clone
-                                .platform
-                                .clone()
-                                .unwrap_or_else(|| "runpod".to_string());
-                            let platform =
-                                crate::resources::v1::containers::factory::platform_factory(
-                                    platform_name,
+                            let container_id = container_clone.id.clone();
+                            let reconciled = fallback::reconcile_with_fallback(
+                                &db_pool,
+                                container_clone,
+                                |platform_name, container| {
+                                    let db_pool = &db_pool;
+                                    async move {
+                                        platform(factory, platform_name, single_pass)
+                                            .reconcile(&container, db_pool)
+                                            .await
+                                    }
+                                },
+                                |platform_name, container| {
+                                    let db_pool = &db_pool;
+                                    async move {
+                                        platform(factory, platform_name, single_pass)
+                                            .launch(&container, db_pool)
+                                            .await
+                                    }
+                                },
+                            );
+                            // Stop acting on the container once another replica may have it
+                            let result = match &lease {
+                                Some(lease) => tokio::select! {
+                                    result = reconciled => result,
+                                    _ = lease.lost() => Err(format!(
+                                        "Lost the reconcile lease on container {}",
+                                        container_id
+                                    )
+                                    .into()),
+                                },
+                                None => reconciled.await,
+                            };
+                            if let Err(e) = &result {
+                                debug!(
+                                    "[DEBUG:controller.rs:spawn] Reconcile failed for container {}: {}",
+                                    container_id, e
                                 );
-                            let _ = platform.reconcile(&container_clone, &db_pool).await;
+                            }
+                            stats.record_container(
+                                &container_id,
+                                result.err().map(|e| e.to_string()),
+                            );
+                            if let Some(lease) = lease {
+                                if let Err(e) = lease.release().await {
+                                    warn!(
+                                        "[Container Controller] Failed to release reconcile lease for container {}: {}",
+                                        container_id, e
+                                    );
+                                }
+                            }
                             debug!(
                                 "[DEBUG:controller.rs:spawn] Returned from platform.reconcile for container {}",
-                                container_clone.id
+                                container_id
                             );
                             info!(
                                 "[Container Controller] Container {} reconcile task finished.",
-                                container_clone.id
+                                container_id
                             )
                         }
+                        .instrument(span)
                     });
 
                     // Store handle in the map
-                    CONTAINER_RECON_TASKS.insert(new_thread_id, handle);
+                    CONTAINER_RECON_TASKS.insert(new_thread_id.clone(), handle);
+                    spawned.push(new_thread_id);
                 }
             }
             Err(e) => {
@@ -164,20 +326,18 @@ impl ContainerController {
             }
         }
         debug!("[DEBUG:controller.rs:reconcile] Finished single reconcile pass");
+        spawned
     }
 
     /// Helper to save the updated `controller_data` back into the DB.
     async fn store_thread_id_in_db(
         container: &containers::Model,
-        rec_data: &ReconcilerData,
+        rec_data: &ControllerData,
         db_pool: &sea_orm::DatabaseConnection,
     ) -> Result<(), sea_orm::DbErr> {
-        // Convert to JSON
-        let data_json = serde_json::to_value(rec_data).unwrap_or_default();
-
         // Build an ActiveModel for the update
         let mut active = containers::ActiveModel::from(container.clone());
-        active.controller_data = sea_orm::ActiveValue::Set(Some(data_json));
+        active.controller_data = sea_orm::ActiveValue::Set(Some(rec_data.to_json()));
 
         // Perform the update
         active.update(db_pool).await?;
@@ -186,19 +346,224 @@ impl ContainerController {
 }
 
 impl ContainerController {
-    /// Spawns a background Tokio task to run the controller reconciliation loop
+    /// Spawns a background Tokio task to run the controller reconciliation loop.
+    /// On Postgres, container changes wake it right away through LISTEN/NOTIFY;
+    /// polling every couple of seconds still catches anything missed.
     pub fn spawn_reconciler(&self) -> tokio::task::JoinHandle<()> {
         let app_state_clone = Arc::clone(&self.app_state);
+        let wake = Arc::new(tokio::sync::Notify::new());
+        if self.app_state.db_pool.get_database_backend() == sea_orm::DbBackend::Postgres {
+            notify::spawn_listener(SERVER_CONFIG.database_url.clone(), wake.clone());
+        }
 
         tokio::spawn(async move {
             let controller = ContainerController::new(app_state_clone);
 
-            // Create an infinite loop to continuously reconcile containers
+            notify::run_reconcile_loop(
+                || async {
+                    controller.reconcile().await;
+                    if controller.stats.passes() % RECONCILE_SUMMARY_EVERY == 0 {
+                        info!("[Container Controller] {}", controller.stats.summary());
+                    }
+                },
+                wake,
+                RECONCILE_POLL_INTERVAL,
+            )
+            .await
+        })
+    }
+
+    /// Spawns a background Tokio task that hard deletes soft-deleted containers
+    /// once they are older than the configured retention window, along with
+    /// expired idempotency keys
+    pub fn spawn_purger(&self) -> tokio::task::JoinHandle<()> {
+        let app_state_clone = Arc::clone(&self.app_state);
+
+        tokio::spawn(async move {
             loop {
-                controller.reconcile().await;
-                // Add a delay between reconciliation cycles
-                tokio::time::sleep(tokio::time::Duration::from_secs(2)).await;
+                let retention =
+                    chrono::Duration::from_std(crate::config::SERVER_CONFIG.deleted_retention)
+                        .unwrap_or(chrono::Duration::days(7));
+                let older_than = chrono::Utc::now() - retention;
+
+                match crate::mutation::Mutation::purge_deleted_containers(
+                    &app_state_clone.db_pool,
+                    older_than,
+                )
+                .await
+                {
+                    Ok(0) => debug!("[Container Controller] No deleted containers to purge"),
+                    Ok(count) => {
+                        info!("[Container Controller] Purged {} deleted containers", count)
+                    }
+                    Err(e) => error!(
+                        "[Container Controller] Failed to purge deleted containers: {:?}",
+                        e
+                    ),
+                }
+
+                let ttl =
+                    chrono::Duration::from_std(crate::config::SERVER_CONFIG.idempotency_key_ttl)
+                        .unwrap_or(chrono::Duration::days(1));
+                match crate::mutation::Mutation::purge_expired_idempotency_keys(
+                    &app_state_clone.db_pool,
+                    chrono::Utc::now() - ttl,
+                )
+                .await
+                {
+                    Ok(0) => (),
+                    Ok(count) => info!(
+                        "[Container Controller] Purged {} expired idempotency keys",
+                        count
+                    ),
+                    Err(e) => error!(
+                        "[Container Controller] Failed to purge idempotency keys: {:?}",
+                        e
+                    ),
+                }
+
+                tokio::time::sleep(tokio::time::Duration::from_secs(60 * 60)).await;
             }
         })
     }
+
+    /// Periodically deletes RunPod network volumes no container has needed for
+    /// `volume_gc_grace`. Does nothing unless the grace period is configured.
+    pub fn spawn_volume_gc(&self) -> Option<tokio::task::JoinHandle<()>> {
+        let grace = SERVER_CONFIG.volume_gc_grace?;
+        let dry_run = SERVER_CONFIG.volume_gc_dry_run;
+        let Some(client) = NetworkVolumeClient::from_env() else {
+            warn!(
+                "[Container Controller] RUNPOD_API_KEY not set, volume garbage collection disabled"
+            );
+            return None;
+        };
+        let app_state_clone = Arc::clone(&self.app_state);
+
+        Some(tokio::spawn(async move {
+            loop {
+                match Query::find_containers_by_platform_including_deleted(
+                    &app_state_clone.db_pool,
+                    "runpod",
+                )
+                .await
+                {
+                    Ok(containers) => {
+                        let usage = volume_usage(&containers);
+                        if let Err(e) =
+                            collect_orphaned_volumes(&client, &usage, grace, dry_run).await
+                        {
+                            error!(
+                                "[Container Controller] Volume garbage collection failed: {}",
+                                e
+                            );
+                        }
+                    }
+                    Err(e) => error!(
+                        "[Container Controller] Failed to load containers for volume garbage collection: {:?}",
+                        e
+                    ),
+                }
+
+                tokio::time::sleep(VOLUME_GC_INTERVAL).await;
+            }
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::resources::v1::containers::base::ContainerStatus;
+    use crate::resources::v1::containers::models::V1ContainerStatus;
+    use crate::resources::v1::containers::runpod::RunpodPlatform;
+    use crate::state::MessageQueue;
+
+    async fn state_with_container(id: &str, platform: &str, status: ContainerStatus) -> AppState {
+        let db = crate::db::test_db().await;
+
+        containers::ActiveModel {
+            id: sea_orm::Set(id.to_string()),
+            namespace: sea_orm::Set("ns".to_string()),
+            name: sea_orm::Set("train".to_string()),
+            full_name: sea_orm::Set("ns/train".to_string()),
+            owner: sea_orm::Set("me".to_string()),
+            image: sea_orm::Set("busybox".to_string()),
+            platform: sea_orm::Set(Some(platform.to_string())),
+            resource_name: sea_orm::Set(Some(format!("pod-{}", id))),
+            restart: sea_orm::Set("Never".to_string()),
+            status: sea_orm::Set(Some(serde_json::json!(V1ContainerStatus {
+                status: Some(status.to_string()),
+                ..Default::default()
+            }))),
+            updated_at: sea_orm::Set(chrono::Utc::now().into()),
+            created_at: sea_orm::Set(chrono::Utc::now().into()),
+            ..Default::default()
+        }
+        .insert(&db)
+        .await
+        .unwrap();
+
+        AppState {
+            db_pool: db,
+            message_queue: MessageQueue::Redis {
+                client: Arc::new(redis::Client::open("redis://127.0.0.1:1").unwrap()),
+            },
+            extra_queues: Default::default(),
+        }
+    }
+
+    /// A controller without reconcile leases, like a single replica; the
+    /// state's Redis isn't reachable
+    fn single_replica(state: AppState) -> ContainerController {
+        ContainerController {
+            leases: None,
+            ..ContainerController::new(Arc::new(state))
+        }
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a Postgres at NEBU_TEST_DATABASE_URL"]
+    async fn test_reconcile_once_runs_exactly_one_pass() {
+        // Arrange
+        let controller =
+            single_replica(state_with_container("once1", "kube", ContainerStatus::Pending).await);
+
+        // Act
+        let summary = controller.reconcile_once().await;
+
+        // Assert
+        assert_eq!(summary.passes, 1);
+        assert_eq!(controller.stats().passes(), 1);
+        assert_eq!(summary.containers, 1);
+        assert!(summary.failing.is_empty());
+        let reconciled = controller.stats().container("once1").unwrap();
+        assert_eq!(reconciled.last_error, None);
+        assert_eq!(reconciled.errors, 0);
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a Postgres at NEBU_TEST_DATABASE_URL"]
+    async fn test_reconcile_once_returns_for_a_running_runpod_container() {
+        // Only read to build the client; the pod lookup fails either way
+        let controller =
+            single_replica(state_with_container("once2", "runpod", ContainerStatus::Running).await)
+                .with_platform_factory(|name| match name.as_str() {
+                    "runpod" => {
+                        PlatformType::Runpod(RunpodPlatform::with_api_key("test".to_string()))
+                    }
+                    _ => platform_factory(name),
+                });
+
+        // A watch would poll the pod until it ends
+        let summary = tokio::time::timeout(
+            std::time::Duration::from_secs(60),
+            controller.reconcile_once(),
+        )
+        .await
+        .expect("reconcile_once should not watch the pod");
+
+        assert_eq!(summary.passes, 1);
+        assert!(controller.stats().container("once2").is_some());
+    }
 }
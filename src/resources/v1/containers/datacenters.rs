@@ -0,0 +1,600 @@
+// src/resources/v1/containers/datacenters.rs
+//
+// Datacenter selection for RunPod pods. GPU pods pick from the datacenters
+// stocking their GPU; CPU pods pick from every listed datacenter with network
+// storage, ranked by CPU stock. Both use `placement_key`, so a pod goes back
+// to the datacenter holding its owner's network volume rather than leaving
+// that volume behind and creating another one elsewhere.
+//
+// `DatacenterClient` is also where RunPod's GPU types and their prices are
+// fetched, for picking a GPU and for the accelerator catalog alike.
+
+use crate::utils::ttl_cache::TtlCache;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use std::time::Duration;
+use tracing::{debug, info, warn};
+
+const DEFAULT_RUNPOD_GRAPHQL_URL: &str = "https://api.runpod.io/graphql";
+
+/// Used for CPU pods when no other datacenter qualifies or RunPod can't be asked
+pub const FALLBACK_CPU_DATACENTER: &str = "EU-RO-1";
+
+const DATACENTERS_QUERY: &str = "query { dataCenters { id name location storageSupport listed } }";
+
+const NETWORK_VOLUMES_QUERY: &str = "query { myself { networkVolumes { id name dataCenterId } } }";
+
+/// The stock of each CPU flavor in a datacenter
+const CPU_STOCK_QUERY: &str = "query CpuStock($input: SpecificsInput) { cpuFlavors { id specifics(input: $input) { stockStatus } } }";
+
+const GPU_TYPES_QUERY: &str = "query { gpuTypes { id displayName memoryInGb securePrice \
+     communityPrice lowestPrice(input: { gpuCount: 1 }) { minimumBidPrice uninterruptablePrice \
+     stockStatus } } }";
+
+/// How long the GPU types are reused before asking RunPod again. Short,
+/// since they also tell what is currently available and what it costs.
+const GPU_TYPES_CACHE_TTL: Duration = Duration::from_secs(60);
+
+/// Shared across requests and every `RunpodPlatform`
+static GPU_TYPES_CACHE: once_cell::sync::Lazy<TtlCache<Vec<GpuPricing>>> =
+    once_cell::sync::Lazy::new(|| TtlCache::new(GPU_TYPES_CACHE_TTL));
+
+// Helper function to assign preference score based on location
+pub(crate) fn location_preference(location: &str) -> i32 {
+    // TODO: configurable!
+    if location.starts_with("United States")
+        || location.starts_with("Europe")
+        || location.starts_with("Canada")
+    {
+        0 // Highest preference: US or Europe
+    } else {
+        2 // Lowest preference: Others
+    }
+}
+
+/// RunPod's stock levels, best first; unknown stock comes last
+pub(crate) fn stock_preference(status: Option<&str>) -> i32 {
+    match status {
+        Some("High") => 0,
+        Some("Medium") => 1,
+        Some("Low") => 2,
+        _ => 3,
+    }
+}
+
+/// Sort key for placing a pod of `owner` in a datacenter, lowest first:
+/// datacenters holding the owner's network volume, then by location and
+/// stock. What's left is ordered by a hash of owner and datacenter, so owners
+/// are spread over equally good datacenters instead of all landing on the
+/// lowest id, while each owner keeps landing on the same one.
+pub(crate) fn placement_key(
+    id: &str,
+    location: &str,
+    stock: Option<&str>,
+    owner: &str,
+    volume_datacenters: &[String],
+) -> (bool, i32, i32, u64) {
+    let digest = ring::digest::digest(
+        &ring::digest::SHA256,
+        format!("{}\0{}", owner, id).as_bytes(),
+    );
+    let mut spread = [0u8; 8];
+    spread.copy_from_slice(&digest.as_ref()[..8]);
+    (
+        !volume_datacenters.iter().any(|dc| dc == id),
+        location_preference(location),
+        stock_preference(stock),
+        u64::from_be_bytes(spread),
+    )
+}
+
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Datacenter {
+    pub id: String,
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub location: String,
+    #[serde(default)]
+    pub storage_support: bool,
+    #[serde(default = "default_listed")]
+    pub listed: bool,
+    /// The best stock of any CPU flavor, when RunPod said
+    #[serde(default)]
+    pub cpu_stock: Option<String>,
+}
+
+fn default_listed() -> bool {
+    true
+}
+
+/// The datacenter for a CPU pod of `owner`: listed, with storage support for
+/// the owner's network volume, in `placement_key` order.
+pub fn select_cpu_datacenter<'a>(
+    datacenters: &'a [Datacenter],
+    owner: &str,
+    volume_datacenters: &[String],
+) -> Option<&'a Datacenter> {
+    datacenters
+        .iter()
+        .filter(|dc| dc.listed && dc.storage_support)
+        .min_by_key(|dc| {
+            placement_key(
+                &dc.id,
+                &dc.location,
+                dc.cpu_stock.as_deref(),
+                owner,
+                volume_datacenters,
+            )
+        })
+}
+
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct LowestPrice {
+    pub minimum_bid_price: Option<f64>,
+    pub uninterruptable_price: Option<f64>,
+    /// `High`, `Medium` or `Low`; absent when none are in stock
+    pub stock_status: Option<String>,
+}
+
+/// A RunPod GPU type with its current prices per GPU and hour
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct GpuPricing {
+    pub id: String,
+    pub display_name: Option<String>,
+    pub memory_in_gb: Option<i64>,
+    pub secure_price: Option<f64>,
+    pub community_price: Option<f64>,
+    pub lowest_price: Option<LowestPrice>,
+}
+
+impl GpuPricing {
+    /// The cheapest on-demand price, falling back to the listed cloud prices
+    pub fn price_per_hour(&self) -> Option<f64> {
+        self.lowest_price
+            .as_ref()
+            .and_then(|p| p.uninterruptable_price)
+            .or_else(|| {
+                [self.secure_price, self.community_price]
+                    .into_iter()
+                    .flatten()
+                    .filter(|price| *price > 0.0)
+                    .min_by(|a, b| a.total_cmp(b))
+            })
+    }
+}
+
+/// A network volume of the account, where it lives decides where pods using it can go
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct NetworkVolume {
+    pub id: String,
+    pub name: String,
+    pub data_center_id: String,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum DatacenterError {
+    #[error("RunPod API request failed: {0}")]
+    Request(#[from] reqwest::Error),
+
+    #[error("RunPod API returned {0}: {1}")]
+    Api(reqwest::StatusCode, String),
+
+    #[error("RunPod API error: {0}")]
+    GraphQL(String),
+}
+
+#[derive(Deserialize)]
+struct GraphQLResponse<T> {
+    data: Option<T>,
+    #[serde(default)]
+    errors: Vec<GraphQLError>,
+}
+
+#[derive(Deserialize)]
+struct GraphQLError {
+    message: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DatacentersData {
+    #[serde(default)]
+    data_centers: Vec<Datacenter>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GpuTypesData {
+    #[serde(default)]
+    gpu_types: Vec<GpuPricing>,
+}
+
+#[derive(Deserialize)]
+struct MyselfData {
+    myself: Option<NetworkVolumesData>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct NetworkVolumesData {
+    #[serde(default)]
+    network_volumes: Vec<NetworkVolume>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CpuStockData {
+    #[serde(default)]
+    cpu_flavors: Vec<CpuFlavorStock>,
+}
+
+#[derive(Deserialize)]
+struct CpuFlavorStock {
+    #[serde(default)]
+    specifics: Option<CpuFlavorSpecifics>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CpuFlavorSpecifics {
+    #[serde(default)]
+    stock_status: Option<String>,
+}
+
+/// Talks to RunPod's GraphQL API, for what its REST client doesn't cover.
+#[derive(Clone)]
+pub struct DatacenterClient {
+    api_key: String,
+    url: String,
+    http: reqwest::Client,
+}
+
+impl DatacenterClient {
+    pub fn new(api_key: String, url: String) -> Self {
+        Self {
+            api_key,
+            url,
+            http: crate::utils::http::shared_client(),
+        }
+    }
+
+    /// Uses `RUNPOD_GRAPHQL_URL` when set
+    pub fn with_api_key(api_key: String) -> Self {
+        let url = std::env::var("RUNPOD_GRAPHQL_URL")
+            .unwrap_or_else(|_| DEFAULT_RUNPOD_GRAPHQL_URL.to_string());
+        Self::new(api_key, url)
+    }
+
+    /// Uses `RUNPOD_API_KEY`, and `RUNPOD_GRAPHQL_URL` when set. None without a key.
+    pub fn from_env() -> Option<Self> {
+        let api_key = std::env::var("RUNPOD_API_KEY").ok()?;
+        Some(Self::with_api_key(api_key))
+    }
+
+    /// Runs a GraphQL query or mutation, returning its `data`
+    pub async fn graphql<T: DeserializeOwned>(
+        &self,
+        query: &str,
+        variables: serde_json::Value,
+    ) -> Result<Option<T>, DatacenterError> {
+        let response = self
+            .http
+            .post(&self.url)
+            .bearer_auth(&self.api_key)
+            .json(&serde_json::json!({ "query": query, "variables": variables }))
+            .send()
+            .await?;
+        if !response.status().is_success() {
+            return Err(DatacenterError::Api(
+                response.status(),
+                response.text().await.unwrap_or_default(),
+            ));
+        }
+        let body: GraphQLResponse<T> = response.json().await?;
+        if !body.errors.is_empty() {
+            let messages: Vec<String> = body.errors.into_iter().map(|e| e.message).collect();
+            return Err(DatacenterError::GraphQL(messages.join("; ")));
+        }
+        Ok(body.data)
+    }
+
+    pub async fn list(&self) -> Result<Vec<Datacenter>, DatacenterError> {
+        let data: Option<DatacentersData> = self
+            .graphql(DATACENTERS_QUERY, serde_json::Value::Null)
+            .await?;
+        Ok(data.map(|d| d.data_centers).unwrap_or_default())
+    }
+
+    /// The account's network volumes
+    pub async fn network_volumes(&self) -> Result<Vec<NetworkVolume>, DatacenterError> {
+        let data: Option<MyselfData> = self
+            .graphql(NETWORK_VOLUMES_QUERY, serde_json::Value::Null)
+            .await?;
+        Ok(data
+            .and_then(|d| d.myself)
+            .map(|m| m.network_volumes)
+            .unwrap_or_default())
+    }
+
+    /// RunPod's GPU types with their current prices and stock
+    pub async fn gpu_types(&self) -> Result<Vec<GpuPricing>, DatacenterError> {
+        let data: Option<GpuTypesData> = self
+            .graphql(GPU_TYPES_QUERY, serde_json::Value::Null)
+            .await?;
+        Ok(data.map(|d| d.gpu_types).unwrap_or_default())
+    }
+
+    /// `gpu_types`, cached for `GPU_TYPES_CACHE_TTL` so bursts of requests
+    /// share one lookup
+    pub async fn cached_gpu_types(&self) -> Result<Vec<GpuPricing>, DatacenterError> {
+        GPU_TYPES_CACHE.get_or_refresh(|| self.gpu_types()).await
+    }
+
+    /// Drops the cached GPU types, e.g. when a pod couldn't get the GPU
+    /// they said was available
+    pub async fn invalidate_gpu_types() {
+        GPU_TYPES_CACHE.invalidate().await;
+    }
+
+    /// The best stock of any CPU flavor in a datacenter, None when none is stocked
+    pub async fn cpu_stock(&self, datacenter_id: &str) -> Result<Option<String>, DatacenterError> {
+        let data: Option<CpuStockData> = self
+            .graphql(
+                CPU_STOCK_QUERY,
+                serde_json::json!({ "input": { "dataCenterId": datacenter_id } }),
+            )
+            .await?;
+        Ok(data
+            .into_iter()
+            .flat_map(|d| d.cpu_flavors)
+            .filter_map(|flavor| flavor.specifics?.stock_status)
+            .min_by_key(|status| stock_preference(Some(status)))
+            .filter(|status| stock_preference(Some(status)) < stock_preference(None)))
+    }
+
+    /// Listed datacenters with storage support, with their CPU stock. A
+    /// datacenter whose stock RunPod can't tell is kept with unknown stock.
+    async fn list_for_cpu(&self) -> Result<Vec<Datacenter>, DatacenterError> {
+        let mut datacenters: Vec<Datacenter> = self
+            .list()
+            .await?
+            .into_iter()
+            .filter(|dc| dc.listed && dc.storage_support)
+            .collect();
+        let stocks =
+            futures::future::join_all(datacenters.iter().map(|dc| self.cpu_stock(&dc.id))).await;
+        for (dc, stock) in datacenters.iter_mut().zip(stocks) {
+            match stock {
+                Ok(stock) => dc.cpu_stock = stock,
+                Err(e) => debug!(
+                    "[Runpod Controller] No CPU stock for datacenter {}: {}",
+                    dc.id, e
+                ),
+            }
+        }
+        Ok(datacenters)
+    }
+}
+
+/// Picks the datacenter for a CPU pod of `owner`, whose network volume lives
+/// in `volume_datacenters`. Falls back to the first of those, or to
+/// [`FALLBACK_CPU_DATACENTER`], when RunPod can't be asked or nothing qualifies.
+pub async fn cpu_datacenter(
+    client: &DatacenterClient,
+    owner: &str,
+    volume_datacenters: &[String],
+) -> String {
+    let fallback = volume_datacenters
+        .first()
+        .map(String::as_str)
+        .unwrap_or(FALLBACK_CPU_DATACENTER);
+    let datacenters = match client.list_for_cpu().await {
+        Ok(datacenters) => datacenters,
+        Err(e) => {
+            warn!(
+                "[Runpod Controller] Failed to list datacenters ({}), using '{}' for CPU pod",
+                e, fallback
+            );
+            return fallback.to_string();
+        }
+    };
+    debug!(
+        "[Runpod Controller] Choosing a CPU datacenter among {}",
+        datacenters.len()
+    );
+    match select_cpu_datacenter(&datacenters, owner, volume_datacenters) {
+        Some(dc) => {
+            info!(
+                "[Runpod Controller] Selected CPU datacenter: ID='{}', Location='{}', CPU Stock: {:?}",
+                dc.id, dc.location, dc.cpu_stock
+            );
+            dc.id.clone()
+        }
+        None => {
+            warn!(
+                "[Runpod Controller] No listed datacenter with storage support, using '{}' for CPU pod",
+                fallback
+            );
+            fallback.to_string()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::routing::post;
+    use axum::{Json, Router};
+
+    fn datacenter(id: &str, location: &str, storage_support: bool, listed: bool) -> Datacenter {
+        Datacenter {
+            id: id.to_string(),
+            name: None,
+            location: location.to_string(),
+            storage_support,
+            listed,
+            cpu_stock: Some("High".to_string()),
+        }
+    }
+
+    fn selected<'a>(
+        datacenters: &'a [Datacenter],
+        owner: &str,
+        volumes: &[&str],
+    ) -> Option<&'a str> {
+        let volumes: Vec<String> = volumes.iter().map(|v| v.to_string()).collect();
+        select_cpu_datacenter(datacenters, owner, &volumes).map(|dc| dc.id.as_str())
+    }
+
+    #[test]
+    fn test_select_cpu_datacenter_prefers_location_then_stock() {
+        // Arrange
+        let datacenters = vec![
+            datacenter("AP-JP-1", "Japan", true, true),
+            Datacenter {
+                cpu_stock: Some("Low".to_string()),
+                ..datacenter("US-TX-3", "United States", true, true)
+            },
+            datacenter("EU-SE-1", "Europe", true, true),
+            datacenter("CA-MTL-1", "Canada", false, true),
+            datacenter("EU-CZ-1", "Europe", true, false),
+        ];
+
+        // Act
+        let selected = selected(&datacenters, "me@example.com", &[]);
+
+        // Assert
+        assert_eq!(selected, Some("EU-SE-1"));
+    }
+
+    #[test]
+    fn test_select_cpu_datacenter_uses_other_locations_last() {
+        let datacenters = vec![
+            datacenter("AP-JP-1", "Japan", true, true),
+            datacenter("US-GA-1", "United States", false, true),
+        ];
+        assert_eq!(
+            selected(&datacenters, "me@example.com", &[]),
+            Some("AP-JP-1")
+        );
+        assert_eq!(selected(&datacenters[1..], "me@example.com", &[]), None);
+    }
+
+    #[test]
+    fn test_select_cpu_datacenter_spreads_ties_by_owner() {
+        let datacenters = vec![
+            datacenter("CA-MTL-1", "Canada", true, true),
+            datacenter("EU-RO-1", "Europe", true, true),
+            datacenter("EU-SE-1", "Europe", true, true),
+            datacenter("US-KS-2", "United States", true, true),
+        ];
+        let picks: std::collections::HashSet<_> = (0..20)
+            .map(|i| selected(&datacenters, &format!("user{}@example.com", i), &[]))
+            .collect();
+        assert!(picks.len() > 1, "every owner got {:?}", picks);
+
+        // The same owner keeps landing on the same datacenter
+        assert_eq!(
+            selected(&datacenters, "me@example.com", &[]),
+            selected(&datacenters, "me@example.com", &[])
+        );
+    }
+
+    #[test]
+    fn test_select_cpu_datacenter_follows_the_owners_volume() {
+        let datacenters = vec![
+            datacenter("US-KS-2", "United States", true, true),
+            Datacenter {
+                cpu_stock: Some("Low".to_string()),
+                ..datacenter("AP-JP-1", "Japan", true, true)
+            },
+        ];
+        assert_eq!(
+            selected(&datacenters, "me@example.com", &["AP-JP-1"]),
+            Some("AP-JP-1")
+        );
+        // A volume in a datacenter that can't take the pod doesn't pin it
+        assert_eq!(
+            selected(&datacenters, "me@example.com", &["EU-RO-1"]),
+            Some("US-KS-2")
+        );
+    }
+
+    async fn serve_fake_runpod(body: serde_json::Value) -> String {
+        serve_fake_runpod_with(move |_| body.clone()).await
+    }
+
+    /// A RunPod GraphQL API answering each request body with `respond`
+    async fn serve_fake_runpod_with(
+        respond: impl Fn(serde_json::Value) -> serde_json::Value + Clone + Send + Sync + 'static,
+    ) -> String {
+        let app = Router::new().route(
+            "/graphql",
+            post(
+                move |Json(request): Json<serde_json::Value>| async move { Json(respond(request)) },
+            ),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        format!("http://{}/graphql", addr)
+    }
+
+    #[tokio::test]
+    async fn test_cpu_datacenter_from_api() {
+        let url = serve_fake_runpod_with(|request| {
+            if !request["query"].as_str().unwrap().contains("cpuFlavors") {
+                return serde_json::json!({
+                    "data": {"dataCenters": [
+                        {"id": "OC-AU-1", "name": "AU", "location": "Australia", "storageSupport": true, "listed": true},
+                        {"id": "US-KS-2", "name": "KS", "location": "United States", "storageSupport": true, "listed": true},
+                        {"id": "US-TX-3", "name": "TX", "location": "United States", "storageSupport": true, "listed": true},
+                        {"id": "EU-RO-1", "name": "RO", "location": "Europe", "storageSupport": false}
+                    ]}
+                });
+            }
+            // Only US-TX-3 has CPUs to spare
+            let stock = match request["variables"]["input"]["dataCenterId"].as_str() {
+                Some("US-TX-3") => serde_json::json!("Medium"),
+                _ => serde_json::Value::Null,
+            };
+            serde_json::json!({"data": {"cpuFlavors": [
+                {"id": "cpu3c", "specifics": {"stockStatus": null}},
+                {"id": "cpu3g", "specifics": {"stockStatus": stock}}
+            ]}})
+        })
+        .await;
+        let client = DatacenterClient::new("key".to_string(), url);
+
+        assert_eq!(
+            client.cpu_stock("US-TX-3").await.unwrap().as_deref(),
+            Some("Medium")
+        );
+        assert_eq!(client.cpu_stock("US-KS-2").await.unwrap(), None);
+        assert_eq!(
+            cpu_datacenter(&client, "me@example.com", &[]).await,
+            "US-TX-3"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_cpu_datacenter_falls_back() {
+        let url = serve_fake_runpod(serde_json::json!({"data": {"dataCenters": []}})).await;
+        let client = DatacenterClient::new("key".to_string(), url);
+
+        assert_eq!(
+            cpu_datacenter(&client, "me@example.com", &[]).await,
+            FALLBACK_CPU_DATACENTER
+        );
+        // Rather than leaving the owner's volume behind
+        assert_eq!(
+            cpu_datacenter(&client, "me@example.com", &["US-KS-2".to_string()]).await,
+            "US-KS-2"
+        );
+    }
+}
@@ -1,10 +1,15 @@
+use crate::accelerator::base::AcceleratorProvider;
+use crate::accelerator::runpod::RunPodProvider;
 use crate::entities::containers;
 use crate::models::V1UserProfile;
 use crate::resources::v1::containers::base::ContainerPlatform;
 use crate::resources::v1::containers::kube::KubePlatform;
-use crate::resources::v1::containers::models::{V1Container, V1ContainerRequest};
+use crate::resources::v1::containers::models::{
+    V1Container, V1ContainerPlan, V1ContainerRequest, V1LogParams,
+};
 use crate::resources::v1::containers::runpod::RunpodPlatform;
 use sea_orm::DatabaseConnection;
+use std::collections::HashMap;
 use std::error::Error;
 
 // Define an enum that can hold any platform type
@@ -39,6 +44,35 @@ impl PlatformType {
         }
     }
 
+    /// What `declare` followed by a create would pick for `request`, without
+    /// storing or launching anything. Kubernetes leaves placement to the
+    /// scheduler, so its plan is just the requested accelerator.
+    pub async fn plan(
+        &self,
+        request: &V1ContainerRequest,
+        db: &DatabaseConnection,
+        owner: &str,
+        namespace: &str,
+    ) -> Result<V1ContainerPlan, Box<dyn Error + Send + Sync>> {
+        match self {
+            PlatformType::Runpod(platform) => platform.plan(request, db, owner, namespace).await,
+            PlatformType::Kube(_) => {
+                let requested = request
+                    .accelerators
+                    .iter()
+                    .flatten()
+                    .next()
+                    .and_then(|accelerator| accelerator.split_once(':'));
+                Ok(V1ContainerPlan {
+                    platform: "kube".to_string(),
+                    accelerator: requested.map(|(_, name)| name.to_string()),
+                    accelerator_count: requested.and_then(|(count, _)| count.parse().ok()),
+                    ..Default::default()
+                })
+            }
+        }
+    }
+
     pub async fn reconcile(
         &self,
         container: &containers::Model,
@@ -50,14 +84,38 @@ impl PlatformType {
         }
     }
 
+    /// Starts a container that was declared on another platform and moved
+    /// here. RunPod creates pending containers when reconciling them.
+    pub async fn launch(
+        &self,
+        container: &containers::Model,
+        db: &DatabaseConnection,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        match self {
+            PlatformType::Runpod(platform) => platform.reconcile(container, db).await,
+            PlatformType::Kube(platform) => platform.launch(container, db).await,
+        }
+    }
+
+    /// Makes `reconcile` take one step and return instead of watching the
+    /// container, see `RunpodPlatform::single_pass`. Kubernetes reconciles
+    /// return right away already.
+    pub fn single_pass(self) -> Self {
+        match self {
+            PlatformType::Runpod(platform) => PlatformType::Runpod(platform.single_pass()),
+            PlatformType::Kube(platform) => PlatformType::Kube(platform),
+        }
+    }
+
     pub async fn logs(
         &self,
         container_id: &str,
+        params: &V1LogParams,
         db: &DatabaseConnection,
     ) -> Result<String, Box<dyn Error + Send + Sync>> {
         match self {
-            PlatformType::Runpod(platform) => platform.logs(container_id, db).await,
-            PlatformType::Kube(platform) => platform.logs(container_id, db).await,
+            PlatformType::Runpod(platform) => platform.logs(container_id, params, db).await,
+            PlatformType::Kube(platform) => platform.logs(container_id, params, db).await,
         }
     }
 
@@ -73,6 +131,13 @@ impl PlatformType {
         }
     }
 
+    pub fn accelerator_map(&self) -> HashMap<String, String> {
+        match self {
+            PlatformType::Runpod(platform) => platform.accelerator_map(),
+            PlatformType::Kube(platform) => platform.accelerator_map(),
+        }
+    }
+
     pub async fn delete(
         &self,
         id: &str,
@@ -87,6 +152,18 @@ impl PlatformType {
     // Add other methods as needed
 }
 
+/// Platforms `platform_factory` can build
+pub const PLATFORMS: &[&str] = &["runpod", "kube"];
+
+/// Accelerators `platform` supports, like its `accelerator_map`, without
+/// building the platform and its API client
+pub fn platform_accelerator_map(platform: &str) -> HashMap<String, String> {
+    match platform {
+        "runpod" => RunPodProvider::new().accelerator_map().clone(),
+        _ => HashMap::new(),
+    }
+}
+
 // Factory function
 pub fn platform_factory(platform: String) -> PlatformType {
     match platform.as_str() {
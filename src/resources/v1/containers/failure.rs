@@ -0,0 +1,126 @@
+// src/resources/v1/containers/failure.rs
+//
+// Recognising why a container failed from its logs. GPU jobs that run out of
+// memory or hit a CUDA error often only say so in their output, so when a
+// container stops the tail of its log is matched against known fatal
+// patterns and the first hit is stored as the status's `failure_reason`.
+// Patterns come from `NEBU_FAILURE_PATTERNS`, defaulting to
+// `config::DEFAULT_FAILURE_PATTERNS`.
+
+use crate::resources::v1::containers::models::V1FailureReason;
+use once_cell::sync::Lazy;
+use regex::Regex;
+
+/// Log lines scanned when a container stops
+pub const FAILURE_SCAN_TAIL_LINES: u64 = 200;
+
+/// Longest matched line kept in a failure reason
+const MAX_LINE_CHARS: usize = 500;
+
+/// Compiled failure patterns
+pub struct FailurePatterns {
+    patterns: Vec<(String, Regex)>,
+}
+
+impl FailurePatterns {
+    pub fn new(patterns: &[(String, String)]) -> Result<Self, String> {
+        let patterns = patterns
+            .iter()
+            .map(|(kind, pattern)| {
+                Regex::new(pattern)
+                    .map(|re| (kind.clone(), re))
+                    .map_err(|e| format!("Invalid failure pattern for '{}': {}", kind, e))
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self { patterns })
+    }
+
+    /// The first pattern matching a line of `logs`, with the last line it
+    /// matched
+    pub fn scan(&self, logs: &str) -> Option<V1FailureReason> {
+        self.patterns.iter().find_map(|(kind, re)| {
+            logs.lines()
+                .rev()
+                .map(str::trim)
+                .find(|line| re.is_match(line))
+                .map(|line| V1FailureReason {
+                    kind: kind.clone(),
+                    line: line.chars().take(MAX_LINE_CHARS).collect(),
+                })
+        })
+    }
+}
+
+/// The patterns from `SERVER_CONFIG`, validated when it was loaded
+pub static FAILURE_PATTERNS: Lazy<FailurePatterns> = Lazy::new(|| {
+    FailurePatterns::new(&crate::config::SERVER_CONFIG.failure_patterns)
+        .expect("failure patterns are validated with the config")
+});
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn defaults() -> FailurePatterns {
+        let patterns: Vec<(String, String)> = crate::config::DEFAULT_FAILURE_PATTERNS
+            .iter()
+            .map(|(kind, pattern)| (kind.to_string(), pattern.to_string()))
+            .collect();
+        FailurePatterns::new(&patterns).unwrap()
+    }
+
+    #[test]
+    fn test_cuda_oom_is_recognised() {
+        // Arrange
+        let logs = "\
+Epoch 3/10
+  File \"train.py\", line 88, in forward
+    out = self.model(batch)
+torch.cuda.OutOfMemoryError: CUDA out of memory. Tried to allocate 2.00 GiB (GPU 0; 23.69 GiB total capacity)
++ echo '[DEBUG] Waiting for final sync (timeout 600s)...'
+";
+
+        // Act
+        let reason = defaults().scan(logs).unwrap();
+
+        // Assert
+        assert_eq!(reason.kind, "cuda_out_of_memory");
+        assert!(reason.line.starts_with("torch.cuda.OutOfMemoryError"));
+    }
+
+    #[test]
+    fn test_other_fatal_snippets() {
+        let patterns = defaults();
+        let kind = |logs: &str| patterns.scan(logs).map(|r| r.kind);
+
+        assert_eq!(
+            kind("RuntimeError: CUDA error: device-side assert triggered\n").as_deref(),
+            Some("cuda_error")
+        );
+        assert_eq!(
+            kind("Last State: Terminated\n  Reason: OOMKilled\n").as_deref(),
+            Some("out_of_memory")
+        );
+        assert_eq!(
+            kind("/dev/fd/63: line 1:  4242 Killed                  python train.py\n").as_deref(),
+            Some("killed")
+        );
+        assert_eq!(kind("loading shards\nKilled\n").as_deref(), Some("killed"));
+        assert_eq!(
+            kind("Epoch 10/10 loss=0.12\nSkilled workers saved the checkpoint\n"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_configured_patterns() {
+        let patterns =
+            FailurePatterns::new(&[("nccl".to_string(), "NCCL error".to_string())]).unwrap();
+
+        let reason = patterns
+            .scan("torch.distributed.DistBackendError: NCCL error in: ...")
+            .unwrap();
+        assert_eq!(reason.kind, "nccl");
+        assert!(patterns.scan("CUDA error: out of memory").is_none());
+    }
+}
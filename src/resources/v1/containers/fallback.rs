@@ -0,0 +1,295 @@
+// src/resources/v1/containers/fallback.rs
+//
+// Multi-platform fallback. A container declared with `platforms` starts on the
+// first of them, and when creating it there fails before anything was created
+// (an API error or no capacity), the reconciler moves it to the next one in
+// the list and launches it there. `platform` always names where the container
+// was last placed.
+
+use crate::entities::containers;
+use crate::mutation::Mutation;
+use crate::resources::v1::containers::base::ContainerStatus;
+use sea_orm::DatabaseConnection;
+use std::error::Error;
+use std::fmt;
+use std::future::Future;
+use std::str::FromStr;
+use tracing::warn;
+
+/// The platform containers without one run on
+pub const DEFAULT_PLATFORM: &str = "runpod";
+
+/// Creating a container on a platform failed before anything was created
+/// there, so it can be tried elsewhere without leaving a resource behind
+#[derive(Debug)]
+pub struct PlacementFailed(pub String);
+
+impl fmt::Display for PlacementFailed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl Error for PlacementFailed {}
+
+/// The platform after `current` in `platforms`. When `current` isn't listed
+/// the list is tried from the start.
+pub fn next_platform<'a>(platforms: &'a [String], current: &str) -> Option<&'a str> {
+    let remaining = match platforms.iter().position(|p| p == current) {
+        Some(i) => &platforms[i + 1..],
+        None => platforms,
+    };
+    remaining.iter().map(|p| p.as_str()).find(|p| *p != current)
+}
+
+/// Whether the container is waiting to be created, so a failed reconcile
+/// means creating it failed
+fn is_starting(container: &containers::Model) -> bool {
+    container
+        .parse_status()
+        .ok()
+        .flatten()
+        .and_then(|s| s.status)
+        .and_then(|s| ContainerStatus::from_str(&s).ok())
+        .is_some_and(|status| status.needs_start())
+}
+
+/// Runs `reconcile` on the container's platform. If creating the container
+/// there fails with `PlacementFailed` and `platforms` lists another one, the
+/// container is moved there and `launch`ed, until that succeeds or the list
+/// runs out. Any other error is returned as is, since the container may
+/// already exist where it is.
+pub async fn reconcile_with_fallback<R, RFut, L, LFut>(
+    db: &DatabaseConnection,
+    container: containers::Model,
+    reconcile: R,
+    launch: L,
+) -> Result<(), Box<dyn Error + Send + Sync>>
+where
+    R: FnOnce(String, containers::Model) -> RFut,
+    RFut: Future<Output = Result<(), Box<dyn Error + Send + Sync>>>,
+    L: Fn(String, containers::Model) -> LFut,
+    LFut: Future<Output = Result<(), Box<dyn Error + Send + Sync>>>,
+{
+    let mut container = container;
+    let mut platform = container
+        .platform
+        .clone()
+        .unwrap_or_else(|| DEFAULT_PLATFORM.to_string());
+    if !is_starting(&container) {
+        return reconcile(platform, container).await;
+    }
+
+    let mut result = reconcile(platform.clone(), container.clone()).await;
+    loop {
+        let Err(e) = result else {
+            return Ok(());
+        };
+        if !e.is::<PlacementFailed>() {
+            return Err(e);
+        }
+        let next = container
+            .platforms
+            .as_deref()
+            .and_then(|platforms| next_platform(platforms, &platform));
+        let Some(next) = next.map(str::to_string) else {
+            return Err(e);
+        };
+
+        warn!(
+            "[Container Controller] Creating container {} on {} failed, falling back to {}: {}",
+            container.id, platform, next, e
+        );
+        Mutation::update_container_platform(db, container.id.clone(), next.clone()).await?;
+        container = Mutation::update_container_status(
+            db,
+            container.id.clone(),
+            Some(ContainerStatus::Pending.to_string()),
+            Some(format!("Failed on {}, trying {}: {}", platform, next, e)),
+            None,
+            None,
+            None,
+            None,
+            None,
+        )
+        .await?;
+        platform = next;
+        result = launch(platform.clone(), container.clone()).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::resources::v1::containers::models::V1ContainerStatus;
+    use sea_orm::{ActiveModelTrait, EntityTrait};
+    use std::sync::{Arc, Mutex};
+
+    fn platforms(names: &[&str]) -> Vec<String> {
+        names.iter().map(|n| n.to_string()).collect()
+    }
+
+    async fn db_with_container(
+        platform: &str,
+        fallbacks: Option<Vec<String>>,
+    ) -> (DatabaseConnection, containers::Model) {
+        let db = crate::db::test_db().await;
+        let container = containers::Model {
+            status: Some(serde_json::json!(V1ContainerStatus {
+                status: Some(ContainerStatus::Pending.to_string()),
+                ..Default::default()
+            })),
+            platform: Some(platform.to_string()),
+            platforms: fallbacks,
+            desired_status: Some(ContainerStatus::Running.to_string()),
+            ..containers::Model::test_fixture()
+        };
+        let container = containers::ActiveModel::from(container)
+            .insert(&db)
+            .await
+            .unwrap();
+        (db, container)
+    }
+
+    type Reconciled = std::future::Ready<Result<(), Box<dyn Error + Send + Sync>>>;
+
+    /// A reconcile or launch that can't place the container on the given
+    /// platforms and records where it ran
+    fn fake_reconcile(
+        failing: &'static [&'static str],
+        calls: Arc<Mutex<Vec<String>>>,
+    ) -> impl Fn(String, containers::Model) -> Reconciled {
+        move |platform, _container| {
+            calls.lock().unwrap().push(platform.clone());
+            std::future::ready(if failing.contains(&platform.as_str()) {
+                Err(PlacementFailed(format!("no capacity on {}", platform)).into())
+            } else {
+                Ok(())
+            })
+        }
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a Postgres at NEBU_TEST_DATABASE_URL"]
+    async fn test_failure_on_first_platform_tries_the_next() {
+        // Arrange
+        let (db, container) =
+            db_with_container("runpod", Some(platforms(&["runpod", "kube"]))).await;
+        let calls = Arc::new(Mutex::new(Vec::new()));
+
+        let fake = fake_reconcile(&["runpod"], calls.clone());
+
+        // Act
+        let result = reconcile_with_fallback(&db, container, &fake, &fake).await;
+
+        // Assert
+        assert!(result.is_ok());
+        assert_eq!(*calls.lock().unwrap(), platforms(&["runpod", "kube"]));
+        let stored = containers::Entity::find_by_id("c1")
+            .one(&db)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(stored.platform.as_deref(), Some("kube"));
+        let status = stored.parse_status().unwrap().unwrap();
+        assert_eq!(
+            status.status.as_deref(),
+            Some(ContainerStatus::Pending.to_string().as_str())
+        );
+        assert!(status.message.unwrap().contains("no capacity on runpod"));
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a Postgres at NEBU_TEST_DATABASE_URL"]
+    async fn test_no_fallback_when_the_list_runs_out() {
+        let (db, container) =
+            db_with_container("runpod", Some(platforms(&["runpod", "kube"]))).await;
+        let calls = Arc::new(Mutex::new(Vec::new()));
+
+        let fake = fake_reconcile(&["runpod", "kube"], calls.clone());
+        let result = reconcile_with_fallback(&db, container, &fake, &fake).await;
+
+        assert!(result.unwrap_err().to_string().contains("kube"));
+        assert_eq!(*calls.lock().unwrap(), platforms(&["runpod", "kube"]));
+
+        let (db, container) = db_with_container("runpod", None).await;
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let fake = fake_reconcile(&["runpod"], calls.clone());
+        let result = reconcile_with_fallback(&db, container, &fake, &fake).await;
+        assert!(result.is_err());
+        assert_eq!(*calls.lock().unwrap(), platforms(&["runpod"]));
+        let stored = containers::Entity::find_by_id("c1")
+            .one(&db)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(stored.platform.as_deref(), Some("runpod"));
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a Postgres at NEBU_TEST_DATABASE_URL"]
+    async fn test_the_next_platform_is_launched() {
+        let (db, container) =
+            db_with_container("runpod", Some(platforms(&["runpod", "kube"]))).await;
+        let launched = Arc::new(Mutex::new(Vec::new()));
+
+        let result = reconcile_with_fallback(
+            &db,
+            container,
+            fake_reconcile(&["runpod"], Arc::default()),
+            |platform, container: containers::Model| -> Reconciled {
+                launched
+                    .lock()
+                    .unwrap()
+                    .push((platform, container.platform));
+                std::future::ready(Ok(()))
+            },
+        )
+        .await;
+
+        assert!(result.is_ok());
+        assert_eq!(
+            *launched.lock().unwrap(),
+            vec![("kube".to_string(), Some("kube".to_string()))]
+        );
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a Postgres at NEBU_TEST_DATABASE_URL"]
+    async fn test_other_errors_do_not_fall_back() {
+        let (db, container) =
+            db_with_container("runpod", Some(platforms(&["runpod", "kube"]))).await;
+        let launched = Arc::new(Mutex::new(Vec::new()));
+        let launch = fake_reconcile(&[], launched.clone());
+
+        // E.g. the database went away after the pod was created
+        let result = reconcile_with_fallback(
+            &db,
+            container,
+            |_, _| -> Reconciled { std::future::ready(Err("database is locked".into())) },
+            &launch,
+        )
+        .await;
+
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("database is locked"));
+        assert!(launched.lock().unwrap().is_empty());
+        let stored = containers::Entity::find_by_id("c1")
+            .one(&db)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(stored.platform.as_deref(), Some("runpod"));
+    }
+
+    #[test]
+    fn test_next_platform() {
+        let list = platforms(&["runpod", "kube"]);
+        assert_eq!(next_platform(&list, "runpod"), Some("kube"));
+        assert_eq!(next_platform(&list, "kube"), None);
+        assert_eq!(next_platform(&platforms(&["kube"]), "runpod"), Some("kube"));
+        assert_eq!(next_platform(&[], "runpod"), None);
+    }
+}
@@ -1,17 +1,21 @@
 use crate::entities::containers;
 use crate::models::V1ResourceMeta;
 use crate::models::V1UserProfile;
+use crate::mutation::Mutation;
 use crate::resources::v1::containers::base::{ContainerPlatform, ContainerStatus};
+use crate::resources::v1::containers::fallback::PlacementFailed;
 use crate::resources::v1::containers::models::{
-    V1Container, V1ContainerRequest, V1ContainerStatus,
+    ControllerData, V1Container, V1ContainerRequest, V1ContainerStatus, V1EnvVar, V1LogParams,
 };
+use crate::resources::v1::volumes::models::V1VolumePath;
 use k8s_openapi::api::batch::v1::{Job, JobSpec};
 use k8s_openapi::api::core::v1::{
-    Container as K8sContainer, ContainerPort, EnvVar, PodSpec, PodTemplateSpec,
+    Container as K8sContainer, ContainerPort, EnvVar, Pod, PodSpec, PodTemplateSpec,
     ResourceRequirements, Volume, VolumeMount,
 };
 use k8s_openapi::apimachinery::pkg::apis::meta::v1::ObjectMeta;
-use kube::{api::PostParams, Api, Client};
+use kube::api::{ListParams, LogParams, PostParams};
+use kube::{Api, Client};
 use petname;
 use sea_orm::{ActiveModelTrait, DatabaseConnection, Set};
 use short_uuid::ShortUuid;
@@ -265,51 +269,22 @@ impl KubePlatform {
         Ok(())
     }
 
-    /// Get common environment variables for all containers
-    fn get_common_env(&self) -> HashMap<String, String> {
-        let mut env = HashMap::new();
-
-        // Add common environment variables here
-        env.insert("PLATFORM".to_string(), "kubernetes".to_string());
-
-        env
-    }
-}
-
-impl ContainerPlatform for KubePlatform {
-    /// Run a container on Kubernetes by creating a Job
-    async fn declare(
+    /// The Job running a container
+    fn build_job(
         &self,
-        config: &V1ContainerRequest,
-        db: &DatabaseConnection,
-        _user_profile: &V1UserProfile,
-        owner_id: &str,
-        namespace: &str,
-        api_key: Option<String>,
-    ) -> Result<V1Container, Box<dyn std::error::Error + Send + Sync>> {
-        let name = config
-            .metadata
-            .as_ref()
-            .and_then(|meta| Some(meta.name.clone()))
-            .unwrap_or_else(|| {
-                // Generate a random human-friendly name using petname
-                petname::petname(3, "-")
-            });
-        let owner_ref: Option<String> = config
-            .metadata
-            .as_ref()
-            .and_then(|meta| meta.owner_ref.clone());
-        info!("[Kubernetes] Using name: {:?}", name);
-
-        // Create a runtime to handle the async call
-        let rt = tokio::runtime::Runtime::new().expect("Failed to create Tokio runtime");
-
+        name: &str,
+        image: &str,
+        command: Option<&str>,
+        accelerators: Option<&Vec<String>>,
+        user_env: Option<&Vec<V1EnvVar>>,
+        volumes: Option<&Vec<V1VolumePath>>,
+    ) -> Job {
         // Determine GPU requirements
         let mut gpu_count = 0;
         let mut gpu_type = "nvidia-tesla-t4"; // Default GPU type
 
         // Parse accelerators if provided
-        if let Some(accelerators) = &config.accelerators {
+        if let Some(accelerators) = accelerators {
             if !accelerators.is_empty() {
                 // Parse the first accelerator in the list (format: "count:type")
                 let parts: Vec<&str> = accelerators[0].split(':').collect();
@@ -348,7 +323,7 @@ impl ContainerPlatform for KubePlatform {
         }
 
         // Add ORIGN_SYNC_CONFIG environment variable with serialized volumes configuration
-        if let Ok(serialized_volumes) = serde_yaml::to_string(&config.volumes) {
+        if let Ok(serialized_volumes) = serde_yaml::to_string(&volumes) {
             env.push(EnvVar {
                 name: "ORIGN_SYNC_CONFIG".to_string(),
                 value: Some(serialized_volumes),
@@ -360,7 +335,7 @@ impl ContainerPlatform for KubePlatform {
         }
 
         // Add user-provided environment variables
-        if let Some(user_env) = &config.env {
+        if let Some(user_env) = user_env {
             for env_var in user_env {
                 env.push(EnvVar {
                     name: env_var.key.clone(),
@@ -430,12 +405,9 @@ impl ContainerPlatform for KubePlatform {
 
         // Create the container
         let container = K8sContainer {
-            name: name.clone().unwrap(),
-            image: Some(config.image.clone()),
-            command: config
-                .command
-                .as_ref()
-                .map(|cmd| cmd.split(" ").map(String::from).collect()),
+            name: name.to_string(),
+            image: Some(image.to_string()),
+            command: command.map(|cmd| cmd.split(" ").map(String::from).collect()),
             ports: Some(vec![ContainerPort {
                 container_port: 8000,
                 ..Default::default()
@@ -460,7 +432,7 @@ impl ContainerPlatform for KubePlatform {
             metadata: Some(ObjectMeta {
                 labels: Some({
                     let mut lbls = BTreeMap::new();
-                    lbls.insert("app".to_string(), name.clone().unwrap());
+                    lbls.insert("app".to_string(), name.to_string());
                     lbls
                 }),
                 ..Default::default()
@@ -476,14 +448,147 @@ impl ContainerPlatform for KubePlatform {
         };
 
         // Create the job
-        let job = Job {
+        Job {
             metadata: ObjectMeta {
-                name: Some(name.clone().unwrap()),
+                name: Some(name.to_string()),
                 ..Default::default()
             },
             spec: Some(job_spec),
             ..Default::default()
-        };
+        }
+    }
+
+    /// Starts an already-declared container as a Job, for containers moved
+    /// here by the fallback. Failing to submit the Job leaves nothing behind,
+    /// so that error is a `PlacementFailed`.
+    pub async fn launch(
+        &self,
+        container: &containers::Model,
+        db: &DatabaseConnection,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let job = self.build_job(
+            &container.name,
+            &container.image,
+            container.command.as_deref(),
+            container.accelerators.as_ref(),
+            container.parse_env()?.as_ref(),
+            container.parse_volumes()?.as_ref(),
+        );
+
+        let client = self
+            .get_client()
+            .await
+            .map_err(|e| PlacementFailed(format!("Failed to create K8s client: {}", e)))?;
+        let jobs: Api<Job> = Api::namespaced(client, &self.namespace);
+        jobs.create(&PostParams::default(), &job)
+            .await
+            .map_err(|e| {
+                PlacementFailed(format!("Error creating Job '{}': {}", container.name, e))
+            })?;
+        info!(
+            "[Kubernetes] Created Job '{}' for container {}",
+            container.name, container.id
+        );
+
+        Mutation::update_container_resource_name(db, container.id.clone(), container.name.clone())
+            .await?;
+        Mutation::update_container_status(
+            db,
+            container.id.clone(),
+            Some(ContainerStatus::Created.to_string()),
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+        )
+        .await?;
+
+        let self_clone = self.clone();
+        let job_name = container.name.clone();
+        let container_id = container.id.clone();
+        tokio::spawn(async move {
+            if let Err(e) = self_clone.watch_job_status(&job_name, &container_id).await {
+                error!("[Kubernetes] Error watching job status: {:?}", e);
+            }
+        });
+        Ok(())
+    }
+
+    /// Get common environment variables for all containers
+    fn get_common_env(&self) -> HashMap<String, String> {
+        let mut env = HashMap::new();
+
+        // Add common environment variables here
+        env.insert("PLATFORM".to_string(), "kubernetes".to_string());
+
+        env
+    }
+}
+
+/// A container log read as Kubernetes takes it
+fn log_params(params: &V1LogParams) -> LogParams {
+    LogParams {
+        tail_lines: params
+            .tail_lines
+            .map(|lines| i64::try_from(lines).unwrap_or(i64::MAX)),
+        since_time: params
+            .since
+            .and_then(|since| chrono::DateTime::from_timestamp(since, 0)),
+        ..Default::default()
+    }
+}
+
+impl ContainerPlatform for KubePlatform {
+    /// Run a container on Kubernetes by creating a Job
+    async fn declare(
+        &self,
+        config: &V1ContainerRequest,
+        db: &DatabaseConnection,
+        _user_profile: &V1UserProfile,
+        owner_id: &str,
+        namespace: &str,
+        api_key: Option<String>,
+    ) -> Result<V1Container, Box<dyn std::error::Error + Send + Sync>> {
+        let config =
+            &crate::resources::v1::containers::base::with_namespace_defaults(db, namespace, config)
+                .await?;
+        crate::validate::validate_image(&config.image)?;
+        if let Some(accelerators) = &config.accelerators {
+            let supported = self.accelerator_map();
+            for accelerator in accelerators {
+                crate::validate::validate_accelerator(accelerator, &supported)?;
+            }
+        }
+        let name = config
+            .metadata
+            .as_ref()
+            .and_then(|meta| Some(meta.name.clone()))
+            .unwrap_or_else(|| {
+                // Generate a random human-friendly name using petname
+                petname::petname(3, "-")
+            });
+        let owner_ref: Option<String> = config
+            .metadata
+            .as_ref()
+            .and_then(|meta| meta.owner_ref.clone());
+        if let Some(name) = &name {
+            crate::validate::validate_full_name(namespace, name)?;
+        }
+        info!("[Kubernetes] Using name: {:?}", name);
+
+        // Create a runtime to handle the async call
+        let rt = tokio::runtime::Runtime::new().expect("Failed to create Tokio runtime");
+
+        let job = self.build_job(
+            name.as_deref().unwrap(),
+            &config.image,
+            config.command.as_deref(),
+            config.accelerators.as_ref(),
+            config.env.as_ref(),
+            config.volumes.as_ref(),
+        );
 
         let id = ShortUuid::generate().to_string();
 
@@ -528,24 +633,40 @@ impl ContainerPlatform for KubePlatform {
                                     cost_per_hr: None,
                                     tailnet_url: None,
                                     ready: None,
+                                    total_cost: None,
+                                    usage: None,
+                                    sync_progress: None,
+                                    ssh_reachable: None,
+                                    last_ssh_check: None,
+                                    failure_reason: None,
                                 }))),
                                 meters: Set(config
                                     .meters
                                     .clone()
                                     .map(|meters| serde_json::json!(meters))),
-                                platform: Set(Some("kubernetes".to_string())),
-                                platforms: Set(None),
+                                platform: Set(Some("kube".to_string())),
+                                platforms: Set(config.platforms.clone()),
                                 resource_name: Set(Some(name.clone().unwrap())),
                                 resource_namespace: Set(Some(self.namespace.clone())),
                                 resource_cost_per_hr: Set(None),
+                                total_cost: Set(None),
+                                cost_accrued_at: Set(None),
                                 restart: Set(config.restart.clone()),
                                 command: Set(config.command.clone()),
                                 args: Set(config.args.clone()),
                                 queue: Set(config.queue.clone()),
                                 timeout: Set(config.timeout.clone()),
                                 desired_status: Set(Some("pending".to_string())),
-                                controller_data: Set(None),
-                                container_user: Set(None),
+                                controller_data: Set(crate::logging::current_request_id().map(
+                                    |request_id| {
+                                        ControllerData {
+                                            request_id: Some(request_id),
+                                            ..Default::default()
+                                        }
+                                        .to_json()
+                                    },
+                                )),
+                                container_user: Set(config.user.clone()),
                                 public_addr: Set(None),
                                 tailnet_ip: Set(None),
                                 authz: Set(config
@@ -574,7 +695,24 @@ impl ContainerPlatform for KubePlatform {
                                     .ssh_keys
                                     .clone()
                                     .map(|keys| serde_json::json!(keys))),
+                                bootstrap: Set(config
+                                    .bootstrap
+                                    .clone()
+                                    .map(|bootstrap| serde_json::json!(bootstrap))),
+                                tailscale: Set(config
+                                    .tailscale
+                                    .clone()
+                                    .map(|tailscale| serde_json::json!(tailscale))),
+                                priority: Set(config.priority),
+                                preemptible: Set(config.preemptible),
+                                webhook_url: Set(config.webhook_url.clone()),
+                                sync_progress: Set(None),
+                                ssh_reachable: Set(None),
+                                last_ssh_check: Set(None),
+                                usage: Set(None),
+                                failure_reason: Set(None),
                                 created_by: Set(Some("kubernetes".to_string())),
+                                deleted_at: Set(None),
                                 updated_at: Set(chrono::Utc::now().into()),
                                 created_at: Set(chrono::Utc::now().into()),
                             };
@@ -635,6 +773,7 @@ impl ContainerPlatform for KubePlatform {
             command: config.command.clone(),
             args: config.args.clone(),
             platform: config.platform.clone().unwrap_or_default(),
+            platforms: config.platforms.clone(),
             volumes: config.volumes.clone(),
             accelerators: config.accelerators.clone(),
             meters: config.meters.clone(),
@@ -649,6 +788,12 @@ impl ContainerPlatform for KubePlatform {
                 cost_per_hr: None,
                 tailnet_url: None,
                 ready: None,
+                total_cost: None,
+                usage: None,
+                sync_progress: None,
+                ssh_reachable: None,
+                last_ssh_check: None,
+                failure_reason: None,
             }),
             restart: config.restart.clone(),
             resources: config.resources.clone(),
@@ -656,6 +801,11 @@ impl ContainerPlatform for KubePlatform {
             ports: config.ports.clone(),
             proxy_port: config.proxy_port.clone(),
             authz: config.authz.clone(),
+            bootstrap: config.bootstrap.clone(),
+            tailscale: config.tailscale.clone(),
+            priority: config.priority,
+            preemptible: config.preemptible,
+            webhook_url: config.webhook_url.clone(),
         })
     }
 
@@ -679,9 +829,32 @@ impl ContainerPlatform for KubePlatform {
     async fn logs(
         &self,
         container_id: &str,
+        params: &V1LogParams,
         db: &DatabaseConnection,
     ) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
-        Ok("".to_string())
+        let container =
+            match crate::query::Query::find_container_by_id(db, container_id.to_string()).await? {
+                Some(model) => model,
+                None => return Err(format!("Container {} not found", container_id).into()),
+            };
+        let job_name = container.resource_name.unwrap_or(container.name);
+
+        // The Job's latest pod; earlier ones were retries
+        let client = self.get_client().await?;
+        let pods: Api<Pod> = Api::namespaced(client, &self.namespace);
+        let list = pods
+            .list(&ListParams::default().labels(&format!("job-name={}", job_name)))
+            .await?;
+        let Some(pod_name) = list
+            .items
+            .into_iter()
+            .max_by_key(|pod| pod.metadata.creation_timestamp.clone())
+            .and_then(|pod| pod.metadata.name)
+        else {
+            return Err(format!("No pod found for Job '{}'", job_name).into());
+        };
+
+        Ok(pods.logs(&pod_name, &log_params(params)).await?)
     }
 
     async fn delete(
@@ -696,3 +869,21 @@ impl ContainerPlatform for KubePlatform {
         return HashMap::new();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_log_params() {
+        let params = log_params(&V1LogParams {
+            tail_lines: Some(50),
+            since: Some(1700000000),
+        });
+        assert_eq!(params.tail_lines, Some(50));
+        assert_eq!(params.since_time.unwrap().timestamp(), 1700000000);
+
+        let params = log_params(&V1LogParams::default());
+        assert_eq!((params.tail_lines, params.since_time), (None, None));
+    }
+}
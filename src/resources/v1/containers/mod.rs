@@ -1,6 +1,20 @@
 pub mod base;
+pub mod bootstrap;
 pub mod controller;
+pub mod datacenters;
 pub mod factory;
+pub mod failure;
+pub mod fallback;
 pub mod kube;
 pub mod models;
+pub mod notify;
+pub mod pod_logs;
+pub mod pricing;
+pub mod reconcile_stats;
+pub mod registry_auth;
 pub mod runpod;
+pub mod ssh_keys;
+pub mod ssh_rotation;
+pub mod usage;
+pub mod volume_gc;
+pub mod webhooks;
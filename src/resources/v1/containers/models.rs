@@ -20,6 +20,10 @@ pub struct V1EnvVar {
     pub key: String,
     pub value: Option<String>,
     pub secret_name: Option<String>,
+    /// Abort container creation when the secret is missing or can't be
+    /// decrypted, instead of starting without the variable
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub required: bool,
 }
 
 fn default_error_response_type() -> String {
@@ -50,10 +54,18 @@ pub struct V1ContainerRequest {
     #[serde(default = "default_container_kind")]
     pub kind: String,
     pub platform: Option<String>,
+    /// Platforms to try in order when creating on one fails or it has no
+    /// capacity, e.g. `["runpod", "kube"]`. The first is used when `platform`
+    /// is unset.
+    #[serde(default)]
+    pub platforms: Option<Vec<String>>,
     pub metadata: Option<V1ResourceMetaRequest>,
     pub image: String,
     pub env: Option<Vec<V1EnvVar>>,
+    /// Run by the container's shell, with `$VAR` and `${VAR}` expanded from
+    /// its env like volume paths are; `$$` is a literal `$`
     pub command: Option<String>,
+    /// Appended to `command`, expanded the same way
     pub args: Option<String>,
     pub volumes: Option<Vec<V1VolumePath>>,
     // pub local_volumes: Option<Vec<V1VolumePath>>,
@@ -69,10 +81,31 @@ pub struct V1ContainerRequest {
     pub ports: Option<Vec<V1PortRequest>>,
     pub proxy_port: Option<i16>,
     pub authz: Option<V1AuthzConfig>,
+    #[serde(default)]
+    pub bootstrap: Option<V1ContainerBootstrap>,
+    #[serde(default)]
+    pub tailscale: Option<V1ContainerTailscale>,
+    /// Queued containers start in priority order, highest first; defaults to 0
+    #[serde(default)]
+    pub priority: Option<i32>,
+    /// Whether a higher priority container may stop this one to take its place
+    #[serde(default)]
+    pub preemptible: Option<bool>,
+    /// User to run as when the image doesn't set one; defaults to the
+    /// namespace's `default_user`, then root
+    #[serde(default)]
+    pub user: Option<String>,
+    /// URL notified with a signed, timestamped POST when the container
+    /// becomes running, completed or failed. The server needs a webhook
+    /// secret for this.
+    #[serde(default)]
+    pub webhook_url: Option<String>,
 }
 
+#[derive(Debug, Clone, PartialEq)]
 pub enum RestartPolicy {
     Always,
+    OnFailure,
     Never,
 }
 
@@ -84,11 +117,72 @@ impl fmt::Display for RestartPolicy {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             RestartPolicy::Always => write!(f, "Always"),
+            RestartPolicy::OnFailure => write!(f, "OnFailure"),
             RestartPolicy::Never => write!(f, "Never"),
         }
     }
 }
 
+impl std::str::FromStr for RestartPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "always" => Ok(RestartPolicy::Always),
+            "onfailure" | "on-failure" | "on_failure" => Ok(RestartPolicy::OnFailure),
+            "never" => Ok(RestartPolicy::Never),
+            _ => Err(format!("Unknown restart policy: {}", s)),
+        }
+    }
+}
+
+/// What controllers keep in a container's `controller_data`. Each writer
+/// gets its own field so keys can't collide; keys this version doesn't know
+/// are kept in `other` and written back untouched.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct ControllerData {
+    /// The reconcile task currently working on the container
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub thread_id: Option<String>,
+    /// The last request that changed the container, for log correlation
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub restart: Option<V1RestartState>,
+    /// What the container's public DNS record points at, so a restarted
+    /// watch knows there is a record to update or remove
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dns_record: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pod: Option<V1PodState>,
+    #[serde(flatten)]
+    pub other: serde_json::Map<String, serde_json::Value>,
+}
+
+impl ControllerData {
+    pub fn to_json(&self) -> serde_json::Value {
+        serde_json::to_value(self).unwrap_or_default()
+    }
+}
+
+/// The container's current pod, kept under the `pod` key of its
+/// `controller_data` so a watch started after a controller restart still
+/// holds a pod that never became ready to its deadline
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct V1PodState {
+    /// Unix seconds
+    pub created_at: i64,
+    pub ever_ready: bool,
+}
+
+/// Restart bookkeeping kept under the `restart` key of a container's `controller_data`
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct V1RestartState {
+    pub attempts: u32,
+    pub next_restart_at: Option<i64>,
+    pub last_status: Option<String>,
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
 pub struct V1ContainerResources {
     pub min_cpu: Option<f64>,
@@ -102,6 +196,10 @@ pub struct V1Port {
     pub port: u16,
     pub protocol: Option<String>,
     pub public_ip: Option<String>,
+    /// Where the port is served when it isn't published on an IP, e.g. by
+    /// RunPod's HTTP proxy
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hostname: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
@@ -120,6 +218,201 @@ pub struct V1ContainerStatus {
     pub cost_per_hr: Option<f64>,
     pub tailnet_url: Option<String>,
     pub ready: Option<bool>,
+    /// Dollars spent so far, see `containers::Model::total_cost`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub total_cost: Option<f64>,
+    /// Latest resource usage sampled from the running container
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub usage: Option<V1ResourceUsage>,
+    /// Latest volume sync progress reported from inside the container
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sync_progress: Option<V1SyncProgress>,
+    /// Whether the latest SSH check reached the container. False while the
+    /// pod runs but its network isn't up yet.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ssh_reachable: Option<bool>,
+    /// Unix timestamp of the latest SSH check
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_ssh_check: Option<i64>,
+    /// Why the container failed, when its logs said so
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub failure_reason: Option<V1FailureReason>,
+}
+
+/// A known fatal error found in a container's logs, see `containers::failure`
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct V1FailureReason {
+    /// Which pattern matched, e.g. `cuda_out_of_memory`
+    pub kind: String,
+    /// The log line it matched
+    pub line: String,
+}
+
+/// One sample of what a running container uses. GPUs are only sampled on
+/// containers with accelerators.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct V1ResourceUsage {
+    /// Unix timestamp of the sample
+    pub timestamp: i64,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub gpus: Vec<V1GpuUsage>,
+    /// One minute load average
+    pub cpu_load: Option<f64>,
+    pub cpu_count: Option<u32>,
+    pub memory_used_mb: Option<u64>,
+    pub memory_total_mb: Option<u64>,
+}
+
+/// Usage of one GPU, as reported by `nvidia-smi`
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct V1GpuUsage {
+    pub index: u32,
+    pub name: String,
+    /// Percent of time a kernel was running over the last sample period
+    pub utilization_percent: Option<f64>,
+    pub memory_used_mb: Option<u64>,
+    pub memory_total_mb: Option<u64>,
+}
+
+/// Progress of one rclone sync, parsed from its JSON stats log lines.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct V1SyncProgress {
+    pub source: String,
+    pub dest: String,
+    pub bytes: u64,
+    pub total_bytes: u64,
+    pub transfers: u64,
+    pub total_transfers: u64,
+    pub errors: u64,
+    /// Seconds until done, when rclone can estimate it
+    pub eta_secs: Option<u64>,
+    /// Bytes per second
+    pub speed: Option<f64>,
+    /// Unix timestamp of the stats line
+    pub updated_at: i64,
+}
+
+/// Query parameters accepted when creating a container
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct V1CreateContainerParams {
+    /// Validate the request and return the plan without creating anything
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+/// What creating a container would pick, from a dry run. Empty fields are
+/// left to the platform or don't apply, e.g. no GPU for a CPU-only container.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct V1ContainerPlan {
+    pub platform: String,
+    /// The accelerator that would be used, e.g. `A100_SXM`
+    pub accelerator: Option<String>,
+    pub accelerator_count: Option<i32>,
+    /// The platform's GPU type for the accelerator
+    pub gpu_type: Option<String>,
+    pub datacenter: Option<String>,
+    /// The GPU's stock in the chosen datacenter
+    pub stock_status: Option<String>,
+    /// Estimated dollars per hour for all accelerators at current prices
+    pub estimated_cost_per_hr: Option<f64>,
+}
+
+/// An accelerator as offered on one platform, with its current price
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct V1AcceleratorOffering {
+    pub name: String,
+    /// Memory in GB
+    pub memory: u32,
+    pub platform: String,
+    /// The platform's own name for the accelerator
+    pub platform_name: String,
+    /// On-demand price per accelerator and hour in USD, when the platform reports one
+    pub price_per_hour: Option<f64>,
+    /// Lowest spot (interruptible) price per accelerator and hour in USD
+    pub spot_price_per_hour: Option<f64>,
+    /// How much stock the platform reports, e.g. `High` or `Low`
+    pub stock_status: Option<String>,
+    /// Whether any are in stock; unknown without pricing
+    pub available: Option<bool>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct V1AcceleratorOfferings {
+    pub accelerators: Vec<V1AcceleratorOffering>,
+}
+
+/// Result of rotating a container's SSH keypair
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct V1SshKeyRotation {
+    pub container_id: String,
+    /// The public key now authorized on the container
+    pub public_key: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct V1ContainerSshKeyParams {
+    /// How long the generated key stays valid, e.g. "30m"; defaults to an hour
+    pub ttl: Option<String>,
+}
+
+/// How to SSH into a container over the tailnet
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct V1ContainerSsh {
+    pub container_id: String,
+    /// The container's tailnet IP, or its tailnet hostname before the IP is known
+    pub host: String,
+    pub port: u16,
+    pub user: String,
+    /// A ready to run `ssh` command line
+    pub command: String,
+    /// Generated private key, only returned when a keypair was requested
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub private_key: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub public_key: Option<String>,
+    /// Unix time after which the container refuses the generated key
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<i64>,
+}
+
+/// Narrows a container log read
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct V1LogParams {
+    /// Only the last this many lines
+    pub tail_lines: Option<u64>,
+    /// Unix timestamp; only what was logged since. Log lines aren't
+    /// timestamped, so this goes by the log's periodic size checks and may
+    /// include up to a check interval's worth of earlier lines.
+    pub since: Option<i64>,
+}
+
+/// Overrides for the bootstrap script that runs before the user command.
+/// See `containers::bootstrap` for the section names and template variables.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct V1ContainerBootstrap {
+    /// Replaces the whole script
+    pub script: Option<String>,
+    /// Replaces individual sections of the default script, keyed by section name
+    pub sections: Option<HashMap<String, String>>,
+    /// Skip installing curl, nebu and tailscale for images that already ship them
+    #[serde(default)]
+    pub skip_install: bool,
+    /// Where the script's output is logged in the container, defaults to
+    /// `$HOME/.logs/nebu_container.log`
+    pub log_file: Option<String>,
+    /// Size in MB at which the log file is rotated, 100 by default; 0 never rotates
+    pub log_max_size_mb: Option<u64>,
+    /// Rotated log files kept as `<log_file>.1` (newest) and up, 3 by default
+    pub log_keep: Option<u32>,
+}
+
+/// Tailscale settings for a container; unset fields fall back to the server config.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct V1ContainerTailscale {
+    /// Tags to advertise, e.g. `["tag:team-a"]`
+    pub tags: Option<Vec<String>>,
+    /// Device hostname template using `{id}`, `{name}` and `{namespace}`
+    pub hostname: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
@@ -134,6 +427,9 @@ pub struct V1Container {
     #[serde(default = "default_container_kind")]
     pub kind: String,
     pub platform: String,
+    /// Fallback order the container was declared with
+    #[serde(default)]
+    pub platforms: Option<Vec<String>>,
     pub metadata: V1ResourceMeta,
     pub image: String,
     pub env: Option<Vec<V1EnvVar>>,
@@ -152,6 +448,16 @@ pub struct V1Container {
     pub ports: Option<Vec<V1PortRequest>>,
     pub proxy_port: Option<i16>,
     pub authz: Option<V1AuthzConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bootstrap: Option<V1ContainerBootstrap>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tailscale: Option<V1ContainerTailscale>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub priority: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub preemptible: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub webhook_url: Option<String>,
 }
 
 impl V1Container {
@@ -175,15 +481,66 @@ pub struct V1Containers {
     pub containers: Vec<V1Container>,
 }
 
+/// Selects the containers a batch operation applies to: either explicit ids,
+/// or every container whose labels include all of `labels` (optionally
+/// limited to one namespace).
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct V1ContainerBatchRequest {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ids: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub labels: Option<HashMap<String, String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub namespace: Option<String>,
+}
+
+/// The outcome of a batch operation for a single container.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct V1ContainerBatchItem {
+    pub id: String,
+    pub success: bool,
+    /// The status code the single-container endpoint would have returned
+    pub status_code: u16,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub container: Option<V1Container>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct V1ContainerBatchResult {
+    pub results: Vec<V1ContainerBatchItem>,
+    pub succeeded: usize,
+    pub failed: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct V1ContainerEvent {
+    pub id: String,
+    pub container_id: String,
+    pub old_status: Option<String>,
+    pub new_status: Option<String>,
+    pub message: Option<String>,
+    pub actor: String,
+    pub created_at: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct V1ContainerEvents {
+    pub events: Vec<V1ContainerEvent>,
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
 pub struct V1UpdateContainer {
     pub image: Option<String>,
+    /// Merged into the current env by key; entries without a value or secret are removed
     pub env: Option<Vec<V1EnvVar>>,
     pub command: Option<String>,
     pub args: Option<String>,
     pub volumes: Option<Vec<V1VolumePath>>,
     pub accelerators: Option<Vec<String>>,
-    pub labels: Option<HashMap<String, String>>,
+    /// Merged into the current labels; `null` values remove a label
+    pub labels: Option<HashMap<String, Option<String>>>,
     pub cpu_request: Option<String>,
     pub memory_request: Option<String>,
     pub platform: Option<String>,
@@ -220,3 +577,57 @@ pub struct V1ContainerSearch {
     pub proxy_port: Option<i16>,
     pub authz: Option<V1AuthzConfig>,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_controller_data_round_trips_with_unknown_keys() {
+        // Arrange
+        let stored = json!({
+            "thread_id": "t-1",
+            "request_id": "req-1",
+            "restart": {"attempts": 2, "next_restart_at": 1700000000, "last_status": "failed"},
+            "host_key": "ssh-ed25519 AAAA",
+        });
+
+        // Act
+        let data: ControllerData = serde_json::from_value(stored.clone()).unwrap();
+        let written = data.to_json();
+
+        // Assert
+        assert_eq!(data.thread_id.as_deref(), Some("t-1"));
+        assert_eq!(data.request_id.as_deref(), Some("req-1"));
+        assert_eq!(
+            data.restart,
+            Some(V1RestartState {
+                attempts: 2,
+                next_restart_at: Some(1700000000),
+                last_status: Some("failed".to_string()),
+            })
+        );
+        assert_eq!(data.other.get("host_key"), Some(&json!("ssh-ed25519 AAAA")));
+        assert_eq!(written, stored);
+    }
+
+    #[test]
+    fn test_controller_data_fields_do_not_clobber_each_other() {
+        let mut data: ControllerData =
+            serde_json::from_value(json!({"request_id": "req-1"})).unwrap();
+
+        data.thread_id = Some("t-2".to_string());
+        data.restart = Some(V1RestartState::default());
+
+        assert_eq!(
+            data.to_json(),
+            json!({
+                "thread_id": "t-2",
+                "request_id": "req-1",
+                "restart": {"attempts": 0, "next_restart_at": null, "last_status": null},
+            })
+        );
+        assert_eq!(ControllerData::default().to_json(), json!({}));
+    }
+}
@@ -0,0 +1,206 @@
+// src/resources/v1/containers/notify.rs
+//
+// Wakes the container reconciler as soon as a container is created or its
+// desired state changes, instead of leaving it to the next poll. On Postgres
+// a trigger on `containers` sends a NOTIFY that a listener turns into a
+// wakeup. Notifications can be missed (e.g. while the listener reconnects),
+// so the reconciler keeps polling as a safety net.
+
+use sea_orm::{ConnectionTrait, DatabaseConnection, DbBackend, DbErr};
+use sqlx::postgres::PgListener;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Notify;
+use tokio::task::JoinHandle;
+use tracing::{debug, info, warn};
+
+/// Channel the containers trigger notifies, with the container id as payload
+pub const CONTAINER_CHANGES_CHANNEL: &str = "nebu_container_changes";
+
+/// How long the listener waits before reconnecting after an error
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+/// Only inserts and changes to the desired state notify. The reconciler's own
+/// status and controller_data writes don't, or every pass would wake the next.
+const TRIGGER_STATEMENTS: &[&str] = &[
+    r#"CREATE OR REPLACE FUNCTION nebu_notify_container_change() RETURNS trigger AS $$
+BEGIN
+    PERFORM pg_notify('nebu_container_changes', NEW.id);
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql"#,
+    "DROP TRIGGER IF EXISTS nebu_container_changes ON containers",
+    "CREATE TRIGGER nebu_container_changes \
+     AFTER INSERT OR UPDATE OF desired_status, deleted_at ON containers \
+     FOR EACH ROW EXECUTE FUNCTION nebu_notify_container_change()",
+];
+
+/// Installs the notify trigger on `containers`. Other backends have no
+/// LISTEN/NOTIFY and rely on polling alone.
+pub async fn install_trigger(db: &DatabaseConnection) -> Result<(), DbErr> {
+    if db.get_database_backend() != DbBackend::Postgres {
+        return Ok(());
+    }
+    for statement in TRIGGER_STATEMENTS {
+        db.execute_unprepared(statement).await?;
+    }
+    Ok(())
+}
+
+/// Listens for container changes on the Postgres at `database_url` and
+/// signals `wake` for each. Reconnects on errors, waking once after every
+/// reconnect since notifications sent in between are lost.
+pub fn spawn_listener(database_url: String, wake: Arc<Notify>) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        loop {
+            let mut listener = match PgListener::connect(&database_url).await {
+                Ok(listener) => listener,
+                Err(e) => {
+                    warn!("[Container Notify] Failed to connect listener: {}", e);
+                    tokio::time::sleep(RECONNECT_DELAY).await;
+                    continue;
+                }
+            };
+            if let Err(e) = listener.listen(CONTAINER_CHANGES_CHANNEL).await {
+                warn!("[Container Notify] Failed to listen for changes: {}", e);
+                tokio::time::sleep(RECONNECT_DELAY).await;
+                continue;
+            }
+            info!(
+                "[Container Notify] Listening on '{}'",
+                CONTAINER_CHANGES_CHANNEL
+            );
+            wake.notify_one();
+
+            loop {
+                match listener.try_recv().await {
+                    Ok(Some(notification)) => {
+                        debug!(
+                            "[Container Notify] Container {} changed",
+                            notification.payload()
+                        );
+                        wake.notify_one();
+                    }
+                    // The connection dropped; the next receive reconnects
+                    Ok(None) => {
+                        warn!("[Container Notify] Listener connection lost, reconnecting");
+                        wake.notify_one();
+                    }
+                    Err(e) => {
+                        warn!("[Container Notify] Listener failed: {}", e);
+                        break;
+                    }
+                }
+            }
+            tokio::time::sleep(RECONNECT_DELAY).await;
+        }
+    })
+}
+
+/// Runs `pass` forever, starting the next one after `poll_interval` or as
+/// soon as `wake` is signalled, whichever comes first. Wakeups during a pass
+/// are kept, so a change made mid-pass gets a pass of its own.
+pub async fn run_reconcile_loop<F, Fut>(mut pass: F, wake: Arc<Notify>, poll_interval: Duration)
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = ()>,
+{
+    loop {
+        pass().await;
+        tokio::select! {
+            _ = tokio::time::sleep(poll_interval) => {}
+            _ = wake.notified() => debug!("[Container Notify] Woken by a container change"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[tokio::test]
+    async fn test_wake_runs_a_pass_before_the_poll_interval() {
+        // Arrange
+        let passes = Arc::new(AtomicUsize::new(0));
+        let wake = Arc::new(Notify::new());
+        let counter = passes.clone();
+        let reconciler = tokio::spawn(run_reconcile_loop(
+            move || {
+                let counter = counter.clone();
+                async move {
+                    counter.fetch_add(1, Ordering::SeqCst);
+                }
+            },
+            wake.clone(),
+            Duration::from_secs(3600),
+        ));
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        // Act
+        wake.notify_one();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        // Assert
+        assert_eq!(passes.load(Ordering::SeqCst), 2);
+        reconciler.abort();
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a Postgres at NEBU_TEST_DATABASE_URL"]
+    async fn test_insert_notifies_listener() {
+        use crate::entities::containers;
+        use sea_orm::{ActiveModelTrait, ActiveValue::Set, Database, EntityTrait, Schema};
+
+        let database_url =
+            std::env::var("NEBU_TEST_DATABASE_URL").expect("NEBU_TEST_DATABASE_URL is not set");
+        let db = Database::connect(&database_url).await.unwrap();
+        let schema = Schema::new(db.get_database_backend());
+        db.execute(
+            db.get_database_backend().build(
+                schema
+                    .create_table_from_entity(containers::Entity)
+                    .if_not_exists(),
+            ),
+        )
+        .await
+        .unwrap();
+        install_trigger(&db).await.unwrap();
+
+        let wake = Arc::new(Notify::new());
+        let listener = spawn_listener(database_url, wake.clone());
+        // The first wakeup comes from connecting
+        tokio::time::timeout(Duration::from_secs(10), wake.notified())
+            .await
+            .unwrap();
+
+        let id = short_uuid::ShortUuid::generate().to_string();
+        containers::ActiveModel {
+            id: Set(id.clone()),
+            namespace: Set("test".to_string()),
+            name: Set(format!("notify-{}", id.to_lowercase())),
+            full_name: Set(format!("test/notify-{}", id.to_lowercase())),
+            owner: Set("me".to_string()),
+            image: Set("busybox".to_string()),
+            restart: Set("Never".to_string()),
+            updated_at: Set(chrono::Utc::now().into()),
+            created_at: Set(chrono::Utc::now().into()),
+            ..Default::default()
+        }
+        .insert(&db)
+        .await
+        .unwrap();
+
+        // Well within the poll interval
+        tokio::time::timeout(Duration::from_secs(1), wake.notified())
+            .await
+            .expect("insert should wake the reconciler");
+
+        containers::Entity::delete_by_id(id)
+            .exec(&db)
+            .await
+            .unwrap();
+        listener.abort();
+    }
+}
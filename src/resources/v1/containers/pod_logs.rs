@@ -0,0 +1,198 @@
+// src/resources/v1/containers/pod_logs.rs
+//
+// Fallback for container logs when SSH over the tailnet is unavailable. RunPod
+// keeps the recent container output of a pod, which its API serves; it is
+// usually less than the full log file, so it is returned with a warning.
+
+use serde::Deserialize;
+use tracing::warn;
+
+const DEFAULT_RUNPOD_LOGS_URL: &str = "https://hapi.runpod.net/v1";
+
+#[derive(Debug, Clone, Default, Deserialize, PartialEq)]
+pub struct PodLogs {
+    /// Output of the pod's container
+    #[serde(default)]
+    pub container: Vec<String>,
+    /// RunPod's own messages about the pod, e.g. image pulls
+    #[serde(default)]
+    pub system: Vec<String>,
+}
+
+impl PodLogs {
+    /// The last `lines` lines of the container's output
+    pub fn container_tail(&self, lines: u64) -> String {
+        let skip = self.container.len().saturating_sub(lines as usize);
+        let mut out = String::new();
+        for line in &self.container[skip..] {
+            out.push_str(line);
+            out.push('\n');
+        }
+        out
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum PodLogsError {
+    #[error("RunPod logs request failed: {0}")]
+    Request(#[from] reqwest::Error),
+
+    #[error("RunPod logs API returned {0}: {1}")]
+    Api(reqwest::StatusCode, String),
+}
+
+/// Reads a pod's logs from the RunPod API.
+pub struct PodLogsClient {
+    api_key: String,
+    base_url: String,
+    http: reqwest::Client,
+}
+
+impl PodLogsClient {
+    pub fn new(api_key: String, base_url: String) -> Self {
+        Self {
+            api_key,
+            base_url: base_url.trim_end_matches('/').to_string(),
+            http: crate::utils::http::shared_client(),
+        }
+    }
+
+    /// Uses `RUNPOD_API_KEY`, and `RUNPOD_LOGS_URL` when set. None without a key.
+    pub fn from_env() -> Option<Self> {
+        let api_key = std::env::var("RUNPOD_API_KEY").ok()?;
+        let base_url = std::env::var("RUNPOD_LOGS_URL")
+            .unwrap_or_else(|_| DEFAULT_RUNPOD_LOGS_URL.to_string());
+        Some(Self::new(api_key, base_url))
+    }
+
+    pub async fn fetch(&self, pod_id: &str) -> Result<PodLogs, PodLogsError> {
+        let response = self
+            .http
+            .get(format!("{}/pod/{}/logs", self.base_url, pod_id))
+            .bearer_auth(&self.api_key)
+            .send()
+            .await?;
+        if !response.status().is_success() {
+            return Err(PodLogsError::Api(
+                response.status(),
+                response.text().await.unwrap_or_default(),
+            ));
+        }
+        Ok(response.json().await?)
+    }
+}
+
+/// Logs read over SSH, or when SSH failed, the pod's logs from the RunPod API
+/// behind a line saying where they came from. Only errors when neither worked.
+pub async fn with_api_fallback(
+    ssh_result: Result<String, std::io::Error>,
+    pod_id: Option<&str>,
+    client: Option<&PodLogsClient>,
+) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+    let ssh_error = match ssh_result {
+        Ok(logs) => return Ok(logs),
+        Err(e) => e,
+    };
+    let (Some(pod_id), Some(client)) = (pod_id, client) else {
+        return Err(ssh_error.into());
+    };
+
+    warn!(
+        "[Pod Logs] SSH log read for pod {} failed, falling back to the RunPod API: {}",
+        pod_id, ssh_error
+    );
+    match client.fetch(pod_id).await {
+        Ok(logs) => {
+            let mut out = format!(
+                "[nebu] SSH unavailable ({}); showing logs from the RunPod API, which may be incomplete\n",
+                ssh_error
+            );
+            for line in logs.system.iter().chain(logs.container.iter()) {
+                out.push_str(line);
+                out.push('\n');
+            }
+            Ok(out)
+        }
+        Err(e) => Err(format!(
+            "Failed to read logs over SSH ({}) and from the RunPod API ({})",
+            ssh_error, e
+        )
+        .into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::extract::Path;
+    use axum::routing::get;
+    use axum::{Json, Router};
+
+    async fn serve_fake_runpod() -> String {
+        async fn logs(Path(pod_id): Path<String>) -> Json<serde_json::Value> {
+            Json(serde_json::json!({
+                "container": [format!("hello from {}", pod_id), "step 2"],
+                "system": ["pulling image"]
+            }))
+        }
+
+        let app = Router::new().route("/pod/:id/logs", get(logs));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        format!("http://{}", addr)
+    }
+
+    fn ssh_down() -> std::io::Error {
+        std::io::Error::new(std::io::ErrorKind::ConnectionRefused, "connection refused")
+    }
+
+    #[tokio::test]
+    async fn test_falls_back_to_api_when_ssh_fails() {
+        // Arrange
+        let client = PodLogsClient::new("key".to_string(), serve_fake_runpod().await);
+
+        // Act
+        let logs = with_api_fallback(Err(ssh_down()), Some("pod1"), Some(&client))
+            .await
+            .unwrap();
+
+        // Assert
+        let lines: Vec<&str> = logs.lines().collect();
+        assert!(lines[0].starts_with("[nebu] SSH unavailable (connection refused)"));
+        assert_eq!(lines[1..], ["pulling image", "hello from pod1", "step 2"]);
+    }
+
+    #[tokio::test]
+    async fn test_ssh_logs_are_returned_as_is() {
+        let client = PodLogsClient::new("key".to_string(), serve_fake_runpod().await);
+        let logs = with_api_fallback(Ok("from ssh\n".to_string()), Some("pod1"), Some(&client))
+            .await
+            .unwrap();
+        assert_eq!(logs, "from ssh\n");
+    }
+
+    #[tokio::test]
+    async fn test_errors_when_both_sources_fail() {
+        // Nothing listens here
+        let client = PodLogsClient::new("key".to_string(), "http://127.0.0.1:9".to_string());
+        let err = with_api_fallback(Err(ssh_down()), Some("pod1"), Some(&client))
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("connection refused"));
+
+        assert!(with_api_fallback(Err(ssh_down()), None, Some(&client))
+            .await
+            .is_err());
+    }
+
+    #[test]
+    fn test_container_tail() {
+        let logs = PodLogs {
+            container: vec!["one".to_string(), "two".to_string(), "three".to_string()],
+            system: vec!["pulling image".to_string()],
+        };
+        assert_eq!(logs.container_tail(2), "two\nthree\n");
+        assert_eq!(logs.container_tail(10), "one\ntwo\nthree\n");
+    }
+}
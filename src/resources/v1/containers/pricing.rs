@@ -0,0 +1,144 @@
+// src/resources/v1/containers/pricing.rs
+//
+// The accelerator catalog with what each one costs right now. Prices and
+// stock come from RunPod's GraphQL API and are cached briefly, since they
+// change often but not from one request to the next. Platforms without a
+// pricing source list their accelerators with the price left empty. The
+// prices themselves are fetched by `datacenters::DatacenterClient`.
+
+use crate::accelerator::aws::AwsProvider;
+use crate::accelerator::base::{AcceleratorProvider, Config};
+use crate::accelerator::runpod::RunPodProvider;
+use crate::resources::v1::containers::datacenters::GpuPricing;
+use crate::resources::v1::containers::models::V1AcceleratorOffering;
+use std::collections::HashMap;
+
+/// Platforms the catalog can be listed for
+pub const CATALOG_PLATFORMS: &[&str] = &["runpod", "aws"];
+
+/// Every supported accelerator on `platform`, priced from `pricing` where the
+/// platform's name for it has an entry. Accelerators the platform doesn't
+/// offer are left out.
+pub fn accelerator_catalog(
+    config: &Config,
+    provider: &dyn AcceleratorProvider,
+    pricing: &[GpuPricing],
+) -> Vec<V1AcceleratorOffering> {
+    let by_id: HashMap<&str, &GpuPricing> = pricing.iter().map(|p| (p.id.as_str(), p)).collect();
+    config
+        .accelerators
+        .supported
+        .iter()
+        .filter_map(|acc| {
+            let platform_name = provider.get_platform_name(&acc.name)?;
+            let priced = by_id.get(platform_name.as_str());
+            let lowest = priced.and_then(|p| p.lowest_price.as_ref());
+            let stock_status = lowest.and_then(|p| p.stock_status.clone());
+            Some(V1AcceleratorOffering {
+                name: acc.name.clone(),
+                memory: acc.memory,
+                platform: provider.name().to_string(),
+                platform_name: platform_name.clone(),
+                price_per_hour: priced.and_then(|p| p.price_per_hour()),
+                spot_price_per_hour: lowest.and_then(|p| p.minimum_bid_price),
+                // Unknown without pricing, rather than unavailable
+                available: priced.map(|_| stock_status.is_some()),
+                stock_status,
+            })
+        })
+        .collect()
+}
+
+/// The provider for `platform`, if the catalog covers it
+pub fn catalog_provider(platform: &str) -> Option<Box<dyn AcceleratorProvider>> {
+    match platform {
+        "runpod" => Some(Box::new(RunPodProvider::new())),
+        "aws" => Some(Box::new(AwsProvider::new())),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::resources::v1::containers::datacenters::DatacenterClient;
+    use axum::routing::post;
+    use axum::{Json, Router};
+
+    async fn serve_fake_runpod(body: serde_json::Value) -> String {
+        let app = Router::new().route("/graphql", post(move || async move { Json(body) }));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        format!("http://{}/graphql", addr)
+    }
+
+    #[tokio::test]
+    async fn test_catalog_includes_price_and_availability() {
+        // Arrange
+        let url = serve_fake_runpod(serde_json::json!({
+            "data": {"gpuTypes": [
+                {
+                    "id": "NVIDIA GeForce RTX 4090",
+                    "displayName": "RTX 4090",
+                    "memoryInGb": 24,
+                    "securePrice": 0.69,
+                    "communityPrice": 0.44,
+                    "lowestPrice": {"minimumBidPrice": 0.29, "uninterruptablePrice": 0.34, "stockStatus": "High"}
+                },
+                {
+                    "id": "NVIDIA H100 80GB HBM3",
+                    "displayName": "H100 SXM",
+                    "memoryInGb": 80,
+                    "securePrice": 2.99,
+                    "communityPrice": 0,
+                    "lowestPrice": {"minimumBidPrice": null, "uninterruptablePrice": null, "stockStatus": null}
+                }
+            ]}
+        }))
+        .await;
+        let client = DatacenterClient::new("key".to_string(), url);
+
+        // Act
+        let pricing = client.gpu_types().await.unwrap();
+        let catalog = accelerator_catalog(&Config::default(), &RunPodProvider::new(), &pricing);
+
+        // Assert
+        let rtx = catalog.iter().find(|a| a.name == "RTX_4090").unwrap();
+        assert_eq!(rtx.platform, "runpod");
+        assert_eq!(rtx.price_per_hour, Some(0.34));
+        assert_eq!(rtx.spot_price_per_hour, Some(0.29));
+        assert_eq!(rtx.stock_status.as_deref(), Some("High"));
+        assert_eq!(rtx.available, Some(true));
+
+        let h100 = catalog.iter().find(|a| a.name == "H100_SXM").unwrap();
+        assert_eq!(h100.price_per_hour, Some(2.99));
+        assert_eq!(h100.available, Some(false));
+
+        let unpriced = catalog.iter().find(|a| a.name == "A40").unwrap();
+        assert_eq!((unpriced.price_per_hour, unpriced.available), (None, None));
+
+        let listed = serde_json::to_value(rtx).unwrap();
+        for field in [
+            "price_per_hour",
+            "spot_price_per_hour",
+            "stock_status",
+            "available",
+        ] {
+            assert!(listed.get(field).is_some(), "missing {}", field);
+        }
+    }
+
+    #[test]
+    fn test_catalog_without_pricing_lists_platform_accelerators() {
+        let provider = catalog_provider("aws").unwrap();
+
+        let catalog = accelerator_catalog(&Config::default(), provider.as_ref(), &[]);
+
+        assert!(!catalog.is_empty());
+        assert!(catalog
+            .iter()
+            .all(|a| a.platform == "aws" && a.price_per_hour.is_none()));
+        assert!(catalog_provider("gce").is_none());
+    }
+}
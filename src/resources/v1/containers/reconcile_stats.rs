@@ -0,0 +1,166 @@
+// src/resources/v1/containers/reconcile_stats.rs
+//
+// Progress of the container reconcile loop: how many passes it has made,
+// when each container was last reconciled and what went wrong. The loop logs
+// a summary every so often, and `nebu daemon --once` prints one after its
+// single pass.
+
+use chrono::{DateTime, Utc};
+use dashmap::DashMap;
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+/// How one container's reconciles have gone
+#[derive(Debug, Clone, PartialEq)]
+pub struct ContainerReconcile {
+    pub last_reconciled: DateTime<Utc>,
+    /// The error of the latest reconcile, None when it succeeded
+    pub last_error: Option<String>,
+    pub errors: u64,
+}
+
+#[derive(Debug, Default)]
+pub struct ReconcileStats {
+    passes: AtomicU64,
+    last_pass: Mutex<Option<DateTime<Utc>>>,
+    containers: DashMap<String, ContainerReconcile>,
+}
+
+impl ReconcileStats {
+    /// Counts a pass of the loop, returning how many there have been
+    pub fn record_pass(&self) -> u64 {
+        *self.last_pass.lock().unwrap() = Some(Utc::now());
+        self.passes.fetch_add(1, Ordering::SeqCst) + 1
+    }
+
+    /// Records how reconciling `container_id` went
+    pub fn record_container(&self, container_id: &str, error: Option<String>) {
+        let mut entry = self
+            .containers
+            .entry(container_id.to_string())
+            .or_insert_with(|| ContainerReconcile {
+                last_reconciled: Utc::now(),
+                last_error: None,
+                errors: 0,
+            });
+        entry.last_reconciled = Utc::now();
+        if error.is_some() {
+            entry.errors += 1;
+        }
+        entry.last_error = error;
+    }
+
+    /// Forgets the containers not in `active`, once they're deleted or
+    /// finished, so the stats don't grow with every container ever run
+    pub fn retain_containers(&self, active: &[&str]) {
+        self.containers
+            .retain(|id, _| active.contains(&id.as_str()));
+    }
+
+    pub fn passes(&self) -> u64 {
+        self.passes.load(Ordering::SeqCst)
+    }
+
+    pub fn container(&self, container_id: &str) -> Option<ContainerReconcile> {
+        self.containers.get(container_id).map(|c| c.clone())
+    }
+
+    pub fn summary(&self) -> ReconcileSummary {
+        let mut failing: Vec<(String, String)> = self
+            .containers
+            .iter()
+            .filter_map(|c| Some((c.key().clone(), c.last_error.clone()?)))
+            .collect();
+        failing.sort();
+        ReconcileSummary {
+            passes: self.passes(),
+            last_pass: *self.last_pass.lock().unwrap(),
+            containers: self.containers.len(),
+            errors: self.containers.iter().map(|c| c.errors).sum(),
+            failing,
+        }
+    }
+}
+
+/// A snapshot of `ReconcileStats`
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReconcileSummary {
+    pub passes: u64,
+    pub last_pass: Option<DateTime<Utc>>,
+    /// Containers reconciled at least once
+    pub containers: usize,
+    /// Failed reconciles across all containers
+    pub errors: u64,
+    /// Containers whose latest reconcile failed, with the error
+    pub failing: Vec<(String, String)>,
+}
+
+impl fmt::Display for ReconcileSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let last_pass = self
+            .last_pass
+            .map(|t| t.to_rfc3339())
+            .unwrap_or_else(|| "never".to_string());
+        write!(
+            f,
+            "{} pass(es), last at {}; {} container(s) reconciled, {} error(s), {} failing",
+            self.passes,
+            last_pass,
+            self.containers,
+            self.errors,
+            self.failing.len()
+        )?;
+        for (id, error) in &self.failing {
+            write!(f, "\n  {}: {}", id, error)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_summary_counts_passes_and_failures() {
+        // Arrange
+        let stats = ReconcileStats::default();
+
+        // Act
+        stats.record_pass();
+        stats.record_container("c1", None);
+        stats.record_container("c2", Some("no capacity".to_string()));
+        stats.record_pass();
+        stats.record_container("c2", Some("still no capacity".to_string()));
+        stats.record_container("c1", Some("gone".to_string()));
+        stats.record_container("c1", None);
+
+        // Assert
+        let summary = stats.summary();
+        assert_eq!(summary.passes, 2);
+        assert!(summary.last_pass.is_some());
+        assert_eq!(summary.containers, 2);
+        assert_eq!(summary.errors, 3);
+        assert_eq!(
+            summary.failing,
+            vec![("c2".to_string(), "still no capacity".to_string())]
+        );
+        assert_eq!(stats.container("c1").unwrap().last_error, None);
+        assert!(summary.to_string().contains("c2: still no capacity"));
+    }
+
+    #[test]
+    fn test_inactive_containers_are_forgotten() {
+        let stats = ReconcileStats::default();
+        stats.record_container("c1", None);
+        stats.record_container("c2", Some("gone".to_string()));
+
+        stats.retain_containers(&["c1"]);
+
+        assert!(stats.container("c1").is_some());
+        assert!(stats.container("c2").is_none());
+        assert_eq!(stats.summary().containers, 1);
+        assert!(stats.summary().failing.is_empty());
+    }
+}
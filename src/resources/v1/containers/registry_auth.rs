@@ -0,0 +1,426 @@
+// src/resources/v1/containers/registry_auth.rs
+//
+// Credentials for pulling a container's image from a private registry. A
+// namespace names a secret holding them in its `registry_secret`, covering
+// the one registry they are for. The username and password authenticate the
+// OCI pull of the image config, and are saved as a RunPod registry auth owned
+// by the namespace that the pod is created with. Images from any other
+// registry fall back to `RUNPOD_CONTAINER_REGISTRY_AUTH_ID`.
+
+use crate::entities::namespaces;
+use crate::query::Query;
+use crate::resources::v1::containers::datacenters::DatacenterClient;
+use oci_distribution::secrets::RegistryAuth;
+use sea_orm::{ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter};
+use serde::Deserialize;
+use tracing::info;
+
+const REGISTRY_AUTHS_QUERY: &str = "query { myself { containerRegistryCreds { id name } } }";
+
+const SAVE_REGISTRY_AUTH_MUTATION: &str =
+    "mutation($input: SaveRegistryAuthInput!) { saveRegistryAuth(input: $input) { id name } }";
+
+const UPDATE_REGISTRY_AUTH_MUTATION: &str =
+    "mutation($input: UpdateRegistryAuthInput!) { updateRegistryAuth(input: $input) { id name } }";
+
+/// What a namespace's registry secret holds, as a JSON object
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RegistryCredentials {
+    /// Host of the registry the credentials are for, e.g. `ghcr.io`
+    pub registry: String,
+    pub username: String,
+    pub password: String,
+}
+
+impl RegistryCredentials {
+    pub fn parse(value: &str) -> Result<Self, String> {
+        let credentials: Self = serde_json::from_str(value.trim())
+            .map_err(|e| format!("Invalid registry credentials: {}", e))?;
+        if credentials.registry.is_empty() {
+            return Err("Registry credentials must name their registry".to_string());
+        }
+        Ok(credentials)
+    }
+
+    /// Whether `image` is pulled from the registry these credentials cover
+    pub fn covers(&self, image: &str) -> bool {
+        image_registry(image).eq_ignore_ascii_case(normalize_registry(&self.registry))
+    }
+
+    /// Name of the RunPod registry auth holding these credentials for
+    /// `namespace`, so every namespace only ever uses its own
+    pub fn runpod_auth_name(&self, namespace: &str) -> String {
+        format!(
+            "nebu-{}-{}",
+            namespace,
+            normalize_registry(&self.registry).to_ascii_lowercase()
+        )
+    }
+}
+
+/// Docker Hub goes by several names
+fn normalize_registry(registry: &str) -> &str {
+    match registry {
+        "index.docker.io" | "registry-1.docker.io" => "docker.io",
+        other => other,
+    }
+}
+
+/// The registry host of an image reference, `docker.io` when it names none
+pub fn image_registry(image: &str) -> &str {
+    match image.split_once('/') {
+        Some((first, _)) if first.contains('.') || first.contains(':') || first == "localhost" => {
+            normalize_registry(first)
+        }
+        _ => "docker.io",
+    }
+}
+
+/// How to pull an image: the OCI auth for reading its config, and the RunPod
+/// registry auth to create its pod with
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ImagePullAuth {
+    pub credentials: Option<RegistryCredentials>,
+    pub runpod_auth_id: Option<String>,
+}
+
+impl ImagePullAuth {
+    /// How to authenticate OCI registry requests, anonymously without
+    /// credentials for the image's registry
+    pub fn oci_auth(&self) -> RegistryAuth {
+        match &self.credentials {
+            Some(c) => RegistryAuth::Basic(c.username.clone(), c.password.clone()),
+            None => RegistryAuth::Anonymous,
+        }
+    }
+}
+
+/// The credentials in the secret `namespace` names as its `registry_secret`,
+/// None when it doesn't name one
+pub async fn namespace_registry_credentials(
+    db: &DatabaseConnection,
+    namespace: &str,
+) -> Result<Option<RegistryCredentials>, String> {
+    let namespace_model = namespaces::Entity::find()
+        .filter(namespaces::Column::Name.eq(namespace))
+        .one(db)
+        .await
+        .map_err(|e| format!("Failed to look up namespace '{}': {}", namespace, e))?;
+    let Some(secret_name) = namespace_model.and_then(|ns| ns.registry_secret) else {
+        return Ok(None);
+    };
+
+    let secret = Query::find_secret_by_namespace_and_name(db, namespace, &secret_name)
+        .await
+        .map_err(|e| format!("Failed to look up registry secret: {}", e))?
+        .ok_or_else(|| {
+            format!(
+                "Registry secret '{}' not found in namespace '{}'",
+                secret_name, namespace
+            )
+        })?;
+    let value = secret
+        .decrypt_value()
+        .map_err(|e| format!("Failed to decrypt registry secret '{}': {}", secret_name, e))?;
+    RegistryCredentials::parse(&value).map(Some)
+}
+
+#[derive(Deserialize)]
+struct RegistryAuthsData {
+    myself: RegistryAuthsOwner,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RegistryAuthsOwner {
+    #[serde(default)]
+    container_registry_creds: Vec<RunpodRegistryAuth>,
+}
+
+#[derive(Deserialize)]
+struct RunpodRegistryAuth {
+    id: String,
+    name: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SavedRegistryAuth {
+    #[serde(alias = "updateRegistryAuth")]
+    save_registry_auth: RunpodRegistryAuth,
+}
+
+/// Id of the RunPod registry auth holding `credentials` for `namespace`. The
+/// auth is saved the first time and its credentials kept up to date after.
+pub async fn ensure_runpod_registry_auth(
+    client: &DatacenterClient,
+    namespace: &str,
+    credentials: &RegistryCredentials,
+) -> Result<String, String> {
+    let name = credentials.runpod_auth_name(namespace);
+    let existing: Option<RegistryAuthsData> = client
+        .graphql(REGISTRY_AUTHS_QUERY, serde_json::Value::Null)
+        .await
+        .map_err(|e| format!("Failed to list RunPod registry auths: {}", e))?;
+    let existing = existing
+        .into_iter()
+        .flat_map(|data| data.myself.container_registry_creds)
+        .find(|auth| auth.name == name);
+
+    let (mutation, input) = match existing {
+        Some(auth) => (
+            UPDATE_REGISTRY_AUTH_MUTATION,
+            serde_json::json!({
+                "id": auth.id,
+                "username": credentials.username,
+                "password": credentials.password,
+            }),
+        ),
+        None => {
+            info!("[Runpod Controller] Saving RunPod registry auth '{}'", name);
+            (
+                SAVE_REGISTRY_AUTH_MUTATION,
+                serde_json::json!({
+                    "name": name,
+                    "username": credentials.username,
+                    "password": credentials.password,
+                }),
+            )
+        }
+    };
+    let saved: Option<SavedRegistryAuth> = client
+        .graphql(mutation, serde_json::json!({ "input": input }))
+        .await
+        .map_err(|e| format!("Failed to save RunPod registry auth '{}': {}", name, e))?;
+    saved
+        .map(|s| s.save_registry_auth.id)
+        .ok_or_else(|| format!("RunPod returned no registry auth for '{}'", name))
+}
+
+/// How to pull `image` for a container in `namespace`: with the namespace's
+/// credentials when they cover its registry, otherwise with the global RunPod
+/// registry auth
+pub async fn image_pull_auth(
+    db: &DatabaseConnection,
+    client: Option<&DatacenterClient>,
+    namespace: &str,
+    image: &str,
+) -> Result<ImagePullAuth, String> {
+    let credentials = namespace_registry_credentials(db, namespace)
+        .await?
+        .filter(|c| c.covers(image));
+    let Some(credentials) = credentials else {
+        return Ok(ImagePullAuth {
+            credentials: None,
+            runpod_auth_id: std::env::var("RUNPOD_CONTAINER_REGISTRY_AUTH_ID")
+                .ok()
+                .filter(|id| !id.is_empty()),
+        });
+    };
+    let client = client.ok_or_else(|| {
+        "RUNPOD_API_KEY is needed to save the namespace's registry credentials".to_string()
+    })?;
+    let runpod_auth_id = ensure_runpod_registry_auth(client, namespace, &credentials).await?;
+    Ok(ImagePullAuth {
+        credentials: Some(credentials),
+        runpod_auth_id: Some(runpod_auth_id),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::entities::secrets;
+    use axum::routing::post;
+    use axum::{Json, Router};
+    use sea_orm::{ActiveModelTrait, ConnectionTrait, Database, Schema, Set};
+    use std::sync::{Arc, Mutex};
+
+    const CREDENTIALS: &str = r#"{"registry": "ghcr.io", "username": "bot", "password": "s3cret"}"#;
+
+    #[test]
+    fn test_credentials_only_cover_their_registry() {
+        // Arrange
+        let credentials = RegistryCredentials::parse(CREDENTIALS).unwrap();
+
+        // Act
+        let covered = [
+            "ghcr.io/team/app:1",
+            "busybox",
+            "library/busybox",
+            "docker.io/library/busybox",
+            "localhost:5000/app",
+        ]
+        .map(|image| credentials.covers(image));
+
+        // Assert
+        assert_eq!(covered, [true, false, false, false, false]);
+        assert_eq!(
+            image_registry("index.docker.io/library/busybox"),
+            "docker.io"
+        );
+        assert_eq!(image_registry("localhost:5000/app"), "localhost:5000");
+    }
+
+    #[test]
+    fn test_parse_registry_credentials() {
+        let credentials = RegistryCredentials::parse(CREDENTIALS).unwrap();
+        assert_eq!(credentials.runpod_auth_name("team"), "nebu-team-ghcr.io");
+        assert!(matches!(
+            ImagePullAuth {
+                credentials: Some(credentials),
+                runpod_auth_id: None,
+            }
+            .oci_auth(),
+            RegistryAuth::Basic(user, password) if user == "bot" && password == "s3cret"
+        ));
+
+        // A bare RunPod auth id could name another tenant's auth
+        assert!(RegistryCredentials::parse("clxyz123").is_err());
+        assert!(RegistryCredentials::parse(
+            r#"{"registry": "ghcr.io", "username": "bot", "password": "x", "runpod_auth_id": "theirs"}"#
+        )
+        .is_err());
+        assert!(RegistryCredentials::parse(
+            r#"{"registry": "", "username": "bot", "password": "x"}"#
+        )
+        .is_err());
+    }
+
+    /// A RunPod GraphQL API holding `auths`, recording the mutations it gets
+    async fn serve_fake_runpod(
+        auths: serde_json::Value,
+        mutations: Arc<Mutex<Vec<serde_json::Value>>>,
+    ) -> String {
+        let app = Router::new().route(
+            "/graphql",
+            post(move |Json(body): Json<serde_json::Value>| async move {
+                let query = body["query"].as_str().unwrap_or_default().to_string();
+                if query.contains("myself") {
+                    return Json(serde_json::json!({
+                        "data": {"myself": {"containerRegistryCreds": auths}}
+                    }));
+                }
+                mutations
+                    .lock()
+                    .unwrap()
+                    .push(body["variables"]["input"].clone());
+                let field = if query.contains("updateRegistryAuth") {
+                    "updateRegistryAuth"
+                } else {
+                    "saveRegistryAuth"
+                };
+                let id = body["variables"]["input"]["id"]
+                    .as_str()
+                    .unwrap_or("new-auth");
+                Json(serde_json::json!({
+                    "data": {field: {"id": id, "name": "nebu-team-ghcr.io"}}
+                }))
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        format!("http://{}/graphql", addr)
+    }
+
+    #[tokio::test]
+    async fn test_runpod_auth_is_saved_from_credentials() {
+        let credentials = RegistryCredentials::parse(CREDENTIALS).unwrap();
+
+        let mutations = Arc::new(Mutex::new(Vec::new()));
+        let url = serve_fake_runpod(
+            serde_json::json!([{"id": "theirs", "name": "nebu-other-ghcr.io"}]),
+            mutations.clone(),
+        )
+        .await;
+        let client = DatacenterClient::new("key".to_string(), url);
+        let id = ensure_runpod_registry_auth(&client, "team", &credentials)
+            .await
+            .unwrap();
+        assert_eq!(id, "new-auth");
+        assert_eq!(
+            *mutations.lock().unwrap(),
+            vec![serde_json::json!({
+                "name": "nebu-team-ghcr.io",
+                "username": "bot",
+                "password": "s3cret",
+            })]
+        );
+
+        let mutations = Arc::new(Mutex::new(Vec::new()));
+        let url = serve_fake_runpod(
+            serde_json::json!([{"id": "ours", "name": "nebu-team-ghcr.io"}]),
+            mutations.clone(),
+        )
+        .await;
+        let client = DatacenterClient::new("key".to_string(), url);
+        let id = ensure_runpod_registry_auth(&client, "team", &credentials)
+            .await
+            .unwrap();
+        assert_eq!(id, "ours");
+        assert_eq!(mutations.lock().unwrap()[0]["id"], "ours");
+    }
+
+    #[tokio::test]
+    async fn test_credentials_come_from_namespace_secret() {
+        let db = Database::connect("sqlite::memory:").await.unwrap();
+        let schema = Schema::new(db.get_database_backend());
+        for stmt in [
+            schema.create_table_from_entity(namespaces::Entity),
+            schema.create_table_from_entity(secrets::Entity),
+        ] {
+            db.execute(db.get_database_backend().build(&stmt))
+                .await
+                .unwrap();
+        }
+        for (name, registry_secret) in [("team", Some("ghcr")), ("other", None)] {
+            namespaces::ActiveModel {
+                id: Set(name.to_string()),
+                name: Set(name.to_string()),
+                owner: Set("me".to_string()),
+                owner_ref: Set(None),
+                labels: Set(None),
+                default_env: Set(None),
+                default_user: Set(None),
+                registry_secret: Set(registry_secret.map(String::from)),
+                default_platform: Set(None),
+                default_accelerators: Set(None),
+                created_by: Set("me".to_string()),
+                updated_at: Set(chrono::Utc::now().into()),
+                created_at: Set(chrono::Utc::now().into()),
+            }
+            .insert(&db)
+            .await
+            .unwrap();
+        }
+        let secret = secrets::Model::new(
+            "s1".to_string(),
+            "ghcr".to_string(),
+            "team".to_string(),
+            "me".to_string(),
+            CREDENTIALS,
+            None,
+            None,
+            None,
+        )
+        .unwrap();
+        secrets::ActiveModel::from(secret)
+            .insert(&db)
+            .await
+            .unwrap();
+
+        let team = namespace_registry_credentials(&db, "team").await.unwrap();
+        let other = namespace_registry_credentials(&db, "other").await.unwrap();
+        assert_eq!(team.unwrap().username, "bot");
+        assert_eq!(other, None);
+
+        // Images from other registries don't use the namespace's credentials
+        let pull = image_pull_auth(&db, None, "team", "busybox").await.unwrap();
+        assert_eq!(pull.credentials, None);
+        assert!(image_pull_auth(&db, None, "team", "ghcr.io/team/app")
+            .await
+            .is_err());
+    }
+}
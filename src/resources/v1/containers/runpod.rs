@@ -1,57 +1,355 @@
 use crate::accelerator::base::AcceleratorProvider;
 use crate::accelerator::runpod::RunPodProvider;
 use crate::agent::aws::delete_s3_scoped_user;
+use crate::dns::records::{dns_action, DnsAction, DnsClient};
 use crate::entities::containers;
 use crate::models::{V1Meter, V1UserProfile};
 use crate::mutation::{self, Mutation};
 use crate::oci::client::pull_and_parse_config;
+use crate::proxy::meter_breaker::{Delivery, METER_INGEST};
+use crate::proxy::meters::{meter_client, meter_event, meter_source};
 use crate::query::Query;
-use crate::resources::v1::containers::base::{ContainerPlatform, ContainerStatus};
+use crate::resources::v1::containers::base::{
+    command_env, desired_status_action, exec_user, expand_variables, expanded_command,
+    get_tailscale_tags, lookup_env_secrets, never_ready_timeout_exceeded, preemption_victims,
+    queue_is_free_for, resolve_container_user, resolve_env, restart_decision,
+    validate_container_tailscale, with_namespace_defaults, ContainerPlatform, ContainerStatus,
+    DesiredStatusAction, QueueEntry, RestartDecision,
+};
+use crate::resources::v1::containers::bootstrap;
+use crate::resources::v1::containers::controller::PLATFORM_OPERATIONS;
+use crate::resources::v1::containers::datacenters::{
+    cpu_datacenter, placement_key, DatacenterClient,
+};
+use crate::resources::v1::containers::failure;
+use crate::resources::v1::containers::fallback::PlacementFailed;
 use crate::resources::v1::containers::models::{
-    RestartPolicy, V1Container, V1ContainerHealthCheck, V1ContainerRequest, V1ContainerStatus,
-    V1Port,
+    ControllerData, RestartPolicy, V1Container, V1ContainerHealthCheck, V1ContainerPlan,
+    V1ContainerRequest, V1ContainerStatus, V1LogParams, V1PodState, V1Port, V1PortRequest,
+    V1RestartState,
 };
+use crate::resources::v1::containers::pod_logs::{self, PodLogsClient};
+use crate::resources::v1::containers::registry_auth;
+use crate::resources::v1::containers::ssh_keys;
+use crate::resources::v1::containers::usage::{self, UsageSampler};
+use crate::resources::v1::containers::volume_gc::volume_name_for_owner;
+use crate::resources::v1::volumes::base::BASE_VOLUME_NAMESPACE;
 use crate::resources::v1::volumes::models::V1VolumePath;
-use crate::ssh::exec::run_ssh_command_ts;
+use crate::ssh::exec::{run_ssh_command_async, run_ssh_command_ts};
 use crate::ssh::keys;
+use crate::utils::http::shared_client;
 use crate::volumes::rclone::{SymlinkConfig, VolumeConfig, VolumePath};
 use petname;
-use regex::Regex;
 use runpod::*;
-use sea_orm::{ActiveModelTrait, DatabaseConnection, Set};
+use sea_orm::{ActiveModelTrait, DatabaseConnection, DbErr, Set};
 use short_uuid::ShortUuid;
 use std::collections::HashMap;
 use std::str::FromStr;
 use std::time::Duration;
 use tracing::{debug, error, info, warn};
 
-// Helper function to assign preference score based on location
-fn location_preference(location: &str) -> i32 {
-    // TODO: configurable!
-    if location.starts_with("United States")
-        || location.starts_with("Europe")
-        || location.starts_with("Canada")
+/// Pause between attempts of an SSH check
+const SSH_CHECK_RETRY_DELAY: Duration = Duration::from_secs(1);
+
+/// Runs `check` up to `attempts` times, each limited to `timeout`, waiting
+/// `delay` after a failed one. Returns the attempt that succeeded, or the
+/// last error.
+pub async fn check_with_retries<F, Fut>(
+    attempts: u32,
+    timeout: Duration,
+    delay: Duration,
+    mut check: F,
+) -> Result<u32, String>
+where
+    F: FnMut() -> Fut,
+    Fut: std::future::Future<Output = Result<(), String>>,
+{
+    let attempts = attempts.max(1);
+    let mut last_error = String::new();
+    for attempt in 1..=attempts {
+        match tokio::time::timeout(timeout, check()).await {
+            Ok(Ok(())) => return Ok(attempt),
+            Ok(Err(e)) => last_error = e,
+            Err(_) => last_error = format!("timed out after {:?}", timeout),
+        }
+        if attempt < attempts {
+            debug!(
+                "[Runpod Controller] Check attempt {}/{} failed: {}",
+                attempt, attempts, last_error
+            );
+            tokio::time::sleep(delay).await;
+        }
+    }
+    Err(last_error)
+}
+
+/// Resolves `nebu://{volume}/rest/of/path` to the same path in the backing
+/// storage of that volume. The volume in `namespace` wins, otherwise the base
+/// volume of that name (e.g. a per-region bucket) is used; either way it must
+/// belong to `owner`.
+pub async fn resolve_nebu_path(
+    db: &DatabaseConnection,
+    namespace: &str,
+    owner: &str,
+    path: &str,
+) -> anyhow::Result<String> {
+    let path_without_prefix = path.strip_prefix("nebu://").unwrap_or(path);
+    let (volume_name, remaining_path) = path_without_prefix
+        .split_once('/')
+        .unwrap_or((path_without_prefix, ""));
+    if volume_name.is_empty() {
+        error!("[Runpod Controller] Failed to parse nebu:// path: {}", path);
+        return Err(anyhow::anyhow!(
+            "[Runpod Controller] Failed to parse nebu:// path: {}",
+            path
+        ));
+    }
+    debug!(
+        "[Runpod Controller] Parsed nebu:// path - volume: {}, remaining: {} with owner: {} and namespace: {}",
+        volume_name, remaining_path, owner, namespace
+    );
+
+    let volume = match Query::find_volume_by_namespace_name_and_owners(
+        db,
+        namespace,
+        volume_name,
+        &[owner], // Use the container's owner for authorization
+    )
+    .await
     {
-        0 // Highest preference: US or Europe
+        Err(DbErr::RecordNotFound(msg)) if namespace != BASE_VOLUME_NAMESPACE => {
+            Query::find_volume_by_namespace_name_and_owners(
+                db,
+                BASE_VOLUME_NAMESPACE,
+                volume_name,
+                &[owner],
+            )
+            .await
+            .map_err(|e| match e {
+                DbErr::RecordNotFound(_) => DbErr::RecordNotFound(msg),
+                e => e,
+            })
+        }
+        found => found,
+    };
+    // The DbErr stays reachable through the context, see `is_invalid_volume`
+    let volume = volume.map_err(|e| {
+        error!(
+            "[Runpod Controller] Failed to find volume '{}' in namespace '{}' with owner '{}': {}",
+            volume_name, namespace, owner, e
+        );
+        anyhow::Error::new(e).context(format!(
+            "[Runpod Controller] Failed to find volume '{}' in namespace '{}'",
+            volume_name, namespace
+        ))
+    })?;
+
+    // Combine the volume's source with the remaining path
+    let resolved = if remaining_path.is_empty() {
+        volume.source
     } else {
-        2 // Lowest preference: Others
+        format!(
+            "{}/{}",
+            volume.source.trim_end_matches('/'),
+            remaining_path.trim_start_matches('/')
+        )
+    };
+    debug!("[Runpod Controller] Resolved {} to: {}", path, resolved);
+    Ok(resolved)
+}
+
+/// Whether building a volume config failed because of the volumes as
+/// declared, which retrying won't fix, rather than e.g. the database being
+/// unreachable while looking one up.
+fn is_invalid_volume(error: &anyhow::Error) -> bool {
+    !matches!(
+        error.downcast_ref::<DbErr>(),
+        Some(e) if !matches!(e, DbErr::RecordNotFound(_))
+    )
+}
+
+/// Container metadata a volume source or destination can refer to as
+/// `{id}`, `{name}`, `{namespace}` and `{owner}`, e.g. `s3://bucket/{owner}/{name}`
+pub struct VolumePlaceholders<'a> {
+    pub id: &'a str,
+    pub name: &'a str,
+    pub namespace: &'a str,
+    pub owner: &'a str,
+}
+
+impl VolumePlaceholders<'_> {
+    /// Replaces the known placeholders in `input`; anything else in braces is kept
+    pub fn expand(&self, input: &str) -> String {
+        [
+            ("{id}", self.id),
+            ("{name}", self.name),
+            ("{namespace}", self.namespace),
+            ("{owner}", self.owner),
+        ]
+        .iter()
+        .fold(input.to_string(), |acc, (placeholder, value)| {
+            acc.replace(placeholder, value)
+        })
     }
 }
 
-// Helper function to assign preference score based on stock status
-fn stock_status_preference(status: &Option<String>) -> i32 {
-    match status.as_deref() {
-        Some("High") => 0,   // Highest preference
-        Some("Medium") => 1, // Next preference
-        Some("Low") => 2,    // Lower preference
-        _ => 3,              // Lowest preference (None or other unexpected values)
+/// The GPU a container gets on RunPod
+#[derive(Debug, Clone, PartialEq)]
+pub struct SelectedGpu {
+    /// RunPod's GPU type id
+    pub gpu_type_id: String,
+    /// Our name for the accelerator
+    pub accelerator: String,
+    pub count: i32,
+}
+
+/// The first of the requested `count:type` accelerators that RunPod knows and
+/// currently offers. An empty `available_gpu_types` means RunPod couldn't
+/// say, and every known type is taken as available.
+pub fn select_accelerator(
+    accelerators: &[String],
+    accelerator_map: &HashMap<String, String>,
+    available_gpu_types: &[String],
+) -> Option<SelectedGpu> {
+    accelerators.iter().find_map(|accelerator| {
+        info!("[Runpod Controller] Accelerator: {}", accelerator);
+        let (count, name) = accelerator.split_once(':')?;
+        let count = count.parse::<i32>().ok()?;
+        let Some(gpu_type_id) = accelerator_map.get(name) else {
+            info!(
+                "[Runpod Controller] Unknown accelerator type: {}, trying next option",
+                name
+            );
+            return None;
+        };
+        if !available_gpu_types.is_empty() && !available_gpu_types.contains(gpu_type_id) {
+            info!(
+                "[Runpod Controller] Accelerator type '{}' is not available, trying next option",
+                gpu_type_id
+            );
+            return None;
+        }
+        Some(SelectedGpu {
+            gpu_type_id: gpu_type_id.clone(),
+            accelerator: name.to_string(),
+            count,
+        })
+    })
+}
+
+fn datacenter_stock_status(dc: &runpod::DataCenterItem, gpu_type_id: &str) -> Option<String> {
+    dc.gpu_availability
+        .iter()
+        .find(|gpu_item| gpu_item.gpuTypeId.as_deref() == Some(gpu_type_id))
+        .and_then(|item| item.stockStatus.clone())
+}
+
+/// The datacenter for a GPU pod of `owner`: one with storage support for the
+/// owner's network volume, in `placement_key` order by the GPU's stock.
+fn select_gpu_datacenter(
+    datacenters: Vec<runpod::DataCenterItem>,
+    gpu_type_id: &str,
+    owner: &str,
+    volume_datacenters: &[String],
+) -> Option<runpod::DataCenterItem> {
+    datacenters
+        .into_iter()
+        .filter(|dc| dc.storageSupport)
+        .min_by_key(|dc| {
+            placement_key(
+                &dc.id,
+                &dc.location,
+                datacenter_stock_status(dc, gpu_type_id).as_deref(),
+                owner,
+                volume_datacenters,
+            )
+        })
+}
+
+/// SSH is always exposed, the agent and checks depend on it
+const SSH_PORT: u16 = 22;
+
+/// Exposed when a container doesn't request any ports
+const DEFAULT_HTTP_PORT: u16 = 8080;
+
+/// The port specs RunPod exposes for a container, e.g. `["22/tcp", "8080/http"]`.
+/// SSH comes first, then the requested ports in order; without any requested
+/// ports the default HTTP port is exposed. Each port is only exposed once.
+/// RunPod can't expose a port privately, so ports requested with
+/// `public: false` aren't exposed at all and are only reachable over SSH.
+pub fn runpod_port_specs(requested: &[V1PortRequest]) -> Vec<String> {
+    let defaults = [V1PortRequest {
+        port: DEFAULT_HTTP_PORT,
+        protocol: Some("http".to_string()),
+        public: None,
+    }];
+    let requested = if requested.is_empty() {
+        &defaults[..]
+    } else {
+        requested
+    };
+
+    let mut seen = vec![SSH_PORT];
+    let mut specs = vec![format!("{}/tcp", SSH_PORT)];
+    for port in requested {
+        if seen.contains(&port.port) || port.public == Some(false) {
+            continue;
+        }
+        seen.push(port.port);
+        let protocol = match port.protocol.as_deref().map(|p| p.to_lowercase()) {
+            Some(p) if p == "http" || p == "https" => "http",
+            _ => "tcp",
+        };
+        specs.push(format!("{}/{}", port.port, protocol));
     }
+    specs
+}
+
+/// The port specs for a container's requested ports, see [`runpod_port_specs`].
+fn container_port_specs(model: &containers::Model) -> Vec<String> {
+    let requested = model.parse_ports().unwrap_or_else(|e| {
+        warn!(
+            "[Runpod Controller] Invalid ports for container {}, using defaults: {}",
+            model.id, e
+        );
+        None
+    });
+    runpod_port_specs(requested.as_deref().unwrap_or_default())
+}
+
+/// HTTP ports aren't published on the pod's IP; RunPod serves them through its
+/// proxy at the `hostname` `<pod id>-<port>.proxy.runpod.net` instead.
+fn proxied_http_ports(pod_id: &str, port_specs: &[String]) -> Vec<V1Port> {
+    port_specs
+        .iter()
+        .filter_map(|spec| spec.strip_suffix("/http")?.parse::<u16>().ok())
+        .map(|port| V1Port {
+            port,
+            protocol: Some("http".to_string()),
+            public_ip: None,
+            hostname: Some(format!("{}-{}.proxy.runpod.net", pod_id, port)),
+        })
+        .collect()
+}
+
+/// A GPU type RunPod offers, as much of it as picking one for a container needs
+#[derive(Debug, Clone)]
+pub struct GpuTypeSummary {
+    pub id: String,
+    pub display_name: String,
+    pub memory: String,
 }
 
 /// A `TrainingPlatform` implementation that schedules training jobs on RunPod.
 #[derive(Clone)]
 pub struct RunpodPlatform {
     runpod_client: RunpodClient,
+    /// For the GraphQL queries `runpod_client` doesn't cover
+    datacenters: DatacenterClient,
+    /// Shared with the rest of the server, see `utils::http`
+    http: reqwest::Client,
+    /// Watch a pod for one poll instead of until it ends, see `single_pass`
+    single_pass: bool,
 }
 
 impl RunpodPlatform {
@@ -60,18 +358,182 @@ impl RunpodPlatform {
         let api_key = std::env::var("RUNPOD_API_KEY")
             .expect("[Runpod Controller] Missing RUNPOD_API_KEY environment variable");
 
-        RunpodPlatform {
-            runpod_client: RunpodClient::new(api_key),
-        }
+        Self::with_api_key(api_key)
     }
 
     /// Create a new RunpodPlatform with a specific API key
     pub fn with_api_key(api_key: String) -> Self {
         RunpodPlatform {
-            runpod_client: RunpodClient::new(api_key),
+            runpod_client: RunpodClient::new(api_key.clone()),
+            datacenters: DatacenterClient::with_api_key(api_key),
+            http: shared_client(),
+            single_pass: false,
         }
     }
 
+    /// The datacenters holding the owner's network volume, where its pods
+    /// should go back to. Empty when RunPod can't be asked.
+    async fn owner_volume_datacenters(&self, owner: &str) -> Vec<String> {
+        let name = volume_name_for_owner(owner);
+        match self.datacenters.network_volumes().await {
+            Ok(volumes) => volumes
+                .into_iter()
+                .filter(|volume| volume.name == name)
+                .map(|volume| volume.data_center_id)
+                .collect(),
+            Err(e) => {
+                warn!(
+                    "[Runpod Controller] Failed to list network volumes of {}: {}",
+                    owner, e
+                );
+                Vec::new()
+            }
+        }
+    }
+
+    /// Makes `reconcile` take one step and return, polling a running pod once
+    /// rather than watching it until it ends. For `nebu daemon --once`.
+    pub fn single_pass(mut self) -> Self {
+        self.single_pass = true;
+        self
+    }
+
+    /// RunPod's GPU types, cached by `DatacenterClient` so bursts of
+    /// creations share one lookup.
+    async fn gpu_types(
+        &self,
+    ) -> Result<Vec<GpuTypeSummary>, Box<dyn std::error::Error + Send + Sync>> {
+        let gpu_types = self.datacenters.cached_gpu_types().await.map_err(|e| {
+            error!("[Runpod Controller] Error fetching GPU types: {}", e);
+            format!("Error fetching GPU types: {}", e)
+        })?;
+        Ok(gpu_types
+            .into_iter()
+            .map(|gpu_type| GpuTypeSummary {
+                memory: match gpu_type.memory_in_gb {
+                    Some(mem) => format!("{} GB", mem),
+                    None => "Unknown".to_string(),
+                },
+                display_name: gpu_type.display_name.unwrap_or_else(|| gpu_type.id.clone()),
+                id: gpu_type.id,
+            })
+            .collect())
+    }
+
+    /// Checks a request the way `declare` does before anything is stored.
+    fn validate_request(
+        &self,
+        config: &V1ContainerRequest,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        crate::validate::validate_image(&config.image)?;
+        if let Some(overrides) = &config.bootstrap {
+            bootstrap::validate(overrides)?;
+        }
+        if let Some(tailscale) = &config.tailscale {
+            validate_container_tailscale(tailscale)?;
+        }
+        if let Some(accelerators) = &config.accelerators {
+            let supported = self.accelerator_map();
+            for accelerator in accelerators {
+                crate::validate::validate_accelerator(accelerator, &supported)?;
+            }
+        }
+        // Destinations using variables or placeholders are checked once expanded, in `create`
+        for volume in config.volumes.iter().flatten() {
+            if !volume.dest.contains('$') && !volume.dest.contains('{') {
+                crate::validate::validate_volume_dest(&volume.dest)?;
+            }
+            let templated = |end: &str| end.contains('$') || end.contains('{');
+            if volume.compress && !templated(&volume.source) && !templated(&volume.dest) {
+                crate::validate::validate_volume_compression(
+                    &volume.source,
+                    &volume.dest,
+                    &volume.driver,
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    /// What creating `config` for `owner` would pick right now: the GPU and
+    /// datacenter `create` would choose and the going price. Nothing is stored
+    /// or launched.
+    pub async fn plan(
+        &self,
+        config: &V1ContainerRequest,
+        db: &DatabaseConnection,
+        owner: &str,
+        namespace: &str,
+    ) -> Result<V1ContainerPlan, Box<dyn std::error::Error + Send + Sync>> {
+        let config = &with_namespace_defaults(db, namespace, config).await?;
+        self.validate_request(config)?;
+
+        let volume_datacenters = self.owner_volume_datacenters(owner).await;
+        let Some(accelerators) = config.accelerators.as_ref().filter(|a| !a.is_empty()) else {
+            return Ok(V1ContainerPlan {
+                platform: "runpod".to_string(),
+                datacenter: Some(
+                    cpu_datacenter(&self.datacenters, owner, &volume_datacenters).await,
+                ),
+                ..Default::default()
+            });
+        };
+
+        let available_gpu_types: Vec<String> = self
+            .gpu_types()
+            .await?
+            .into_iter()
+            .map(|gpu_type| gpu_type.id)
+            .collect();
+        let selected =
+            select_accelerator(accelerators, &self.accelerator_map(), &available_gpu_types)
+                .ok_or("None of the requested accelerator types are available on RunPod")?;
+        let datacenters = self
+            .runpod_client
+            .find_datacenters_with_desired_gpu(&selected.gpu_type_id, selected.count)
+            .await
+            .map_err(|e| {
+                format!(
+                    "Failed to find datacenters for GPU {}: {}",
+                    selected.gpu_type_id, e
+                )
+            })?;
+        let datacenter = select_gpu_datacenter(
+            datacenters,
+            &selected.gpu_type_id,
+            owner,
+            &volume_datacenters,
+        )
+        .ok_or_else(|| {
+            format!(
+                "No datacenters found for GPU {} with storage support.",
+                selected.gpu_type_id
+            )
+        })?;
+
+        // A plan without a price is still useful, so pricing failures only warn
+        let price_per_gpu = match self.datacenters.cached_gpu_types().await {
+            Ok(pricing) => pricing
+                .iter()
+                .find(|p| p.id == selected.gpu_type_id)
+                .and_then(|p| p.price_per_hour()),
+            Err(e) => {
+                warn!("[Runpod Controller] Failed to fetch GPU pricing: {}", e);
+                None
+            }
+        };
+
+        Ok(V1ContainerPlan {
+            platform: "runpod".to_string(),
+            accelerator: Some(selected.accelerator),
+            accelerator_count: Some(selected.count),
+            gpu_type: Some(selected.gpu_type_id.clone()),
+            stock_status: datacenter_stock_status(&datacenter, &selected.gpu_type_id),
+            datacenter: Some(datacenter.id),
+            estimated_cost_per_hr: price_per_gpu.map(|price| price * selected.count as f64),
+        })
+    }
+
     /// Report metrics to OpenMeter for a running container
     async fn report_meters(
         &self,
@@ -95,26 +557,14 @@ impl RunpodPlatform {
             return;
         }
 
-        // Get OpenMeter configuration from environment
-        let openmeter_url = match std::env::var("OPENMETER_URL") {
-            Ok(url) => url,
-            Err(_) => {
-                error!("[Runpod Controller] OPENMETER_URL environment variable not set");
-                return;
-            }
-        };
-
-        let openmeter_token = match std::env::var("OPENMETER_TOKEN") {
-            Ok(token) => token,
-            Err(_) => {
-                error!("[Runpod Controller] OPENMETER_TOKEN environment variable not set");
+        let meter_client = match meter_client() {
+            Ok(client) => client,
+            Err(e) => {
+                error!("[Runpod Controller] {}", e);
                 return;
             }
         };
 
-        // Create OpenMeter client
-        let meter_client = openmeter::MeterClient::new(openmeter_url, openmeter_token);
-
         // Create and send events for each meter
         for meter in meters_vec {
             // Determine final cost using cost plus cost percentage if present.
@@ -212,26 +662,28 @@ impl RunpodPlatform {
             };
 
             // Create CloudEvent
-            let cloud_event = openmeter::CloudEvent {
-                id: event_id,
-                source: "nebulous-runpod-controller".to_string(),
-                specversion: "1.0".to_string(),
-                r#type: meter.metric.clone(),
-                subject: owner_id.clone(),
-                time: Some(chrono::Utc::now().to_rfc3339()),
-                dataschema: None,
-                datacontenttype: Some("application/json".to_string()),
-                data: Some(data),
-            };
+            let cloud_event = meter_event(
+                event_id,
+                meter_source("nebulous-runpod-controller"),
+                &owner_id,
+                &meter,
+                data,
+            );
 
             // Send the event to OpenMeter
-            match meter_client.ingest_events(&[cloud_event]).await {
-                Ok(_) => {
+            match METER_INGEST.ingest(meter_client, vec![cloud_event]).await {
+                Ok(Delivery::Sent) => {
                     debug!(
                         "[Runpod Controller] Successfully reported meter {:?} for container {}",
                         meter, container_id
                     );
                 }
+                Ok(Delivery::Spooled) => {
+                    debug!(
+                        "[Runpod Controller] OpenMeter breaker is open, spooled meter {:?} for container {}",
+                        meter, container_id
+                    );
+                }
                 Err(e) => {
                     error!(
                         "[Runpod Controller] Failed to report meter {:?} for container {}: {}",
@@ -242,9 +694,12 @@ impl RunpodPlatform {
         }
     }
 
+    /// The pod's public TCP ports, plus the proxy addresses of the HTTP ports
+    /// among `port_specs`.
     pub async fn get_public_ports_for_pod(
         &self,
         pod_id: &str,
+        port_specs: &[String],
     ) -> Result<Vec<V1Port>, Box<dyn std::error::Error + Send + Sync>> {
         // Fetch all pods (with their ports) from RunPod
         let pods_with_ports_data = self.runpod_client.fetch_my_pods_with_ports().await?;
@@ -263,7 +718,7 @@ impl RunpodPlatform {
             // The `runtime` field is optional, so check if it's present
             if let Some(runtime) = pod.runtime {
                 // Filter only the public ports, then map fields into our V1Port model
-                let public_ports: Vec<V1Port> = runtime
+                let mut public_ports: Vec<V1Port> = runtime
                     .ports
                     .into_iter()
                     .filter(|port| port.is_ip_public)
@@ -271,8 +726,10 @@ impl RunpodPlatform {
                         port: port.public_port as u16,
                         protocol: Some("tcp".to_string()),
                         public_ip: Some(port.ip),
+                        hostname: None,
                     })
                     .collect();
+                public_ports.extend(proxied_http_ports(pod_id, port_specs));
 
                 Ok(public_ports)
             } else {
@@ -285,6 +742,264 @@ impl RunpodPlatform {
         }
     }
 
+    /// Stop the container's pod without deleting it or its network volume, and mark
+    /// the container as Stopped.
+    async fn stop_container(
+        &self,
+        db: &DatabaseConnection,
+        container: &containers::Model,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        if let Some(pod_id) = &container.resource_name {
+            info!(
+                "[Runpod Controller] Stopping pod {} for container {}",
+                pod_id, container.id
+            );
+            self.runpod_client
+                .stop_pod(pod_id)
+                .await
+                .map_err(|e| format!("Failed to stop pod {}: {:?}", pod_id, e))?;
+        }
+
+        Mutation::update_container_status(
+            db,
+            container.id.clone(),
+            Some(ContainerStatus::Stopped.to_string()),
+            Some("Stopped by user".to_string()),
+            None,
+            None,
+            None,
+            None,
+            Some(false),
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Stop a running container so a higher priority container can take its
+    /// place in their queue. Its pod is removed and it waits in the queue again,
+    /// to be recreated once it is next in line.
+    async fn preempt_container(
+        &self,
+        db: &DatabaseConnection,
+        victim_id: &str,
+        preempted_by: &containers::Model,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let Some(victim) = Query::find_container_by_id(db, victim_id.to_string()).await? else {
+            return Ok(());
+        };
+        info!(
+            "[Runpod Controller] Preempting container {} for higher priority container {} in queue {:?}",
+            victim.id, preempted_by.id, victim.queue
+        );
+
+        // Requeue first, so the victim's watch lets go of the pod instead of
+        // reporting it stopped
+        Mutation::update_container_status(
+            db,
+            victim.id.clone(),
+            Some(ContainerStatus::Queued.to_string()),
+            Some(format!(
+                "Preempted by higher priority container {}",
+                preempted_by.id
+            )),
+            None,
+            None,
+            None,
+            None,
+            Some(false),
+        )
+        .await?;
+
+        if let Some(pod_id) = &victim.resource_name {
+            if let Err(e) = self.runpod_client.delete_pod(pod_id).await {
+                error!(
+                    "[Runpod Controller] Failed to remove pod {} of preempted container {}: {}",
+                    pod_id, victim.id, e
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Resume a stopped container's pod. If the pod can't be started again (e.g. its
+    /// GPU was reassigned), it is removed and the container recreated from Pending.
+    async fn resume_container(
+        &self,
+        db: &DatabaseConnection,
+        container: &containers::Model,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let resumed = match &container.resource_name {
+            Some(pod_id) => match self.runpod_client.start_pod(pod_id).await {
+                Ok(_) => {
+                    info!(
+                        "[Runpod Controller] Resumed pod {} for container {}",
+                        pod_id, container.id
+                    );
+                    true
+                }
+                Err(e) => {
+                    warn!(
+                        "[Runpod Controller] Could not resume pod {} for container {}, recreating: {:?}",
+                        pod_id, container.id, e
+                    );
+                    if let Err(e) = self.runpod_client.delete_pod(pod_id).await {
+                        error!(
+                            "[Runpod Controller] Failed to remove pod {}: {:?}",
+                            pod_id, e
+                        );
+                    }
+                    false
+                }
+            },
+            None => false,
+        };
+
+        let (status, message) = if resumed {
+            (ContainerStatus::Restarting, "Resuming stopped container")
+        } else {
+            (ContainerStatus::Pending, "Recreating stopped container")
+        };
+        Mutation::update_container_status(
+            db,
+            container.id.clone(),
+            Some(status.to_string()),
+            Some(message.to_string()),
+            None,
+            None,
+            None,
+            None,
+            Some(false),
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Apply the container's restart policy once its pod reached a terminal status.
+    /// On restart the old pod is removed and the container is put back to Pending,
+    /// with the backoff deadline recorded in `controller_data` for `reconcile`.
+    async fn handle_restart(
+        &self,
+        db: &DatabaseConnection,
+        container: &containers::Model,
+        pod_id: &str,
+        status: &ContainerStatus,
+    ) {
+        if container.desired_status == Some(ContainerStatus::Stopped.to_string()) {
+            debug!(
+                "[Runpod Controller] Container {} was stopped by the user; not restarting",
+                container.id
+            );
+            return;
+        }
+
+        let mut restart_state = container
+            .parse_restart_state()
+            .ok()
+            .flatten()
+            .unwrap_or_default();
+
+        match restart_decision(&container.restart, status, restart_state.attempts) {
+            RestartDecision::Restart { attempt, backoff } => {
+                info!(
+                    "[Runpod Controller] Restarting container {} ({} policy, attempt {}) in {:?}",
+                    container.id, container.restart, attempt, backoff
+                );
+
+                if let Err(e) = self.runpod_client.delete_pod(pod_id).await {
+                    error!(
+                        "[Runpod Controller] Failed to remove pod {} before restart: {}",
+                        pod_id, e
+                    );
+                }
+
+                restart_state.attempts = attempt;
+                restart_state.next_restart_at =
+                    Some(chrono::Utc::now().timestamp() + backoff.as_secs() as i64);
+                restart_state.last_status = Some(status.to_string());
+
+                if let Err(e) =
+                    Mutation::update_container_controller_data(db, container.id.clone(), |data| {
+                        data.restart = Some(restart_state)
+                    })
+                    .await
+                {
+                    error!(
+                        "[Runpod Controller] Failed to record restart attempt for container {}: {}",
+                        container.id, e
+                    );
+                    return;
+                }
+
+                if let Err(e) = Mutation::update_container_status(
+                    db,
+                    container.id.clone(),
+                    Some(ContainerStatus::Pending.to_string()),
+                    Some(format!(
+                        "Restarting after container {} (attempt {}), backing off {}s",
+                        status,
+                        attempt,
+                        backoff.as_secs()
+                    )),
+                    None,
+                    None,
+                    None,
+                    None,
+                    Some(false),
+                )
+                .await
+                {
+                    error!(
+                        "[Runpod Controller] Failed to mark container {} for restart: {}",
+                        container.id, e
+                    );
+                }
+            }
+            RestartDecision::Exhausted => {
+                warn!(
+                    "[Runpod Controller] Container {} reached the restart limit of {} attempts",
+                    container.id, restart_state.attempts
+                );
+                if let Err(e) = Mutation::update_container_status(
+                    db,
+                    container.id.clone(),
+                    None,
+                    Some(format!(
+                        "Container {} and reached the restart limit of {} attempts",
+                        status, restart_state.attempts
+                    )),
+                    None,
+                    None,
+                    None,
+                    None,
+                    None,
+                )
+                .await
+                {
+                    error!(
+                        "[Runpod Controller] Failed to update status for container {}: {}",
+                        container.id, e
+                    );
+                }
+            }
+            RestartDecision::NoRestart => {}
+        }
+    }
+
+    /// Remembers what the container's DNS record points at in its `controller_data`
+    async fn store_dns_record(db: &DatabaseConnection, container_id: &str, record: Option<String>) {
+        if let Err(e) =
+            Mutation::update_container_controller_data(db, container_id.to_string(), |data| {
+                data.dns_record = record
+            })
+            .await
+        {
+            warn!(
+                "[Runpod Controller] Failed to store DNS record of container {}: {}",
+                container_id, e
+            );
+        }
+    }
+
     /// Watch a pod and update its status in the database
     pub async fn watch(
         &self,
@@ -325,7 +1040,7 @@ impl RunpodPlatform {
         let mut container_start_time: Option<std::time::Instant> = None;
 
         // Get initial status from database
-        let (mut last_status, resource_name) =
+        let (mut last_status, resource_name, pod_state) =
             match crate::query::Query::find_container_by_id(db, container_id.clone().to_string())
                 .await
             {
@@ -357,19 +1072,26 @@ impl RunpodPlatform {
                             });
 
                     let resource_name = container.as_ref().and_then(|c| c.resource_name.clone());
+                    let pod_state = container
+                        .as_ref()
+                        .and_then(|c| c.typed_controller_data().ok())
+                        .and_then(|data| data.pod);
 
-                    (status, resource_name)
+                    (status, resource_name, pod_state)
                 }
                 Err(e) => {
                     error!(
                         "[Runpod Controller] Error fetching initial container from database: {}",
                         e
                     );
-                    (None, None)
+                    (None, None, None)
                 }
             };
 
         info!("[Runpod Controller] Resource name: {:?}", resource_name);
+        // Whether the current pod has been ready at any point, also in earlier watches
+        let mut ever_ready = pod_state.as_ref().is_some_and(|pod| pod.ever_ready);
+        let pod_created_at = pod_state.map(|pod| pod.created_at);
         // Use resource_name from database if available, otherwise use the provided pod_id
         let pod_id_to_watch = resource_name.clone().unwrap_or_default();
         info!(
@@ -380,10 +1102,38 @@ impl RunpodPlatform {
         let mut consecutive_errors = 0;
         const MAX_ERRORS: usize = 5;
 
+        let port_specs = container_port_specs(&container);
+
+        // Public DNS record for the container, when a zone is configured
+        let dns = DnsClient::from_server_config();
+        let dns_name = dns
+            .as_ref()
+            .map(|dns| dns.record_name(&container.namespace, &container.name));
+        let mut dns_registered: Option<String> = container
+            .typed_controller_data()
+            .ok()
+            .and_then(|data| data.dns_record);
+
+        let mut usage_sampler =
+            UsageSampler::new(crate::config::SERVER_CONFIG.usage_sample_interval);
+
         // Poll the pod status every 20 seconds
         let mut iteration_count = 0;
         loop {
+            // Only the iteration's API calls count against the limit, not the sleep
+            let permit = PLATFORM_OPERATIONS.acquire().await;
             iteration_count += 1;
+
+            // A preempted container is back in its queue and its pod is going away
+            if let Ok(Some(current)) = Query::find_container_by_id(db, container_id.clone()).await {
+                if QueueEntry::from(&current).status == Some(ContainerStatus::Queued) {
+                    info!(
+                        "[Runpod Controller] Container {} was requeued, no longer watching pod {}",
+                        container_id, pod_id_to_watch
+                    );
+                    break;
+                }
+            }
             debug!(
                 "[DEBUG:runpod.rs:watch] container={} iteration={}",
                 container_id.clone(),
@@ -429,7 +1179,10 @@ impl RunpodPlatform {
                             }
                         }
 
-                        let ports = match self.get_public_ports_for_pod(&pod_id_to_watch).await {
+                        let ports = match self
+                            .get_public_ports_for_pod(&pod_id_to_watch, &port_specs)
+                            .await
+                        {
                             Ok(p) => p,
                             Err(e) => {
                                 error!(
@@ -458,6 +1211,26 @@ impl RunpodPlatform {
                         };
                         debug!("[Runpod Controller] runpod_status: {:?}", runpod_status);
 
+                        // Accrue spend for the time since the previous poll while RunPod bills the pod
+                        let accrued = if runpod_status == ContainerStatus::Running {
+                            Mutation::accrue_container_cost(
+                                db,
+                                container_id.clone(),
+                                pod_info.cost_per_hr,
+                                chrono::Utc::now().timestamp(),
+                            )
+                            .await
+                            .map(|_| ())
+                        } else {
+                            Mutation::stop_container_cost_accrual(db, container_id.clone()).await
+                        };
+                        if let Err(e) = accrued {
+                            error!(
+                                "[Runpod Controller] Failed to record spend for container {}: {}",
+                                container_id, e
+                            );
+                        }
+
                         // --- SSH Accessibility Check ---
                         let is_ssh_accessible = match self.is_ssh_accessible(&container).await {
                             Ok(accessible) => accessible,
@@ -466,6 +1239,19 @@ impl RunpodPlatform {
                                 false
                             }
                         };
+                        if let Err(e) = Mutation::record_container_ssh_check(
+                            db,
+                            container_id.clone(),
+                            is_ssh_accessible,
+                            chrono::Utc::now().timestamp(),
+                        )
+                        .await
+                        {
+                            error!(
+                                "[Runpod Controller] Failed to record SSH check for container {}: {}",
+                                container_id, e
+                            );
+                        }
 
                         let final_status: ContainerStatus;
 
@@ -509,9 +1295,95 @@ impl RunpodPlatform {
                                         is_ready = false; // Error parsing, assume not ready
                                     }
                                 }
-                            } else {
-                                // If Runpod status is not Running (e.g., Created, Pending), it's not ready yet.
-                                is_ready = false;
+                            } else {
+                                // If Runpod status is not Running (e.g., Created, Pending), it's not ready yet.
+                                is_ready = false;
+                            }
+                        }
+
+                        // Containers with a health check get their readiness written by
+                        // perform_health_check, so read it back
+                        let observed_ready = if is_ready || final_status != ContainerStatus::Running
+                        {
+                            is_ready
+                        } else {
+                            Query::find_container_by_id(db, container_id.clone())
+                                .await
+                                .ok()
+                                .flatten()
+                                .and_then(|c| c.parse_status().ok().flatten())
+                                .and_then(|s| s.ready)
+                                .unwrap_or(false)
+                        };
+                        if observed_ready && !ever_ready {
+                            ever_ready = true;
+                            if let Err(e) = Mutation::update_container_controller_data(
+                                db,
+                                container_id.clone(),
+                                |data| {
+                                    // Pods created before this was tracked count from the container
+                                    data.pod
+                                        .get_or_insert_with(|| V1PodState {
+                                            created_at: container.created_at.timestamp(),
+                                            ever_ready: true,
+                                        })
+                                        .ever_ready = true;
+                                },
+                            )
+                            .await
+                            {
+                                warn!(
+                                    "[Runpod Controller] Failed to record that container {} became ready: {}",
+                                    container_id, e
+                                );
+                            }
+                        }
+
+                        // The runtime timeout below only starts once the container is
+                        // ready, so one that never gets there is held to a deadline
+                        // counted from its creation instead
+                        if let Some((timeout, timeout_str)) = &timeout_duration {
+                            if !ever_ready
+                                && container_start_time.is_none()
+                                && !final_status.is_inactive()
+                                && never_ready_timeout_exceeded(
+                                    container.created_at.timestamp(),
+                                    pod_created_at,
+                                    chrono::Utc::now().timestamp(),
+                                    *timeout,
+                                )
+                            {
+                                info!(
+                                    "[Runpod Controller] Container {} did not become ready within its timeout of {:?}, terminating",
+                                    container_id, timeout
+                                );
+                                if let Err(del_err) = self.delete(&container_id, db).await {
+                                    error!(
+                                        "[Runpod Controller] Error deleting never-ready container {}: {}",
+                                        container_id, del_err
+                                    );
+                                } else if let Err(e) = Mutation::update_container_status(
+                                    db,
+                                    container_id.clone(),
+                                    Some(ContainerStatus::Failed.to_string()),
+                                    Some(format!(
+                                        "Container terminated after not becoming ready within timeout of {}",
+                                        timeout_str
+                                    )),
+                                    None,
+                                    None,
+                                    None,
+                                    None,
+                                    Some(false),
+                                )
+                                .await
+                                {
+                                    error!(
+                                        "[Runpod Controller] Failed to update status for never-ready container: {}",
+                                        e
+                                    );
+                                }
+                                break;
                             }
                         }
 
@@ -598,6 +1470,14 @@ impl RunpodPlatform {
                                     );
                                 }
                             };
+                            if usage_sampler.due(std::time::Instant::now()) {
+                                if let Err(e) = self.sample_usage(&container, db).await {
+                                    warn!(
+                                        "[Runpod Controller] Failed to sample usage of container {}: {}",
+                                        container_id, e
+                                    );
+                                }
+                            }
                             info!(
                                 "[Runpod Controller] container.restart={}",
                                 container.restart
@@ -607,17 +1487,10 @@ impl RunpodPlatform {
                             {
                                 info!("[Runpod Controller] checking for /done.txt");
                                 match self
-                                    .check_done_file(
-                                        &container_id,
-                                        &container
-                                            .container_user
-                                            .clone()
-                                            .unwrap_or("root".to_string()),
-                                        db,
-                                    )
+                                    .check_done_file(&container_id, &exec_user(&container), db)
                                     .await
                                 {
-                                    Ok(true) => {
+                                    Ok(bootstrap::DoneFile::Done) => {
                                         info!(
                                     "[Runpod Controller] /done.txt found for container {} -> deleting container",
                                     container_id
@@ -631,7 +1504,43 @@ impl RunpodPlatform {
                                         // Once deleted, no further watch is needed
                                         break;
                                     }
-                                    Ok(false) => {
+                                    Ok(bootstrap::DoneFile::SyncFailed) => {
+                                        error!(
+                                            "[Runpod Controller] Final sync failed for container {} -> marking failed",
+                                            container_id
+                                        );
+                                        self.record_failure_reason(&container_id, None, db).await;
+                                        if let Err(del_err) = self.delete(&container_id, db).await {
+                                            error!(
+                                                "[Runpod Controller] Error deleting container {}: {}",
+                                                container_id, del_err
+                                            );
+                                        } else if let Err(e) = Mutation::update_container_status(
+                                            db,
+                                            container_id.clone(),
+                                            Some(ContainerStatus::Failed.to_string()),
+                                            Some(format!(
+                                                "Final volume sync did not complete within {}s",
+                                                crate::config::SERVER_CONFIG
+                                                    .sync_wait_timeout
+                                                    .as_secs()
+                                            )),
+                                            None,
+                                            None,
+                                            None,
+                                            None,
+                                            None,
+                                        )
+                                        .await
+                                        {
+                                            error!(
+                                                "[Runpod Controller] Failed to update status for container {}: {}",
+                                                container_id, e
+                                            );
+                                        }
+                                        break;
+                                    }
+                                    Ok(bootstrap::DoneFile::Missing) => {
                                         // Not done yet, keep going
                                     }
                                     Err(check_err) => {
@@ -677,6 +1586,43 @@ impl RunpodPlatform {
                         );
                         info!("[Runpod Controller] Calculated readiness: {}", is_ready);
 
+                        if let (Some(dns), Some(fqdn)) = (&dns, &dns_name) {
+                            let public_ip = ports.iter().find_map(|p| p.public_ip.clone());
+                            match dns_action(
+                                dns_registered.as_deref(),
+                                &final_status,
+                                observed_ready,
+                                public_ip.as_deref(),
+                            ) {
+                                DnsAction::Upsert(ip) => match dns.upsert(fqdn, &ip).await {
+                                    Ok(()) => {
+                                        dns_registered = Some(ip);
+                                        Self::store_dns_record(
+                                            db,
+                                            &container_id,
+                                            dns_registered.clone(),
+                                        )
+                                        .await;
+                                    }
+                                    Err(e) => error!(
+                                        "[Runpod Controller] Failed to register DNS record {} for container {}: {}",
+                                        fqdn, container_id, e
+                                    ),
+                                },
+                                DnsAction::Delete => match dns.delete(fqdn).await {
+                                    Ok(()) => {
+                                        dns_registered = None;
+                                        Self::store_dns_record(db, &container_id, None).await;
+                                    }
+                                    Err(e) => error!(
+                                        "[Runpod Controller] Failed to remove DNS record {} for container {}: {}",
+                                        fqdn, container_id, e
+                                    ),
+                                },
+                                DnsAction::Keep => {}
+                            }
+                        }
+
                         // If status changed, update the database
                         // Also update if readiness changed but status didn't (edge case?)
                         // TODO: Check if readiness needs its own tracking like last_status
@@ -731,6 +1677,25 @@ impl RunpodPlatform {
                                     "[Runpod Controller] Pod {:?} reached terminal state: {}",
                                     resource_name, final_status
                                 );
+                                if matches!(
+                                    final_status,
+                                    ContainerStatus::Failed | ContainerStatus::Exited
+                                ) {
+                                    // Before a restart removes the pod and its logs
+                                    self.record_failure_reason(
+                                        &container_id,
+                                        Some(&pod_id_to_watch),
+                                        db,
+                                    )
+                                    .await;
+                                }
+                                self.handle_restart(
+                                    db,
+                                    &container,
+                                    &pod_id_to_watch,
+                                    &final_status,
+                                )
+                                .await;
                                 break;
                             }
                         }
@@ -828,6 +1793,15 @@ impl RunpodPlatform {
                     match crate::query::Query::find_container_by_id(db, container_id.to_string())
                         .await
                     {
+                        Ok(Some(current))
+                            if QueueEntry::from(&current).status
+                                == Some(ContainerStatus::Queued) =>
+                        {
+                            info!(
+                                "[Runpod Controller] Container {} was preempted and requeued",
+                                container_id
+                            );
+                        }
                         Ok(Some(_container)) => {
                             // If the container row is still there, mark it "Stopped" or "Deleted"
                             error!(
@@ -905,10 +1879,14 @@ impl RunpodPlatform {
                     }
                 }
             }
+            if self.single_pass {
+                break;
+            }
             debug!(
                 "[DEBUG:runpod.rs:watch] container={} iteration={} sleeping 20s",
                 container_id, iteration_count
             );
+            drop(permit);
             // Wait before checking again
             tokio::time::sleep(duration).await;
         }
@@ -931,10 +1909,7 @@ impl RunpodPlatform {
             None => self.get_tailscale_device_name(container).await,
         };
 
-        let user = container
-            .container_user
-            .clone()
-            .unwrap_or("root".to_string());
+        let user = exec_user(container);
         let cmd = "echo 'SSH connection test'";
 
         info!(
@@ -943,66 +1918,49 @@ impl RunpodPlatform {
             user
         );
 
-        let hostname_for_ssh = hostname.clone();
-        // Set a short timeout for the SSH check (5 seconds)
-        match tokio::time::timeout(
-            std::time::Duration::from_secs(5),
-            tokio::task::spawn_blocking(move || {
-                run_ssh_command_ts(
-                    &hostname_for_ssh,
-                    cmd.split_whitespace().map(|s| s.to_string()).collect(),
-                    false,
-                    false,
-                    Some(&user),
-                )
-            }),
+        let config = &crate::config::SERVER_CONFIG;
+        let result = check_with_retries(
+            config.ssh_check_attempts,
+            config.ssh_check_timeout,
+            SSH_CHECK_RETRY_DELAY,
+            || {
+                let hostname = hostname.clone();
+                let user = user.clone();
+                async move {
+                    run_ssh_command_async(
+                        &hostname,
+                        cmd.split_whitespace().map(|s| s.to_string()).collect(),
+                        Some(&user),
+                    )
+                    .await
+                    .map(|_| ())
+                    .map_err(|e| e.to_string())
+                }
+            },
         )
-        .await
-        {
-            Ok(Ok(_)) => {
+        .await;
+
+        match result {
+            Ok(attempt) => {
                 info!(
-                    "[Runpod Controller] SSH connection successful to {}",
-                    hostname
+                    "[Runpod Controller] SSH connection successful to {} (attempt {})",
+                    hostname, attempt
                 );
                 Ok(true)
             }
-            Ok(Err(e)) => {
-                info!(
-                    "[Runpod Controller] SSH connection failed to {}: {}",
-                    hostname, e
-                );
-                Ok(false)
-            }
-            Err(_) => {
+            Err(e) => {
                 info!(
-                    "[Runpod Controller] SSH connection timed out to {}",
-                    hostname
+                    "[Runpod Controller] SSH connection to {} failed after {} attempt(s): {}",
+                    hostname, config.ssh_check_attempts, e
                 );
                 Ok(false)
             }
         }
     }
 
-    // A helper for substituting both $VAR and ${VAR} with values from env_map
-    fn expand_variables(&self, input: &str, env_map: &HashMap<String, String>) -> String {
-        let re = Regex::new(r"\$([A-Za-z0-9_]+)|\$\{([A-Za-z0-9_]+)\}").unwrap();
-        re.replace_all(input, |caps: &regex::Captures| {
-            // The capture groups are 1 and 2 respectively for $VAR or ${VAR}
-            if let Some(key) = caps.get(1) {
-                // If the user wrote something like $VAR
-                env_map.get(key.as_str()).cloned().unwrap_or_default()
-            } else if let Some(key) = caps.get(2) {
-                // If the user wrote something like ${VAR}
-                env_map.get(key.as_str()).cloned().unwrap_or_default()
-            } else {
-                String::new()
-            }
-        })
-        .to_string()
-    }
-
     async fn determine_volumes_config(
         &self,
+        id: &str,
         name: &str,
         namespace: &str,
         model: Vec<V1VolumePath>,
@@ -1015,9 +1973,22 @@ impl RunpodPlatform {
         let cache_dir = "/nebu/cache".to_string();
 
         for path in model {
-            // Expand environment variables in source/dest prior to rewriting
-            let expanded_source = self.expand_variables(&path.source, env_map);
-            let expanded_dest = self.expand_variables(&path.dest, env_map);
+            // Expand metadata placeholders, then environment variables, prior to rewriting
+            let placeholders = VolumePlaceholders {
+                id,
+                name,
+                namespace,
+                owner,
+            };
+            let expanded_source = expand_variables(&placeholders.expand(&path.source), env_map);
+            let expanded_dest = expand_variables(&placeholders.expand(&path.dest), env_map);
+            if path.compress {
+                crate::validate::validate_volume_compression(
+                    &expanded_source,
+                    &expanded_dest,
+                    &path.driver,
+                )?;
+            }
 
             debug!("[Runpod Controller] Expanded source: {}", expanded_source);
             debug!("[Runpod Controller] Expanded dest: {}", expanded_dest);
@@ -1029,72 +2000,9 @@ impl RunpodPlatform {
                 && !expanded_dest.starts_with("nebu://");
 
             let final_dest = if expanded_dest.starts_with("nebu://") {
-                // Extract the volume name and remaining path from nebu://{volume}/rest/of/path
-                let path_without_prefix = expanded_dest.strip_prefix("nebu://").unwrap_or("");
-                let mut parts = path_without_prefix.splitn(2, '/');
-
-                let volume_name = match parts.next() {
-                    Some(volume_name) => volume_name,
-                    None => {
-                        error!(
-                            "[Runpod Controller] Failed to parse nebu:// path: {}",
-                            expanded_dest
-                        );
-                        return Err(anyhow::anyhow!(
-                            "[Runpod Controller] Failed to parse nebu:// path: {}",
-                            expanded_dest
-                        ));
-                    }
-                };
-                debug!("[Runpod Controller] Volume name: {}", volume_name);
-                let remaining_path = parts.next().unwrap_or("");
-
-                debug!(
-                    "[Runpod Controller] Parsed nebu:// path - volume: {}, remaining: {} with owners: {} and namespace: {}",
-                    volume_name, remaining_path, owner, namespace
-                );
-
-                // Query for the volume
-                match Query::find_volume_by_namespace_name_and_owners(
-                    db,
-                    namespace,
-                    volume_name,
-                    &[owner], // Use the container's owner for authorization
-                )
-                .await
-                {
-                    Ok(volume) => {
-                        // Combine the volume's source with the remaining path
-                        let final_path = if remaining_path.is_empty() {
-                            volume.source
-                        } else {
-                            format!(
-                                "{}/{}",
-                                volume.source.trim_end_matches('/'),
-                                remaining_path.trim_start_matches('/')
-                            )
-                        };
-
-                        debug!(
-                            "[Runpod Controller] Resolved nebu:// path to: {}",
-                            final_path
-                        );
-                        final_path
-                    }
-                    Err(e) => {
-                        error!(
-                            "[Runpod Controller] Failed to find volume '{}' in namespace '{}' with owners '{}': {}",
-                            volume_name, namespace, owner, e
-                        );
-                        return Err(anyhow::anyhow!(
-                            "[Runpod Controller] Failed to find volume '{}' in namespace '{}': {}",
-                            volume_name,
-                            namespace,
-                            e
-                        ));
-                    }
-                }
+                resolve_nebu_path(db, namespace, owner, &expanded_dest).await?
             } else if is_local_destination {
+                crate::validate::validate_volume_dest(&expanded_dest)?;
                 // For local paths, we'll sync to cache directory instead
                 let path_without_leading_slash = expanded_dest.trim_start_matches('/');
                 let cache_path = format!("{}/{}", cache_dir, path_without_leading_slash);
@@ -1110,12 +2018,21 @@ impl RunpodPlatform {
                 expanded_dest
             };
 
+            // A nebu:// source is read from the volume's backing storage
+            let final_source = if expanded_source.starts_with("nebu://") {
+                resolve_nebu_path(db, namespace, owner, &expanded_source).await?
+            } else {
+                expanded_source
+            };
+
             let volume_path = VolumePath {
-                source: expanded_source,
+                source: final_source,
                 dest: final_dest,
                 resync: path.resync,
                 continuous: path.continuous,
                 driver: path.driver,
+                checksum: path.checksum,
+                compress: path.compress,
             };
             volume_paths.push(volume_path);
         }
@@ -1148,22 +2065,7 @@ impl RunpodPlatform {
         .await?;
 
         info!("[Runpod Controller] Using name: {}", model.name);
-        let gpu_types_response = match self.runpod_client.list_gpu_types_graphql().await {
-            Ok(response) => response,
-            Err(e) => {
-                error!("[Runpod Controller] Error fetching GPU types: {:?}", e);
-
-                // More detailed error information
-                if let Some(status) = e.status() {
-                    error!("[Runpod Controller] HTTP Status: {}", status);
-                }
-
-                return Err(Box::<dyn std::error::Error + Send + Sync>::from(format!(
-                    "Error fetching GPU types: {:?}",
-                    e
-                )));
-            }
-        };
+        let gpu_types = self.gpu_types().await?;
 
         let mut runpod_gpu_type_id: String = "NVIDIA_TESLA_T4".to_string(); // Default value
         let mut nebu_gpu_type_id: String = "NVIDIA_TESLA_T4".to_string(); // Default value
@@ -1171,91 +2073,41 @@ impl RunpodPlatform {
         let mut _datacenter_id = String::from("US"); // Default value
         let mut available_gpu_types = Vec::new();
 
-        // Extract available GPU types from the response
-        if let Some(data) = &gpu_types_response.data {
-            // Log the available GPU types
-            for gpu_type in data {
-                available_gpu_types.push(gpu_type.id.clone());
-
-                // Log GPU type details
-                let memory_str = match gpu_type.memory_in_gb {
-                    Some(mem) => format!("{} GB", mem),
-                    None => "Unknown".to_string(),
-                };
-
-                info!(
-                    "[Runpod Controller] GPU Type: {}, Display Name: {}, Memory: {}",
-                    gpu_type.id, gpu_type.display_name, memory_str
-                );
-            }
-            info!(
-                "[Runpod Controller] Available GPU types: {:?}",
-                available_gpu_types
+        // Collect the available GPU types
+        for gpu_type in &gpu_types {
+            available_gpu_types.push(gpu_type.id.clone());
+            debug!(
+                "[Runpod Controller] GPU Type: {}, Display Name: {}, Memory: {}",
+                gpu_type.id, gpu_type.display_name, gpu_type.memory
             );
         }
         info!(
-            "[Runpod Controller] GPU types response: {:?}",
-            gpu_types_response
+            "[Runpod Controller] Available GPU types: {:?}",
+            available_gpu_types
         );
 
         // Parse accelerators if provided
-        if let Some(accelerators) = &model.accelerators {
-            if !accelerators.is_empty() {
-                let mut found_valid_accelerator = false;
-
-                // Try each accelerator in the list until we find one that works
-                for accelerator in accelerators {
-                    // Parse the accelerator (format: "count:type")
-                    info!("[Runpod Controller] Accelerator: {}", accelerator);
-                    let parts: Vec<&str> = accelerator.split(':').collect();
-                    if parts.len() == 2 {
-                        if let Ok(count) = parts[0].parse::<i32>() {
-                            // Convert from our accelerator name to RunPod's GPU type ID
-                            if let Some(runpod_gpu_name) = self.accelerator_map().get(parts[1]) {
-                                info!("[Runpod Controller] RunPod GPU name: {}", runpod_gpu_name);
-                                // Check if this GPU type is available on RunPod
-                                if available_gpu_types.is_empty()
-                                    || available_gpu_types.contains(runpod_gpu_name)
-                                {
-                                    // This accelerator is available, use it
-                                    requested_gpu_count = count;
-                                    runpod_gpu_type_id = runpod_gpu_name.clone();
-                                    nebu_gpu_type_id = parts[1].to_string();
-                                    found_valid_accelerator = true;
-
-                                    info!(
-                                        "[Runpod Controller] Using accelerator: {} (count: {})",
-                                        runpod_gpu_type_id, requested_gpu_count
-                                    );
-
-                                    // We found a valid accelerator, stop looking
-                                    break;
-                                } else {
-                                    info!(
-                                        "[Runpod Controller] Accelerator type '{}' is not available, trying next option",
-                                        runpod_gpu_name
-                                    );
-                                }
-                            } else {
-                                info!(
-                                    "[Runpod Controller] Unknown accelerator type: {}, trying next option",
-                                    parts[1]
-                                );
-                            }
-                        }
-                    }
+        if let Some(accelerators) = model.accelerators.as_ref().filter(|a| !a.is_empty()) {
+            match select_accelerator(accelerators, &self.accelerator_map(), &available_gpu_types) {
+                Some(selected) => {
+                    info!(
+                        "[Runpod Controller] Using accelerator: {} (count: {})",
+                        selected.gpu_type_id, selected.count
+                    );
+                    requested_gpu_count = selected.count;
+                    runpod_gpu_type_id = selected.gpu_type_id;
+                    nebu_gpu_type_id = selected.accelerator;
                 }
-
-                // If we couldn't find any valid accelerator, return an error
-                if !found_valid_accelerator {
+                None => {
                     error!(
                         "[Runpod Controller] None of the requested accelerator types are available. Available types: {:?}",
                         available_gpu_types
                     );
-                    return Err(Box::<dyn std::error::Error + Send + Sync>::from(
+                    return Err(PlacementFailed(
                         "None of the requested accelerator types are available on RunPod"
                             .to_string(),
-                    ));
+                    )
+                    .into());
                 }
             }
         }
@@ -1331,72 +2183,69 @@ impl RunpodPlatform {
             "[Runpod Controller] Getting container default user for image: {}",
             model.image
         );
+        let registry = registry_auth::image_pull_auth(
+            db,
+            Some(&self.datacenters),
+            &model.namespace,
+            &model.image,
+        )
+        .await?;
+
         // A "match" to catch errors, log them, and possibly do something else
-        let (_parsed_manifest, container_user) = match pull_and_parse_config(&model.image).await {
-            Ok((parsed_manifest, container_user)) => (parsed_manifest, container_user),
-            Err(err) => {
-                error!(
-                    "[Runpod Controller] Failed pulling/parsing config for image '{}': {:#}",
-                    model.image, err
-                );
-                // We can either choose to return immediately with Err or
-                // fallback to defaults. For now, just return the error:
-                return Err(err);
-            }
-        };
+        let (_parsed_manifest, container_user) =
+            match pull_and_parse_config(&model.image, &registry.oci_auth()).await {
+                Ok((parsed_manifest, container_user)) => (parsed_manifest, container_user),
+                Err(err) => {
+                    error!(
+                        "[Runpod Controller] Failed pulling/parsing config for image '{}': {:#}",
+                        model.image, err
+                    );
+                    // We can either choose to return immediately with Err or
+                    // fallback to defaults. For now, just return the error:
+                    return Err(err);
+                }
+            };
 
         debug!(
             "[Runpod Controller] Container default user from OCI config: {}",
             container_user
         );
 
-        // If you want a graceful fallback to 'root' in case container_user is empty:
-        let final_user = if container_user.is_empty() {
-            "root".to_string()
-        } else {
-            container_user.clone()
-        };
+        // The image's user wins, then the fallback recorded when the container was declared
+        let final_user = resolve_container_user([
+            Some(container_user.as_str()),
+            model.container_user.as_deref(),
+        ]);
         Mutation::update_container_user(db, model.id.clone(), Some(final_user)).await?;
 
         match model.parse_env() {
             Ok(Some(env)) => {
                 // We have a valid, non-empty list of environment variables.
-                for env_var in env {
-                    let value = match env_var.secret_name {
-                        Some(secret_name) => {
-                            let secret_model =
-                                match crate::query::Query::find_secret_by_namespace_and_name(
-                                    db,
-                                    &model.namespace,
-                                    &secret_name,
-                                )
-                                .await?
-                                {
-                                    Some(secret) => secret,
-                                    None => {
-                                        error!(
-                                            "[Runpod Controller] Secret not found: {}",
-                                            secret_name
-                                        );
-                                        continue;
-                                    }
-                                };
-                            secret_model.decrypt_value().ok()
-                        }
-                        None => env_var.value.clone(),
-                    };
-
-                    if value.is_none() {
+                let secrets = lookup_env_secrets(db, &model.namespace, &env).await?;
+                let resolved = match resolve_env(&env, &secrets) {
+                    Ok(resolved) => resolved,
+                    Err(e) => {
                         error!(
-                            "[Runpod Controller] Failed to find value for key: {}",
-                            env_var.key
+                            "[Runpod Controller] Not creating container {}: {}",
+                            model.id, e
                         );
-                        continue;
+                        Mutation::update_container_status(
+                            db,
+                            model.id.clone(),
+                            Some(ContainerStatus::Failed.to_string()),
+                            Some(e.to_string()),
+                            None,
+                            None,
+                            None,
+                            None,
+                            None,
+                        )
+                        .await?;
+                        return Err(e.into());
                     }
-                    env_vec.push(runpod::EnvVar {
-                        key: env_var.key.clone(),
-                        value: value.unwrap(),
-                    });
+                };
+                for (key, value) in resolved {
+                    env_vec.push(runpod::EnvVar { key, value });
                 }
                 info!("[Runpod Controller] Successfully parsed and added environment variables from model");
             }
@@ -1431,6 +2280,7 @@ impl RunpodPlatform {
                 debug!("[Runpod Controller] Owner: {}", model.owner);
                 let volume_config = match self
                     .determine_volumes_config(
+                        &model.id,
                         &model.name,
                         &model.namespace,
                         volumes,
@@ -1441,11 +2291,43 @@ impl RunpodPlatform {
                     .await
                 {
                     Ok(volume_config) => volume_config,
+                    Err(e) if !is_invalid_volume(&e) => {
+                        warn!(
+                            "[Runpod Controller] Failed to look up volumes of {}, retrying: {:#}",
+                            model.id, e
+                        );
+                        // Back to pending so the next reconcile creates it again
+                        Mutation::update_container_status(
+                            db,
+                            model.id.clone(),
+                            Some(ContainerStatus::Pending.to_string()),
+                            Some(format!("Failed to look up volumes, retrying: {:#}", e)),
+                            None,
+                            None,
+                            None,
+                            None,
+                            None,
+                        )
+                        .await?;
+                        return Err(e.into());
+                    }
                     Err(e) => {
                         error!(
                             "[Runpod Controller] Failed to determine volumes config: {}",
                             e
                         );
+                        Mutation::update_container_status(
+                            db,
+                            model.id.clone(),
+                            Some(ContainerStatus::Failed.to_string()),
+                            Some(format!("Invalid volumes: {}", e)),
+                            None,
+                            None,
+                            None,
+                            None,
+                            None,
+                        )
+                        .await?;
                         return Err(e.into());
                     }
                 };
@@ -1486,9 +2368,37 @@ impl RunpodPlatform {
         let hostname = self.get_tailscale_device_name(&model).await;
         info!("[Runpod Controller] Hostname: {}", hostname);
 
-        let docker_command = self.build_command(&model, &hostname);
+        let env_vars = model.parse_env().ok().flatten().unwrap_or_default();
+        let command = expanded_command(
+            model.command.as_deref(),
+            model.args.as_deref(),
+            &command_env(&env_map, &env_vars),
+        );
+        let authorized_keys = match ssh_keys::container_authorized_keys(db, &model).await {
+            Ok(keys) => keys,
+            Err(e) => {
+                error!("[Runpod Controller] Failed to resolve SSH keys: {}", e);
+                Mutation::update_container_status(
+                    db,
+                    model.id.clone(),
+                    Some(ContainerStatus::Failed.to_string()),
+                    Some(format!("Invalid ssh_keys: {}", e)),
+                    None,
+                    None,
+                    None,
+                    None,
+                    None,
+                )
+                .await?;
+                return Err(e.into());
+            }
+        };
+        let docker_command = self.build_command(&model, command, &hostname, authorized_keys);
         info!("[Runpod Controller] Docker command: {:?}", docker_command);
 
+        // Going back to the owner's network volume keeps its cache and doesn't
+        // leave it behind for another one
+        let volume_datacenters = self.owner_volume_datacenters(&model.owner).await;
         let datacenter_id = if model.accelerators.is_some()
             && !model.accelerators.as_ref().unwrap().is_empty()
         {
@@ -1514,84 +2424,37 @@ impl RunpodPlatform {
                     runpod_gpu_type_id
                 );
 
-            let mut suitable_datacenters: Vec<runpod::DataCenterItem> = all_datacenters
-                .into_iter()
-                .filter(|dc| dc.storageSupport) // MUST have storage support (it's a bool)
-                .collect();
-
-            if suitable_datacenters.is_empty() {
-                let error_msg = format!(
-                    "No datacenters found for GPU {} with storage support.",
-                    runpod_gpu_type_id
-                );
-                error!("[Runpod Controller] {}", error_msg);
-                return Err(Box::new(std::io::Error::new(
-                    std::io::ErrorKind::NotFound,
-                    error_msg,
-                )));
-            }
-
-            info!(
-                    "[Runpod Controller] Found {} datacenters for GPU {} with storage support. Sorting by preference (Location > Stock > ID)...",
-                    suitable_datacenters.len(),
-                    runpod_gpu_type_id
-                );
-
-            suitable_datacenters.sort_by(|a, b| {
-                // Primary: Location (US/EU > Canada > Others)
-                let loc_a = location_preference(&a.location); // Pass as &str
-                let loc_b = location_preference(&b.location); // Pass as &str
-                if loc_a != loc_b {
-                    return loc_a.cmp(&loc_b);
-                }
-
-                // Secondary: GPU Stock Status for the requested GPU type
-                let get_stock_status = |dc: &runpod::DataCenterItem| -> Option<String> {
-                    dc.gpu_availability // Use Rust field name: gpu_availability (Vec)
-                        .iter()
-                        .find(|gpu_item| gpu_item.gpuTypeId.as_deref() == Some(&runpod_gpu_type_id)) // Use .gpuTypeId
-                        .and_then(|item| item.stockStatus.clone()) // Use .stockStatus
-                };
-
-                let stock_pref_a = stock_status_preference(&get_stock_status(a));
-                let stock_pref_b = stock_status_preference(&get_stock_status(b));
-                if stock_pref_a != stock_pref_b {
-                    return stock_pref_a.cmp(&stock_pref_b);
-                }
-
-                // Tertiary (Tie-breaker): Datacenter ID (alphabetical)
-                a.id.cmp(&b.id)
-            });
-
-            let selected_dc = suitable_datacenters.first().ok_or_else(|| {
-                    let msg = format!(
-                        "No suitable datacenters remained after sorting for GPU {} (with storage, preferred location/stock).",
-                        runpod_gpu_type_id
-                    );
-                    error!("[Runpod Controller] {}", msg);
-                    Box::new(std::io::Error::new(std::io::ErrorKind::NotFound, msg)) as Box<dyn std::error::Error + Send + Sync>
-                })?;
+            let selected_dc = select_gpu_datacenter(
+                all_datacenters,
+                &runpod_gpu_type_id,
+                &model.owner,
+                &volume_datacenters,
+            )
+            .ok_or_else(|| {
+                let msg = format!(
+                    "No datacenters found for GPU {} with storage support.",
+                    runpod_gpu_type_id
+                );
+                error!("[Runpod Controller] {}", msg);
+                Box::new(std::io::Error::new(std::io::ErrorKind::NotFound, msg))
+                    as Box<dyn std::error::Error + Send + Sync>
+            })?;
 
             info!(
-                    "[Runpod Controller] Selected Datacenter: ID='{}', Location='{}', Storage={}, GPU Stock for '{}': {:?}",
-                    selected_dc.id,
-                    &selected_dc.location, // Log as &str
-                    selected_dc.storageSupport, // Direct bool
-                    runpod_gpu_type_id,
-                    selected_dc.gpu_availability // Use Rust field name: gpu_availability (Vec)
-                        .iter()
-                        .find(|gpu_item| gpu_item.gpuTypeId.as_deref() == Some(&runpod_gpu_type_id)) // Use .gpuTypeId
-                        .and_then(|item| item.stockStatus.as_deref()) // Use .stockStatus
-                        .unwrap_or("N/A")
-                );
+                "[Runpod Controller] Selected Datacenter: ID='{}', Location='{}', Storage={}, GPU Stock for '{}': {:?}",
+                selected_dc.id,
+                &selected_dc.location,
+                selected_dc.storageSupport,
+                runpod_gpu_type_id,
+                datacenter_stock_status(&selected_dc, &runpod_gpu_type_id).unwrap_or_default()
+            );
             selected_dc.id.clone()
         } else {
-            // For CPU-only workloads, default to EU-RO-1.
-            // Based on logs provided by user, EU-RO-1 has storageSupport: true.
-            // If a more dynamic selection for CPU-only datacenters (e.g., from a list of known good CPU datacenters with storage)
-            // is needed in the future, logic similar to GPU selection (minus GPU specifics) could be implemented.
-            info!("[Runpod Controller] CPU-only workload. Defaulting to datacenter 'EU-RO-1' (known to have storage support).");
-            "EU-RO-1".to_string()
+            // CPU-only workload: any listed datacenter with storage support, by CPU stock
+            info!(
+                "[Runpod Controller] CPU-only workload. Finding a datacenter with storage support."
+            );
+            cpu_datacenter(&self.datacenters, &model.owner, &volume_datacenters).await
         };
 
         info!(
@@ -1600,16 +2463,7 @@ impl RunpodPlatform {
         );
         let volume = match self
             .runpod_client
-            .ensure_volume_in_datacenter(
-                &model
-                    .owner
-                    .replace(".", "-")
-                    .replace("@", "-")
-                    .replace("+", "-")
-                    .replace("_", "-"),
-                &datacenter_id,
-                500,
-            )
+            .ensure_volume_in_datacenter(&volume_name_for_owner(&model.owner), &datacenter_id, 500)
             .await
         {
             Ok(vol) => {
@@ -1633,13 +2487,7 @@ impl RunpodPlatform {
             }
         };
 
-        // let container_registry_auth_id = match std::env::var("RUNPOD_CONTAINER_REGISTRY_AUTH_ID") {
-        //     Ok(id) => id,
-        //     Err(_) => {
-        //         error!("[Runpod Controller] RUNPOD_CONTAINER_REGISTRY_AUTH_ID environment variable not set");
-        //         return Err("RUNPOD_CONTAINER_REGISTRY_AUTH_ID must be set".into());
-        //     }
-        // };
+        let port_specs = container_port_specs(&model);
 
         // 5) Create an on-demand instance instead of a spot instance
         let create_request =
@@ -1658,12 +2506,11 @@ impl RunpodPlatform {
                     image_name: Some(model.image.clone()),
                     docker_args: None,
                     docker_entrypoint: docker_command.clone(),
-                    ports: Some(vec!["22/tcp".to_string(), "8080/http".to_string()]),
+                    ports: Some(port_specs.clone()),
                     env: env_vec,
                     network_volume_id: Some(volume.id),
                     volume_mount_path: Some("/nebu/cache".to_string()),
-                    container_registry_auth_id: None,
-                    // container_registry_auth_id: Some(container_registry_auth_id.to_string()),
+                    container_registry_auth_id: registry.runpod_auth_id.clone(),
                 }
             } else {
                 // CPU-only workload
@@ -1680,12 +2527,11 @@ impl RunpodPlatform {
                     image_name: Some(model.image.clone()),
                     docker_args: None,
                     docker_entrypoint: docker_command.clone(),
-                    ports: Some(vec!["22/tcp".to_string(), "8080/http".to_string()]),
+                    ports: Some(port_specs.clone()),
                     env: env_vec,
                     network_volume_id: Some(volume.id),
                     volume_mount_path: Some("/nebu/cache".to_string()),
-                    container_registry_auth_id: None,
-                    // container_registry_auth_id: Some(container_registry_auth_id.to_string()),
+                    container_registry_auth_id: registry.runpod_auth_id.clone(),
                 }
             };
 
@@ -1710,6 +2556,14 @@ impl RunpodPlatform {
 
                     Mutation::update_container_resource_name(db, model.id.clone(), pod.id.clone())
                         .await?;
+                    // A new pod gets its own never-ready deadline
+                    Mutation::update_container_controller_data(db, model.id.clone(), |data| {
+                        data.pod = Some(V1PodState {
+                            created_at: chrono::Utc::now().timestamp(),
+                            ever_ready: false,
+                        })
+                    })
+                    .await?;
 
                     info!(
                         "[Runpod Controller] Updating container status to Created, and accelerator to {}",
@@ -1736,18 +2590,20 @@ impl RunpodPlatform {
                     .await?;
                     pod.id
                 } else {
-                    return Err(format!(
+                    return Err(PlacementFailed(format!(
                         "On-Demand Pod creation returned empty data for job '{}'",
                         model.id
-                    )
+                    ))
                     .into());
                 }
             }
             Err(e) => {
-                return Err(format!(
+                // Often a GPU type that just ran out, so don't pick from a stale list again
+                DatacenterClient::invalidate_gpu_types().await;
+                return Err(PlacementFailed(format!(
                     "Error creating on-demand pod on RunPod for '{}': {:?}",
                     model.id, e
-                )
+                ))
                 .into());
             }
         };
@@ -1760,168 +2616,119 @@ impl RunpodPlatform {
         Ok(pod_id)
     }
 
-    fn build_command(&self, model: &containers::Model, hostname: &str) -> Option<Vec<String>> {
-        let cmd = model.command.clone()?;
+    /// The pod's entrypoint: the bootstrap script running `command`, already
+    /// expanded with the container's env
+    fn build_command(
+        &self,
+        model: &containers::Model,
+        command: Option<String>,
+        hostname: &str,
+        authorized_keys: Vec<String>,
+    ) -> Option<Vec<String>> {
+        let cmd = command?;
 
         let _proxy_value = "socks5h://127.0.0.1:1055".to_string();
 
-        // Statements to install curl if missing:
-        let curl_install = r#"
-            echo "[DEBUG] Installing curl (if not present)..."
-            if ! command -v curl &> /dev/null; then
-                apt-get update && apt-get install -y curl \
-                || (apk update && apk add --no-cache curl) \
-                || echo 'Failed to install curl'
-            fi
-        "#;
-
-        // Statements to install nebu if missing:
-        let nebu_install = r#"
-            echo "[DEBUG] Installing nebu (if not present)..."
-            if ! command -v nebu &> /dev/null; then
-                curl -s https://raw.githubusercontent.com/agentsea/nebulous/main/remote_install.sh | bash \
-                || echo 'Failed to install nebu'
-            fi
-        "#;
-
-        // Here's the new portion for checking tailscale before installing
-        let tailscale_install = r#"
-    echo "[DEBUG] Installing tailscale (if not present)..."
-    if ! command -v tailscale &> /dev/null; then
-        echo "[DEBUG] Tailscale not installed. Installing..."
-        curl -fsSL https://tailscale.com/install.sh | sh
-    else
-        echo "[DEBUG] Tailscale already installed."
-    fi
-"#;
-
-        let log_file = "$HOME/.logs/nebu_container.log";
-
         // export ALL_PROXY={proxy_value}  # TODO: this is problematic for DNS resolution but we may need it
         // export HTTP_PROXY={proxy_value}
         // export HTTPS_PROXY={proxy_value}
 
-        // Wrap the user command in parentheses and add a semicolon to ensure it's treated as a complete statement
-        let base_script = format!(
-            r#"
-    mkdir -p "$HOME/.logs"
-    set -x
-    exec > >(tee -a {log_file}) 2>&1
-    
-    nvidia-smi
-    echo "[DEBUG] Starting setup..."
-    {curl_install}
-    echo "[DEBUG] Done installing curl..."
-    
-    {nebu_install}
-    echo "[DEBUG] Done installing nebu; checking version..."
-    nebu --version
-
-    echo "[DEBUG] Setting HF_HOME to /nebu/cache/huggingface"
-    mkdir -p /nebu/cache/huggingface
-
-    {tailscale_install}
-
-    echo "[DEBUG] Starting tailscale daemon ..."
-    tailscaled --tun=userspace-networking --socks5-server=localhost:1055 --outbound-http-proxy-listen=localhost:1055  > "$HOME/.logs/tailscaled.log" 2>&1 &
-
-    echo "[DEBUG] Waiting for tailscale daemon to start..."
-    daemon_running=false
-    for i in $(seq 1 10); do
-        echo "[DEBUG] Checking tailscale status (attempt $i)..."
-        status_output=$(tailscale status 2>&1)
-        echo "$status_output"
-
-        # Check if we have either a valid Tailscale IP address or 'Logged out' status
-        if grep -Eq -- '(^|[^0-9])([0-9]{{1,3}}\.){{3}}[0-9]{{1,3}}([^0-9]|$)' <<< "$status_output" || echo "$status_output" | grep -q "Logged out."; then
-            echo "[DEBUG] Tailscale daemon is running (found IP address or 'Logged out' status)."
-            daemon_running=true
-            break
-        else
-            echo "[DEBUG] Tailscale not yet ready, retrying..."
-            sleep 1
-        fi
-    done
-
-    # Check if daemon was confirmed running after the loop
-    if [ "$daemon_running" = false ]; then
-        echo "[ERROR] Tailscale daemon did not get an IP address or 'Logged out' status after 10 attempts."
-        echo "[DEBUG] Last tailscale status output:"
-        echo "$status_output"
-        echo "[DEBUG] Checking tailscaled logs..."
-        cat "$HOME/.logs/tailscaled.log"
-        exit 1
-    fi
-
-    echo "[DEBUG] Checking if TS_AUTHKEY is set..."
-    if [ -z "$TS_AUTHKEY" ]; then
-        echo "[ERROR] TS_AUTHKEY is not set. Please set it and try again."
-        exit 1
-    fi
-
-    echo "[DEBUG] Starting tailscale up..."
-    tailscale up --auth-key=$TS_AUTHKEY --hostname="{hostname}" --ssh --advertise-tags=tag:container
-
-    echo "[DEBUG] Invoking nebu sync..."
-    nebu sync volumes --config /nebu/sync.yaml --interval-seconds 5 \
-        --create-if-missing --config-from-env
-
-    echo "[DEBUG] Invoking nebu sync background..."
-    nebu sync volumes --config /nebu/sync.yaml --interval-seconds 5 \
-        --create-if-missing --watch --background --block-once --config-from-env
-
-    nvidia-smi
-    echo "[DEBUG] All done with base_command; now your user command: {cmd}"
-    ({cmd}) # Wrap in parentheses and add semicolon
-    "#,
-            curl_install = curl_install,
-            nebu_install = nebu_install,
-            cmd = cmd
-        );
-
-        // 2) Always wait for final sync - now without the leading &&
-        let wait_script = r#"
-echo "[DEBUG] Waiting for final sync..."
-nebu sync wait --config /nebu/sync.yaml --interval-seconds 5
-"#;
-
-        // 3) Only if restart == Never, mark done and loop forever - now without the leading &&
-        let never_script = if model.restart == RestartPolicy::Never.to_string() {
-            r#"
-echo "[DEBUG] Writing /done.txt..."
-echo "done" > /done.txt
-while true; do
-    echo ">>>all done"
-    sleep 3
-done
-"#
-        } else {
-            ""
+        let bootstrap_overrides = match model.parse_bootstrap() {
+            Ok(overrides) => overrides,
+            Err(e) => {
+                warn!(
+                    "[Runpod Controller] Invalid bootstrap for container {}, using defaults: {}",
+                    model.id, e
+                );
+                None
+            }
+        };
+        let vars = bootstrap::BootstrapVars {
+            authorized_keys,
+            ..bootstrap::BootstrapVars::new(
+                hostname.to_string(),
+                get_tailscale_tags(model).join(","),
+                cmd,
+                bootstrap_overrides.as_ref(),
+                crate::config::SERVER_CONFIG.sync_wait_timeout.as_secs(),
+            )
         };
 
-        // 4) Combine them into our final script
-        let final_script = format!(
-            r#"{base_script}
-{wait_script}
-{never_script}
-"#,
-            base_script = base_script,
-            wait_script = wait_script,
-            never_script = never_script
-        );
+        // Only if restart == Never, mark done and loop forever after the final sync
+        let include_done = model.restart == RestartPolicy::Never.to_string();
+        let final_script = bootstrap::render(&vars, bootstrap_overrides.as_ref(), include_done);
 
-        info!("[Runpod Controller] Final script: {}", final_script);
+        // The user command is encoded in the script, so there's no point logging it whole
+        debug!(
+            "[Runpod Controller] Bootstrap script for container {} is {} bytes",
+            model.id,
+            final_script.len()
+        );
 
         Some(vec!["bash".to_string(), "-c".to_string(), final_script])
     }
 
+    /// Scans the tail of a failed container's log for known fatal errors,
+    /// such as running out of GPU memory, and records the first one found
+    /// as its `failure_reason`. SSH can't reach a pod that has exited, so
+    /// the logs of `exited_pod` are read from the RunPod API instead.
+    async fn record_failure_reason(
+        &self,
+        container_id: &str,
+        exited_pod: Option<&str>,
+        db: &DatabaseConnection,
+    ) {
+        let logs: Result<String, Box<dyn std::error::Error + Send + Sync>> = match exited_pod {
+            Some(pod_id) => match PodLogsClient::from_env() {
+                Some(client) => client
+                    .fetch(pod_id)
+                    .await
+                    .map(|logs| logs.container_tail(failure::FAILURE_SCAN_TAIL_LINES))
+                    .map_err(|e| e.into()),
+                None => Err("RUNPOD_API_KEY is not set".into()),
+            },
+            None => {
+                let params = V1LogParams {
+                    tail_lines: Some(failure::FAILURE_SCAN_TAIL_LINES),
+                    since: None,
+                };
+                self.logs(container_id, &params, db).await
+            }
+        };
+        let logs = match logs {
+            Ok(logs) => logs,
+            Err(e) => {
+                warn!(
+                    "[Runpod Controller] Could not read logs of container {} to look for a failure reason: {}",
+                    container_id, e
+                );
+                return;
+            }
+        };
+        let Some(reason) = failure::FAILURE_PATTERNS.scan(&logs) else {
+            return;
+        };
+        info!(
+            "[Runpod Controller] Container {} logs show {}: {}",
+            container_id, reason.kind, reason.line
+        );
+        if let Err(e) =
+            Mutation::record_container_failure_reason(db, container_id.to_string(), reason).await
+        {
+            error!(
+                "[Runpod Controller] Failed to record failure reason for container {}: {}",
+                container_id, e
+            );
+        }
+    }
+
     /// Checks if `/done.txt` exists in the container. Returns `Ok(true)` if found, `Ok(false)` otherwise.
     pub async fn check_done_file(
         &self,
         container_id: &str,
         container_user: &str,
         db: &DatabaseConnection,
-    ) -> Result<bool, Box<dyn std::error::Error + Send + Sync>> {
+    ) -> Result<bootstrap::DoneFile, Box<dyn std::error::Error + Send + Sync>> {
         // 1) Fetch the container from the database
         let container_model =
             match crate::query::Query::find_container_by_id(db, container_id.to_string()).await? {
@@ -1958,12 +2765,11 @@ done
         // debug!("[Runpod Controller] SSH private key: {}", ssh_private_key);
         // debug!("[Runpod Controller] SSH public key: {}", _ssh_public_key);
 
-        // 4) Form a command that checks existence of /done.txt
-        //    - On success, it prints '1', otherwise '0'
-        let cmd = "test -f /done.txt && echo 1 || echo 0";
+        // 4) Form a command that prints the marked contents of /done.txt, or '0' if it doesn't exist
+        let cmd = bootstrap::DONE_FILE_CHECK;
         info!("[Runpod Controller] Done file check command: {}", cmd);
 
-        let hostname = match container_model.tailnet_ip {
+        let hostname = match container_model.tailnet_ip.clone() {
             Some(ip) => ip,
             None => self.get_tailscale_device_name(&container_model).await,
         };
@@ -1980,27 +2786,46 @@ done
             Ok(output) => output,
             Err(e) => return Err(e.into()),
         };
-        info!(
-            "[Runpod Controller] Check done file output: '{}'",
-            output.trim()
-        );
-
-        info!("raw output.trim() = {:?}", output.trim());
-        info!("len = {}", output.trim().len());
-        for (i, b) in output.trim().as_bytes().iter().enumerate() {
-            info!("byte[{}] = {:#04x}", i, b);
+        debug!("[Runpod Controller] Check done file output: {:?}", output);
+        if !output.contains(bootstrap::DONE_MARKER) {
+            warn!(
+                "[Runpod Controller] Done file check for container {} printed no marker",
+                container_id
+            );
         }
 
-        if output.trim() == "1".to_string() {
-            info!("[Runpod Controller] Done file is present!");
-        } else {
-            info!("[Runpod Controller] Done file is not present");
-        }
+        // 6) Parse the contents; a missing file prints '0'
+        let done_file = bootstrap::parse_done_file(&output);
+        info!("[Runpod Controller] Done file: {:?}", done_file);
+        Ok(done_file)
+    }
+
+    /// Samples the container's GPU, CPU and memory usage over SSH and stores
+    /// it. GPUs are left out for containers without accelerators.
+    async fn sample_usage(
+        &self,
+        container: &containers::Model,
+        db: &DatabaseConnection,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let hostname = match &container.tailnet_ip {
+            Some(ip) => ip.clone(),
+            None => self.get_tailscale_device_name(container).await,
+        };
+        let command = usage::usage_command(usage::has_gpus(container));
+        let output = tokio::time::timeout(
+            crate::config::SERVER_CONFIG.ssh_check_timeout,
+            run_ssh_command_async(&hostname, vec![command], Some(&exec_user(container))),
+        )
+        .await
+        .map_err(|_| format!("Sampling usage of container {} timed out", container.id))??;
 
-        // 6) If output contains "1", the file exists, otherwise it doesn't
-        let file_exists = output.trim() == "1";
-        info!("[Runpod Controller] File exists: {}", file_exists);
-        Ok(file_exists)
+        let sample = usage::parse_usage(&output, chrono::Utc::now().timestamp());
+        debug!(
+            "[Runpod Controller] Usage of container {}: {:?}",
+            container.id, sample
+        );
+        Mutation::record_container_usage(db, container.id.clone(), sample).await?;
+        Ok(())
     }
 
     // Add this new function to the RunpodPlatform impl block
@@ -2034,13 +2859,8 @@ done
             None => std::time::Duration::from_secs(5),
         };
 
-        let client = reqwest::Client::builder()
-            .timeout(timeout_duration)
-            .build()
-            .unwrap_or_default();
-
         // Perform the health check
-        match client.get(&url).send().await {
+        match self.http.get(&url).timeout(timeout_duration).send().await {
             Ok(response) => {
                 if response.status().is_success() {
                     info!(
@@ -2122,6 +2942,8 @@ impl ContainerPlatform for RunpodPlatform {
         namespace: &str,
         api_key: Option<String>,
     ) -> Result<V1Container, Box<dyn std::error::Error + Send + Sync>> {
+        let config = &with_namespace_defaults(db, namespace, config).await?;
+        self.validate_request(config)?;
         let name = config
             .metadata
             .as_ref()
@@ -2131,22 +2953,7 @@ impl ContainerPlatform for RunpodPlatform {
                 petname::petname(3, "-")
             });
         info!("[Runpod Controller] Using name: {:?}", name);
-        let gpu_types_response = match self.runpod_client.list_gpu_types_graphql().await {
-            Ok(response) => response,
-            Err(e) => {
-                error!("[Runpod Controller] Error fetching GPU types: {:?}", e);
-
-                // More detailed error information
-                if let Some(status) = e.status() {
-                    error!("[Runpod Controller] HTTP Status: {}", status);
-                }
-
-                return Err(Box::<dyn std::error::Error + Send + Sync>::from(format!(
-                    "Error fetching GPU types: {:?}",
-                    e
-                )));
-            }
-        };
+        let gpu_types = self.gpu_types().await?;
 
         let mut runpod_gpu_type_id: String = "NVIDIA_TESLA_T4".to_string(); // Default value
         let mut requested_gpu_count = 1; // Default value
@@ -2154,82 +2961,31 @@ impl ContainerPlatform for RunpodPlatform {
         let mut available_gpu_types = Vec::new();
         let mut resource_cost_per_hr: Option<f64> = None;
 
-        // Extract available GPU types from the response
-        if let Some(data) = &gpu_types_response.data {
-            // Log the available GPU types
-            for gpu_type in data {
-                available_gpu_types.push(gpu_type.id.clone());
-
-                // Log GPU type details
-                let memory_str = match gpu_type.memory_in_gb {
-                    Some(mem) => format!("{} GB", mem),
-                    None => "Unknown".to_string(),
-                };
-
-                info!(
-                    "[Runpod Controller] GPU Type: {}, Display Name: {}, Memory: {}",
-                    gpu_type.id, gpu_type.display_name, memory_str
-                );
-            }
-            info!(
-                "[Runpod Controller] Available GPU types: {:?}",
-                available_gpu_types
+        // Collect the available GPU types
+        for gpu_type in &gpu_types {
+            available_gpu_types.push(gpu_type.id.clone());
+            debug!(
+                "[Runpod Controller] GPU Type: {}, Display Name: {}, Memory: {}",
+                gpu_type.id, gpu_type.display_name, gpu_type.memory
             );
         }
         info!(
-            "[Runpod Controller] GPU types response: {:?}",
-            gpu_types_response
+            "[Runpod Controller] Available GPU types: {:?}",
+            available_gpu_types
         );
 
         // Parse accelerators if provided
-        if let Some(accelerators) = &config.accelerators {
-            if !accelerators.is_empty() {
-                let mut found_valid_accelerator = false;
-
-                // Try each accelerator in the list until we find one that works
-                for accelerator in accelerators {
-                    // Parse the accelerator (format: "count:type")
-                    info!("[Runpod Controller] Accelerator: {}", accelerator);
-                    let parts: Vec<&str> = accelerator.split(':').collect();
-                    if parts.len() == 2 {
-                        if let Ok(count) = parts[0].parse::<i32>() {
-                            // Convert from our accelerator name to RunPod's GPU type ID
-                            if let Some(runpod_gpu_name) = self.accelerator_map().get(parts[1]) {
-                                info!("[Runpod Controller] RunPod GPU name: {}", runpod_gpu_name);
-                                // Check if this GPU type is available on RunPod
-                                if available_gpu_types.is_empty()
-                                    || available_gpu_types.contains(runpod_gpu_name)
-                                {
-                                    // This accelerator is available, use it
-                                    requested_gpu_count = count;
-                                    runpod_gpu_type_id = runpod_gpu_name.clone();
-                                    found_valid_accelerator = true;
-
-                                    info!(
-                                        "[Runpod Controller] Using accelerator: {} (count: {})",
-                                        runpod_gpu_type_id, requested_gpu_count
-                                    );
-
-                                    // We found a valid accelerator, stop looking
-                                    break;
-                                } else {
-                                    info!(
-                                        "[Runpod Controller] Accelerator type '{}' is not available, trying next option",
-                                        runpod_gpu_name
-                                    );
-                                }
-                            } else {
-                                info!(
-                                    "[Runpod Controller] Unknown accelerator type: {}, trying next option",
-                                    parts[1]
-                                );
-                            }
-                        }
-                    }
+        if let Some(accelerators) = config.accelerators.as_ref().filter(|a| !a.is_empty()) {
+            match select_accelerator(accelerators, &self.accelerator_map(), &available_gpu_types) {
+                Some(selected) => {
+                    info!(
+                        "[Runpod Controller] Using accelerator: {} (count: {})",
+                        selected.gpu_type_id, selected.count
+                    );
+                    requested_gpu_count = selected.count;
+                    runpod_gpu_type_id = selected.gpu_type_id;
                 }
-
-                // If we couldn't find any valid accelerator, return an error
-                if !found_valid_accelerator {
+                None => {
                     error!(
                         "[Runpod Controller] None of the requested accelerator types are available. Available types: {:?}",
                         available_gpu_types
@@ -2287,9 +3043,13 @@ impl ContainerPlatform for RunpodPlatform {
         let name = name.unwrap_or_else(|| {
             petname::petname(3, "-").unwrap_or_else(|| {
                 // Fallback to a simple default name if petname fails
-                format!("container-{}", ShortUuid::generate())
+                format!(
+                    "container-{}",
+                    ShortUuid::generate().to_string().to_lowercase()
+                )
             })
         });
+        crate::validate::validate_full_name(namespace, &name)?;
 
         debug!(
             "[Runpod Controller] Creating container record in database with GPU type ID: {}",
@@ -2319,9 +3079,15 @@ impl ContainerPlatform for RunpodPlatform {
                 cost_per_hr: None,
                 tailnet_url: None,
                 ready: None,
+                total_cost: None,
+                usage: None,
+                sync_progress: None,
+                ssh_reachable: None,
+                last_ssh_check: None,
+                failure_reason: None,
             }))),
             platform: Set(Some("runpod".to_string())),
-            platforms: Set(None),
+            platforms: Set(config.platforms.clone()),
             meters: Set(config
                 .meters
                 .clone()
@@ -2329,6 +3095,8 @@ impl ContainerPlatform for RunpodPlatform {
             resource_name: Set(None),
             resource_namespace: Set(None),
             resource_cost_per_hr: Set(None),
+            total_cost: Set(None),
+            cost_accrued_at: Set(None),
             command: Set(config.command.clone()),
             args: Set(config.args.clone()),
             labels: Set(config
@@ -2348,16 +3116,40 @@ impl ContainerPlatform for RunpodPlatform {
                 .map(|health_check| serde_json::json!(health_check))),
             desired_status: Set(Some(ContainerStatus::Running.to_string())),
             ssh_keys: Set(config.ssh_keys.clone().map(|keys| serde_json::json!(keys))),
+            bootstrap: Set(config
+                .bootstrap
+                .clone()
+                .map(|bootstrap| serde_json::json!(bootstrap))),
+            tailscale: Set(config
+                .tailscale
+                .clone()
+                .map(|tailscale| serde_json::json!(tailscale))),
+            priority: Set(config.priority),
+            preemptible: Set(config.preemptible),
+            webhook_url: Set(config.webhook_url.clone()),
+            sync_progress: Set(None),
+            ssh_reachable: Set(None),
+            last_ssh_check: Set(None),
+            usage: Set(None),
+            failure_reason: Set(None),
             public_addr: Set(None),
             tailnet_ip: Set(None),
             authz: Set(config.authz.clone().map(|authz| serde_json::json!(authz))),
             ports: Set(config.ports.clone().map(|ports| serde_json::json!(ports))),
             proxy_port: Set(config.proxy_port.clone()),
-            container_user: Set(None),
+            // The fallback user, until `create` finds the image's own
+            container_user: Set(config.user.clone()),
             created_by: Set(Some(owner_id.to_string())),
             updated_at: Set(chrono::Utc::now().into()),
             created_at: Set(chrono::Utc::now().into()),
-            controller_data: Set(None),
+            controller_data: Set(crate::logging::current_request_id().map(|request_id| {
+                ControllerData {
+                    request_id: Some(request_id),
+                    ..Default::default()
+                }
+                .to_json()
+            })),
+            deleted_at: Set(None),
         };
 
         if let Err(e) = container.insert(db).await {
@@ -2388,6 +3180,7 @@ impl ContainerPlatform for RunpodPlatform {
             },
             image: config.image.clone(),
             platform: "runpod".to_string(),
+            platforms: config.platforms.clone(),
             env: config.env.clone(),
             command: config.command.clone(),
             args: config.args.clone(),
@@ -2405,6 +3198,12 @@ impl ContainerPlatform for RunpodPlatform {
                 cost_per_hr: None,
                 tailnet_url: None,
                 ready: None,
+                total_cost: None,
+                usage: None,
+                sync_progress: None,
+                ssh_reachable: None,
+                last_ssh_check: None,
+                failure_reason: None,
             }),
             restart: config.restart.clone(),
             resources: config.resources.clone(),
@@ -2412,6 +3211,11 @@ impl ContainerPlatform for RunpodPlatform {
             ports: config.ports.clone(),
             proxy_port: config.proxy_port.clone(),
             authz: config.authz.clone(),
+            bootstrap: config.bootstrap.clone(),
+            tailscale: config.tailscale.clone(),
+            priority: config.priority,
+            preemptible: config.preemptible,
+            webhook_url: config.webhook_url.clone(),
         })
     }
 
@@ -2425,13 +3229,35 @@ impl ContainerPlatform for RunpodPlatform {
             container.id
         );
 
+        let current_status = container
+            .parse_status()
+            .ok()
+            .flatten()
+            .and_then(|s| s.status)
+            .and_then(|s| ContainerStatus::from_str(&s).ok())
+            .unwrap_or(ContainerStatus::Invalid);
+        match desired_status_action(container.desired_status.as_deref(), &current_status) {
+            DesiredStatusAction::Stop => return self.stop_container(db, container).await,
+            DesiredStatusAction::Resume => return self.resume_container(db, container).await,
+            DesiredStatusAction::None => {}
+        }
+
         // If this container is assigned to a queue,
         // ensure no other container in that same queue is running/active.
         if let Some(queue_name) = &container.queue {
             // We check if the queue is free. We'll skip starting if not free.
-            let queue_is_free =
-                crate::query::Query::is_queue_free(db, queue_name, &container.id).await?;
+            let others =
+                Query::find_other_containers_in_queue(db, queue_name, &container.id).await?;
+            let others: Vec<QueueEntry> = others.iter().map(QueueEntry::from).collect();
+            let entry = QueueEntry::from(container);
+            let queue_is_free = queue_is_free_for(&entry, &others);
             if !queue_is_free {
+                if crate::config::SERVER_CONFIG.preemption && current_status.needs_start() {
+                    for victim in preemption_victims(&entry, &others) {
+                        self.preempt_container(db, &victim.id, container).await?;
+                    }
+                }
+
                 // The queue is blocked by another container.
                 // Set this container to "Queued" status if it's not already in a terminal state.
                 info!(
@@ -2489,10 +3315,27 @@ impl ContainerPlatform for RunpodPlatform {
                     "[Runpod Controller] Container {} needs to be started",
                     container.id
                 );
+                // Respect the restart backoff, if this is a restart
+                if let Ok(Some(V1RestartState {
+                    next_restart_at: Some(next_restart_at),
+                    ..
+                })) = container.parse_restart_state()
+                {
+                    if chrono::Utc::now().timestamp() < next_restart_at {
+                        debug!(
+                            "[Runpod Controller] Container {} is backing off until {}",
+                            container.id, next_restart_at
+                        );
+                        return Ok(());
+                    }
+                }
+
                 if let Some(ds) = &container.desired_status {
                     if ds == &ContainerStatus::Running.to_string() {
                         info!("[Runpod Controller] Container {} has a desired status of 'running', creating...", container.id);
-                        self.create(db, container.clone()).await?;
+                        PLATFORM_OPERATIONS
+                            .run(self.create(db, container.clone()))
+                            .await?;
                     }
                 } else {
                     info!("[Runpod Controller] Container {} does not have a desired status of 'running'", container.id);
@@ -2541,7 +3384,7 @@ impl ContainerPlatform for RunpodPlatform {
         // let _ssh_public_key = maybe_public_key
         //     .ok_or_else(|| format!("No SSH public key found for container {}", container_id))?;
 
-        let hostname = match container_model.tailnet_ip {
+        let hostname = match container_model.tailnet_ip.clone() {
             Some(ip) => ip,
             None => self.get_tailscale_device_name(&container_model).await,
         };
@@ -2552,12 +3395,7 @@ impl ContainerPlatform for RunpodPlatform {
             command.split_whitespace().map(|s| s.to_string()).collect(),
             false,
             false,
-            Some(
-                &container_model
-                    .container_user
-                    .clone()
-                    .unwrap_or("root".to_string()),
-            ),
+            Some(&exec_user(&container_model)),
         ) {
             Ok(output) => output,
             Err(e) => return Err(e.into()),
@@ -2572,16 +3410,17 @@ impl ContainerPlatform for RunpodPlatform {
     async fn logs(
         &self,
         container_id: &str,
+        params: &V1LogParams,
         db: &DatabaseConnection,
     ) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
-        let log_file = "$HOME/.logs/nebu_container.log";
-
         // 1) Fetch the container from the database
         let container_model =
             match crate::query::Query::find_container_by_id(db, container_id.to_string()).await? {
                 Some(model) => model,
                 None => return Err(format!("Container {} not found", container_id).into()),
             };
+        // The current file; rotated ones are left out
+        let log_file = bootstrap::container_log_file(&container_model);
 
         // 2) Retrieve the RunPod Pod ID (stored in container.resource_name)
         // let resource_name = container_model
@@ -2598,30 +3437,27 @@ impl ContainerPlatform for RunpodPlatform {
         //     .ok_or_else(|| format!("No SSH public key found for container {}", container_id))?;
 
         // 4) SSH into the container and retrieve the log file
-        //    Modify this as needed (for tailing, for instance).
-        let command = format!("cat {}", log_file);
+        let command = bootstrap::log_read_command(&log_file, params);
 
-        let hostname = match container_model.tailnet_ip {
+        let hostname = match container_model.tailnet_ip.clone() {
             Some(ip) => ip,
             None => self.get_tailscale_device_name(&container_model).await,
         };
-        let output = match crate::ssh::exec::run_ssh_command_ts(
+        let ssh_result = crate::ssh::exec::run_ssh_command_ts(
             &hostname,
-            command.split_whitespace().map(|s| s.to_string()).collect(),
+            vec![command],
             false,
             false,
-            Some(
-                &container_model
-                    .container_user
-                    .clone()
-                    .unwrap_or("root".to_string()),
-            ),
-        ) {
-            Ok(output) => output,
-            Err(e) => return Err(e.into()),
-        };
+            Some(&exec_user(&container_model)),
+        );
 
-        Ok(output)
+        // Fall back to RunPod's copy of the logs when the tailnet can't reach the pod
+        pod_logs::with_api_fallback(
+            ssh_result,
+            container_model.resource_name.as_deref(),
+            PodLogsClient::from_env().as_ref(),
+        )
+        .await
     }
 
     async fn delete(
@@ -2640,6 +3476,17 @@ impl ContainerPlatform for RunpodPlatform {
                 None => return Err(format!("Container {} not found", id).into()),
             };
 
+        if let Some(dns) = DnsClient::from_server_config() {
+            let fqdn = dns.record_name(&container_model.namespace, &container_model.name);
+            if let Err(e) = dns.delete(&fqdn).await {
+                // Don't block the delete on DNS; the record can be cleaned up by hand
+                error!(
+                    "[Runpod Controller] Failed to remove DNS record {} for container {}: {}",
+                    fqdn, id, e
+                );
+            }
+        }
+
         // First, list all pods to find the one with our name
         match self.runpod_client.list_pods().await {
             Ok(pods_response) => {
@@ -2685,7 +3532,6 @@ impl ContainerPlatform for RunpodPlatform {
                                     }
                                 }
 
-                                // TODO: soft delete
                                 // Update container status in database
                                 // if let Err(e) = crate::mutation::Mutation::update_container_status(
                                 //     &db,
@@ -2828,3 +3674,423 @@ impl ContainerPlatform for RunpodPlatform {
 pub fn is_not_found(err: &reqwest::Error) -> bool {
     err.status() == Some(reqwest::StatusCode::NOT_FOUND)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_select_accelerator_takes_first_offered() {
+        let map = RunPodProvider::new().accelerator_map().clone();
+        let requested = vec![
+            "1:NOPE".to_string(),
+            "2:H100_SXM".to_string(),
+            "4:A100_SXM".to_string(),
+        ];
+        let offered = vec!["NVIDIA A100-SXM4-80GB".to_string()];
+
+        let selected = select_accelerator(&requested, &map, &offered);
+
+        assert_eq!(
+            selected,
+            Some(SelectedGpu {
+                gpu_type_id: "NVIDIA A100-SXM4-80GB".to_string(),
+                accelerator: "A100_SXM".to_string(),
+                count: 4,
+            })
+        );
+        // Without a list of offered types, any known type goes
+        assert_eq!(
+            select_accelerator(&requested, &map, &[]).map(|s| s.accelerator),
+            Some("H100_SXM".to_string())
+        );
+        assert_eq!(select_accelerator(&requested[..1], &map, &[]), None);
+    }
+
+    fn gpu_datacenter(id: &str, location: &str, stock: &str) -> runpod::DataCenterItem {
+        runpod::DataCenterItem {
+            typename: None,
+            id: id.to_string(),
+            name: id.to_string(),
+            location: location.to_string(),
+            storageSupport: true,
+            gpu_availability: vec![runpod::GpuAvailabilityItem {
+                typename: None,
+                stockStatus: Some(stock.to_string()),
+                gpuTypeId: Some("NVIDIA H100".to_string()),
+                gpuTypeDisplayName: None,
+                displayName: None,
+            }],
+        }
+    }
+
+    #[test]
+    fn test_select_gpu_datacenter_prefers_volume_then_stock() {
+        let datacenters = || {
+            vec![
+                gpu_datacenter("CA-MTL-1", "Canada", "Low"),
+                gpu_datacenter("US-TX-3", "United States", "High"),
+                gpu_datacenter("AP-JP-1", "Japan", "High"),
+            ]
+        };
+        let select = |volumes: &[&str]| {
+            let volumes: Vec<String> = volumes.iter().map(|v| v.to_string()).collect();
+            select_gpu_datacenter(datacenters(), "NVIDIA H100", "me@example.com", &volumes)
+                .map(|dc| dc.id)
+        };
+
+        assert_eq!(select(&[]).as_deref(), Some("US-TX-3"));
+        assert_eq!(select(&["AP-JP-1"]).as_deref(), Some("AP-JP-1"));
+    }
+
+    /// A `RunpodPlatform` whose GraphQL queries are answered by a fake RunPod
+    async fn platform_with_fake_runpod(
+        respond: impl Fn(&str) -> serde_json::Value + Clone + Send + Sync + 'static,
+    ) -> RunpodPlatform {
+        use axum::routing::post;
+        use axum::{Json, Router};
+
+        let app = Router::new().route(
+            "/graphql",
+            post(move |Json(request): Json<serde_json::Value>| async move {
+                Json(respond(request["query"].as_str().unwrap_or_default()))
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        RunpodPlatform {
+            datacenters: DatacenterClient::new(
+                "key".to_string(),
+                format!("http://{}/graphql", addr),
+            ),
+            ..RunpodPlatform::with_api_key("key".to_string())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_plan_places_the_pod_without_creating_it() {
+        use sea_orm::ConnectionTrait;
+
+        // Arrange
+        let platform = platform_with_fake_runpod(|query| {
+            if query.contains("networkVolumes") {
+                serde_json::json!({"data": {"myself": {"networkVolumes": [
+                    {"id": "vol1", "name": volume_name_for_owner("me@example.com"), "dataCenterId": "US-KS-2"}
+                ]}}})
+            } else if query.contains("dataCenters") {
+                serde_json::json!({"data": {"dataCenters": [
+                    {"id": "US-KS-2", "location": "United States", "storageSupport": true},
+                    {"id": "EU-SE-1", "location": "Europe", "storageSupport": true}
+                ]}})
+            } else if query.contains("cpuFlavors") {
+                serde_json::json!({"data": {"cpuFlavors": [{"id": "cpu3c", "specifics": {"stockStatus": "Low"}}]}})
+            } else {
+                serde_json::json!({"data": {"gpuTypes": [
+                    {"id": "NVIDIA A100-SXM4-80GB", "displayName": "A100 SXM", "memoryInGb": 80}
+                ]}})
+            }
+        })
+        .await;
+        let db = sea_orm::Database::connect("sqlite::memory:").await.unwrap();
+        let backend = db.get_database_backend();
+        let stmt = sea_orm::Schema::new(backend)
+            .create_table_from_entity(crate::entities::namespaces::Entity);
+        db.execute(backend.build(&stmt)).await.unwrap();
+        let request = V1ContainerRequest {
+            image: "busybox".to_string(),
+            ..Default::default()
+        };
+
+        // Act
+        let plan = platform
+            .plan(&request, &db, "me@example.com", "ns")
+            .await
+            .unwrap();
+
+        // Assert: back to the datacenter holding the owner's volume
+        assert_eq!(plan.platform, "runpod");
+        assert_eq!(plan.datacenter.as_deref(), Some("US-KS-2"));
+        assert_eq!(plan.accelerator, None);
+
+        let request = V1ContainerRequest {
+            accelerators: Some(vec!["1:H100_SXM".to_string()]),
+            ..request
+        };
+        let err = platform
+            .plan(&request, &db, "me@example.com", "ns")
+            .await
+            .unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("None of the requested accelerator types"));
+    }
+
+    #[tokio::test]
+    async fn test_slow_host_is_reachable_after_retries() {
+        // Arrange: the first attempt outlives the timeout, the next is refused
+        let calls = std::sync::atomic::AtomicU32::new(0);
+        let check = || {
+            let call = calls.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            async move {
+                match call {
+                    0 => {
+                        tokio::time::sleep(Duration::from_millis(500)).await;
+                        Ok(())
+                    }
+                    1 => Err("Connection refused".to_string()),
+                    _ => Ok(()),
+                }
+            }
+        };
+
+        // Act
+        let result = check_with_retries(
+            3,
+            Duration::from_millis(50),
+            Duration::from_millis(1),
+            check,
+        )
+        .await;
+
+        // Assert
+        assert_eq!(result, Ok(3));
+        assert_eq!(calls.load(std::sync::atomic::Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_unreachable_host_fails_after_all_attempts() {
+        let calls = std::sync::atomic::AtomicU32::new(0);
+        let result = check_with_retries(2, Duration::from_millis(50), Duration::ZERO, || {
+            calls.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            async { Err::<(), _>("Connection refused".to_string()) }
+        })
+        .await;
+
+        assert_eq!(result, Err("Connection refused".to_string()));
+        assert_eq!(calls.load(std::sync::atomic::Ordering::SeqCst), 2);
+
+        // Zero attempts still tries once
+        let result = check_with_retries(0, Duration::from_millis(50), Duration::ZERO, || async {
+            Ok(())
+        })
+        .await;
+        assert_eq!(result, Ok(1));
+    }
+
+    async fn volumes_db(volumes: &[(&str, &str, &str, &str)]) -> DatabaseConnection {
+        use crate::entities::volumes;
+        use sea_orm::{ConnectionTrait, Database, Schema};
+
+        let db = Database::connect("sqlite::memory:").await.unwrap();
+        let stmt = Schema::new(db.get_database_backend()).create_table_from_entity(volumes::Entity);
+        db.execute(db.get_database_backend().build(&stmt))
+            .await
+            .unwrap();
+        for (namespace, name, owner, source) in volumes {
+            let volume = volumes::Model::new(
+                format!("{}-{}", namespace, name),
+                name.to_string(),
+                namespace.to_string(),
+                owner.to_string(),
+                owner.to_string(),
+                None,
+                source.to_string(),
+            )
+            .unwrap();
+            volumes::ActiveModel::from(volume)
+                .insert(&db)
+                .await
+                .unwrap();
+        }
+        db
+    }
+
+    #[tokio::test]
+    async fn test_nebu_source_resolves_to_backing_url() {
+        // Arrange
+        let db = volumes_db(&[
+            (
+                "team-a",
+                "datasets",
+                "me",
+                "s3://nebu-data/team-a/datasets/",
+            ),
+            (BASE_VOLUME_NAMESPACE, "models", "me", "s3://nebu-models"),
+            ("team-a", "private", "someone-else", "s3://other/private"),
+        ])
+        .await;
+
+        // Act
+        let source = resolve_nebu_path(&db, "team-a", "me", "nebu://datasets/train/part-0")
+            .await
+            .unwrap();
+
+        // Assert
+        assert_eq!(source, "s3://nebu-data/team-a/datasets/train/part-0");
+        // Falls back to the base volume of that name
+        assert_eq!(
+            resolve_nebu_path(&db, "team-a", "me", "nebu://models")
+                .await
+                .unwrap(),
+            "s3://nebu-models"
+        );
+        // Another owner's volume stays out of reach
+        assert!(resolve_nebu_path(&db, "team-a", "me", "nebu://private/x")
+            .await
+            .is_err());
+        assert!(resolve_nebu_path(&db, "team-a", "me", "nebu:///x")
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_only_lookup_failures_are_retried() {
+        use sea_orm::ConnectionTrait;
+
+        let db = volumes_db(&[]).await;
+        let missing = resolve_nebu_path(&db, "team-a", "me", "nebu://datasets")
+            .await
+            .unwrap_err();
+        assert!(is_invalid_volume(&missing));
+        let malformed = resolve_nebu_path(&db, "team-a", "me", "nebu:///x")
+            .await
+            .unwrap_err();
+        assert!(is_invalid_volume(&malformed));
+        assert!(is_invalid_volume(
+            &crate::validate::validate_volume_dest("/etc").unwrap_err()
+        ));
+
+        // E.g. the database went away
+        db.execute_unprepared("DROP TABLE volumes").await.unwrap();
+        let unreachable = resolve_nebu_path(&db, "team-a", "me", "nebu://datasets")
+            .await
+            .unwrap_err();
+        assert!(!is_invalid_volume(&unreachable));
+    }
+
+    #[test]
+    fn test_volume_placeholders_expand_to_container_metadata() {
+        let placeholders = VolumePlaceholders {
+            id: "c1",
+            name: "train",
+            namespace: "team-a",
+            owner: "me@example.com",
+        };
+
+        assert_eq!(
+            placeholders.expand("s3://bucket/{owner}/{name}"),
+            "s3://bucket/me@example.com/train"
+        );
+        assert_eq!(
+            placeholders.expand("/data/{namespace}/{id}/{id}"),
+            "/data/team-a/c1/c1"
+        );
+        assert_eq!(placeholders.expand("/data/{unknown}"), "/data/{unknown}");
+    }
+
+    #[tokio::test]
+    async fn test_volume_config_expands_placeholders() {
+        let db = volumes_db(&[(
+            "team-a",
+            "datasets",
+            "me@example.com",
+            "s3://nebu-data/team-a/datasets",
+        )])
+        .await;
+        let paths = vec![
+            V1VolumePath {
+                source: "s3://bucket/{owner}/{name}/$RUN".to_string(),
+                dest: "/data/{id}".to_string(),
+                ..Default::default()
+            },
+            V1VolumePath {
+                source: "nebu://datasets/{name}".to_string(),
+                dest: "/datasets".to_string(),
+                ..Default::default()
+            },
+        ];
+        let env = HashMap::from([("RUN".to_string(), "r1".to_string())]);
+
+        let config = RunpodPlatform::with_api_key("key".to_string())
+            .determine_volumes_config("c1", "train", "team-a", paths, &env, &db, "me@example.com")
+            .await
+            .unwrap();
+
+        assert_eq!(
+            config.paths[0].source,
+            "s3://bucket/me@example.com/train/r1"
+        );
+        assert_eq!(config.paths[0].dest, "/nebu/cache/data/c1");
+        assert_eq!(config.symlinks[0].symlink_path, "/data/c1");
+        assert_eq!(
+            config.paths[1].source,
+            "s3://nebu-data/team-a/datasets/train"
+        );
+    }
+
+    fn port(port: u16, protocol: Option<&str>) -> V1PortRequest {
+        V1PortRequest {
+            port,
+            protocol: protocol.map(str::to_string),
+            public: Some(true),
+        }
+    }
+
+    #[test]
+    fn test_requested_ports_are_exposed_with_ssh() {
+        // Arrange
+        let requested = vec![
+            port(3000, Some("http")),
+            port(5432, Some("tcp")),
+            port(9000, None),
+        ];
+
+        // Act
+        let specs = runpod_port_specs(&requested);
+
+        // Assert
+        assert_eq!(specs, vec!["22/tcp", "3000/http", "5432/tcp", "9000/tcp"]);
+    }
+
+    #[test]
+    fn test_default_ports_without_request() {
+        assert_eq!(runpod_port_specs(&[]), vec!["22/tcp", "8080/http"]);
+    }
+
+    #[test]
+    fn test_private_ports_are_not_exposed() {
+        let requested = vec![
+            V1PortRequest {
+                public: Some(false),
+                ..port(5432, Some("tcp"))
+            },
+            port(3000, Some("http")),
+        ];
+        assert_eq!(runpod_port_specs(&requested), vec!["22/tcp", "3000/http"]);
+    }
+
+    #[test]
+    fn test_duplicate_ports_are_exposed_once() {
+        let requested = vec![
+            port(22, Some("http")),
+            port(8000, Some("HTTPS")),
+            port(8000, Some("tcp")),
+        ];
+        assert_eq!(runpod_port_specs(&requested), vec!["22/tcp", "8000/http"]);
+    }
+
+    #[test]
+    fn test_proxied_http_ports() {
+        let specs = runpod_port_specs(&[port(3000, Some("http")), port(5432, None)]);
+        assert_eq!(
+            proxied_http_ports("abc123", &specs),
+            vec![V1Port {
+                port: 3000,
+                protocol: Some("http".to_string()),
+                public_ip: None,
+                hostname: Some("abc123-3000.proxy.runpod.net".to_string()),
+            }]
+        );
+    }
+}
@@ -0,0 +1,141 @@
+// src/resources/v1/containers/ssh_keys.rs
+//
+// The public keys users can SSH into a container with. A container's
+// `ssh_keys` gives each either inline as `public_key` or as the name of a
+// secret in its namespace holding it, `public_key_secret`. The bootstrap
+// script adds them to the pod's authorized_keys, so they're only accepted
+// for RunPod containers.
+
+use crate::entities::containers;
+use crate::query::Query;
+use crate::resources::v1::containers::models::V1SSHKey;
+use crate::validate::validate_ssh_public_key;
+use sea_orm::DatabaseConnection;
+
+/// The public key of `key`, read from its secret in `namespace` if it names
+/// one. Keys are validated, so a secret holding something else is an error.
+pub async fn resolve_ssh_key(
+    db: &DatabaseConnection,
+    namespace: &str,
+    key: &V1SSHKey,
+) -> Result<String, String> {
+    let public_key = match (&key.public_key, &key.public_key_secret) {
+        (Some(public_key), _) => public_key.trim().to_string(),
+        (None, Some(secret_name)) => {
+            let secret = Query::find_secret_by_namespace_and_name(db, namespace, secret_name)
+                .await
+                .map_err(|e| format!("Failed to look up SSH key secret: {}", e))?
+                .ok_or_else(|| {
+                    format!(
+                        "SSH key secret '{}' not found in namespace '{}'",
+                        secret_name, namespace
+                    )
+                })?;
+            secret
+                .decrypt_value()
+                .map_err(|e| format!("Failed to decrypt SSH key secret '{}': {}", secret_name, e))?
+                .trim()
+                .to_string()
+        }
+        (None, None) => return Err("SSH key has neither public_key nor public_key_secret".into()),
+    };
+    validate_ssh_public_key(&public_key).map_err(|e| e.to_string())?;
+    Ok(public_key)
+}
+
+/// The public keys to authorize on `container`, in the order of its `ssh_keys`
+pub async fn container_authorized_keys(
+    db: &DatabaseConnection,
+    container: &containers::Model,
+) -> Result<Vec<String>, String> {
+    let keys = container
+        .parse_ssh_keys()
+        .map_err(|e| format!("Invalid ssh_keys: {}", e))?
+        .unwrap_or_default();
+    let mut authorized = Vec::with_capacity(keys.len());
+    for key in &keys {
+        let public_key = resolve_ssh_key(db, &container.namespace, key).await?;
+        if !authorized.contains(&public_key) {
+            authorized.push(public_key);
+        }
+    }
+    Ok(authorized)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::entities::secrets;
+    use sea_orm::{ActiveModelTrait, ConnectionTrait, Database, Schema};
+
+    const KEY: &str =
+        "ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAAIBfu6M2GuTXcOmhgUu1PPGUSrht7g4CQLwJ2ddo9wDvO dev@laptop";
+
+    async fn db_with_secret(name: &str, value: &str) -> DatabaseConnection {
+        let db = Database::connect("sqlite::memory:").await.unwrap();
+        let schema = Schema::new(db.get_database_backend());
+        let stmt = schema.create_table_from_entity(secrets::Entity);
+        db.execute(db.get_database_backend().build(&stmt))
+            .await
+            .unwrap();
+        let secret = secrets::Model::new(
+            "s1".to_string(),
+            name.to_string(),
+            "team".to_string(),
+            "me".to_string(),
+            value,
+            None,
+            None,
+            None,
+        )
+        .unwrap();
+        secrets::ActiveModel::from(secret)
+            .insert(&db)
+            .await
+            .unwrap();
+        db
+    }
+
+    fn ssh_key(public_key: Option<&str>, secret: Option<&str>) -> V1SSHKey {
+        V1SSHKey {
+            public_key: public_key.map(String::from),
+            public_key_secret: secret.map(String::from),
+            copy_local: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_keys_resolve_inline_and_from_secrets() {
+        // Arrange
+        let db = db_with_secret("laptop-key", &format!("{}\n", KEY)).await;
+
+        // Act
+        let inline = resolve_ssh_key(&db, "team", &ssh_key(Some(KEY), None)).await;
+        let from_secret = resolve_ssh_key(&db, "team", &ssh_key(None, Some("laptop-key"))).await;
+
+        // Assert
+        assert_eq!(inline.unwrap(), KEY);
+        assert_eq!(from_secret.unwrap(), KEY);
+    }
+
+    #[tokio::test]
+    async fn test_missing_or_malformed_keys_are_errors() {
+        let db = db_with_secret("not-a-key", "hunter2").await;
+
+        assert!(
+            resolve_ssh_key(&db, "team", &ssh_key(None, Some("not-a-key")))
+                .await
+                .is_err()
+        );
+        assert!(
+            resolve_ssh_key(&db, "other", &ssh_key(None, Some("not-a-key")))
+                .await
+                .is_err()
+        );
+        assert!(
+            resolve_ssh_key(&db, "team", &ssh_key(Some("ssh-rsa AAAA"), None))
+                .await
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,468 @@
+// src/resources/v1/containers/ssh_rotation.rs
+//
+// Rotates the SSH keypair a container was created with. The new public key
+// is authorized on the running container next to the old one, both stored
+// secrets are swapped in one transaction, and only then is the old key
+// removed, so the connection used for the rotation keeps working throughout.
+// A failed step undoes the ones before it.
+
+use crate::entities::secrets;
+use sea_orm::{
+    ActiveModelTrait, ActiveValue::Set, ColumnTrait, DatabaseConnection, DbErr, EntityTrait,
+    QueryFilter, TransactionTrait,
+};
+use std::future::Future;
+use tracing::{error, info, warn};
+
+/// Printed by the remote commands once they've done their part, so a
+/// platform whose exec silently does nothing can't pass for a success.
+const KEY_INSTALLED: &str = "nebu-key-installed";
+const KEY_REVOKED: &str = "nebu-key-revoked";
+
+#[derive(Debug, thiserror::Error)]
+pub enum RotationError {
+    #[error("Container has no stored SSH keypair")]
+    NoKeypair,
+
+    #[error("Failed to read the stored SSH keypair: {0}")]
+    Decrypt(String),
+
+    #[error("Failed to authorize the new key on the container: {0}")]
+    Install(String),
+
+    #[error("Failed to store the new SSH keypair: {0}")]
+    Store(String),
+
+    #[error("Failed to remove the old key from the container: {0}")]
+    Revoke(String),
+
+    #[error(transparent)]
+    Database(#[from] DbErr),
+}
+
+/// Full names of the `(private, public)` key secrets of a container.
+pub fn keypair_secret_names(namespace: &str, container_id: &str) -> (String, String) {
+    (
+        format!("{}/ssh-private-key-{}", namespace, container_id),
+        format!("{}/ssh-public-key-{}", namespace, container_id),
+    )
+}
+
+/// Quotes `value` as a single bash word
+pub fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', r"'\''"))
+}
+
+/// Appends `public_key` to the user's authorized_keys unless it's there.
+/// The leading newline guards against a last line without one.
+pub fn install_key_command(public_key: &str) -> String {
+    let key = shell_quote(public_key);
+    format!(
+        "mkdir -p ~/.ssh && chmod 700 ~/.ssh && touch ~/.ssh/authorized_keys && \
+         chmod 600 ~/.ssh/authorized_keys && {{ grep -qxF {key} ~/.ssh/authorized_keys || \
+         printf '\\n%s\\n' {key} >> ~/.ssh/authorized_keys; }} && echo {marker}",
+        key = key,
+        marker = KEY_INSTALLED
+    )
+}
+
+/// An authorized_keys line for `public_key` that sshd stops accepting at
+/// `expires_at`. The time is written in UTC, which is what containers run in.
+pub fn expiring_key_line(public_key: &str, expires_at: chrono::DateTime<chrono::Utc>) -> String {
+    format!(
+        "expiry-time=\"{}\" {}",
+        expires_at.format("%Y%m%d%H%M%S"),
+        public_key.trim()
+    )
+}
+
+/// Drops the expiring keys from authorized_keys whose time has passed, so
+/// temporary keys don't pile up. Keys without an expiry are kept.
+pub fn prune_expired_keys_command(now: chrono::DateTime<chrono::Utc>) -> String {
+    format!(
+        "mkdir -p ~/.ssh && touch ~/.ssh/authorized_keys && awk -v now={now} \
+         'index($0, \"expiry-time=\\\"\") != 1 || substr($0, 14, 14) \"\" >= now \"\"' \
+         ~/.ssh/authorized_keys > ~/.ssh/authorized_keys.nebu && cat ~/.ssh/authorized_keys.nebu > \
+         ~/.ssh/authorized_keys && rm -f ~/.ssh/authorized_keys.nebu",
+        now = now.format("%Y%m%d%H%M%S")
+    )
+}
+
+/// Authorizes `public_key` on the container until `expires_at`, first
+/// dropping earlier temporary keys that have expired. sshd refuses a key
+/// once it has expired, so nothing else has to clean it up.
+pub async fn authorize_temporary_key<F, Fut>(
+    run: F,
+    public_key: &str,
+    expires_at: chrono::DateTime<chrono::Utc>,
+) -> Result<(), RotationError>
+where
+    F: Fn(String) -> Fut,
+    Fut: Future<Output = Result<String, String>>,
+{
+    let line = expiring_key_line(public_key, expires_at);
+    let command = format!(
+        "{} && {}",
+        prune_expired_keys_command(chrono::Utc::now()),
+        install_key_command(&line)
+    );
+    confirmed(run(command).await, KEY_INSTALLED).map_err(RotationError::Install)
+}
+
+/// Removes every line that is exactly `public_key` from authorized_keys,
+/// rewriting the file in place to keep its permissions.
+pub fn revoke_key_command(public_key: &str) -> String {
+    format!(
+        "touch ~/.ssh/authorized_keys && {{ grep -vxF {key} ~/.ssh/authorized_keys > \
+         ~/.ssh/authorized_keys.nebu || [ $? -eq 1 ]; }} && cat ~/.ssh/authorized_keys.nebu > \
+         ~/.ssh/authorized_keys && rm -f ~/.ssh/authorized_keys.nebu && echo {marker}",
+        key = shell_quote(public_key),
+        marker = KEY_REVOKED
+    )
+}
+
+fn confirmed(result: Result<String, String>, marker: &str) -> Result<(), String> {
+    let output = result?;
+    if output.lines().any(|line| line.trim() == marker) {
+        Ok(())
+    } else {
+        Err(format!("command did not complete: {}", output.trim()))
+    }
+}
+
+/// Overwrites the encrypted values of `secrets` in one transaction.
+async fn write_secrets(
+    db: &DatabaseConnection,
+    updates: Vec<(secrets::Model, String, String)>,
+) -> Result<(), DbErr> {
+    let txn = db.begin().await?;
+    for (secret, encrypted_value, nonce) in updates {
+        let mut active: secrets::ActiveModel = secret.into();
+        active.encrypted_value = Set(encrypted_value);
+        active.nonce = Set(nonce);
+        active.updated_at = Set(chrono::Utc::now().into());
+        active.update(&txn).await?;
+    }
+    txn.commit().await
+}
+
+async fn store_keypair(
+    db: &DatabaseConnection,
+    private_secret: &secrets::Model,
+    public_secret: &secrets::Model,
+    private_key: &str,
+    public_key: &str,
+) -> Result<(), String> {
+    let (private_value, private_nonce) = secrets::Model::encrypt_value(private_key)?;
+    let (public_value, public_nonce) = secrets::Model::encrypt_value(public_key)?;
+    write_secrets(
+        db,
+        vec![
+            (private_secret.clone(), private_value, private_nonce),
+            (public_secret.clone(), public_value, public_nonce),
+        ],
+    )
+    .await
+    .map_err(|e| e.to_string())
+}
+
+/// Best-effort removal of a key during rollback.
+async fn revoke_or_log<F, Fut>(run: &F, public_key: &str, container_id: &str)
+where
+    F: Fn(String) -> Fut,
+    Fut: Future<Output = Result<String, String>>,
+{
+    if let Err(e) = confirmed(run(revoke_key_command(public_key)).await, KEY_REVOKED) {
+        error!(
+            "[SSH Rotation] Failed to remove new key from container {} during rollback: {}",
+            container_id, e
+        );
+    }
+}
+
+/// Replaces the stored SSH keypair of `container_id` with `new_keypair`
+/// (private, public) and makes the container accept only the new key. `run`
+/// executes a shell command on the container and returns its output.
+/// Returns the new public key.
+pub async fn rotate_ssh_keypair<F, Fut>(
+    db: &DatabaseConnection,
+    namespace: &str,
+    container_id: &str,
+    new_keypair: (String, String),
+    run: F,
+) -> Result<String, RotationError>
+where
+    F: Fn(String) -> Fut,
+    Fut: Future<Output = Result<String, String>>,
+{
+    let (private_name, public_name) = keypair_secret_names(namespace, container_id);
+    let private_secret = secrets::Entity::find()
+        .filter(secrets::Column::FullName.eq(private_name))
+        .one(db)
+        .await?
+        .ok_or(RotationError::NoKeypair)?;
+    let public_secret = secrets::Entity::find()
+        .filter(secrets::Column::FullName.eq(public_name))
+        .one(db)
+        .await?
+        .ok_or(RotationError::NoKeypair)?;
+    let old_public_key = public_secret
+        .decrypt_value()
+        .map_err(RotationError::Decrypt)?
+        .trim()
+        .to_string();
+    let (new_private_key, new_public_key) = new_keypair;
+    let new_public_key = new_public_key.trim().to_string();
+
+    // 1) Authorize the new key next to the old one
+    confirmed(
+        run(install_key_command(&new_public_key)).await,
+        KEY_INSTALLED,
+    )
+    .map_err(RotationError::Install)?;
+
+    // 2) Swap the stored keys
+    if let Err(e) = store_keypair(
+        db,
+        &private_secret,
+        &public_secret,
+        &new_private_key,
+        &new_public_key,
+    )
+    .await
+    {
+        revoke_or_log(&run, &new_public_key, container_id).await;
+        return Err(RotationError::Store(e));
+    }
+
+    // 3) Drop the old key, or put everything back as it was
+    if let Err(e) = confirmed(run(revoke_key_command(&old_public_key)).await, KEY_REVOKED) {
+        warn!(
+            "[SSH Rotation] Rolling back rotation of container {}: {}",
+            container_id, e
+        );
+        let restore = vec![
+            (
+                private_secret.clone(),
+                private_secret.encrypted_value.clone(),
+                private_secret.nonce.clone(),
+            ),
+            (
+                public_secret.clone(),
+                public_secret.encrypted_value.clone(),
+                public_secret.nonce.clone(),
+            ),
+        ];
+        if let Err(restore_err) = write_secrets(db, restore).await {
+            error!(
+                "[SSH Rotation] Failed to restore the old keypair of container {}: {}",
+                container_id, restore_err
+            );
+        }
+        revoke_or_log(&run, &new_public_key, container_id).await;
+        return Err(RotationError::Revoke(e));
+    }
+
+    info!(
+        "[SSH Rotation] Rotated SSH keypair of container {}",
+        container_id
+    );
+    Ok(new_public_key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mutation::Mutation;
+    use sea_orm::{ConnectionTrait, Database, Schema};
+    use std::path::{Path, PathBuf};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    const OLD_PUBLIC: &str = "ssh-ed25519 AAAAold nebu@old";
+    const NEW_PUBLIC: &str = "ssh-ed25519 AAAAnew nebu@new";
+
+    async fn setup() -> DatabaseConnection {
+        let db = Database::connect("sqlite::memory:").await.unwrap();
+        let schema = Schema::new(db.get_database_backend());
+        db.execute(
+            db.get_database_backend()
+                .build(&schema.create_table_from_entity(secrets::Entity)),
+        )
+        .await
+        .unwrap();
+        Mutation::store_ssh_keypair(&db, "c1", "ns", "old-private", OLD_PUBLIC, "me", None)
+            .await
+            .unwrap();
+        db
+    }
+
+    /// A container on the local machine: commands run in `sh` with `home` as
+    /// the user's home directory.
+    fn local_home(authorized: &[&str]) -> tempfile::TempDir {
+        let home = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(home.path().join(".ssh")).unwrap();
+        std::fs::write(authorized_keys(home.path()), authorized.join("\n")).unwrap();
+        home
+    }
+
+    fn authorized_keys(home: &Path) -> PathBuf {
+        home.join(".ssh").join("authorized_keys")
+    }
+
+    async fn run_local(home: PathBuf, command: String) -> Result<String, String> {
+        let output = tokio::process::Command::new("sh")
+            .arg("-c")
+            .arg(command)
+            .env("HOME", home)
+            .output()
+            .await
+            .map_err(|e| e.to_string())?;
+        if !output.status.success() {
+            return Err(String::from_utf8_lossy(&output.stderr).to_string());
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).to_string())
+    }
+
+    fn keys_in(home: &Path) -> Vec<String> {
+        std::fs::read_to_string(authorized_keys(home))
+            .unwrap()
+            .lines()
+            .filter(|line| !line.is_empty())
+            .map(String::from)
+            .collect()
+    }
+
+    async fn stored_keys(db: &DatabaseConnection) -> (String, String) {
+        let (private_name, public_name) = keypair_secret_names("ns", "c1");
+        let mut values = Vec::new();
+        for name in [private_name, public_name] {
+            let secret = secrets::Entity::find()
+                .filter(secrets::Column::FullName.eq(name))
+                .one(db)
+                .await
+                .unwrap()
+                .unwrap();
+            values.push(secret.decrypt_value().unwrap());
+        }
+        (values[0].clone(), values[1].clone())
+    }
+
+    #[tokio::test]
+    async fn test_rotation_swaps_keys_on_container_and_secrets() {
+        // Arrange
+        let db = setup().await;
+        let home = local_home(&["ssh-rsa AAAAother someone@else", OLD_PUBLIC]);
+        let path = home.path().to_path_buf();
+
+        // Act
+        let public_key = rotate_ssh_keypair(
+            &db,
+            "ns",
+            "c1",
+            ("new-private".to_string(), format!("{}\n", NEW_PUBLIC)),
+            |command| run_local(path.clone(), command),
+        )
+        .await
+        .unwrap();
+
+        // Assert
+        assert_eq!(public_key, NEW_PUBLIC);
+        assert_eq!(
+            keys_in(home.path()),
+            vec!["ssh-rsa AAAAother someone@else", NEW_PUBLIC]
+        );
+        assert_eq!(
+            stored_keys(&db).await,
+            ("new-private".to_string(), NEW_PUBLIC.to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_failed_revoke_rolls_back() {
+        let db = setup().await;
+        let home = local_home(&[OLD_PUBLIC]);
+        let path = home.path().to_path_buf();
+        let calls = AtomicUsize::new(0);
+
+        // The second command, removing the old key, fails
+        let result = rotate_ssh_keypair(
+            &db,
+            "ns",
+            "c1",
+            ("new-private".to_string(), NEW_PUBLIC.to_string()),
+            |command| {
+                let call = calls.fetch_add(1, Ordering::SeqCst);
+                let path = path.clone();
+                async move {
+                    if call == 1 {
+                        return Err("connection reset".to_string());
+                    }
+                    run_local(path, command).await
+                }
+            },
+        )
+        .await;
+
+        assert!(matches!(result, Err(RotationError::Revoke(_))));
+        assert_eq!(keys_in(home.path()), vec![OLD_PUBLIC]);
+        assert_eq!(
+            stored_keys(&db).await,
+            ("old-private".to_string(), OLD_PUBLIC.to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_failed_install_changes_nothing() {
+        let db = setup().await;
+
+        // An exec that runs nothing doesn't count as installing the key
+        let result = rotate_ssh_keypair(
+            &db,
+            "ns",
+            "c1",
+            ("new-private".to_string(), NEW_PUBLIC.to_string()),
+            |_| async { Ok(String::new()) },
+        )
+        .await;
+
+        assert!(matches!(result, Err(RotationError::Install(_))));
+        assert_eq!(
+            stored_keys(&db).await,
+            ("old-private".to_string(), OLD_PUBLIC.to_string())
+        );
+
+        let missing = rotate_ssh_keypair(
+            &db,
+            "ns",
+            "other",
+            ("new-private".to_string(), NEW_PUBLIC.to_string()),
+            |_| async { Ok(String::new()) },
+        )
+        .await;
+        assert!(matches!(missing, Err(RotationError::NoKeypair)));
+    }
+
+    #[tokio::test]
+    async fn test_temporary_key_is_authorized_with_expiry() {
+        let expired = format!("expiry-time=\"20000101000000\" {}", OLD_PUBLIC);
+        let home = local_home(&[OLD_PUBLIC, &expired]);
+        let expires_at = chrono::DateTime::from_timestamp(1_800_000_000, 0).unwrap();
+
+        let path = home.path().to_path_buf();
+        authorize_temporary_key(
+            move |command| run_local(path.clone(), command),
+            NEW_PUBLIC,
+            expires_at,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(
+            keys_in(home.path()),
+            vec![
+                OLD_PUBLIC.to_string(),
+                format!("expiry-time=\"20270115080000\" {}", NEW_PUBLIC),
+            ]
+        );
+    }
+}
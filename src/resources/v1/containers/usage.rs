@@ -0,0 +1,265 @@
+// src/resources/v1/containers/usage.rs
+//
+// Resource usage of running containers. While a container runs, its watch
+// loop executes `usage_command` in it over SSH every
+// `NEBU_USAGE_SAMPLE_INTERVAL` and parses the output into a
+// `V1ResourceUsage`. The last few samples are kept in the container's `usage`
+// column, and its status shows the latest.
+
+use crate::entities::containers;
+use crate::resources::v1::containers::models::{V1GpuUsage, V1ResourceUsage};
+use std::time::{Duration, Instant};
+
+/// Samples kept per container, oldest dropped first
+pub const USAGE_SAMPLES_KEPT: usize = 30;
+
+const GPU_SECTION: &str = "--- nebu-usage-gpu";
+const CPU_SECTION: &str = "--- nebu-usage-cpu";
+const MEMORY_SECTION: &str = "--- nebu-usage-mem";
+
+/// Columns asked of `nvidia-smi`, in the order `parse_nvidia_smi` reads them
+pub const NVIDIA_SMI_QUERY: &str = "nvidia-smi \
+     --query-gpu=index,name,utilization.gpu,memory.used,memory.total \
+     --format=csv,noheader,nounits";
+
+/// Whether the container asked for accelerators. CPU-only containers have no
+/// `nvidia-smi` to run.
+pub fn has_gpus(container: &containers::Model) -> bool {
+    container
+        .accelerators
+        .as_ref()
+        .is_some_and(|accelerators| !accelerators.is_empty())
+}
+
+/// A shell command printing everything a sample needs, one marked section
+/// each. A missing tool leaves its section empty instead of failing the rest.
+pub fn usage_command(gpus: bool) -> String {
+    let mut command = String::new();
+    if gpus {
+        command.push_str(&format!(
+            "echo '{}'; {} 2>/dev/null; ",
+            GPU_SECTION, NVIDIA_SMI_QUERY
+        ));
+    }
+    command.push_str(&format!(
+        "echo '{}'; nproc 2>/dev/null; cat /proc/loadavg 2>/dev/null; \
+         echo '{}'; cat /proc/meminfo 2>/dev/null; true",
+        CPU_SECTION, MEMORY_SECTION
+    ));
+    command
+}
+
+/// `None` for the placeholders `nvidia-smi` prints when it can't tell,
+/// like `[N/A]` or `[Not Supported]`
+fn smi_value<T: std::str::FromStr>(field: &str) -> Option<T> {
+    field.trim().parse().ok()
+}
+
+/// Parses `nvidia-smi` CSV output from `NVIDIA_SMI_QUERY`, one GPU per line.
+/// Lines that don't look like one are skipped.
+pub fn parse_nvidia_smi(csv: &str) -> Vec<V1GpuUsage> {
+    csv.lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split(',').map(|f| f.trim()).collect();
+            let [index, name, utilization, used, total] = fields.as_slice() else {
+                return None;
+            };
+            Some(V1GpuUsage {
+                index: index.parse().ok()?,
+                name: name.to_string(),
+                utilization_percent: smi_value(utilization),
+                memory_used_mb: smi_value(used),
+                memory_total_mb: smi_value(total),
+            })
+        })
+        .collect()
+}
+
+/// `(cpu_count, one minute load)` from `nproc` followed by `/proc/loadavg`
+fn parse_cpu(section: &str) -> (Option<u32>, Option<f64>) {
+    let mut lines = section.lines().map(str::trim).filter(|l| !l.is_empty());
+    let cpu_count = lines.next().and_then(|l| l.parse().ok());
+    let load = lines
+        .next()
+        .and_then(|l| l.split_whitespace().next())
+        .and_then(|l| l.parse().ok());
+    (cpu_count, load)
+}
+
+/// `(used, total)` memory in MB from `/proc/meminfo`
+fn parse_meminfo(section: &str) -> (Option<u64>, Option<u64>) {
+    let kb = |key: &str| {
+        section.lines().find_map(|line| {
+            let value = line.strip_prefix(key)?.strip_prefix(':')?;
+            value.split_whitespace().next()?.parse::<u64>().ok()
+        })
+    };
+    let total = kb("MemTotal");
+    let used = match (total, kb("MemAvailable")) {
+        (Some(total), Some(available)) => Some(total.saturating_sub(available) / 1024),
+        _ => None,
+    };
+    (used, total.map(|total| total / 1024))
+}
+
+/// Parses the output of `usage_command` into a sample taken at `timestamp`
+pub fn parse_usage(output: &str, timestamp: i64) -> V1ResourceUsage {
+    let mut sections: Vec<(&str, String)> = Vec::new();
+    for line in output.lines() {
+        match line.trim() {
+            marker @ (GPU_SECTION | CPU_SECTION | MEMORY_SECTION) => {
+                sections.push((marker, String::new()))
+            }
+            _ => {
+                if let Some((_, body)) = sections.last_mut() {
+                    body.push_str(line);
+                    body.push('\n');
+                }
+            }
+        }
+    }
+    let section = |marker: &str| {
+        sections
+            .iter()
+            .find(|(m, _)| *m == marker)
+            .map(|(_, body)| body.as_str())
+            .unwrap_or_default()
+    };
+
+    let (cpu_count, cpu_load) = parse_cpu(section(CPU_SECTION));
+    let (memory_used_mb, memory_total_mb) = parse_meminfo(section(MEMORY_SECTION));
+    V1ResourceUsage {
+        timestamp,
+        gpus: parse_nvidia_smi(section(GPU_SECTION)),
+        cpu_load,
+        cpu_count,
+        memory_used_mb,
+        memory_total_mb,
+    }
+}
+
+/// Appends `sample` to `history`, keeping the latest `USAGE_SAMPLES_KEPT`
+pub fn push_sample(history: &mut Vec<V1ResourceUsage>, sample: V1ResourceUsage) {
+    history.push(sample);
+    if history.len() > USAGE_SAMPLES_KEPT {
+        history.drain(..history.len() - USAGE_SAMPLES_KEPT);
+    }
+}
+
+/// Decides when a watch loop samples next
+#[derive(Debug)]
+pub struct UsageSampler {
+    interval: Option<Duration>,
+    last: Option<Instant>,
+}
+
+impl UsageSampler {
+    /// `None` never samples
+    pub fn new(interval: Option<Duration>) -> Self {
+        Self {
+            interval,
+            last: None,
+        }
+    }
+
+    /// Whether a sample is due at `now`, counting it as taken if so
+    pub fn due(&mut self, now: Instant) -> bool {
+        let Some(interval) = self.interval else {
+            return false;
+        };
+        if self
+            .last
+            .is_some_and(|last| now.duration_since(last) < interval)
+        {
+            return false;
+        }
+        self.last = Some(now);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_nvidia_smi_csv() {
+        // Arrange
+        let csv = "0, NVIDIA A100-SXM4-80GB, 87, 61234, 81920\n\
+                   1, NVIDIA A100-SXM4-80GB, [N/A], 3, 81920\n\
+                   \n\
+                   NVIDIA-SMI has failed because it couldn't communicate with the driver\n";
+
+        // Act
+        let gpus = parse_nvidia_smi(csv);
+
+        // Assert
+        assert_eq!(
+            gpus,
+            vec![
+                V1GpuUsage {
+                    index: 0,
+                    name: "NVIDIA A100-SXM4-80GB".to_string(),
+                    utilization_percent: Some(87.0),
+                    memory_used_mb: Some(61234),
+                    memory_total_mb: Some(81920),
+                },
+                V1GpuUsage {
+                    index: 1,
+                    name: "NVIDIA A100-SXM4-80GB".to_string(),
+                    utilization_percent: None,
+                    memory_used_mb: Some(3),
+                    memory_total_mb: Some(81920),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_usage_sections() {
+        let output = format!(
+            "{}\n0, NVIDIA GeForce RTX 4090, 12, 2048, 24564\n{}\n16\n3.52 2.10 1.05 2/345 6789\n{}\n\
+             MemTotal:       65536000 kB\nMemFree:         1024000 kB\nMemAvailable:   49152000 kB\n",
+            GPU_SECTION, CPU_SECTION, MEMORY_SECTION
+        );
+
+        let usage = parse_usage(&output, 1700000000);
+
+        assert_eq!(usage.timestamp, 1700000000);
+        assert_eq!(usage.gpus.len(), 1);
+        assert_eq!(usage.gpus[0].utilization_percent, Some(12.0));
+        assert_eq!(usage.cpu_count, Some(16));
+        assert_eq!(usage.cpu_load, Some(3.52));
+        assert_eq!(usage.memory_total_mb, Some(64000));
+        assert_eq!(usage.memory_used_mb, Some(16000));
+
+        // CPU-only containers don't ask for GPUs at all
+        assert!(!usage_command(false).contains("nvidia-smi"));
+        let cpu_only = parse_usage(&output.replace(GPU_SECTION, ""), 0);
+        assert!(cpu_only.gpus.is_empty());
+        assert_eq!(cpu_only.cpu_count, Some(16));
+    }
+
+    #[test]
+    fn test_history_and_interval() {
+        let mut history = Vec::new();
+        for timestamp in 0..(USAGE_SAMPLES_KEPT as i64 + 5) {
+            push_sample(
+                &mut history,
+                V1ResourceUsage {
+                    timestamp,
+                    ..Default::default()
+                },
+            );
+        }
+        assert_eq!(history.len(), USAGE_SAMPLES_KEPT);
+        assert_eq!(history[0].timestamp, 5);
+
+        let start = Instant::now();
+        let mut sampler = UsageSampler::new(Some(Duration::from_secs(60)));
+        assert!(sampler.due(start));
+        assert!(!sampler.due(start + Duration::from_secs(30)));
+        assert!(sampler.due(start + Duration::from_secs(60)));
+        assert!(!UsageSampler::new(None).due(start));
+    }
+}
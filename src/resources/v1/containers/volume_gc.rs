@@ -0,0 +1,377 @@
+// src/resources/v1/containers/volume_gc.rs
+//
+// Garbage collection for the RunPod network volumes `create` makes per owner
+// and datacenter. A volume is only considered when its name matches an owner
+// we know from the containers table, so volumes made outside nebulous are
+// never touched.
+
+use crate::entities::containers;
+use crate::resources::v1::containers::base::ContainerStatus;
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::time::Duration;
+use tracing::{debug, info, warn};
+
+const DEFAULT_RUNPOD_REST_URL: &str = "https://rest.runpod.io/v1";
+
+/// How often the garbage collector looks for orphaned volumes
+pub const VOLUME_GC_INTERVAL: Duration = Duration::from_secs(10 * 60);
+
+/// The name `create` gives an owner's network volumes.
+pub fn volume_name_for_owner(owner: &str) -> String {
+    owner
+        .replace(".", "-")
+        .replace("@", "-")
+        .replace("+", "-")
+        .replace("_", "-")
+}
+
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct NetworkVolume {
+    pub id: String,
+    pub name: String,
+    #[serde(default)]
+    pub data_center_id: Option<String>,
+    #[serde(default)]
+    pub size: Option<i64>,
+}
+
+/// How an owner's RunPod containers use their volumes.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VolumeUsage {
+    /// A container still needs the volume: it is running, starting, or stopped
+    /// and resumable
+    pub in_use: bool,
+    /// When one of the owner's containers last changed
+    pub last_used: DateTime<Utc>,
+}
+
+/// Whether a container still needs its owner's volume.
+fn needs_volume(container: &containers::Model) -> bool {
+    if container.deleted_at.is_some() {
+        return false;
+    }
+    let status = container
+        .parse_status()
+        .ok()
+        .flatten()
+        .and_then(|s| s.status)
+        .and_then(|s| ContainerStatus::from_str(&s).ok())
+        .unwrap_or(ContainerStatus::Pending);
+    // Stopped containers keep their volume so they can be resumed
+    status.is_active() || status == ContainerStatus::Stopped
+}
+
+/// Volume usage by volume name, from the RunPod containers in `all`.
+pub fn volume_usage(all: &[containers::Model]) -> HashMap<String, VolumeUsage> {
+    let mut usage: HashMap<String, VolumeUsage> = HashMap::new();
+    for container in all
+        .iter()
+        .filter(|c| c.platform.as_deref() == Some("runpod"))
+    {
+        let last_used = container.updated_at.with_timezone(&Utc);
+        let entry = usage
+            .entry(volume_name_for_owner(&container.owner))
+            .or_insert(VolumeUsage {
+                in_use: false,
+                last_used,
+            });
+        entry.in_use |= needs_volume(container);
+        entry.last_used = entry.last_used.max(last_used);
+    }
+    usage
+}
+
+/// Volumes nebulous created that no container has needed for `grace`.
+pub fn orphaned_volumes<'a>(
+    volumes: &'a [NetworkVolume],
+    usage: &HashMap<String, VolumeUsage>,
+    now: DateTime<Utc>,
+    grace: Duration,
+) -> Vec<&'a NetworkVolume> {
+    let grace = chrono::Duration::from_std(grace).unwrap_or(chrono::Duration::MAX);
+    volumes
+        .iter()
+        .filter(|volume| match usage.get(&volume.name) {
+            Some(usage) => !usage.in_use && now - usage.last_used >= grace,
+            // Not one of ours
+            None => false,
+        })
+        .collect()
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum VolumeGcError {
+    #[error("RunPod API request failed: {0}")]
+    Request(#[from] reqwest::Error),
+
+    #[error("RunPod API returned {0}: {1}")]
+    Api(reqwest::StatusCode, String),
+}
+
+/// The RunPod REST calls the garbage collector needs.
+pub struct NetworkVolumeClient {
+    api_key: String,
+    base_url: String,
+    http: reqwest::Client,
+}
+
+impl NetworkVolumeClient {
+    pub fn new(api_key: String, base_url: String) -> Self {
+        Self {
+            api_key,
+            base_url: base_url.trim_end_matches('/').to_string(),
+            http: crate::utils::http::shared_client(),
+        }
+    }
+
+    /// Uses `RUNPOD_API_KEY`, and `RUNPOD_REST_URL` when set. None without a key.
+    pub fn from_env() -> Option<Self> {
+        let api_key = std::env::var("RUNPOD_API_KEY").ok()?;
+        let base_url = std::env::var("RUNPOD_REST_URL")
+            .unwrap_or_else(|_| DEFAULT_RUNPOD_REST_URL.to_string());
+        Some(Self::new(api_key, base_url))
+    }
+
+    pub async fn list(&self) -> Result<Vec<NetworkVolume>, VolumeGcError> {
+        let response = self
+            .http
+            .get(format!("{}/networkvolumes", self.base_url))
+            .bearer_auth(&self.api_key)
+            .send()
+            .await?;
+        if !response.status().is_success() {
+            return Err(VolumeGcError::Api(
+                response.status(),
+                response.text().await.unwrap_or_default(),
+            ));
+        }
+        Ok(response.json().await?)
+    }
+
+    pub async fn delete(&self, id: &str) -> Result<(), VolumeGcError> {
+        let response = self
+            .http
+            .delete(format!("{}/networkvolumes/{}", self.base_url, id))
+            .bearer_auth(&self.api_key)
+            .send()
+            .await?;
+        if !response.status().is_success() {
+            return Err(VolumeGcError::Api(
+                response.status(),
+                response.text().await.unwrap_or_default(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// One garbage collection pass. Returns the ids of the orphaned volumes, which
+/// are only logged in `dry_run` mode and deleted otherwise.
+pub async fn collect_orphaned_volumes(
+    client: &NetworkVolumeClient,
+    usage: &HashMap<String, VolumeUsage>,
+    grace: Duration,
+    dry_run: bool,
+) -> Result<Vec<String>, VolumeGcError> {
+    let volumes = client.list().await?;
+    let orphans = orphaned_volumes(&volumes, usage, Utc::now(), grace);
+    debug!(
+        "[Volume GC] {} network volumes, {} orphaned",
+        volumes.len(),
+        orphans.len()
+    );
+
+    let mut collected = Vec::new();
+    for volume in orphans {
+        if dry_run {
+            info!(
+                "[Volume GC] Would delete network volume {} ({}) in {}",
+                volume.id,
+                volume.name,
+                volume
+                    .data_center_id
+                    .as_deref()
+                    .unwrap_or("unknown datacenter")
+            );
+            collected.push(volume.id.clone());
+            continue;
+        }
+        match client.delete(&volume.id).await {
+            Ok(()) => {
+                info!(
+                    "[Volume GC] Deleted network volume {} ({})",
+                    volume.id, volume.name
+                );
+                collected.push(volume.id.clone());
+            }
+            Err(e) => warn!(
+                "[Volume GC] Failed to delete network volume {} ({}): {}",
+                volume.id, volume.name, e
+            ),
+        }
+    }
+    Ok(collected)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::extract::{Path, State};
+    use axum::routing::{delete, get};
+    use axum::{Json, Router};
+    use std::sync::{Arc, Mutex};
+
+    fn volume(id: &str, name: &str) -> NetworkVolume {
+        NetworkVolume {
+            id: id.to_string(),
+            name: name.to_string(),
+            data_center_id: Some("EU-RO-1".to_string()),
+            size: Some(500),
+        }
+    }
+
+    fn usage(in_use: bool, idle_hours: i64, now: DateTime<Utc>) -> VolumeUsage {
+        VolumeUsage {
+            in_use,
+            last_used: now - chrono::Duration::hours(idle_hours),
+        }
+    }
+
+    #[test]
+    fn test_orphaned_volumes_respects_usage_and_grace() {
+        // Arrange
+        let now = Utc::now();
+        let volumes = vec![
+            volume("v1", "busy-example-com"),
+            volume("v2", "idle-example-com"),
+            volume("v3", "recent-example-com"),
+            volume("v4", "someone-elses-volume"),
+        ];
+        let usage = HashMap::from([
+            ("busy-example-com".to_string(), usage(true, 500, now)),
+            ("idle-example-com".to_string(), usage(false, 48, now)),
+            ("recent-example-com".to_string(), usage(false, 2, now)),
+        ]);
+
+        // Act
+        let orphans = orphaned_volumes(&volumes, &usage, now, Duration::from_secs(24 * 3600));
+
+        // Assert
+        let ids: Vec<&str> = orphans.iter().map(|v| v.id.as_str()).collect();
+        assert_eq!(ids, vec!["v2"]);
+    }
+
+    fn container(
+        owner: &str,
+        platform: &str,
+        status: &str,
+        deleted: bool,
+        idle_hours: i64,
+    ) -> containers::Model {
+        let updated_at = Utc::now() - chrono::Duration::hours(idle_hours);
+        containers::Model {
+            id: short_uuid::ShortUuid::generate().to_string(),
+            name: "c".to_string(),
+            full_name: "ns/c".to_string(),
+            owner: owner.to_string(),
+            status: Some(serde_json::json!({ "status": status })),
+            platform: Some(platform.to_string()),
+            deleted_at: deleted.then(|| updated_at.into()),
+            updated_at: updated_at.into(),
+            created_at: updated_at.into(),
+            ..containers::Model::test_fixture()
+        }
+    }
+
+    #[test]
+    fn test_volume_usage_from_containers() {
+        let usage = volume_usage(&[
+            container("a@example.com", "runpod", "running", false, 100),
+            container("a@example.com", "runpod", "completed", false, 1),
+            container("b@example.com", "runpod", "stopped", false, 100),
+            container("c@example.com", "runpod", "running", true, 50),
+            container("c@example.com", "runpod", "failed", false, 30),
+            container("d@example.com", "kube", "running", false, 1),
+        ]);
+
+        let a = &usage["a-example-com"];
+        assert!(a.in_use);
+        assert!(Utc::now() - a.last_used < chrono::Duration::hours(2));
+        assert!(usage["b-example-com"].in_use);
+        let c = &usage["c-example-com"];
+        assert!(!c.in_use);
+        assert!(Utc::now() - c.last_used >= chrono::Duration::hours(30));
+        assert!(!usage.contains_key("d-example-com"));
+    }
+
+    #[test]
+    fn test_volume_name_for_owner() {
+        assert_eq!(
+            volume_name_for_owner("jane.doe+gpu@example_org.com"),
+            "jane-doe-gpu-example-org-com"
+        );
+    }
+
+    /// Volumes held by the fake RunPod API, and the ids deleted from it
+    #[derive(Clone, Default)]
+    struct FakeRunpod {
+        volumes: Arc<Mutex<Vec<serde_json::Value>>>,
+        deleted: Arc<Mutex<Vec<String>>>,
+    }
+
+    async fn serve_fake_runpod(fake: FakeRunpod) -> String {
+        async fn list(State(fake): State<FakeRunpod>) -> Json<Vec<serde_json::Value>> {
+            Json(fake.volumes.lock().unwrap().clone())
+        }
+        async fn remove(State(fake): State<FakeRunpod>, Path(id): Path<String>) {
+            fake.volumes
+                .lock()
+                .unwrap()
+                .retain(|v| v["id"] != id.as_str());
+            fake.deleted.lock().unwrap().push(id);
+        }
+
+        let app = Router::new()
+            .route("/networkvolumes", get(list))
+            .route("/networkvolumes/:id", delete(remove))
+            .with_state(fake);
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        format!("http://{}", addr)
+    }
+
+    #[tokio::test]
+    async fn test_dry_run_deletes_nothing() {
+        let fake = FakeRunpod::default();
+        *fake.volumes.lock().unwrap() = vec![
+            serde_json::json!({"id": "v1", "name": "idle-example-com", "dataCenterId": "EU-RO-1", "size": 500}),
+            serde_json::json!({"id": "v2", "name": "busy-example-com", "dataCenterId": "EU-RO-1", "size": 500}),
+        ];
+        let client =
+            NetworkVolumeClient::new("key".to_string(), serve_fake_runpod(fake.clone()).await);
+        let now = Utc::now();
+        let usage = HashMap::from([
+            ("idle-example-com".to_string(), usage(false, 48, now)),
+            ("busy-example-com".to_string(), usage(true, 48, now)),
+        ]);
+        let grace = Duration::from_secs(3600);
+
+        let would_delete = collect_orphaned_volumes(&client, &usage, grace, true)
+            .await
+            .unwrap();
+        assert_eq!(would_delete, vec!["v1".to_string()]);
+        assert!(fake.deleted.lock().unwrap().is_empty());
+
+        let deleted = collect_orphaned_volumes(&client, &usage, grace, false)
+            .await
+            .unwrap();
+        assert_eq!(deleted, vec!["v1".to_string()]);
+        assert_eq!(*fake.deleted.lock().unwrap(), vec!["v1".to_string()]);
+        assert_eq!(fake.volumes.lock().unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,486 @@
+// src/resources/v1/containers/webhooks.rs
+//
+// Notifies a container's `webhook_url` when it becomes running, completed or
+// failed. Webhooks need `NEBU_WEBHOOK_SECRET`: every payload is signed with it
+// together with the time it was sent, so receivers can check it came from
+// this server and reject replays. Targets must resolve to public addresses,
+// unless `NEBU_WEBHOOK_ALLOW_PRIVATE` is set, and redirects aren't followed,
+// so a webhook can't be pointed at the server's own network.
+
+use crate::entities::container_events::is_transition;
+use crate::entities::containers;
+use crate::resources::v1::containers::base::ContainerStatus;
+use crate::validate::is_public_ip;
+use serde::{Deserialize, Serialize};
+use std::net::SocketAddr;
+use std::str::FromStr;
+use std::time::Duration;
+use tracing::{debug, error, warn};
+
+/// Header carrying `sha256=<hex HMAC of "<timestamp>.<body>">`
+pub const SIGNATURE_HEADER: &str = "X-Nebu-Signature";
+
+/// Header carrying the Unix time the request was signed at
+pub const TIMESTAMP_HEADER: &str = "X-Nebu-Timestamp";
+
+/// Delivery attempts before a notification is dropped
+pub const WEBHOOK_ATTEMPTS: u32 = 4;
+
+/// Delay before the first retry, doubled after each failure
+const WEBHOOK_RETRY_DELAY: Duration = Duration::from_secs(2);
+
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct V1ContainerStatusWebhook {
+    pub kind: String,
+    pub container_id: String,
+    pub namespace: String,
+    pub name: String,
+    pub old_status: Option<String>,
+    pub new_status: String,
+    pub message: Option<String>,
+    pub timestamp: String,
+}
+
+/// Whether a container reaching `status` is worth a notification.
+pub fn notifies(status: &str) -> bool {
+    matches!(
+        ContainerStatus::from_str(status),
+        Ok(ContainerStatus::Running | ContainerStatus::Completed | ContainerStatus::Failed)
+    )
+}
+
+/// How webhooks are delivered
+#[derive(Debug, Clone)]
+pub struct WebhookSettings {
+    /// Key payloads are signed with
+    pub secret: String,
+    /// Send to loopback, private and link-local addresses too
+    pub allow_private: bool,
+    /// Delay before the first retry, doubled after each failure
+    pub retry_delay: Duration,
+}
+
+impl WebhookSettings {
+    /// The settings from `SERVER_CONFIG`, None when webhooks are off because
+    /// there's no secret to sign them with
+    pub fn from_config() -> Option<Self> {
+        let config = &crate::config::SERVER_CONFIG;
+        Some(Self {
+            secret: config.webhook_secret.clone()?,
+            allow_private: config.webhook_allow_private,
+            retry_delay: WEBHOOK_RETRY_DELAY,
+        })
+    }
+}
+
+/// The payload for a transition, or None when the container has no webhook,
+/// the status didn't change or the new status isn't one we notify about.
+pub fn status_webhook(
+    container: &containers::Model,
+    old_status: Option<&str>,
+    new_status: Option<&str>,
+    message: Option<&str>,
+) -> Option<(String, V1ContainerStatusWebhook)> {
+    let url = container.webhook_url.clone().filter(|u| !u.is_empty())?;
+    if !is_transition(old_status, new_status) {
+        return None;
+    }
+    let new_status = new_status.filter(|s| notifies(s))?;
+    Some((
+        url,
+        V1ContainerStatusWebhook {
+            kind: "ContainerStatusChanged".to_string(),
+            container_id: container.id.clone(),
+            namespace: container.namespace.clone(),
+            name: container.name.clone(),
+            old_status: old_status.map(|s| s.to_string()),
+            new_status: new_status.to_string(),
+            message: message.map(|m| m.to_string()),
+            timestamp: chrono::Utc::now().to_rfc3339(),
+        },
+    ))
+}
+
+/// `sha256=<hex>` HMAC-SHA256 of `body` under `secret`.
+pub fn sign(secret: &str, body: &[u8]) -> String {
+    let key = ring::hmac::Key::new(ring::hmac::HMAC_SHA256, secret.as_bytes());
+    let tag = ring::hmac::sign(&key, body);
+    let hex: String = tag.as_ref().iter().map(|b| format!("{:02x}", b)).collect();
+    format!("sha256={}", hex)
+}
+
+/// The signature of `body` sent at `timestamp`: the HMAC of
+/// `<timestamp>.<body>`, so a captured request can't be replayed later with
+/// a fresh timestamp.
+pub fn sign_request(secret: &str, timestamp: i64, body: &[u8]) -> String {
+    let mut signed = format!("{}.", timestamp).into_bytes();
+    signed.extend_from_slice(body);
+    sign(secret, &signed)
+}
+
+/// Resolves the host of `url` to the address webhooks are sent to, refusing
+/// non-public addresses unless `allow_private`. Every address the host
+/// resolves to is checked, and the one returned is the one connected to, so
+/// the name can't be re-pointed between the check and the request.
+async fn resolve_target(url: &str, allow_private: bool) -> Result<(String, SocketAddr), String> {
+    let parsed = reqwest::Url::parse(url).map_err(|e| format!("invalid URL: {}", e))?;
+    let host = parsed
+        .host_str()
+        .ok_or_else(|| "URL has no host".to_string())?
+        .trim_start_matches('[')
+        .trim_end_matches(']')
+        .to_string();
+    let port = parsed.port_or_known_default().unwrap_or(80);
+    let addrs: Vec<SocketAddr> = tokio::net::lookup_host((host.as_str(), port))
+        .await
+        .map_err(|e| format!("failed to resolve {}: {}", host, e))?
+        .collect();
+    if let Some(addr) = addrs
+        .iter()
+        .find(|addr| !allow_private && !is_public_ip(&addr.ip()))
+    {
+        return Err(format!(
+            "{} resolves to non-public address {}",
+            host,
+            addr.ip()
+        ));
+    }
+    addrs
+        .into_iter()
+        .next()
+        .map(|addr| (host.clone(), addr))
+        .ok_or_else(|| format!("{} has no addresses", host))
+}
+
+/// A client sending to `addr` for `host` that doesn't follow redirects
+fn webhook_client(host: &str, addr: SocketAddr) -> reqwest::Result<reqwest::Client> {
+    reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .resolve(host, addr)
+        .timeout(WEBHOOK_TIMEOUT)
+        .build()
+}
+
+/// POSTs `payload` to `url`, retrying failed attempts with exponential
+/// backoff. Returns whether it was delivered.
+pub async fn deliver(
+    url: &str,
+    payload: &V1ContainerStatusWebhook,
+    settings: &WebhookSettings,
+) -> bool {
+    let body = match serde_json::to_vec(payload) {
+        Ok(body) => body,
+        Err(e) => {
+            error!("[Webhooks] Failed to serialize webhook payload: {}", e);
+            return false;
+        }
+    };
+    let client = match resolve_target(url, settings.allow_private).await {
+        Ok((host, addr)) => webhook_client(&host, addr),
+        Err(e) => {
+            warn!("[Webhooks] Not sending webhook to {}: {}", url, e);
+            return false;
+        }
+    };
+    let client = match client {
+        Ok(client) => client,
+        Err(e) => {
+            error!("[Webhooks] Failed to build webhook client: {}", e);
+            return false;
+        }
+    };
+
+    let mut delay = settings.retry_delay;
+    for attempt in 1..=WEBHOOK_ATTEMPTS {
+        let timestamp = chrono::Utc::now().timestamp();
+        let request = client
+            .post(url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .header(TIMESTAMP_HEADER, timestamp.to_string())
+            .header(
+                SIGNATURE_HEADER,
+                sign_request(&settings.secret, timestamp, &body),
+            )
+            .body(body.clone());
+
+        match request.send().await {
+            Ok(response) if response.status().is_success() => {
+                debug!(
+                    "[Webhooks] Delivered {} for container {} to {}",
+                    payload.new_status, payload.container_id, url
+                );
+                return true;
+            }
+            Ok(response) => warn!(
+                "[Webhooks] Attempt {}/{} to {} returned {}",
+                attempt,
+                WEBHOOK_ATTEMPTS,
+                url,
+                response.status()
+            ),
+            Err(e) => warn!(
+                "[Webhooks] Attempt {}/{} to {} failed: {}",
+                attempt, WEBHOOK_ATTEMPTS, url, e
+            ),
+        }
+
+        if attempt < WEBHOOK_ATTEMPTS {
+            tokio::time::sleep(delay).await;
+            delay *= 2;
+        }
+    }
+
+    error!(
+        "[Webhooks] Giving up on {} webhook for container {} after {} attempts",
+        payload.new_status, payload.container_id, WEBHOOK_ATTEMPTS
+    );
+    false
+}
+
+/// Sends the webhook for a status transition in the background, if any.
+pub fn notify_status_transition(
+    container: &containers::Model,
+    old_status: Option<&str>,
+    new_status: Option<&str>,
+    message: Option<&str>,
+) {
+    let Some(settings) = WebhookSettings::from_config() else {
+        if container.webhook_url.is_some() {
+            warn!(
+                "[Webhooks] Not notifying container {}: NEBU_WEBHOOK_SECRET is not set",
+                container.id
+            );
+        }
+        return;
+    };
+    send_status_transition(container, old_status, new_status, message, settings);
+}
+
+/// Sends the webhook for a status transition with `settings`, returning the
+/// delivery task when there is one.
+pub fn send_status_transition(
+    container: &containers::Model,
+    old_status: Option<&str>,
+    new_status: Option<&str>,
+    message: Option<&str>,
+    settings: WebhookSettings,
+) -> Option<tokio::task::JoinHandle<bool>> {
+    let (url, payload) = status_webhook(container, old_status, new_status, message)?;
+    Some(tokio::spawn(async move {
+        deliver(&url, &payload, &settings).await
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::{HeaderMap, StatusCode};
+    use axum::routing::post;
+    use axum::Router;
+    use std::sync::{Arc, Mutex};
+
+    fn container(webhook_url: Option<&str>) -> containers::Model {
+        containers::Model {
+            webhook_url: webhook_url.map(|u| u.to_string()),
+            ..containers::Model::test_fixture()
+        }
+    }
+
+    /// Requests received by the fake receiver, with their timestamp and
+    /// signature headers
+    #[derive(Clone, Default)]
+    struct Received {
+        requests: Arc<Mutex<Vec<(Option<String>, Option<String>, serde_json::Value)>>>,
+        failures_left: Arc<Mutex<u32>>,
+    }
+
+    fn settings() -> WebhookSettings {
+        WebhookSettings {
+            secret: "s3cret".to_string(),
+            allow_private: true,
+            retry_delay: Duration::from_millis(1),
+        }
+    }
+
+    async fn serve_receiver(received: Received) -> String {
+        let app = Router::new().route(
+            "/hook",
+            post(
+                move |headers: HeaderMap, axum::Json(body): axum::Json<serde_json::Value>| {
+                    let received = received.clone();
+                    async move {
+                        let header = |name: &str| {
+                            headers
+                                .get(name)
+                                .and_then(|v| v.to_str().ok())
+                                .map(|v| v.to_string())
+                        };
+                        received.requests.lock().unwrap().push((
+                            header(TIMESTAMP_HEADER),
+                            header(SIGNATURE_HEADER),
+                            body,
+                        ));
+                        let mut failures_left = received.failures_left.lock().unwrap();
+                        if *failures_left > 0 {
+                            *failures_left -= 1;
+                            return StatusCode::INTERNAL_SERVER_ERROR;
+                        }
+                        StatusCode::OK
+                    }
+                },
+            ),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        format!("http://{}/hook", addr)
+    }
+
+    #[tokio::test]
+    async fn test_transition_fires_one_signed_webhook() {
+        // Arrange
+        let received = Received::default();
+        let url = serve_receiver(received.clone()).await;
+        let container = container(Some(&url));
+        let (target, payload) = status_webhook(
+            &container,
+            Some("creating"),
+            Some("running"),
+            Some("Pod up"),
+        )
+        .unwrap();
+
+        // Act
+        let delivered = deliver(&target, &payload, &settings()).await;
+
+        // Assert
+        assert!(delivered);
+        let requests = received.requests.lock().unwrap();
+        assert_eq!(requests.len(), 1);
+        let (timestamp, signature, body) = &requests[0];
+        assert_eq!(body["kind"], "ContainerStatusChanged");
+        assert_eq!(body["container_id"], "c1");
+        assert_eq!(body["namespace"], "ns");
+        assert_eq!(body["name"], "train");
+        assert_eq!(body["old_status"], "creating");
+        assert_eq!(body["new_status"], "running");
+        assert_eq!(body["message"], "Pod up");
+        let sent_body = serde_json::to_vec(&payload).unwrap();
+        let timestamp: i64 = timestamp.as_deref().unwrap().parse().unwrap();
+        assert!((chrono::Utc::now().timestamp() - timestamp).abs() < 60);
+        assert_eq!(
+            signature.as_deref(),
+            Some(sign_request("s3cret", timestamp, &sent_body).as_str())
+        );
+        assert_ne!(
+            sign_request("s3cret", timestamp + 1, &sent_body),
+            sign_request("s3cret", timestamp, &sent_body)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_webhook_fires_once_per_transition() {
+        let received = Received::default();
+        let url = serve_receiver(received.clone()).await;
+        let container = container(Some(&url));
+
+        // The statuses a watch records as a container starts, keeps running
+        // and completes
+        let updates = [
+            (Some("pending"), Some("creating")),
+            (Some("creating"), Some("running")),
+            (Some("running"), Some("running")),
+            (Some("running"), None),
+            (Some("running"), Some("completed")),
+            (Some("completed"), Some("completed")),
+        ];
+        for (old, new) in updates {
+            if let Some(task) = send_status_transition(&container, old, new, None, settings()) {
+                assert!(task.await.unwrap());
+            }
+        }
+
+        let requests = received.requests.lock().unwrap();
+        let statuses: Vec<_> = requests
+            .iter()
+            .map(|(_, _, body)| &body["new_status"])
+            .collect();
+        assert_eq!(statuses, ["running", "completed"]);
+    }
+
+    #[tokio::test]
+    async fn test_private_targets_are_refused() {
+        let received = Received::default();
+        let url = serve_receiver(received.clone()).await;
+        let (target, payload) =
+            status_webhook(&container(Some(&url)), None, Some("failed"), None).unwrap();
+        let public_only = WebhookSettings {
+            allow_private: false,
+            ..settings()
+        };
+
+        assert!(!deliver(&target, &payload, &public_only).await);
+        assert!(received.requests.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_redirects_are_not_followed() {
+        let received = Received::default();
+        let hook = serve_receiver(received.clone()).await;
+        let app = Router::new().route(
+            "/redirect",
+            post(move || {
+                let hook = hook.clone();
+                async move { axum::response::Redirect::temporary(&hook) }
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        let url = format!("http://{}/redirect", addr);
+        let (target, payload) =
+            status_webhook(&container(Some(&url)), None, Some("failed"), None).unwrap();
+
+        assert!(!deliver(&target, &payload, &settings()).await);
+        assert!(received.requests.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_failed_delivery_is_retried() {
+        let received = Received::default();
+        *received.failures_left.lock().unwrap() = 2;
+        let url = serve_receiver(received.clone()).await;
+        let (target, payload) =
+            status_webhook(&container(Some(&url)), None, Some("failed"), None).unwrap();
+
+        let delivered = deliver(&target, &payload, &settings()).await;
+
+        assert!(delivered);
+        let requests = received.requests.lock().unwrap();
+        assert_eq!(requests.len(), 3);
+        assert!(requests
+            .iter()
+            .all(|(timestamp, signature, _)| timestamp.is_some() && signature.is_some()));
+    }
+
+    #[test]
+    fn test_only_opted_in_notable_transitions_notify() {
+        let hooked = container(Some("http://example.com/hook"));
+        assert!(status_webhook(&hooked, Some("running"), Some("completed"), None).is_some());
+        assert!(status_webhook(&hooked, Some("pending"), Some("creating"), None).is_none());
+        assert!(status_webhook(&hooked, Some("running"), None, None).is_none());
+        assert!(status_webhook(&hooked, Some("running"), Some("running"), None).is_none());
+        assert!(status_webhook(&container(None), None, Some("running"), None).is_none());
+    }
+
+    #[test]
+    fn test_sign_matches_known_hmac() {
+        // RFC 4231 test case 2
+        assert_eq!(
+            sign("Jefe", b"what do ya want for nothing?"),
+            "sha256=5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+}
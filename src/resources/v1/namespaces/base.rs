@@ -0,0 +1,196 @@
+use crate::resources::v1::containers::base::ContainerStatus;
+use crate::resources::v1::namespaces::models::{V1ContainerSpend, V1NamespaceSpend};
+use std::str::FromStr;
+
+/// The namespace holding the server's base resources, never deletable
+pub const ROOT_NAMESPACE: &str = "root";
+
+/// Names of the resources still living in a namespace.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct NamespaceContents {
+    pub containers: Vec<String>,
+    pub processors: Vec<String>,
+    pub secrets: Vec<String>,
+    /// Volumes other than the namespace's own default volume
+    pub volumes: Vec<String>,
+}
+
+impl NamespaceContents {
+    pub fn is_empty(&self) -> bool {
+        self.containers.is_empty()
+            && self.processors.is_empty()
+            && self.secrets.is_empty()
+            && self.volumes.is_empty()
+    }
+
+    /// Human readable summary, e.g. "2 container(s), 1 secret(s)"
+    pub fn summary(&self) -> String {
+        [
+            ("container", &self.containers),
+            ("processor", &self.processors),
+            ("secret", &self.secrets),
+            ("volume", &self.volumes),
+        ]
+        .iter()
+        .filter(|(_, names)| !names.is_empty())
+        .map(|(kind, names)| format!("{} {}(s)", names.len(), kind))
+        .collect::<Vec<_>>()
+        .join(", ")
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum NamespaceDeletion {
+    /// Nothing left in the namespace, delete it directly
+    Empty,
+    /// Delete the contained resources first
+    Cascade,
+}
+
+#[derive(Debug, thiserror::Error, PartialEq)]
+pub enum NamespaceDeleteError {
+    #[error("Namespace '{0}' is protected and cannot be deleted")]
+    Protected(String),
+
+    #[error("Namespace '{0}' is not empty ({1}); delete its resources first or pass cascade=true")]
+    NotEmpty(String, String),
+}
+
+/// Decides whether a namespace can be deleted and how.
+pub fn plan_deletion(
+    namespace: &str,
+    contents: &NamespaceContents,
+    cascade: bool,
+) -> Result<NamespaceDeletion, NamespaceDeleteError> {
+    if namespace == ROOT_NAMESPACE {
+        return Err(NamespaceDeleteError::Protected(namespace.to_string()));
+    }
+    if contents.is_empty() {
+        return Ok(NamespaceDeletion::Empty);
+    }
+    if !cascade {
+        return Err(NamespaceDeleteError::NotEmpty(
+            namespace.to_string(),
+            contents.summary(),
+        ));
+    }
+    Ok(NamespaceDeletion::Cascade)
+}
+
+/// Whether a container still accrues spend.
+fn is_spending(container: &V1ContainerSpend) -> bool {
+    !container.deleted
+        && container
+            .status
+            .as_deref()
+            .and_then(|s| ContainerStatus::from_str(s).ok())
+            .is_some_and(|s| s.is_active())
+}
+
+/// Splits a namespace's container spend into active and historical totals.
+pub fn summarize_spend(namespace: &str, containers: Vec<V1ContainerSpend>) -> V1NamespaceSpend {
+    let mut summary = V1NamespaceSpend {
+        namespace: namespace.to_string(),
+        ..Default::default()
+    };
+    for container in &containers {
+        if is_spending(container) {
+            summary.active_cost += container.total_cost;
+            summary.cost_per_hr += container.cost_per_hr.unwrap_or(0.0);
+        } else {
+            summary.historical_cost += container.total_cost;
+        }
+    }
+    summary.total_cost = summary.active_cost + summary.historical_cost;
+    summary.containers = containers;
+    summary
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn contents() -> NamespaceContents {
+        NamespaceContents {
+            containers: vec!["web".to_string(), "worker".to_string()],
+            secrets: vec!["token".to_string()],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_plan_deletion_refuses_non_empty_namespace() {
+        // Arrange
+        let contents = contents();
+
+        // Act
+        let result = plan_deletion("team-a", &contents, false);
+
+        // Assert
+        assert_eq!(
+            result,
+            Err(NamespaceDeleteError::NotEmpty(
+                "team-a".to_string(),
+                "2 container(s), 1 secret(s)".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn test_summarize_spend_splits_active_and_historical() {
+        let spend = |name: &str, status: &str, total_cost: f64, deleted: bool| V1ContainerSpend {
+            id: name.to_string(),
+            name: name.to_string(),
+            status: Some(status.to_string()),
+            total_cost,
+            cost_per_hr: Some(0.5),
+            deleted,
+        };
+
+        let summary = summarize_spend(
+            "team-a",
+            vec![
+                spend("train", "running", 2.0, false),
+                spend("done", "completed", 3.0, false),
+                spend("gone", "running", 1.0, true),
+            ],
+        );
+
+        assert_eq!(summary.active_cost, 2.0);
+        assert_eq!(summary.historical_cost, 4.0);
+        assert_eq!(summary.total_cost, 6.0);
+        assert_eq!(summary.cost_per_hr, 0.5);
+        assert_eq!(summary.containers.len(), 3);
+    }
+
+    #[test]
+    fn test_plan_deletion_cascades_when_requested() {
+        assert_eq!(
+            plan_deletion("team-a", &contents(), true),
+            Ok(NamespaceDeletion::Cascade)
+        );
+    }
+
+    #[test]
+    fn test_plan_deletion_empty_namespace() {
+        let empty = NamespaceContents::default();
+        assert_eq!(
+            plan_deletion("team-a", &empty, false),
+            Ok(NamespaceDeletion::Empty)
+        );
+        assert_eq!(
+            plan_deletion("team-a", &empty, true),
+            Ok(NamespaceDeletion::Empty)
+        );
+    }
+
+    #[test]
+    fn test_plan_deletion_protects_root() {
+        let expected = Err(NamespaceDeleteError::Protected("root".to_string()));
+        assert_eq!(
+            plan_deletion(ROOT_NAMESPACE, &NamespaceContents::default(), false),
+            expected
+        );
+        assert_eq!(plan_deletion(ROOT_NAMESPACE, &contents(), true), expected);
+    }
+}
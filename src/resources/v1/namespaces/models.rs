@@ -1,4 +1,5 @@
 use crate::models::{V1ResourceMeta, V1ResourceMetaRequest};
+use crate::resources::v1::containers::models::V1EnvVar;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 #[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
@@ -6,11 +7,73 @@ pub struct V1Namespace {
     #[serde(default = "default_namespace_kind")]
     pub kind: String,
     pub metadata: V1ResourceMeta,
+    /// Env applied to every container in the namespace, under the container's own env
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_env: Option<Vec<V1EnvVar>>,
+    /// User to run as in containers whose image and request don't set one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_user: Option<String>,
+    /// Secret in the namespace holding the credentials images are pulled with,
+    /// as `{"registry": "ghcr.io", "username": "..", "password": ".."}`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub registry_secret: Option<String>,
+    /// Platform containers run on when the request names none
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_platform: Option<String>,
+    /// Accelerators containers get when the request asks for none
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_accelerators: Option<Vec<String>>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
 pub struct V1NamespaceRequest {
     pub metadata: V1NamespaceMetaRequest,
+    #[serde(default)]
+    pub default_env: Option<Vec<V1EnvVar>>,
+    #[serde(default)]
+    pub default_user: Option<String>,
+    #[serde(default)]
+    pub registry_secret: Option<String>,
+    #[serde(default)]
+    pub default_platform: Option<String>,
+    #[serde(default)]
+    pub default_accelerators: Option<Vec<String>>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct V1UpdateNamespace {
+    pub labels: Option<HashMap<String, String>>,
+    pub default_env: Option<Vec<V1EnvVar>>,
+    pub default_user: Option<String>,
+    pub registry_secret: Option<String>,
+    pub default_platform: Option<String>,
+    pub default_accelerators: Option<Vec<String>>,
+}
+
+/// Spend recorded for one container in a namespace.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct V1ContainerSpend {
+    pub id: String,
+    pub name: String,
+    pub status: Option<String>,
+    pub total_cost: f64,
+    pub cost_per_hr: Option<f64>,
+    /// Soft-deleted containers still count towards historical spend
+    pub deleted: bool,
+}
+
+/// Spend across a namespace's containers, in dollars.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct V1NamespaceSpend {
+    pub namespace: String,
+    /// Spent by containers that are still running or starting
+    pub active_cost: f64,
+    /// Spent by containers that finished, failed, stopped or were deleted
+    pub historical_cost: f64,
+    pub total_cost: f64,
+    /// What the active containers currently cost per hour together
+    pub cost_per_hr: f64,
+    pub containers: Vec<V1ContainerSpend>,
 }
 
 fn default_namespace_kind() -> String {
@@ -24,6 +87,14 @@ pub struct V1NamespaceMetaRequest {
     pub owner: Option<String>,
 }
 
+/// Query parameters accepted by the namespace delete endpoint
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct V1DeleteNamespaceParams {
+    /// Delete all resources in the namespace along with it
+    #[serde(default)]
+    pub cascade: bool,
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
 pub struct V1Namespaces {
     pub namespaces: Vec<V1Namespace>,
@@ -4,9 +4,11 @@ use crate::resources::v1::processors::base::ProcessorPlatform;
 use crate::resources::v1::processors::standard::StandardProcessor;
 use crate::state::AppState;
 use crate::state::MessageQueue;
+use crate::streams::redis::{dead_letter_entries, dead_letter_stream, reclaim_idle_entries};
+use redis::Commands;
 use std::sync::Arc;
 use tokio::task::JoinHandle;
-use tracing::{debug, error, info};
+use tracing::{debug, error, info, warn};
 
 use dashmap::DashMap;
 use once_cell::sync::Lazy;
@@ -119,16 +121,25 @@ impl ProcessorController {
                         );
                         continue;
                     }
+                    let redis_client = match self
+                        .app_state
+                        .processor_redis(processor.queue_backend.as_deref())
+                    {
+                        Ok(client) => client.clone(),
+                        Err(e) => {
+                            error!(
+                                "[Processor Controller] Can't reconcile processor {}: {}",
+                                processor.id, e
+                            );
+                            continue;
+                        }
+                    };
                     let app_state = Arc::clone(&self.app_state);
                     let processor_clone = processor.clone();
 
                     // Actually spawn a background task
                     let handle = tokio::spawn({
                         let db_pool = self.app_state.db_pool.clone();
-                        let redis_client = match &self.app_state.message_queue {
-                            MessageQueue::Redis { client } => client.clone(),
-                            _ => panic!("Redis client not found in app state"),
-                        };
                         async move {
                             info!(
                                 "[Processor Controller] Reconciling processor {} in background task",
@@ -213,4 +224,111 @@ impl ProcessorController {
             }
         })
     }
+
+    /// Spawns a background Tokio task that hard deletes soft-deleted processors
+    /// once they are older than the configured retention window
+    pub fn spawn_purger(&self) -> tokio::task::JoinHandle<()> {
+        let app_state_clone = Arc::clone(&self.app_state);
+
+        tokio::spawn(async move {
+            loop {
+                let retention =
+                    chrono::Duration::from_std(crate::config::SERVER_CONFIG.deleted_retention)
+                        .unwrap_or(chrono::Duration::days(7));
+                let older_than = chrono::Utc::now() - retention;
+
+                match crate::mutation::Mutation::purge_deleted_processors(
+                    &app_state_clone.db_pool,
+                    older_than,
+                )
+                .await
+                {
+                    Ok(0) => debug!("[Processor Controller] No deleted processors to purge"),
+                    Ok(count) => {
+                        info!("[Processor Controller] Purged {} deleted processors", count)
+                    }
+                    Err(e) => error!(
+                        "[Processor Controller] Failed to purge deleted processors: {:?}",
+                        e
+                    ),
+                }
+
+                tokio::time::sleep(tokio::time::Duration::from_secs(60 * 60)).await;
+            }
+        })
+    }
+
+    /// Spawns a background Tokio task that reclaims stream entries left
+    /// unacknowledged by crashed consumers, so other readers pick them up
+    pub fn spawn_reclaimer(&self) -> tokio::task::JoinHandle<()> {
+        let app_state_clone = Arc::clone(&self.app_state);
+
+        tokio::spawn(async move {
+            loop {
+                if let Err(e) = Self::reclaim_pass(&app_state_clone).await {
+                    error!(
+                        "[Processor Controller] Failed to reclaim idle stream entries: {:?}",
+                        e
+                    );
+                }
+
+                tokio::time::sleep(tokio::time::Duration::from_secs(30)).await;
+            }
+        })
+    }
+
+    /// One reclaim pass over every consumer group of every active processor.
+    async fn reclaim_pass(
+        app_state: &AppState,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let min_idle_ms = crate::config::SERVER_CONFIG.stream_reclaim_idle.as_millis() as u64;
+        let max_deliveries = crate::config::SERVER_CONFIG.stream_max_deliveries;
+        let processors = Query::find_all_active_processors(&app_state.db_pool).await?;
+
+        for processor in processors {
+            // Kafka commits what it hands out, so only Redis has entries to reclaim
+            let Ok(MessageQueue::Redis { client }) =
+                app_state.queue(processor.queue_backend.as_deref())
+            else {
+                continue;
+            };
+            let mut conn = client.get_connection()?;
+            let groups: redis::streams::StreamInfoGroupsReply =
+                match conn.xinfo_groups(&processor.stream) {
+                    Ok(groups) => groups,
+                    // Nothing has been sent to the processor yet
+                    Err(_) => continue,
+                };
+            for group in groups.groups {
+                match reclaim_idle_entries(&mut conn, &processor.stream, &group.name, min_idle_ms) {
+                    Ok(0) => {}
+                    Ok(count) => info!(
+                        "[Processor Controller] Reclaimed {} idle entries on {} for group {}",
+                        count, processor.stream, group.name
+                    ),
+                    Err(e) => error!(
+                        "[Processor Controller] Failed to reclaim entries on {} for group {}: {:?}",
+                        processor.stream, group.name, e
+                    ),
+                }
+                match dead_letter_entries(&mut conn, &processor.stream, &group.name, max_deliveries)
+                {
+                    Ok(0) => {}
+                    Ok(count) => warn!(
+                        "[Processor Controller] Moved {} entries of {} for group {} to {} after {} deliveries",
+                        count,
+                        processor.stream,
+                        group.name,
+                        dead_letter_stream(&processor.stream),
+                        max_deliveries
+                    ),
+                    Err(e) => error!(
+                        "[Processor Controller] Failed to dead-letter entries on {} for group {}: {:?}",
+                        processor.stream, group.name, e
+                    ),
+                }
+            }
+        }
+        Ok(())
+    }
 }
@@ -0,0 +1,209 @@
+// src/resources/v1/processors/health.rs
+//
+// Health across a processor's replicas. The health stream is answered by
+// whichever replica picks the request up, so each replica's own health comes
+// from its container's status instead, and the overall status reflects how
+// many of them are serving.
+
+use crate::entities::containers;
+use crate::resources::v1::containers::base::ContainerStatus;
+use crate::resources::v1::processors::models::{V1ProcessorHealthResponse, V1ReplicaHealth};
+use std::str::FromStr;
+
+pub const REPLICA_HEALTHY: &str = "healthy";
+pub const REPLICA_UNHEALTHY: &str = "unhealthy";
+
+/// How long a health check waits for an answer when the request doesn't say
+pub const DEFAULT_HEALTH_CHECK_TIMEOUT_MS: u64 = 30_000;
+
+/// Longest a health check may wait, so a request can't hold a connection open
+pub const MAX_HEALTH_CHECK_TIMEOUT_MS: u64 = 120_000;
+
+/// Some replicas are healthy, some aren't
+pub const PROCESSOR_DEGRADED: &str = "degraded";
+/// No replica is healthy
+pub const PROCESSOR_UNHEALTHY: &str = "unhealthy";
+
+/// The wait for a health check, from the requested `timeout_ms`. Zero would
+/// block forever, so it's rejected along with anything above the maximum.
+pub fn health_check_timeout_ms(requested: Option<u64>) -> Result<u64, String> {
+    match requested {
+        None => Ok(DEFAULT_HEALTH_CHECK_TIMEOUT_MS),
+        Some(0) => Err("timeout_ms must be greater than 0".to_string()),
+        Some(ms) if ms > MAX_HEALTH_CHECK_TIMEOUT_MS => Err(format!(
+            "timeout_ms must be at most {}",
+            MAX_HEALTH_CHECK_TIMEOUT_MS
+        )),
+        Some(ms) => Ok(ms),
+    }
+}
+
+/// A replica is healthy when its container is running and, if it has a
+/// health check, that check has marked it ready.
+pub fn replica_health(container: &containers::Model) -> V1ReplicaHealth {
+    let status = container.parse_status().ok().flatten();
+    let container_status = status.as_ref().and_then(|s| s.status.clone());
+    let ready = status.as_ref().and_then(|s| s.ready);
+    let running = container_status
+        .as_deref()
+        .and_then(|s| ContainerStatus::from_str(s).ok())
+        == Some(ContainerStatus::Running);
+    let healthy = running
+        && if container.health_check.is_some() {
+            ready == Some(true)
+        } else {
+            ready != Some(false)
+        };
+
+    V1ReplicaHealth {
+        container_id: container.id.clone(),
+        name: container.name.clone(),
+        status: if healthy {
+            REPLICA_HEALTHY
+        } else {
+            REPLICA_UNHEALTHY
+        }
+        .to_string(),
+        container_status,
+        ready,
+        message: status.and_then(|s| s.message),
+    }
+}
+
+/// The processor's status given the status its health stream answered with.
+/// With every replica healthy that answer stands; otherwise the processor is
+/// degraded, or unhealthy when none are. Without replica information the
+/// answer is all there is.
+pub fn overall_status(answered: &str, replicas: &[V1ReplicaHealth]) -> String {
+    let healthy = replicas
+        .iter()
+        .filter(|r| r.status == REPLICA_HEALTHY)
+        .count();
+    if replicas.is_empty() || healthy == replicas.len() {
+        answered.to_string()
+    } else if healthy == 0 {
+        PROCESSOR_UNHEALTHY.to_string()
+    } else {
+        PROCESSOR_DEGRADED.to_string()
+    }
+}
+
+/// Folds replica health into the answer from the health stream.
+pub fn with_replica_health(
+    mut response: V1ProcessorHealthResponse,
+    replicas: Vec<V1ReplicaHealth>,
+) -> V1ProcessorHealthResponse {
+    response.status = overall_status(&response.status, &replicas);
+    response.replicas = Some(replicas);
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn container(
+        id: &str,
+        status: Option<&str>,
+        ready: Option<bool>,
+        checked: bool,
+    ) -> containers::Model {
+        containers::Model {
+            id: id.to_string(),
+            name: format!("echo-{}", id),
+            full_name: format!("ns/echo-{}", id),
+            owner_ref: Some("echo.ns.Processor".to_string()),
+            status: status.map(|s| json!({"status": s, "ready": ready, "message": null})),
+            restart: "Always".to_string(),
+            health_check: checked.then(|| json!({"path": "/health"})),
+            ..containers::Model::test_fixture()
+        }
+    }
+
+    fn answered() -> V1ProcessorHealthResponse {
+        V1ProcessorHealthResponse {
+            status: "ok".to_string(),
+            message: Some("consumer up".to_string()),
+            details: None,
+            replicas: None,
+        }
+    }
+
+    #[test]
+    fn test_mixed_replicas_are_degraded() {
+        // Arrange
+        let replicas: Vec<V1ReplicaHealth> = [
+            container("a", Some("running"), Some(true), true),
+            container("b", Some("running"), None, true),
+            container("c", Some("failed"), Some(false), false),
+        ]
+        .iter()
+        .map(replica_health)
+        .collect();
+
+        // Act
+        let response = with_replica_health(answered(), replicas);
+
+        // Assert
+        assert_eq!(response.status, PROCESSOR_DEGRADED);
+        assert_eq!(response.message.as_deref(), Some("consumer up"));
+        let statuses: Vec<(&str, &str)> = response
+            .replicas
+            .as_ref()
+            .unwrap()
+            .iter()
+            .map(|r| (r.container_id.as_str(), r.status.as_str()))
+            .collect();
+        assert_eq!(
+            statuses,
+            vec![
+                ("a", REPLICA_HEALTHY),
+                ("b", REPLICA_UNHEALTHY),
+                ("c", REPLICA_UNHEALTHY)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_all_or_no_healthy_replicas() {
+        let healthy: Vec<V1ReplicaHealth> = [
+            container("a", Some("running"), Some(true), true),
+            container("b", Some("running"), None, false),
+        ]
+        .iter()
+        .map(replica_health)
+        .collect();
+        assert_eq!(with_replica_health(answered(), healthy).status, "ok");
+
+        let unhealthy: Vec<V1ReplicaHealth> = [
+            container("a", Some("pending"), None, false),
+            container("b", None, None, false),
+            container("c", Some("running"), Some(false), false),
+        ]
+        .iter()
+        .map(replica_health)
+        .collect();
+        assert_eq!(
+            with_replica_health(answered(), unhealthy).status,
+            PROCESSOR_UNHEALTHY
+        );
+
+        assert_eq!(with_replica_health(answered(), Vec::new()).status, "ok");
+    }
+
+    #[test]
+    fn test_health_check_timeout_bounds() {
+        assert_eq!(
+            health_check_timeout_ms(None),
+            Ok(DEFAULT_HEALTH_CHECK_TIMEOUT_MS)
+        );
+        assert_eq!(health_check_timeout_ms(Some(500)), Ok(500));
+        assert_eq!(
+            health_check_timeout_ms(Some(MAX_HEALTH_CHECK_TIMEOUT_MS)),
+            Ok(MAX_HEALTH_CHECK_TIMEOUT_MS)
+        );
+        assert!(health_check_timeout_ms(Some(0)).is_err());
+        assert!(health_check_timeout_ms(Some(MAX_HEALTH_CHECK_TIMEOUT_MS + 1)).is_err());
+    }
+}
@@ -0,0 +1,347 @@
+// src/resources/v1/processors/logs.rs
+//
+// Logs of the containers serving a processor. Each container's logs are
+// narrowed with `tail_lines`/`since` where they're read and then cut to
+// `max_bytes`, so one chatty replica can't make the response enormous.
+// `/logs/pages` reads a page of containers at a time; `/logs` keeps its
+// original shape, a flat map of every container's logs.
+
+use crate::entities::containers;
+use crate::resources::v1::containers::models::V1LogParams;
+use crate::resources::v1::processors::models::{
+    V1ContainerLogs, V1ProcessorLogs, V1ProcessorLogsParams,
+};
+use std::future::Future;
+
+/// Bytes kept of each container's logs unless the request says otherwise
+pub const DEFAULT_LOG_MAX_BYTES: usize = 256 * 1024;
+/// Most bytes a request may ask for per container
+pub const MAX_LOG_MAX_BYTES: usize = 4 * 1024 * 1024;
+/// Containers read per page unless the request says otherwise
+pub const DEFAULT_LOG_CONTAINERS: usize = 10;
+/// Most containers a page may read
+pub const MAX_LOG_CONTAINERS: usize = 50;
+
+/// The limits of a logs request, once checked
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LogLimits {
+    /// Bytes kept of each container's logs
+    pub max_bytes: usize,
+    /// Containers read at once
+    pub limit: usize,
+}
+
+/// Checks the limits in `params`
+pub fn log_limits(params: &V1ProcessorLogsParams) -> Result<LogLimits, String> {
+    let max_bytes = params.max_bytes.unwrap_or(DEFAULT_LOG_MAX_BYTES);
+    if max_bytes == 0 || max_bytes > MAX_LOG_MAX_BYTES {
+        return Err(format!(
+            "max_bytes must be between 1 and {}",
+            MAX_LOG_MAX_BYTES
+        ));
+    }
+    let limit = params.limit.unwrap_or(DEFAULT_LOG_CONTAINERS);
+    if limit == 0 || limit > MAX_LOG_CONTAINERS {
+        return Err(format!(
+            "limit must be between 1 and {}",
+            MAX_LOG_CONTAINERS
+        ));
+    }
+    Ok(LogLimits { max_bytes, limit })
+}
+
+/// The last `lines` lines of `logs`
+fn tail(logs: &str, lines: u64) -> &str {
+    if lines == 0 {
+        return "";
+    }
+    let trimmed = logs.strip_suffix('\n').unwrap_or(logs);
+    match trimmed.rmatch_indices('\n').nth(lines as usize - 1) {
+        Some((i, _)) => &logs[i + 1..],
+        None => logs,
+    }
+}
+
+/// The end of `logs` within `max_bytes`, starting on a char boundary, and
+/// whether anything was cut
+fn keep_end(logs: &str, max_bytes: usize) -> (&str, bool) {
+    if logs.len() <= max_bytes {
+        return (logs, false);
+    }
+    let mut start = logs.len() - max_bytes;
+    while !logs.is_char_boundary(start) {
+        start += 1;
+    }
+    (&logs[start..], true)
+}
+
+/// Name a container's logs are listed under
+fn log_key(container: &containers::Model) -> String {
+    if container.name.is_empty() {
+        container.id.clone()
+    } else {
+        container.name.clone()
+    }
+}
+
+/// One container's logs as read, or why they couldn't be
+struct ContainerRead {
+    key: String,
+    container_id: String,
+    logs: Result<V1ContainerLogs, String>,
+}
+
+/// Reads the logs of `containers` with `fetch`, concurrently. Platforms that
+/// can't tail return everything, so `tail_lines` is applied again here.
+async fn read_logs<F, Fut>(
+    containers: Vec<containers::Model>,
+    params: &V1ProcessorLogsParams,
+    max_bytes: usize,
+    fetch: &F,
+) -> Vec<ContainerRead>
+where
+    F: Fn(containers::Model, V1LogParams) -> Fut,
+    Fut: Future<Output = Result<String, String>>,
+{
+    let log_params = V1LogParams {
+        tail_lines: params.tail_lines,
+        since: params.since,
+    };
+    futures::future::join_all(containers.into_iter().map(|container| {
+        let key = log_key(&container);
+        let container_id = container.id.clone();
+        let read = fetch(container, log_params.clone());
+        async move {
+            let logs = read.await.map(|logs| {
+                let logs = match params.tail_lines {
+                    Some(lines) => tail(&logs, lines),
+                    None => &logs,
+                };
+                let (logs, truncated) = keep_end(logs, max_bytes);
+                V1ContainerLogs {
+                    container_id: container_id.clone(),
+                    logs: logs.to_string(),
+                    truncated,
+                }
+            });
+            ContainerRead {
+                key,
+                container_id,
+                logs,
+            }
+        }
+    }))
+    .await
+}
+
+/// Reads the logs of one page of `containers`, in name order. Containers
+/// past the page aren't read at all.
+pub async fn collect_container_logs<F, Fut>(
+    mut containers: Vec<containers::Model>,
+    params: &V1ProcessorLogsParams,
+    limits: LogLimits,
+    fetch: F,
+) -> V1ProcessorLogs
+where
+    F: Fn(containers::Model, V1LogParams) -> Fut,
+    Fut: Future<Output = Result<String, String>>,
+{
+    let offset = params.offset.unwrap_or(0);
+    containers.sort_by_key(log_key);
+    let total = containers.len();
+
+    let page: Vec<containers::Model> = containers
+        .into_iter()
+        .skip(offset)
+        .take(limits.limit)
+        .collect();
+    let mut response = V1ProcessorLogs {
+        next_offset: Some(offset + limits.limit).filter(|next| *next < total),
+        ..Default::default()
+    };
+    for read in read_logs(page, params, limits.max_bytes, &fetch).await {
+        match read.logs {
+            Ok(logs) => {
+                response.logs.insert(read.key, logs);
+            }
+            Err(e) => {
+                response.errors.insert(read.key, e);
+            }
+        }
+    }
+    response
+}
+
+/// Reads the logs of all `containers`, `limit` at a time, in the shape
+/// `/logs` has always returned: logs by container name, and
+/// `{"error": ...}` by container id for the ones that couldn't be read.
+/// `offset` doesn't apply.
+pub async fn collect_all_container_logs<F, Fut>(
+    containers: Vec<containers::Model>,
+    params: &V1ProcessorLogsParams,
+    limits: LogLimits,
+    fetch: F,
+) -> serde_json::Map<String, serde_json::Value>
+where
+    F: Fn(containers::Model, V1LogParams) -> Fut,
+    Fut: Future<Output = Result<String, String>>,
+{
+    let mut response = serde_json::Map::new();
+    let mut containers = containers.into_iter().peekable();
+    while containers.peek().is_some() {
+        let chunk: Vec<containers::Model> = containers.by_ref().take(limits.limit).collect();
+        for read in read_logs(chunk, params, limits.max_bytes, &fetch).await {
+            match read.logs {
+                Ok(logs) => {
+                    response.insert(read.key, serde_json::Value::String(logs.logs));
+                }
+                Err(e) => {
+                    response.insert(read.container_id, serde_json::json!({ "error": e }));
+                }
+            }
+        }
+    }
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    fn container(id: &str, name: &str) -> containers::Model {
+        containers::Model {
+            id: id.to_string(),
+            name: name.to_string(),
+            full_name: format!("ns/{}", name),
+            owner_ref: Some("proc.ns.Processor".to_string()),
+            ..containers::Model::test_fixture()
+        }
+    }
+
+    /// Logs of 100 numbered lines, failing for containers named `broken`
+    async fn fake_logs(container: containers::Model) -> Result<String, String> {
+        if container.name == "broken" {
+            return Err("SSH unavailable".to_string());
+        }
+        Ok((1..=100)
+            .map(|i| format!("{} line {}\n", container.name, i))
+            .collect())
+    }
+
+    #[tokio::test]
+    async fn test_tail_limits_apply_to_every_container() {
+        // Arrange
+        let containers = vec![
+            container("c2", "replica-b"),
+            container("c1", "replica-a"),
+            container("c3", "broken"),
+        ];
+        let requested = Arc::new(Mutex::new(Vec::new()));
+        let params = V1ProcessorLogsParams {
+            tail_lines: Some(3),
+            since: Some(1700000000),
+            ..Default::default()
+        };
+
+        // Act
+        let limits = log_limits(&params).unwrap();
+        let logs = collect_container_logs(containers, &params, limits, |container, log_params| {
+            requested.lock().unwrap().push(log_params);
+            fake_logs(container)
+        })
+        .await;
+
+        // Assert
+        assert_eq!(
+            logs.logs["replica-a"].logs,
+            "replica-a line 98\nreplica-a line 99\nreplica-a line 100\n"
+        );
+        assert_eq!(logs.logs["replica-b"].container_id, "c2");
+        assert_eq!(logs.logs["replica-b"].logs.lines().count(), 3);
+        assert!(!logs.logs["replica-b"].truncated);
+        assert_eq!(logs.errors["broken"], "SSH unavailable");
+        assert_eq!(logs.next_offset, None);
+        // Passed through to each container's read
+        assert!(requested.lock().unwrap().iter().all(|p| p
+            == &V1LogParams {
+                tail_lines: Some(3),
+                since: Some(1700000000),
+            }));
+    }
+
+    #[tokio::test]
+    async fn test_pages_and_size_limits() {
+        let containers = (0..5)
+            .map(|i| container(&format!("c{}", i), &format!("replica-{}", i)))
+            .collect::<Vec<_>>();
+        let read = Arc::new(Mutex::new(Vec::new()));
+        let params = V1ProcessorLogsParams {
+            max_bytes: Some(40),
+            offset: Some(2),
+            limit: Some(2),
+            ..Default::default()
+        };
+
+        let limits = log_limits(&params).unwrap();
+        let logs = collect_container_logs(containers, &params, limits, |container, _| {
+            read.lock().unwrap().push(container.name.clone());
+            fake_logs(container)
+        })
+        .await;
+
+        let mut read = read.lock().unwrap().clone();
+        read.sort();
+        assert_eq!(read, vec!["replica-2", "replica-3"]);
+        assert_eq!(logs.next_offset, Some(4));
+        let replica = &logs.logs["replica-2"];
+        assert!(replica.truncated);
+        assert!(replica.logs.len() <= 40);
+        assert!(replica.logs.ends_with("replica-2 line 100\n"));
+
+        let too_much = V1ProcessorLogsParams {
+            max_bytes: Some(MAX_LOG_MAX_BYTES + 1),
+            ..Default::default()
+        };
+        assert!(log_limits(&too_much).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_all_logs_keep_the_original_shape() {
+        let mut containers = (0..3)
+            .map(|i| container(&format!("c{}", i), &format!("replica-{}", i)))
+            .collect::<Vec<_>>();
+        containers.push(container("c9", "broken"));
+        let params = V1ProcessorLogsParams {
+            tail_lines: Some(1),
+            limit: Some(2),
+            ..Default::default()
+        };
+
+        let limits = log_limits(&params).unwrap();
+        let logs = collect_all_container_logs(containers, &params, limits, |container, _| {
+            fake_logs(container)
+        })
+        .await;
+
+        assert_eq!(
+            serde_json::Value::Object(logs),
+            serde_json::json!({
+                "replica-0": "replica-0 line 100\n",
+                "replica-1": "replica-1 line 100\n",
+                "replica-2": "replica-2 line 100\n",
+                "c9": { "error": "SSH unavailable" },
+            })
+        );
+    }
+
+    #[test]
+    fn test_tail_and_keep_end() {
+        assert_eq!(tail("a\nb\nc\n", 2), "b\nc\n");
+        assert_eq!(tail("a\nb\nc", 2), "b\nc");
+        assert_eq!(tail("a\nb\n", 5), "a\nb\n");
+        assert_eq!(tail("a\nb\n", 0), "");
+        assert_eq!(keep_end("héllo", 4), ("llo", true));
+        assert_eq!(keep_end("hello", 10), ("hello", false));
+    }
+}
@@ -1,7 +1,12 @@
 pub mod base;
 pub mod controller;
 pub mod factory;
+pub mod health;
+pub mod logs;
 pub mod models;
 pub mod standard;
+pub mod streams;
+pub mod topics;
+pub mod warmup;
 
 pub use models::*;
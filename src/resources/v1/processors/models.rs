@@ -1,5 +1,6 @@
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
 use serde_json::Value;
+use std::collections::BTreeMap;
 
 use crate::models::{V1ResourceMeta, V1ResourceMetaRequest, V1ResourceReference};
 use crate::resources::v1::containers::models::V1ContainerRequest;
@@ -9,6 +10,12 @@ pub struct V1ProcessorStatus {
     pub status: Option<String>,
     pub message: Option<String>,
     pub pressure: Option<i32>,
+    /// Replicas with a running container, see `min_replicas`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub warm_replicas: Option<i32>,
+    /// Warm replicas whose container reports ready
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ready_replicas: Option<i32>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
@@ -42,12 +49,26 @@ pub struct V1Processor {
     pub metadata: V1ResourceMeta,
     pub container: Option<V1ContainerRequest>,
     pub stream: String,
+    /// Approximate cap on entries kept in the processor's streams, not set
+    /// for processors declared before streams were capped
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stream_max_len: Option<u64>,
+    /// Message queue backend the processor's streams live on, the server's
+    /// default when not set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub queue_backend: Option<String>,
     pub schema: Option<Value>,
     pub common_schema: Option<String>,
     pub min_replicas: Option<i32>,
     pub max_replicas: Option<i32>,
     pub scale: Option<V1Scale>,
+    /// Content sent to the processor's stream as each replica comes up
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub warmup_message: Option<Value>,
     pub status: Option<V1ProcessorStatus>,
+    /// The processor's stream as Redis reports it, with `include_stream_info`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stream_info: Option<V1ProcessorStreamInfo>,
 }
 
 impl V1Processor {
@@ -72,6 +93,19 @@ pub struct V1ProcessorRequest {
     pub min_replicas: Option<i32>,
     pub max_replicas: Option<i32>,
     pub scale: Option<V1Scale>,
+    /// Approximate cap on entries kept in the processor's streams, older
+    /// entries are trimmed as new ones are added. Defaults to 10,000.
+    #[serde(default)]
+    pub stream_max_len: Option<u64>,
+    /// Message queue backend for the processor's streams, `redis` or
+    /// `kafka`. Must be configured on the server; defaults to its own.
+    #[serde(default)]
+    pub queue_backend: Option<String>,
+    /// Content sent to the processor's stream as each replica comes up, so
+    /// caches are primed before real messages arrive. With `min_replicas`,
+    /// that many replicas are provisioned as soon as the processor is declared.
+    #[serde(default)]
+    pub warmup_message: Option<Value>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, Default)]
@@ -96,9 +130,27 @@ pub struct V1UpdateProcessor {
     pub scale: Option<V1Scale>,
     pub schema: Option<Value>,
     pub common_schema: Option<String>,
+    #[serde(default)]
+    pub stream_max_len: Option<u64>,
+    /// Left as is when missing, cleared by `null`
+    #[serde(
+        default,
+        deserialize_with = "present",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub warmup_message: Option<Option<Value>>,
     pub no_delete: Option<bool>,
 }
 
+/// Tells a field set to `null`, `Some(None)`, from a missing one, `None`
+fn present<'de, D, T>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    Option::<T>::deserialize(deserializer).map(Some)
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct V1ReadStreamRequest {
     pub consumer_group: String,
@@ -106,6 +158,130 @@ pub struct V1ReadStreamRequest {
     pub max_records: u64,
     #[serde(default = "default_wait_time_ms")]
     pub wait_time_ms: u64,
+    /// Acknowledge messages as they are read instead of through the ack endpoint
+    #[serde(default)]
+    pub auto_ack: bool,
+}
+
+/// Query parameters for peeking at a processor's stream without a consumer group
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct V1PeekStreamParams {
+    /// Only messages after this `entry_id`, from the start of the stream without one
+    pub after: Option<String>,
+    #[serde(default = "default_max_records")]
+    pub max_records: u64,
+    #[serde(default = "default_wait_time_ms")]
+    pub wait_time_ms: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct V1AckStreamRequest {
+    pub consumer_group: String,
+    /// `entry_id`s of the messages returned by a stream read
+    pub entry_ids: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct V1AckStreamResponse {
+    pub acknowledged: u64,
+}
+
+/// Query parameters accepted by the get processor endpoint
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct V1GetProcessorParams {
+    /// Include soft-deleted processors (admins only)
+    #[serde(default)]
+    pub include_deleted: bool,
+    /// Query the processor's stream for its length, groups and offsets
+    #[serde(default)]
+    pub include_stream_info: bool,
+}
+
+/// Where external producers write to a processor and how far its readers
+/// have got
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct V1ProcessorStreamInfo {
+    pub name: String,
+    pub length: u64,
+    /// Id of the newest entry, None when nothing was ever sent
+    pub last_generated_id: Option<String>,
+    pub groups: Vec<V1StreamGroupInfo>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct V1StreamGroupInfo {
+    pub name: String,
+    pub consumers: u64,
+    /// Delivered but not yet acknowledged
+    pub pending: u64,
+    pub last_delivered_id: String,
+    /// Not yet delivered to the group
+    pub lag: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct V1PendingParams {
+    /// Defaults to the processor's own consumer group
+    pub consumer_group: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct V1HealthCheckParams {
+    /// How long to wait for a replica to answer, in milliseconds
+    pub timeout_ms: Option<u64>,
+}
+
+/// Narrows and pages the logs of a processor's containers
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct V1ProcessorLogsParams {
+    /// Only the last this many lines of each container's logs
+    pub tail_lines: Option<u64>,
+    /// Unix timestamp; only what each container logged since
+    pub since: Option<i64>,
+    /// Bytes kept of each container's logs, counted from the end
+    pub max_bytes: Option<usize>,
+    /// Containers to skip, in name order
+    pub offset: Option<usize>,
+    /// Containers to read logs from
+    pub limit: Option<usize>,
+}
+
+/// The logs of one container, possibly cut down to `max_bytes`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct V1ContainerLogs {
+    pub container_id: String,
+    pub logs: String,
+    /// Whether the start of the logs was left out to fit `max_bytes`
+    pub truncated: bool,
+}
+
+/// A page of a processor's container logs, keyed by container name
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct V1ProcessorLogs {
+    pub logs: BTreeMap<String, V1ContainerLogs>,
+    /// Why reading a container's logs failed, by container name
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub errors: BTreeMap<String, String>,
+    /// The `offset` of the next page, if there are more containers
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub next_offset: Option<usize>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct V1ConsumerPending {
+    pub name: String,
+    pub pending: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct V1StreamPending {
+    pub stream: String,
+    pub consumer_group: String,
+    /// Delivered but not yet acknowledged
+    pub pending: u64,
+    /// Not yet delivered to the group
+    pub lag: u64,
+    pub consumers: Vec<V1ConsumerPending>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -115,6 +291,22 @@ pub struct V1ProcessorHealthResponse {
     pub message: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub details: Option<Value>,
+    /// Health of each container serving the processor
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub replicas: Option<Vec<V1ReplicaHealth>>,
+}
+
+/// Health of one processor replica, judged from its container's status
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct V1ReplicaHealth {
+    pub container_id: String,
+    pub name: String,
+    /// `healthy` or `unhealthy`
+    pub status: String,
+    pub container_status: Option<String>,
+    pub ready: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
 }
 
 fn default_processor_kind() -> String {
@@ -128,3 +320,24 @@ fn default_max_records() -> u64 {
 fn default_wait_time_ms() -> u64 {
     1000
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_update_tells_cleared_warmup_message_from_missing() {
+        let update = |body: Value| -> V1UpdateProcessor { serde_json::from_value(body).unwrap() };
+
+        assert_eq!(update(json!({})).warmup_message, None);
+        assert_eq!(
+            update(json!({"warmup_message": null})).warmup_message,
+            Some(None)
+        );
+        assert_eq!(
+            update(json!({"warmup_message": {"prompt": "hi"}})).warmup_message,
+            Some(Some(json!({"prompt": "hi"})))
+        );
+    }
+}
@@ -15,6 +15,11 @@ use crate::resources::v1::processors::base::{ProcessorPlatform, ProcessorStatus}
 use crate::resources::v1::processors::models::{
     V1Processor, V1ProcessorRequest, V1ProcessorStatus,
 };
+use crate::resources::v1::processors::streams::DEFAULT_STREAM_MAX_LEN;
+use crate::resources::v1::processors::topics;
+use crate::resources::v1::processors::warmup::{
+    provision_warm_replicas, replica_counts, send_warmup_messages,
+};
 use crate::state::MessageQueue;
 use crate::streams::redis::get_consumer_group_progress;
 use crate::AppState;
@@ -128,18 +133,21 @@ impl StandardProcessor {
             key: "REDIS_USERNAME".to_string(),
             value: Some(username.clone()),
             secret_name: None,
+            required: false,
         });
 
         env.push(V1EnvVar {
             key: "REDIS_PASSWORD".to_string(),
             value: Some(password.clone()),
             secret_name: None,
+            required: false,
         });
 
         env.push(V1EnvVar {
             key: "REDISCLI_AUTH".to_string(),
             value: Some(password.clone()),
             secret_name: None,
+            required: false,
         });
 
         // Fetch Redis IP from Tailscale
@@ -173,16 +181,19 @@ impl StandardProcessor {
             key: "REDIS_URL".to_string(),
             value: Some(redis_url),
             secret_name: None,
+            required: false,
         });
         env.push(V1EnvVar {
             key: "REDIS_CONSUMER_GROUP".to_string(),
             value: Some(processor.id.clone()),
             secret_name: None,
+            required: false,
         });
         env.push(V1EnvVar {
             key: "REDIS_STREAM".to_string(),
             value: Some(processor.stream.clone()),
             secret_name: None,
+            required: false,
         });
 
         // Configure labels and metadata
@@ -538,6 +549,21 @@ impl StandardProcessor {
             current_replicas
         );
 
+        let (warm_replicas, ready_replicas) = replica_counts(&active_runpod_containers);
+        if let Err(e) = Mutation::update_processor_replica_counts(
+            db,
+            processor.id.clone(),
+            warm_replicas,
+            ready_replicas,
+        )
+        .await
+        {
+            warn!(
+                "[Processor Controller] Failed to record replica counts for processor {}: {}",
+                processor.id, e
+            );
+        }
+
         // Get desired replicas from processor config (this might be updated later by scaling logic)
         // Initialize desired_replicas based on processor's min_replicas if desired_replicas field is None
         let initial_desired_replicas = processor.desired_replicas.unwrap_or_else(|| {
@@ -888,16 +914,24 @@ impl StandardProcessor {
             for replica_index in current_replicas..new_replica_count {
                 let mut request_for_replica = container.clone();
                 if let Some(mut meta) = request_for_replica.metadata.take() {
-                    meta.name = Some(format!(
-                        "{}-replica-{}-{}",
-                        meta.name.unwrap_or_default(),
+                    // Keep replica names valid DNS labels: lowercase suffix, truncated base
+                    let suffix = format!(
+                        "-replica-{}-{}",
                         replica_index,
                         ShortUuid::generate()
                             .to_string()
+                            .to_lowercase()
                             .chars()
                             .take(5)
                             .collect::<String>()
-                    ));
+                    );
+                    let base: String = meta
+                        .name
+                        .unwrap_or_default()
+                        .chars()
+                        .take(crate::validate::MAX_NAME_LENGTH - suffix.len())
+                        .collect();
+                    meta.name = Some(format!("{}{}", base.trim_end_matches('-'), suffix));
                     request_for_replica.metadata = Some(meta);
                 }
 
@@ -923,6 +957,27 @@ impl StandardProcessor {
                     declared.metadata.name, declared.metadata.id, processor.id
                 );
             }
+
+            // Prime the new replicas; a missed warmup only costs a cold first message
+            let new_replicas = new_replica_count - current_replicas;
+            let sent = match self.state.queue(processor.queue_backend.as_deref()) {
+                Ok(queue) => {
+                    send_warmup_messages(queue, processor, new_replicas, Some(agent_key.clone()))
+                        .await
+                }
+                Err(e) => Err(e.into()),
+            };
+            match sent {
+                Ok(0) => {}
+                Ok(sent) => info!(
+                    "[Processor Controller] Sent {} warmup message(s) for processor {}",
+                    sent, processor.id
+                ),
+                Err(e) => warn!(
+                    "[Processor Controller] Failed to send warmup message for processor {}: {}",
+                    processor.id, e
+                ),
+            }
         } else if new_replica_count < current_replicas {
             // Use the provided list of active containers
             let mut sorted_containers = active_runpod_containers;
@@ -956,24 +1011,12 @@ impl StandardProcessor {
                     "Attempting to delete container DB record for ID: {}",
                     container.id
                 );
-                match crate::entities::containers::Entity::delete_by_id(container.id.clone()) // Clone ID for the call
-                    .exec(db)
-                    .await
-                {
-                    Ok(delete_result) => {
-                        // delete_result is sea_orm::DeleteResult
-                        if delete_result.rows_affected > 0 {
-                            info!(
-                                "Successfully deleted container DB record {} ({} row(s) affected).",
-                                container.id, // Original container.id is fine here
-                                delete_result.rows_affected
-                            );
-                        } else {
-                            warn!(
-                                "Container DB record {} not found or already deleted during attempt to delete.",
-                                container.id
-                            );
-                        }
+                match Mutation::delete_container(db, container.id.clone()).await {
+                    Ok(_) => {
+                        info!(
+                            "Successfully soft deleted container DB record {}.",
+                            container.id
+                        );
                     }
                     Err(e) => {
                         error!("Error deleting container DB record {}: {}", container.id, e);
@@ -1007,6 +1050,20 @@ impl ProcessorPlatform for StandardProcessor {
             "Declaring processor {:?} in namespace {:?}",
             name, namespace
         );
+        let stream = format!("processor:{}:{}", namespace, name);
+
+        // The controller can't run the processor without somewhere to keep its state
+        let state_redis = self
+            .state
+            .processor_redis(config.queue_backend.as_deref())?
+            .clone();
+
+        // With Kafka, the stream's topics have to exist before anything is sent
+        if let MessageQueue::Kafka { admin, .. } =
+            self.state.queue(config.queue_backend.as_deref())?
+        {
+            topics::create_processor_topics(admin, &stream, &SERVER_CONFIG.kafka).await?;
+        }
 
         // 2. Create an ActiveModel to represent the new record in the database.
         let processor_am = processors::ActiveModel {
@@ -1040,13 +1097,19 @@ impl ProcessorPlatform for StandardProcessor {
                 .map(|l| serde_json::to_value(l))
                 .transpose()?),
 
-            stream: Set(format!("processor:{}:{}", namespace, name)),
+            stream: Set(stream),
+            stream_max_len: Set(Some(
+                config.stream_max_len.unwrap_or(DEFAULT_STREAM_MAX_LEN) as i64,
+            )),
+            queue_backend: Set(config.queue_backend.clone()),
+            warmup_message: Set(config.warmup_message.clone()),
 
             // Typically set an initial status or desired_status to "Defined" or similar.
             status: Set(Some(serde_json::to_value(V1ProcessorStatus {
                 status: Some(ProcessorStatus::Defined.to_string()),
                 message: None,
                 pressure: None,
+                ..Default::default()
             })?)),
             desired_status: Set(Some(ProcessorStatus::Running.to_string())),
 
@@ -1189,6 +1252,41 @@ impl ProcessorPlatform for StandardProcessor {
 
         // Update the processor record with the secret ID
 
+        // Provision the warm replicas now, so the first message doesn't wait
+        // on a cold start. The controller keeps them from here on, and scales
+        // up to `min_replicas` itself when this fails.
+        {
+            let client = &state_redis;
+            let container_request = config.container.clone().unwrap_or_default();
+            let warm_replicas = provision_warm_replicas(db, &inserted_model, |target| {
+                info!(
+                    "Provisioning {} warm replica(s) for processor {}",
+                    target, inserted_model.id
+                );
+                self.reconcile_replicas(
+                    &inserted_model,
+                    0,
+                    target,
+                    Vec::new(),
+                    container_request,
+                    db,
+                    user_profile,
+                    client,
+                )
+            })
+            .await;
+            match warm_replicas {
+                Ok(warm_replicas) => debug!(
+                    "Provisioned {} warm replica(s) for processor {}",
+                    warm_replicas, inserted_model.id
+                ),
+                Err(e) => error!(
+                    "Failed to provision warm replicas for processor {}, leaving them to the controller: {}",
+                    inserted_model.id, e
+                ),
+            }
+        }
+
         let v1_processor = match inserted_model.to_v1_processor() {
             Ok(processor) => processor,
             Err(e) => {
@@ -1248,7 +1346,7 @@ impl ProcessorPlatform for StandardProcessor {
             "Fetching user profile using processor agent key from {}",
             auth_server
         );
-        let client = reqwest::Client::new();
+        let client = crate::utils::http::shared_client();
         let user_profile_url = format!("{}/v1/users/me", auth_server);
 
         let response = client
@@ -1325,7 +1423,7 @@ impl ProcessorPlatform for StandardProcessor {
                     processor.id
                 );
                 // 1) Match on the enum to get the Redis Client, if it's a Redis-based queue
-                match &self.state.message_queue {
+                match self.state.queue(processor.queue_backend.as_deref())? {
                     MessageQueue::Redis { client } => {
                         self.watch_processor(
                             db,
@@ -1358,7 +1456,6 @@ impl ProcessorPlatform for StandardProcessor {
         &self,
         id: &str,
         db: &DatabaseConnection,
-        redis_client: &redis::Client,
     ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
         debug!("Deleting processor: {}", id);
         use crate::entities::processors;
@@ -1390,36 +1487,57 @@ impl ProcessorPlatform for StandardProcessor {
 
         // --- BEGIN: Delete Redis Stream ---
         let stream_name = processor.stream.clone();
-        debug!(
-            "Attempting to delete Redis stream '{}' for processor {}",
-            stream_name, processor.id
-        );
-        match redis_client.get_connection() {
-            Ok(mut conn) => {
-                match redis::cmd("DEL").arg(&stream_name).query::<()>(&mut conn) {
-                    Ok(_) => info!(
-                        "Successfully deleted Redis stream '{}' for processor {}",
-                        stream_name, processor.id
-                    ),
-                    Err(e) => error!(
-                        "Failed to delete Redis stream '{}' for processor {}: {}",
+        let queue = self.state.queue(processor.queue_backend.as_deref());
+        if let Ok(MessageQueue::Redis { client }) = queue {
+            debug!(
+                "Attempting to delete Redis stream '{}' for processor {}",
+                stream_name, processor.id
+            );
+            match client.get_connection() {
+                Ok(mut conn) => {
+                    match redis::cmd("DEL").arg(&stream_name).query::<()>(&mut conn) {
+                        Ok(_) => info!(
+                            "Successfully deleted Redis stream '{}' for processor {}",
+                            stream_name, processor.id
+                        ),
+                        Err(e) => error!(
+                            "Failed to delete Redis stream '{}' for processor {}: {}",
+                            stream_name,
+                            processor.id,
+                            e // Decide if this should be a hard error or just logged
+                        ),
+                    }
+                }
+                Err(e) => {
+                    error!(
+                        "Failed to get Redis connection to delete stream '{}' for processor {}: {}",
                         stream_name,
                         processor.id,
                         e // Decide if this should be a hard error or just logged
-                    ),
+                    );
                 }
             }
-            Err(e) => {
-                error!(
-                    "Failed to get Redis connection to delete stream '{}' for processor {}: {}",
-                    stream_name,
-                    processor.id,
-                    e // Decide if this should be a hard error or just logged
-                );
-            }
         }
         // --- END: Delete Redis Stream ---
 
+        // --- BEGIN: Delete Kafka Topics ---
+        if let Ok(MessageQueue::Kafka { admin, .. }) = queue {
+            match topics::delete_processor_topics(admin.clone(), &stream_name, &SERVER_CONFIG.kafka)
+                .await
+            {
+                Ok(deleted) => info!(
+                    "Deleted {} Kafka topic(s) for processor {}",
+                    deleted.len(),
+                    processor.id
+                ),
+                Err(e) => error!(
+                    "Failed to delete Kafka topics for processor {}: {}",
+                    processor.id, e
+                ),
+            }
+        }
+        // --- END: Delete Kafka Topics ---
+
         // 2) Query containers using the correct owner_ref format
         let owner_ref_string = format!("{}.{}.Processor", processor.name, processor.namespace);
         let associated_containers_result =
@@ -1472,24 +1590,12 @@ impl ProcessorPlatform for StandardProcessor {
                 "Attempting to delete container DB record for ID: {}",
                 container.id
             );
-            match crate::entities::containers::Entity::delete_by_id(container.id.clone()) // Clone ID for the call
-                .exec(db)
-                .await
-            {
-                Ok(delete_result) => {
-                    // delete_result is sea_orm::DeleteResult
-                    if delete_result.rows_affected > 0 {
-                        info!(
-                            "Successfully deleted container DB record {} ({} row(s) affected).",
-                            container.id, // Original container.id is fine here
-                            delete_result.rows_affected
-                        );
-                    } else {
-                        warn!(
-                            "Container DB record {} not found or already deleted during attempt to delete.",
-                            container.id
-                        );
-                    }
+            match Mutation::delete_container(db, container.id.clone()).await {
+                Ok(_) => {
+                    info!(
+                        "Successfully soft deleted container DB record {}.",
+                        container.id
+                    );
                 }
                 Err(e) => {
                     error!("Error deleting container DB record {}: {}", container.id, e);
@@ -1539,9 +1645,7 @@ impl ProcessorPlatform for StandardProcessor {
 
         debug!("Deleting processor record: {}", processor.id);
         // 4) Finally, delete the processor record
-        processors::Entity::delete_by_id(processor.id)
-            .exec(db)
-            .await?;
+        Mutation::delete_processor(db, processor.id).await?;
         tracing::info!(
             "Successfully deleted processor '{}' and its associated containers.",
             id
@@ -0,0 +1,290 @@
+// src/resources/v1/processors/streams.rs
+//
+// Helpers for the Redis streams backing processors. Every XADD goes through
+// `xadd_capped` so streams are trimmed as they are written and never grow
+// without bound. Processors declared before streams were capped have no
+// `stream_max_len` and aren't trimmed, so upgrading doesn't drop entries
+// they still hold; setting one through an update caps them too.
+
+use crate::entities::processors;
+use std::sync::Arc;
+use tracing::{debug, warn};
+
+/// Entries kept in the stream of a newly declared processor that doesn't set
+/// `stream_max_len`
+pub const DEFAULT_STREAM_MAX_LEN: u64 = 10_000;
+
+/// Return streams only carry the replies to a single message
+pub const RETURN_STREAM_MAX_LEN: u64 = 1_000;
+
+/// Return streams expire on their own after this long, in case the request
+/// that created them never cleans up. Longer than the longest wait.
+pub const RETURN_STREAM_TTL_SECS: u64 = 2 * 60 * 60;
+
+/// How long a send waits for its reply, from the requested `timeout_ms`.
+/// Without one it waits as long as the server allows; zero would block
+/// forever, so it's rejected along with anything above `max_ms`.
+pub fn send_wait_timeout_ms(requested: Option<u64>, max_ms: u64) -> Result<u64, String> {
+    match requested {
+        None => Ok(max_ms),
+        Some(0) => Err("timeout_ms must be greater than 0".to_string()),
+        Some(ms) if ms > max_ms => Err(format!("timeout_ms must be at most {}", max_ms)),
+        Some(ms) => Ok(ms),
+    }
+}
+
+/// The stream cap for a processor, none for those declared before streams
+/// were capped.
+pub fn stream_max_len(processor: &processors::Model) -> Option<u64> {
+    processor
+        .stream_max_len
+        .and_then(|n| u64::try_from(n).ok())
+        .filter(|n| *n > 0)
+}
+
+/// Builds `XADD <stream> MAXLEN ~ <max_len> * <field> <value> ...`, leaving
+/// out the `MAXLEN` without a cap. The `~` lets Redis trim lazily at node
+/// boundaries, so the length may briefly exceed `max_len` by a node's worth
+/// of entries.
+pub fn xadd_capped(stream: &str, max_len: Option<u64>, fields: &[(&str, &str)]) -> redis::Cmd {
+    let mut cmd = redis::cmd("XADD");
+    cmd.arg(stream);
+    if let Some(max_len) = max_len {
+        cmd.arg("MAXLEN").arg("~").arg(max_len);
+    }
+    cmd.arg("*");
+    for (field, value) in fields {
+        cmd.arg(*field).arg(*value);
+    }
+    cmd
+}
+
+/// Creates a return stream with its `init` marker and a TTL, returning the
+/// marker's entry id so readers can block for entries after it.
+pub fn init_return_stream(
+    conn: &mut redis::Connection,
+    return_stream: &str,
+) -> redis::RedisResult<String> {
+    let (init_id,): (String,) = redis::pipe()
+        .add_command(xadd_capped(
+            return_stream,
+            Some(RETURN_STREAM_MAX_LEN),
+            &[("init", "true")],
+        ))
+        .cmd("EXPIRE")
+        .arg(return_stream)
+        .arg(RETURN_STREAM_TTL_SECS)
+        .ignore()
+        .query(conn)?;
+    Ok(init_id)
+}
+
+/// Whether a return message ends its stream: an explicit `stream_complete`
+/// flag, or the final `StreamResponseMessage` a streaming processor sends.
+pub fn is_terminal_return_message(message: &serde_json::Value) -> bool {
+    if message.get("stream_complete").and_then(|v| v.as_bool()) == Some(true) {
+        return true;
+    }
+    message.get("kind").and_then(|v| v.as_str()) == Some("StreamResponseMessage")
+        && matches!(
+            message.get("status").and_then(|v| v.as_str()),
+            Some("success") | Some("error")
+        )
+}
+
+/// Deletes a return stream when dropped, so every exit from a handler that
+/// created one (including early returns through `?`) cleans it up. Call
+/// `disarm` to hand the stream off to someone else instead.
+pub struct ReturnStreamGuard {
+    client: Arc<redis::Client>,
+    stream: Option<String>,
+}
+
+impl ReturnStreamGuard {
+    pub fn new(client: Arc<redis::Client>, stream: &str) -> Self {
+        Self {
+            client,
+            stream: Some(stream.to_string()),
+        }
+    }
+
+    /// Keeps the stream alive past the guard; its TTL still applies.
+    pub fn disarm(mut self) {
+        self.stream = None;
+    }
+}
+
+impl Drop for ReturnStreamGuard {
+    fn drop(&mut self) {
+        let Some(stream) = self.stream.take() else {
+            return;
+        };
+        let result = self
+            .client
+            .get_connection()
+            .and_then(|mut conn| redis::cmd("DEL").arg(&stream).query::<()>(&mut conn));
+        match result {
+            Ok(()) => debug!("Deleted return stream '{}'", stream),
+            // The stream's TTL removes it eventually
+            Err(e) => warn!("Failed to delete return stream '{}': {}", stream, e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The arguments of `cmd` as sent: an array header, then each argument
+    /// as `$<len>` followed by that many bytes
+    fn packed_args(cmd: &redis::Cmd) -> Vec<String> {
+        let packed = String::from_utf8(cmd.get_packed_command()).unwrap();
+        let (_header, mut rest) = packed.split_once("\r\n").unwrap();
+        let mut args = Vec::new();
+        while let Some((len, after)) = rest.split_once("\r\n") {
+            let len: usize = len.strip_prefix('$').unwrap().parse().unwrap();
+            args.push(after[..len].to_string());
+            rest = &after[len + 2..];
+        }
+        args
+    }
+
+    #[test]
+    fn test_xadd_capped_adds_maxlen() {
+        // Arrange
+        let fields = [("data", "{\"a\":1}")];
+
+        // Act
+        let cmd = xadd_capped("processor:ns:name", Some(500), &fields);
+
+        // Assert
+        assert_eq!(
+            packed_args(&cmd),
+            vec![
+                "XADD",
+                "processor:ns:name",
+                "MAXLEN",
+                "~",
+                "500",
+                "*",
+                "data",
+                "{\"a\":1}"
+            ]
+        );
+        assert_eq!(
+            packed_args(&xadd_capped("processor:ns:old", None, &fields)),
+            vec!["XADD", "processor:ns:old", "*", "data", "{\"a\":1}"]
+        );
+    }
+
+    #[test]
+    fn test_send_wait_timeout_ms() {
+        assert_eq!(send_wait_timeout_ms(None, 3_600_000), Ok(3_600_000));
+        assert_eq!(send_wait_timeout_ms(Some(250), 3_600_000), Ok(250));
+        assert!(send_wait_timeout_ms(Some(0), 3_600_000).is_err());
+        assert!(send_wait_timeout_ms(Some(3_600_001), 3_600_000).is_err());
+    }
+
+    #[test]
+    fn test_stream_max_len() {
+        // Declared before streams were capped
+        let mut processor = processors::Model::test_fixture();
+        assert_eq!(stream_max_len(&processor), None);
+
+        processor.stream_max_len = Some(250);
+        assert_eq!(stream_max_len(&processor), Some(250));
+
+        processor.stream_max_len = Some(0);
+        assert_eq!(stream_max_len(&processor), None);
+    }
+
+    #[test]
+    #[ignore = "needs a Redis at NEBU_TEST_REDIS_URL"]
+    fn test_trimming_keeps_stream_bounded() {
+        let url = std::env::var("NEBU_TEST_REDIS_URL").expect("NEBU_TEST_REDIS_URL is not set");
+        let client = redis::Client::open(url).unwrap();
+        let mut conn = client.get_connection().unwrap();
+        let stream = format!("test:trim:{}", short_uuid::ShortUuid::generate());
+
+        for i in 0..1_000 {
+            let _: String = xadd_capped(&stream, Some(10), &[("data", i.to_string().as_str())])
+                .query(&mut conn)
+                .unwrap();
+        }
+        let len: u64 = redis::cmd("XLEN").arg(&stream).query(&mut conn).unwrap();
+        let _: () = redis::cmd("DEL").arg(&stream).query(&mut conn).unwrap();
+
+        // Approximate trimming keeps at most one extra macro node (100 entries by default)
+        assert!(len <= 10 + 100, "stream grew to {} entries", len);
+    }
+
+    #[test]
+    fn test_is_terminal_return_message() {
+        assert!(is_terminal_return_message(
+            &serde_json::json!({"stream_complete": true})
+        ));
+        assert!(is_terminal_return_message(&serde_json::json!({
+            "kind": "StreamResponseMessage",
+            "status": "success",
+            "content": {}
+        })));
+        assert!(is_terminal_return_message(&serde_json::json!({
+            "kind": "StreamResponseMessage",
+            "status": "error"
+        })));
+        assert!(!is_terminal_return_message(&serde_json::json!({
+            "kind": "StreamResponseMessage",
+            "status": "running"
+        })));
+        assert!(!is_terminal_return_message(
+            &serde_json::json!({"stream_complete": false, "content": "chunk"})
+        ));
+    }
+
+    /// Creates a return stream, then fails before anything reads from it.
+    fn fail_mid_flight(client: &Arc<redis::Client>, stream: &str) -> redis::RedisResult<()> {
+        let _guard = ReturnStreamGuard::new(client.clone(), stream);
+        let mut conn = client.get_connection()?;
+        init_return_stream(&mut conn, stream)?;
+        // A command Redis rejects, standing in for a failed read
+        redis::cmd("XREAD")
+            .arg("NOT-A-FLAG")
+            .query::<()>(&mut conn)?;
+        Ok(())
+    }
+
+    #[test]
+    #[ignore = "needs a Redis at NEBU_TEST_REDIS_URL"]
+    fn test_guard_deletes_return_stream_on_error() {
+        // Arrange
+        let url = std::env::var("NEBU_TEST_REDIS_URL").expect("NEBU_TEST_REDIS_URL is not set");
+        let client = Arc::new(redis::Client::open(url).unwrap());
+        let stream = format!("test:return:{}", short_uuid::ShortUuid::generate());
+
+        // Act
+        let result = fail_mid_flight(&client, &stream);
+
+        // Assert
+        assert!(result.is_err());
+        let mut conn = client.get_connection().unwrap();
+        let exists: u64 = redis::cmd("EXISTS").arg(&stream).query(&mut conn).unwrap();
+        assert_eq!(exists, 0);
+    }
+
+    #[test]
+    #[ignore = "needs a Redis at NEBU_TEST_REDIS_URL"]
+    fn test_disarmed_guard_keeps_return_stream() {
+        let url = std::env::var("NEBU_TEST_REDIS_URL").expect("NEBU_TEST_REDIS_URL is not set");
+        let client = Arc::new(redis::Client::open(url).unwrap());
+        let stream = format!("test:return:{}", short_uuid::ShortUuid::generate());
+        let mut conn = client.get_connection().unwrap();
+
+        let guard = ReturnStreamGuard::new(client.clone(), &stream);
+        init_return_stream(&mut conn, &stream).unwrap();
+        guard.disarm();
+
+        let exists: u64 = redis::cmd("EXISTS").arg(&stream).query(&mut conn).unwrap();
+        let _: () = redis::cmd("DEL").arg(&stream).query(&mut conn).unwrap();
+        assert_eq!(exists, 1);
+    }
+}
@@ -0,0 +1,417 @@
+// src/resources/v1/processors/topics.rs
+//
+// Kafka topics backing processors when the server runs with a Kafka message
+// queue. A processor's stream and its `.health` stream get topics when it is
+// declared; on delete those go along with every `.return.*` topic made for
+// individual messages. Messages are produced keyed by their id and read
+// through a consumer group per reader, committing what was handed out. The
+// consumers stay subscribed between reads, so a read doesn't rebalance its
+// group.
+
+use crate::config::KafkaConfig;
+use once_cell::sync::Lazy;
+use rdkafka::admin::{AdminClient, AdminOptions, NewTopic, TopicReplication};
+use rdkafka::client::DefaultClientContext;
+use rdkafka::consumer::{BaseConsumer, CommitMode, Consumer};
+use rdkafka::error::KafkaError;
+use rdkafka::producer::{FutureProducer, FutureRecord};
+use rdkafka::types::RDKafkaErrorCode;
+use rdkafka::{ClientConfig, Message};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::{debug, info};
+
+#[derive(Debug, thiserror::Error)]
+pub enum TopicError {
+    #[error("Kafka request failed: {0}")]
+    Kafka(#[from] KafkaError),
+
+    #[error("Kafka rejected topic '{0}': {1}")]
+    Topic(String, RDKafkaErrorCode),
+
+    #[error("Kafka metadata lookup failed: {0}")]
+    Metadata(String),
+}
+
+/// The topic for a processor stream. Kafka topic names only allow
+/// `[a-zA-Z0-9._-]`, so the `:` separators become `.` and anything else
+/// outside that set becomes `_`.
+pub fn topic_name(stream: &str) -> String {
+    stream
+        .chars()
+        .map(|c| match c {
+            ':' => '.',
+            c if c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-') => c,
+            _ => '_',
+        })
+        .collect()
+}
+
+/// Topics created up front for a processor stream: the stream itself and
+/// its health checks. Return topics are per message and made on demand.
+pub fn declared_topics(stream: &str) -> Vec<String> {
+    let topic = topic_name(stream);
+    vec![topic.clone(), format!("{}.health", topic)]
+}
+
+/// Whether `candidate` belongs to the processor with `stream`, including the
+/// `.return.*` topics of its messages and health checks.
+pub fn is_processor_topic(stream: &str, candidate: &str) -> bool {
+    let topic = topic_name(stream);
+    let health = format!("{}.health", topic);
+    candidate == topic
+        || candidate == health
+        || candidate.starts_with(&format!("{}.return.", topic))
+        || candidate.starts_with(&format!("{}.return.", health))
+}
+
+fn admin_options(kafka: &KafkaConfig) -> AdminOptions {
+    AdminOptions::new().operation_timeout(Some(Duration::from_millis(kafka.timeout_ms as u64)))
+}
+
+/// Creates the topics for a processor stream with the configured partitions
+/// and replication. Topics that already exist are left as they are.
+pub async fn create_processor_topics(
+    admin: &AdminClient<DefaultClientContext>,
+    stream: &str,
+    kafka: &KafkaConfig,
+) -> Result<Vec<String>, TopicError> {
+    let names = declared_topics(stream);
+    let topics: Vec<NewTopic> = names
+        .iter()
+        .map(|name| {
+            NewTopic::new(
+                name,
+                kafka.topic_partitions,
+                TopicReplication::Fixed(kafka.topic_replication),
+            )
+        })
+        .collect();
+
+    for result in admin.create_topics(&topics, &admin_options(kafka)).await? {
+        match result {
+            Ok(name) => info!("[Kafka] Created topic '{}'", name),
+            Err((name, RDKafkaErrorCode::TopicAlreadyExists)) => {
+                debug!("[Kafka] Topic '{}' already exists", name)
+            }
+            Err((name, code)) => return Err(TopicError::Topic(name, code)),
+        }
+    }
+    Ok(names)
+}
+
+/// Deletes every topic of a processor stream, returning the ones removed.
+pub async fn delete_processor_topics(
+    admin: Arc<AdminClient<DefaultClientContext>>,
+    stream: &str,
+    kafka: &KafkaConfig,
+) -> Result<Vec<String>, TopicError> {
+    // Metadata requests block, so they stay off the runtime's threads
+    let timeout = Duration::from_millis(kafka.timeout_ms as u64);
+    let lookup = admin.clone();
+    let existing: Vec<String> = tokio::task::spawn_blocking(move || {
+        lookup
+            .inner()
+            .fetch_metadata(None, timeout)
+            .map(|metadata| {
+                metadata
+                    .topics()
+                    .iter()
+                    .map(|t| t.name().to_string())
+                    .collect()
+            })
+    })
+    .await
+    .map_err(|e| TopicError::Metadata(e.to_string()))??;
+
+    let names: Vec<&str> = existing
+        .iter()
+        .map(String::as_str)
+        .filter(|name| is_processor_topic(stream, name))
+        .collect();
+    if names.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let mut deleted = Vec::new();
+    for result in admin.delete_topics(&names, &admin_options(kafka)).await? {
+        match result {
+            Ok(name) => {
+                info!("[Kafka] Deleted topic '{}'", name);
+                deleted.push(name);
+            }
+            Err((name, RDKafkaErrorCode::UnknownTopicOrPartition)) => {
+                debug!("[Kafka] Topic '{}' was already gone", name)
+            }
+            Err((name, code)) => return Err(TopicError::Topic(name, code)),
+        }
+    }
+    Ok(deleted)
+}
+
+/// A message read from a processor topic, with the `partition-offset` it
+/// was read at
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConsumedMessage {
+    pub entry_id: String,
+    pub payload: String,
+}
+
+/// Produces `payload` to the topic of `stream`, keyed by the message id so
+/// its entries keep their order on one partition. Returns the
+/// `partition-offset` it was stored at.
+pub async fn produce_message(
+    producer: &FutureProducer,
+    stream: &str,
+    key: &str,
+    payload: &str,
+    kafka: &KafkaConfig,
+) -> Result<String, TopicError> {
+    let topic = topic_name(stream);
+    let (partition, offset) = producer
+        .send(
+            FutureRecord::to(&topic).key(key).payload(payload),
+            Duration::from_millis(kafka.timeout_ms as u64),
+        )
+        .await
+        .map_err(|(e, _)| TopicError::Kafka(e))?;
+    debug!(
+        "[Kafka] Produced message {} to '{}' at {}-{}",
+        key, topic, partition, offset
+    );
+    Ok(format!("{}-{}", partition, offset))
+}
+
+/// How many subscribed consumers are kept for reads, the least recently
+/// used going first
+const MAX_CACHED_CONSUMERS: usize = 256;
+
+type SharedConsumer = Arc<Mutex<BaseConsumer>>;
+
+struct CachedConsumer {
+    consumer: SharedConsumer,
+    last_used: Instant,
+}
+
+/// Subscribed consumers by group and topic
+static CONSUMERS: Lazy<Mutex<HashMap<(String, String), CachedConsumer>>> =
+    Lazy::new(Default::default);
+
+/// The consumer reading `topic` as `group`, subscribed the first time
+fn cached_consumer(
+    kafka: &KafkaConfig,
+    group: &str,
+    topic: &str,
+) -> Result<SharedConsumer, TopicError> {
+    let mut consumers = CONSUMERS.lock().unwrap();
+    let key = (group.to_string(), topic.to_string());
+    if let Some(cached) = consumers.get_mut(&key) {
+        cached.last_used = Instant::now();
+        return Ok(cached.consumer.clone());
+    }
+
+    let consumer: BaseConsumer = ClientConfig::new()
+        .set("bootstrap.servers", &kafka.bootstrap_servers)
+        .set("group.id", group)
+        .set("enable.auto.commit", "false")
+        .set("auto.offset.reset", "earliest")
+        .create()?;
+    consumer.subscribe(&[topic])?;
+
+    let mut evicted = None;
+    if consumers.len() >= MAX_CACHED_CONSUMERS {
+        let oldest = consumers
+            .iter()
+            .min_by_key(|(_, cached)| cached.last_used)
+            .map(|(key, _)| key.clone());
+        evicted = oldest.and_then(|key| consumers.remove(&key));
+    }
+    let consumer = Arc::new(Mutex::new(consumer));
+    consumers.insert(
+        key,
+        CachedConsumer {
+            consumer: consumer.clone(),
+            last_used: Instant::now(),
+        },
+    );
+    // Closing a consumer waits on the broker, so not while holding the lock
+    drop(consumers);
+    drop(evicted);
+    Ok(consumer)
+}
+
+/// Reads up to `max` messages from the topic of `stream` as `group`, waiting
+/// at most `wait` for them, and commits their offsets. Kafka has no
+/// per-message acknowledgement, so what is returned counts as handled.
+pub async fn consume_messages(
+    kafka: &KafkaConfig,
+    stream: &str,
+    group: &str,
+    max: usize,
+    wait: Duration,
+) -> Result<Vec<ConsumedMessage>, TopicError> {
+    let consumer = cached_consumer(kafka, group, &topic_name(stream))?;
+
+    // Polling blocks, so it stays off the runtime's threads
+    tokio::task::spawn_blocking(move || {
+        let consumer = consumer.lock().unwrap();
+        let deadline = Instant::now() + wait;
+        let mut messages = Vec::new();
+        while messages.len() < max {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                break;
+            }
+            let Some(result) = consumer.poll(remaining) else {
+                break;
+            };
+            let message = result?;
+            messages.push(ConsumedMessage {
+                entry_id: format!("{}-{}", message.partition(), message.offset()),
+                payload: String::from_utf8_lossy(message.payload().unwrap_or_default()).to_string(),
+            });
+        }
+        if !messages.is_empty() {
+            consumer.commit_consumer_state(CommitMode::Sync)?;
+        }
+        Ok(messages)
+    })
+    .await
+    .map_err(|e| TopicError::Metadata(e.to_string()))?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rdkafka::ClientConfig as KafkaClientConfig;
+
+    #[test]
+    fn test_topic_name_is_kafka_safe() {
+        // Arrange
+        let stream = "processor:my-ns:image gen";
+
+        // Act
+        let topic = topic_name(stream);
+
+        // Assert
+        assert_eq!(topic, "processor.my-ns.image_gen");
+    }
+
+    #[test]
+    fn test_consumers_are_kept_per_group() {
+        // Creating a consumer doesn't connect yet
+        let kafka = KafkaConfig {
+            bootstrap_servers: "127.0.0.1:1".to_string(),
+            timeout_ms: 100,
+            topic_partitions: 1,
+            topic_replication: 1,
+        };
+        let group = format!("test-{}", short_uuid::ShortUuid::generate());
+
+        let first = cached_consumer(&kafka, &group, "processor.ns.echo").unwrap();
+        let again = cached_consumer(&kafka, &group, "processor.ns.echo").unwrap();
+        let other_group =
+            cached_consumer(&kafka, &format!("{}-2", group), "processor.ns.echo").unwrap();
+        let other_topic = cached_consumer(&kafka, &group, "processor.ns.other").unwrap();
+
+        assert!(Arc::ptr_eq(&first, &again));
+        assert!(!Arc::ptr_eq(&first, &other_group));
+        assert!(!Arc::ptr_eq(&first, &other_topic));
+    }
+
+    #[test]
+    fn test_processor_topics_cover_health_and_returns() {
+        let stream = "processor:ns:echo";
+
+        assert_eq!(
+            declared_topics(stream),
+            vec!["processor.ns.echo", "processor.ns.echo.health"]
+        );
+        assert!(is_processor_topic(stream, "processor.ns.echo"));
+        assert!(is_processor_topic(stream, "processor.ns.echo.return.abc"));
+        assert!(is_processor_topic(
+            stream,
+            "processor.ns.echo.health.return.abc"
+        ));
+        assert!(!is_processor_topic(stream, "processor.ns.echo2"));
+        assert!(!is_processor_topic(stream, "processor.ns.echo2.health"));
+        assert!(!is_processor_topic(stream, "processor.ns.echo.returned"));
+    }
+
+    fn topics_in(admin: &AdminClient<DefaultClientContext>, prefix: &str) -> Vec<String> {
+        let metadata = admin
+            .inner()
+            .fetch_metadata(None, Duration::from_secs(5))
+            .unwrap();
+        metadata
+            .topics()
+            .iter()
+            .map(|t| t.name().to_string())
+            .filter(|name| name.starts_with(prefix))
+            .collect()
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a Kafka at NEBU_TEST_KAFKA_BOOTSTRAP_SERVERS"]
+    async fn test_topics_created_on_declare_and_removed_on_delete() {
+        let bootstrap_servers = std::env::var("NEBU_TEST_KAFKA_BOOTSTRAP_SERVERS")
+            .expect("NEBU_TEST_KAFKA_BOOTSTRAP_SERVERS is not set");
+        let kafka = KafkaConfig {
+            bootstrap_servers: bootstrap_servers.clone(),
+            timeout_ms: 5000,
+            topic_partitions: 2,
+            topic_replication: 1,
+        };
+        let admin: Arc<AdminClient<DefaultClientContext>> = Arc::new(
+            KafkaClientConfig::new()
+                .set("bootstrap.servers", &bootstrap_servers)
+                .create()
+                .unwrap(),
+        );
+        let stream = format!("processor:test:{}", short_uuid::ShortUuid::generate());
+        let prefix = topic_name(&stream);
+
+        let created = create_processor_topics(&admin, &stream, &kafka)
+            .await
+            .unwrap();
+        // Declaring again is a no-op
+        create_processor_topics(&admin, &stream, &kafka)
+            .await
+            .unwrap();
+        // A message's return topic, as made on demand
+        let return_topic = format!("{}.return.msg1", prefix);
+        let extra = NewTopic::new(&return_topic, 1, TopicReplication::Fixed(1));
+        admin
+            .create_topics([&extra], &admin_options(&kafka))
+            .await
+            .unwrap();
+
+        let mut present = topics_in(&admin, &prefix);
+        present.sort();
+        assert_eq!(
+            present,
+            vec![created[0].clone(), created[1].clone(), return_topic.clone()]
+        );
+        let metadata = admin
+            .inner()
+            .fetch_metadata(Some(&created[0]), Duration::from_secs(5))
+            .unwrap();
+        assert_eq!(metadata.topics()[0].partitions().len(), 2);
+
+        let mut deleted = delete_processor_topics(admin.clone(), &stream, &kafka)
+            .await
+            .unwrap();
+        deleted.sort();
+        assert_eq!(deleted, present);
+
+        // Deletion settles asynchronously on the brokers
+        for _ in 0..20 {
+            if topics_in(&admin, &prefix).is_empty() {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(250)).await;
+        }
+        panic!("topics still present: {:?}", topics_in(&admin, &prefix));
+    }
+}
@@ -0,0 +1,271 @@
+// src/resources/v1/processors/warmup.rs
+//
+// Keeping processors warm. A processor scaled up from nothing makes its first
+// message wait out a container cold start, so one with `min_replicas` has
+// that many replicas provisioned as soon as it's declared rather than on the
+// controller's first pass. Its `warmup_message` is sent to the stream as
+// replicas come up, to prime caches before real messages arrive.
+
+use crate::entities::{containers, processors};
+use crate::models::V1StreamMessage;
+use crate::resources::v1::processors::health::{replica_health, REPLICA_HEALTHY};
+use crate::resources::v1::processors::streams::{stream_max_len, xadd_capped};
+use crate::resources::v1::processors::topics::produce_message;
+use crate::state::MessageQueue;
+use sea_orm::{ActiveModelTrait, ActiveValue::Set, DatabaseConnection};
+use short_uuid::ShortUuid;
+use std::future::Future;
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Replicas to provision when a processor is declared
+pub fn warm_replica_target(min_replicas: Option<i32>) -> i32 {
+    min_replicas.unwrap_or(0).max(0)
+}
+
+/// Provisions the warm replicas of a just declared processor, calling
+/// `provision` with how many to create, and records them as its desired
+/// replicas so the controller keeps them. Returns how many were provisioned.
+pub async fn provision_warm_replicas<F, Fut>(
+    db: &DatabaseConnection,
+    processor: &processors::Model,
+    provision: F,
+) -> Result<i32, BoxError>
+where
+    F: FnOnce(i32) -> Fut,
+    Fut: Future<Output = Result<(), BoxError>>,
+{
+    let target = warm_replica_target(processor.min_replicas);
+    if target == 0 {
+        return Ok(0);
+    }
+    provision(target).await?;
+
+    let mut active_model = processors::ActiveModel::from(processor.clone());
+    active_model.desired_replicas = Set(Some(target));
+    active_model.update(db).await?;
+    Ok(target)
+}
+
+/// Warm and ready replicas among a processor's active containers. Ready ones
+/// are those `replica_health` counts as healthy.
+pub fn replica_counts(active_containers: &[containers::Model]) -> (i32, i32) {
+    let ready = active_containers
+        .iter()
+        .filter(|c| replica_health(c).status == REPLICA_HEALTHY)
+        .count();
+    (active_containers.len() as i32, ready as i32)
+}
+
+/// The message that primes a replica. Nothing waits for its reply, so it
+/// has no return stream.
+pub fn warmup_stream_message(
+    processor: &processors::Model,
+    content: serde_json::Value,
+    api_key: Option<String>,
+) -> V1StreamMessage {
+    V1StreamMessage {
+        kind: "StreamMessage".to_string(),
+        id: ShortUuid::generate().to_string(),
+        content,
+        created_at: chrono::Utc::now().timestamp(),
+        return_stream: None,
+        user_id: None,
+        orgs: None,
+        handle: None,
+        adapter: Some(format!("processor:{}", processor.id)),
+        api_key,
+        entry_id: None,
+    }
+}
+
+/// Sends the processor's warmup message on its `queue` once for each of
+/// `replicas` that came up, nothing when it has none. Returns how many were
+/// sent.
+pub async fn send_warmup_messages(
+    queue: &MessageQueue,
+    processor: &processors::Model,
+    replicas: i32,
+    api_key: Option<String>,
+) -> Result<i32, BoxError> {
+    let Some(content) = processor.warmup_message.clone() else {
+        return Ok(0);
+    };
+    let messages = (0..replicas)
+        .map(|_| {
+            let message = warmup_stream_message(processor, content.clone(), api_key.clone());
+            serde_json::to_string(&message).map(|json| (message.id, json))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    match queue {
+        MessageQueue::Redis { client } => {
+            let mut conn = client.get_connection()?;
+            let max_len = stream_max_len(processor);
+            for (_, message_json) in &messages {
+                let _: String = xadd_capped(
+                    &processor.stream,
+                    max_len,
+                    &[("data", message_json.as_str())],
+                )
+                .query(&mut conn)?;
+            }
+        }
+        MessageQueue::Kafka { producer, .. } => {
+            for (id, message_json) in &messages {
+                produce_message(
+                    producer,
+                    &processor.stream,
+                    id,
+                    message_json,
+                    &crate::config::SERVER_CONFIG.kafka,
+                )
+                .await?;
+            }
+        }
+    }
+    Ok(messages.len() as i32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sea_orm::{ConnectionTrait, Database, EntityTrait, Schema};
+    use serde_json::json;
+    use std::sync::Mutex;
+
+    fn processor(min_replicas: Option<i32>) -> processors::Model {
+        processors::Model {
+            id: ShortUuid::generate().to_string(),
+            name: "echo".to_string(),
+            full_name: "ns/echo".to_string(),
+            min_replicas,
+            stream: format!("test:warmup:{}", ShortUuid::generate()),
+            warmup_message: Some(json!({"prompt": "hello"})),
+            ..processors::Model::test_fixture()
+        }
+    }
+
+    async fn declared(processor: &processors::Model) -> DatabaseConnection {
+        let db = Database::connect("sqlite::memory:").await.unwrap();
+        let schema = Schema::new(db.get_database_backend());
+        let stmt = schema.create_table_from_entity(processors::Entity);
+        db.execute(db.get_database_backend().build(&stmt))
+            .await
+            .unwrap();
+        processors::ActiveModel::from(processor.clone())
+            .insert(&db)
+            .await
+            .unwrap();
+        db
+    }
+
+    #[tokio::test]
+    async fn test_declaring_with_min_replicas_provisions_immediately() {
+        // Arrange
+        let processor = processor(Some(2));
+        let db = declared(&processor).await;
+        let provisioned = Mutex::new(Vec::new());
+        let recorder = &provisioned;
+
+        // Act
+        let warm = provision_warm_replicas(&db, &processor, |n| async move {
+            recorder.lock().unwrap().push(n);
+            Ok::<(), BoxError>(())
+        })
+        .await
+        .unwrap();
+
+        // Assert
+        assert_eq!(warm, 2);
+        assert_eq!(*provisioned.lock().unwrap(), vec![2]);
+        let stored = processors::Entity::find_by_id(processor.id.clone())
+            .one(&db)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(stored.desired_replicas, Some(2));
+    }
+
+    #[tokio::test]
+    async fn test_no_min_replicas_leaves_provisioning_to_the_controller() {
+        for min_replicas in [None, Some(0)] {
+            let processor = processor(min_replicas);
+            let db = declared(&processor).await;
+            let provisioned = Mutex::new(Vec::new());
+            let recorder = &provisioned;
+
+            let warm = provision_warm_replicas(&db, &processor, |n| async move {
+                recorder.lock().unwrap().push(n);
+                Ok::<(), BoxError>(())
+            })
+            .await
+            .unwrap();
+
+            assert_eq!(warm, 0);
+            assert!(provisioned.lock().unwrap().is_empty());
+            let stored = processors::Entity::find_by_id(processor.id.clone())
+                .one(&db)
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(stored.desired_replicas, None);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_failed_provisioning_fails_the_warmup() {
+        let processor = processor(Some(1));
+        let db = declared(&processor).await;
+
+        let result = provision_warm_replicas(&db, &processor, |_| async move {
+            Err::<(), BoxError>("no capacity".into())
+        })
+        .await;
+
+        assert_eq!(result.unwrap_err().to_string(), "no capacity");
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a Redis at NEBU_TEST_REDIS_URL"]
+    async fn test_warmup_message_is_sent_per_replica() {
+        let url = std::env::var("NEBU_TEST_REDIS_URL").expect("NEBU_TEST_REDIS_URL is not set");
+        let client = std::sync::Arc::new(redis::Client::open(url).unwrap());
+        let mut conn = client.get_connection().unwrap();
+        let queue = MessageQueue::Redis { client };
+        let processor = processor(Some(2));
+
+        let sent = send_warmup_messages(&queue, &processor, 2, None)
+            .await
+            .unwrap();
+
+        assert_eq!(sent, 2);
+        let entries: Vec<(String, Vec<String>)> = redis::cmd("XRANGE")
+            .arg(&processor.stream)
+            .arg("-")
+            .arg("+")
+            .query(&mut conn)
+            .unwrap();
+        assert_eq!(entries.len(), 2);
+        let (_, fields) = &entries[0];
+        assert_eq!(fields[0], "data");
+        let message: V1StreamMessage = serde_json::from_str(&fields[1]).unwrap();
+        assert_eq!(message.content, json!({"prompt": "hello"}));
+        assert_eq!(message.return_stream, None);
+        let _: () = redis::cmd("DEL")
+            .arg(&processor.stream)
+            .query(&mut conn)
+            .unwrap();
+
+        let silent = processors::Model {
+            warmup_message: None,
+            ..processor
+        };
+        assert_eq!(
+            send_warmup_messages(&queue, &silent, 2, None)
+                .await
+                .unwrap(),
+            0
+        );
+    }
+}
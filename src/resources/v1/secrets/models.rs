@@ -6,7 +6,11 @@ pub struct V1Secret {
     #[serde(default = "default_secret_kind")]
     pub kind: String,
     pub metadata: V1ResourceMeta,
+    /// Only set when the value was asked for with `?reveal=true`
     pub value: Option<String>,
+    /// Starts at 1 and goes up each time the value changes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<i32>,
     pub expires_at: Option<i32>,
 }
 
@@ -37,3 +41,10 @@ pub struct V1SecretRequest {
     pub value: String,
     pub expires_at: Option<i32>,
 }
+
+/// Request body for creating several secrets at once, e.g. from a `.env` file.
+/// Either all of them are created or none are.
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq)]
+pub struct V1SecretsRequest {
+    pub secrets: Vec<V1SecretRequest>,
+}
@@ -0,0 +1,201 @@
+use crate::config::BaseVolume;
+use crate::entities::volumes::{self, ActiveModel as VolumeActiveModel};
+use aws_config::{BehaviorVersion, Region};
+use sea_orm::{
+    ActiveModelTrait, ActiveValue::Set, ColumnTrait, DatabaseConnection, DbErr, EntityTrait,
+    QueryFilter,
+};
+use tracing::{debug, info, warn};
+
+/// Namespace holding the base volumes
+pub const BASE_VOLUME_NAMESPACE: &str = "root";
+
+/// Internal helper function to ensure a volume exists with the given parameters.
+/// Returns the volume if it exists, or creates it if it doesn't.
+pub async fn ensure_volume(
+    db_pool: &DatabaseConnection,
+    namespace: &str,
+    name: &str,
+    owner: &str,
+    source: &str,
+    created_by: &str,
+    labels: Option<serde_json::Value>,
+) -> Result<volumes::Model, DbErr> {
+    // First, try to find the volume by namespace and name
+    let existing_volume = volumes::Entity::find()
+        .filter(volumes::Column::Namespace.eq(namespace))
+        .filter(volumes::Column::Name.eq(name))
+        .one(db_pool)
+        .await?;
+
+    // If the volume exists and has the same source, return it
+    if let Some(volume) = existing_volume {
+        if volume.source == source {
+            return Ok(volume);
+        } else {
+            return Err(DbErr::Custom(format!(
+                "Volume with name '{}' already exists in namespace '{}', but has a different source ('{}' vs '{}'). This is currently not supported.",
+                name, namespace, volume.source, source
+            )));
+        }
+    }
+
+    // If we get here, either the volume doesn't exist or has a different source
+    // Generate a unique ID for the new volume
+    let id = short_uuid::ShortUuid::generate().to_string();
+
+    // Create the volume entity
+    let volume_entity = volumes::Model::new(
+        id,
+        name.to_string(),
+        namespace.to_string(),
+        owner.to_string(),
+        created_by.to_string(),
+        labels,
+        source.to_string(),
+    )
+    .map_err(|e| DbErr::Custom(format!("Failed to create volume: {}", e)))?;
+
+    // Insert the volume into the database
+    let volume_entity = VolumeActiveModel {
+        id: Set(volume_entity.id),
+        name: Set(volume_entity.name),
+        namespace: Set(volume_entity.namespace),
+        full_name: Set(volume_entity.full_name),
+        owner: Set(volume_entity.owner),
+        owner_ref: Set(volume_entity.owner_ref),
+        source: Set(volume_entity.source),
+        labels: Set(volume_entity.labels),
+        created_by: Set(volume_entity.created_by),
+        updated_at: Set(volume_entity.updated_at),
+        created_at: Set(volume_entity.created_at),
+    };
+
+    let volume_entity = volume_entity.insert(db_pool).await?;
+
+    Ok(volume_entity)
+}
+
+/// Creates a volume in the root namespace for each base volume.
+pub async fn ensure_base_volumes(
+    db: &DatabaseConnection,
+    volumes: &[BaseVolume],
+    root_owner: &str,
+) -> Result<(), DbErr> {
+    for volume in volumes {
+        debug!(
+            "Ensuring base volume '{}' on {}",
+            volume.name, volume.source
+        );
+        ensure_volume(
+            db,
+            BASE_VOLUME_NAMESPACE,
+            &volume.name,
+            root_owner,
+            &volume.source,
+            root_owner,
+            None,
+        )
+        .await?;
+    }
+    Ok(())
+}
+
+/// Checks every S3 backed base volume's bucket exists and the server's
+/// credentials can reach it, warning about those that can't. The server
+/// still starts, as a bucket may only be briefly unreachable and the other
+/// volumes still work. Other providers are left to rclone.
+pub async fn check_base_volumes(volumes: &[BaseVolume]) {
+    for volume in volumes {
+        let Some(bucket) = volume.s3_bucket() else {
+            info!(
+                "Skipping reachability check for base volume '{}' on {}",
+                volume.name, volume.source
+            );
+            continue;
+        };
+        let config = aws_config::defaults(BehaviorVersion::latest())
+            .region(Region::new(volume.region.clone()))
+            .load()
+            .await;
+        match aws_sdk_s3::Client::new(&config)
+            .head_bucket()
+            .bucket(bucket)
+            .send()
+            .await
+        {
+            Ok(_) => info!(
+                "Base volume '{}' on {} is reachable",
+                volume.name, volume.source
+            ),
+            Err(e) => warn!(
+                "Bucket '{}' for base volume '{}' is not reachable: {}",
+                bucket,
+                volume.name,
+                aws_sdk_s3::error::DisplayErrorContext(e)
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::parse_base_volumes;
+    use crate::entities::volumes;
+    use sea_orm::{ConnectionTrait, Database, EntityTrait, Schema};
+
+    #[tokio::test]
+    async fn test_ensure_base_volumes_creates_one_volume_per_bucket() {
+        // Arrange
+        let db = Database::connect("sqlite::memory:").await.unwrap();
+        let schema = Schema::new(db.get_database_backend());
+        db.execute(
+            db.get_database_backend()
+                .build(&schema.create_table_from_entity(volumes::Entity)),
+        )
+        .await
+        .unwrap();
+        let base = parse_base_volumes(
+            Some("eu=s3://nebu-eu@eu-west-1,gcs=gs://nebu-gcs"),
+            "nebu-main",
+            "us-east-1",
+        )
+        .unwrap();
+
+        // Act
+        ensure_base_volumes(&db, &base, "admin").await.unwrap();
+        // Running again at the next startup changes nothing
+        ensure_base_volumes(&db, &base, "admin").await.unwrap();
+
+        // Assert
+        let mut created: Vec<(String, String, String)> = volumes::Entity::find()
+            .all(&db)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|v| (v.namespace, v.name, v.source))
+            .collect();
+        created.sort();
+        assert_eq!(
+            created,
+            vec![
+                (
+                    "root".to_string(),
+                    "eu".to_string(),
+                    "s3://nebu-eu".to_string()
+                ),
+                (
+                    "root".to_string(),
+                    "gcs".to_string(),
+                    "gs://nebu-gcs".to_string()
+                ),
+                (
+                    "root".to_string(),
+                    "root".to_string(),
+                    "s3://nebu-main".to_string()
+                ),
+            ]
+        );
+    }
+}
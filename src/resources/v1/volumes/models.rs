@@ -22,6 +22,8 @@ fn default_volume_kind() -> String {
 
 #[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
 pub struct V1VolumePath {
+    /// May use `$VAR` from the container's env and the `{id}`, `{name}`,
+    /// `{namespace}` and `{owner}` placeholders, like `dest`
     pub source: String,
     pub dest: String,
     #[serde(default)]
@@ -30,6 +32,19 @@ pub struct V1VolumePath {
     pub continuous: bool,
     #[serde(default = "default_volume_driver")]
     pub driver: V1VolumeDriver,
+    /// Compare files by checksum instead of size and modification time
+    #[serde(default)]
+    pub checksum: bool,
+    /// Keep the object storage end gzip-compressed, reading and writing it
+    /// through an rclone `compress` remote. Checkpoints and other
+    /// compressible data transfer and store smaller, at the cost of CPU to
+    /// compress on upload and decompress on download; data that's already
+    /// compressed only gets slower. Anything synced with it must be read
+    /// back with it. Needs exactly one local end and isn't supported for
+    /// bidirectional syncs. Compressed files have no hashes to compare, so
+    /// `checksum` falls back to comparing sizes.
+    #[serde(default)]
+    pub compress: bool,
 }
 
 fn default_volume_driver() -> V1VolumeDriver {
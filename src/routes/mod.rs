@@ -1,23 +1,31 @@
 use crate::auth::server::handlers::{get_api_key, list_api_keys};
 use crate::handlers::v1::{
-    check_processor_health, create_container, create_namespace, create_processor,
-    create_scoped_s3_token, create_secret, create_volume, delete_cache_key, delete_container,
-    delete_container_by_id, delete_namespace, delete_processor, delete_scoped_s3_token,
-    delete_secret, delete_secret_by_id, delete_volume, fetch_container_logs,
-    fetch_container_logs_by_id, generate_temp_s3_credentials, get_cache_key, get_container,
-    get_container_by_id, get_namespace, get_processor, get_processor_logs, get_secret,
-    get_secret_by_id, get_user_profile, get_volume, list_cache_keys, list_containers,
-    list_namespaces, list_processors, list_secrets, list_volumes, patch_container,
-    processor_websocket, read_processor_stream, read_return_message, scale_processor,
-    search_containers, send_processor, stream_logs_ws, stream_logs_ws_by_id,
-    stream_processor_return_ws, update_processor, update_secret, update_secret_by_id,
+    ack_processor_stream, batch_container_status, batch_delete_containers, check_processor_health,
+    create_container, create_container_ssh_key, create_namespace, create_processor,
+    create_scoped_s3_token, create_secret, create_secrets, create_volume, delete_cache_key,
+    delete_container, delete_container_by_id, delete_namespace, delete_processor,
+    delete_scoped_s3_token, delete_secret, delete_secret_by_id, delete_volume,
+    fetch_container_logs, fetch_container_logs_by_id, generate_temp_s3_credentials, get_cache_key,
+    get_container, get_container_by_id, get_container_events, get_container_ssh, get_namespace,
+    get_namespace_spend, get_processor, get_processor_log_pages, get_processor_logs,
+    get_processor_pending, get_secret, get_secret_by_id, get_user_profile, get_volume,
+    list_accelerators, list_cache_key_pages, list_cache_keys, list_containers, list_namespaces,
+    list_processors, list_secrets, list_volumes, patch_container, peek_processor_stream,
+    processor_websocket, put_cache_key, read_processor_stream, read_return_message,
+    report_container_sync_progress, rotate_container_ssh, scale_processor, search_containers,
+    send_processor, start_container, stop_container, stream_logs_ws, stream_logs_ws_by_id,
+    stream_processor_return_ws, update_namespace, update_processor, update_secret,
+    update_secret_by_id,
 };
-use crate::handlers::{health_handler, root_handler};
+use crate::handlers::{health_handler, metrics_handler, ready_handler, root_handler};
+use crate::logging::{request_id_middleware, request_span};
 use crate::middleware::auth_middleware;
+use crate::proxy::containers::proxy_container_http;
+use crate::rate_limit::rate_limit_middleware;
 use crate::state::AppState;
 use axum::{
     middleware,
-    routing::{delete, get, post},
+    routing::{any, delete, get, post},
     Router,
 };
 use tower_http::trace::{self, TraceLayer};
@@ -27,7 +35,9 @@ pub fn create_routes(app_state: AppState) -> Router<AppState> {
     // Public routes that do not require authentication
     let public_routes = Router::new()
         .route("/", get(root_handler))
-        .route("/health", get(health_handler));
+        .route("/health", get(health_handler))
+        .route("/health/ready", get(ready_handler))
+        .route("/metrics", get(metrics_handler));
 
     // Private routes that require authentication
     let private_routes = Router::new()
@@ -50,27 +60,63 @@ pub fn create_routes(app_state: AppState) -> Router<AppState> {
             get(list_containers).post(create_container),
         )
         .route("/v1/containers/search", post(search_containers))
+        .route("/v1/containers/batch-delete", post(batch_delete_containers))
+        .route("/v1/containers/batch-status", post(batch_container_status))
         .route(
             "/v1/containers/:id",
             get(get_container_by_id).delete(delete_container_by_id),
         )
         .route("/v1/containers/:id/logs", get(fetch_container_logs_by_id))
         .route("/v1/containers/:id/logs/stream", get(stream_logs_ws_by_id))
+        .route(
+            "/v1/containers/:id/sync-progress",
+            post(report_container_sync_progress),
+        )
         .route(
             "/v1/containers/:namespace/:name",
             get(get_container)
                 .delete(delete_container)
                 .patch(patch_container),
         )
+        .route(
+            "/v1/containers/:namespace/:name/events",
+            get(get_container_events),
+        )
+        .route("/v1/containers/:namespace/:name/stop", post(stop_container))
+        .route(
+            "/v1/containers/:namespace/:name/start",
+            post(start_container),
+        )
+        .route(
+            "/v1/containers/:namespace/:name/rotate-ssh",
+            post(rotate_container_ssh),
+        )
+        .route(
+            "/v1/containers/:namespace/:name/ssh",
+            get(get_container_ssh),
+        )
+        .route(
+            "/v1/containers/:namespace/:name/ssh/keys",
+            post(create_container_ssh_key),
+        )
         .route(
             "/v1/containers/:namespace/:name/logs",
             get(fetch_container_logs),
         )
+        .route(
+            "/v1/containers/:namespace/:name/proxy",
+            any(proxy_container_http),
+        )
+        .route(
+            "/v1/containers/:namespace/:name/proxy/*path",
+            any(proxy_container_http),
+        )
         .route(
             "/v1/containers/:namespace/:name/logs/stream",
             get(stream_logs_ws),
         )
         .route("/v1/secrets", get(list_secrets).post(create_secret))
+        .route("/v1/secrets/bulk", post(create_secrets))
         .route(
             "/v1/secrets/:id",
             get(get_secret_by_id)
@@ -112,10 +158,26 @@ pub fn create_routes(app_state: AppState) -> Router<AppState> {
             "/v1/processors/:namespace/:name/logs",
             get(get_processor_logs),
         )
+        .route(
+            "/v1/processors/:namespace/:name/logs/pages",
+            get(get_processor_log_pages),
+        )
         .route(
             "/v1/processors/:namespace/:name/stream",
             post(read_processor_stream),
         )
+        .route(
+            "/v1/processors/:namespace/:name/stream/entries",
+            get(peek_processor_stream),
+        )
+        .route(
+            "/v1/processors/:namespace/:name/stream/ack",
+            post(ack_processor_stream),
+        )
+        .route(
+            "/v1/processors/:namespace/:name/stream/pending",
+            get(get_processor_pending),
+        )
         .route(
             "/v1/processors/:namespace/:name/return/:message_id",
             post(read_return_message),
@@ -128,10 +190,14 @@ pub fn create_routes(app_state: AppState) -> Router<AppState> {
             "/v1/processors/:namespace/:name/ws",
             get(processor_websocket),
         )
+        .route("/v1/accelerators", get(list_accelerators))
         .route("/v1/cache", get(list_cache_keys))
+        .route("/v1/cache/pages", get(list_cache_key_pages))
         .route(
             "/v1/cache/:namespace/:key",
-            get(get_cache_key).delete(delete_cache_key),
+            get(get_cache_key)
+                .put(put_cache_key)
+                .delete(delete_cache_key),
         )
         .route("/v1/users/me", get(get_user_profile))
         .route(
@@ -140,18 +206,29 @@ pub fn create_routes(app_state: AppState) -> Router<AppState> {
         )
         .route(
             "/v1/namespaces/:name",
-            get(get_namespace).delete(delete_namespace),
+            get(get_namespace)
+                .delete(delete_namespace)
+                .patch(update_namespace),
         )
+        .route("/v1/namespaces/:name/spend", get(get_namespace_spend))
         // Apply the authentication middleware to private routes
         .layer(middleware::from_fn_with_state(
             app_state.clone(),
             auth_middleware,
         ));
 
-    // Combine public and private routes
-    public_routes.merge(private_routes).layer(
-        TraceLayer::new_for_http()
-            .make_span_with(trace::DefaultMakeSpan::new().level(Level::INFO))
-            .on_response(trace::DefaultOnResponse::new().level(Level::INFO)),
-    )
+    // Combine public and private routes, rate limiting both per API key
+    public_routes
+        .merge(private_routes)
+        .layer(middleware::from_fn_with_state(
+            app_state.clone(),
+            rate_limit_middleware,
+        ))
+        .layer(
+            TraceLayer::new_for_http()
+                .make_span_with(request_span)
+                .on_response(trace::DefaultOnResponse::new().level(Level::INFO)),
+        )
+        // Outermost, so the request id exists before the trace span is made
+        .layer(middleware::from_fn(request_id_middleware))
 }
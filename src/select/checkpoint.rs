@@ -1,78 +1,297 @@
+use regex::Regex;
 use std::fs;
 use std::io;
 use std::path::{Path, PathBuf};
+use std::str::FromStr;
 
-/// Select a checkpoint directory by either "latest" (numerically highest)
-/// or "best" (highest eval score).
-///
-/// - base_dir: Base directory containing "checkpoint-N" subdirectories.
-/// - criteria: Either "latest" or "best".
+/// Metric used by the bare `best` criterion, kept for compatibility.
+const DEFAULT_BEST_METRIC: &str = "eval_loss";
+
+/// Whether a metric should be minimized or maximized.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Goal {
+    Min,
+    Max,
+}
+
+/// A checkpoint selection criterion.
 ///
-/// Returns the path of the selected checkpoint directory, or None if none found.
-pub fn select_checkpoint(base_dir: &Path, criteria: &str) -> io::Result<Option<PathBuf>> {
-    // Read all directories inside base_dir.
-    let entries = fs::read_dir(base_dir)?;
-
-    let mut best_path: Option<PathBuf> = None;
-
-    // For "latest" we track the highest checkpoint number.
-    let mut best_checkpoint_num: i64 = -1;
-
-    // For "best" (lowest eval loss), we start with a large sentinel value.
-    let mut best_loss: f64 = f64::MAX;
-
-    for entry in entries {
-        let entry = entry?;
-        let path = entry.path();
-
-        // Only proceed if it's a directory named "checkpoint-<number>".
-        if path.is_dir() {
-            if let Some(dir_name) = path.file_name().and_then(|n| n.to_str()) {
-                if let Some(checkpoint_num_str) = dir_name.strip_prefix("checkpoint-") {
-                    // Attempt to parse the trailing number.
-                    if let Ok(checkpoint_num) = checkpoint_num_str.parse::<i64>() {
-                        match criteria {
-                            "latest" => {
-                                // We pick the largest checkpoint number.
-                                if checkpoint_num > best_checkpoint_num {
-                                    best_checkpoint_num = checkpoint_num;
-                                    best_path = Some(path.clone());
-                                }
-                            }
-                            "best" => {
-                                // Look for Hugging Face `trainer_state.json`.
-                                let trainer_state_file = path.join("trainer_state.json");
-
-                                // Default to "very large" if we can't read or parse.
-                                let eval_loss = match fs::read_to_string(&trainer_state_file) {
-                                    Ok(contents) => {
-                                        match serde_json::from_str::<serde_json::Value>(&contents) {
-                                            Ok(json_val) => json_val
-                                                .get("metrics")
-                                                .and_then(|metrics| metrics.get("eval_loss"))
-                                                .and_then(|val| val.as_f64())
-                                                .unwrap_or(f64::MAX),
-                                            Err(_) => f64::MAX,
-                                        }
-                                    }
-                                    Err(_) => f64::MAX,
-                                };
-
-                                // Update the "best checkpoint" when we find a new lowest eval_loss.
-                                if eval_loss < best_loss {
-                                    best_loss = eval_loss;
-                                    best_path = Some(path.clone());
-                                }
-                            }
-                            _ => {
-                                // Unrecognized criterion, do nothing or handle as an error.
-                            }
-                        }
+/// - `latest`: the highest numbered `checkpoint-<n>` directory.
+/// - `best:<metric>:<min|max>`: the checkpoint with the lowest or highest value
+///   of `metric`, read from its metrics file. `best` alone means `best:eval_loss:min`.
+/// - `step:<n>`: the checkpoint for step `n`.
+/// - `glob:<pattern>`: the latest directory whose name matches `pattern`
+///   (`*` matches any run of characters, `?` a single one).
+#[derive(Debug, Clone, PartialEq)]
+pub enum Selector {
+    Latest,
+    Best { metric: String, goal: Goal },
+    Step(i64),
+    Glob(String),
+}
+
+impl FromStr for Selector {
+    type Err = io::Error;
+
+    fn from_str(criteria: &str) -> Result<Self, Self::Err> {
+        let invalid = |msg: String| io::Error::new(io::ErrorKind::InvalidInput, msg);
+
+        let (kind, arg) = match criteria.split_once(':') {
+            Some((kind, arg)) => (kind, Some(arg)),
+            None => (criteria, None),
+        };
+
+        match (kind, arg) {
+            ("latest", None) => Ok(Selector::Latest),
+            ("best", None) => Ok(Selector::Best {
+                metric: DEFAULT_BEST_METRIC.to_string(),
+                goal: Goal::Min,
+            }),
+            ("best", Some(arg)) => {
+                let (metric, goal) = arg.rsplit_once(':').ok_or_else(|| {
+                    invalid(format!(
+                        "Invalid criteria '{}': expected 'best:<metric>:<min|max>'",
+                        criteria
+                    ))
+                })?;
+                let goal = match goal {
+                    "min" => Goal::Min,
+                    "max" => Goal::Max,
+                    _ => {
+                        return Err(invalid(format!(
+                            "Invalid criteria '{}': goal must be 'min' or 'max'",
+                            criteria
+                        )))
                     }
+                };
+                if metric.is_empty() {
+                    return Err(invalid(format!(
+                        "Invalid criteria '{}': metric name is empty",
+                        criteria
+                    )));
                 }
+                Ok(Selector::Best {
+                    metric: metric.to_string(),
+                    goal,
+                })
             }
+            ("step", Some(arg)) => arg.parse::<i64>().map(Selector::Step).map_err(|_| {
+                invalid(format!(
+                    "Invalid criteria '{}': step must be an integer",
+                    criteria
+                ))
+            }),
+            ("glob", Some(arg)) if !arg.is_empty() => Ok(Selector::Glob(arg.to_string())),
+            _ => Err(invalid(format!(
+                "Unknown criteria '{}'. Supported: latest, best:<metric>:<min|max>, step:<n>, glob:<pattern>",
+                criteria
+            ))),
+        }
+    }
+}
+
+/// Select a checkpoint directory from `base_dir` using `criteria`, see [`Selector`].
+///
+/// - base_dir: Base directory containing "checkpoint-N" subdirectories.
+/// - criteria: A selector such as "latest", "best:eval_loss:min", "step:500" or "glob:run-*".
+///
+/// Returns the path of the selected checkpoint directory, None if none matched,
+/// or an `InvalidInput` error for unknown criteria.
+pub fn select_checkpoint(base_dir: &Path, criteria: &str) -> io::Result<Option<PathBuf>> {
+    let selector: Selector = criteria.parse()?;
+
+    let mut dirs = Vec::new();
+    for entry in fs::read_dir(base_dir)? {
+        let path = entry?.path();
+        if !path.is_dir() {
+            continue;
+        }
+        if let Some(name) = path.file_name().and_then(|n| n.to_str()) {
+            dirs.push((name.to_string(), checkpoint_step(name), path.clone()));
+        }
+    }
+
+    let selected = match selector {
+        Selector::Latest => dirs
+            .into_iter()
+            .filter_map(|(_, step, path)| step.map(|step| (step, path)))
+            .max_by_key(|(step, _)| *step)
+            .map(|(_, path)| path),
+        Selector::Step(n) => dirs
+            .into_iter()
+            .find(|(_, step, _)| *step == Some(n))
+            .map(|(_, _, path)| path),
+        Selector::Glob(pattern) => {
+            let matcher = glob_regex(&pattern);
+            dirs.into_iter()
+                .filter(|(name, _, _)| matcher.is_match(name))
+                .max_by(|(a_name, a_step, _), (b_name, b_step, _)| {
+                    a_step.cmp(b_step).then_with(|| a_name.cmp(b_name))
+                })
+                .map(|(_, _, path)| path)
+        }
+        Selector::Best { metric, goal } => dirs
+            .into_iter()
+            .filter(|(_, step, _)| step.is_some())
+            .filter_map(|(_, _, path)| read_metric(&path, &metric).map(|value| (value, path)))
+            .max_by(|(a, _), (b, _)| match goal {
+                Goal::Max => a.total_cmp(b),
+                Goal::Min => b.total_cmp(a),
+            })
+            .map(|(_, path)| path),
+    };
+
+    Ok(selected)
+}
+
+/// Parses the step out of a "checkpoint-<n>" directory name.
+fn checkpoint_step(dir_name: &str) -> Option<i64> {
+    dir_name.strip_prefix("checkpoint-")?.parse::<i64>().ok()
+}
+
+/// Reads a metric for a checkpoint, from a top level key of `metrics.json` or
+/// from the `metrics` object of a Hugging Face `trainer_state.json`.
+fn read_metric(checkpoint_dir: &Path, metric: &str) -> Option<f64> {
+    let read_json = |file: &str| -> Option<serde_json::Value> {
+        let contents = fs::read_to_string(checkpoint_dir.join(file)).ok()?;
+        serde_json::from_str(&contents).ok()
+    };
+
+    read_json("metrics.json")
+        .and_then(|json| json.get(metric).and_then(|v| v.as_f64()))
+        .or_else(|| {
+            read_json("trainer_state.json").and_then(|json| {
+                json.get("metrics")
+                    .and_then(|metrics| metrics.get(metric))
+                    .and_then(|v| v.as_f64())
+            })
+        })
+        .filter(|value| !value.is_nan())
+}
+
+/// Translates a shell style glob into an anchored regex.
+fn glob_regex(pattern: &str) -> Regex {
+    let mut regex = String::from("^");
+    for c in pattern.chars() {
+        match c {
+            '*' => regex.push_str(".*"),
+            '?' => regex.push('.'),
+            c => regex.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    regex.push('$');
+    Regex::new(&regex).expect("escaped glob is a valid regex")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// checkpoint-100, checkpoint-200, checkpoint-1000 and run-best with metrics
+    fn checkpoints() -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        for (name, metrics) in [
+            ("checkpoint-100", r#"{"eval_loss": 0.9, "accuracy": 0.70}"#),
+            ("checkpoint-200", r#"{"eval_loss": 0.4, "accuracy": 0.81}"#),
+            ("checkpoint-1000", r#"{"eval_loss": 0.6, "accuracy": 0.85}"#),
+            ("run-best", r#"{"eval_loss": 0.1, "accuracy": 0.99}"#),
+        ] {
+            let path = dir.path().join(name);
+            fs::create_dir(&path).unwrap();
+            fs::write(path.join("metrics.json"), metrics).unwrap();
+        }
+        fs::write(dir.path().join("checkpoint-5000"), "not a directory").unwrap();
+        dir
+    }
+
+    fn selected_name(base_dir: &Path, criteria: &str) -> Option<String> {
+        select_checkpoint(base_dir, criteria)
+            .unwrap()
+            .map(|path| path.file_name().unwrap().to_str().unwrap().to_string())
+    }
+
+    #[test]
+    fn test_select_latest() {
+        // Arrange
+        let dir = checkpoints();
+
+        // Act
+        let selected = selected_name(dir.path(), "latest");
+
+        // Assert
+        assert_eq!(selected.as_deref(), Some("checkpoint-1000"));
+    }
+
+    #[test]
+    fn test_select_best_min_and_max() {
+        let dir = checkpoints();
+        assert_eq!(
+            selected_name(dir.path(), "best:eval_loss:min").as_deref(),
+            Some("checkpoint-200")
+        );
+        assert_eq!(
+            selected_name(dir.path(), "best:accuracy:max").as_deref(),
+            Some("checkpoint-1000")
+        );
+        assert_eq!(selected_name(dir.path(), "best:missing:max"), None);
+    }
+
+    #[test]
+    fn test_select_best_defaults_to_eval_loss_from_trainer_state() {
+        let dir = tempfile::tempdir().unwrap();
+        for (name, loss) in [("checkpoint-1", 0.5), ("checkpoint-2", 0.3)] {
+            let path = dir.path().join(name);
+            fs::create_dir(&path).unwrap();
+            fs::write(
+                path.join("trainer_state.json"),
+                format!(r#"{{"metrics": {{"eval_loss": {}}}}}"#, loss),
+            )
+            .unwrap();
         }
+
+        assert_eq!(
+            selected_name(dir.path(), "best").as_deref(),
+            Some("checkpoint-2")
+        );
+    }
+
+    #[test]
+    fn test_select_step() {
+        let dir = checkpoints();
+        assert_eq!(
+            selected_name(dir.path(), "step:200").as_deref(),
+            Some("checkpoint-200")
+        );
+        assert_eq!(selected_name(dir.path(), "step:300"), None);
     }
 
-    Ok(best_path)
+    #[test]
+    fn test_select_glob() {
+        let dir = checkpoints();
+        assert_eq!(
+            selected_name(dir.path(), "glob:checkpoint-?00").as_deref(),
+            Some("checkpoint-200")
+        );
+        assert_eq!(
+            selected_name(dir.path(), "glob:run-*").as_deref(),
+            Some("run-best")
+        );
+        assert_eq!(selected_name(dir.path(), "glob:nothing*"), None);
+    }
+
+    #[test]
+    fn test_select_unknown_criteria_is_an_error() {
+        let dir = checkpoints();
+        for criteria in [
+            "newest",
+            "best:eval_loss",
+            "best:eval_loss:lowest",
+            "step:abc",
+            "glob:",
+            "latest:1",
+        ] {
+            let err = select_checkpoint(dir.path(), criteria).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidInput, "{}", criteria);
+        }
+    }
 }
@@ -51,35 +51,43 @@ pub fn run_ssh_command_ts(
         command, username
     );
 
+    let mut ssh_cmd = ssh_command(hostname, interactive, tty, username);
+
+    // Append the command to be run
+    ssh_cmd.args(command);
+
+    // Capture output
+    let output = ssh_cmd
+        .output()
+        .map_err(|err| IoError::new(ErrorKind::Other, format!("Failed to spawn ssh: {err}")))?;
+
+    ssh_output(output)
+}
+
+/// `ssh` to `hostname` as `username`, without a command yet
+fn ssh_command(hostname: &str, interactive: bool, tty: bool, username: Option<&str>) -> Command {
     let mut ssh_cmd = Command::new("ssh");
 
     // Disable host key checking and skip writing to known_hosts:
     ssh_cmd.arg("-o").arg("StrictHostKeyChecking=no");
     ssh_cmd.arg("-o").arg("UserKnownHostsFile=/dev/null");
 
+    // "ssh -l user host", so a user can't be read as an option
     if let Some(u) = username {
-        // Option A: "ssh user@host"
-        ssh_cmd.arg(format!("{u}@{hostname}"));
-        // Option B (commented out): "ssh -l user host"
-        // ssh_cmd.arg("-l").arg(u).arg(hostname);
-    } else {
-        ssh_cmd.arg(hostname);
+        ssh_cmd.arg("-l").arg(u);
     }
+    ssh_cmd.arg(hostname);
 
     // For an interactive session (i.e. keep STDIN open) you typically need at least one `-t`.
     // You can do further logic if you want to differentiate single-tty vs. forced double-tty:
     if interactive || tty {
         ssh_cmd.arg("-t");
     }
+    ssh_cmd
+}
 
-    // Append the command to be run
-    ssh_cmd.args(command);
-
-    // Capture output
-    let output = ssh_cmd
-        .output()
-        .map_err(|err| IoError::new(ErrorKind::Other, format!("Failed to spawn ssh: {err}")))?;
-
+/// The stdout of a finished ssh, or its stderr as the error when it failed
+fn ssh_output(output: std::process::Output) -> Result<String, IoError> {
     if !output.status.success() {
         return Err(IoError::new(
             ErrorKind::Other,
@@ -94,6 +102,33 @@ pub fn run_ssh_command_ts(
     Ok(String::from_utf8_lossy(&output.stdout).to_string())
 }
 
+/// Runs a non-interactive command like `run_ssh_command_ts` without blocking
+/// the runtime. Dropping the future, as a timeout does, kills the ssh process.
+pub async fn run_ssh_command_async(
+    hostname: &str,
+    command: Vec<String>,
+    username: Option<&str>,
+) -> Result<String, IoError> {
+    debug!(
+        "Running SSH command: '{:?}' on {hostname} as {:?}",
+        command, username
+    );
+    let mut ssh_cmd = tokio::process::Command::from(ssh_command(hostname, false, false, username));
+    ssh_cmd.args(command);
+    ssh_output(killed_on_drop_output(ssh_cmd).await?)
+}
+
+async fn killed_on_drop_output(
+    mut command: tokio::process::Command,
+) -> Result<std::process::Output, IoError> {
+    command
+        .stdin(Stdio::null())
+        .kill_on_drop(true)
+        .output()
+        .await
+        .map_err(|err| IoError::new(ErrorKind::Other, format!("Failed to spawn ssh: {err}")))
+}
+
 /// Executes a command via SSH and streams its output directly to stdio.
 ///
 /// This is similar to `run_ssh_command_ts` but does not buffer the output.
@@ -117,10 +152,9 @@ pub fn stream_ssh_command_ts(
     ssh_cmd.arg("-o").arg("UserKnownHostsFile=/dev/null");
 
     if let Some(u) = username {
-        ssh_cmd.arg(format!("{}@{}", u, hostname));
-    } else {
-        ssh_cmd.arg(hostname);
+        ssh_cmd.arg("-l").arg(u);
     }
+    ssh_cmd.arg(hostname);
 
     if interactive || tty {
         ssh_cmd.arg("-t");
@@ -415,3 +449,29 @@ pub fn open_ssh_shell(
     channel.wait_close()?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_command_is_killed_when_timed_out() {
+        // Arrange
+        let dir = tempfile::tempdir().unwrap();
+        let marker = dir.path().join("finished");
+        let mut command = tokio::process::Command::new("sh");
+        command
+            .arg("-c")
+            .arg(format!("sleep 0.5; touch '{}'", marker.display()));
+
+        // Act
+        let result =
+            tokio::time::timeout(Duration::from_millis(50), killed_on_drop_output(command)).await;
+        tokio::time::sleep(Duration::from_millis(800)).await;
+
+        // Assert
+        assert!(result.is_err());
+        assert!(!marker.exists());
+    }
+}
@@ -3,8 +3,12 @@ use rdkafka::admin::AdminClient;
 use rdkafka::client::DefaultClientContext;
 use rdkafka::producer::FutureProducer;
 use redis::Client as RedisClient;
+use std::collections::HashMap;
 use std::sync::Arc;
 
+/// Queue backends a processor can be declared with
+pub const QUEUE_BACKENDS: &[&str] = &["redis", "kafka"];
+
 #[derive(Clone)]
 pub enum MessageQueue {
     Kafka {
@@ -16,8 +20,133 @@ pub enum MessageQueue {
     },
 }
 
+impl MessageQueue {
+    /// The name processors pick this backend by
+    pub fn backend(&self) -> &'static str {
+        match self {
+            MessageQueue::Kafka { .. } => "kafka",
+            MessageQueue::Redis { .. } => "redis",
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct AppState {
     pub db_pool: DbPool,
+    /// The server's default queue, used by processors that don't pick one
     pub message_queue: MessageQueue,
+    /// Further configured queues by backend name, for processors that pick them
+    pub extra_queues: HashMap<String, MessageQueue>,
+}
+
+impl AppState {
+    /// The queue for a processor's `backend`, the default one for `None`.
+    /// Errors when this server has no queue of that backend configured.
+    pub fn queue(&self, backend: Option<&str>) -> Result<&MessageQueue, String> {
+        match backend {
+            None => Ok(&self.message_queue),
+            Some(backend) if backend == self.message_queue.backend() => Ok(&self.message_queue),
+            Some(backend) => self.extra_queues.get(backend).ok_or_else(|| {
+                let mut configured: Vec<&str> = std::iter::once(self.message_queue.backend())
+                    .chain(self.extra_queues.keys().map(String::as_str))
+                    .collect();
+                configured.sort();
+                format!(
+                    "Queue backend '{}' is not configured on this server, expected one of: {}",
+                    backend,
+                    configured.join(", ")
+                )
+            }),
+        }
+    }
+
+    /// The Redis a processor's replica and scaling state lives in: its own
+    /// queue when that is Redis, otherwise any configured Redis queue
+    pub fn processor_redis(&self, backend: Option<&str>) -> Result<&Arc<RedisClient>, String> {
+        if let MessageQueue::Redis { client } = self.queue(backend)? {
+            return Ok(client);
+        }
+        std::iter::once(&self.message_queue)
+            .chain(self.extra_queues.values())
+            .find_map(|queue| match queue {
+                MessageQueue::Redis { client } => Some(client),
+                MessageQueue::Kafka { .. } => None,
+            })
+            .ok_or_else(|| {
+                "Processors on Kafka need a Redis queue configured as well, to keep their state in"
+                    .to_string()
+            })
+    }
+}
+
+#[cfg(test)]
+impl AppState {
+    /// Redis by default with Kafka besides, on an in-memory database.
+    /// Neither queue connects until it is used.
+    pub(crate) async fn with_kafka_for_tests() -> Self {
+        let kafka_config = rdkafka::ClientConfig::new()
+            .set("bootstrap.servers", "127.0.0.1:1")
+            .clone();
+        let kafka = MessageQueue::Kafka {
+            producer: Arc::new(kafka_config.create().unwrap()),
+            admin: Arc::new(kafka_config.create().unwrap()),
+        };
+        AppState {
+            db_pool: sea_orm::Database::connect("sqlite::memory:").await.unwrap(),
+            message_queue: MessageQueue::Redis {
+                client: Arc::new(RedisClient::open("redis://127.0.0.1:1").unwrap()),
+            },
+            extra_queues: HashMap::from([("kafka".to_string(), kafka)]),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::resources::v1::processors::models::V1ProcessorRequest;
+
+    #[tokio::test]
+    async fn test_processors_dispatch_to_their_own_backend() {
+        // Arrange
+        let state = AppState::with_kafka_for_tests().await;
+        let on_default = V1ProcessorRequest::default();
+        let on_kafka = V1ProcessorRequest {
+            queue_backend: Some("kafka".to_string()),
+            ..Default::default()
+        };
+
+        // Act
+        let default_queue = state.queue(on_default.queue_backend.as_deref()).unwrap();
+        let kafka_queue = state.queue(on_kafka.queue_backend.as_deref()).unwrap();
+
+        // Assert
+        assert_eq!(default_queue.backend(), "redis");
+        assert_eq!(kafka_queue.backend(), "kafka");
+        assert_eq!(state.queue(Some("redis")).unwrap().backend(), "redis");
+    }
+
+    #[tokio::test]
+    async fn test_unconfigured_backend_is_rejected() {
+        let mut state = AppState::with_kafka_for_tests().await;
+        state.extra_queues.clear();
+
+        let err = state.queue(Some("kafka")).err().unwrap();
+
+        assert!(err.contains("'kafka' is not configured"));
+        assert!(err.ends_with("expected one of: redis"));
+        assert!(state.queue(Some("rabbitmq")).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_kafka_processors_keep_state_in_redis() {
+        let mut state = AppState::with_kafka_for_tests().await;
+        let redis = state.processor_redis(None).unwrap().clone();
+
+        let for_kafka = state.processor_redis(Some("kafka")).unwrap();
+        assert!(Arc::ptr_eq(for_kafka, &redis));
+
+        state.message_queue = state.extra_queues.remove("kafka").unwrap();
+        assert!(state.processor_redis(None).is_err());
+    }
 }
@@ -1,5 +1,25 @@
-use redis::streams::{StreamInfoGroupsReply, StreamPendingReply};
+use redis::streams::{
+    StreamClaimReply, StreamId, StreamInfoGroupsReply, StreamPendingCountReply, StreamPendingReply,
+    StreamRangeReply, StreamReadReply,
+};
 use redis::{Commands, Connection, RedisResult};
+use std::collections::HashMap;
+
+/// Consumer that parks entries reclaimed from idle consumers until another
+/// reader picks them up.
+pub const RECLAIM_CONSUMER: &str = "nebu:reclaimed";
+
+/// Entries handled per XAUTOCLAIM round trip
+const RECLAIM_BATCH: usize = 100;
+
+/// Most entries kept in a dead-letter stream, oldest go first
+const DEAD_LETTER_MAX_LEN: u64 = 10_000;
+
+/// Where entries of `stream_key` handed out too often end up, see
+/// [`dead_letter_entries`]
+pub fn dead_letter_stream(stream_key: &str) -> String {
+    format!("{}:dead", stream_key)
+}
 
 // This is our custom struct, not from redis::streams
 #[derive(Debug, Clone)]
@@ -66,6 +86,284 @@ pub fn get_consumer_group_progress(
     })
 }
 
+/// Pending and undelivered counts for one consumer group.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GroupBacklog {
+    /// Delivered but not yet acknowledged
+    pub pending: u64,
+    /// Not yet delivered to the group
+    pub lag: u64,
+    /// Pending entries per consumer, including reclaimed ones
+    pub consumers: Vec<(String, u64)>,
+}
+
+pub fn get_group_backlog(
+    con: &mut Connection,
+    stream_key: &str,
+    group_name: &str,
+) -> RedisResult<GroupBacklog> {
+    let (pending, consumers) = match con.xpending(stream_key, group_name)? {
+        StreamPendingReply::Data(data) => (
+            data.count as u64,
+            data.consumers
+                .into_iter()
+                .map(|c| (c.name, c.pending as u64))
+                .collect(),
+        ),
+        StreamPendingReply::Empty => (0, Vec::new()),
+    };
+
+    let groups_info: StreamInfoGroupsReply = con.xinfo_groups(stream_key)?;
+    let lag = groups_info
+        .groups
+        .iter()
+        .find(|group| group.name == group_name)
+        .and_then(|group| group.lag)
+        .unwrap_or(0) as u64;
+
+    Ok(GroupBacklog {
+        pending,
+        lag,
+        consumers,
+    })
+}
+
+/// A stream's length, newest entry id and consumer groups, as `XINFO` reports them.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StreamInfo {
+    pub length: u64,
+    /// Id of the newest entry ever added, None for a stream never written to
+    pub last_generated_id: Option<String>,
+    pub groups: Vec<StreamGroupInfo>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct StreamGroupInfo {
+    pub name: String,
+    pub consumers: u64,
+    /// Delivered but not yet acknowledged
+    pub pending: u64,
+    pub last_delivered_id: String,
+    /// Not yet delivered to the group, None when Redis can't tell
+    pub lag: Option<u64>,
+}
+
+/// Reads `XINFO STREAM` and `XINFO GROUPS`. A stream that doesn't exist yet
+/// is reported empty rather than as an error.
+pub fn get_stream_info(con: &mut Connection, stream_key: &str) -> RedisResult<StreamInfo> {
+    // Read field by field, the first and last entries aren't needed and are
+    // nil on an empty stream
+    let fields: HashMap<String, redis::Value> =
+        match redis::cmd("XINFO").arg("STREAM").arg(stream_key).query(con) {
+            Ok(fields) => fields,
+            Err(e) if e.to_string().contains("no such key") => return Ok(StreamInfo::default()),
+            Err(e) => return Err(e),
+        };
+    let length: u64 = match fields.get("length") {
+        Some(value) => redis::from_redis_value(value)?,
+        None => 0,
+    };
+    let last_generated_id = match fields.get("last-generated-id") {
+        Some(value) => Some(redis::from_redis_value::<String>(value)?),
+        None => None,
+    }
+    .filter(|id| id != "0-0");
+
+    let groups_info: StreamInfoGroupsReply = con.xinfo_groups(stream_key)?;
+    let groups = groups_info
+        .groups
+        .into_iter()
+        .map(|group| StreamGroupInfo {
+            name: group.name,
+            consumers: group.consumers as u64,
+            pending: group.pending as u64,
+            last_delivered_id: group.last_delivered_id,
+            lag: group.lag.map(|lag| lag as u64),
+        })
+        .collect();
+
+    Ok(StreamInfo {
+        length,
+        last_generated_id,
+        groups,
+    })
+}
+
+/// Acknowledges entries for a group, returning how many were pending.
+pub fn ack_entries(
+    con: &mut Connection,
+    stream_key: &str,
+    group_name: &str,
+    ids: &[String],
+) -> RedisResult<u64> {
+    if ids.is_empty() {
+        return Ok(0);
+    }
+    con.xack(stream_key, group_name, ids)
+}
+
+/// Moves entries that have been pending longer than `min_idle_ms` (their
+/// consumer crashed or gave up) to [`RECLAIM_CONSUMER`], where
+/// [`take_reclaimed_entries`] hands them to the next reader. Returns how many
+/// entries were claimed; parked entries nobody has taken yet are claimed
+/// again and counted too.
+pub fn reclaim_idle_entries(
+    con: &mut Connection,
+    stream_key: &str,
+    group_name: &str,
+    min_idle_ms: u64,
+) -> RedisResult<usize> {
+    let mut cursor = "0-0".to_string();
+    let mut reclaimed = 0;
+    loop {
+        // Reply is [next cursor, claimed ids, deleted ids (Redis 7+)]
+        let reply: Vec<redis::Value> = redis::cmd("XAUTOCLAIM")
+            .arg(stream_key)
+            .arg(group_name)
+            .arg(RECLAIM_CONSUMER)
+            .arg(min_idle_ms)
+            .arg(&cursor)
+            .arg("COUNT")
+            .arg(RECLAIM_BATCH)
+            .arg("JUSTID")
+            .query(con)?;
+        let mut parts = reply.iter();
+        let next: String = match parts.next() {
+            Some(value) => redis::from_redis_value(value)?,
+            None => break,
+        };
+        let claimed: Vec<String> = match parts.next() {
+            Some(value) => redis::from_redis_value(value)?,
+            None => Vec::new(),
+        };
+
+        reclaimed += claimed.len();
+        if next == "0-0" {
+            break;
+        }
+        cursor = next;
+    }
+    Ok(reclaimed)
+}
+
+/// Moves reclaimed entries that were already handed out `max_deliveries`
+/// times to [`dead_letter_stream`] and acknowledges them, so a message that
+/// keeps failing its consumers stops coming back. The dead-letter entry
+/// keeps the original fields and adds `nebu_source_id`, `nebu_group` and
+/// `nebu_deliveries`. Returns how many entries were moved.
+pub fn dead_letter_entries(
+    con: &mut Connection,
+    stream_key: &str,
+    group_name: &str,
+    max_deliveries: u64,
+) -> RedisResult<usize> {
+    let dead_letter = dead_letter_stream(stream_key);
+    let mut start = "-".to_string();
+    let mut moved = 0;
+    loop {
+        let parked: StreamPendingCountReply = con.xpending_consumer_count(
+            stream_key,
+            group_name,
+            &start,
+            "+",
+            RECLAIM_BATCH,
+            RECLAIM_CONSUMER,
+        )?;
+        let Some(last) = parked.ids.last() else {
+            break;
+        };
+        start = format!("({}", last.id);
+
+        for pending in parked.ids.iter() {
+            let deliveries = pending.times_delivered as u64;
+            if deliveries < max_deliveries {
+                continue;
+            }
+            let mut pipe = redis::pipe();
+            pipe.atomic();
+            // Trimmed entries have nothing left to keep, they're only acked
+            let range: StreamRangeReply = con.xrange(stream_key, &pending.id, &pending.id)?;
+            if let Some(entry) = range.ids.first() {
+                let add = pipe
+                    .cmd("XADD")
+                    .arg(&dead_letter)
+                    .arg("MAXLEN")
+                    .arg("~")
+                    .arg(DEAD_LETTER_MAX_LEN)
+                    .arg("*");
+                for (field, value) in &entry.map {
+                    add.arg(field)
+                        .arg(redis::from_redis_value::<Vec<u8>>(value)?);
+                }
+                add.arg("nebu_source_id")
+                    .arg(&pending.id)
+                    .arg("nebu_group")
+                    .arg(group_name)
+                    .arg("nebu_deliveries")
+                    .arg(deliveries)
+                    .ignore();
+            }
+            pipe.xack(stream_key, group_name, &[&pending.id]).ignore();
+            let () = pipe.query(con)?;
+            moved += 1;
+        }
+        if parked.ids.len() < RECLAIM_BATCH {
+            break;
+        }
+    }
+    Ok(moved)
+}
+
+/// Claims up to `count` reclaimed entries for `consumer`, leaving out those
+/// handed out `max_deliveries` times already for [`dead_letter_entries`].
+pub fn take_reclaimed_entries(
+    con: &mut Connection,
+    stream_key: &str,
+    group_name: &str,
+    consumer: &str,
+    count: usize,
+    max_deliveries: u64,
+) -> RedisResult<Vec<StreamId>> {
+    if count == 0 {
+        return Ok(Vec::new());
+    }
+    let parked: StreamPendingCountReply =
+        con.xpending_consumer_count(stream_key, group_name, "-", "+", count, RECLAIM_CONSUMER)?;
+    let ids: Vec<String> = parked
+        .ids
+        .into_iter()
+        .filter(|p| (p.times_delivered as u64) < max_deliveries)
+        .map(|p| p.id)
+        .collect();
+    if ids.is_empty() {
+        return Ok(Vec::new());
+    }
+    let claimed: StreamClaimReply = con.xclaim(stream_key, group_name, consumer, 0, &ids)?;
+    Ok(claimed.ids)
+}
+
+/// Reads up to `count` entries added after `after` without a consumer group,
+/// so nothing is delivered, left pending or created on the stream. Waits up
+/// to `block_ms` for some to arrive when there are none yet.
+pub fn peek_entries(
+    con: &mut Connection,
+    stream_key: &str,
+    after: &str,
+    count: usize,
+    block_ms: Option<u64>,
+) -> RedisResult<Vec<StreamId>> {
+    let mut cmd = redis::cmd("XREAD");
+    cmd.arg("COUNT").arg(count);
+    if let Some(block_ms) = block_ms {
+        cmd.arg("BLOCK").arg(block_ms);
+    }
+    let reply: Option<StreamReadReply> =
+        cmd.arg("STREAMS").arg(stream_key).arg(after).query(con)?;
+    Ok(reply
+        .map(|reply| reply.keys.into_iter().flat_map(|key| key.ids).collect())
+        .unwrap_or_default())
+}
+
 // fn main() -> redis::RedisResult<()> {
 //     let client = Client::open("redis://127.0.0.1/")?;
 //     let mut con = client.get_connection()?;
@@ -93,3 +391,206 @@ pub fn get_consumer_group_progress(
 
 //     Ok(())
 // }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GROUP: &str = "group";
+
+    /// A fresh stream with an empty consumer group, when `NEBU_TEST_REDIS_URL` is set.
+    fn test_stream() -> Option<(Connection, String)> {
+        let url = std::env::var("NEBU_TEST_REDIS_URL").ok()?;
+        let mut con = redis::Client::open(url).unwrap().get_connection().unwrap();
+        let stream = format!("test:group:{}", short_uuid::ShortUuid::generate());
+        let _: () = redis::cmd("XGROUP")
+            .arg("CREATE")
+            .arg(&stream)
+            .arg(GROUP)
+            .arg("0")
+            .arg("MKSTREAM")
+            .query(&mut con)
+            .unwrap();
+        Some((con, stream))
+    }
+
+    fn add_and_read(
+        con: &mut Connection,
+        stream: &str,
+        consumer: &str,
+        count: usize,
+    ) -> Vec<String> {
+        for i in 0..count {
+            let _: String = con.xadd(stream, "*", &[("data", i)]).unwrap();
+        }
+        let reply: StreamReadReply = redis::cmd("XREADGROUP")
+            .arg("GROUP")
+            .arg(GROUP)
+            .arg(consumer)
+            .arg("STREAMS")
+            .arg(stream)
+            .arg(">")
+            .query(con)
+            .unwrap();
+        reply
+            .keys
+            .into_iter()
+            .flat_map(|key| key.ids)
+            .map(|entry| entry.id)
+            .collect()
+    }
+
+    #[test]
+    #[ignore = "needs a Redis at NEBU_TEST_REDIS_URL"]
+    fn test_ack_clears_pending() {
+        // Arrange
+        let (mut con, stream) = test_stream().expect("NEBU_TEST_REDIS_URL is not set");
+        let ids = add_and_read(&mut con, &stream, "worker-1", 2);
+        assert_eq!(
+            get_group_backlog(&mut con, &stream, GROUP).unwrap().pending,
+            2
+        );
+
+        // Act
+        let acked = ack_entries(&mut con, &stream, GROUP, &ids[..1]).unwrap();
+
+        // Assert
+        let backlog = get_group_backlog(&mut con, &stream, GROUP).unwrap();
+        let _: () = con.del(&stream).unwrap();
+        assert_eq!(acked, 1);
+        assert_eq!(backlog.pending, 1);
+        assert_eq!(backlog.lag, 0);
+        assert_eq!(backlog.consumers, vec![("worker-1".to_string(), 1)]);
+    }
+
+    #[test]
+    #[ignore = "needs a Redis at NEBU_TEST_REDIS_URL"]
+    fn test_reclaim_hands_idle_entries_to_next_reader() {
+        let (mut con, stream) = test_stream().expect("NEBU_TEST_REDIS_URL is not set");
+        let ids = add_and_read(&mut con, &stream, "crashed", 1);
+        std::thread::sleep(std::time::Duration::from_millis(50));
+
+        // Entries younger than the threshold stay with their consumer
+        assert_eq!(
+            reclaim_idle_entries(&mut con, &stream, GROUP, 60_000).unwrap(),
+            0
+        );
+        assert_eq!(
+            reclaim_idle_entries(&mut con, &stream, GROUP, 10).unwrap(),
+            1
+        );
+
+        let taken = take_reclaimed_entries(&mut con, &stream, GROUP, "worker-2", 10, 5).unwrap();
+        let backlog = get_group_backlog(&mut con, &stream, GROUP).unwrap();
+        let _: () = con.del(&stream).unwrap();
+
+        assert_eq!(
+            taken.into_iter().map(|entry| entry.id).collect::<Vec<_>>(),
+            ids
+        );
+        assert_eq!(backlog.consumers, vec![("worker-2".to_string(), 1)]);
+    }
+
+    #[test]
+    #[ignore = "needs a Redis at NEBU_TEST_REDIS_URL"]
+    fn test_stream_info_reflects_seeded_stream() {
+        let (mut con, stream) = test_stream().expect("NEBU_TEST_REDIS_URL is not set");
+        let ids = add_and_read(&mut con, &stream, "worker-1", 3);
+        let last_id: String = con.xadd(&stream, "*", &[("data", "unread")]).unwrap();
+
+        let info = get_stream_info(&mut con, &stream).unwrap();
+        let _: () = con.del(&stream).unwrap();
+
+        assert_eq!(info.length, 4);
+        assert_eq!(info.last_generated_id, Some(last_id));
+        assert_eq!(
+            info.groups,
+            vec![StreamGroupInfo {
+                name: GROUP.to_string(),
+                consumers: 1,
+                pending: 3,
+                last_delivered_id: ids[2].clone(),
+                lag: Some(1),
+            }]
+        );
+    }
+
+    #[test]
+    #[ignore = "needs a Redis at NEBU_TEST_REDIS_URL"]
+    fn test_stream_info_of_missing_stream() {
+        let (mut con, stream) = test_stream().expect("NEBU_TEST_REDIS_URL is not set");
+        let _: () = con.del(&stream).unwrap();
+
+        assert_eq!(
+            get_stream_info(&mut con, &stream).unwrap(),
+            StreamInfo::default()
+        );
+    }
+
+    #[test]
+    #[ignore = "needs a Redis at NEBU_TEST_REDIS_URL"]
+    fn test_take_reclaimed_entries_empty_pool() {
+        let (mut con, stream) = test_stream().expect("NEBU_TEST_REDIS_URL is not set");
+        add_and_read(&mut con, &stream, "worker-1", 1);
+
+        let taken = take_reclaimed_entries(&mut con, &stream, GROUP, "worker-2", 10, 5).unwrap();
+        let _: () = con.del(&stream).unwrap();
+
+        assert!(taken.is_empty());
+    }
+
+    #[test]
+    #[ignore = "needs a Redis at NEBU_TEST_REDIS_URL"]
+    fn test_entries_handed_out_too_often_are_dead_lettered() {
+        let (mut con, stream) = test_stream().expect("NEBU_TEST_REDIS_URL is not set");
+        let ids = add_and_read(&mut con, &stream, "crashed-1", 1);
+        std::thread::sleep(std::time::Duration::from_millis(20));
+
+        // Handed out a second time, and that reader crashes too
+        reclaim_idle_entries(&mut con, &stream, GROUP, 10).unwrap();
+        assert_eq!(dead_letter_entries(&mut con, &stream, GROUP, 2).unwrap(), 0);
+        let taken = take_reclaimed_entries(&mut con, &stream, GROUP, "crashed-2", 10, 2).unwrap();
+        assert_eq!(taken.len(), 1);
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        reclaim_idle_entries(&mut con, &stream, GROUP, 10).unwrap();
+
+        let held_back = take_reclaimed_entries(&mut con, &stream, GROUP, "worker", 10, 2).unwrap();
+        let moved = dead_letter_entries(&mut con, &stream, GROUP, 2).unwrap();
+        let backlog = get_group_backlog(&mut con, &stream, GROUP).unwrap();
+        let dead: StreamRangeReply = con.xrange_all(dead_letter_stream(&stream)).unwrap();
+        let _: () = con
+            .del(&[stream.clone(), dead_letter_stream(&stream)])
+            .unwrap();
+
+        assert!(held_back.is_empty());
+        assert_eq!(moved, 1);
+        assert_eq!(backlog.pending, 0);
+        let entry = &dead.ids[0];
+        assert_eq!(entry.get::<String>("data").as_deref(), Some("0"));
+        assert_eq!(entry.get::<String>("nebu_source_id"), Some(ids[0].clone()));
+        assert_eq!(entry.get::<String>("nebu_group").as_deref(), Some(GROUP));
+        assert_eq!(entry.get::<u64>("nebu_deliveries"), Some(2));
+    }
+
+    #[test]
+    #[ignore = "needs a Redis at NEBU_TEST_REDIS_URL"]
+    fn test_peek_leaves_the_groups_alone() {
+        let (mut con, stream) = test_stream().expect("NEBU_TEST_REDIS_URL is not set");
+        for i in 0..3 {
+            let _: String = con.xadd(&stream, "*", &[("data", i)]).unwrap();
+        }
+
+        let first = peek_entries(&mut con, &stream, "0", 2, None).unwrap();
+        let rest = peek_entries(&mut con, &stream, &first[1].id, 2, None).unwrap();
+        let info = get_stream_info(&mut con, &stream).unwrap();
+        let backlog = get_group_backlog(&mut con, &stream, GROUP).unwrap();
+        let _: () = con.del(&stream).unwrap();
+
+        assert_eq!(first.len(), 2);
+        assert_eq!(rest.len(), 1);
+        assert_eq!(rest[0].get::<String>("data").as_deref(), Some("2"));
+        assert_eq!(info.groups.len(), 1);
+        assert_eq!(backlog.pending, 0);
+        assert_eq!(backlog.lag, 3);
+    }
+}
@@ -0,0 +1,169 @@
+// src/tls.rs
+//
+// Serving the API over HTTPS. With `NEBU_TLS_CERT` and `NEBU_TLS_KEY` set the
+// server terminates TLS itself, so it can be exposed without a proxy in
+// front. `NEBU_TLS_REDIRECT_PORT` adds a plain HTTP listener that only
+// redirects to HTTPS.
+
+use axum::extract::Host;
+use axum::http::Uri;
+use axum::response::Redirect;
+use axum::Router;
+use axum_server::tls_rustls::RustlsConfig;
+use std::io;
+use std::net::TcpListener;
+
+/// Loads the PEM certificate chain and private key to serve with
+pub async fn load_rustls_config(cert_path: &str, key_path: &str) -> io::Result<RustlsConfig> {
+    // Dependencies enable more than one rustls crypto backend, so pick one
+    // rather than let rustls refuse to choose. Fails harmlessly when already set.
+    let _ = rustls::crypto::ring::default_provider().install_default();
+
+    RustlsConfig::from_pem_file(cert_path, key_path)
+        .await
+        .map_err(|e| {
+            io::Error::new(
+                e.kind(),
+                format!(
+                    "Failed to load TLS certificate '{}' and key '{}': {}",
+                    cert_path, key_path, e
+                ),
+            )
+        })
+}
+
+/// The TLS config from `SERVER_CONFIG`, `None` when TLS isn't configured
+pub async fn server_rustls_config() -> io::Result<Option<RustlsConfig>> {
+    let config = &crate::config::SERVER_CONFIG;
+    match (&config.tls_cert_path, &config.tls_key_path) {
+        (Some(cert), Some(key)) => load_rustls_config(cert, key).await.map(Some),
+        _ => Ok(None),
+    }
+}
+
+/// Serves `app` on `listener`, over HTTPS when `tls` is given
+pub async fn serve(
+    listener: TcpListener,
+    app: Router,
+    tls: Option<RustlsConfig>,
+) -> io::Result<()> {
+    listener.set_nonblocking(true)?;
+    match tls {
+        Some(tls) => {
+            axum_server::from_tcp_rustls(listener, tls)
+                .serve(app.into_make_service())
+                .await
+        }
+        None => axum::serve(tokio::net::TcpListener::from_std(listener)?, app).await,
+    }
+}
+
+/// Where a plain HTTP request for `uri` on `host` goes over HTTPS
+pub fn https_location(host: &str, uri: &Uri, https_port: u16) -> String {
+    // IPv6 hosts keep their brackets, only a trailing port is dropped
+    let hostname = match host.rsplit_once(':') {
+        Some((name, port)) if !name.is_empty() && port.chars().all(|c| c.is_ascii_digit()) => name,
+        _ => host,
+    };
+    let path = uri.path_and_query().map(|p| p.as_str()).unwrap_or("/");
+    if https_port == 443 {
+        format!("https://{}{}", hostname, path)
+    } else {
+        format!("https://{}:{}{}", hostname, https_port, path)
+    }
+}
+
+/// An app answering every request with a permanent redirect to HTTPS on
+/// `https_port`, keeping host, path and query
+pub fn redirect_app(https_port: u16) -> Router {
+    Router::new().fallback(move |Host(host): Host, uri: Uri| async move {
+        Redirect::permanent(&https_location(&host, &uri, https_port))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::routing::get;
+
+    /// A self-signed certificate for localhost, written to a temp dir
+    fn self_signed() -> (tempfile::TempDir, String, String, String) {
+        let certified = rcgen::generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+        let cert_pem = certified.cert.pem();
+        let dir = tempfile::tempdir().unwrap();
+        let cert_path = dir.path().join("cert.pem");
+        let key_path = dir.path().join("key.pem");
+        std::fs::write(&cert_path, &cert_pem).unwrap();
+        std::fs::write(&key_path, certified.key_pair.serialize_pem()).unwrap();
+        (
+            dir,
+            cert_path.to_string_lossy().to_string(),
+            key_path.to_string_lossy().to_string(),
+            cert_pem,
+        )
+    }
+
+    #[tokio::test]
+    async fn test_serves_https_with_self_signed_cert() {
+        // Arrange
+        let (_dir, cert_path, key_path, cert_pem) = self_signed();
+        let tls = load_rustls_config(&cert_path, &key_path).await.unwrap();
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let app = Router::new().route("/health", get(|| async { "ok" }));
+        tokio::spawn(serve(listener, app, Some(tls)));
+
+        let client = reqwest::Client::builder()
+            .add_root_certificate(reqwest::Certificate::from_pem(cert_pem.as_bytes()).unwrap())
+            .build()
+            .unwrap();
+
+        // Act
+        let response = client
+            .get(format!("https://localhost:{}/health", port))
+            .send()
+            .await
+            .unwrap();
+
+        // Assert
+        assert_eq!(response.status(), reqwest::StatusCode::OK);
+        assert_eq!(response.text().await.unwrap(), "ok");
+    }
+
+    #[tokio::test]
+    async fn test_plain_http_redirects_to_https() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(serve(listener, redirect_app(8443), None));
+
+        let client = reqwest::Client::builder()
+            .redirect(reqwest::redirect::Policy::none())
+            .build()
+            .unwrap();
+        let response = client
+            .get(format!("http://localhost:{}/v1/containers?limit=5", port))
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), reqwest::StatusCode::PERMANENT_REDIRECT);
+        assert_eq!(
+            response.headers()["location"],
+            "https://localhost:8443/v1/containers?limit=5"
+        );
+    }
+
+    #[test]
+    fn test_https_location() {
+        let uri: Uri = "/a?b=c".parse().unwrap();
+        assert_eq!(
+            https_location("api.example.com:80", &uri, 443),
+            "https://api.example.com/a?b=c"
+        );
+        assert_eq!(
+            https_location("[::1]:8080", &uri, 8443),
+            "https://[::1]:8443/a?b=c"
+        );
+        assert_eq!(https_location("[::1]", &uri, 443), "https://[::1]/a?b=c");
+    }
+}
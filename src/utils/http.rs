@@ -0,0 +1,99 @@
+// src/utils/http.rs
+//
+// The process wide HTTP client. reqwest clients hold a connection pool, so
+// building one per call throws away warm connections and TLS sessions; clone
+// this one instead (clones share the pool).
+
+use once_cell::sync::OnceCell;
+use std::time::Duration;
+
+static SHARED_CLIENT: OnceCell<reqwest::Client> = OnceCell::new();
+static PROXY_CLIENT: OnceCell<reqwest::Client> = OnceCell::new();
+
+/// Establishing a connection longer than this fails the request
+pub const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Idle pooled connections are closed after this long
+pub const POOL_IDLE_TIMEOUT: Duration = Duration::from_secs(90);
+
+/// Redirects the shared client follows before failing the request
+pub const MAX_REDIRECTS: usize = 5;
+
+/// The shared client. There is no overall request timeout since the proxy
+/// streams long lived responses through it; set one per request with
+/// `RequestBuilder::timeout` where a call should be bounded. It follows up to
+/// `MAX_REDIRECTS` redirects; reqwest drops credentials on redirects to
+/// another host.
+pub fn shared_client() -> reqwest::Client {
+    SHARED_CLIENT
+        .get_or_init(|| {
+            builder()
+                .redirect(reqwest::redirect::Policy::limited(MAX_REDIRECTS))
+                .build()
+                .expect("Failed to build the shared HTTP client")
+        })
+        .clone()
+}
+
+/// The client the proxy forwards requests with. It hands redirects back to
+/// the caller rather than following them, so a container can't send the
+/// proxy to another host.
+pub fn proxy_client() -> reqwest::Client {
+    PROXY_CLIENT
+        .get_or_init(|| {
+            builder()
+                .redirect(reqwest::redirect::Policy::none())
+                .build()
+                .expect("Failed to build the proxy HTTP client")
+        })
+        .clone()
+}
+
+fn builder() -> reqwest::ClientBuilder {
+    reqwest::Client::builder()
+        .connect_timeout(CONNECT_TIMEOUT)
+        .pool_idle_timeout(POOL_IDLE_TIMEOUT)
+        .pool_max_idle_per_host(32)
+        .tcp_keepalive(Duration::from_secs(60))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::extract::Path;
+    use axum::response::Redirect;
+    use axum::routing::get;
+    use axum::Router;
+
+    #[tokio::test]
+    async fn test_shared_client_stops_following_redirects() {
+        // Arrange
+        let app = Router::new()
+            .route(
+                "/hop/:n",
+                get(|Path(n): Path<usize>| async move {
+                    Redirect::temporary(&format!("/hop/{}", n + 1))
+                }),
+            )
+            .route("/start", get(|| async { Redirect::temporary("/end") }))
+            .route("/end", get(|| async { "done" }));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        // Act
+        let followed = shared_client()
+            .get(format!("http://{}/start", addr))
+            .send()
+            .await
+            .unwrap();
+        let looped = shared_client()
+            .get(format!("http://{}/hop/0", addr))
+            .send()
+            .await;
+
+        // Assert
+        assert_eq!(followed.text().await.unwrap(), "done");
+        assert!(looped.unwrap_err().is_redirect());
+    }
+}
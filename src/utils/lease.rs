@@ -0,0 +1,237 @@
+// src/utils/lease.rs
+//
+// Leases in Redis, so work on a resource happens in one replica at a time.
+// A lease is a key holding its holder's id with a TTL. It is renewed in the
+// background while held, so long work keeps it, and expires on its own when
+// the holder dies without releasing it. A holder that can't renew in time
+// has lost the lease and should stop the work it covers.
+
+use once_cell::sync::Lazy;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tokio::time::Instant;
+use tokio_util::sync::CancellationToken;
+use tracing::warn;
+
+/// Identifies this process as a lease holder
+pub static PROCESS_HOLDER_ID: Lazy<String> = Lazy::new(|| {
+    let host = std::env::var("HOSTNAME").unwrap_or_else(|_| "nebu".to_string());
+    format!("{}-{}", host, short_uuid::ShortUuid::generate())
+});
+
+/// Extends the lease if it's still ours
+const RENEW_SCRIPT: &str = r#"
+if redis.call('GET', KEYS[1]) == ARGV[1] then
+    return redis.call('PEXPIRE', KEYS[1], ARGV[2])
+end
+return 0
+"#;
+
+/// Deletes the lease if it's still ours
+const RELEASE_SCRIPT: &str = r#"
+if redis.call('GET', KEYS[1]) == ARGV[1] then
+    return redis.call('DEL', KEYS[1])
+end
+return 0
+"#;
+
+/// Hands out leases for one holder
+#[derive(Clone)]
+pub struct LeaseManager {
+    client: Arc<redis::Client>,
+    holder: String,
+    ttl: Duration,
+}
+
+impl LeaseManager {
+    pub fn new(client: Arc<redis::Client>, holder: String, ttl: Duration) -> Self {
+        Self {
+            client,
+            holder,
+            ttl,
+        }
+    }
+
+    /// Takes the lease on `key` unless someone else holds it. A held lease is
+    /// renewed every third of its TTL until released or dropped, and is lost
+    /// once another holder has it or it can't be renewed before expiring.
+    pub async fn try_acquire(&self, key: &str) -> redis::RedisResult<Option<Lease>> {
+        let mut conn = self.client.get_multiplexed_async_connection().await?;
+        let acquired: Option<String> = redis::cmd("SET")
+            .arg(key)
+            .arg(&self.holder)
+            .arg("NX")
+            .arg("PX")
+            .arg(self.ttl.as_millis() as u64)
+            .query_async(&mut conn)
+            .await?;
+        if acquired.is_none() {
+            return Ok(None);
+        }
+
+        let lost = CancellationToken::new();
+        let renewer = tokio::spawn({
+            let key = key.to_string();
+            let holder = self.holder.clone();
+            let ttl = self.ttl;
+            let lost = lost.clone();
+            async move {
+                let mut interval = tokio::time::interval(ttl / 3);
+                interval.tick().await;
+                let mut renewed_at = Instant::now();
+                loop {
+                    interval.tick().await;
+                    match renew(&mut conn, &key, &holder, ttl).await {
+                        Ok(true) => renewed_at = Instant::now(),
+                        Ok(false) => {
+                            warn!("Lease {} was lost to another holder", key);
+                            lost.cancel();
+                            return;
+                        }
+                        // The next try would come after the lease expired
+                        Err(e) if renewed_at.elapsed() + ttl / 3 >= ttl => {
+                            warn!("Failed to renew lease {} before it expires: {}", key, e);
+                            lost.cancel();
+                            return;
+                        }
+                        Err(e) => warn!("Failed to renew lease {}: {}", key, e),
+                    }
+                }
+            }
+        });
+
+        Ok(Some(Lease {
+            client: self.client.clone(),
+            key: key.to_string(),
+            holder: self.holder.clone(),
+            renewer,
+            lost,
+        }))
+    }
+}
+
+async fn renew(
+    conn: &mut redis::aio::MultiplexedConnection,
+    key: &str,
+    holder: &str,
+    ttl: Duration,
+) -> redis::RedisResult<bool> {
+    let renewed: i32 = redis::Script::new(RENEW_SCRIPT)
+        .key(key)
+        .arg(holder)
+        .arg(ttl.as_millis() as u64)
+        .invoke_async(conn)
+        .await?;
+    Ok(renewed == 1)
+}
+
+/// A held lease. Dropping it stops the renewals, so it expires after its
+/// TTL; `release` frees it right away.
+pub struct Lease {
+    client: Arc<redis::Client>,
+    key: String,
+    holder: String,
+    renewer: JoinHandle<()>,
+    lost: CancellationToken,
+}
+
+impl Lease {
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    /// Resolves once the lease is lost, after which another holder may take it
+    pub async fn lost(&self) {
+        self.lost.cancelled().await
+    }
+
+    /// Frees the lease for the next holder, if it's still ours
+    pub async fn release(self) -> redis::RedisResult<()> {
+        self.renewer.abort();
+        let mut conn = self.client.get_multiplexed_async_connection().await?;
+        let _: i32 = redis::Script::new(RELEASE_SCRIPT)
+            .key(&self.key)
+            .arg(&self.holder)
+            .invoke_async(&mut conn)
+            .await?;
+        Ok(())
+    }
+}
+
+impl Drop for Lease {
+    fn drop(&mut self) {
+        self.renewer.abort();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_redis() -> Arc<redis::Client> {
+        let url = std::env::var("NEBU_TEST_REDIS_URL").expect("NEBU_TEST_REDIS_URL is not set");
+        Arc::new(redis::Client::open(url).unwrap())
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a Redis at NEBU_TEST_REDIS_URL"]
+    async fn test_second_acquirer_is_blocked_while_lease_is_held() {
+        // Arrange
+        let client = test_redis();
+        let ttl = Duration::from_millis(300);
+        let first = LeaseManager::new(client.clone(), "replica-a".to_string(), ttl);
+        let second = LeaseManager::new(client.clone(), "replica-b".to_string(), ttl);
+        let key = format!("test:lease:{}", short_uuid::ShortUuid::generate());
+
+        // Act
+        let lease = first.try_acquire(&key).await.unwrap().unwrap();
+        // Outlive the TTL, which renewals must cover
+        tokio::time::sleep(ttl * 3).await;
+        let blocked = second.try_acquire(&key).await.unwrap();
+
+        // Assert
+        assert!(blocked.is_none());
+        lease.release().await.unwrap();
+        let taken_over = second.try_acquire(&key).await.unwrap();
+        assert!(taken_over.is_some());
+
+        // A dropped lease lapses after its TTL
+        drop(taken_over);
+        assert!(first.try_acquire(&key).await.unwrap().is_none());
+        tokio::time::sleep(ttl * 2).await;
+        first
+            .try_acquire(&key)
+            .await
+            .unwrap()
+            .unwrap()
+            .release()
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a Redis at NEBU_TEST_REDIS_URL"]
+    async fn test_lease_taken_over_is_lost() {
+        let client = test_redis();
+        let ttl = Duration::from_millis(300);
+        let leases = LeaseManager::new(client.clone(), "replica-a".to_string(), ttl);
+        let key = format!("test:lease:{}", short_uuid::ShortUuid::generate());
+        let lease = leases.try_acquire(&key).await.unwrap().unwrap();
+
+        let mut conn = client.get_multiplexed_async_connection().await.unwrap();
+        let _: () = redis::cmd("SET")
+            .arg(&key)
+            .arg("replica-b")
+            .query_async(&mut conn)
+            .await
+            .unwrap();
+
+        tokio::time::timeout(ttl, lease.lost()).await.unwrap();
+        let _: () = redis::cmd("DEL")
+            .arg(&key)
+            .query_async(&mut conn)
+            .await
+            .unwrap();
+    }
+}
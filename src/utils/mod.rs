@@ -1 +1,5 @@
+pub mod http;
+pub mod lease;
 pub mod namespace;
+pub mod ttl_cache;
+pub mod work_pool;
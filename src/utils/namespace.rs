@@ -1,15 +1,148 @@
 use crate::models::V1UserProfile;
+use crate::validate::MAX_NAME_LENGTH;
+
+/// Returns the user's personal namespace, derived from their handle or email
+/// and normalized to a DNS label:
+///
+/// - the handle is used when the profile has one, the email otherwise
+/// - it is lowercased and every character outside `[a-z0-9]` becomes `-`
+/// - leading and trailing `-` are trimmed
+/// - it is cut to `MAX_NAME_LENGTH` characters, trimming any `-` left at the end
+/// - when that changed anything but the case, `-` and the first
+///   `HANDLE_SUFFIX_LENGTH` hex digits of the SHA-256 of the lowercased
+///   source are appended, so `jane.doe@example.com` and
+///   `jane-doe@example.com` don't share a namespace
+///
+/// So `Jane.Doe+dev@example.com` becomes `jane-doe-dev-example-com-` plus a
+/// suffix. This is the namespace requests fall back to when they don't name
+/// one.
+pub fn user_handle(user_profile: &V1UserProfile) -> String {
+    let raw = user_profile
+        .handle
+        .clone()
+        .unwrap_or_else(|| user_profile.email.clone())
+        .to_lowercase();
+
+    let label: String = raw
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+        .collect();
+    let label = truncate_label(label.trim_matches('-'), MAX_NAME_LENGTH);
+    if label == raw {
+        return label;
+    }
+
+    let digest = ring::digest::digest(&ring::digest::SHA256, raw.as_bytes());
+    let suffix: String = digest
+        .as_ref()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect::<String>()
+        .chars()
+        .take(HANDLE_SUFFIX_LENGTH)
+        .collect();
+    let label = truncate_label(&label, MAX_NAME_LENGTH - HANDLE_SUFFIX_LENGTH - 1);
+    if label.is_empty() {
+        suffix
+    } else {
+        format!("{}-{}", label, suffix)
+    }
+}
+
+/// Hex digits appended to handles that normalizing changed
+const HANDLE_SUFFIX_LENGTH: usize = 8;
+
+fn truncate_label(label: &str, max_len: usize) -> String {
+    label
+        .chars()
+        .take(max_len)
+        .collect::<String>()
+        .trim_end_matches('-')
+        .to_string()
+}
 
 pub fn resolve_namespace(namespace: &str, user_profile: &V1UserProfile) -> String {
     if namespace == "-" {
-        user_profile.handle.clone().unwrap_or(
-            user_profile
-                .email
-                .clone()
-                .replace("@", "-")
-                .replace(".", "-"),
-        )
+        user_handle(user_profile)
     } else {
         namespace.to_string()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn profile(email: &str, handle: Option<&str>) -> V1UserProfile {
+        V1UserProfile {
+            email: email.to_string(),
+            handle: handle.map(|h| h.to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_user_handle_from_email() {
+        // Arrange
+        let user_profile = profile("Jane.Doe+dev@example.com", None);
+
+        // Act
+        let handle = user_handle(&user_profile);
+
+        // Assert
+        assert!(handle.starts_with("jane-doe-dev-example-com-"));
+        assert_eq!(
+            handle.len(),
+            "jane-doe-dev-example-com-".len() + HANDLE_SUFFIX_LENGTH
+        );
+        assert_eq!(
+            handle,
+            user_handle(&profile("jane.doe+DEV@example.com", None))
+        );
+    }
+
+    #[test]
+    fn test_user_handle_prefers_handle() {
+        assert_eq!(
+            user_handle(&profile("a@b.com", Some("my-handle"))),
+            "my-handle"
+        );
+        assert_eq!(
+            user_handle(&profile("a@b.com", Some("MyHandle"))),
+            "myhandle"
+        );
+        assert!(user_handle(&profile("a@b.com", Some("My_Handle"))).starts_with("my-handle-"));
+    }
+
+    #[test]
+    fn test_user_handles_do_not_collide() {
+        let handles = [
+            user_handle(&profile("jane.doe@example.com", None)),
+            user_handle(&profile("jane-doe@example.com", None)),
+            user_handle(&profile("jane_doe@example.com", None)),
+            user_handle(&profile("a@b.com", Some("jane-doe-example-com"))),
+            user_handle(&profile("a@b.com", Some("jane.doe.example.com"))),
+        ];
+        for (i, a) in handles.iter().enumerate() {
+            assert!(crate::validate::validate_namespace(a).is_ok(), "{}", a);
+            for b in &handles[i + 1..] {
+                assert_ne!(a, b);
+            }
+        }
+
+        let long = |tail: &str| format!("{}{}@example.com", "x".repeat(80), tail);
+        assert_ne!(
+            user_handle(&profile(&long("a"), None)),
+            user_handle(&profile(&long("b"), None))
+        );
+    }
+
+    #[test]
+    fn test_user_handle_is_valid_namespace() {
+        let long_email = format!("{}@example.com", "x".repeat(80));
+        let handle = user_handle(&profile(&long_email, None));
+        assert!(crate::validate::validate_namespace(&handle).is_ok());
+        let handle = user_handle(&profile("a@b.com", Some("___")));
+        assert!(crate::validate::validate_namespace(&handle).is_ok());
+    }
+}
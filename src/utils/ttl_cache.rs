@@ -0,0 +1,118 @@
+// src/utils/ttl_cache.rs
+//
+// A single value cached for a fixed time. Refreshes hold the lock, so a burst
+// of callers that all miss share one fetch instead of each making their own.
+
+use std::future::Future;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+
+pub struct TtlCache<T> {
+    ttl: Duration,
+    entry: Mutex<Option<(Instant, T)>>,
+}
+
+impl<T: Clone> TtlCache<T> {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            entry: Mutex::new(None),
+        }
+    }
+
+    /// The cached value if it is younger than the TTL, otherwise the result
+    /// of `fetch`. Errors are returned as is and not cached.
+    pub async fn get_or_refresh<F, Fut, E>(&self, fetch: F) -> Result<T, E>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+    {
+        let mut entry = self.entry.lock().await;
+        if let Some((fetched_at, value)) = entry.as_ref() {
+            if fetched_at.elapsed() < self.ttl {
+                return Ok(value.clone());
+            }
+        }
+        let value = fetch().await?;
+        *entry = Some((Instant::now(), value.clone()));
+        Ok(value)
+    }
+
+    /// Drops the cached value so the next call fetches a fresh one.
+    pub async fn invalidate(&self) {
+        *self.entry.lock().await = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    async fn fetch_counted(calls: &AtomicUsize) -> Result<Vec<String>, String> {
+        calls.fetch_add(1, Ordering::SeqCst);
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        Ok(vec!["NVIDIA_TESLA_T4".to_string()])
+    }
+
+    #[tokio::test]
+    async fn test_calls_within_ttl_share_one_fetch() {
+        // Arrange
+        let cache = TtlCache::new(Duration::from_secs(60));
+        let calls = AtomicUsize::new(0);
+
+        // Act
+        let (first, second) = tokio::join!(
+            cache.get_or_refresh(|| fetch_counted(&calls)),
+            cache.get_or_refresh(|| fetch_counted(&calls)),
+        );
+        let third = cache.get_or_refresh(|| fetch_counted(&calls)).await;
+
+        // Assert
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        assert_eq!(first, second);
+        assert_eq!(second, third);
+    }
+
+    #[tokio::test]
+    async fn test_refetches_after_ttl_and_invalidate() {
+        let cache = TtlCache::new(Duration::from_millis(10));
+        let calls = AtomicUsize::new(0);
+
+        cache
+            .get_or_refresh(|| fetch_counted(&calls))
+            .await
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        cache
+            .get_or_refresh(|| fetch_counted(&calls))
+            .await
+            .unwrap();
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+
+        let cache = TtlCache::new(Duration::from_secs(60));
+        cache
+            .get_or_refresh(|| fetch_counted(&calls))
+            .await
+            .unwrap();
+        cache.invalidate().await;
+        cache
+            .get_or_refresh(|| fetch_counted(&calls))
+            .await
+            .unwrap();
+        assert_eq!(calls.load(Ordering::SeqCst), 4);
+    }
+
+    #[tokio::test]
+    async fn test_errors_are_not_cached() {
+        let cache: TtlCache<u32> = TtlCache::new(Duration::from_secs(60));
+        let failed: Result<u32, String> = cache
+            .get_or_refresh(|| async { Err("upstream down".to_string()) })
+            .await;
+        assert!(failed.is_err());
+        assert_eq!(
+            cache.get_or_refresh(|| async { Ok::<_, String>(7) }).await,
+            Ok(7)
+        );
+    }
+}
@@ -0,0 +1,144 @@
+// src/utils/work_pool.rs
+//
+// Caps how many operations of a kind run at once. Callers past the limit wait
+// in FIFO order (tokio's semaphore is fair) and are counted as queued.
+
+use std::future::Future;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+pub struct WorkPool {
+    limit: usize,
+    semaphore: Arc<Semaphore>,
+    queued: Arc<AtomicUsize>,
+    in_flight: Arc<AtomicUsize>,
+}
+
+/// Holds a slot in the pool until dropped.
+pub struct WorkPermit {
+    _permit: OwnedSemaphorePermit,
+    in_flight: Arc<AtomicUsize>,
+}
+
+impl Drop for WorkPermit {
+    fn drop(&mut self) {
+        self.in_flight.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Counts a waiter as queued until it gets its permit or gives up
+struct QueuedGuard(Arc<AtomicUsize>);
+
+impl Drop for QueuedGuard {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+impl WorkPool {
+    /// A pool running at most `limit` operations at once; 0 is treated as 1.
+    pub fn new(limit: usize) -> Self {
+        let limit = limit.max(1);
+        Self {
+            limit,
+            semaphore: Arc::new(Semaphore::new(limit)),
+            queued: Arc::new(AtomicUsize::new(0)),
+            in_flight: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    /// Waits for a free slot.
+    pub async fn acquire(&self) -> WorkPermit {
+        self.queued.fetch_add(1, Ordering::SeqCst);
+        let queued = QueuedGuard(self.queued.clone());
+        let permit = self
+            .semaphore
+            .clone()
+            .acquire_owned()
+            .await
+            .expect("work pool semaphore is never closed");
+        drop(queued);
+        self.in_flight.fetch_add(1, Ordering::SeqCst);
+        WorkPermit {
+            _permit: permit,
+            in_flight: self.in_flight.clone(),
+        }
+    }
+
+    /// Runs `operation` once a slot is free.
+    pub async fn run<F: Future>(&self, operation: F) -> F::Output {
+        let _permit = self.acquire().await;
+        operation.await
+    }
+
+    pub fn limit(&self) -> usize {
+        self.limit
+    }
+
+    /// Operations waiting for a slot
+    pub fn queue_depth(&self) -> usize {
+        self.queued.load(Ordering::SeqCst)
+    }
+
+    pub fn in_flight(&self) -> usize {
+        self.in_flight.load(Ordering::SeqCst)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_at_most_limit_operations_run_concurrently() {
+        // Arrange
+        let pool = Arc::new(WorkPool::new(3));
+        let running = Arc::new(AtomicUsize::new(0));
+        let max_running = Arc::new(AtomicUsize::new(0));
+
+        // Act
+        let tasks: Vec<_> = (0..12)
+            .map(|_| {
+                let pool = pool.clone();
+                let running = running.clone();
+                let max_running = max_running.clone();
+                tokio::spawn(async move {
+                    pool.run(async {
+                        let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+                        max_running.fetch_max(now, Ordering::SeqCst);
+                        tokio::time::sleep(Duration::from_millis(20)).await;
+                        running.fetch_sub(1, Ordering::SeqCst);
+                    })
+                    .await
+                })
+            })
+            .collect();
+        tokio::time::sleep(Duration::from_millis(5)).await;
+        let queued_while_busy = pool.queue_depth();
+        for task in tasks {
+            task.await.unwrap();
+        }
+
+        // Assert
+        assert_eq!(max_running.load(Ordering::SeqCst), 3);
+        assert!(queued_while_busy > 0);
+        assert_eq!(pool.queue_depth(), 0);
+        assert_eq!(pool.in_flight(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_waiter_leaves_the_queue() {
+        let pool = WorkPool::new(1);
+        let held = pool.acquire().await;
+        assert_eq!(pool.in_flight(), 1);
+
+        let waited = tokio::time::timeout(Duration::from_millis(10), pool.acquire()).await;
+        assert!(waited.is_err());
+        assert_eq!(pool.queue_depth(), 0);
+
+        drop(held);
+        assert_eq!(pool.in_flight(), 0);
+    }
+}
@@ -1,4 +1,6 @@
 use crate::errors::ApiError;
+use crate::resources::v1::containers::models::V1SSHKey;
+use crate::resources::v1::volumes::models::V1VolumeDriver;
 use anyhow::{bail, Result};
 use axum::{
     async_trait,
@@ -8,6 +10,8 @@ use axum::{
 use once_cell::sync::Lazy;
 use regex::Regex;
 use serde::de::DeserializeOwned;
+use std::collections::HashMap;
+use std::net::IpAddr;
 
 pub struct ValidatedJson<T>(pub T);
 
@@ -25,26 +29,783 @@ where
     }
 }
 
-// Restricts to letters, digits, underscores, hyphens, dots. Must be 1–256 characters long.
-static NAME_REGEX: Lazy<Regex> =
-    Lazy::new(|| Regex::new(r"^[a-zA-Z0-9._-]{1,256}$").expect("Failed to compile NAME_REGEX"));
+/// Maximum length of a name or namespace (a DNS label).
+pub const MAX_NAME_LENGTH: usize = 63;
 
-pub fn validate_name(name: &str) -> Result<()> {
-    if !NAME_REGEX.is_match(name) {
+// DNS label: lowercase letters, digits and hyphens, starting and ending with an alphanumeric.
+static NAME_REGEX: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"^[a-z0-9]([a-z0-9-]*[a-z0-9])?$").expect("Failed to compile NAME_REGEX")
+});
+
+fn validate_dns_label(kind: &str, value: &str) -> Result<()> {
+    if value.len() > MAX_NAME_LENGTH {
+        bail!(
+            "Invalid {}: must be at most {} characters long, got {}.",
+            kind,
+            MAX_NAME_LENGTH,
+            value.len()
+        );
+    }
+    if !NAME_REGEX.is_match(value) {
         bail!(
-            "Invalid name: must be 1–256 characters long and only contain letters, \
-            digits, underscores, hyphens, or periods."
+            "Invalid {}: must be 1–{} characters long, only contain lowercase letters, \
+            digits, or hyphens, and start and end with a letter or digit.",
+            kind,
+            MAX_NAME_LENGTH
         );
     }
     Ok(())
 }
 
+pub fn validate_name(name: &str) -> Result<()> {
+    validate_dns_label("name", name)
+}
+
 pub fn validate_namespace(namespace: &str) -> Result<()> {
-    if !NAME_REGEX.is_match(namespace) {
+    validate_dns_label("namespace", namespace)
+}
+
+/// Validates both parts of a resource's `namespace/name` full name.
+pub fn validate_full_name(namespace: &str, name: &str) -> Result<()> {
+    validate_namespace(namespace)?;
+    validate_name(name)
+}
+
+// Docker reference grammar: [registry[:port]/]path[:tag][@digest]
+static IMAGE_REGEX: Lazy<Regex> = Lazy::new(|| {
+    let domain_component = r"(?:[a-zA-Z0-9]|[a-zA-Z0-9][a-zA-Z0-9-]*[a-zA-Z0-9])";
+    let path_component = r"[a-z0-9]+(?:(?:[._]|__|-+)[a-z0-9]+)*";
+    let pattern = format!(
+        r"^(?:(?P<registry>{d}(?:\.{d})*(?::[0-9]+)?)/)?(?P<repository>{p}(?:/{p})*)(?::(?P<tag>[\w][\w.-]{{0,127}}))?(?:@(?P<digest>[A-Za-z][A-Za-z0-9]*(?:[-_+.][A-Za-z][A-Za-z0-9]*)*:[0-9a-fA-F]{{32,}}))?$",
+        d = domain_component,
+        p = path_component,
+    );
+    Regex::new(&pattern).expect("Failed to compile IMAGE_REGEX")
+});
+
+const MAX_IMAGE_NAME_LENGTH: usize = 255;
+
+/// Validates a container image reference such as `nginx`, `ghcr.io/org/app:v1.2`
+/// or `ubuntu@sha256:<digest>`.
+pub fn validate_image(image: &str) -> Result<()> {
+    if image.trim().is_empty() {
+        bail!("Invalid image: must not be empty");
+    }
+
+    let captures = match IMAGE_REGEX.captures(image) {
+        Some(captures) => captures,
+        None => bail!(
+            "Invalid image '{}': expected a reference like [registry/]repository[:tag][@digest]",
+            image
+        ),
+    };
+
+    let name_len = captures
+        .name("registry")
+        .map_or(0, |m| m.as_str().len() + 1)
+        + captures.name("repository").map_or(0, |m| m.as_str().len());
+    if name_len > MAX_IMAGE_NAME_LENGTH {
+        bail!(
+            "Invalid image '{}': repository name must be at most {} characters",
+            image,
+            MAX_IMAGE_NAME_LENGTH
+        );
+    }
+    Ok(())
+}
+
+// Container users: a POSIX login name, so it can't be read as an ssh option.
+static CONTAINER_USER_REGEX: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"^[a-z_][a-z0-9_-]{0,31}$").expect("Failed to compile CONTAINER_USER_REGEX")
+});
+
+pub fn validate_container_user(user: &str) -> Result<()> {
+    if !CONTAINER_USER_REGEX.is_match(user) {
+        bail!(
+            "Invalid user '{}': must start with a lowercase letter or underscore, followed by \
+            at most 31 lowercase letters, digits, underscores or hyphens.",
+            user
+        );
+    }
+    Ok(())
+}
+
+// Tailscale tags: `tag:` followed by a letter, then letters, digits or hyphens.
+static TAILSCALE_TAG_REGEX: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"^tag:[a-zA-Z][a-zA-Z0-9-]*$").expect("Failed to compile TAILSCALE_TAG_REGEX")
+});
+
+pub fn validate_tailscale_tag(tag: &str) -> Result<()> {
+    if !TAILSCALE_TAG_REGEX.is_match(tag) {
+        bail!(
+            "Invalid Tailscale tag '{}': must look like 'tag:<name>', where the name starts \
+            with a letter and only contains letters, digits, or hyphens.",
+            tag
+        );
+    }
+    Ok(())
+}
+
+/// Placeholders available in a Tailscale hostname template.
+pub const HOSTNAME_TEMPLATE_PLACEHOLDERS: &[&str] = &["{id}", "{name}", "{namespace}"];
+
+/// Validates a device hostname template. It must include `{id}` so hostnames stay
+/// unique, and may only use the placeholders in `HOSTNAME_TEMPLATE_PLACEHOLDERS`.
+pub fn validate_hostname_template(template: &str) -> Result<()> {
+    if !template.contains("{id}") {
+        bail!(
+            "Invalid hostname template '{}': must contain '{{id}}'",
+            template
+        );
+    }
+    let stripped = HOSTNAME_TEMPLATE_PLACEHOLDERS
+        .iter()
+        .fold(template.to_string(), |acc, p| acc.replace(p, "x"));
+    if stripped.contains('{') || stripped.contains('}') {
+        bail!(
+            "Invalid hostname template '{}': supported placeholders are {}",
+            template,
+            HOSTNAME_TEMPLATE_PLACEHOLDERS.join(", ")
+        );
+    }
+    if !stripped
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '-')
+    {
+        bail!(
+            "Invalid hostname template '{}': only letters, digits, and hyphens are allowed",
+            template
+        );
+    }
+    Ok(())
+}
+
+/// Validates an accelerator string of the form `"<count>:<type>"`, e.g. `"2:A100_SXM"`.
+/// `supported` is the platform's accelerator map; its keys are the accepted types.
+pub fn validate_accelerator(accelerator: &str, supported: &HashMap<String, String>) -> Result<()> {
+    let mut supported_types: Vec<&str> = supported.keys().map(|k| k.as_str()).collect();
+    supported_types.sort();
+
+    let (count, accelerator_type) = match accelerator.split_once(':') {
+        Some(parts) => parts,
+        None => bail!(
+            "Invalid accelerator '{}': expected format '<count>:<type>', e.g. '1:{}'",
+            accelerator,
+            supported_types.first().unwrap_or(&"A100")
+        ),
+    };
+
+    match count.parse::<u32>() {
+        Ok(n) if n > 0 => (),
+        _ => bail!(
+            "Invalid accelerator '{}': count must be a positive integer",
+            accelerator
+        ),
+    }
+
+    if !supported.contains_key(accelerator_type) {
+        bail!(
+            "Invalid accelerator '{}': unsupported type '{}'. Supported types: {}",
+            accelerator,
+            accelerator_type,
+            supported_types.join(", ")
+        );
+    }
+    Ok(())
+}
+
+/// System directories a volume may never be synced into, nor anywhere below.
+const PROTECTED_VOLUME_TREES: &[&str] = &["/boot", "/dev", "/nebu", "/proc", "/sys"];
+
+/// Directories a volume may be synced below but never replace outright.
+const PROTECTED_VOLUME_PATHS: &[&str] = &[
+    "/",
+    "/bin",
+    "/etc",
+    "/home",
+    "/lib",
+    "/lib32",
+    "/lib64",
+    "/opt",
+    "/root",
+    "/root/.ssh",
+    "/run",
+    "/sbin",
+    "/tmp",
+    "/usr",
+    "/usr/bin",
+    "/usr/lib",
+    "/usr/local",
+    "/usr/sbin",
+    "/var",
+];
+
+/// Validates where a volume is synced to in the container. Local destinations
+/// are replaced by a symlink into the cache, so they must not be system
+/// directories or climb out with `..`. Remote destinations (`s3://` and the
+/// like) are left alone.
+pub fn validate_volume_dest(dest: &str) -> Result<()> {
+    if dest.contains("://") {
+        return Ok(());
+    }
+    if dest.trim().is_empty() {
+        bail!("Invalid volume destination: must not be empty");
+    }
+    let segments: Vec<&str> = dest
+        .split('/')
+        .filter(|segment| !segment.is_empty() && *segment != ".")
+        .collect();
+    if segments.contains(&"..") {
+        bail!(
+            "Invalid volume destination '{}': must not contain '..'",
+            dest
+        );
+    }
+    let normalized = format!("/{}", segments.join("/"));
+    let protected_tree = PROTECTED_VOLUME_TREES
+        .iter()
+        .find(|tree| normalized == **tree || normalized.starts_with(&format!("{}/", tree)));
+    if let Some(tree) = protected_tree {
+        bail!(
+            "Invalid volume destination '{}': nothing under {} can be a volume",
+            dest,
+            tree
+        );
+    }
+    if PROTECTED_VOLUME_PATHS.contains(&normalized.as_str()) {
+        bail!(
+            "Invalid volume destination '{}': {} is a protected system path, use a directory below it",
+            dest,
+            normalized
+        );
+    }
+    Ok(())
+}
+
+/// Validates a compressed volume transfer: the compressed end has to be
+/// object storage and the other end local, so data is compressed on its way
+/// to storage and decompressed on its way back. Bidirectional syncs would
+/// see the compressed files as different from the local ones.
+pub fn validate_volume_compression(
+    source: &str,
+    dest: &str,
+    driver: &V1VolumeDriver,
+) -> Result<()> {
+    if *driver == V1VolumeDriver::RCLONE_BISYNC {
+        bail!("Invalid volume: compress is not supported for bidirectional syncs");
+    }
+    let is_remote = |end: &str| end.contains("://") || end.starts_with("s3:");
+    match (is_remote(source), is_remote(dest)) {
+        (true, false) | (false, true) => Ok(()),
+        (false, false) => bail!(
+            "Invalid volume: compress needs object storage on one end, got '{}' and '{}'",
+            source,
+            dest
+        ),
+        (true, true) => bail!(
+            "Invalid volume: compress needs a local end, got '{}' and '{}'",
+            source,
+            dest
+        ),
+    }
+}
+
+/// Validates a container's fallback platforms: known, non-empty and listed once each.
+pub fn validate_platforms(platforms: &[String], known: &[&str]) -> Result<()> {
+    if platforms.is_empty() {
+        bail!("Invalid platforms: at least one platform is required");
+    }
+    for (i, platform) in platforms.iter().enumerate() {
+        if !known.contains(&platform.as_str()) {
+            bail!(
+                "Invalid platform '{}': expected one of: {}",
+                platform,
+                known.join(", ")
+            );
+        }
+        if platforms[..i].contains(platform) {
+            bail!("Invalid platforms: '{}' is listed more than once", platform);
+        }
+    }
+    Ok(())
+}
+
+pub const MAX_IDEMPOTENCY_KEY_LENGTH: usize = 255;
+
+/// Validates an `Idempotency-Key` header value: 1-255 visible ASCII characters.
+pub fn validate_idempotency_key(key: &str) -> Result<()> {
+    if key.is_empty() || key.len() > MAX_IDEMPOTENCY_KEY_LENGTH {
+        bail!(
+            "Invalid idempotency key: must be between 1 and {} characters",
+            MAX_IDEMPOTENCY_KEY_LENGTH
+        );
+    }
+    if !key.chars().all(|c| c.is_ascii_graphic()) {
+        bail!("Invalid idempotency key: only visible ASCII characters are allowed");
+    }
+    Ok(())
+}
+
+/// Largest secret value accepted, in bytes
+pub const MAX_SECRET_VALUE_BYTES: usize = 64 * 1024;
+
+/// Validates a secret value's size.
+pub fn validate_secret_value(value: &str) -> Result<()> {
+    if value.len() > MAX_SECRET_VALUE_BYTES {
+        bail!(
+            "Invalid secret value: {} bytes, must be at most {}",
+            value.len(),
+            MAX_SECRET_VALUE_BYTES
+        );
+    }
+    Ok(())
+}
+
+/// Key types accepted in a container's `ssh_keys`
+pub const SSH_KEY_TYPES: &[&str] = &[
+    "ssh-ed25519",
+    "ssh-rsa",
+    "ecdsa-sha2-nistp256",
+    "ecdsa-sha2-nistp384",
+    "ecdsa-sha2-nistp521",
+    "sk-ssh-ed25519@openssh.com",
+    "sk-ecdsa-sha2-nistp256@openssh.com",
+];
+
+/// Validates an OpenSSH public key line, `<type> <base64 key> [comment]`.
+/// The key must decode and name the same type it's declared as, and the
+/// line must fit on one line of authorized_keys.
+pub fn validate_ssh_public_key(key: &str) -> Result<()> {
+    use base64::{engine::general_purpose::STANDARD, Engine as _};
+
+    let key = key.trim();
+    if key.chars().any(|c| c.is_control()) {
+        bail!("Invalid SSH public key: must be a single line");
+    }
+    let mut parts = key.splitn(3, ' ');
+    let (Some(key_type), Some(encoded)) = (parts.next(), parts.next()) else {
+        bail!("Invalid SSH public key: expected '<type> <key> [comment]'");
+    };
+    if !SSH_KEY_TYPES.contains(&key_type) {
+        bail!(
+            "Invalid SSH public key type '{}': expected one of: {}",
+            key_type,
+            SSH_KEY_TYPES.join(", ")
+        );
+    }
+    let blob = match STANDARD.decode(encoded) {
+        Ok(blob) => blob,
+        Err(_) => bail!("Invalid SSH public key: the key is not valid base64"),
+    };
+    // The blob starts with its type as a length-prefixed string
+    let embedded_type = blob
+        .get(..4)
+        .map(|len| u32::from_be_bytes([len[0], len[1], len[2], len[3]]) as usize)
+        .and_then(|len| blob.get(4..4 + len));
+    if embedded_type != Some(key_type.as_bytes()) {
+        bail!(
+            "Invalid SSH public key: the key data is not a {} key",
+            key_type
+        );
+    }
+    Ok(())
+}
+
+/// Validates a container's `ssh_keys`: each names either a public key, which
+/// must be well formed, or a secret holding one, not both.
+pub fn validate_ssh_keys(keys: &[V1SSHKey]) -> Result<()> {
+    for (i, key) in keys.iter().enumerate() {
+        match (&key.public_key, &key.public_key_secret) {
+            (Some(public_key), None) => validate_ssh_public_key(public_key)
+                .map_err(|e| anyhow::anyhow!("ssh_keys[{}]: {}", i, e))?,
+            (None, Some(secret)) if !secret.trim().is_empty() => {}
+            _ => bail!(
+                "ssh_keys[{}]: set exactly one of public_key or public_key_secret",
+                i
+            ),
+        }
+    }
+    Ok(())
+}
+
+/// Whether `ip` is reachable on the public internet: not loopback, private,
+/// link-local, shared (CGNAT, which tailnets use), unspecified, broadcast,
+/// documentation or multicast.
+pub fn is_public_ip(ip: &IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, ..] = ip.octets();
+            !(ip.is_loopback()
+                || ip.is_private()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+                || ip.is_documentation()
+                || ip.is_multicast()
+                || a == 0
+                || (a == 100 && (b & 0xc0) == 64))
+        }
+        IpAddr::V6(ip) => {
+            if let Some(mapped) = ip.to_ipv4_mapped() {
+                return is_public_ip(&IpAddr::V4(mapped));
+            }
+            let first = ip.segments()[0];
+            !(ip.is_loopback()
+                || ip.is_unspecified()
+                || ip.is_multicast()
+                || (first & 0xfe00) == 0xfc00
+                || (first & 0xffc0) == 0xfe80)
+        }
+    }
+}
+
+/// Validates a container status webhook URL: absolute http(s) with a host,
+/// which unless `allow_private` can't be a non-public address or localhost.
+/// Names are checked again when they're resolved for delivery.
+pub fn validate_webhook_url(url: &str, allow_private: bool) -> Result<()> {
+    let parsed = match reqwest::Url::parse(url) {
+        Ok(parsed) => parsed,
+        Err(e) => bail!("Invalid webhook URL '{}': {}", url, e),
+    };
+    if !matches!(parsed.scheme(), "http" | "https") || parsed.host_str().is_none() {
+        bail!("Invalid webhook URL '{}': must be an http(s) URL", url);
+    }
+    if allow_private {
+        return Ok(());
+    }
+    let private = match parsed.host() {
+        Some(url::Host::Ipv4(ip)) => !is_public_ip(&IpAddr::V4(ip)),
+        Some(url::Host::Ipv6(ip)) => !is_public_ip(&IpAddr::V6(ip)),
+        Some(url::Host::Domain(domain)) => {
+            let domain = domain.trim_end_matches('.').to_ascii_lowercase();
+            domain == "localhost" || domain.ends_with(".localhost")
+        }
+        None => true,
+    };
+    if private {
         bail!(
-            "Invalid namespace: must be 1–256 characters long and only contain letters, \
-            digits, underscores, hyphens, or periods."
+            "Invalid webhook URL '{}': must target a public address",
+            url
         );
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn supported() -> HashMap<String, String> {
+        let mut map = HashMap::new();
+        map.insert("A100".to_string(), "NVIDIA A100 80GB PCIe".to_string());
+        map.insert("T4".to_string(), "Tesla T4".to_string());
+        map
+    }
+
+    #[test]
+    fn test_validate_name_valid() {
+        // Arrange
+        let longest = "a".repeat(MAX_NAME_LENGTH);
+        let names = ["a", "my-model", "llama-3-70b", "0", longest.as_str()];
+
+        // Act
+        let results: Vec<_> = names.iter().map(|n| validate_name(n)).collect();
+
+        // Assert
+        for (name, result) in names.iter().zip(results) {
+            assert!(result.is_ok(), "expected '{}' to be valid", name);
+        }
+    }
+
+    #[test]
+    fn test_validate_name_too_long() {
+        let err = validate_name(&"a".repeat(MAX_NAME_LENGTH + 1)).unwrap_err();
+        assert!(err.to_string().contains("at most 63 characters"));
+        assert!(validate_name("").is_err());
+    }
+
+    #[test]
+    fn test_validate_name_uppercase() {
+        assert!(validate_name("MyModel").is_err());
+        assert!(validate_namespace("Team-A").is_err());
+    }
+
+    #[test]
+    fn test_validate_name_invalid_characters() {
+        for name in [
+            "my_model", "my.model", "my model", "my/model", "-model", "model-", "modèle",
+        ] {
+            assert!(
+                validate_name(name).is_err(),
+                "expected '{}' to be invalid",
+                name
+            );
+            assert!(
+                validate_namespace(name).is_err(),
+                "expected '{}' to be invalid",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn test_validate_full_name() {
+        let label = "a".repeat(MAX_NAME_LENGTH);
+        assert!(validate_full_name(&label, &label).is_ok());
+        assert!(validate_full_name("ns", "My_Model").is_err());
+        assert!(validate_full_name("ns/nested", "model").is_err());
+        assert!(validate_full_name(&label, &"a".repeat(MAX_NAME_LENGTH + 1)).is_err());
+    }
+
+    #[test]
+    fn test_validate_image_with_tag() {
+        // Arrange
+        let images = [
+            "nginx",
+            "nginx:latest",
+            "library/ubuntu:22.04",
+            "ghcr.io/agentsea/nebulous:v0.1.0-rc.1",
+            "localhost:5000/my_app/worker__v2:dev",
+            "pytorch/pytorch:2.1.0-cuda12.1-cudnn8-runtime",
+        ];
+
+        // Act
+        let results: Vec<_> = images.iter().map(|i| validate_image(i)).collect();
+
+        // Assert
+        for (image, result) in images.iter().zip(results) {
+            assert!(result.is_ok(), "expected '{}' to be valid", image);
+        }
+    }
+
+    #[test]
+    fn test_validate_image_with_digest() {
+        let digest = "sha256:".to_string() + &"a".repeat(64);
+        assert!(validate_image(&format!("ubuntu@{}", digest)).is_ok());
+        assert!(validate_image(&format!("docker.io/library/ubuntu:22.04@{}", digest)).is_ok());
+        assert!(validate_image("ubuntu@sha256:abc").is_err());
+        assert!(validate_image(&format!("ubuntu@{}", digest.replace('a', "z"))).is_err());
+    }
+
+    #[test]
+    fn test_validate_image_malformed() {
+        assert!(validate_image("").is_err());
+        assert!(validate_image("   ").is_err());
+        assert!(validate_image("Nginx").is_err());
+        assert!(validate_image("nginx:").is_err());
+        assert!(validate_image("nginx:latest:extra").is_err());
+        assert!(validate_image("nginx latest").is_err());
+        assert!(validate_image("/nginx").is_err());
+        assert!(validate_image("nginx/").is_err());
+        assert!(validate_image("nginx:-bad").is_err());
+        assert!(validate_image(&"a".repeat(256)).is_err());
+    }
+
+    #[test]
+    fn test_validate_tailscale_tag() {
+        assert!(validate_tailscale_tag("tag:container").is_ok());
+        assert!(validate_tailscale_tag("tag:team-a2").is_ok());
+        assert!(validate_tailscale_tag("container").is_err());
+        assert!(validate_tailscale_tag("tag:").is_err());
+        assert!(validate_tailscale_tag("tag:2team").is_err());
+        assert!(validate_tailscale_tag("tag:team_a").is_err());
+        assert!(validate_tailscale_tag("tag:team a").is_err());
+    }
+
+    #[test]
+    fn test_validate_hostname_template() {
+        assert!(validate_hostname_template("container-{id}").is_ok());
+        assert!(validate_hostname_template("{namespace}-{name}-{id}").is_ok());
+        assert!(validate_hostname_template("{namespace}-{name}").is_err());
+        assert!(validate_hostname_template("{owner}-{id}").is_err());
+        assert!(validate_hostname_template("box.{id}").is_err());
+    }
+
+    #[test]
+    fn test_validate_accelerator_valid() {
+        // Arrange
+        let accelerator = "2:A100";
+
+        // Act
+        let result = validate_accelerator(accelerator, &supported());
+
+        // Assert
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn test_validate_accelerator_missing_count() {
+        let err = validate_accelerator("A100", &supported()).unwrap_err();
+        assert!(err.to_string().contains("<count>:<type>"));
+    }
+
+    #[test]
+    fn test_validate_accelerator_bad_count() {
+        assert!(validate_accelerator("0:A100", &supported()).is_err());
+        assert!(validate_accelerator("-1:A100", &supported()).is_err());
+        assert!(validate_accelerator("two:A100", &supported()).is_err());
+        assert!(validate_accelerator(":A100", &supported()).is_err());
+    }
+
+    #[test]
+    fn test_validate_accelerator_unknown_type_lists_supported() {
+        let err = validate_accelerator("1:a100", &supported()).unwrap_err();
+        assert!(err.to_string().contains("Supported types: A100, T4"));
+        assert!(validate_accelerator("1:", &supported()).is_err());
+        assert!(validate_accelerator("1:A100:extra", &supported()).is_err());
+    }
+
+    #[test]
+    fn test_validate_volume_dest_rejects_system_paths() {
+        for dest in [
+            "/",
+            "//",
+            "/etc",
+            "/etc/",
+            "/./usr",
+            "/root/.ssh",
+            "/proc/self",
+            "/nebu/cache/data",
+            "/data/../etc",
+            "..",
+            "",
+        ] {
+            assert!(validate_volume_dest(dest).is_err(), "accepted {:?}", dest);
+        }
+    }
+
+    #[test]
+    fn test_validate_volume_dest_accepts_data_paths() {
+        for dest in [
+            "/data",
+            "/workspace/models",
+            "/etc/myapp",
+            "/root/.cache/huggingface",
+            "outputs",
+            "s3://bucket/..",
+            "nebu://datasets/train",
+        ] {
+            assert!(validate_volume_dest(dest).is_ok(), "rejected {:?}", dest);
+        }
+    }
+
+    #[test]
+    fn test_validate_container_user() {
+        for user in ["root", "ubuntu", "_apt", "app-user", "u1"] {
+            assert!(validate_container_user(user).is_ok(), "{}", user);
+        }
+        for user in [
+            "",
+            "-oProxyCommand=sh",
+            "Root",
+            "1000",
+            "a b",
+            "app@host",
+            &"a".repeat(33),
+        ] {
+            assert!(validate_container_user(user).is_err(), "{}", user);
+        }
+    }
+
+    #[test]
+    fn test_validate_volume_compression() {
+        let sync = &V1VolumeDriver::RCLONE_SYNC;
+
+        assert!(validate_volume_compression("/checkpoints", "s3://bucket/ckpt", sync).is_ok());
+        assert!(validate_volume_compression("nebu://models/llama", "/models", sync).is_ok());
+        assert!(validate_volume_compression("/a", "/b", sync).is_err());
+        assert!(validate_volume_compression("s3://a/x", "s3://b/x", sync).is_err());
+        assert!(validate_volume_compression(
+            "/checkpoints",
+            "s3://bucket/ckpt",
+            &V1VolumeDriver::RCLONE_BISYNC
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_validate_platforms() {
+        let known = &["runpod", "kube"];
+        let platforms = |names: &[&str]| names.iter().map(|n| n.to_string()).collect::<Vec<_>>();
+
+        assert!(validate_platforms(&platforms(&["runpod", "kube"]), known).is_ok());
+        assert!(validate_platforms(&[], known).is_err());
+        assert!(validate_platforms(&platforms(&["runpod", "runpod"]), known).is_err());
+        let err = validate_platforms(&platforms(&["aws"]), known).unwrap_err();
+        assert!(err.to_string().contains("expected one of: runpod, kube"));
+    }
+
+    #[test]
+    fn test_validate_idempotency_key() {
+        assert!(validate_idempotency_key("0b5f6c1e-retry").is_ok());
+        assert!(validate_idempotency_key("").is_err());
+        assert!(validate_idempotency_key("has space").is_err());
+        assert!(validate_idempotency_key(&"k".repeat(MAX_IDEMPOTENCY_KEY_LENGTH + 1)).is_err());
+    }
+
+    #[test]
+    fn test_validate_secret_value() {
+        assert!(validate_secret_value("hunter2").is_ok());
+        assert!(validate_secret_value(&"s".repeat(MAX_SECRET_VALUE_BYTES)).is_ok());
+        assert!(validate_secret_value(&"s".repeat(MAX_SECRET_VALUE_BYTES + 1)).is_err());
+    }
+
+    #[test]
+    fn test_validate_ssh_public_key() {
+        let key =
+            "ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAAIBfu6M2GuTXcOmhgUu1PPGUSrht7g4CQLwJ2ddo9wDvO";
+        assert!(validate_ssh_public_key(key).is_ok());
+        assert!(validate_ssh_public_key(&format!("{} dev@laptop\n", key)).is_ok());
+
+        assert!(validate_ssh_public_key("").is_err());
+        assert!(validate_ssh_public_key("ssh-ed25519").is_err());
+        assert!(validate_ssh_public_key("ssh-dss AAAAB3NzaC1kc3MAAACBAP").is_err());
+        assert!(validate_ssh_public_key("ssh-ed25519 not*base64").is_err());
+        // An ed25519 blob declared as RSA
+        assert!(validate_ssh_public_key(&key.replace("ssh-ed25519", "ssh-rsa")).is_err());
+        // A second key smuggled in on another line
+        assert!(validate_ssh_public_key(&format!("{} a\n{} b", key, key)).is_err());
+    }
+
+    #[test]
+    fn test_validate_ssh_keys() {
+        let key = |public_key: Option<&str>, secret: Option<&str>| V1SSHKey {
+            public_key: public_key.map(String::from),
+            public_key_secret: secret.map(String::from),
+            copy_local: None,
+        };
+        let valid =
+            "ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAAIBfu6M2GuTXcOmhgUu1PPGUSrht7g4CQLwJ2ddo9wDvO";
+
+        assert!(
+            validate_ssh_keys(&[key(Some(valid), None), key(None, Some("laptop-key"))]).is_ok()
+        );
+        assert!(validate_ssh_keys(&[key(None, None)]).is_err());
+        assert!(validate_ssh_keys(&[key(Some(valid), Some("laptop-key"))]).is_err());
+        let err = validate_ssh_keys(&[key(Some(valid), None), key(Some("ssh-rsa nope"), None)])
+            .unwrap_err();
+        assert!(err.to_string().starts_with("ssh_keys[1]:"));
+    }
+
+    #[test]
+    fn test_validate_webhook_url() {
+        assert!(validate_webhook_url("https://hooks.example.com/nebu", false).is_ok());
+        assert!(validate_webhook_url("http://203.0.113.9.nip.io/status", false).is_ok());
+        assert!(validate_webhook_url("ftp://example.com/hook", false).is_err());
+        assert!(validate_webhook_url("not a url", false).is_err());
+        for url in [
+            "http://10.0.0.5:8080/status",
+            "http://127.0.0.1/hook",
+            "http://169.254.169.254/latest/meta-data",
+            "http://100.100.100.100/",
+            "http://[::1]/hook",
+            "http://[::ffff:127.0.0.1]/hook",
+            "http://[fd7a:115c:a1e0::1]/hook",
+            "http://localhost:3000/hook",
+            "http://api.localhost/hook",
+        ] {
+            assert!(validate_webhook_url(url, false).is_err(), "{}", url);
+        }
+        assert!(validate_webhook_url("http://10.0.0.5:8080/status", true).is_ok());
+    }
+}
@@ -1,2 +1,4 @@
+pub mod progress;
 pub mod rclone;
+pub mod resume;
 pub mod s3_sync;
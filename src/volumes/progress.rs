@@ -0,0 +1,155 @@
+// src/volumes/progress.rs
+//
+// Progress reporting for the rclone processes behind `nebu sync volumes`.
+// rclone is run with JSON logs and periodic stats; each stats line is parsed
+// into a `V1SyncProgress` and, inside a nebulous container, posted back to the
+// server so it shows up in the container's status.
+
+use crate::resources::v1::containers::models::V1SyncProgress;
+use serde::Deserialize;
+use serde_json::Value;
+
+/// Makes rclone log a JSON stats line every 10 seconds at the default log level
+pub const RCLONE_PROGRESS_ARGS: &[&str] = &[
+    "--use-json-log",
+    "--stats",
+    "10s",
+    "--stats-log-level",
+    "NOTICE",
+];
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+struct RcloneStats {
+    bytes: u64,
+    total_bytes: u64,
+    transfers: u64,
+    total_transfers: u64,
+    errors: u64,
+    eta: Option<f64>,
+    speed: Option<f64>,
+}
+
+/// Parses one rclone `--use-json-log` line, returning the progress when the
+/// line carries stats and None for any other log line.
+pub fn parse_stats_line(line: &str, source: &str, dest: &str) -> Option<V1SyncProgress> {
+    let json: Value = serde_json::from_str(line.trim()).ok()?;
+    let stats: RcloneStats = serde_json::from_value(json.get("stats")?.clone()).ok()?;
+    let updated_at = json
+        .get("time")
+        .and_then(|t| t.as_str())
+        .and_then(|t| chrono::DateTime::parse_from_rfc3339(t).ok())
+        .map(|t| t.timestamp())
+        .unwrap_or_else(|| chrono::Utc::now().timestamp());
+
+    Some(V1SyncProgress {
+        source: source.to_string(),
+        dest: dest.to_string(),
+        bytes: stats.bytes,
+        total_bytes: stats.total_bytes,
+        transfers: stats.transfers,
+        total_transfers: stats.total_transfers,
+        errors: stats.errors,
+        eta_secs: stats.eta.filter(|eta| *eta >= 0.0).map(|eta| eta as u64),
+        speed: stats.speed,
+        updated_at,
+    })
+}
+
+/// Posts sync progress to the nebulous server the container belongs to.
+#[derive(Clone)]
+pub struct ProgressReporter {
+    url: String,
+    api_key: String,
+    http: reqwest::Client,
+}
+
+impl ProgressReporter {
+    /// Uses the `NEBULOUS_SERVER`, `NEBU_API_KEY` and `NEBU_CONTAINER_ID` every
+    /// container gets. None outside a container.
+    pub fn from_env() -> Option<Self> {
+        let server = std::env::var("NEBULOUS_SERVER")
+            .or_else(|_| std::env::var("NEBU_SERVER"))
+            .ok()?;
+        let api_key = std::env::var("NEBU_API_KEY").ok()?;
+        let container_id = std::env::var("NEBU_CONTAINER_ID").ok()?;
+        Some(Self {
+            url: format!(
+                "{}/v1/containers/{}/sync-progress",
+                server.trim_end_matches('/'),
+                container_id
+            ),
+            api_key,
+            http: crate::utils::http::shared_client(),
+        })
+    }
+
+    /// Best effort: a failed report only logs, the sync carries on.
+    pub async fn report(&self, progress: &V1SyncProgress) {
+        let result = self
+            .http
+            .post(&self.url)
+            .bearer_auth(&self.api_key)
+            .json(progress)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status());
+        if let Err(e) = result {
+            eprintln!("Failed to report sync progress: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STATS_LINE: &str = r#"{"level":"notice","msg":"\nTransferred:   \t  1.200 MiB / 10 MiB, 12%, 300 KiB/s, ETA 30s\n","source":"accounting/stats.go:482","stats":{"bytes":1258291,"checks":0,"deletedDirs":0,"deletes":0,"elapsedTime":4.01,"errors":0,"eta":30,"fatalError":false,"renames":0,"retryError":false,"speed":307200.5,"totalBytes":10485760,"totalChecks":0,"totalTransfers":5,"transferTime":3.9,"transfers":1},"time":"2025-03-01T12:00:00.000000+00:00"}"#;
+
+    #[test]
+    fn test_parse_stats_line() {
+        // Arrange
+        let line = STATS_LINE;
+
+        // Act
+        let progress = parse_stats_line(line, "/nebu/data", "s3://bucket/data");
+
+        // Assert
+        assert_eq!(
+            progress,
+            Some(V1SyncProgress {
+                source: "/nebu/data".to_string(),
+                dest: "s3://bucket/data".to_string(),
+                bytes: 1258291,
+                total_bytes: 10485760,
+                transfers: 1,
+                total_transfers: 5,
+                errors: 0,
+                eta_secs: Some(30),
+                speed: Some(307200.5),
+                updated_at: 1740830400,
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_stats_line_without_eta() {
+        let line = r#"{"level":"notice","msg":"stats","stats":{"bytes":0,"errors":2,"eta":null,"speed":0,"totalBytes":0,"totalTransfers":0,"transfers":0},"time":"2025-03-01T12:00:10Z"}"#;
+
+        let progress = parse_stats_line(line, "a", "b").unwrap();
+
+        assert_eq!(progress.eta_secs, None);
+        assert_eq!(progress.errors, 2);
+    }
+
+    #[test]
+    fn test_parse_stats_line_ignores_other_lines() {
+        for line in [
+            r#"{"level":"info","msg":"Copied (new)","object":"a.txt","objectType":"*local.Object","source":"operations/copy.go:271","time":"2025-03-01T12:00:05Z"}"#,
+            "2025/03/01 12:00:00 NOTICE: plain text log",
+            "",
+        ] {
+            assert_eq!(parse_stats_line(line, "a", "b"), None, "{}", line);
+        }
+    }
+}
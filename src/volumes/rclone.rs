@@ -1,6 +1,8 @@
 use crate::query::Query;
-use crate::resources::v1::containers::models::V1ContainerStatus;
+use crate::resources::v1::containers::models::{V1ContainerStatus, V1SyncProgress};
 use crate::resources::v1::volumes::models::V1VolumeDriver;
+use crate::volumes::progress::{self, parse_stats_line, ProgressReporter};
+use crate::volumes::resume::{self, SyncAction};
 use sea_orm::{DatabaseConnection, DbErr};
 use serde::{Deserialize, Serialize};
 use serde_json::from_str;
@@ -39,6 +41,12 @@ pub struct VolumePath {
     pub continuous: bool,
     #[serde(default = "default_volume_driver")]
     pub driver: V1VolumeDriver,
+    /// Compare files by checksum instead of size and modification time
+    #[serde(default)]
+    pub checksum: bool,
+    /// Keep the object storage end compressed, see `V1VolumePath::compress`
+    #[serde(default)]
+    pub compress: bool,
 }
 
 fn default_volume_driver() -> V1VolumeDriver {
@@ -114,6 +122,8 @@ impl VolumeConfig {
             resync,
             continuous,
             driver,
+            checksum: false,
+            compress: false,
         });
     }
 
@@ -382,67 +392,77 @@ pub async fn execute_continuous_sync(
         tokio::time::sleep(tokio::time::Duration::from_secs(interval_seconds)).await;
     }
 }
-/// Start a new rclone sync process for a path
-async fn start_sync_process(
-    path: &VolumePath,
-    _cache_dir: &str,
-) -> Result<tokio::process::Child, Box<dyn Error>> {
-    // Build the rclone command
-    let mut cmd = TokioCommand::new("rclone");
-
-    // Normalize S3 paths
-    let source = normalize_s3_path(&path.source);
-    let dest = normalize_s3_path(&path.dest);
-
-    // Create source and destination directories if they don't exist
-    ensure_path_exists(&source).await?;
-    ensure_path_exists(&dest).await?;
-
-    // Decide which rclone subcommand to use based on the driver
-    if path.driver == V1VolumeDriver::RCLONE_BISYNC {
-        // Use bisync for bidirectional sync
-        cmd.arg("bisync");
-        cmd.arg(&source);
-        cmd.arg(&dest);
 
-        cmd.arg("--resync");
-    } else if path.driver == V1VolumeDriver::RCLONE_COPY {
-        // Use copy for unidirectional copy
-        cmd.arg("copy");
-        cmd.arg(&source);
-        cmd.arg(&dest);
-    } else {
-        // Use sync for unidirectional sync
-        cmd.arg("sync");
-        cmd.arg(&source);
-        cmd.arg(&dest);
-    }
+/// Runs an rclone command with JSON stats on stderr, printing its progress
+/// and reporting it to the server when running in a container. Returns
+/// whether it succeeded and its stderr output.
+async fn run_with_progress(
+    mut cmd: TokioCommand,
+    source: &str,
+    dest: &str,
+) -> Result<(bool, String), Box<dyn Error>> {
+    cmd.args(progress::RCLONE_PROGRESS_ARGS);
+    cmd.stdout(Stdio::null());
+    cmd.stderr(Stdio::piped());
 
-    // Add resync flag if needed and it's a bidirectional sync
-    if path.resync && path.driver == V1VolumeDriver::RCLONE_BISYNC {
-        cmd.arg("--resync");
+    let mut child = cmd.spawn()?;
+    let reporter = ProgressReporter::from_env();
+    let mut stderr = String::new();
+    if let Some(pipe) = child.stderr.take() {
+        let mut lines = BufReader::new(pipe).lines();
+        while let Some(line) = lines.next_line().await? {
+            if let Some(progress) = parse_stats_line(&line, source, dest) {
+                print_progress(&progress);
+                if let Some(reporter) = &reporter {
+                    reporter.report(&progress).await;
+                }
+            }
+            stderr.push_str(&line);
+            stderr.push('\n');
+        }
     }
 
-    // cmd.arg("--create-empty-src-dirs");
-
-    // // Add common options
-    // cmd.arg("--verbose");
-    // cmd.arg("--fast-list");
-
-    // // Add cache directory
-    // cmd.arg("--cache-dir");
-    // cmd.arg(cache_dir);
+    let status = child.wait().await?;
+    Ok((status.success(), stderr))
+}
 
-    // Add resilient mode for bidirectional sync
-    if path.driver == V1VolumeDriver::RCLONE_BISYNC {
-        // cmd.arg("--resilient");
+fn print_progress(progress: &V1SyncProgress) {
+    println!(
+        "[progress: {} ⟷ {}] {}/{} bytes, {}/{} files, {} errors, ETA {}",
+        progress.source,
+        progress.dest,
+        progress.bytes,
+        progress.total_bytes,
+        progress.transfers,
+        progress.total_transfers,
+        progress.errors,
+        progress
+            .eta_secs
+            .map(|eta| format!("{}s", eta))
+            .unwrap_or_else(|| "-".to_string())
+    );
+}
 
-        // Add --force flag to help with empty directory issues
-        cmd.arg("--force");
+/// Records a finished one-time sync so a restart only verifies the path.
+fn mark_synced(cache_dir: &str, path: &VolumePath) {
+    if let Err(e) = resume::write_marker(cache_dir, path) {
+        println!(
+            "Warning: Failed to write sync marker for {} ⟷ {}: {}",
+            path.source, path.dest, e
+        );
     }
+}
 
-    // Spawn the process
+/// Start a new rclone sync process for a path
+async fn start_sync_process(
+    path: &VolumePath,
+    _cache_dir: &str,
+) -> Result<tokio::process::Child, Box<dyn Error>> {
+    // Create source and destination directories if they don't exist
+    ensure_path_exists(&normalize_s3_path(&path.source)).await?;
+    ensure_path_exists(&normalize_s3_path(&path.dest)).await?;
 
+    let mut cmd = continuous_sync_command(path);
     cmd.stdout(Stdio::piped());
     cmd.stderr(Stdio::piped());
 
@@ -464,13 +484,22 @@ async fn start_sync_process(
     if let Some(stderr) = child.stderr.take() {
         let source_clone = path.source.clone();
         let dest_clone = path.dest.clone();
+        let reporter = ProgressReporter::from_env();
         tokio::spawn(async move {
             let mut reader = BufReader::new(stderr).lines();
             while let Ok(Some(line)) = reader.next_line().await {
-                println!(
-                    "[rclone stderr: {} ⟷ {}] {}",
-                    source_clone, dest_clone, line
-                );
+                match parse_stats_line(&line, &source_clone, &dest_clone) {
+                    Some(progress) => {
+                        print_progress(&progress);
+                        if let Some(reporter) = &reporter {
+                            reporter.report(&progress).await;
+                        }
+                    }
+                    None => println!(
+                        "[rclone stderr: {} ⟷ {}] {}",
+                        source_clone, dest_clone, line
+                    ),
+                }
             }
         });
     }
@@ -489,6 +518,74 @@ fn normalize_s3_path(path: &str) -> String {
     }
 }
 
+/// The source and destination rclone transfers between for `path`. With
+/// `compress`, the object storage end is wrapped in an on-the-fly compress
+/// remote, so files are gzipped on their way to storage and unpacked again
+/// when read back.
+fn rclone_endpoints(path: &VolumePath) -> (String, String) {
+    let source = normalize_s3_path(&path.source);
+    let dest = normalize_s3_path(&path.dest);
+    if !path.compress {
+        return (source, dest);
+    }
+    let wrap = |end: String| {
+        if end.contains(':') && !end.starts_with('/') {
+            format!(":compress,remote=\"{}\":", end)
+        } else {
+            end
+        }
+    };
+    (wrap(source), wrap(dest))
+}
+
+/// The rclone command transferring `path` once. Bidirectional syncs start
+/// over from both sides with `resync`, as they must on their first run.
+///
+/// Through a compress remote files have no hashes to compare, so there
+/// `--checksum` falls back to comparing sizes, as does `rclone check` unless
+/// it downloads the files.
+fn sync_command(path: &VolumePath, resync: bool) -> TokioCommand {
+    let mut cmd = TokioCommand::new("rclone");
+    let (source, dest) = rclone_endpoints(path);
+
+    if path.driver == V1VolumeDriver::RCLONE_BISYNC {
+        cmd.arg("bisync");
+        cmd.arg(&source);
+        cmd.arg(&dest);
+
+        if resync {
+            cmd.arg("--resync");
+        }
+
+        // Add --force flag to help with empty directory issues
+        cmd.arg("--force");
+    } else if path.driver == V1VolumeDriver::RCLONE_COPY {
+        // For rclone copy
+        cmd.arg("copy");
+        cmd.arg(&source);
+        cmd.arg(&dest);
+    } else {
+        // Default to unidirectional sync
+        cmd.arg("sync");
+        cmd.arg(&source);
+        cmd.arg(&dest);
+    }
+
+    if path.checksum {
+        cmd.arg("--checksum");
+    }
+    cmd
+}
+
+/// The rclone command a continuous sync starts for `path`, reporting its
+/// progress as JSON stats lines on stderr. A bidirectional one resyncs, since
+/// the process it replaces may have stopped halfway.
+fn continuous_sync_command(path: &VolumePath) -> TokioCommand {
+    let mut cmd = sync_command(path, true);
+    cmd.args(progress::RCLONE_PROGRESS_ARGS);
+    cmd
+}
+
 /// Execute rclone bisync for all paths in the configuration
 pub async fn execute_sync(
     config_path: String,
@@ -531,45 +628,27 @@ pub async fn execute_sync(
             path.dest
         );
 
-        // Normalize S3 paths
-        let source = normalize_s3_path(&path.source);
-        let dest = normalize_s3_path(&path.dest);
-
         // Create source and destination directories if they don't exist
-        ensure_path_exists(&source).await?;
-        ensure_path_exists(&dest).await?;
+        ensure_path_exists(&normalize_s3_path(&path.source)).await?;
+        ensure_path_exists(&normalize_s3_path(&path.dest)).await?;
 
-        // Build the rclone command
-        let mut cmd = Command::new("rclone");
-
-        // Normalize S3 paths
-        let source = normalize_s3_path(&path.source);
-        let dest = normalize_s3_path(&path.dest);
-
-        if path.driver == V1VolumeDriver::RCLONE_BISYNC {
-            cmd.arg("bisync");
-            cmd.arg(&source);
-            cmd.arg(&dest);
-
-            // Add --resync flag if needed
-            if path.resync {
-                cmd.arg("--resync");
+        // Skip paths that finished before and haven't changed since
+        let marker = resume::read_marker(&config.cache_dir, &path.source, &path.dest);
+        if resume::plan_sync(path, marker.as_ref()) == SyncAction::Verify {
+            if verify_path(path).await? {
+                println!(
+                    "Already synced between {} and {}, skipping",
+                    path.source, path.dest
+                );
+                continue;
             }
-
-            // Add --force flag to help with empty directory issues
-            cmd.arg("--force");
-        } else if path.driver == V1VolumeDriver::RCLONE_COPY {
-            // For rclone copy
-            cmd.arg("copy");
-            cmd.arg(&source);
-            cmd.arg(&dest);
-        } else {
-            // Default to unidirectional sync
-            cmd.arg("sync");
-            cmd.arg(&source);
-            cmd.arg(&dest);
+            println!("Changes since the last sync, syncing again");
         }
 
+        // Build the rclone command
+        let cmd = sync_command(path, path.resync);
+        let (source, dest) = rclone_endpoints(path);
+
         // Add common options
         // cmd.arg("--verbose");
         // cmd.arg("--fast-list");
@@ -579,13 +658,14 @@ pub async fn execute_sync(
         // cmd.arg("--cache-dir");
         // cmd.arg(&config.cache_dir);
 
-        // Execute the command
-        let output = cmd.output()?;
-        if output.status.success() {
+        // Execute the command, reporting its progress
+        let (success, error) = run_with_progress(cmd, &path.source, &path.dest).await?;
+        if success {
             println!(
                 "Successfully synced between {} and {}",
                 path.source, path.dest
             );
+            mark_synced(&config.cache_dir, path);
 
             // If this was a resync operation, mark it as completed
             if path.resync && path.driver == V1VolumeDriver::RCLONE_BISYNC {
@@ -597,8 +677,10 @@ pub async fn execute_sync(
                 }
             }
         } else {
-            let error = String::from_utf8_lossy(&output.stderr);
             println!("Failed to sync: {}", error);
+            if let Err(e) = resume::clear_marker(&config.cache_dir, &path.source, &path.dest) {
+                println!("Warning: Failed to clear sync marker: {}", e);
+            }
 
             // Check if this is the "empty prior listing" error and we need to resync
             if error.contains("empty prior Path1 listing")
@@ -1111,36 +1193,27 @@ pub async fn execute_non_continuous_sync(
             path.dest
         );
 
-        // Normalize S3 paths
-        let source = normalize_s3_path(&path.source);
-        let dest = normalize_s3_path(&path.dest);
-
         // Create source and destination directories if they don't exist
-        ensure_path_exists(&source).await?;
-        ensure_path_exists(&dest).await?;
-
-        // Build the rclone command
-        let mut cmd = Command::new("rclone");
-
-        // Normalize S3 paths
-        let source = normalize_s3_path(&path.source);
-        let dest = normalize_s3_path(&path.dest);
-
-        if path.driver.clone() == V1VolumeDriver::RCLONE_BISYNC {
-            cmd.arg("bisync");
-            cmd.arg(&source);
-            cmd.arg(&dest);
-
-            cmd.arg("--resync");
+        ensure_path_exists(&normalize_s3_path(&path.source)).await?;
+        ensure_path_exists(&normalize_s3_path(&path.dest)).await?;
 
-            // Add --force flag to help with empty directory issues
-            cmd.arg("--force");
-        } else {
-            cmd.arg("sync");
-            cmd.arg(&source);
-            cmd.arg(&dest);
+        // Skip paths that finished before and haven't changed since
+        let marker = resume::read_marker(&config.cache_dir, &path.source, &path.dest);
+        if resume::plan_sync(path, marker.as_ref()) == SyncAction::Verify {
+            if verify_path(path).await? {
+                println!(
+                    "Already synced between {} and {}, skipping",
+                    path.source, path.dest
+                );
+                continue;
+            }
+            println!("Changes since the last sync, syncing again");
         }
 
+        // Every one-time run starts a bidirectional sync over
+        let cmd = sync_command(path, true);
+        let (source, dest) = rclone_endpoints(path);
+
         // Add common options
         // cmd.arg("--verbose");
         // cmd.arg("--fast-list");
@@ -1150,10 +1223,11 @@ pub async fn execute_non_continuous_sync(
         // cmd.arg("--cache-dir");
         // cmd.arg(&config.cache_dir);
 
-        // Execute the command
-        let output = cmd.output()?;
-        if output.status.success() {
+        // Execute the command, reporting its progress
+        let (success, error) = run_with_progress(cmd, &path.source, &path.dest).await?;
+        if success {
             println!("Successfully synced between {} and {}", source, dest);
+            mark_synced(&config.cache_dir, path);
 
             // If this was a resync operation, mark it as completed
             if path.resync && path.driver.clone() == V1VolumeDriver::RCLONE_BISYNC {
@@ -1172,8 +1246,10 @@ pub async fn execute_non_continuous_sync(
                 }
             }
         } else {
-            let error = String::from_utf8_lossy(&output.stderr);
             println!("Failed to sync: {}", error);
+            if let Err(e) = resume::clear_marker(&config.cache_dir, &path.source, &path.dest) {
+                println!("Warning: Failed to clear sync marker: {}", e);
+            }
 
             // Check if this is the "empty prior listing" error and we need to resync
             if error.contains("empty prior Path1 listing")
@@ -1210,6 +1286,47 @@ pub async fn execute_non_continuous_sync(
     Ok(())
 }
 
+/// Check every path in the configuration against its destination without
+/// transferring anything, failing if any of them differ
+pub async fn execute_verify(config_path: &str) -> Result<(), Box<dyn Error>> {
+    println!("Verifying synced paths from: {}", config_path);
+
+    let config = VolumeConfig::read_from_file(config_path)?;
+    let paths: Vec<_> = config
+        .paths
+        .iter()
+        .filter(|path| path.driver != V1VolumeDriver::RCLONE_MOUNT)
+        .collect();
+
+    let mut mismatched = Vec::new();
+    for (index, path) in paths.iter().enumerate() {
+        let in_sync = verify_path(path).await?;
+        println!(
+            "[{}/{}] {} ⟷ {}: {}",
+            index + 1,
+            paths.len(),
+            path.source,
+            path.dest,
+            if in_sync { "in sync" } else { "differs" }
+        );
+        if !in_sync {
+            mismatched.push(format!("{} ⟷ {}", path.source, path.dest));
+        }
+    }
+
+    if !mismatched.is_empty() {
+        return Err(format!(
+            "{} of {} paths differ: {}",
+            mismatched.len(),
+            paths.len(),
+            mismatched.join(", ")
+        )
+        .into());
+    }
+    println!("All {} paths verified", paths.len());
+    Ok(())
+}
+
 /// ------------------------------------------------------------------------------------------------
 /// Sync checkers
 /// ------------------------------------------------------------------------------------------------
@@ -1539,3 +1656,253 @@ pub async fn check_paths(source: &str, dest: &str) -> Result<bool, Box<dyn std::
     // A 0 exit code means everything is in sync, non-zero means differences or errors.
     Ok(output.status.success())
 }
+
+/// Whether the destination of `path` matches its source, comparing hashes
+/// where both sides support them. Copies only need every source file in the
+/// destination; syncs must also have no extra files there. With `checksum`,
+/// files are downloaded and compared when there's no common hash instead of
+/// falling back to sizes. Nothing is transferred.
+pub async fn verify_path(path: &VolumePath) -> Result<bool, Box<dyn std::error::Error>> {
+    let mut cmd = TokioCommand::new("rclone");
+    let (source, dest) = rclone_endpoints(path);
+    cmd.arg("check").arg(source).arg(dest);
+    if path.driver == V1VolumeDriver::RCLONE_COPY {
+        cmd.arg("--one-way");
+    }
+    if path.checksum {
+        cmd.arg("--download");
+    }
+    let output = cmd.stdout(Stdio::null()).output().await?;
+    if output.status.success() {
+        return Ok(true);
+    }
+
+    // Differences and a missing destination both exit non-zero; anything
+    // else is a failed check and keeps rclone's reason
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    if stderr.contains("differences found") || stderr.contains("directory not found") {
+        return Ok(false);
+    }
+    Err(format!(
+        "rclone check between {} and {} failed ({}): {}",
+        path.source,
+        path.dest,
+        output.status,
+        stderr.trim()
+    )
+    .into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, SystemTime};
+
+    /// A source fixture, an empty destination and a config syncing them once
+    fn fixture() -> (tempfile::TempDir, String, VolumeConfig) {
+        let root = tempfile::tempdir().unwrap();
+        let source = root.path().join("source");
+        let dest = root.path().join("dest");
+        fs::create_dir_all(source.join("nested")).unwrap();
+        fs::write(source.join("a.txt"), "alpha").unwrap();
+        fs::write(source.join("nested/b.txt"), "bravo").unwrap();
+
+        let mut config = VolumeConfig::new();
+        config.cache_dir = root.path().join("cache").to_str().unwrap().to_string();
+        config.add_path(
+            source.to_str().unwrap().to_string(),
+            dest.to_str().unwrap().to_string(),
+            false,
+            V1VolumeDriver::RCLONE_SYNC,
+            false,
+        );
+        let config_path = root.path().join("sync.yaml").to_str().unwrap().to_string();
+        config.write_to_file(&config_path).unwrap();
+        (root, config_path, config)
+    }
+
+    fn modified(path: &str) -> SystemTime {
+        fs::metadata(path).unwrap().modified().unwrap()
+    }
+
+    #[tokio::test]
+    #[ignore = "needs rclone"]
+    async fn test_completed_sync_is_skipped_on_rerun() {
+        // Arrange
+        let (_root, config_path, config) = fixture();
+        let path = &config.paths[0];
+        execute_non_continuous_sync(&config_path, false)
+            .await
+            .unwrap();
+        let synced = format!("{}/nested/b.txt", path.dest);
+        assert_eq!(fs::read_to_string(&synced).unwrap(), "bravo");
+        // Same content but an older modtime: a sync would copy it again
+        let old = SystemTime::now() - Duration::from_secs(3600);
+        fs::File::options()
+            .write(true)
+            .open(&synced)
+            .unwrap()
+            .set_modified(old)
+            .unwrap();
+
+        // Act
+        execute_non_continuous_sync(&config_path, false)
+            .await
+            .unwrap();
+
+        // Assert
+        assert!(modified(&synced) < SystemTime::now() - Duration::from_secs(60));
+        assert!(resume::read_marker(&config.cache_dir, &path.source, &path.dest).is_some());
+    }
+
+    #[tokio::test]
+    #[ignore = "needs rclone"]
+    async fn test_changed_source_is_synced_again() {
+        let (_root, config_path, config) = fixture();
+        let path = &config.paths[0];
+        execute_non_continuous_sync(&config_path, false)
+            .await
+            .unwrap();
+        fs::write(format!("{}/a.txt", path.source), "alpha, edited").unwrap();
+
+        assert!(!verify_path(path).await.unwrap());
+        execute_non_continuous_sync(&config_path, false)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            fs::read_to_string(format!("{}/a.txt", path.dest)).unwrap(),
+            "alpha, edited"
+        );
+        assert!(verify_path(path).await.unwrap());
+    }
+
+    #[tokio::test]
+    #[ignore = "needs rclone"]
+    async fn test_verify_reports_missing_files() {
+        let (_root, config_path, config) = fixture();
+        fs::create_dir_all(&config.paths[0].dest).unwrap();
+
+        assert!(execute_verify(&config_path).await.is_err());
+        execute_non_continuous_sync(&config_path, false)
+            .await
+            .unwrap();
+        execute_verify(&config_path).await.unwrap();
+    }
+
+    fn command_args(cmd: &TokioCommand) -> Vec<String> {
+        cmd.as_std()
+            .get_args()
+            .map(|arg| arg.to_string_lossy().into_owned())
+            .collect()
+    }
+
+    #[test]
+    fn test_compressed_sync_wraps_the_storage_end() {
+        let path = VolumePath {
+            source: "/checkpoints".to_string(),
+            dest: "s3://bucket/ckpt".to_string(),
+            resync: false,
+            continuous: false,
+            driver: V1VolumeDriver::RCLONE_SYNC,
+            checksum: true,
+            compress: true,
+        };
+
+        assert_eq!(
+            command_args(&sync_command(&path, false)),
+            vec![
+                "sync",
+                "/checkpoints",
+                ":compress,remote=\"s3:bucket/ckpt\":",
+                "--checksum"
+            ]
+        );
+
+        let restore = VolumePath {
+            source: path.dest.clone(),
+            dest: path.source.clone(),
+            driver: V1VolumeDriver::RCLONE_COPY,
+            checksum: false,
+            ..path.clone()
+        };
+        assert_eq!(
+            command_args(&sync_command(&restore, false)),
+            vec![
+                "copy",
+                ":compress,remote=\"s3:bucket/ckpt\":",
+                "/checkpoints"
+            ]
+        );
+
+        let plain = VolumePath {
+            compress: false,
+            ..path
+        };
+        assert_eq!(
+            command_args(&sync_command(&plain, false)),
+            vec!["sync", "/checkpoints", "s3:bucket/ckpt", "--checksum"]
+        );
+    }
+
+    fn volume_path(driver: V1VolumeDriver, resync: bool) -> VolumePath {
+        VolumePath {
+            source: "/data".to_string(),
+            dest: "s3://bucket/data".to_string(),
+            resync,
+            continuous: true,
+            driver,
+            checksum: false,
+            compress: false,
+        }
+    }
+
+    #[test]
+    fn test_sync_command_per_driver() {
+        // One-time syncs resync only when the path asks to
+        assert_eq!(
+            command_args(&sync_command(
+                &volume_path(V1VolumeDriver::RCLONE_BISYNC, false),
+                false
+            )),
+            vec!["bisync", "/data", "s3:bucket/data", "--force"]
+        );
+        assert_eq!(
+            command_args(&sync_command(
+                &volume_path(V1VolumeDriver::RCLONE_BISYNC, false),
+                true
+            )),
+            vec!["bisync", "/data", "s3:bucket/data", "--resync", "--force"]
+        );
+        for (driver, subcommand) in [
+            (V1VolumeDriver::RCLONE_COPY, "copy"),
+            (V1VolumeDriver::RCLONE_SYNC, "sync"),
+        ] {
+            assert_eq!(
+                command_args(&sync_command(&volume_path(driver, true), true)),
+                vec![subcommand, "/data", "s3:bucket/data"]
+            );
+        }
+    }
+
+    #[test]
+    fn test_continuous_sync_command_reports_progress() {
+        let args = command_args(&continuous_sync_command(&volume_path(
+            V1VolumeDriver::RCLONE_BISYNC,
+            false,
+        )));
+
+        assert_eq!(
+            args[..5],
+            ["bisync", "/data", "s3:bucket/data", "--resync", "--force"]
+        );
+        assert_eq!(args[5..], *progress::RCLONE_PROGRESS_ARGS);
+        assert_eq!(
+            command_args(&continuous_sync_command(&volume_path(
+                V1VolumeDriver::RCLONE_COPY,
+                false
+            )))[..3],
+            ["copy", "/data", "s3:bucket/data"]
+        );
+    }
+}
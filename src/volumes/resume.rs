@@ -0,0 +1,175 @@
+// src/volumes/resume.rs
+//
+// Completion markers for one-time volume syncs. A path that finished syncing
+// gets a marker under `<cache_dir>/sync-markers`, so a restarted container
+// only verifies it instead of copying it again. Paths without a marker are
+// synced, and rclone skips the files that already made it across.
+
+use crate::resources::v1::volumes::models::V1VolumeDriver;
+use crate::volumes::rclone::VolumePath;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+const MARKER_DIR: &str = "sync-markers";
+
+/// A path's last completed one-time sync
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SyncMarker {
+    pub source: String,
+    pub dest: String,
+    /// Whether files were compared by checksum rather than size and modtime
+    pub checksum: bool,
+    pub completed_at: i64,
+}
+
+/// What a one-time sync should do for a path.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SyncAction {
+    /// Transfer whatever differs, resuming an interrupted sync
+    Sync,
+    /// Completed before: only check that nothing changed since
+    Verify,
+}
+
+/// Decides how to sync `path` given its marker. Bidirectional syncs always
+/// run, since a one-way check can't see changes on the destination side.
+pub fn plan_sync(path: &VolumePath, marker: Option<&SyncMarker>) -> SyncAction {
+    if path.resync || path.driver == V1VolumeDriver::RCLONE_BISYNC {
+        return SyncAction::Sync;
+    }
+    match marker {
+        // A marker from a weaker comparison doesn't vouch for checksums
+        Some(marker) if marker.checksum || !path.checksum => SyncAction::Verify,
+        _ => SyncAction::Sync,
+    }
+}
+
+/// Where the marker for `source` → `dest` lives.
+pub fn marker_path(cache_dir: &str, source: &str, dest: &str) -> PathBuf {
+    let digest = ring::digest::digest(
+        &ring::digest::SHA256,
+        format!("{}\0{}", source, dest).as_bytes(),
+    );
+    let name: String = digest
+        .as_ref()
+        .iter()
+        .take(16)
+        .map(|b| format!("{:02x}", b))
+        .collect();
+    Path::new(cache_dir)
+        .join(MARKER_DIR)
+        .join(format!("{}.json", name))
+}
+
+pub fn read_marker(cache_dir: &str, source: &str, dest: &str) -> Option<SyncMarker> {
+    let contents = fs::read_to_string(marker_path(cache_dir, source, dest)).ok()?;
+    serde_json::from_str::<SyncMarker>(&contents)
+        .ok()
+        .filter(|marker| marker.source == source && marker.dest == dest)
+}
+
+/// Records that `path` finished syncing.
+pub fn write_marker(cache_dir: &str, path: &VolumePath) -> io::Result<()> {
+    let marker_file = marker_path(cache_dir, &path.source, &path.dest);
+    if let Some(parent) = marker_file.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let marker = SyncMarker {
+        source: path.source.clone(),
+        dest: path.dest.clone(),
+        checksum: path.checksum,
+        completed_at: chrono::Utc::now().timestamp(),
+    };
+    fs::write(marker_file, serde_json::to_vec_pretty(&marker)?)
+}
+
+/// Forgets that `source` → `dest` finished, so the next run syncs it.
+pub fn clear_marker(cache_dir: &str, source: &str, dest: &str) -> io::Result<()> {
+    match fs::remove_file(marker_path(cache_dir, source, dest)) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn volume_path(source: &str, dest: &str, driver: V1VolumeDriver) -> VolumePath {
+        VolumePath {
+            source: source.to_string(),
+            dest: dest.to_string(),
+            resync: false,
+            continuous: false,
+            driver,
+            checksum: false,
+            compress: false,
+        }
+    }
+
+    #[test]
+    fn test_completed_path_is_only_verified() {
+        // Arrange
+        let cache = tempfile::tempdir().unwrap();
+        let cache_dir = cache.path().to_str().unwrap();
+        let path = volume_path("/data", "s3://bucket/data", V1VolumeDriver::RCLONE_SYNC);
+
+        // Act
+        let before = plan_sync(
+            &path,
+            read_marker(cache_dir, "/data", "s3://bucket/data").as_ref(),
+        );
+        write_marker(cache_dir, &path).unwrap();
+        let after = plan_sync(
+            &path,
+            read_marker(cache_dir, "/data", "s3://bucket/data").as_ref(),
+        );
+
+        // Assert
+        assert_eq!(before, SyncAction::Sync);
+        assert_eq!(after, SyncAction::Verify);
+    }
+
+    #[test]
+    fn test_resync_bisync_and_stronger_checks_always_sync() {
+        let marker = SyncMarker {
+            source: "/data".to_string(),
+            dest: "s3://bucket/data".to_string(),
+            checksum: false,
+            completed_at: 0,
+        };
+
+        let mut resync = volume_path("/data", "s3://bucket/data", V1VolumeDriver::RCLONE_COPY);
+        resync.resync = true;
+        assert_eq!(plan_sync(&resync, Some(&marker)), SyncAction::Sync);
+
+        let bisync = volume_path("/data", "s3://bucket/data", V1VolumeDriver::RCLONE_BISYNC);
+        assert_eq!(plan_sync(&bisync, Some(&marker)), SyncAction::Sync);
+
+        let mut checksum = volume_path("/data", "s3://bucket/data", V1VolumeDriver::RCLONE_SYNC);
+        checksum.checksum = true;
+        assert_eq!(plan_sync(&checksum, Some(&marker)), SyncAction::Sync);
+        let checksummed = SyncMarker {
+            checksum: true,
+            ..marker
+        };
+        assert_eq!(plan_sync(&checksum, Some(&checksummed)), SyncAction::Verify);
+    }
+
+    #[test]
+    fn test_markers_are_per_path_and_clearable() {
+        let cache = tempfile::tempdir().unwrap();
+        let cache_dir = cache.path().to_str().unwrap();
+        let path = volume_path("/data", "s3://bucket/a", V1VolumeDriver::RCLONE_SYNC);
+
+        write_marker(cache_dir, &path).unwrap();
+
+        assert!(read_marker(cache_dir, "/data", "s3://bucket/a").is_some());
+        assert!(read_marker(cache_dir, "/data", "s3://bucket/b").is_none());
+        clear_marker(cache_dir, "/data", "s3://bucket/a").unwrap();
+        assert!(read_marker(cache_dir, "/data", "s3://bucket/a").is_none());
+        clear_marker(cache_dir, "/data", "s3://bucket/a").unwrap();
+    }
+}